@@ -0,0 +1,81 @@
+//! The application as `main` serves it, built in one place so tests can
+//! serve the same thing.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceFactory, ServiceRequest, ServiceResponse};
+use actix_web::{get, web, App, HttpResponse, Responder};
+use tracing_actix_web::TracingLogger;
+use crate::db::Db;
+use crate::maintenance::MaintenanceMode;
+use crate::origin_check::TrustedOrigins;
+use crate::request_id::RequestIdScope;
+use crate::request_logger::RequestLogger;
+use crate::{federation, graphql, openapi, routes};
+
+/// Which optional parts of the API to serve
+#[derive(Debug, Clone, Copy)]
+pub struct AppConfig {
+    /// Serve the OpenAPI document and Swagger UI
+    pub docs: bool,
+    /// Serve the ActivityPub endpoints
+    pub federation: bool,
+}
+
+impl AppConfig {
+    /// As `API_DOCS` and `FEDERATION` set it
+    pub fn from_env() -> Self {
+        AppConfig { docs: openapi::docs_enabled(), federation: federation::enabled() }
+    }
+}
+
+#[get("/")]
+async fn hello() -> impl Responder {
+    HttpResponse::Ok().body("Hello world!")
+}
+
+#[get("/users/{user_id}/")] // <- define path parameters
+async fn index(path: web::Path<String>) -> impl Responder {
+    let user_id = path.into_inner();
+    let result = format!("Welcome {}!", user_id);
+    HttpResponse::Ok().body(result)
+}
+
+// Add a test endpoint that returns different status codes for testing
+#[get("/test/{status}")]
+async fn test_status(path: web::Path<u16>) -> impl Responder {
+    let status_code = path.into_inner();
+    match status_code {
+        200 => HttpResponse::Ok().body("Success!"),
+        404 => HttpResponse::NotFound().body("Not Found!"),
+        500 => HttpResponse::InternalServerError().body("Server Error!"),
+        _ => HttpResponse::BadRequest().body("Bad Request!"),
+    }
+}
+
+/// The app with every route and middleware, on the database `db`.
+/// Background work the app starts, like mail delivery and webhooks, stays on
+/// the process-wide handle.
+pub fn build(config: AppConfig, db: Db) -> App<
+    impl ServiceFactory<
+        ServiceRequest,
+        Config = (),
+        Response = ServiceResponse<impl MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    App::new()
+        .app_data(web::Data::new(db))
+        .wrap(MaintenanceMode) // Turn away non-admin writes during maintenance
+        .wrap(TrustedOrigins::with_defaults()) // Reject cross-site state-changing requests
+        .wrap(RequestLogger::with_defaults()) // Add custom request logger
+        .wrap(RequestIdScope) // Expose the request id to error responses
+        .wrap(TracingLogger::default())
+        .service(hello)
+        .service(index)
+        .service(test_status) // Add test endpoint
+        .configure(|cfg| if config.federation { federation::configure(cfg) })
+        .configure(routes::configure)
+        .configure(graphql::configure)
+        .configure(|cfg| if config.docs { openapi::configure(cfg) })
+}
@@ -0,0 +1,98 @@
+//! Extra files on tracks: stems, project files, sheet music and the like.
+//!
+//! Attachments are kept in media storage and listed on the track. Only the
+//! file types in `ATTACHMENT_TYPES` are accepted, by extension, and each
+//! file may be up to `ATTACHMENT_MAX_MB` (default 200). A track holds at
+//! most `MAX_ATTACHMENTS_PER_TRACK` files and `MAX_TRACK_ATTACHMENT_BYTES`
+//! in all. The content type is derived from the extension, never taken from
+//! the client.
+
+use std::env;
+use crate::db::error::Error;
+
+/// Most attachments on one track
+pub const MAX_ATTACHMENTS_PER_TRACK: usize = 20;
+
+/// Most bytes of attachments on one track
+pub const MAX_TRACK_ATTACHMENT_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Longest attachment name kept, in characters
+const MAX_NAME_LEN: usize = 200;
+
+/// Extensions accepted when `ATTACHMENT_TYPES` isn't set
+const DEFAULT_TYPES: &[&str] = &["wav", "flac", "aiff", "mp3", "ogg", "mid", "zip", "als", "flp", "pdf", "txt"];
+
+/// Largest attachment accepted, in bytes, set in megabytes with `ATTACHMENT_MAX_MB`
+pub fn max_bytes() -> usize {
+    env::var("ATTACHMENT_MAX_MB")
+        .ok()
+        .and_then(|mb| mb.parse::<usize>().ok())
+        .filter(|mb| *mb > 0)
+        .unwrap_or(200)
+        * 1024 * 1024
+}
+
+/// Accepted extensions, lowercase, from the comma-separated `ATTACHMENT_TYPES`
+pub fn allowed_types() -> Vec<String> {
+    match env::var("ATTACHMENT_TYPES") {
+        Ok(types) => types
+            .split(',')
+            .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+            .filter(|ext| !ext.is_empty())
+            .collect(),
+        Err(_) => DEFAULT_TYPES.iter().map(|ext| ext.to_string()).collect(),
+    }
+}
+
+fn content_type(ext: &str) -> &'static str {
+    match ext {
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "aiff" => "audio/aiff",
+        "mp3" => "audio/mpeg",
+        "ogg" => "audio/ogg",
+        "mid" | "midi" => "audio/midi",
+        "zip" => "application/zip",
+        "pdf" => "application/pdf",
+        "txt" => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A checked attachment name with its extension and content type
+#[derive(Debug, Clone)]
+pub struct CheckedFile {
+    pub name: String,
+    pub extension: String,
+    pub content_type: &'static str,
+}
+
+/// Check an uploaded file's name against the accepted types. Any path the
+/// client sent is dropped, as are control characters.
+pub fn check_file(filename: Option<&str>, size: usize) -> Result<CheckedFile, Error> {
+    if size == 0 {
+        return Err(Error::Validation("file is empty".to_string()));
+    }
+
+    let name: String = filename
+        .and_then(|name| name.rsplit(['/', '\\']).next())
+        .unwrap_or("")
+        .chars()
+        .filter(|c| !c.is_control())
+        .collect();
+    let name = name.trim();
+    if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+        return Err(Error::Validation(format!("file name must be 1 to {} characters", MAX_NAME_LEN)));
+    }
+
+    let extension = name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+    if !allowed_types().contains(&extension) {
+        return Err(Error::UnsupportedMedia(format!("attachments must be one of: {}", allowed_types().join(", "))));
+    }
+
+    Ok(CheckedFile {
+        name: name.to_string(),
+        content_type: content_type(&extension),
+        extension,
+    })
+}
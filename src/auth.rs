@@ -0,0 +1,188 @@
+use std::sync::LazyLock;
+use actix_web::{dev::Payload, http::{header, Method}, FromRequest, HttpRequest};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use futures_util::future::LocalBoxFuture;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use crate::db::audit::AuditOperations;
+use crate::db::error::Error;
+use crate::db::session::SessionOperations;
+use crate::db::{Db, UserOperations};
+use crate::types::id::UserId;
+use crate::types::session::Session;
+use crate::types::user::User;
+
+/// Shortest password accepted
+pub const MIN_PASSWORD_LEN: usize = 8;
+
+/// Longest password accepted, so hashing stays cheap
+pub const MAX_PASSWORD_LEN: usize = 256;
+
+/// Check a new password's length, in characters
+pub fn validate_password(password: &str) -> Result<(), Error> {
+    let len = password.chars().count();
+    if !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&len) {
+        return Err(Error::Validation(format!(
+            "password must be {} to {} characters",
+            MIN_PASSWORD_LEN, MAX_PASSWORD_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// Hash a password into a PHC string using Argon2id
+pub fn hash_password(password: &str) -> Result<String, Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| Error::Db(e.to_string()))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashScheme {
+    Argon2,
+    Bcrypt,
+}
+
+impl HashScheme {
+    /// Detect the scheme of a stored hash from its prefix
+    pub fn detect(hashed_password: &str) -> Option<Self> {
+        if hashed_password.starts_with("$argon2") {
+            Some(HashScheme::Argon2)
+        } else if ["$2a$", "$2b$", "$2y$"].iter().any(|p| hashed_password.starts_with(p)) {
+            Some(HashScheme::Bcrypt)
+        } else {
+            None
+        }
+    }
+}
+
+/// Check a password against a stored hash. Argon2 is what we issue; bcrypt is
+/// accepted for accounts imported from other platforms.
+pub fn verify_password(password: &str, hashed_password: &str) -> bool {
+    match HashScheme::detect(hashed_password) {
+        Some(HashScheme::Argon2) => PasswordHash::new(hashed_password)
+            .map(|parsed| Argon2::default().verify_password(password.as_bytes(), &parsed).is_ok())
+            .unwrap_or(false),
+        Some(HashScheme::Bcrypt) => bcrypt::verify(password, hashed_password).unwrap_or(false),
+        None => false,
+    }
+}
+
+/// Argon2 hash of a random password, made with the same parameters as real
+/// hashes so verifying against it costs the same
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    hash_password(&generate_token()).expect("hashing a dummy password")
+});
+
+/// Spend as long as `verify_password` would on a real account, so a login
+/// for an unknown email can't be told apart by how quickly it fails
+pub fn verify_dummy_password(password: &str) {
+    let _ = verify_password(password, &DUMMY_HASH);
+}
+
+/// Generate a random bearer token
+pub fn generate_token() -> String {
+    hex::encode(rand::random::<[u8; 32]>())
+}
+
+/// Hash a bearer token for storage and lookup
+pub fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    req.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|token| token.trim().to_string())
+}
+
+/// The user behind a valid `Authorization: Bearer <token>` header
+pub struct AuthUser {
+    pub user: User,
+    pub session: Session,
+}
+
+impl AuthUser {
+    /// The admin acting as this user, if the session is an impersonation
+    pub fn impersonator(&self) -> Option<UserId> {
+        self.session.impersonator_id
+    }
+
+    /// The user behind a bearer token sent some other way than the
+    /// `Authorization` header, such as over a WebSocket
+    pub async fn from_token(db: &Db, token: &str) -> Result<Self, Error> {
+        let session = SessionOperations::new(db).get_session_by_token(token).await?;
+        let user = UserOperations::new(db).get_user_by_id(session.user_id)
+            .await
+            .map_err(|e| match e {
+                Error::UserNotFound => Error::Unauthorized,
+                e => e,
+            })?;
+
+        Ok(AuthUser { user, session })
+    }
+
+    /// Refuse impersonated sessions, for actions only the account holder
+    /// may take, like erasing the account
+    pub fn reject_impersonation(&self) -> Result<(), Error> {
+        match self.impersonator() {
+            Some(_) => Err(Error::Forbidden),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromRequest for AuthUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let token = bearer_token(req);
+        let is_write = !matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+        let request = format!("{} {}", req.method(), req.path());
+        let db = Db::of(req);
+
+        Box::pin(async move {
+            let AuthUser { user, session } = AuthUser::from_token(&db, &token.ok_or(Error::Unauthorized)?).await?;
+
+            // Every write made while impersonating is attributed to the admin
+            if let Some(impersonator_id) = session.impersonator_id.filter(|_| is_write) {
+                let detail = json!({ "user_id": user.id, "session_id": session.id, "request": request });
+                AuditOperations::new(&db).record(Some(impersonator_id), "impersonation.write", detail).await?;
+            }
+
+            Ok(AuthUser { user, session })
+        })
+    }
+}
+
+/// An authenticated user whose profile carries the admin flag
+pub struct AdminUser(pub User);
+
+impl FromRequest for AdminUser {
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, payload: &mut Payload) -> Self::Future {
+        let auth = AuthUser::from_request(req, payload);
+
+        Box::pin(async move {
+            let AuthUser { user, session } = auth.await?;
+            let is_admin = user.profile.as_ref().is_some_and(|p| p.is_admin);
+
+            // Admin rights never carry over into an impersonated session
+            if !is_admin || session.impersonator_id.is_some() {
+                return Err(Error::Forbidden);
+            }
+
+            Ok(AdminUser(user))
+        })
+    }
+}
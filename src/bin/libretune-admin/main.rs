@@ -0,0 +1,436 @@
+//! Operator tool for tasks that would otherwise need hand-written queries.
+//!
+//! Connects to the same database as the server and reads the same `.env`.
+
+use std::path::PathBuf;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use clap::{Parser, Subcommand};
+use dotenv::dotenv;
+use libretune::db::error::Error;
+use libretune::db::{connect_db, Db, UserOperations};
+use libretune::db::erasure::ErasureOperations;
+use libretune::types::user::{CreatedVia, NewUser, User, UserProfile};
+use libretune::config::{self, IssueLevel};
+use libretune::{erasure, reconcile};
+
+mod backup;
+#[cfg(feature = "fixtures")]
+mod seed;
+
+#[derive(Parser)]
+#[command(name = "libretune-admin", about = "Operational tasks for a Libretune instance")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create an admin account, prompting for its password
+    CreateAdmin {
+        #[arg(long)]
+        email: String,
+        #[arg(long)]
+        username: String,
+    },
+    /// Recount denormalized counters and fix any that drifted
+    ReconcileCounters {
+        /// Only this counter, e.g. `followers`
+        #[arg(long)]
+        counter: Option<String>,
+    },
+    /// Erase soft-deleted accounts that aren't under legal hold
+    Purge {
+        /// List what would be erased without erasing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export or restore the database
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+    /// Fill an empty database with fake users, tracks and activity. Left
+    /// out of builds without the default `fixtures` feature.
+    #[cfg(feature = "fixtures")]
+    Seed {
+        /// Number of users to create
+        #[arg(long, default_value_t = 50)]
+        users: usize,
+        /// Same seed, same data
+        #[arg(long, default_value_t = 42)]
+        seed: u64,
+        /// Seed even if the database already has users
+        #[arg(long)]
+        force: bool,
+    },
+    /// Inspect accounts
+    User {
+        #[command(subcommand)]
+        command: UserCommand,
+    },
+    /// Inspect the configuration this machine's environment gives the server
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackupCommand {
+    /// Dump the database and write a manifest next to it
+    Export {
+        #[arg(long, default_value = "backup.surql")]
+        out: PathBuf,
+        /// Only these tables, comma-separated
+        #[arg(long, value_delimiter = ',')]
+        tables: Vec<String>,
+        /// Also archive the local media directory
+        #[arg(long)]
+        media: bool,
+    },
+    /// Restore a dump and check it against its manifest
+    Import {
+        #[arg(long, default_value = "backup.surql")]
+        from: PathBuf,
+        /// Import even if the database already has records
+        #[arg(long)]
+        force: bool,
+        /// Also restore the media archive into the media directory
+        #[arg(long)]
+        media: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Show an account by email
+    Info { email: String },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Validate the settings, exiting non-zero if the server would refuse them
+    Check,
+    /// Show every setting as the server would load it, secrets masked
+    Show,
+    /// Generate a key pair for VAPID_PUBLIC_KEY and VAPID_PRIVATE_KEY
+    VapidKeys,
+}
+
+/// Print rows under a header, each column padded to its widest cell
+fn print_table<const N: usize>(header: [&str; N], rows: Vec<[String; N]>) {
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let print_row = |cells: &[String]| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+
+    print_row(&header.map(str::to_string));
+    print_row(&widths.map(|width| "-".repeat(width)));
+    for row in &rows {
+        print_row(row);
+    }
+}
+
+async fn create_admin(db: &Db, email: String, username: String) -> Result<(), Error> {
+    let password = rpassword::prompt_password("Password: ").map_err(|e| Error::Db(e.to_string()))?;
+    let confirm = rpassword::prompt_password("Confirm password: ").map_err(|e| Error::Db(e.to_string()))?;
+    if password != confirm {
+        return Err(Error::Validation("passwords don't match".to_string()));
+    }
+
+    let user = add_admin(db, email, username, password).await?;
+
+    println!("Created admin {} ({})", user.username, user.id);
+    Ok(())
+}
+
+async fn add_admin(db: &Db, email: String, username: String, password: String) -> Result<User, Error> {
+    let input = NewUser { username, email, password, bio: None }.into_input(CreatedVia::Cli)?;
+    let user = UserOperations::new(db).create_user(input).await?;
+
+    let mut profile = UserProfile::new(user.username.clone());
+    profile.is_admin = true;
+    UserOperations::new(db).update_profile(user.id, profile).await
+}
+
+async fn reconcile_counters(db: &Db, counter: Option<String>) -> Result<(), Error> {
+    let reports = match counter {
+        Some(name) => vec![reconcile::reconcile_named(db, &name).await?],
+        None => reconcile::reconcile_all(db).await?,
+    };
+
+    print_table(
+        ["COUNTER", "CHECKED", "CORRECTED"],
+        reports
+            .into_iter()
+            .map(|report| [report.counter, report.checked.to_string(), report.corrected.to_string()])
+            .collect(),
+    );
+    Ok(())
+}
+
+async fn purge(db: &Db, dry_run: bool) -> Result<(), Error> {
+    let users = UserOperations::new(db).get_purgeable_users().await?;
+    if users.is_empty() {
+        println!("Nothing to purge");
+        return Ok(());
+    }
+
+    print_table(
+        ["ID", "USERNAME", "DELETED"],
+        users
+            .iter()
+            .map(|user| [user.id.to_string(), user.username.clone(), user.updated_at.to_rfc3339()])
+            .collect(),
+    );
+
+    if dry_run {
+        println!("\n{} account(s) would be erased", users.len());
+        return Ok(());
+    }
+
+    for user in &users {
+        let job = ErasureOperations::new(db).create_job(user.id).await?;
+        erasure::run(db, job).await?;
+    }
+    println!("\nErased {} account(s)", users.len());
+    Ok(())
+}
+
+async fn backup_database(db: &Db, command: BackupCommand) -> Result<(), Error> {
+    let manifest = match command {
+        BackupCommand::Export { out, tables, media } => backup::export(db, &out, tables, media).await?,
+        BackupCommand::Import { from, force, media } => backup::import(db, &from, force, media).await?,
+    };
+
+    let total: u64 = manifest.tables.values().sum();
+    println!("\nDone, {} record(s) in {} table(s)", total, manifest.tables.len());
+    Ok(())
+}
+
+#[cfg(feature = "fixtures")]
+async fn seed_database(options: seed::SeedOptions) -> Result<(), Error> {
+    let summary = seed::run(options).await?;
+
+    print_table(
+        ["CREATED", "COUNT"],
+        vec![
+            ["users".to_string(), summary.users.to_string()],
+            ["follows".to_string(), summary.follows.to_string()],
+            ["tracks".to_string(), summary.tracks.to_string()],
+            ["playlists".to_string(), summary.playlists.to_string()],
+            ["comments".to_string(), summary.comments.to_string()],
+            ["reactions".to_string(), summary.reactions.to_string()],
+        ],
+    );
+    println!("\nEvery account's password is \"{}\"", seed::PASSWORD);
+    Ok(())
+}
+
+async fn user_info(db: &Db, email: String) -> Result<(), Error> {
+    let user = UserOperations::new(db).get_user_by_email(email.trim().to_lowercase()).await?;
+    let profile = user.profile.as_ref();
+    let flag = |set: bool| if set { "yes" } else { "no" }.to_string();
+
+    print_table(
+        ["FIELD", "VALUE"],
+        vec![
+            ["id".to_string(), user.id.to_string()],
+            ["username".to_string(), user.username.clone()],
+            ["email".to_string(), user.email.clone()],
+            ["email verified".to_string(), flag(user.email_verified)],
+            ["created".to_string(), user.created_at.to_rfc3339()],
+            ["created via".to_string(), format!("{:?}", user.created_via)],
+            ["last login".to_string(), profile.and_then(|p| p.last_login).map_or("never".to_string(), |at| at.to_rfc3339())],
+            ["admin".to_string(), flag(profile.is_some_and(|p| p.is_admin))],
+            ["banned".to_string(), flag(profile.is_some_and(|p| p.is_banned()))],
+            ["deleted".to_string(), flag(profile.is_some_and(|p| p.is_deleted()))],
+            ["legal hold".to_string(), flag(user.legal_hold)],
+        ],
+    );
+    Ok(())
+}
+
+/// Print any configuration problems, returning false if the server would refuse to start
+fn check_config() -> bool {
+    let issues = config::check();
+    if issues.is_empty() {
+        println!("Configuration is valid");
+        return true;
+    }
+
+    let errors = issues.iter().filter(|issue| issue.level == IssueLevel::Error).count();
+    print_table(
+        ["LEVEL", "SETTING", "PROBLEM"],
+        issues
+            .into_iter()
+            .map(|issue| [format!("{:?}", issue.level).to_lowercase(), issue.setting, issue.message])
+            .collect(),
+    );
+
+    if errors > 0 {
+        eprintln!("\n❌ {} setting(s) would stop the server from starting", errors);
+        return false;
+    }
+    true
+}
+
+fn show_config() {
+    print_table(
+        ["SETTING", "VALUE"],
+        config::effective()
+            .into_iter()
+            .map(|setting| [setting.name, setting.value.unwrap_or_else(|| "(default)".to_string())])
+            .collect(),
+    );
+}
+
+/// Print a fresh VAPID key pair in the form `.env` takes it
+fn generate_vapid_keys() -> Result<(), Error> {
+    let (keypair, _) = ece::generate_keypair_and_auth_secret().map_err(|e| Error::Db(e.to_string()))?;
+    let components = keypair.raw_components().map_err(|e| Error::Db(e.to_string()))?;
+
+    println!("VAPID_PUBLIC_KEY={}", URL_SAFE_NO_PAD.encode(components.public_key()));
+    println!("VAPID_PRIVATE_KEY={}", URL_SAFE_NO_PAD.encode(components.private_key()));
+    Ok(())
+}
+
+#[actix_web::main]
+async fn main() {
+    dotenv().ok();
+    let cli = Cli::parse();
+
+    // Configuration commands have to work without a database
+    if let Command::Config { command } = &cli.command {
+        match command {
+            ConfigCommand::Check => {
+                if !check_config() {
+                    std::process::exit(1);
+                }
+            }
+            ConfigCommand::Show => show_config(),
+            ConfigCommand::VapidKeys => {
+                if let Err(e) = generate_vapid_keys() {
+                    eprintln!("❌ {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        return;
+    }
+
+    if let Err(e) = connect_db().await {
+        eprintln!("❌ Failed to connect to SurrealDB: {}", e);
+        std::process::exit(1);
+    }
+
+    let db = Db::global();
+    let result = match cli.command {
+        Command::CreateAdmin { email, username } => create_admin(&db, email, username).await,
+        Command::ReconcileCounters { counter } => reconcile_counters(&db, counter).await,
+        Command::Purge { dry_run } => purge(&db, dry_run).await,
+        Command::Backup { command } => backup_database(&db, command).await,
+        #[cfg(feature = "fixtures")]
+        Command::Seed { users, seed, force } => seed_database(seed::SeedOptions { users, seed, force }).await,
+        Command::User { command: UserCommand::Info { email } } => user_info(&db, email).await,
+        Command::Config { .. } => unreachable!("handled before connecting"),
+    };
+
+    // Operators get the underlying cause that API clients never see
+    if let Err(e) = result {
+        match e {
+            Error::Db(detail) => eprintln!("❌ {}", detail),
+            e => eprintln!("❌ {}", e),
+        }
+        std::process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use libretune::auth::verify_password;
+    use libretune::db::error::Error;
+    use libretune::db::{Db, UserOperations};
+    use libretune::fixtures::UserFixture;
+    use libretune::types::user::CreatedVia;
+
+    async fn db() -> Db {
+        Db::memory().await.expect("in-memory database connects")
+    }
+
+    #[actix_web::test]
+    async fn create_admin_makes_an_admin_who_can_log_in() {
+        let db = db().await;
+
+        let admin = super::add_admin(&db, "root@example.com".to_string(), "operator".to_string(), "correct horse battery".to_string())
+            .await
+            .expect("admin is created");
+
+        let stored = UserOperations::new(&db).get_user_by_id(admin.id).await.expect("admin is stored");
+        assert!(stored.profile.is_some_and(|p| p.is_admin));
+        assert!(matches!(stored.created_via, CreatedVia::Cli));
+        assert!(verify_password("correct horse battery", &stored.hashed_password));
+    }
+
+    #[actix_web::test]
+    async fn reconcile_counters_repairs_a_drifted_counter() {
+        let db = db().await;
+        let users = UserOperations::new(&db);
+        let alice = UserFixture::new().create(&db).await.expect("user is created");
+        let bob = UserFixture::new().create(&db).await.expect("user is created");
+        users.follow_user(bob.id, alice.id).await.expect("bob follows alice");
+        db.query("UPDATE type::thing('users', $id) SET profile.followers = []")
+            .bind(("id", alice.id.to_string()))
+            .await
+            .expect("followers are cleared");
+        db.user_cache().clear();
+
+        super::reconcile_counters(&db, Some("followers".to_string())).await.expect("counters are reconciled");
+
+        let alice = users.get_user_by_id(alice.id).await.expect("alice is here");
+        assert_eq!(alice.profile.and_then(|p| p.followers), Some(vec![bob.id]));
+        let unknown = super::reconcile_counters(&db, Some("karma".to_string())).await;
+        assert!(unknown.is_err(), "an unknown counter is refused");
+    }
+
+    #[actix_web::test]
+    async fn purge_erases_deleted_accounts_unless_dry_run() {
+        let db = db().await;
+        let users = UserOperations::new(&db);
+        let gone = UserFixture::new().create(&db).await.expect("user is created");
+        let held = UserFixture::new().create(&db).await.expect("user is created");
+        users.delete_user(gone.id).await.expect("user is deleted");
+        users.delete_user(held.id).await.expect("user is deleted");
+        users.set_legal_hold(held.id, true).await.expect("hold is set");
+
+        super::purge(&db, true).await.expect("dry run lists");
+        assert_eq!(users.get_purgeable_users().await.expect("users are listed").len(), 1, "a dry run erases nothing");
+
+        super::purge(&db, false).await.expect("purge runs");
+        assert!(users.get_purgeable_users().await.expect("users are listed").is_empty());
+        assert!(users.get_user_by_id(held.id).await.is_ok(), "an account under legal hold is kept");
+    }
+
+    #[actix_web::test]
+    async fn user_info_finds_users_by_email() {
+        let db = db().await;
+        UserFixture::new().email("alice@example.com").create(&db).await.expect("user is created");
+
+        super::user_info(&db, " Alice@Example.com ".to_string()).await.expect("alice is found");
+        let missing = super::user_info(&db, "nobody@example.com".to_string()).await;
+        assert!(matches!(missing, Err(Error::UserNotFound)), "got {:?}", missing);
+    }
+}
@@ -0,0 +1,190 @@
+//! Fixture data for local development.
+//!
+//! Everything is made with `libretune::fixtures`, which writes through the
+//! same operations the API uses, so seeded data obeys the same invariants.
+//! Names, the social graph and content are derived from the seed alone;
+//! record ids and write timestamps still come from the operations
+//! themselves.
+
+use chrono::Duration;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use libretune::auth::hash_password;
+use libretune::clock;
+use libretune::db::error::Error;
+use libretune::db::track::TrackOperations;
+use libretune::db::{Db, UserOperations};
+use libretune::fixtures::{CommentFixture, PlaylistFixture, TrackFixture, UserFixture};
+use libretune::types::license::License;
+use libretune::types::user::{CreatedVia, Track, User};
+
+/// Refuse to seed a database with more users than this unless forced
+pub const MAX_EXISTING_USERS: u64 = 5;
+
+/// Password of every seeded account
+pub const PASSWORD: &str = "password";
+
+/// Spread of track release dates, in days before now
+const HISTORY_DAYS: i64 = 90;
+
+const ADJECTIVES: &[&str] = &[
+    "amber", "blue", "cosmic", "dusty", "electric", "faded", "golden", "hollow",
+    "lunar", "midnight", "neon", "quiet", "rusty", "silver", "velvet", "wild",
+];
+const NOUNS: &[&str] = &[
+    "echo", "fox", "harbor", "lantern", "meadow", "orbit", "pilot", "river",
+    "signal", "sparrow", "tide", "vinyl", "wave", "willow", "wolf", "zephyr",
+];
+const GENRES: &[&str] = &["ambient", "electronic", "folk", "hip-hop", "indie", "jazz", "metal", "techno"];
+const TAGS: &[&str] = &["chill", "demo", "live", "lo-fi", "remix", "instrumental", "acoustic", "experimental"];
+const PRONOUNS: &[&str] = &["she/her", "he/him", "they/them"];
+const COMMENTS: &[&str] = &[
+    "Love this one!",
+    "The mix on this is so clean.",
+    "On repeat all week.",
+    "That drop at the end though",
+    "Any plans to release the stems?",
+    "Reminds me of early 2000s records.",
+];
+const REPLIES: &[&str] = &["Agreed!", "Thanks so much!", "Same here.", "Glad you like it :)"];
+
+pub struct SeedOptions {
+    pub users: usize,
+    pub seed: u64,
+    pub force: bool,
+}
+
+#[derive(Default)]
+pub struct SeedSummary {
+    pub users: usize,
+    pub follows: usize,
+    pub tracks: usize,
+    pub playlists: usize,
+    pub comments: usize,
+    pub reactions: usize,
+}
+
+pub async fn run(options: SeedOptions) -> Result<SeedSummary, Error> {
+    let db = Db::global();
+    let existing = UserOperations::new(&db).get_user_stats().await?.total_users;
+    if existing > MAX_EXISTING_USERS && !options.force {
+        return Err(Error::Validation(format!(
+            "database already has {} users, pass --force to seed it anyway",
+            existing
+        )));
+    }
+
+    let mut rng = StdRng::seed_from_u64(options.seed);
+    let mut summary = SeedSummary::default();
+
+    // Argon2 is slow on purpose, so every account shares one hash
+    let hashed_password = hash_password(PASSWORD)?;
+
+    let mut users: Vec<User> = Vec::with_capacity(options.users);
+    for i in 0..options.users {
+        let adjective = ADJECTIVES.choose(&mut rng).copied().unwrap_or("quiet");
+        let noun = NOUNS.choose(&mut rng).copied().unwrap_or("echo");
+        let username = format!("{}_{}{}", adjective, noun, i);
+
+        let mut user = UserFixture::new()
+            .email(format!("{}@example.test", username))
+            .username(username)
+            .hashed_password(hashed_password.clone())
+            .created_via(CreatedVia::Cli)
+            .profile_name(format!("{} {}", capitalize(adjective), capitalize(noun)))
+            .pronouns(PRONOUNS.choose(&mut rng).copied().unwrap_or("they/them"))
+            .bio(format!("Making {} music since {}.", GENRES.choose(&mut rng).unwrap_or(&"indie"), rng.random_range(1995..2024)));
+        if rng.random_bool(0.1) {
+            user = user.private();
+        }
+        users.push(user.create(&db).await?);
+        summary.users += 1;
+    }
+
+    for follower in &users {
+        let count = rng.random_range(0..=8.min(users.len().saturating_sub(1)));
+        for followee in users.choose_multiple(&mut rng, count) {
+            if followee.id == follower.id {
+                continue;
+            }
+            UserOperations::new(&db).follow_user(follower.id, followee.id).await?;
+            summary.follows += 1;
+        }
+    }
+
+    let now = clock::now();
+    let mut tracks: Vec<Track> = Vec::new();
+    for user in &users {
+        for n in 0..rng.random_range(0..=5) {
+            let genre = GENRES.choose(&mut rng).unwrap_or(&"indie").to_string();
+            let mut track = TrackFixture::new()
+                .owner(user.id)
+                .title(format!("{} {}", capitalize(ADJECTIVES.choose(&mut rng).unwrap_or(&"quiet")), capitalize(NOUNS.choose(&mut rng).unwrap_or(&"echo"))))
+                .description(format!("A {} track.", genre))
+                .audio_url(format!("https://example.test/audio/{}/{}.mp3", user.username, n))
+                .tags(TAGS.choose_multiple(&mut rng, 2).copied())
+                .genre(genre)
+                .created_at(now - Duration::minutes(rng.random_range(0..HISTORY_DAYS * 24 * 60)))
+                .license(License::CcBy)
+                .language("en");
+            if !rng.random_bool(0.9) {
+                track = track.private();
+            }
+            if rng.random_bool(0.3) {
+                track = track.downloadable();
+            }
+            tracks.push(track.create(&db).await?);
+            summary.tracks += 1;
+        }
+    }
+
+    let public: Vec<&Track> = tracks.iter().filter(|t| t.is_public).collect();
+    for track in &public {
+        for _ in 0..rng.random_range(0..=3) {
+            let Some(author) = users.choose(&mut rng) else { break };
+            let content = COMMENTS.choose(&mut rng).unwrap_or(&"Nice!");
+            let comment = CommentFixture::new().on(track).by(author.id).content(*content).create(&db).await?;
+            summary.comments += 1;
+
+            if rng.random_bool(0.4) {
+                let reply = REPLIES.choose(&mut rng).unwrap_or(&"Thanks!");
+                CommentFixture::new().on(track).by(track.user_id).content(*reply).reply_to(comment.id).create(&db).await?;
+                summary.comments += 1;
+            }
+
+            let count = rng.random_range(0..=4);
+            for reactor in users.choose_multiple(&mut rng, count) {
+                TrackOperations::new(&db).react_to_comment(track.id, comment.id, reactor.id, rng.random_bool(0.85)).await?;
+                summary.reactions += 1;
+            }
+        }
+    }
+
+    for user in &users {
+        if public.is_empty() || !rng.random_bool(0.5) {
+            continue;
+        }
+        let count = rng.random_range(1..=public.len().min(10));
+        let track_ids: Vec<_> = public.choose_multiple(&mut rng, count).map(|t| t.id).collect();
+        let mut playlist = PlaylistFixture::new()
+            .owner(user.id)
+            .name(format!("{} mix", capitalize(GENRES.choose(&mut rng).unwrap_or(&"indie"))))
+            .tracks(&track_ids);
+        if !rng.random_bool(0.8) {
+            playlist = playlist.private();
+        }
+        playlist.create(&db).await?;
+        summary.playlists += 1;
+    }
+
+    Ok(summary)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
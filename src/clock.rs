@@ -0,0 +1,79 @@
+//! The time as the app sees it.
+//!
+//! Everything that stamps a record or checks an expiry asks a `Clock`
+//! rather than calling `Utc::now()`, so tests can move time instead of
+//! sleeping. A `Db` carries the clock its operations use; code that isn't
+//! handed one yet goes through `now()`, which reads the process-wide clock.
+//! That's the system clock unless a test installed another first.
+
+use std::sync::{Arc, Mutex, OnceLock};
+use chrono::{DateTime, Duration, Utc};
+
+/// A source of the current time
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The real time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+/// A clock that stands still until moved, for tests
+#[derive(Debug)]
+pub struct MockClock {
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl MockClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        MockClock { now: Mutex::new(start) }
+    }
+
+    /// Move the clock forward by `by`
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += by;
+    }
+
+    /// Put the clock at `at`, even if that's earlier
+    pub fn set(&self, at: DateTime<Utc>) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) = at;
+    }
+}
+
+impl Default for MockClock {
+    /// Starting at the real time
+    fn default() -> Self {
+        Self::new(Utc::now())
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+static CLOCK: OnceLock<Arc<dyn Clock>> = OnceLock::new();
+
+/// Tell the time with `clock` process-wide instead of the system clock,
+/// e.g. a `MockClock` in tests. Only takes before the time is first asked
+/// for; returns whether it did.
+pub fn install(clock: Arc<dyn Clock>) -> bool {
+    CLOCK.set(clock).is_ok()
+}
+
+/// The process-wide clock
+pub fn clock() -> &'static dyn Clock {
+    CLOCK.get_or_init(|| Arc::new(SystemClock)).as_ref()
+}
+
+/// The current time by the process-wide clock
+pub fn now() -> DateTime<Utc> {
+    clock().now()
+}
@@ -0,0 +1,129 @@
+//! Conditional GET support.
+//!
+//! Handlers opt in by returning `conditional::json(&req, policy, &body)`
+//! instead of `HttpResponse::Ok().json(body)`. The body is hashed into a weak
+//! ETag; when the client's `If-None-Match` already names it the response is a
+//! bodiless 304.
+//!
+//! Resources that know when they last changed can use `json_modified`
+//! instead, which also sends `Last-Modified` and answers `If-Modified-Since`
+//! for clients that cache by date. As the spec requires, `If-Modified-Since`
+//! is ignored when the request carries `If-None-Match`.
+
+use std::time::SystemTime;
+use actix_web::http::header::{self, HttpDate};
+use actix_web::{HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use crate::db::error::Error;
+
+/// How long public API responses may be cached, in seconds
+pub const DEFAULT_MAX_AGE: u32 = 60;
+
+/// How shared caches may store a response
+#[derive(Debug, Clone, Copy)]
+pub enum CachePolicy {
+    /// Anyone may cache it for `max_age` seconds
+    Public { max_age: u32 },
+    /// Only the client may cache it, and must revalidate every time. Use for
+    /// anything that depends on who is asking.
+    Private,
+}
+
+impl CachePolicy {
+    /// Public unless the viewer sees more than an anonymous visitor would
+    pub fn for_viewer(personalized: bool, max_age: u32) -> Self {
+        if personalized {
+            CachePolicy::Private
+        } else {
+            CachePolicy::Public { max_age }
+        }
+    }
+
+    fn header_value(self) -> String {
+        match self {
+            CachePolicy::Public { max_age } => format!("public, max-age={}", max_age),
+            CachePolicy::Private => "private, no-cache".to_string(),
+        }
+    }
+}
+
+/// Weak ETag over the serialized body
+pub fn etag(body: &[u8]) -> String {
+    let digest = Sha256::digest(body);
+    format!("W/\"{}\"", hex::encode(&digest[..16]))
+}
+
+/// Whether an `If-None-Match` header value matches `etag`. Comparison is
+/// weak, as the spec requires for `If-None-Match`.
+pub fn matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+
+    if_none_match
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == etag)
+}
+
+/// Whether an `If-Modified-Since` header value is no earlier than
+/// `last_modified`. HTTP dates have whole-second precision, so the
+/// comparison is too.
+pub fn unmodified_since(if_modified_since: &str, last_modified: DateTime<Utc>) -> bool {
+    if_modified_since
+        .parse::<HttpDate>()
+        .map(SystemTime::from)
+        .is_ok_and(|since| DateTime::<Utc>::from(since).timestamp() >= last_modified.timestamp())
+}
+
+/// Respond with `body` as JSON, or 304 if the client already has it
+pub fn json<T: Serialize>(req: &HttpRequest, policy: CachePolicy, body: &T) -> Result<HttpResponse, Error> {
+    respond(req, policy, body, None)
+}
+
+/// Like `json`, also sending `Last-Modified` and honouring `If-Modified-Since`
+pub fn json_modified<T: Serialize>(
+    req: &HttpRequest,
+    policy: CachePolicy,
+    body: &T,
+    last_modified: DateTime<Utc>,
+) -> Result<HttpResponse, Error> {
+    respond(req, policy, body, Some(last_modified))
+}
+
+fn respond<T: Serialize>(
+    req: &HttpRequest,
+    policy: CachePolicy,
+    body: &T,
+    last_modified: Option<DateTime<Utc>>,
+) -> Result<HttpResponse, Error> {
+    let body = serde_json::to_vec(body)?;
+    let etag = etag(&body);
+    let header_str = |name: header::HeaderName| req.headers().get(name).and_then(|h| h.to_str().ok());
+
+    let not_modified = match header_str(header::IF_NONE_MATCH) {
+        Some(if_none_match) => matches(if_none_match, &etag),
+        None => last_modified.zip(header_str(header::IF_MODIFIED_SINCE))
+            .is_some_and(|(last_modified, since)| unmodified_since(since, last_modified)),
+    };
+
+    let mut response = if not_modified {
+        HttpResponse::NotModified()
+    } else {
+        HttpResponse::Ok()
+    };
+    response
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, policy.header_value()))
+        .insert_header((header::VARY, "Authorization"));
+
+    if let Some(last_modified) = last_modified {
+        response.insert_header((header::LAST_MODIFIED, HttpDate::from(SystemTime::from(last_modified))));
+    }
+
+    if not_modified {
+        return Ok(response.finish());
+    }
+
+    Ok(response.content_type("application/json").body(body))
+}
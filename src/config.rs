@@ -0,0 +1,337 @@
+use std::env;
+use std::num::NonZeroUsize;
+use std::thread;
+use std::time::Duration;
+use actix_web::http::KeepAlive;
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::flags;
+
+/// Whether we're running in production, set with `ENV=production`
+pub fn is_production() -> bool {
+    env::var("ENV").is_ok_and(|env| env.eq_ignore_ascii_case("production"))
+}
+
+/// Base URL the server is reachable at, used to build absolute links.
+/// Set with `PUBLIC_URL`, without a trailing slash.
+pub fn public_url() -> String {
+    env::var("PUBLIC_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|_| {
+            let host = env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
+            let port = env::var("PORT").unwrap_or_else(|_| "8000".to_string());
+            format!("http://{}:{}", host, port)
+        })
+}
+
+/// HTTP worker threads, set with `WORKERS`. Defaults to one per CPU.
+pub fn workers() -> usize {
+    match env::var("WORKERS") {
+        Ok(workers) => workers
+            .parse::<NonZeroUsize>()
+            .expect("WORKERS must be a positive number")
+            .get(),
+        Err(_) => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+    }
+}
+
+/// How long idle connections are kept open, set in seconds with
+/// `KEEP_ALIVE_SECS`. `0` turns keep-alive off; unset keeps actix's default.
+pub fn keep_alive() -> KeepAlive {
+    match env::var("KEEP_ALIVE_SECS") {
+        Ok(secs) => match secs.parse::<u64>().expect("KEEP_ALIVE_SECS must be a number") {
+            0 => KeepAlive::Disabled,
+            secs => KeepAlive::Timeout(Duration::from_secs(secs)),
+        },
+        Err(_) => KeepAlive::default(),
+    }
+}
+
+/// What happens to a soft-deleted user's username and email
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeletedHandles {
+    /// Keep them reserved, so nobody can take over a deleted account's name
+    Reserve,
+    /// Tombstone them on the deleted record so new signups can reuse them
+    Release,
+}
+
+/// Set with `DELETED_HANDLES=release`. Defaults to `reserve`, since a freed
+/// handle lets someone else pose as the deleted account to its old followers.
+pub fn deleted_handles() -> DeletedHandles {
+    match env::var("DELETED_HANDLES") {
+        Ok(policy) if policy.eq_ignore_ascii_case("release") => DeletedHandles::Release,
+        Ok(policy) if policy.eq_ignore_ascii_case("reserve") => DeletedHandles::Reserve,
+        Ok(policy) => panic!("DELETED_HANDLES must be reserve or release, got {}", policy),
+        Err(_) => DeletedHandles::Reserve,
+    }
+}
+
+/// Every environment variable the server reads. `FLAG_<NAME>` overrides are
+/// checked separately against the known flags.
+pub const SETTINGS: &[&str] = &[
+    "API_DOCS",
+    "ATTACHMENT_MAX_MB",
+    "ATTACHMENT_TYPES",
+    "AUDIO_MAX_MB",
+    "DB_LATENCY_PROBE_SECS",
+    "DB_LATENCY_THRESHOLD_MS",
+    "DELETED_HANDLES",
+    "EMAIL_BACKEND",
+    "EMAIL_FROM",
+    "ENV",
+    "FEDERATION",
+    "FLAG_REFRESH_SECS",
+    "GC_INTERVAL_SECS",
+    "GC_MAX_DELETIONS",
+    "GC_MIN_AGE_HOURS",
+    "GEOCODING",
+    "HOST",
+    "KEEP_ALIVE_SECS",
+    "LOG_FORMAT",
+    "LOG_REQUESTS_CONSOLE",
+    "LOG_REQUESTS_FILE",
+    "LOG_REQUESTS_FILE_PATH",
+    "LOG_REQUESTS_FORMAT",
+    "LOG_REQUESTS_SAMPLE_RATE",
+    "LOG_REQUESTS_SAMPLE_SEED",
+    "LOG_SLOW_REQUEST_MS",
+    "MAINTENANCE",
+    "MAINTENANCE_MESSAGE",
+    "MAINTENANCE_REFRESH_SECS",
+    "MAINTENANCE_SCOPE",
+    "MAX_CONCURRENT_UPLOADS",
+    "MEDIA_DIR",
+    "PORT",
+    "PRESENCE_MAX_LISTENERS",
+    "PUBLIC_URL",
+    "QUERY_TIMEOUT_MS",
+    "RECONCILE_INTERVAL_SECS",
+    "REPORT_URGENT_THRESHOLD",
+    "RESERVED_USERNAMES",
+    "SITEMAP_DIR",
+    "SITEMAP_INTERVAL_SECS",
+    "SMTP_HOST",
+    "SMTP_PASSWORD",
+    "SMTP_PORT",
+    "SMTP_TLS",
+    "SMTP_USERNAME",
+    "SWAGGER_UI_ASSETS",
+    "TRUSTED_ORIGINS",
+    "UPLOAD_STAGING_DIR",
+    "USERNAME_FILTER",
+    "USER_CACHE_CAPACITY",
+    "USER_CACHE_TTL_SECS",
+    "VAPID_PRIVATE_KEY",
+    "VAPID_PUBLIC_KEY",
+    "VAPID_SUBJECT",
+    "WORKERS",
+    "WS_IDLE_TIMEOUT_SECS",
+    "WS_MAX_CONNECTIONS",
+    "WS_MAX_CONNECTIONS_PER_IP",
+    "WS_MAX_CONNECTIONS_PER_USER",
+];
+
+/// Settings that must be whole numbers when set
+const NUMERIC: &[&str] = &[
+    "ATTACHMENT_MAX_MB",
+    "AUDIO_MAX_MB",
+    "DB_LATENCY_PROBE_SECS",
+    "DB_LATENCY_THRESHOLD_MS",
+    "FLAG_REFRESH_SECS",
+    "GC_INTERVAL_SECS",
+    "GC_MAX_DELETIONS",
+    "GC_MIN_AGE_HOURS",
+    "KEEP_ALIVE_SECS",
+    "LOG_REQUESTS_SAMPLE_SEED",
+    "LOG_SLOW_REQUEST_MS",
+    "MAINTENANCE_REFRESH_SECS",
+    "MAX_CONCURRENT_UPLOADS",
+    "PRESENCE_MAX_LISTENERS",
+    "QUERY_TIMEOUT_MS",
+    "RECONCILE_INTERVAL_SECS",
+    "REPORT_URGENT_THRESHOLD",
+    "SITEMAP_INTERVAL_SECS",
+    "SMTP_PORT",
+    "USER_CACHE_CAPACITY",
+    "USER_CACHE_TTL_SECS",
+    "WS_IDLE_TIMEOUT_SECS",
+    "WS_MAX_CONNECTIONS",
+    "WS_MAX_CONNECTIONS_PER_IP",
+    "WS_MAX_CONNECTIONS_PER_USER",
+];
+
+/// Prefix of variables meant for this server that none of it reads, which
+/// usually means a typo
+const STRAY_PREFIX: &str = "LIBRETUNE_";
+
+/// One setting as the process loaded it
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct EffectiveSetting {
+    pub name: String,
+    /// Masked for secrets; None when unset and the built-in default applies
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueLevel {
+    Error, // the server refuses to start
+    Warning,
+}
+
+/// A problem with the configuration
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ConfigIssue {
+    pub level: IssueLevel,
+    pub setting: String,
+    pub message: String,
+}
+
+impl ConfigIssue {
+    fn error(setting: &str, message: impl Into<String>) -> Self {
+        Self { level: IssueLevel::Error, setting: setting.to_string(), message: message.into() }
+    }
+
+    fn warning(setting: &str, message: impl Into<String>) -> Self {
+        Self { level: IssueLevel::Warning, setting: setting.to_string(), message: message.into() }
+    }
+}
+
+/// Whether a variable holds a credential that must never be shown
+fn is_secret(name: &str) -> bool {
+    ["SECRET", "PASSWORD", "TOKEN", "KEY"].iter().any(|word| name.contains(word))
+}
+
+fn is_on(name: &str) -> bool {
+    env::var(name).is_ok_and(|v| matches!(v.to_lowercase().as_str(), "on" | "true" | "1"))
+}
+
+/// Every known setting with the value it was loaded with, secrets masked.
+/// `FLAG_<NAME>` overrides that are set are included too.
+pub fn effective() -> Vec<EffectiveSetting> {
+    let mut names: Vec<String> = SETTINGS.iter().map(|name| name.to_string()).collect();
+    names.extend(env::vars().map(|(key, _)| key).filter(|key| key.starts_with("FLAG_") && !SETTINGS.contains(&key.as_str())));
+    names.sort();
+
+    names.into_iter()
+        .map(|name| {
+            let value = env::var(&name).ok().map(|value| if is_secret(&name) { "********".to_string() } else { value });
+            EffectiveSetting { name, value }
+        })
+        .collect()
+}
+
+/// Check settings on their own and against each other. Errors mean the
+/// server would misbehave or panic later; warnings are likely mistakes.
+pub fn check() -> Vec<ConfigIssue> {
+    let mut issues = Vec::new();
+
+    for name in NUMERIC {
+        if env::var(name).is_ok_and(|value| value.parse::<u64>().is_err()) {
+            issues.push(ConfigIssue::error(name, "must be a whole number"));
+        }
+    }
+
+    if env::var("PORT").is_ok_and(|port| port.parse::<u16>().is_err()) {
+        issues.push(ConfigIssue::error("PORT", "must be a port number"));
+    }
+
+    if env::var("WORKERS").is_ok_and(|workers| workers.parse::<NonZeroUsize>().is_err()) {
+        issues.push(ConfigIssue::error("WORKERS", "must be a positive number"));
+    }
+
+    if env::var("DELETED_HANDLES").is_ok_and(|policy| !["reserve", "release"].contains(&policy.to_lowercase().as_str())) {
+        issues.push(ConfigIssue::error("DELETED_HANDLES", "must be reserve or release"));
+    }
+
+    if env::var("LOG_FORMAT").is_ok_and(|format| !["compact", "json"].contains(&format.to_lowercase().as_str())) {
+        issues.push(ConfigIssue::warning("LOG_FORMAT", "should be compact or json; anything else logs compact"));
+    }
+
+    let rate = env::var("LOG_REQUESTS_SAMPLE_RATE").ok().map(|rate| rate.parse::<f64>());
+    if rate.is_some_and(|rate| !rate.is_ok_and(|rate| (0.0..=1.0).contains(&rate))) {
+        issues.push(ConfigIssue::warning("LOG_REQUESTS_SAMPLE_RATE", "should be between 0.0 and 1.0; it's clamped, or ignored if not a number"));
+    }
+
+    for name in ["LOG_REQUESTS_CONSOLE", "LOG_REQUESTS_FILE"] {
+        if env::var(name).is_ok_and(|value| value.parse::<bool>().is_err()) {
+            issues.push(ConfigIssue::warning(name, "should be true or false; the default is used instead"));
+        }
+    }
+
+    if is_on("LOG_REQUESTS_FILE") && env::var("LOG_REQUESTS_FILE_PATH").is_ok_and(|path| path.trim().is_empty()) {
+        issues.push(ConfigIssue::error("LOG_REQUESTS_FILE_PATH", "must not be empty while LOG_REQUESTS_FILE is on"));
+    }
+
+    // Links in feeds, sitemaps and emails are built from the public URL
+    match env::var("PUBLIC_URL") {
+        Ok(url) if !(url.starts_with("http://") || url.starts_with("https://")) => {
+            issues.push(ConfigIssue::error("PUBLIC_URL", "must be an http or https URL"));
+        }
+        Ok(url) if is_on("FEDERATION") && !url.starts_with("https://") => {
+            issues.push(ConfigIssue::error("PUBLIC_URL", "must be https while FEDERATION is on, other servers won't fetch actors over http"));
+        }
+        Err(_) if is_production() => {
+            issues.push(ConfigIssue::error("PUBLIC_URL", "must be set in production, links would point at the bind address"));
+        }
+        _ => {}
+    }
+
+    match env::var("EMAIL_BACKEND").map(|backend| backend.to_lowercase()) {
+        Ok(backend) if backend == "smtp" => {
+            for name in ["SMTP_HOST", "EMAIL_FROM"] {
+                if env::var(name).is_err() {
+                    issues.push(ConfigIssue::error(name, "must be set while EMAIL_BACKEND is smtp"));
+                }
+            }
+        }
+        Ok(backend) if backend != "none" => issues.push(ConfigIssue::error("EMAIL_BACKEND", "must be smtp or none")),
+        _ => {}
+    }
+
+    if env::var("SMTP_TLS").is_ok_and(|tls| !["starttls", "tls", "none"].contains(&tls.to_lowercase().as_str())) {
+        issues.push(ConfigIssue::error("SMTP_TLS", "must be starttls, tls or none"));
+    }
+
+    if env::var("MAINTENANCE_SCOPE").is_ok_and(|scope| !["writes", "all"].contains(&scope.to_lowercase().as_str())) {
+        issues.push(ConfigIssue::error("MAINTENANCE_SCOPE", "must be writes or all"));
+    }
+
+    match (env::var("VAPID_PUBLIC_KEY").is_ok(), env::var("VAPID_PRIVATE_KEY").is_ok()) {
+        (true, false) => issues.push(ConfigIssue::error("VAPID_PRIVATE_KEY", "must be set while VAPID_PUBLIC_KEY is")),
+        (false, true) => issues.push(ConfigIssue::error("VAPID_PUBLIC_KEY", "must be set while VAPID_PRIVATE_KEY is")),
+        (true, true) => match env::var("VAPID_SUBJECT") {
+            Ok(subject) if subject.starts_with("mailto:") || subject.starts_with("https:") => {}
+            Ok(_) => issues.push(ConfigIssue::error("VAPID_SUBJECT", "must be a mailto: or https: URL")),
+            Err(_) => issues.push(ConfigIssue::error("VAPID_SUBJECT", "must be set while push keys are, push services reject pushes without a contact")),
+        },
+        (false, false) => {}
+    }
+
+    for name in ["MAINTENANCE_MESSAGE", "MAINTENANCE_SCOPE"] {
+        if env::var(name).is_ok() && !is_on("MAINTENANCE") {
+            issues.push(ConfigIssue::warning(name, "has no effect unless MAINTENANCE is on"));
+        }
+    }
+
+    for (key, _) in env::vars() {
+        if let Some(flag) = key.strip_prefix("FLAG_") {
+            let known = SETTINGS.contains(&key.as_str()) || flags::KNOWN.iter().any(|(name, _)| name.eq_ignore_ascii_case(flag));
+            if !known {
+                issues.push(ConfigIssue::warning(&key, "overrides a feature flag that doesn't exist"));
+            }
+        }
+
+        if let Some(rest) = key.strip_prefix(STRAY_PREFIX) {
+            let message = if SETTINGS.contains(&rest) {
+                format!("is not read, did you mean {}?", rest)
+            } else {
+                "is not read by anything".to_string()
+            };
+            issues.push(ConfigIssue::warning(&key, message));
+        }
+    }
+
+    issues
+}
@@ -0,0 +1,140 @@
+//! Caps on long-lived connections such as WebSockets, so idle sockets can't
+//! be used to exhaust the server.
+//!
+//! A connection takes a permit before the upgrade is accepted; `acquire`
+//! fails when the server already holds `WS_MAX_CONNECTIONS` (default 10000)
+//! or the client's IP holds `WS_MAX_CONNECTIONS_PER_IP` (default 20). The
+//! handler should then close the socket with `Rejection::close_code` and
+//! `reason`. The permit is given back when it's dropped, however the
+//! connection ends. Connections that send nothing for `WS_IDLE_TIMEOUT_SECS`
+//! (default 60) should be dropped. Counts are per process.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// Close code for a server over its connection limit: try again later
+pub const CLOSE_TRY_AGAIN_LATER: u16 = 1013;
+
+/// Close code for a client over its own limit: policy violation
+pub const CLOSE_POLICY_VIOLATION: u16 = 1008;
+
+#[derive(Default)]
+struct Open {
+    total: usize,
+    per_ip: HashMap<String, usize>, // IPs with none are removed
+}
+
+static OPEN: LazyLock<Mutex<Open>> = LazyLock::new(Default::default);
+
+fn setting(name: &str, default: usize) -> usize {
+    env::var(name)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(default)
+}
+
+/// Most connections the server holds at once, set with `WS_MAX_CONNECTIONS`
+pub fn max_connections() -> usize {
+    setting("WS_MAX_CONNECTIONS", 10_000)
+}
+
+/// Most connections one IP holds at once, set with `WS_MAX_CONNECTIONS_PER_IP`
+pub fn max_connections_per_ip() -> usize {
+    setting("WS_MAX_CONNECTIONS_PER_IP", 20)
+}
+
+/// How long a connection may stay silent, set with `WS_IDLE_TIMEOUT_SECS`
+pub fn idle_timeout() -> Duration {
+    Duration::from_secs(setting("WS_IDLE_TIMEOUT_SECS", 60) as u64)
+}
+
+/// Whether a connection last heard from at `last_seen` should be dropped
+pub fn is_idle(last_seen: Instant) -> bool {
+    last_seen.elapsed() >= idle_timeout()
+}
+
+/// Why a connection was turned away
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    ServerFull,
+    TooManyFromIp,
+}
+
+impl Rejection {
+    pub fn close_code(&self) -> u16 {
+        match self {
+            Rejection::ServerFull => CLOSE_TRY_AGAIN_LATER,
+            Rejection::TooManyFromIp => CLOSE_POLICY_VIOLATION,
+        }
+    }
+
+    pub fn reason(&self) -> &'static str {
+        match self {
+            Rejection::ServerFull => "too many connections, try again later",
+            Rejection::TooManyFromIp => "too many connections from this address",
+        }
+    }
+}
+
+/// One open connection, released on drop
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    ip: String,
+}
+
+/// Count a new connection from `ip`, or turn it away if a limit is reached
+pub fn acquire(ip: &str) -> Result<ConnectionPermit, Rejection> {
+    let mut open = OPEN.lock().unwrap_or_else(|e| e.into_inner());
+
+    if open.total >= max_connections() {
+        return Err(Rejection::ServerFull);
+    }
+    let count = open.per_ip.entry(ip.to_string()).or_insert(0);
+    if *count >= max_connections_per_ip() {
+        return Err(Rejection::TooManyFromIp);
+    }
+    *count += 1;
+    open.total += 1;
+
+    Ok(ConnectionPermit { ip: ip.to_string() })
+}
+
+/// Connections open right now
+pub fn open_connections() -> usize {
+    OPEN.lock().unwrap_or_else(|e| e.into_inner()).total
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let mut open = OPEN.lock().unwrap_or_else(|e| e.into_inner());
+        open.total = open.total.saturating_sub(1);
+        if let Some(count) = open.per_ip.get_mut(&self.ip) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                open.per_ip.remove(&self.ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{acquire, max_connections_per_ip, Rejection};
+
+    #[test]
+    fn a_connection_over_the_per_ip_cap_is_rejected() {
+        let ip = "203.0.113.7";
+        let mut permits: Vec<_> = (0..max_connections_per_ip())
+            .map(|_| acquire(ip).expect("under the cap"))
+            .collect();
+
+        assert_eq!(acquire(ip).unwrap_err(), Rejection::TooManyFromIp);
+        assert!(acquire("203.0.113.8").is_ok(), "other addresses have caps of their own");
+
+        permits.pop();
+        assert!(acquire(ip).is_ok(), "closing a connection frees its place");
+    }
+}
@@ -1,16 +1,117 @@
-use std::sync::LazyLock;
-use surrealdb;
-use surrealdb::Surreal;
-use surrealdb::engine::remote::ws::Client;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::future::Future;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+use actix_web::{web, HttpRequest};
+use serde::{de::DeserializeOwned, Serialize};
+use surrealdb::{RecordId, Surreal};
+use surrealdb::method::Query;
+use surrealdb::opt::IntoQuery;
+use surrealdb::Response;
+use surrealdb::engine::any::Any;
 use surrealdb::opt::auth::Root;
-use surrealdb::engine::remote::ws::Ws;
 use uuid::Uuid;
-use chrono::Utc;
-use crate::types::user::{User, UserProfile, CreatedVia};
+use chrono::{DateTime, Utc};
+use cache::{CachedUser, UserCache};
+use crate::clock::{self, Clock};
+use crate::config::{self, DeletedHandles};
+use crate::images::ProfileImage;
+use crate::types::id::{TrackId, UserId};
+use crate::types::latency::ConnectionState;
+use crate::types::location::Location;
+use crate::types::notification::NotificationKind;
+use crate::types::pagination::{cursor_page, Cursor};
+use crate::types::user::{username_sort_key, AccountStatus, User, UserProfile, CreateUserInput, FollowSuggestion, ProfileTrackOrder, PublicUser, SignupCount, UserSort, MAX_PINNED_TRACKS};
+use crate::types::webhook::WebhookEvent;
 
-mod error {
+pub mod announcement;
+pub mod audit;
+pub mod backup;
+pub mod cache;
+pub mod email_outbox;
+pub mod email_token;
+pub mod erasure;
+pub mod feature_flag;
+pub mod federation;
+pub mod gc;
+pub mod import;
+pub mod lyrics;
+pub mod maintenance;
+pub mod notification;
+pub mod play;
+pub mod playlist;
+pub mod push;
+pub mod reconcile;
+pub mod release;
+pub mod report;
+pub mod session;
+pub mod share_link;
+pub mod sitemap;
+pub mod slug;
+pub mod track;
+pub mod transaction;
+pub mod upload;
+pub mod verification;
+pub mod webhook;
+
+pub mod error {
     use actix_web::{HttpResponse, ResponseError};
+    use serde::Serialize;
     use thiserror::Error;
+    use utoipa::ToSchema;
+    use uuid::Uuid;
+    use crate::{config, request_id};
+    use crate::types::id::TrackId;
+    
+    /// JSON body returned for every error response
+    #[derive(Debug, Serialize, ToSchema)]
+    pub struct ErrorBody {
+        pub error: String,
+        /// Set on server errors so a client can report which failure it saw
+        #[serde(skip_serializing_if = "Option::is_none")]
+        pub request_id: Option<Uuid>,
+    }
+    
+    impl ErrorBody {
+        pub fn new(error: impl Into<String>) -> Self {
+            Self { error: error.into(), request_id: None }
+        }
+        
+        /// Body for a server error. The full detail is always logged under the
+        /// request's id, but only shown to the client outside production.
+        /// Outside a request a fresh id stands in.
+        pub fn internal(detail: &str) -> Self {
+            let request_id = request_id::current().unwrap_or_else(Uuid::new_v4);
+            tracing::error!(%request_id, "internal error: {}", detail);
+            
+            let error = if config::is_production() {
+                "Internal server error".to_string()
+            } else {
+                detail.to_string()
+            };
+            
+            Self { error, request_id: Some(request_id) }
+        }
+    }
+    
+    /// Ids in a batch that stopped it: tracks the caller can see but
+    /// doesn't own, and ones that don't exist as far as they can tell
+    #[derive(Debug, Clone, Default, Serialize, ToSchema)]
+    pub struct RejectedTracks {
+        pub not_owned: Vec<TrackId>,
+        pub not_found: Vec<TrackId>,
+    }
+    
+    /// JSON body returned when a batch of tracks is refused
+    #[derive(Debug, Serialize, ToSchema)]
+    pub struct RejectedTracksBody {
+        pub error: String,
+        #[serde(flatten)]
+        pub rejected: RejectedTracks,
+    }
     
     #[derive(Error, Debug)]
     pub enum Error {
@@ -25,15 +126,98 @@ mod error {
         
         #[error("username already exists")]
         UsernameExists,
+        
+        #[error("invalid credentials")]
+        InvalidCredentials,
+        
+        #[error("authentication required")]
+        Unauthorized,
+        
+        #[error("forbidden")]
+        Forbidden,
+        
+        #[error("forbidden")]
+        TracksRejected(RejectedTracks),
+        
+        #[error("account is under legal hold")]
+        LegalHold,
+        
+        #[error("erasure job not found")]
+        ErasureJobNotFound,
+        
+        #[error("track not found")]
+        TrackNotFound,
+        
+        #[error("comment not found")]
+        CommentNotFound,
+        
+        #[error("playlist not found")]
+        PlaylistNotFound,
+        
+        #[error("release not found")]
+        ReleaseNotFound,
+        
+        #[error("webhook not found")]
+        WebhookNotFound,
+        
+        #[error("sitemap not found")]
+        SitemapNotFound,
+        
+        #[error("profile not found")]
+        ProfileNotFound,
+        
+        #[error("media not found")]
+        MediaNotFound,
+        
+        #[error("not found")]
+        NotFound,
+        
+        #[error("invalid request: {0}")]
+        Validation(String),
+        
+        #[error("conflict: {0}")]
+        Conflict(String),
+        
+        #[error("too many requests: {0}")]
+        TooManyRequests(String),
+        
+        #[error("payload too large")]
+        PayloadTooLarge,
+        
+        #[error("unsupported media type: {0}")]
+        UnsupportedMedia(String),
     }
     
     impl ResponseError for Error {
         fn error_response(&self) -> HttpResponse {
             match self {
-                Error::Db(e) => HttpResponse::InternalServerError().body(e.to_string()),
-                Error::UserNotFound => HttpResponse::NotFound().body("User not found"),
-                Error::EmailExists => HttpResponse::Conflict().body("Email already exists"),
-                Error::UsernameExists => HttpResponse::Conflict().body("Username already exists"),
+                Error::Db(e) => HttpResponse::InternalServerError().json(ErrorBody::internal(e)),
+                Error::UserNotFound => HttpResponse::NotFound().json(ErrorBody::new("User not found")),
+                Error::EmailExists => HttpResponse::Conflict().json(ErrorBody::new("Email already exists")),
+                Error::UsernameExists => HttpResponse::Conflict().json(ErrorBody::new("Username already exists")),
+                Error::InvalidCredentials => HttpResponse::Unauthorized().json(ErrorBody::new("Invalid credentials")),
+                Error::Unauthorized => HttpResponse::Unauthorized().json(ErrorBody::new("Authentication required")),
+                Error::Forbidden => HttpResponse::Forbidden().json(ErrorBody::new("Forbidden")),
+                Error::TracksRejected(rejected) => HttpResponse::Forbidden().json(RejectedTracksBody {
+                    error: "Some tracks aren't yours to change".to_string(),
+                    rejected: rejected.clone(),
+                }),
+                Error::LegalHold => HttpResponse::Conflict().json(ErrorBody::new("Account is under legal hold")),
+                Error::ErasureJobNotFound => HttpResponse::NotFound().json(ErrorBody::new("Erasure job not found")),
+                Error::TrackNotFound => HttpResponse::NotFound().json(ErrorBody::new("Track not found")),
+                Error::CommentNotFound => HttpResponse::NotFound().json(ErrorBody::new("Comment not found")),
+                Error::PlaylistNotFound => HttpResponse::NotFound().json(ErrorBody::new("Playlist not found")),
+                Error::ReleaseNotFound => HttpResponse::NotFound().json(ErrorBody::new("Release not found")),
+                Error::WebhookNotFound => HttpResponse::NotFound().json(ErrorBody::new("Webhook not found")),
+                Error::SitemapNotFound => HttpResponse::NotFound().json(ErrorBody::new("Sitemap not found")),
+                Error::ProfileNotFound => HttpResponse::NotFound().json(ErrorBody::new("Profile not found")),
+                Error::MediaNotFound => HttpResponse::NotFound().json(ErrorBody::new("Media not found")),
+                Error::NotFound => HttpResponse::NotFound().json(ErrorBody::new("Not found")),
+                Error::Validation(e) => HttpResponse::BadRequest().json(ErrorBody::new(e.to_string())),
+                Error::Conflict(e) => HttpResponse::Conflict().json(ErrorBody::new(e.to_string())),
+                Error::TooManyRequests(e) => HttpResponse::TooManyRequests().json(ErrorBody::new(e.to_string())),
+                Error::PayloadTooLarge => HttpResponse::PayloadTooLarge().json(ErrorBody::new("Payload too large")),
+                Error::UnsupportedMedia(e) => HttpResponse::UnsupportedMediaType().json(ErrorBody::new(e.to_string())),
             }
         }
     }
@@ -44,63 +228,359 @@ mod error {
             Self::Db(error.to_string())
         }
     }
+    
+    impl From<serde_json::Error> for Error {
+        fn from(error: serde_json::Error) -> Self {
+            eprintln!("{error}");
+            Self::Db(error.to_string())
+        }
+    }
 }
 
-pub static DB: LazyLock<Surreal<Client>> = LazyLock::new(Surreal::init);
+/// A handle on one database. Cloning it shares the connection.
+///
+/// Operations that take a `Db` work against whichever database they're
+/// handed, so each test can have its own, and whether it's a server or
+/// in-memory is decided where the handle is made. Handlers get theirs from
+/// app data as `web::Data<Db>`.
+///
+/// The handle also carries the clock its operations tell the time by, so
+/// a test can hand one a `MockClock` along with its database, and the cache
+/// of the users read through it.
+#[derive(Clone)]
+pub struct Db {
+    surreal: Surreal<Any>,
+    clock: Option<Arc<dyn Clock>>, // None follows the process-wide clock
+    users: Arc<UserCache>, // shared by clones, so by everything on this database
+    queries: Arc<AtomicU64>, // sent through any clone
+}
 
-pub async fn connect_db() -> Result<(), surrealdb::Error> {
-    DB.connect::<Ws>("localhost:8000").await?;
-    DB.signin(Root {
-        username: "root",
-        password: "root",
-    })
-    .await?;
+impl Db {
+    /// Connect to `endpoint`, whose scheme picks the engine: `ws://` or
+    /// `http://` for a server, `mem://` for an in-memory database
+    pub async fn connect(endpoint: &str) -> Result<Self, surrealdb::Error> {
+        let db = Db::new();
+        db.open(endpoint).await?;
+        
+        Ok(db)
+    }
+    
+    /// An empty in-memory database of its own. Needs SurrealDB's `kv-mem`
+    /// engine, which the dev-dependencies turn on.
+    pub async fn memory() -> Result<Self, surrealdb::Error> {
+        Self::connect("mem://").await
+    }
     
-    // Use namespace and database
-    DB.use_ns("libretune").use_db("main").await?;
+    fn new() -> Self {
+        Db {
+            surreal: Surreal::init(),
+            clock: None,
+            users: Arc::new(UserCache::from_env()),
+            queries: Arc::default(),
+        }
+    }
+    
+    /// The same database, telling the time by `clock`
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Db { clock: Some(clock), ..self }
+    }
+    
+    /// The current time by this handle's clock
+    pub fn now(&self) -> DateTime<Utc> {
+        match &self.clock {
+            Some(clock) => clock.now(),
+            None => clock::now(),
+        }
+    }
+    
+    /// The users read through this handle, by id
+    pub fn user_cache(&self) -> &UserCache {
+        &self.users
+    }
+    
+    /// Send a query, counting it. Shadows `Surreal::query` so every query
+    /// made through a handle is counted.
+    pub fn query(&self, query: impl IntoQuery) -> Query<'_, Any> {
+        self.queries.fetch_add(1, Ordering::Relaxed);
+        self.surreal.query(query)
+    }
+    
+    /// How many queries have been sent to this database
+    pub fn queries_sent(&self) -> u64 {
+        self.queries.load(Ordering::Relaxed)
+    }
+    
+    /// Drop a written record from any cache holding it
+    pub(crate) fn invalidate_cached(&self, table: &str, id: impl Into<Uuid>) {
+        if table == "users" {
+            self.users.invalidate(UserId::from(id.into()));
+        }
+    }
+    
+    /// The process-wide handle `connect_db` connects
+    pub fn global() -> Db {
+        Db::clone(&DB)
+    }
+    
+    /// The handle the app serving `req` was built with, for extractors,
+    /// which can't take `web::Data<Db>` themselves
+    pub fn of(req: &HttpRequest) -> Db {
+        req.app_data::<web::Data<Db>>().map_or_else(Db::global, |db| Db::clone(db))
+    }
+    
+    async fn open(&self, endpoint: &str) -> Result<(), surrealdb::Error> {
+        self.surreal.connect(endpoint).await?;
+        if !endpoint.starts_with("mem://") {
+            self.surreal.signin(Root {
+                username: "root",
+                password: "root",
+            })
+            .await?;
+        }
+        
+        // Use namespace and database
+        self.surreal.use_ns("libretune").use_db("main").await?;
+        
+        Ok(())
+    }
+}
+
+impl Deref for Db {
+    type Target = Surreal<Any>;
+    
+    fn deref(&self) -> &Surreal<Any> {
+        &self.surreal
+    }
+}
+
+/// The process-wide database handle, for work that isn't tied to a request,
+/// like the background jobs and the admin tool
+pub static DB: LazyLock<Db> = LazyLock::new(Db::new);
+
+/// Where `DB`'s connection stands, as a `ConnectionState`
+static CONNECTION_STATE: AtomicU8 = AtomicU8::new(ConnectionState::Disconnected as u8);
+
+/// Where `DB`'s connection stands. It's set by `connect_db` and kept up to
+/// date by the latency probe, which notices when the database stops
+/// answering and when it's back.
+pub fn connection_state() -> ConnectionState {
+    ConnectionState::from_u8(CONNECTION_STATE.load(Ordering::Relaxed))
+}
+
+fn set_connection_state(state: ConnectionState) {
+    CONNECTION_STATE.store(state as u8, Ordering::Relaxed);
+}
+
+/// Note whether the database answered a probe. Only moves between
+/// connected and reconnecting; connecting is left to `connect_db`.
+pub fn note_probe(answered: bool) {
+    let (from, to) = match answered {
+        true => (ConnectionState::Reconnecting, ConnectionState::Connected),
+        false => (ConnectionState::Connected, ConnectionState::Reconnecting),
+    };
+    let _ = CONNECTION_STATE.compare_exchange(from as u8, to as u8, Ordering::Relaxed, Ordering::Relaxed);
+}
+
+/// Fail fast unless `DB` is connected, rather than waiting on a query that
+/// can't be answered
+pub fn ensure_connected() -> Result<(), error::Error> {
+    match connection_state() {
+        ConnectionState::Connected => Ok(()),
+        state => Err(error::Error::Db(format!("database is {}", state.name()))),
+    }
+}
+
+/// Open `DB` on `endpoint`, tracking the attempt in `connection_state`
+async fn connect_global(endpoint: &str) -> Result<(), surrealdb::Error> {
+    let attempt = match connection_state() {
+        ConnectionState::Disconnected | ConnectionState::Connecting => ConnectionState::Connecting,
+        ConnectionState::Connected | ConnectionState::Reconnecting => ConnectionState::Reconnecting,
+    };
+    set_connection_state(attempt);
+    
+    let result = DB.open(endpoint).await;
+    set_connection_state(match result {
+        Ok(()) => ConnectionState::Connected,
+        Err(_) => ConnectionState::Disconnected,
+    });
+    
+    result
+}
+
+pub async fn connect_db() -> Result<(), surrealdb::Error> {
+    connect_global("ws://localhost:8000").await?;
     
     println!("🚀 Connected to SurrealDB!");
     Ok(())
 }
 
-pub struct UserOperations;
+/// Connect the process-wide handle to an empty in-memory database instead,
+/// for tests
+pub async fn connect_memory_db() -> Result<(), surrealdb::Error> {
+    connect_global("mem://").await
+}
 
-impl UserOperations {
-    /// Create a new user
-    pub async fn create_user(
-        username: String,
-        email: String,
-        hashed_password: String,
-        created_via: CreatedVia,
-        bio: Option<String>,
-    ) -> Result<User, error::Error> {
-        // Check if email already exists
-        let existing_email: Option<User> = DB
-            .query("SELECT * FROM users WHERE email = $email")
-            .bind(("email", email.clone()))
+/// Serialize a model as plain JSON so uuids and timestamps are stored as
+/// strings, matching what the models expect when they are read back.
+pub(crate) fn to_content<T: Serialize>(value: &T) -> Result<serde_json::Value, error::Error> {
+    Ok(serde_json::to_value(value)?)
+}
+
+/// Decode the rows of statement `index` into models.
+///
+/// Reads must project `record::id(id) AS id` so the record key comes back as
+/// the bare uuid rather than a `table:⟨key⟩` record id.
+pub(crate) fn take_rows<T: DeserializeOwned>(response: &mut Response, index: usize) -> Result<Vec<T>, error::Error> {
+    let rows: Vec<serde_json::Value> = response.take(index)?;
+    rows.into_iter()
+        .map(|row| serde_json::from_value(row).map_err(error::Error::from))
+        .collect()
+}
+
+/// Decode the first row of statement `index`, if any.
+pub(crate) fn take_row<T: DeserializeOwned>(response: &mut Response, index: usize) -> Result<Option<T>, error::Error> {
+    Ok(take_rows(response, index)?.into_iter().next())
+}
+
+/// How long one branch of a composite read may take, set in milliseconds
+/// with `QUERY_TIMEOUT_MS`
+pub fn query_timeout() -> Duration {
+    static TIMEOUT: LazyLock<Duration> = LazyLock::new(|| {
+        let millis = env::var("QUERY_TIMEOUT_MS")
+            .ok()
+            .and_then(|millis| millis.parse().ok())
+            .unwrap_or(5000);
+        Duration::from_millis(millis)
+    });
+    *TIMEOUT
+}
+
+/// Run one query of a composite read under `query_timeout()`.
+///
+/// Independent queries should be awaited together, so a handler pays for
+/// its slowest query instead of the sum of them all:
+///
+/// ```ignore
+/// let tracks = TrackOperations::new(&db);
+/// let (count, genres) = futures_util::try_join!(
+///     bounded("track count", tracks.count_tracks(id, false)),
+///     bounded("genre breakdown", tracks.genre_breakdown(id, false)),
+/// )?;
+/// ```
+///
+/// Queries that always run together belong in one multi-statement request
+/// instead, which costs a single round trip.
+pub async fn bounded<T>(
+    label: &str,
+    query: impl Future<Output = Result<T, error::Error>>,
+) -> Result<T, error::Error> {
+    actix_web::rt::time::timeout(query_timeout(), query)
+        .await
+        .map_err(|_| error::Error::Db(format!("{} timed out", label)))?
+}
+
+/// The id of the record keyed by `id` in `table`. Every record keyed by a
+/// uuid is addressed through this, so the key is always the hyphenated string.
+pub(crate) fn record_id(table: &str, id: impl Into<Uuid>) -> RecordId {
+    RecordId::from_table_key(table, id.into().to_string())
+}
+
+impl Db {
+    /// Create a record keyed by `id` in `table`
+    pub(crate) async fn create_record<T: Serialize + DeserializeOwned>(
+        &self,
+        table: &'static str,
+        id: impl Into<Uuid>,
+        value: &T,
+    ) -> Result<Option<T>, error::Error> {
+        let mut response = self
+            .query("CREATE $record CONTENT $data RETURN *, record::id(id) AS id")
+            .bind(("record", record_id(table, id)))
+            .bind(("data", to_content(value)?))
+            .await?;
+            
+        take_row(&mut response, 0)
+    }
+    
+    /// Select a record by `id` from `table`
+    pub(crate) async fn select_record<T: DeserializeOwned>(
+        &self,
+        table: &'static str,
+        id: impl Into<Uuid>,
+    ) -> Result<Option<T>, error::Error> {
+        let mut response = self
+            .query("SELECT *, record::id(id) AS id FROM $record")
+            .bind(("record", record_id(table, id)))
+            .await?;
+            
+        take_row(&mut response, 0)
+    }
+    
+    /// Replace the content of an existing record, returning `None` if it doesn't exist
+    pub(crate) async fn update_record<T: Serialize + DeserializeOwned>(
+        &self,
+        table: &'static str,
+        id: impl Into<Uuid>,
+        value: &T,
+    ) -> Result<Option<T>, error::Error> {
+        let id = id.into();
+        let mut response = self
+            .query("UPDATE $record CONTENT $data RETURN *, record::id(id) AS id")
+            .bind(("record", record_id(table, id)))
+            .bind(("data", to_content(value)?))
+            .await?;
+        self.invalidate_cached(table, id);
+            
+        take_row(&mut response, 0)
+    }
+    
+    /// Delete a record by `id` from `table`
+    pub(crate) async fn delete_record(&self, table: &'static str, id: impl Into<Uuid>) -> Result<(), error::Error> {
+        let id = id.into();
+        self.query("DELETE $record")
+            .bind(("record", record_id(table, id)))
             .await?
-            .take(0)?;
+            .check()?;
+        self.invalidate_cached(table, id);
             
-        if existing_email.is_some() {
+        Ok(())
+    }
+}
+
+/// Reads and writes on the users table through one database handle
+#[derive(Clone, Copy)]
+pub struct UserOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> UserOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        UserOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> UserOperations<'static> {
+        UserOperations::new(&DB)
+    }
+    
+    /// Create a new user
+    pub async fn create_user(&self, input: CreateUserInput) -> Result<User, error::Error> {
+        let CreateUserInput { username, email, hashed_password, created_via, bio } = input;
+        
+        if is_taken(self.db, "email", email.clone(), None).await? {
             return Err(error::Error::EmailExists);
         }
         
-        // Check if username already exists
-        let existing_username: Option<User> = DB
-            .query("SELECT * FROM users WHERE username = $username")
-            .bind(("username", username.clone()))
-            .await?
-            .take(0)?;
-            
-        if existing_username.is_some() {
+        if is_taken(self.db, "username", username.clone(), None).await? {
             return Err(error::Error::UsernameExists);
         }
         
-        let now = Utc::now();
-        let user_id = Uuid::new_v4();
+        let now = self.db.now();
+        let user_id = UserId::new();
         
         let user = User {
             id: user_id,
+            username_sort_key: username_sort_key(&username),
             username: username.clone(),
             email: email.clone(),
             hashed_password,
@@ -111,142 +591,121 @@ impl UserOperations {
             profile: None,
             email_verified: false,
             playlists: None,
+            deleted_playlists: Vec::new(),
+            legal_hold: false,
         };
         
-        let created_user: Option<User> = DB
-            .create(("users", user_id.to_string()))
-            .content(user)
-            .await?;
+        let created_user: Option<User> = self.db.create_record("users", user_id, &user).await?;
             
         created_user.ok_or(error::Error::Db("Failed to create user".to_string()))
     }
     
-    /// Get user by ID
-    pub async fn get_user_by_id(user_id: Uuid) -> Result<User, error::Error> {
-        let user: Option<User> = DB
-            .select(("users", user_id.to_string()))
-            .await?;
+    /// Get user by ID, through the user cache
+    pub async fn get_user_by_id(&self, user_id: UserId) -> Result<User, error::Error> {
+        Ok(self.get_cached_user(user_id).await?.user.clone())
+    }
+    
+    /// Get the public view of a user by ID, through the user cache
+    pub async fn get_public_user(&self, user_id: UserId) -> Result<PublicUser, error::Error> {
+        Ok(self.get_cached_user(user_id).await?.public.clone())
+    }
+    
+    async fn get_cached_user(&self, user_id: UserId) -> Result<Arc<CachedUser>, error::Error> {
+        if let Some(cached) = self.db.user_cache().get(user_id) {
+            return Ok(cached);
+        }
+        
+        let user: Option<User> = self.db.select_record("users", user_id).await?;
+        let user = user.ok_or(error::Error::UserNotFound)?;
             
-        user.ok_or(error::Error::UserNotFound)
+        Ok(self.db.user_cache().insert(user))
     }
     
     /// Get user by email
-    pub async fn get_user_by_email(email: String) -> Result<User, error::Error> {
-        let user: Option<User> = DB
-            .query("SELECT * FROM users WHERE email = $email")
+    pub async fn get_user_by_email(&self, email: String) -> Result<User, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users WHERE email = $email")
             .bind(("email", email))
-            .await?
-            .take(0)?;
+            .await?;
+        let user: Option<User> = take_row(&mut response, 0)?;
             
         user.ok_or(error::Error::UserNotFound)
     }
     
     /// Get user by username
-    pub async fn get_user_by_username(username: String) -> Result<User, error::Error> {
-        let user: Option<User> = DB
-            .query("SELECT * FROM users WHERE username = $username")
+    pub async fn get_user_by_username(&self, username: String) -> Result<User, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users WHERE username = $username")
             .bind(("username", username))
-            .await?
-            .take(0)?;
+            .await?;
+        let user: Option<User> = take_row(&mut response, 0)?;
             
         user.ok_or(error::Error::UserNotFound)
     }
     
     /// Update user with modified user object (checks for changes)  
-    pub async fn update_user(user_id: Uuid, mut modified_user: User) -> Result<User, error::Error> {
+    pub async fn update_user(&self, user_id: UserId, mut modified_user: User) -> Result<User, error::Error> {
         // Get current user from database
-        let current_user = Self::get_user_by_id(user_id).await?;
+        let current_user = self.get_user_by_id(user_id).await?;
         
         // Ensure the user ID matches
         modified_user.id = user_id;
         
         // Check for conflicts if username has changed
-        if modified_user.username != current_user.username {
-            let existing: Option<User> = DB
-                .query("SELECT * FROM users WHERE username = $username AND id != $user_id")
-                .bind(("username", modified_user.username.clone()))
-                .bind(("user_id", user_id.to_string()))
-                .await?
-                .take(0)?;
-                
-            if existing.is_some() {
-                return Err(error::Error::UsernameExists);
-            }
+        if modified_user.username != current_user.username
+            && is_taken(self.db, "username", modified_user.username.clone(), Some(user_id)).await?
+        {
+            return Err(error::Error::UsernameExists);
         }
         
         // Check for conflicts if email has changed
-        if modified_user.email != current_user.email {
-            let existing: Option<User> = DB
-                .query("SELECT * FROM users WHERE email = $email AND id != $user_id")
-                .bind(("email", modified_user.email.clone()))
-                .bind(("user_id", user_id.to_string()))
-                .await?
-                .take(0)?;
-                
-            if existing.is_some() {
-                return Err(error::Error::EmailExists);
-            }
+        if modified_user.email != current_user.email
+            && is_taken(self.db, "email", modified_user.email.clone(), Some(user_id)).await?
+        {
+            return Err(error::Error::EmailExists);
         }
         
         // Preserve certain fields that shouldn't be changed through this method
+        modified_user.username_sort_key = username_sort_key(&modified_user.username);
         modified_user.created_at = current_user.created_at;
         modified_user.hashed_password = current_user.hashed_password; // Password changes should use separate method
         modified_user.email_verified = current_user.email_verified; // Email verification should use separate method
         
         // Update the timestamp
-        modified_user.updated_at = Utc::now();
+        modified_user.updated_at = self.db.now();
         
-        let updated_user: Option<User> = DB
-            .update(("users", user_id.to_string()))
-            .content(modified_user)
-            .await?;
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &modified_user).await?;
             
         updated_user.ok_or(error::Error::Db("Failed to update user".to_string()))
     }
     
     /// Update user basic information with individual fields
     pub async fn update_user_fields(
-        user_id: Uuid,
+        &self,
+        user_id: UserId,
         username: Option<String>,
         email: Option<String>,
         bio: Option<String>,
     ) -> Result<User, error::Error> {
         // Check if user exists
-        let mut user = Self::get_user_by_id(user_id).await?;
+        let mut user = self.get_user_by_id(user_id).await?;
         
         // Check for conflicts if updating username or email
         if let Some(ref new_username) = username {
-            if new_username != &user.username {
-                let existing: Option<User> = DB
-                    .query("SELECT * FROM users WHERE username = $username AND id != $user_id")
-                    .bind(("username", new_username.clone()))
-                    .bind(("user_id", user_id.to_string()))
-                    .await?
-                    .take(0)?;
-                    
-                if existing.is_some() {
-                    return Err(error::Error::UsernameExists);
-                }
+            if new_username != &user.username && is_taken(self.db, "username", new_username.clone(), Some(user_id)).await? {
+                return Err(error::Error::UsernameExists);
             }
         }
         
         if let Some(ref new_email) = email {
-            if new_email != &user.email {
-                let existing: Option<User> = DB
-                    .query("SELECT * FROM users WHERE email = $email AND id != $user_id")
-                    .bind(("email", new_email.clone()))
-                    .bind(("user_id", user_id.to_string()))
-                    .await?
-                    .take(0)?;
-                    
-                if existing.is_some() {
-                    return Err(error::Error::EmailExists);
-                }
+            if new_email != &user.email && is_taken(self.db, "email", new_email.clone(), Some(user_id)).await? {
+                return Err(error::Error::EmailExists);
             }
         }
         
         // Update fields
         if let Some(new_username) = username {
+            user.username_sort_key = username_sort_key(&new_username);
             user.username = new_username;
         }
         if let Some(new_email) = email {
@@ -256,75 +715,113 @@ impl UserOperations {
             user.bio = bio;
         }
         
-        user.updated_at = Utc::now();
+        user.updated_at = self.db.now();
         
-        let updated_user: Option<User> = DB
-            .update(("users", user_id.to_string()))
-            .content(user)
-            .await?;
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
             
         updated_user.ok_or(error::Error::Db("Failed to update user".to_string()))
     }
     
     /// Update user password
-    pub async fn update_password(user_id: Uuid, new_hashed_password: String) -> Result<User, error::Error> {
-        let mut user = Self::get_user_by_id(user_id).await?;
+    pub async fn update_password(&self, user_id: UserId, new_hashed_password: String) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
         user.hashed_password = new_hashed_password;
-        user.updated_at = Utc::now();
+        user.updated_at = self.db.now();
         
-        let updated_user: Option<User> = DB
-            .update(("users", user_id.to_string()))
-            .content(user)
-            .await?;
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
             
         updated_user.ok_or(error::Error::Db("Failed to update password".to_string()))
     }
     
     /// Verify user email
-    pub async fn verify_email(user_id: Uuid) -> Result<User, error::Error> {
-        let mut user = Self::get_user_by_id(user_id).await?;
+    pub async fn verify_email(&self, user_id: UserId) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
         user.email_verified = true;
-        user.updated_at = Utc::now();
+        user.updated_at = self.db.now();
         
-        let updated_user: Option<User> = DB
-            .update(("users", user_id.to_string()))
-            .content(user)
-            .await?;
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
             
         updated_user.ok_or(error::Error::Db("Failed to verify email".to_string()))
     }
     
     /// Create or update user profile
-    pub async fn update_profile(user_id: Uuid, profile: UserProfile) -> Result<User, error::Error> {
-        let mut user = Self::get_user_by_id(user_id).await?;
+    pub async fn update_profile(&self, user_id: UserId, profile: UserProfile) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
+        
+        // Verification only changes through an admin's review, status through
+        // banning and deleting, and pins and track order through their own
+        // operations, which check the tracks
+        let mut profile = profile;
+        if let Some(current) = user.profile.as_ref() {
+            profile.status = current.status;
+            profile.banned_until = current.banned_until;
+            profile.pinned_track_ids = current.pinned_track_ids.clone();
+            profile.track_order = current.track_order;
+            profile.manual_track_order = current.manual_track_order.clone();
+        }
+        profile.is_verified = user.profile.as_ref().is_some_and(|p| p.is_verified);
+        
         user.profile = Some(profile);
-        user.updated_at = Utc::now();
+        user.updated_at = self.db.now();
         
-        let updated_user: Option<User> = DB
-            .update(("users", user_id.to_string()))
-            .content(user)
-            .await?;
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
             
         updated_user.ok_or(error::Error::Db("Failed to update profile".to_string()))
     }
     
-    /// Get all users with pagination
-    pub async fn get_users(limit: Option<u32>, offset: Option<u32>) -> Result<Vec<User>, error::Error> {
+    /// Point the profile picture or banner at a new URL, returning the old one
+    pub async fn set_profile_image(
+        &self,
+        user_id: UserId,
+        kind: ProfileImage,
+        url: String,
+    ) -> Result<Option<String>, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
+        let profile = user.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        
+        let slot = match kind {
+            ProfileImage::Picture => &mut profile.profile_picture,
+            ProfileImage::Banner => &mut profile.profile_banner,
+        };
+        let previous = slot.replace(url);
+        user.updated_at = self.db.now();
+        
+        let _: Option<User> = self.db.update_record("users", user_id, &user).await?;
+        
+        Ok(previous)
+    }
+    
+    /// Get all users with pagination, in `sort` order
+    pub async fn get_users(&self, limit: Option<u32>, offset: Option<u32>, sort: UserSort) -> Result<Vec<User>, error::Error> {
         let limit = limit.unwrap_or(50);
         let offset = offset.unwrap_or(0);
         
-        let users: Vec<User> = DB
-            .query("SELECT * FROM users ORDER BY created_at DESC LIMIT $limit START $offset")
+        let mut response = self.db
+            .query(format!("SELECT *, record::id(id) AS id FROM users ORDER BY {} LIMIT $limit START $offset", sort.order_by()))
             .bind(("limit", limit))
             .bind(("offset", offset))
-            .await?
-            .take(0)?;
+            .await?;
+        let users: Vec<User> = take_rows(&mut response, 0)?;
             
         Ok(users)
     }
     
-    /// Search users by username or profile name
+    /// Get users oldest first, for exports. Users created while an export
+    /// pages through land after the pages already read instead of shifting them.
+    pub async fn get_users_oldest_first(&self, limit: u32, offset: u32) -> Result<Vec<User>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users ORDER BY created_at ASC LIMIT $limit START $offset")
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// Search users by username or profile name. An exact username match
+    /// comes first, then verified accounts, each newest first.
     pub async fn search_users(
+        &self,
         query: String,
         limit: Option<u32>,
         offset: Option<u32>,
@@ -332,71 +829,130 @@ impl UserOperations {
         let limit = limit.unwrap_or(20);
         let offset = offset.unwrap_or(0);
         
-        let users: Vec<User> = DB
+        let mut response = self.db
             .query(
-                "SELECT * FROM users WHERE 
+                "SELECT *, record::id(id) AS id,
+                (IF string::lowercase(username) = string::lowercase($query) THEN 2 ELSE 0 END) +
+                (IF profile.is_verified = true THEN 1 ELSE 0 END) AS rank
+                FROM users WHERE 
                 string::lowercase(username) CONTAINS string::lowercase($query) OR 
                 string::lowercase(profile.profile_name) CONTAINS string::lowercase($query)
-                ORDER BY created_at DESC 
+                ORDER BY rank DESC, created_at DESC 
                 LIMIT $limit START $offset"
             )
             .bind(("query", query))
             .bind(("limit", limit))
             .bind(("offset", offset))
-            .await?
-            .take(0)?;
+            .await?;
+        let users: Vec<User> = take_rows(&mut response, 0)?;
             
         Ok(users)
     }
     
-    /// Delete user (soft delete)
-    pub async fn delete_user(user_id: Uuid) -> Result<(), error::Error> {
+    /// Count the users `search_users` would match across all pages
+    pub async fn count_search_users(&self, query: String) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT count() FROM users WHERE 
+                string::lowercase(username) CONTAINS string::lowercase($query) OR 
+                string::lowercase(profile.profile_name) CONTAINS string::lowercase($query)
+                GROUP ALL"
+            )
+            .bind(("query", query))
+            .await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Count every user
+    pub async fn count_users(&self) -> Result<u64, error::Error> {
+        let mut response = self.db.query("SELECT count() FROM users GROUP ALL").await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Delete user (soft delete). Under `DeletedHandles::Release` the username
+    /// and email get a tombstone suffix so they can be signed up with again.
+    pub async fn delete_user(&self, user_id: UserId) -> Result<(), error::Error> {
         // First check if user exists
-        let mut user = Self::get_user_by_id(user_id).await?;
+        let mut user = self.get_user_by_id(user_id).await?;
         
         // Update profile to mark as deleted if profile exists
         if let Some(ref mut profile) = user.profile {
-            profile.is_deleted = true;
+            profile.status = AccountStatus::Deleted;
         }
         
-        user.updated_at = Utc::now();
+        if config::deleted_handles() == DeletedHandles::Release {
+            user.username = tombstone(&user.username, user_id);
+            user.username_sort_key = username_sort_key(&user.username);
+            user.email = tombstone(&user.email, user_id);
+        }
         
-        let _: Option<User> = DB
-            .update(("users", user_id.to_string()))
-            .content(user)
-            .await?;
+        user.updated_at = self.db.now();
+        
+        let _: Option<User> = self.db.update_record("users", user_id, &user).await?;
             
         Ok(())
     }
     
+    /// Fill in username sort keys that are missing or were computed
+    /// differently, returning how many users were checked and corrected
+    pub async fn repair_username_sort_keys(&self) -> Result<(usize, usize), error::Error> {
+        let mut response = self.db
+            .query("SELECT record::id(id) AS id, username, username_sort_key ?? '' AS stored FROM users")
+            .await?;
+        let rows: Vec<SortKeyRow> = take_rows(&mut response, 0)?;
+        let mut corrected = 0;
+        
+        for row in &rows {
+            let actual = username_sort_key(&row.username);
+            if row.stored != actual {
+                tracing::warn!("Reconciling username_sort_key for user {}: {:?} -> {:?}", row.id, row.stored, actual);
+                self.db.query("UPDATE $record SET username_sort_key = $key")
+                    .bind(("record", record_id("users", row.id)))
+                    .bind(("key", actual))
+                    .await?
+                    .check()?;
+                self.db.invalidate_cached("users", row.id);
+                corrected += 1;
+            }
+        }
+        
+        Ok((rows.len(), corrected))
+    }
+    
     /// Hard delete user (permanently remove from database)
-    pub async fn hard_delete_user(user_id: Uuid) -> Result<(), error::Error> {
+    pub async fn hard_delete_user(&self, user_id: UserId) -> Result<(), error::Error> {
         // Check if user exists first
-        let _user = Self::get_user_by_id(user_id).await?;
+        let _user = self.get_user_by_id(user_id).await?;
         
-        let _: Option<User> = DB
-            .delete(("users", user_id.to_string()))
-            .await?;
+        self.db.delete_record("users", user_id).await?;
             
         Ok(())
     }
     
+    /// Soft-deleted users that may be erased, i.e. not under legal hold
+    pub async fn get_purgeable_users(&self) -> Result<Vec<User>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users WHERE profile.is_deleted = true AND legal_hold = false ORDER BY updated_at ASC")
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
     /// Get user statistics/counts
-    pub async fn get_user_stats() -> Result<UserStats, error::Error> {
-        let total_users: Option<i64> = DB
+    pub async fn get_user_stats(&self) -> Result<UserStats, error::Error> {
+        // One request for all three counts
+        let mut response = self.db
             .query("SELECT count() FROM users GROUP ALL")
-            .await?
-            .take((0, "count"))?;
-            
-        let verified_users: Option<i64> = DB
             .query("SELECT count() FROM users WHERE email_verified = true GROUP ALL")
-            .await?
-            .take((0, "count"))?;
-            
-        let active_users: Option<i64> = DB
             .query("SELECT count() FROM users WHERE profile.is_active = true GROUP ALL")
-            .await?
-            .take((0, "count"))?;
+            .await?;
+        let total_users: Option<i64> = response.take((0, "count"))?;
+        let verified_users: Option<i64> = response.take((1, "count"))?;
+        let active_users: Option<i64> = response.take((2, "count"))?;
             
         Ok(UserStats {
             total_users: total_users.unwrap_or(0) as u64,
@@ -405,68 +961,88 @@ impl UserOperations {
         })
     }
     
+    /// Count signups per `created_via` between `from` and `to`, inclusive,
+    /// most common first
+    pub async fn signups_by_source(&self, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<SignupCount>, error::Error> {
+        if from > to {
+            return Err(error::Error::Validation("from must not be after to".to_string()));
+        }
+        
+        let mut response = self.db
+            .query(
+                "SELECT created_via, count() AS count FROM users
+                WHERE <datetime> created_at >= <datetime> $from AND <datetime> created_at <= <datetime> $to
+                GROUP BY created_via",
+            )
+            .bind(("from", from.to_rfc3339()))
+            .bind(("to", to.to_rfc3339()))
+            .await?;
+        let mut counts: Vec<SignupCount> = take_rows(&mut response, 0)?;
+        
+        // The grouped totals don't come back in ORDER BY order, so they're
+        // sorted here
+        counts.sort_by_key(|count| std::cmp::Reverse(count.count));
+        
+        Ok(counts)
+    }
+    
     /// Check if username is available
-    pub async fn is_username_available(username: String) -> Result<bool, error::Error> {
-        let existing: Option<User> = DB
-            .query("SELECT * FROM users WHERE username = $username")
-            .bind(("username", username))
-            .await?
-            .take(0)?;
-            
-        Ok(existing.is_none())
+    pub async fn is_username_available(&self, username: String) -> Result<bool, error::Error> {
+        Ok(!is_taken(self.db, "username", username, None).await?)
     }
     
     /// Check if email is available
-    pub async fn is_email_available(email: String) -> Result<bool, error::Error> {
-        let existing: Option<User> = DB
-            .query("SELECT * FROM users WHERE email = $email")
-            .bind(("email", email))
-            .await?
-            .take(0)?;
-            
-        Ok(existing.is_none())
+    pub async fn is_email_available(&self, email: String) -> Result<bool, error::Error> {
+        Ok(!is_taken(self.db, "email", email, None).await?)
     }
     
     /// Ban user
-    pub async fn ban_user(user_id: Uuid) -> Result<User, error::Error> {
-        let mut user = Self::get_user_by_id(user_id).await?;
+    pub async fn ban_user(&self, user_id: UserId) -> Result<User, error::Error> {
+        self.ban(user_id, None).await
+    }
+    
+    /// Ban user until `duration` from now, after which they're active again
+    pub async fn ban_user_for(&self, user_id: UserId, duration: chrono::Duration) -> Result<User, error::Error> {
+        self.ban(user_id, Some(self.db.now() + duration)).await
+    }
+    
+    async fn ban(&self, user_id: UserId, until: Option<DateTime<Utc>>) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
         
-        if let Some(ref mut profile) = user.profile {
-            profile.is_banned = true;
+        // A deleted account stays deleted
+        if let Some(profile) = user.profile.as_mut().filter(|p| !p.is_deleted()) {
+            profile.status = AccountStatus::Banned;
+            profile.banned_until = until;
         }
         
-        user.updated_at = Utc::now();
+        user.updated_at = self.db.now();
         
-        let updated_user: Option<User> = DB
-            .update(("users", user_id.to_string()))
-            .content(user)
-            .await?;
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
             
         updated_user.ok_or(error::Error::Db("Failed to ban user".to_string()))
     }
     
     /// Unban user
-    pub async fn unban_user(user_id: Uuid) -> Result<User, error::Error> {
-        let mut user = Self::get_user_by_id(user_id).await?;
+    pub async fn unban_user(&self, user_id: UserId) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
         
-        if let Some(ref mut profile) = user.profile {
-            profile.is_banned = false;
+        // Also tidies up a temporary ban that already lifted
+        if let Some(profile) = user.profile.as_mut().filter(|p| p.status == AccountStatus::Banned) {
+            profile.status = AccountStatus::Active;
+            profile.banned_until = None;
         }
         
-        user.updated_at = Utc::now();
+        user.updated_at = self.db.now();
         
-        let updated_user: Option<User> = DB
-            .update(("users", user_id.to_string()))
-            .content(user)
-            .await?;
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
             
         updated_user.ok_or(error::Error::Db("Failed to unban user".to_string()))
     }
     
     /// Update user last login
-    pub async fn update_last_login(user_id: Uuid) -> Result<User, error::Error> {
-        let mut user = Self::get_user_by_id(user_id).await?;
-        let now = Utc::now();
+    pub async fn update_last_login(&self, user_id: UserId) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
+        let now = self.db.now();
         
         if let Some(ref mut profile) = user.profile {
             profile.last_login = Some(now);
@@ -475,13 +1051,396 @@ impl UserOperations {
         
         user.updated_at = now;
         
-        let updated_user: Option<User> = DB
-            .update(("users", user_id.to_string()))
-            .content(user)
-            .await?;
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
             
         updated_user.ok_or(error::Error::Db("Failed to update last login".to_string()))
     }
+    
+    /// Place or lift a legal hold, which blocks account erasure
+    pub async fn set_legal_hold(&self, user_id: UserId, legal_hold: bool) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
+        user.legal_hold = legal_hold;
+        user.updated_at = self.db.now();
+        
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
+            
+        updated_user.ok_or(error::Error::Db("Failed to update legal hold".to_string()))
+    }
+    
+    /// Set a profile's location. Without an explicit `display` string one is
+    /// derived from the structured location.
+    pub async fn set_location(
+        &self,
+        user_id: UserId,
+        location: Option<Location>,
+        display: Option<String>,
+    ) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
+        let profile = user.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        
+        profile.location = display.or_else(|| location.as_ref().map(Location::display));
+        profile.structured_location = location;
+        user.updated_at = self.db.now();
+        
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
+            
+        updated_user.ok_or(error::Error::Db("Failed to update location".to_string()))
+    }
+    
+    /// Feature one of a user's own public tracks first on their profile. At
+    /// most `MAX_PINNED_TRACKS` can be pinned; pinning again is a no-op.
+    pub async fn pin_track(&self, user_id: UserId, track_id: TrackId) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
+        let profile = user.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        
+        let track = profile.uploads.as_ref()
+            .and_then(|uploads| uploads.iter().find(|t| t.id == track_id && !t.is_deleted))
+            .ok_or(error::Error::TrackNotFound)?;
+        if !track.is_public {
+            return Err(error::Error::Validation("only public tracks can be pinned".to_string()));
+        }
+        if profile.pinned_track_ids.contains(&track_id) {
+            return Ok(user);
+        }
+        // Evicting an older pin silently would surprise whoever chose it
+        if profile.pinned_track_ids.len() >= MAX_PINNED_TRACKS {
+            return Err(error::Error::Conflict(format!(
+                "at most {} tracks can be pinned; unpin one first",
+                MAX_PINNED_TRACKS
+            )));
+        }
+        
+        profile.pinned_track_ids.push(track_id);
+        user.updated_at = self.db.now();
+        
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
+            
+        updated_user.ok_or(error::Error::Db("Failed to pin track".to_string()))
+    }
+    
+    /// Take a track off a user's pins. Unpinning a track that isn't pinned
+    /// is a no-op.
+    pub async fn unpin_track(&self, user_id: UserId, track_id: TrackId) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
+        let profile = user.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        
+        if !profile.pinned_track_ids.contains(&track_id) {
+            return Ok(user);
+        }
+        profile.pinned_track_ids.retain(|id| *id != track_id);
+        user.updated_at = self.db.now();
+        
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
+            
+        updated_user.ok_or(error::Error::Db("Failed to unpin track".to_string()))
+    }
+    
+    /// Choose how a user's uploads are listed after the pinned ones. A
+    /// manual arrangement, when given, may only name the user's own tracks,
+    /// each once; tracks left out of it follow, newest first.
+    pub async fn set_track_order(
+        &self,
+        user_id: UserId,
+        order: ProfileTrackOrder,
+        arrangement: Option<Vec<TrackId>>,
+    ) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
+        let profile = user.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        
+        if let Some(arrangement) = arrangement {
+            let uploads = profile.uploads.as_deref().unwrap_or_default();
+            let mut seen = HashSet::new();
+            for id in &arrangement {
+                if !seen.insert(*id) {
+                    return Err(error::Error::Validation(format!("track {} is listed twice", id)));
+                }
+                if !uploads.iter().any(|t| t.id == *id && !t.is_deleted) {
+                    return Err(error::Error::Validation(format!("track {} is not one of your tracks", id)));
+                }
+            }
+            profile.manual_track_order = arrangement;
+        }
+        profile.track_order = order;
+        user.updated_at = self.db.now();
+        
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
+            
+        updated_user.ok_or(error::Error::Db("Failed to update track order".to_string()))
+    }
+    
+    /// Choose whether the caller's profile shows what they're listening to
+    pub async fn set_share_now_playing(&self, user_id: UserId, share: bool) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
+        let profile = user.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        profile.share_now_playing = share;
+        user.updated_at = self.db.now();
+        
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
+            
+        updated_user.ok_or(error::Error::Db("Failed to update now playing setting".to_string()))
+    }
+    
+    /// Choose which kinds of notification the user gets pushed
+    pub async fn set_push_kinds(&self, user_id: UserId, kinds: Vec<NotificationKind>) -> Result<User, error::Error> {
+        let mut user = self.get_user_by_id(user_id).await?;
+        let profile = user.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        profile.push_kinds = Some(kinds);
+        user.updated_at = self.db.now();
+        
+        let updated_user: Option<User> = self.db.update_record("users", user_id, &user).await?;
+        
+        updated_user.ok_or(error::Error::Db("Failed to update push preferences".to_string()))
+    }
+    
+    /// Count a view of a user's profile. The increment happens in the
+    /// database so concurrent views aren't lost.
+    pub async fn record_profile_view(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("UPDATE $user SET profile.profile_views += 1 WHERE profile != NONE")
+            .bind(("user", record_id("users", user_id)))
+            .await?
+            .check()?;
+        self.db.invalidate_cached("users", user_id);
+        
+        Ok(())
+    }
+    
+    /// Load two distinct users that both have a profile
+    async fn get_user_pair(&self, user_id: UserId, other_id: UserId) -> Result<(User, User), error::Error> {
+        if user_id == other_id {
+            return Err(error::Error::Validation("cannot target yourself".to_string()));
+        }
+        
+        let user = self.get_user_by_id(user_id).await?;
+        let other = self.get_user_by_id(other_id).await?;
+        
+        if user.profile.is_none() || other.profile.is_none() {
+            return Err(error::Error::ProfileNotFound);
+        }
+        
+        Ok((user, other))
+    }
+    
+    /// Make `follower_id` follow `followee_id`, updating both users atomically
+    pub async fn follow_user(&self, follower_id: UserId, followee_id: UserId) -> Result<(), error::Error> {
+        self.get_user_pair(follower_id, followee_id).await?;
+        
+        // Blocks are checked and the lists changed as they stand when the
+        // transaction runs, so a block or another follow landing meanwhile
+        // isn't missed or overwritten
+        let now = self.db.now();
+        let params = || pair_params(follower_id, followee_id, now);
+        self.db.transaction(|tx| {
+            tx.forbid_if(
+                "((SELECT VALUE profile.blocked_users ?? [] FROM ONLY type::thing('users', $a)) CONTAINS $b
+                OR (SELECT VALUE profile.blocked_users ?? [] FROM ONLY type::thing('users', $b)) CONTAINS $a)",
+                params(),
+            );
+            tx.set("users", follower_id, "profile.following = array::union(profile.following ?? [], [$b]), updated_at = $now", params());
+            tx.set("users", followee_id, "profile.followers = array::union(profile.followers ?? [], [$a]), updated_at = $now", params());
+            Ok(())
+        }).await?;
+        
+        crate::webhook::dispatch(
+            self.db,
+            WebhookEvent::UserFollowed,
+            Some(followee_id),
+            serde_json::json!({ "follower_id": follower_id, "followee_id": followee_id }),
+        );
+        
+        Ok(())
+    }
+    
+    /// Undo a follow, updating both users atomically
+    pub async fn unfollow_user(&self, follower_id: UserId, followee_id: UserId) -> Result<(), error::Error> {
+        self.get_user_pair(follower_id, followee_id).await?;
+        
+        let now = self.db.now();
+        let params = || pair_params(follower_id, followee_id, now);
+        self.db.transaction(|tx| {
+            tx.set("users", follower_id, "profile.following = array::complement(profile.following ?? [], [$b]), updated_at = $now", params());
+            tx.set("users", followee_id, "profile.followers = array::complement(profile.followers ?? [], [$a]), updated_at = $now", params());
+            Ok(())
+        }).await
+    }
+    
+    /// Block a user, which also removes any follow or friendship between the
+    /// two in either direction
+    pub async fn block_user(&self, blocker_id: UserId, blocked_id: UserId) -> Result<(), error::Error> {
+        self.get_user_pair(blocker_id, blocked_id).await?;
+        
+        let now = self.db.now();
+        let params = || pair_params(blocker_id, blocked_id, now);
+        self.db.transaction(|tx| {
+            tx.set(
+                "users",
+                blocker_id,
+                "profile.blocked_users = array::union(profile.blocked_users ?? [], [$b]),
+                profile.following = array::complement(profile.following ?? [], [$b]),
+                profile.followers = array::complement(profile.followers ?? [], [$b]),
+                profile.friends_list = array::complement(profile.friends_list ?? [], [$b]),
+                updated_at = $now",
+                params(),
+            );
+            tx.set(
+                "users",
+                blocked_id,
+                "profile.following = array::complement(profile.following ?? [], [$a]),
+                profile.followers = array::complement(profile.followers ?? [], [$a]),
+                profile.friends_list = array::complement(profile.friends_list ?? [], [$a]),
+                updated_at = $now",
+                params(),
+            );
+            Ok(())
+        }).await
+    }
+    
+    /// Lift a block
+    pub async fn unblock_user(&self, blocker_id: UserId, blocked_id: UserId) -> Result<User, error::Error> {
+        let mut blocker = self.get_user_by_id(blocker_id).await?;
+        let profile = blocker.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        remove_id(&mut profile.blocked_users, blocked_id);
+        blocker.updated_at = self.db.now();
+        
+        let updated_user: Option<User> = self.db.update_record("users", blocker_id, &blocker).await?;
+            
+        updated_user.ok_or(error::Error::Db("Failed to unblock user".to_string()))
+    }
+    
+    /// Suggest people followed by the people a user follows, ranked by how
+    /// many of them do. Existing follows, blocked users in either direction
+    /// and unavailable accounts are left out.
+    pub async fn suggested_follows(&self, user_id: UserId, limit: u32) -> Result<Vec<FollowSuggestion>, error::Error> {
+        let user = self.get_user_by_id(user_id).await?;
+        let profile = user.profile.as_ref().ok_or(error::Error::ProfileNotFound)?;
+        let following = profile.following.clone().unwrap_or_default();
+        let blocked = profile.blocked_users.clone().unwrap_or_default();
+        
+        if following.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        // Only the follow lists are needed, not whole users
+        let mut response = self.db
+            .query("SELECT VALUE profile.following ?? [] FROM users WHERE record::id(id) IN $ids")
+            .bind(("ids", following.iter().map(ToString::to_string).collect::<Vec<_>>()))
+            .await?;
+        let followed: Vec<Vec<UserId>> = take_rows(&mut response, 0)?;
+        
+        let mut mutual_counts: HashMap<UserId, u64> = HashMap::new();
+        for their_following in &followed {
+            for candidate in their_following {
+                if *candidate != user_id && !following.contains(candidate) && !blocked.contains(candidate) {
+                    *mutual_counts.entry(*candidate).or_default() += 1;
+                }
+            }
+        }
+        
+        let candidate_ids: Vec<UserId> = mutual_counts.keys().copied().collect();
+        let mut suggestions: Vec<FollowSuggestion> = self.get_users_by_ids(&candidate_ids)
+            .await?
+            .into_iter()
+            .filter(|candidate| candidate.profile.as_ref().is_some_and(|p| {
+                !p.is_deleted()
+                    && !p.is_banned()
+                    && !p.blocked_users.as_ref().is_some_and(|ids| ids.contains(&user_id))
+            }))
+            .map(|candidate| FollowSuggestion {
+                mutual_count: mutual_counts[&candidate.id],
+                user: PublicUser::from(candidate),
+            })
+            .collect();
+        
+        suggestions.sort_by(|a, b| {
+            b.mutual_count.cmp(&a.mutual_count).then_with(|| username_sort_key(&a.user.username).cmp(&username_sort_key(&b.user.username)))
+        });
+        suggestions.truncate(limit as usize);
+        
+        Ok(suggestions)
+    }
+    
+    /// One page of a user's followers, newest accounts first
+    pub async fn list_followers(
+        &self,
+        user_id: UserId,
+        after: Option<Cursor>,
+        limit: u32,
+    ) -> Result<(Vec<User>, Option<Cursor>), error::Error> {
+        let user = self.get_user_by_id(user_id).await?;
+        let follower_ids = user.profile.and_then(|p| p.followers).unwrap_or_default();
+        
+        let followers: Vec<User> = self.get_users_by_ids(&follower_ids)
+            .await?
+            .into_iter()
+            .filter(|follower| follower.profile.as_ref().is_some_and(|p| !p.is_deleted() && !p.is_banned()))
+            .collect();
+            
+        Ok(cursor_page(followers, |follower| (follower.created_at, follower.id), after, limit))
+    }
+    
+    /// Load several users at once. Missing ids are skipped.
+    pub async fn get_users_by_ids(&self, user_ids: &[UserId]) -> Result<Vec<User>, error::Error> {
+        if user_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let ids: Vec<String> = user_ids.iter().map(ToString::to_string).collect();
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users WHERE record::id(id) IN $ids")
+            .bind(("ids", ids))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+}
+
+/// Whether a user other than `except` already has `value` in `field`. Only the
+/// record id is read back, so a malformed document can't fail the check.
+async fn is_taken(db: &Db, field: &'static str, value: String, except: Option<UserId>) -> Result<bool, error::Error> {
+    let mut response = db
+        .query("SELECT VALUE record::id(id) FROM users WHERE type::field($field) = $value AND record::id(id) != $except LIMIT 1")
+        .bind(("field", field))
+        .bind(("value", value))
+        .bind(("except", except.map(|id| id.to_string()).unwrap_or_default()))
+        .await?;
+    let existing: Option<String> = response.take(0)?;
+    
+    Ok(existing.is_some())
+}
+
+/// Suffix a released handle with the owner's id. `~` is never valid in a
+/// username, so the result can't collide with a real one.
+fn tombstone(handle: &str, user_id: UserId) -> String {
+    format!("{}~deleted~{}", handle, user_id.as_uuid().simple())
+}
+
+/// `$a` and `$b` for a pair of users changed together, and `$now`
+fn pair_params(a: UserId, b: UserId, now: DateTime<Utc>) -> Vec<(&'static str, serde_json::Value)> {
+    vec![
+        ("a", serde_json::json!(a)),
+        ("b", serde_json::json!(b)),
+        ("now", serde_json::json!(now)),
+    ]
+}
+
+fn add_id(ids: &mut Option<Vec<UserId>>, id: UserId) {
+    let ids = ids.get_or_insert_with(Vec::new);
+    if !ids.contains(&id) {
+        ids.push(id);
+    }
+}
+
+fn remove_id(ids: &mut Option<Vec<UserId>>, id: UserId) {
+    if let Some(ids) = ids.as_mut() {
+        ids.retain(|existing| *existing != id);
+    }
+}
+
+/// A user's stored username sort key, for `repair_username_sort_keys`
+#[derive(Debug, serde::Deserialize)]
+struct SortKeyRow {
+    id: UserId,
+    username: String,
+    stored: String,
 }
 
 #[derive(Debug, serde::Serialize)]
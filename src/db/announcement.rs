@@ -0,0 +1,108 @@
+use uuid::Uuid;
+use crate::types::announcement::{Announcement, AnnouncementDismissal, Audience};
+use crate::types::id::UserId;
+use super::{error, take_rows, to_content, record_id, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct AnnouncementOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> AnnouncementOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        AnnouncementOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> AnnouncementOperations<'static> {
+        AnnouncementOperations::new(&DB)
+    }
+    
+    /// Store a new announcement
+    pub async fn create_announcement(&self, announcement: Announcement) -> Result<Announcement, error::Error> {
+        let created: Option<Announcement> = self.db.create_record("announcements", announcement.id, &announcement).await?;
+        
+        created.ok_or(error::Error::Db("Failed to create announcement".to_string()))
+    }
+    
+    /// Get announcement by ID
+    pub async fn get_announcement(&self, announcement_id: Uuid) -> Result<Announcement, error::Error> {
+        let announcement: Option<Announcement> = self.db.select_record("announcements", announcement_id).await?;
+        
+        announcement.ok_or(error::Error::NotFound)
+    }
+    
+    /// Every announcement, scheduled and expired ones included, newest first
+    pub async fn list_announcements(&self) -> Result<Vec<Announcement>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM announcements ORDER BY starts_at DESC")
+            .await?;
+        
+        take_rows(&mut response, 0)
+    }
+    
+    /// Persist changes to an announcement
+    pub async fn save_announcement(&self, announcement: Announcement) -> Result<Announcement, error::Error> {
+        let saved: Option<Announcement> = self.db.update_record("announcements", announcement.id, &announcement).await?;
+        
+        saved.ok_or(error::Error::NotFound)
+    }
+    
+    /// Delete an announcement along with its dismissals
+    pub async fn delete_announcement(&self, announcement_id: Uuid) -> Result<(), error::Error> {
+        self.db.query("DELETE $announcement")
+            .query("DELETE announcement_dismissals WHERE announcement_id = $announcement_id")
+            .bind(("announcement", record_id("announcements", announcement_id)))
+            .bind(("announcement_id", announcement_id.to_string()))
+            .await?
+            .check()?;
+        
+        Ok(())
+    }
+    
+    /// Announcements inside their time window for any of `audiences`, minus
+    /// those `user_id` dismissed. The window is checked here, at read time,
+    /// so scheduled announcements need no job to switch them on or off.
+    pub async fn get_active(&self, audiences: &[Audience], user_id: Option<UserId>) -> Result<Vec<Announcement>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT *, record::id(id) AS id FROM announcements WHERE
+                <datetime> starts_at <= <datetime> $now AND
+                (ends_at = NONE OR ends_at = NULL OR <datetime> ends_at > <datetime> $now) AND
+                audience IN $audiences AND
+                record::id(id) NOTINSIDE (SELECT VALUE announcement_id FROM announcement_dismissals WHERE user_id = $user_id)
+                ORDER BY starts_at DESC"
+            )
+            .bind(("audiences", audiences.iter().map(Audience::as_str).collect::<Vec<_>>()))
+            .bind(("user_id", user_id.map(|id| id.to_string()).unwrap_or_default()))
+            .bind(("now", self.db.now().to_rfc3339()))
+            .await?;
+        
+        take_rows(&mut response, 0)
+    }
+    
+    /// Stop showing an announcement to a user. Dismissing twice is a no-op.
+    pub async fn dismiss(&self, announcement_id: Uuid, user_id: UserId) -> Result<(), error::Error> {
+        self.get_announcement(announcement_id).await?;
+        
+        let dismissal = AnnouncementDismissal { announcement_id, user_id, dismissed_at: self.db.now() };
+        self.db.query("UPSERT type::thing('announcement_dismissals', [$announcement_id, $user_id]) CONTENT $data")
+            .bind(("announcement_id", announcement_id.to_string()))
+            .bind(("user_id", user_id.to_string()))
+            .bind(("data", to_content(&dismissal)?))
+            .await?
+            .check()?;
+        
+        Ok(())
+    }
+    
+    /// Forget every dismissal a user made
+    pub async fn delete_dismissals_by_user(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE announcement_dismissals WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+        
+        Ok(())
+    }
+}
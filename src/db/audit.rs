@@ -0,0 +1,53 @@
+use uuid::Uuid;
+use crate::types::audit::AuditEntry;
+use crate::types::id::UserId;
+use super::{error, take_rows, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct AuditOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> AuditOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        AuditOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> AuditOperations<'static> {
+        AuditOperations::new(&DB)
+    }
+    
+    /// Record an operator action
+    pub async fn record(
+        &self,
+        actor_id: Option<UserId>,
+        action: &str,
+        detail: serde_json::Value,
+    ) -> Result<AuditEntry, error::Error> {
+        let entry_id = Uuid::new_v4();
+        let entry = AuditEntry {
+            id: entry_id,
+            actor_id,
+            action: action.to_string(),
+            detail,
+            created_at: self.db.now(),
+        };
+        
+        let created: Option<AuditEntry> = self.db.create_record("audit_log", entry_id, &entry).await?;
+            
+        created.ok_or(error::Error::Db("Failed to record audit entry".to_string()))
+    }
+    
+    /// Get audit entries with pagination, newest first. Entries from the
+    /// same instant keep one order, so pages neither repeat nor skip them.
+    pub async fn get_entries(&self, limit: u32, offset: u32) -> Result<Vec<AuditEntry>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM audit_log ORDER BY created_at DESC, id DESC LIMIT $limit START $offset")
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+}
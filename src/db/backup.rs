@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use super::{error, Db, DB};
+
+/// Written next to every export so a restore can be checked against it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub created_at: DateTime<Utc>,
+    pub version: String, // of the crate that wrote the export
+    pub tables: BTreeMap<String, u64>, // record count per exported table
+    pub media: Option<String>, // file name of the media tarball, if one was made
+}
+
+#[derive(Clone, Copy)]
+pub struct BackupOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> BackupOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        BackupOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> BackupOperations<'static> {
+        BackupOperations::new(&DB)
+    }
+    
+    /// Names of every table in the database
+    pub async fn tables(&self) -> Result<Vec<String>, error::Error> {
+        let mut response = self.db.query("INFO FOR DB").await?;
+        let info: Option<Value> = response.take(0)?;
+        
+        Ok(info
+            .as_ref()
+            .and_then(|info| info.get("tables"))
+            .and_then(Value::as_object)
+            .map(|tables| tables.keys().cloned().collect())
+            .unwrap_or_default())
+    }
+    
+    /// Number of records in a table
+    pub async fn count(&self, table: &str) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query("SELECT count() FROM type::table($table) GROUP ALL")
+            .bind(("table", table.to_string()))
+            .await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Write `tables`, or everything when empty, to a SurrealQL file
+    pub async fn export(&self, path: &Path, tables: Vec<String>) -> Result<(), error::Error> {
+        if tables.is_empty() {
+            self.db.export(path).await?;
+        } else {
+            self.db.export(path).with_config().tables(tables).await?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Run a SurrealQL export against the database
+    pub async fn import(&self, path: &Path) -> Result<(), error::Error> {
+        self.db.import(path).await?;
+        
+        // Anything cached predates the restore
+        self.db.user_cache().clear();
+        
+        Ok(())
+    }
+}
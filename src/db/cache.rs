@@ -0,0 +1,137 @@
+//! In-process cache for user lookups by id, one per database handle.
+//!
+//! Entries live for `USER_CACHE_TTL_SECS` (default 30, `0` turns the cache
+//! off) and hold the user along with its already converted `PublicUser`.
+//! Every write to the `users` table through `update_record`, `delete_record`
+//! or a `Transaction` invalidates the entry, so a read after a write always
+//! sees the write.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::types::id::UserId;
+use crate::types::user::{PublicUser, User};
+
+/// Most users kept at once
+const DEFAULT_CAPACITY: usize = 10_000;
+const DEFAULT_TTL_SECS: u64 = 30;
+
+pub struct CachedUser {
+    pub user: User,
+    pub public: PublicUser,
+}
+
+struct Entry {
+    value: Arc<CachedUser>,
+    inserted_at: Instant,
+}
+
+pub struct UserCache {
+    entries: Mutex<HashMap<UserId, Entry>>,
+    ttl: Duration,
+    capacity: usize,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// Hit and miss counts since startup
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub size: usize,
+}
+
+impl UserCache {
+    /// A cache sized by `USER_CACHE_TTL_SECS` and `USER_CACHE_CAPACITY`
+    pub fn from_env() -> Self {
+        let ttl = env::var("USER_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|secs| secs.parse().ok())
+            .unwrap_or(DEFAULT_TTL_SECS);
+        let capacity = env::var("USER_CACHE_CAPACITY")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_CAPACITY);
+
+        UserCache::new(Duration::from_secs(ttl), capacity)
+    }
+
+    pub fn new(ttl: Duration, capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            ttl,
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        !self.ttl.is_zero() && self.capacity > 0
+    }
+
+    pub fn get(&self, user_id: UserId) -> Option<Arc<CachedUser>> {
+        if !self.enabled() {
+            return None;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let fresh = entries.get(&user_id).filter(|entry| entry.inserted_at.elapsed() < self.ttl);
+
+        match fresh {
+            Some(entry) => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                Some(Arc::clone(&entry.value))
+            }
+            None => {
+                entries.remove(&user_id);
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                None
+            }
+        }
+    }
+
+    pub fn insert(&self, user: User) -> Arc<CachedUser> {
+        let value = Arc::new(CachedUser { public: PublicUser::from(user.clone()), user });
+
+        if self.enabled() {
+            let mut entries = self.entries.lock().unwrap();
+            if entries.len() >= self.capacity {
+                self.evict(&mut entries);
+            }
+            entries.insert(value.user.id, Entry { value: Arc::clone(&value), inserted_at: Instant::now() });
+        }
+
+        value
+    }
+
+    /// Drop expired entries, or the oldest one if none have expired
+    fn evict(&self, entries: &mut HashMap<UserId, Entry>) {
+        entries.retain(|_, entry| entry.inserted_at.elapsed() < self.ttl);
+
+        if entries.len() >= self.capacity {
+            let oldest = entries.iter().min_by_key(|(_, entry)| entry.inserted_at).map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                entries.remove(&oldest);
+            }
+        }
+    }
+
+    pub fn invalidate(&self, user_id: UserId) {
+        self.entries.lock().unwrap().remove(&user_id);
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            size: self.entries.lock().unwrap().len(),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::email::Email;
+use crate::types::email::QueuedEmail;
+use super::{error, take_rows, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct EmailOutboxOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> EmailOutboxOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        EmailOutboxOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> EmailOutboxOperations<'static> {
+        EmailOutboxOperations::new(&DB)
+    }
+    
+    /// Keep a message whose first delivery failed, to be tried again at
+    /// `next_attempt_at`
+    pub async fn enqueue(&self, email: Email, error: String, next_attempt_at: DateTime<Utc>) -> Result<QueuedEmail, error::Error> {
+        let queued = QueuedEmail {
+            id: Uuid::new_v4(),
+            email,
+            attempts: 1,
+            last_error: error,
+            next_attempt_at,
+            created_at: self.db.now(),
+        };
+        
+        let created: Option<QueuedEmail> = self.db.create_record("email_outbox", queued.id, &queued).await?;
+            
+        created.ok_or(error::Error::Db("Failed to queue email".to_string()))
+    }
+    
+    /// Messages due another try, longest waiting first
+    pub async fn get_due(&self, limit: u32) -> Result<Vec<QueuedEmail>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT *, record::id(id) AS id FROM email_outbox
+                WHERE <datetime> next_attempt_at <= <datetime> $now
+                ORDER BY next_attempt_at ASC LIMIT $limit"
+            )
+            .bind(("now", self.db.now().to_rfc3339()))
+            .bind(("limit", limit))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// Record another failed try
+    pub async fn save(&self, queued: QueuedEmail) -> Result<QueuedEmail, error::Error> {
+        let updated: Option<QueuedEmail> = self.db.update_record("email_outbox", queued.id, &queued).await?;
+            
+        updated.ok_or(error::Error::NotFound)
+    }
+    
+    /// Drop a message that was delivered or given up on
+    pub async fn remove(&self, id: Uuid) -> Result<(), error::Error> {
+        self.db.delete_record("email_outbox", id).await
+    }
+    
+    /// Drop everything still waiting to go to `to`
+    pub async fn delete_for_address(&self, to: &str) -> Result<(), error::Error> {
+        self.db.query("DELETE email_outbox WHERE string::lowercase(email.to) = string::lowercase($to)")
+            .bind(("to", to.to_string()))
+            .await?
+            .check()?;
+            
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use chrono::Duration;
+use uuid::Uuid;
+use crate::auth;
+use crate::types::email_token::{EmailToken, EmailTokenPurpose};
+use crate::types::id::UserId;
+use super::{error, take_row, Db, DB};
+
+/// How soon another token for the same purpose may be sent, so an account
+/// can't be used to flood an inbox
+pub const RESEND_INTERVAL: Duration = Duration::minutes(1);
+
+#[derive(Clone, Copy)]
+pub struct EmailTokenOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> EmailTokenOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        EmailTokenOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> EmailTokenOperations<'static> {
+        EmailTokenOperations::new(&DB)
+    }
+    
+    /// Issue a token for `user_id` to be sent to `email`, replacing any
+    /// earlier one for the same purpose. Returns the plain token.
+    pub async fn issue(&self, user_id: UserId, email: &str, purpose: EmailTokenPurpose) -> Result<String, error::Error> {
+        let now = self.db.now();
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM email_tokens WHERE user_id = $user_id AND purpose = $purpose ORDER BY created_at DESC LIMIT 1")
+            .bind(("user_id", user_id.to_string()))
+            .bind(("purpose", purpose.as_str()))
+            .await?;
+        let latest: Option<EmailToken> = take_row(&mut response, 0)?;
+        if latest.is_some_and(|token| token.created_at + RESEND_INTERVAL > now) {
+            return Err(error::Error::TooManyRequests("an email was sent less than a minute ago".to_string()));
+        }
+        
+        let token = auth::generate_token();
+        let record = EmailToken {
+            id: Uuid::new_v4(),
+            user_id,
+            purpose,
+            token_hash: auth::hash_token(&token),
+            email: email.to_string(),
+            created_at: now,
+            expires_at: now + purpose.ttl(),
+            used_at: None,
+        };
+        self.db.query("DELETE email_tokens WHERE user_id = $user_id AND purpose = $purpose")
+            .bind(("user_id", user_id.to_string()))
+            .bind(("purpose", purpose.as_str()))
+            .await?
+            .check()?;
+        let created: Option<EmailToken> = self.db.create_record("email_tokens", record.id, &record).await?;
+        created.ok_or(error::Error::Db("Failed to create email token".to_string()))?;
+        
+        Ok(token)
+    }
+    
+    /// Use up an unexpired token. It's marked used in the same statement
+    /// that finds it, so it works once even when presented twice at once.
+    pub async fn redeem(&self, token: &str, purpose: EmailTokenPurpose) -> Result<EmailToken, error::Error> {
+        let mut response = self.db
+            .query(
+                "UPDATE email_tokens SET used_at = $now
+                WHERE token_hash = $token_hash AND purpose = $purpose AND (used_at = NONE OR used_at = NULL) AND <datetime> expires_at > <datetime> $now
+                RETURN *, record::id(id) AS id"
+            )
+            .bind(("token_hash", auth::hash_token(token)))
+            .bind(("purpose", purpose.as_str()))
+            .bind(("now", self.db.now().to_rfc3339()))
+            .await?;
+        
+        take_row(&mut response, 0)?.ok_or(error::Error::Validation("invalid or expired token".to_string()))
+    }
+    
+    /// Delete every token sent to a user
+    pub async fn delete_tokens_for_user(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE email_tokens WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+        
+        Ok(())
+    }
+}
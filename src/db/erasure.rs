@@ -0,0 +1,92 @@
+use uuid::Uuid;
+use crate::types::erasure::{ErasureJob, ErasureStatus};
+use crate::types::id::UserId;
+use super::{error, take_row, take_rows, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct ErasureOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> ErasureOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        ErasureOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> ErasureOperations<'static> {
+        ErasureOperations::new(&DB)
+    }
+    
+    /// Queue an erasure job for a user, reusing an unfinished one if it exists
+    pub async fn create_job(&self, user_id: UserId) -> Result<ErasureJob, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM erasure_jobs WHERE user_id = $user_id AND status != 'Completed'")
+            .bind(("user_id", user_id.to_string()))
+            .await?;
+        let existing: Option<ErasureJob> = take_row(&mut response, 0)?;
+        
+        if let Some(job) = existing {
+            return Ok(job);
+        }
+        
+        let now = self.db.now();
+        let job_id = Uuid::new_v4();
+        
+        let job = ErasureJob {
+            id: job_id,
+            user_id: Some(user_id),
+            status: ErasureStatus::Pending,
+            completed_steps: Vec::new(),
+            records_scrubbed: 0,
+            attempts: 0,
+            last_error: None,
+            requested_at: now,
+            updated_at: now,
+            completed_at: None,
+        };
+        
+        let created: Option<ErasureJob> = self.db.create_record("erasure_jobs", job_id, &job).await?;
+            
+        created.ok_or(error::Error::Db("Failed to create erasure job".to_string()))
+    }
+    
+    /// Get erasure job by ID
+    pub async fn get_job(&self, job_id: Uuid) -> Result<ErasureJob, error::Error> {
+        let job: Option<ErasureJob> = self.db.select_record("erasure_jobs", job_id).await?;
+        
+        job.ok_or(error::Error::ErasureJobNotFound)
+    }
+    
+    /// Persist a job's progress
+    pub async fn save_job(&self, mut job: ErasureJob) -> Result<ErasureJob, error::Error> {
+        job.updated_at = self.db.now();
+        
+        let updated: Option<ErasureJob> = self.db.update_record("erasure_jobs", job.id, &job).await?;
+            
+        updated.ok_or(error::Error::ErasureJobNotFound)
+    }
+    
+    /// Get all erasure jobs with pagination, newest first
+    pub async fn get_jobs(&self, limit: Option<u32>, offset: Option<u32>) -> Result<Vec<ErasureJob>, error::Error> {
+        let limit = limit.unwrap_or(50);
+        let offset = offset.unwrap_or(0);
+        
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM erasure_jobs ORDER BY requested_at DESC LIMIT $limit START $offset")
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// Get jobs that haven't completed, e.g. because the process crashed mid-run
+    pub async fn get_unfinished_jobs(&self) -> Result<Vec<ErasureJob>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM erasure_jobs WHERE status != 'Completed' ORDER BY requested_at ASC")
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+}
@@ -0,0 +1,46 @@
+use crate::types::feature_flag::FeatureFlag;
+use super::{error, take_row, take_rows, to_content, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct FeatureFlagOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> FeatureFlagOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        FeatureFlagOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> FeatureFlagOperations<'static> {
+        FeatureFlagOperations::new(&DB)
+    }
+    
+    /// Get every stored flag
+    pub async fn list_flags(&self) -> Result<Vec<FeatureFlag>, error::Error> {
+        let mut response = self.db
+            .query("SELECT * OMIT id FROM feature_flags ORDER BY name")
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// Create or replace a flag, keyed by its name
+    pub async fn set_flag(
+        &self,
+        name: String,
+        enabled: bool,
+        rollout_percentage: Option<u8>,
+    ) -> Result<FeatureFlag, error::Error> {
+        let flag = FeatureFlag { name, enabled, rollout_percentage, updated_at: self.db.now() };
+        
+        let mut response = self.db
+            .query("UPSERT type::thing('feature_flags', $name) CONTENT $data RETURN * OMIT id")
+            .bind(("name", flag.name.clone()))
+            .bind(("data", to_content(&flag)?))
+            .await?;
+        let saved: Option<FeatureFlag> = take_row(&mut response, 0)?;
+            
+        saved.ok_or(error::Error::Db("Failed to save feature flag".to_string()))
+    }
+}
@@ -0,0 +1,83 @@
+use uuid::Uuid;
+use crate::types::federation::RemoteFollower;
+use crate::types::id::UserId;
+use super::{error, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct FederationOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> FederationOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        FederationOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> FederationOperations<'static> {
+        FederationOperations::new(&DB)
+    }
+    
+    /// Record a remote follow, ignoring repeats
+    pub async fn add_remote_follower(
+        &self,
+        user_id: UserId,
+        actor: String,
+        follow_id: Option<String>,
+    ) -> Result<(), error::Error> {
+        let mut response = self.db
+            .query("SELECT VALUE record::id(id) FROM remote_followers WHERE user_id = $user_id AND actor = $actor LIMIT 1")
+            .bind(("user_id", user_id.to_string()))
+            .bind(("actor", actor.clone()))
+            .await?;
+        let existing: Option<String> = response.take(0)?;
+        
+        if existing.is_some() {
+            return Ok(());
+        }
+        
+        let follower_id = Uuid::new_v4();
+        let follower = RemoteFollower {
+            id: follower_id,
+            user_id,
+            actor,
+            follow_id,
+            created_at: self.db.now(),
+        };
+        
+        let _: Option<RemoteFollower> = self.db.create_record("remote_followers", follower_id, &follower).await?;
+        Ok(())
+    }
+    
+    /// Forget a remote follow
+    pub async fn remove_remote_follower(&self, user_id: UserId, actor: String) -> Result<(), error::Error> {
+        self.db.query("DELETE remote_followers WHERE user_id = $user_id AND actor = $actor")
+            .bind(("user_id", user_id.to_string()))
+            .bind(("actor", actor))
+            .await?
+            .check()?;
+            
+        Ok(())
+    }
+    
+    /// Count a user's remote followers
+    pub async fn count_remote_followers(&self, user_id: UserId) -> Result<u64, error::Error> {
+        let count: Option<u64> = self.db
+            .query("SELECT count() FROM remote_followers WHERE user_id = $user_id GROUP ALL")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .take((0, "count"))?;
+            
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Delete every remote follow of a user
+    pub async fn delete_remote_followers_for_user(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE remote_followers WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+            
+        Ok(())
+    }
+}
@@ -0,0 +1,74 @@
+use crate::types::gc::GcRun;
+use super::{error, take_rows, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct GcOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> GcOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        GcOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> GcOperations<'static> {
+        GcOperations::new(&DB)
+    }
+    
+    /// Every media URL stored anywhere: profile images, uploads with their
+    /// earlier audio and attachments, and the copies kept in playlists,
+    /// deleted ones included
+    pub async fn referenced_urls(&self) -> Result<Vec<String>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT VALUE [
+                    profile.profile_picture,
+                    profile.profile_banner,
+                    profile.uploads.*.audio_url,
+                    profile.uploads.*.audio_versions.*.audio_url,
+                    profile.uploads.*.attachments.*.url,
+                    profile.uploads.*.cover_image_url,
+                    playlists.*.cover_image_url,
+                    playlists.*.tracks.*.audio_url,
+                    playlists.*.tracks.*.cover_image_url,
+                    deleted_playlists.*.cover_image_url,
+                    deleted_playlists.*.tracks.*.audio_url,
+                    deleted_playlists.*.tracks.*.cover_image_url
+                ] FROM users"
+            )
+            .await?;
+        let rows: Vec<serde_json::Value> = response.take(0)?;
+        
+        let mut urls = Vec::new();
+        rows.iter().for_each(|row| collect_strings(row, &mut urls));
+        Ok(urls)
+    }
+    
+    /// Persist the report of a run
+    pub async fn record_run(&self, run: &GcRun) -> Result<GcRun, error::Error> {
+        let created: Option<GcRun> = self.db.create_record("gc_runs", run.id, run).await?;
+        
+        created.ok_or(error::Error::Db("Failed to record gc run".to_string()))
+    }
+    
+    /// Get past runs with pagination, newest first
+    pub async fn get_runs(&self, limit: u32, offset: u32) -> Result<Vec<GcRun>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM gc_runs ORDER BY started_at DESC LIMIT $limit START $offset")
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+}
+
+/// Gather every string in a value of arbitrarily nested arrays, skipping nulls
+fn collect_strings(value: &serde_json::Value, out: &mut Vec<String>) {
+    match value {
+        serde_json::Value::String(s) => out.push(s.clone()),
+        serde_json::Value::Array(values) => values.iter().for_each(|value| collect_strings(value, out)),
+        _ => {}
+    }
+}
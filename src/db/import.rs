@@ -0,0 +1,63 @@
+use std::collections::HashSet;
+use serde::Deserialize;
+use crate::types::user::User;
+use super::{error, take_rows, to_content, Db, DB};
+
+#[derive(Deserialize)]
+struct Identity {
+    username: String,
+    email: String,
+}
+
+#[derive(Clone, Copy)]
+pub struct ImportOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> ImportOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        ImportOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> ImportOperations<'static> {
+        ImportOperations::new(&DB)
+    }
+    
+    /// Which of `emails` and `usernames` are already taken, in one round trip
+    pub async fn existing_identities(
+        &self,
+        emails: Vec<String>,
+        usernames: Vec<String>,
+    ) -> Result<(HashSet<String>, HashSet<String>), error::Error> {
+        let mut response = self.db
+            .query("SELECT username, email FROM users WHERE email IN $emails OR username IN $usernames")
+            .bind(("emails", emails))
+            .bind(("usernames", usernames))
+            .await?;
+        let identities: Vec<Identity> = take_rows(&mut response, 0)?;
+        
+        let emails = identities.iter().map(|i| i.email.clone()).collect();
+        let usernames = identities.into_iter().map(|i| i.username).collect();
+        Ok((emails, usernames))
+    }
+    
+    /// Insert a batch of users with a single statement
+    pub async fn insert_users(&self, users: &[User]) -> Result<(), error::Error> {
+        if users.is_empty() {
+            return Ok(());
+        }
+        
+        // Each row carries its uuid as `id`, which becomes the record key
+        let rows = users.iter()
+            .map(to_content)
+            .collect::<Result<Vec<_>, error::Error>>()?;
+        
+        self.db.query("INSERT INTO users $rows")
+            .bind(("rows", rows))
+            .await?
+            .check()?;
+            
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+use crate::types::id::{TrackId, UserId};
+use crate::types::lyrics::{Lyrics, LyricsPatch};
+use super::track::TrackOperations;
+use super::{error, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct LyricsOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> LyricsOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        LyricsOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> LyricsOperations<'static> {
+        LyricsOperations::new(&DB)
+    }
+    
+    /// Get a track's lyrics, if it has any
+    pub async fn get_lyrics(&self, track_id: TrackId) -> Result<Option<Lyrics>, error::Error> {
+        self.db.select_record("lyrics", track_id).await
+    }
+    
+    /// Apply `patch` to the lyrics of a track owned by `owner_id`, keeping
+    /// the track's `has_lyrics` flag in step. Returns the lyrics left, if any.
+    pub async fn set_lyrics(&self, track_id: TrackId, owner_id: UserId, patch: LyricsPatch) -> Result<Option<Lyrics>, error::Error> {
+        let (mut owner, track) = TrackOperations::new(self.db).get_track_with_owner(track_id).await?;
+        if track.user_id != owner_id {
+            // Don't reveal tracks the caller couldn't see anyway
+            return Err(if track.is_visible_to(Some(owner_id)) {
+                error::Error::Forbidden
+            } else {
+                error::Error::TrackNotFound
+            });
+        }
+        
+        let current = self.get_lyrics(track_id).await?;
+        let duration = track.technical_metadata.as_ref().map(|m| m.duration);
+        let lyrics = patch.apply(current, track_id, owner_id, duration)?;
+        
+        let now = self.db.now();
+        if let Some(track) = owner.profile.as_mut()
+            .and_then(|p| p.uploads.as_mut())
+            .and_then(|uploads| uploads.iter_mut().find(|t| t.id == track_id))
+        {
+            track.has_lyrics = lyrics.is_some();
+            track.updated_at = now;
+        }
+        owner.updated_at = now;
+        
+        self.db.transaction(|tx| {
+            tx.update("users", owner.id, &owner)?;
+            match &lyrics {
+                Some(lyrics) => tx.upsert("lyrics", track_id, lyrics),
+                None => {
+                    tx.delete("lyrics", track_id);
+                    Ok(())
+                }
+            }
+        }).await?;
+        
+        Ok(lyrics)
+    }
+    
+    /// Delete the lyrics of every track a user uploaded
+    pub async fn delete_lyrics_for_user(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE lyrics WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,37 @@
+use crate::types::maintenance::MaintenanceState;
+use super::{error, take_row, to_content, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct MaintenanceOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> MaintenanceOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        MaintenanceOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> MaintenanceOperations<'static> {
+        MaintenanceOperations::new(&DB)
+    }
+    
+    /// The stored maintenance state, if it was ever set
+    pub async fn get_state(&self) -> Result<Option<MaintenanceState>, error::Error> {
+        let mut response = self.db
+            .query("SELECT * OMIT id FROM settings:maintenance")
+            .await?;
+            
+        take_row(&mut response, 0)
+    }
+    
+    /// Replace the stored maintenance state
+    pub async fn set_state(&self, state: &MaintenanceState) -> Result<(), error::Error> {
+        self.db.query("UPSERT settings:maintenance CONTENT $data")
+            .bind(("data", to_content(state)?))
+            .await?
+            .check()?;
+            
+        Ok(())
+    }
+}
@@ -0,0 +1,169 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::types::id::UserId;
+use crate::{push, realtime};
+use crate::types::notification::{Notification, NotificationKind};
+use crate::types::realtime::ServerMessage;
+use super::{error, take_rows, to_content, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct NotificationOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> NotificationOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        NotificationOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> NotificationOperations<'static> {
+        NotificationOperations::new(&DB)
+    }
+    
+    /// Store a notification for a user
+    pub async fn notify(
+        &self,
+        user_id: UserId,
+        kind: NotificationKind,
+        message: String,
+        data: serde_json::Value,
+    ) -> Result<Notification, error::Error> {
+        let notification_id = Uuid::new_v4();
+        let notification = Notification {
+            id: notification_id,
+            user_id,
+            kind,
+            message,
+            data,
+            read: false,
+            created_at: self.db.now(),
+        };
+        
+        let created: Option<Notification> = self.db.create_record("notifications", notification_id, &notification).await?;
+        let created = created.ok_or(error::Error::Db("Failed to create notification".to_string()))?;
+        
+        self.push_live(&created).await?;
+        push::notify(self.db, vec![created.clone()]);
+        Ok(created)
+    }
+    
+    /// Store the same notification for many users in one query
+    pub async fn notify_many(
+        &self,
+        user_ids: &[UserId],
+        kind: NotificationKind,
+        message: String,
+        data: serde_json::Value,
+    ) -> Result<(), error::Error> {
+        if user_ids.is_empty() {
+            return Ok(());
+        }
+        
+        let now = self.db.now();
+        let notifications: Vec<Notification> = user_ids.iter()
+            .map(|user_id| Notification {
+                id: Uuid::new_v4(),
+                user_id: *user_id,
+                kind,
+                message: message.clone(),
+                data: data.clone(),
+                read: false,
+                created_at: now,
+            })
+            .collect();
+        let content = notifications.iter().map(to_content).collect::<Result<Vec<_>, _>>()?;
+        
+        self.db.query("INSERT INTO notifications $notifications")
+            .bind(("notifications", content))
+            .await?
+            .check()?;
+        
+        for notification in &notifications {
+            self.push_live(notification).await?;
+        }
+        push::notify(self.db, notifications);
+        Ok(())
+    }
+    
+    /// Push a new notification and the unread count that goes with it to
+    /// the user's open streams, if they have any or might resume one
+    async fn push_live(&self, notification: &Notification) -> Result<(), error::Error> {
+        if !realtime::is_listening(notification.user_id) {
+            return Ok(());
+        }
+        
+        realtime::push(notification.user_id, &ServerMessage::Notification { notification: notification.clone() });
+        let unread_count = self.count_unread(notification.user_id).await?;
+        realtime::push(notification.user_id, &ServerMessage::UnreadCount { unread_count });
+        Ok(())
+    }
+    
+    /// Get a user's notifications with pagination, newest first
+    pub async fn get_notifications(&self, user_id: UserId, limit: u32, offset: u32) -> Result<Vec<Notification>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM notifications WHERE user_id = $user_id ORDER BY created_at DESC LIMIT $limit START $offset")
+            .bind(("user_id", user_id.to_string()))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// Count a user's notifications
+    pub async fn count_notifications(&self, user_id: UserId) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query("SELECT count() FROM notifications WHERE user_id = $user_id GROUP ALL")
+            .bind(("user_id", user_id.to_string()))
+            .await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Count a user's unread notifications
+    pub async fn count_unread(&self, user_id: UserId) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query("SELECT count() FROM notifications WHERE user_id = $user_id AND read = false GROUP ALL")
+            .bind(("user_id", user_id.to_string()))
+            .await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Count a user's notifications created after `since`
+    pub async fn count_since(&self, user_id: UserId, since: DateTime<Utc>) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query("SELECT count() FROM notifications WHERE user_id = $user_id AND <datetime> created_at > <datetime> $since GROUP ALL")
+            .bind(("user_id", user_id.to_string()))
+            .bind(("since", since.to_rfc3339()))
+            .await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Mark every notification a user has as read
+    pub async fn mark_all_read(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("UPDATE notifications SET read = true WHERE user_id = $user_id AND read = false")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+        
+        // Other tabs and devices clear their badges too
+        realtime::push(user_id, &ServerMessage::UnreadCount { unread_count: 0 });
+        Ok(())
+    }
+    
+    /// Delete every notification a user has
+    pub async fn delete_notifications_for_user(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE notifications WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+            
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use serde::Serialize;
+use crate::types::id::{TrackId, UserId};
+use crate::types::stats::PlayBucket;
+use super::{error, take_rows, to_content, Db, DB};
+
+/// How long replays of a track by one signed-in listener count as one play
+pub const PLAY_DEDUP_WINDOW: Duration = Duration::minutes(30);
+
+/// When each signed-in listener last had a play of a track counted. Kept in
+/// memory, so plays stay untied to listeners in the database; each instance
+/// dedupes the plays it takes.
+static LAST_COUNTED: LazyLock<Mutex<LastCounted>> = LazyLock::new(Default::default);
+
+type LastCounted = HashMap<(UserId, TrackId), DateTime<Utc>>;
+
+/// One play of a track. Listeners aren't recorded.
+#[derive(Debug, Serialize)]
+struct Play {
+    track_id: TrackId,
+    played_at: String,
+}
+
+#[derive(Clone, Copy)]
+pub struct PlayOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> PlayOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        PlayOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> PlayOperations<'static> {
+        PlayOperations::new(&DB)
+    }
+    
+    /// Count a play of a track, unless `listener` had one counted within
+    /// `PLAY_DEDUP_WINDOW`. Returns whether it was counted.
+    pub async fn record_play(&self, track_id: TrackId, listener: Option<UserId>) -> Result<bool, error::Error> {
+        let now = self.db.now();
+        if let Some(listener) = listener {
+            let mut counted = LAST_COUNTED.lock().unwrap_or_else(|e| e.into_inner());
+            counted.retain(|_, at| now - *at < PLAY_DEDUP_WINDOW);
+            if counted.contains_key(&(listener, track_id)) {
+                return Ok(false);
+            }
+        }
+        
+        let play = Play { track_id, played_at: now.to_rfc3339() };
+        self.db.query("CREATE plays CONTENT $play")
+            .bind(("play", to_content(&play)?))
+            .await?
+            .check()?;
+        
+        if let Some(listener) = listener {
+            LAST_COUNTED.lock().unwrap_or_else(|e| e.into_inner()).insert((listener, track_id), now);
+        }
+        
+        Ok(true)
+    }
+    
+    /// How many times a track was played, ever
+    pub async fn count_plays(&self, track_id: TrackId) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query("SELECT count() FROM plays WHERE track_id = $track_id GROUP ALL")
+            .bind(("track_id", track_id.to_string()))
+            .await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// A track's plays per UTC day over the last `days` days, today
+    /// included, oldest first. Days without plays are counted as zero.
+    pub async fn plays_by_day(&self, track_id: TrackId, days: u32) -> Result<Vec<PlayBucket>, error::Error> {
+        let today = self.db.now().date_naive();
+        let first = today - Duration::days(i64::from(days.max(1)) - 1);
+        
+        let mut response = self.db
+            .query(
+                "SELECT string::slice(played_at, 0, 10) AS day, count() AS plays FROM plays
+                WHERE track_id = $track_id AND <datetime> played_at >= <datetime> $since
+                GROUP BY day"
+            )
+            .bind(("track_id", track_id.to_string()))
+            .bind(("since", first.and_time(NaiveTime::MIN).and_utc().to_rfc3339()))
+            .await?;
+        let counted: HashMap<NaiveDate, u64> = take_rows::<PlayBucket>(&mut response, 0)?
+            .into_iter()
+            .map(|bucket| (bucket.day, bucket.plays))
+            .collect();
+        
+        Ok(first.iter_days()
+            .take_while(|day| *day <= today)
+            .map(|day| PlayBucket { day, plays: counted.get(&day).copied().unwrap_or(0) })
+            .collect())
+    }
+}
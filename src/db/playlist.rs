@@ -0,0 +1,334 @@
+use crate::live_playlists::{self, Change};
+use crate::types::id::{PlaylistId, TrackId, UserId};
+use crate::types::user::{Playlist, User};
+use super::slug::{SlugOperations, SlugScope};
+use super::track::TrackOperations;
+use super::{error, record_id, take_row, take_rows, to_content, UserOperations, Db, DB};
+
+/// Longest playlist name accepted, in characters
+pub const MAX_NAME_LEN: usize = 100;
+
+/// Most tracks one playlist may hold
+pub const MAX_TRACKS: usize = 1000;
+
+/// Tries at storing an edit sent without a revision before giving up
+const EDIT_ATTEMPTS: usize = 3;
+
+#[derive(Clone, Copy)]
+pub struct PlaylistOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> PlaylistOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        PlaylistOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> PlaylistOperations<'static> {
+        PlaylistOperations::new(&DB)
+    }
+    
+    /// Get the user who owns a playlist
+    pub async fn get_owner(&self, playlist_id: PlaylistId) -> Result<User, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users WHERE playlists.*.id CONTAINS $playlist_id")
+            .bind(("playlist_id", playlist_id.to_string()))
+            .await?;
+        let owner: Option<User> = take_row(&mut response, 0)?;
+        
+        owner.ok_or(error::Error::PlaylistNotFound)
+    }
+    
+    /// Get playlist by ID along with its owner
+    pub async fn get_playlist(&self, playlist_id: PlaylistId) -> Result<(User, Playlist), error::Error> {
+        let owner = self.get_owner(playlist_id).await?;
+        
+        let playlist = owner.playlists.iter()
+            .flatten()
+            .find(|p| p.id == playlist_id)
+            .cloned()
+            .ok_or(error::Error::PlaylistNotFound)?;
+        
+        Ok((owner, playlist))
+    }
+    
+    /// Create a playlist of `track_ids`, in that order. Tracks that don't
+    /// exist or that the owner can't see are left out.
+    pub async fn create_playlist(
+        &self,
+        user_id: UserId,
+        name: String,
+        description: Option<String>,
+        track_ids: &[TrackId],
+        is_public: bool,
+    ) -> Result<Playlist, error::Error> {
+        let name = name.trim().to_string();
+        if name.is_empty() || name.chars().count() > MAX_NAME_LEN {
+            return Err(error::Error::Validation(format!("name must be 1 to {} characters", MAX_NAME_LEN)));
+        }
+        
+        let mut user = UserOperations::new(self.db).get_user_by_id(user_id).await?;
+        let found = TrackOperations::new(self.db).get_tracks(track_ids).await?;
+        let tracks = track_ids.iter()
+            .filter_map(|id| found.iter().find(|t| t.id == *id))
+            .filter(|t| t.is_visible_to(Some(user_id)))
+            .cloned()
+            .collect();
+        
+        let slug = SlugOperations::new(self.db).free_slugs(SlugScope::Playlists, &[name.as_str()], None).await?.pop();
+        let now = self.db.now();
+        let playlist = Playlist {
+            id: PlaylistId::new(),
+            user_id,
+            name,
+            description,
+            tags: None,
+            cover_image_url: None,
+            is_public,
+            is_deleted: false,
+            is_collaborative: false,
+            tracks,
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            slug,
+            slug_aliases: Vec::new(),
+        };
+        
+        user.playlists.get_or_insert_with(Vec::new).push(playlist.clone());
+        user.updated_at = now;
+        let _: Option<User> = self.db.update_record("users", user_id, &user).await?;
+        
+        Ok(playlist)
+    }
+    
+    /// Add a track the editor can see at `index` among the tracks they see,
+    /// or at the end
+    pub async fn add_track(
+        &self,
+        playlist_id: PlaylistId,
+        editor_id: UserId,
+        track_id: TrackId,
+        index: Option<usize>,
+        revision: Option<u64>,
+    ) -> Result<(User, Playlist), error::Error> {
+        let track = TrackOperations::new(self.db).get_track(track_id).await?;
+        if !track.is_visible_to(Some(editor_id)) {
+            return Err(error::Error::TrackNotFound);
+        }
+        
+        let (owner, playlist) = self.edit(playlist_id, editor_id, revision, |playlist| {
+            if playlist.tracks.iter().any(|t| t.id == track_id) {
+                return Err(error::Error::Conflict("the track is already in the playlist".to_string()));
+            }
+            if playlist.tracks.len() >= MAX_TRACKS {
+                return Err(error::Error::Validation(format!("a playlist may hold at most {} tracks", MAX_TRACKS)));
+            }
+            
+            let at = index.map_or(playlist.tracks.len(), |index| playlist.full_index(Some(editor_id), index));
+            playlist.tracks.insert(at, track.clone());
+            Ok(())
+        }).await?;
+        
+        live_playlists::publish(&playlist, editor_id, &Change::Added(track_id));
+        Ok((owner, playlist))
+    }
+    
+    /// Take a track the editor can see out of a playlist
+    pub async fn remove_track(
+        &self,
+        playlist_id: PlaylistId,
+        editor_id: UserId,
+        track_id: TrackId,
+        revision: Option<u64>,
+    ) -> Result<(User, Playlist), error::Error> {
+        let mut removed = None;
+        let (owner, playlist) = self.edit(playlist_id, editor_id, revision, |playlist| {
+            let at = playlist.tracks.iter()
+                .position(|t| t.id == track_id && t.is_visible_to(Some(editor_id)))
+                .ok_or(error::Error::TrackNotFound)?;
+            removed = Some(playlist.tracks.remove(at));
+            Ok(())
+        }).await?;
+        
+        if let Some(track) = removed {
+            live_playlists::publish(&playlist, editor_id, &Change::Removed(Box::new(track)));
+        }
+        Ok((owner, playlist))
+    }
+    
+    /// Move a track the editor can see to `index` among the other tracks
+    /// they see
+    pub async fn move_track(
+        &self,
+        playlist_id: PlaylistId,
+        editor_id: UserId,
+        track_id: TrackId,
+        index: usize,
+        revision: Option<u64>,
+    ) -> Result<(User, Playlist), error::Error> {
+        let (owner, playlist) = self.edit(playlist_id, editor_id, revision, |playlist| {
+            let from = playlist.tracks.iter()
+                .position(|t| t.id == track_id && t.is_visible_to(Some(editor_id)))
+                .ok_or(error::Error::TrackNotFound)?;
+            let track = playlist.tracks.remove(from);
+            let to = playlist.full_index(Some(editor_id), index);
+            playlist.tracks.insert(to, track);
+            Ok(())
+        }).await?;
+        
+        live_playlists::publish(&playlist, editor_id, &Change::Moved(track_id));
+        Ok((owner, playlist))
+    }
+    
+    /// Let others edit a playlist's tracks, or stop them. Only the owner may.
+    pub async fn set_collaborative(&self, playlist_id: PlaylistId, owner_id: UserId, is_collaborative: bool) -> Result<(User, Playlist), error::Error> {
+        let (owner, mut playlist) = self.get_playlist(playlist_id).await?;
+        if !playlist.is_visible_to(Some(owner_id)) {
+            return Err(error::Error::PlaylistNotFound);
+        }
+        if playlist.user_id != owner_id {
+            return Err(error::Error::Forbidden);
+        }
+        
+        playlist.is_collaborative = is_collaborative;
+        playlist.updated_at = self.db.now();
+        if !self.store(&owner, &playlist, playlist.revision).await? {
+            return Err(error::Error::Conflict("the playlist changed meanwhile; try again".to_string()));
+        }
+        
+        Ok((owner, playlist))
+    }
+    
+    /// Delete a playlist, taking it out of its owner's `playlists`. Unless
+    /// `hard`, it's kept aside in `deleted_playlists` rather than dropped.
+    /// Only the owner may; subscribers watching it live are told it's gone.
+    pub async fn delete_playlist(&self, playlist_id: PlaylistId, owner_id: UserId, hard: bool) -> Result<(), error::Error> {
+        let (owner, mut playlist) = self.get_playlist(playlist_id).await?;
+        if !playlist.is_visible_to(Some(owner_id)) {
+            return Err(error::Error::PlaylistNotFound);
+        }
+        if playlist.user_id != owner_id {
+            return Err(error::Error::Forbidden);
+        }
+        
+        let index = playlist_index(&owner, playlist_id)?;
+        let expected = playlist.revision;
+        playlist.is_deleted = true;
+        playlist.updated_at = self.db.now();
+        
+        // Refused if an edit landed since the read, so the copy kept aside
+        // is never older than what was deleted. It's cut out by position,
+        // as in `store`.
+        let mut response = self.db
+            .query(
+                "UPDATE $record SET
+                    deleted_playlists = IF $hard THEN deleted_playlists ?? [] ELSE (deleted_playlists ?? []).append($playlist) END,
+                    playlists = array::concat(array::slice(playlists, 0, $index), array::slice(playlists, $index + 1)),
+                    updated_at = $now
+                WHERE playlists[$index].id = $playlist.id AND (playlists[$index].revision ?? 0) = $expected
+                RETURN VALUE record::id(id)",
+            )
+            .bind(("record", record_id("users", owner.id)))
+            .bind(("index", index))
+            .bind(("playlist", to_content(&playlist)?))
+            .bind(("hard", hard))
+            .bind(("now", self.db.now().to_rfc3339()))
+            .bind(("expected", expected))
+            .await?;
+        self.db.invalidate_cached("users", owner.id);
+        
+        let updated: Vec<String> = take_rows(&mut response, 0)?;
+        if updated.is_empty() {
+            return Err(error::Error::Conflict("the playlist changed meanwhile; try again".to_string()));
+        }
+        
+        live_playlists::close(playlist_id);
+        Ok(())
+    }
+    
+    /// Apply `edit` to a playlist's tracks for `editor_id` and store the
+    /// result as the next revision. With `revision`, the edit is refused
+    /// unless that's still the stored revision; without, an edit racing
+    /// another is made again on top of it.
+    async fn edit<F>(
+        &self,
+        playlist_id: PlaylistId,
+        editor_id: UserId,
+        revision: Option<u64>,
+        mut edit: F,
+    ) -> Result<(User, Playlist), error::Error>
+    where
+        F: FnMut(&mut Playlist) -> Result<(), error::Error>,
+    {
+        for _ in 0..EDIT_ATTEMPTS {
+            let (owner, mut playlist) = self.get_playlist(playlist_id).await?;
+            if !playlist.is_visible_to(Some(editor_id)) {
+                return Err(error::Error::PlaylistNotFound);
+            }
+            let blocked = owner.profile.as_ref()
+                .and_then(|p| p.blocked_users.as_ref())
+                .is_some_and(|blocked| blocked.contains(&editor_id));
+            if !playlist.is_editable_by(editor_id) || blocked {
+                return Err(error::Error::Forbidden);
+            }
+            if revision.is_some_and(|revision| revision != playlist.revision) {
+                return Err(error::Error::Conflict(format!(
+                    "the playlist is at revision {}; reload it and try again",
+                    playlist.revision
+                )));
+            }
+            
+            let expected = playlist.revision;
+            edit(&mut playlist)?;
+            playlist.revision += 1;
+            playlist.updated_at = self.db.now();
+            
+            if self.store(&owner, &playlist, expected).await? {
+                return Ok((owner, playlist));
+            }
+            if revision.is_some() {
+                return Err(error::Error::Conflict("the playlist changed meanwhile; reload it and try again".to_string()));
+            }
+        }
+        
+        Err(error::Error::Conflict("the playlist is changing too fast; try again".to_string()))
+    }
+    
+    /// Replace a playlist on its owner's record, provided the stored copy is
+    /// still at `expected` and where it was when `owner` was read. Returns
+    /// whether it was, in which case nothing written in between is lost.
+    async fn store(&self, owner: &User, playlist: &Playlist, expected: u64) -> Result<bool, error::Error> {
+        let index = playlist_index(owner, playlist.id)?;
+        
+        // Closures in queries can't see bound parameters, so the playlist
+        // is replaced by its position rather than matched by id
+        let mut response = self.db
+            .query(
+                "UPDATE $record SET
+                    playlists[$index] = $playlist,
+                    updated_at = $now
+                WHERE playlists[$index].id = $playlist.id AND (playlists[$index].revision ?? 0) = $expected
+                RETURN VALUE record::id(id)",
+            )
+            .bind(("record", record_id("users", owner.id)))
+            .bind(("index", index))
+            .bind(("playlist", to_content(playlist)?))
+            .bind(("now", self.db.now().to_rfc3339()))
+            .bind(("expected", expected))
+            .await?;
+        self.db.invalidate_cached("users", owner.id);
+        
+        let updated: Vec<String> = take_rows(&mut response, 0)?;
+        Ok(!updated.is_empty())
+    }
+}
+
+/// Where a playlist is in its owner's `playlists`
+fn playlist_index(owner: &User, playlist_id: PlaylistId) -> Result<usize, error::Error> {
+    owner.playlists.iter()
+        .flatten()
+        .position(|p| p.id == playlist_id)
+        .ok_or(error::Error::PlaylistNotFound)
+}
@@ -0,0 +1,93 @@
+use uuid::Uuid;
+use crate::types::id::UserId;
+use crate::types::push::{PushSubscription, MAX_PUSH_SUBSCRIPTIONS};
+use super::{error, record_id, take_rows, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct PushOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> PushOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        PushOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> PushOperations<'static> {
+        PushOperations::new(&DB)
+    }
+    
+    /// Store a browser's subscription. A browser has one endpoint, so an
+    /// earlier subscription with it is replaced, whoever it belonged to,
+    /// and past the cap the user's oldest ones are dropped.
+    pub async fn subscribe(&self, subscription: PushSubscription) -> Result<PushSubscription, error::Error> {
+        self.db.query("DELETE push_subscriptions WHERE endpoint = $endpoint")
+            .bind(("endpoint", subscription.endpoint.clone()))
+            .await?
+            .check()?;
+        
+        let created: Option<PushSubscription> = self.db.create_record("push_subscriptions", subscription.id, &subscription).await?;
+        let created = created.ok_or(error::Error::Db("Failed to store push subscription".to_string()))?;
+        
+        let mut response = self.db
+            .query("SELECT VALUE record::id(id) FROM push_subscriptions WHERE user_id = $user_id ORDER BY created_at DESC START $keep")
+            .bind(("user_id", created.user_id.to_string()))
+            .bind(("keep", MAX_PUSH_SUBSCRIPTIONS))
+            .await?;
+        let evicted: Vec<String> = take_rows(&mut response, 0)?;
+        if !evicted.is_empty() {
+            self.db.query("DELETE push_subscriptions WHERE record::id(id) INSIDE $ids")
+                .bind(("ids", evicted))
+                .await?
+                .check()?;
+        }
+        
+        Ok(created)
+    }
+    
+    /// A user's subscriptions, newest first
+    pub async fn get_subscriptions(&self, user_id: UserId) -> Result<Vec<PushSubscription>, error::Error> {
+        self.subscriptions_for(&[user_id]).await
+    }
+    
+    /// The subscriptions of any of `user_ids`, newest first
+    pub async fn subscriptions_for(&self, user_ids: &[UserId]) -> Result<Vec<PushSubscription>, error::Error> {
+        let ids: Vec<String> = user_ids.iter().map(ToString::to_string).collect();
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM push_subscriptions WHERE user_id INSIDE $ids ORDER BY created_at DESC")
+            .bind(("ids", ids))
+            .await?;
+        
+        take_rows(&mut response, 0)
+    }
+    
+    /// Remove one of a user's subscriptions
+    pub async fn unsubscribe(&self, user_id: UserId, subscription_id: Uuid) -> Result<(), error::Error> {
+        let mut response = self.db
+            .query("DELETE $record WHERE user_id = $user_id RETURN BEFORE")
+            .bind(("record", record_id("push_subscriptions", subscription_id)))
+            .bind(("user_id", user_id.to_string()))
+            .await?;
+        let deleted: Vec<serde_json::Value> = response.take(0)?;
+        
+        if deleted.is_empty() {
+            return Err(error::Error::NotFound);
+        }
+        Ok(())
+    }
+    
+    /// Forget a subscription its push service says is gone
+    pub async fn prune(&self, subscription_id: Uuid) -> Result<(), error::Error> {
+        self.db.delete_record("push_subscriptions", subscription_id).await
+    }
+    
+    pub async fn delete_subscriptions_for_user(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE push_subscriptions WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,48 @@
+use serde::Deserialize;
+use serde_json::Value;
+use uuid::Uuid;
+use crate::reconcile::Counter;
+use super::{error, record_id, take_rows, Db, DB};
+
+/// A record's denormalized value next to the recounted one
+#[derive(Debug, Deserialize)]
+pub struct Recount {
+    pub id: Uuid,
+    pub stored: Value,
+    pub actual: Value,
+}
+
+#[derive(Clone, Copy)]
+pub struct ReconcileOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> ReconcileOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        ReconcileOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> ReconcileOperations<'static> {
+        ReconcileOperations::new(&DB)
+    }
+    
+    /// Run a counter's recount query
+    pub async fn recount(&self, counter: &Counter) -> Result<Vec<Recount>, error::Error> {
+        let mut response = self.db.query(counter.recount).await?;
+        
+        take_rows(&mut response, 0)
+    }
+    
+    /// Write the true value into a counter's field
+    pub async fn fix(&self, counter: &Counter, id: Uuid, value: Value) -> Result<(), error::Error> {
+        self.db.query(format!("UPDATE $record SET {} = $value", counter.field))
+            .bind(("record", record_id(counter.table, id)))
+            .bind(("value", value))
+            .await?
+            .check()?;
+        self.db.invalidate_cached(counter.table, id);
+            
+        Ok(())
+    }
+}
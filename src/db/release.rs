@@ -0,0 +1,162 @@
+use uuid::Uuid;
+use crate::types::id::{TrackId, UserId};
+use crate::types::notification::NotificationKind;
+use crate::types::release::Release;
+use crate::types::user::User;
+use super::notification::NotificationOperations;
+use super::{error, take_rows, UserOperations, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct ReleaseOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> ReleaseOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        ReleaseOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> ReleaseOperations<'static> {
+        ReleaseOperations::new(&DB)
+    }
+    
+    /// Store a new draft release
+    pub async fn create_release(&self, release: Release) -> Result<Release, error::Error> {
+        let artist = UserOperations::new(self.db).get_user_by_id(release.user_id).await?;
+        check_tracks(&artist, &release.track_ids)?;
+        
+        let created: Option<Release> = self.db.create_record("releases", release.id, &release).await?;
+        
+        created.ok_or(error::Error::Db("Failed to create release".to_string()))
+    }
+    
+    /// Get release by ID
+    pub async fn get_release(&self, release_id: Uuid) -> Result<Release, error::Error> {
+        let release: Option<Release> = self.db.select_record("releases", release_id).await?;
+        
+        release.ok_or(error::Error::ReleaseNotFound)
+    }
+    
+    /// Get release by ID, only if it belongs to `user_id`
+    pub async fn get_owned_release(&self, release_id: Uuid, user_id: UserId) -> Result<Release, error::Error> {
+        let release = self.get_release(release_id).await?;
+        
+        if release.user_id != user_id {
+            // Don't reveal drafts the caller couldn't see anyway
+            return Err(if release.is_visible_to(Some(user_id)) {
+                error::Error::Forbidden
+            } else {
+                error::Error::ReleaseNotFound
+            });
+        }
+        
+        Ok(release)
+    }
+    
+    /// An artist's releases, newest first. Drafts only with `include_drafts`.
+    pub async fn list_releases(&self, user_id: UserId, include_drafts: bool) -> Result<Vec<Release>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT *, record::id(id) AS id FROM releases
+                WHERE user_id = $user_id AND ($include_drafts OR published_at != NONE AND published_at != NULL)
+                ORDER BY release_date DESC, created_at DESC"
+            )
+            .bind(("user_id", user_id.to_string()))
+            .bind(("include_drafts", include_drafts))
+            .await?;
+        
+        take_rows(&mut response, 0)
+    }
+    
+    /// Releases a track appears on. Drafts only with `include_drafts`.
+    pub async fn releases_for_track(&self, track_id: TrackId, include_drafts: bool) -> Result<Vec<Release>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT *, record::id(id) AS id FROM releases
+                WHERE track_ids CONTAINS $track_id AND ($include_drafts OR published_at != NONE AND published_at != NULL)
+                ORDER BY created_at ASC"
+            )
+            .bind(("track_id", track_id.to_string()))
+            .bind(("include_drafts", include_drafts))
+            .await?;
+        
+        take_rows(&mut response, 0)
+    }
+    
+    /// Persist changes to a release
+    pub async fn save_release(&self, release: Release) -> Result<Release, error::Error> {
+        let artist = UserOperations::new(self.db).get_user_by_id(release.user_id).await?;
+        check_tracks(&artist, &release.track_ids)?;
+        
+        let saved: Option<Release> = self.db.update_record("releases", release.id, &release).await?;
+        
+        saved.ok_or(error::Error::ReleaseNotFound)
+    }
+    
+    /// Delete a release. Its tracks are only detached, never deleted.
+    pub async fn delete_release(&self, release_id: Uuid) -> Result<(), error::Error> {
+        self.db.delete_record("releases", release_id).await
+    }
+    
+    /// Publish a draft release and make every track on it public in one
+    /// transaction, then notify the artist's followers once for the release
+    pub async fn publish(&self, release_id: Uuid, user_id: UserId) -> Result<Release, error::Error> {
+        let mut release = self.get_owned_release(release_id, user_id).await?;
+        if release.published_at.is_some() {
+            return Err(error::Error::Validation("release is already published".to_string()));
+        }
+        if release.track_ids.is_empty() {
+            return Err(error::Error::Validation("a release needs at least one track".to_string()));
+        }
+        
+        let mut artist = UserOperations::new(self.db).get_user_by_id(user_id).await?;
+        check_tracks(&artist, &release.track_ids)?;
+        
+        let now = self.db.now();
+        let uploads = artist.profile.as_mut().and_then(|p| p.uploads.as_mut());
+        for track in uploads.into_iter().flatten().filter(|t| release.track_ids.contains(&t.id) && !t.is_public) {
+            track.is_public = true;
+            track.updated_at = now;
+        }
+        artist.updated_at = now;
+        release.published_at = Some(now);
+        release.updated_at = now;
+        
+        self.db.transaction(|tx| {
+            tx.update("users", user_id, &artist)?;
+            tx.update("releases", release.id, &release)
+        }).await?;
+        
+        let followers = artist.profile.as_ref().and_then(|p| p.followers.clone()).unwrap_or_default();
+        NotificationOperations::new(self.db).notify_many(
+            &followers,
+            NotificationKind::ReleasePublished,
+            format!("{} released {}", artist.username, release.title),
+            serde_json::json!({ "release_id": release.id, "user_id": user_id }),
+        ).await?;
+        
+        Ok(release)
+    }
+    
+    /// Delete every release a user made
+    pub async fn delete_releases_for_user(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE releases WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+        
+        Ok(())
+    }
+}
+
+/// Releases may only list the artist's own tracks that aren't deleted
+fn check_tracks(artist: &User, track_ids: &[TrackId]) -> Result<(), error::Error> {
+    let uploads = artist.profile.as_ref().and_then(|p| p.uploads.as_ref());
+    let owns = |id: &TrackId| uploads.is_some_and(|uploads| uploads.iter().any(|t| t.id == *id && !t.is_deleted));
+    
+    match track_ids.iter().find(|id| !owns(id)) {
+        Some(id) => Err(error::Error::Validation(format!("track {} is not one of your tracks", id))),
+        None => Ok(()),
+    }
+}
@@ -0,0 +1,157 @@
+use uuid::Uuid;
+use crate::types::id::UserId;
+use crate::types::user::{Report, ReportStatus, ReportTarget, ReportTargetKind, TargetReportCount};
+use super::{error, record_id, take_row, take_rows, to_content, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct ReportOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> ReportOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        ReportOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> ReportOperations<'static> {
+        ReportOperations::new(&DB)
+    }
+    
+    /// File a report against a user, track or comment
+    pub async fn create_report(
+        &self,
+        reporter_id: UserId,
+        target: ReportTarget,
+        reason: String,
+        description: Option<String>,
+    ) -> Result<Report, error::Error> {
+        let now = self.db.now();
+        let report_id = Uuid::new_v4();
+        
+        let report = Report {
+            id: report_id,
+            user_id: reporter_id,
+            target,
+            reason,
+            description,
+            created_at: now,
+            updated_at: now,
+            status: ReportStatus::Open,
+        };
+        
+        let created: Option<Report> = self.db.create_record("reports", report_id, &report).await?;
+            
+        created.ok_or(error::Error::Db("Failed to create report".to_string()))
+    }
+    
+    /// Reports with pagination, oldest first so the queue is worked in
+    /// order, optionally only those against one kind of target
+    pub async fn list_reports(
+        &self,
+        kind: Option<ReportTargetKind>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<Report>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT *, record::id(id) AS id FROM reports
+                WHERE $kind = '' OR target.type = $kind
+                ORDER BY created_at ASC LIMIT $limit START $offset"
+            )
+            .bind(("kind", kind.map_or("", ReportTargetKind::name)))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// Count the reports `list_reports` would return across all pages
+    pub async fn count_reports(&self, kind: Option<ReportTargetKind>) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query("SELECT count() FROM reports WHERE $kind = '' OR target.type = $kind GROUP ALL")
+            .bind(("kind", kind.map_or("", ReportTargetKind::name)))
+            .await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Move a report along the queue
+    pub async fn set_status(&self, report_id: Uuid, status: ReportStatus) -> Result<Report, error::Error> {
+        let mut response = self.db
+            .query("UPDATE $record SET status = $status, updated_at = $now RETURN *, record::id(id) AS id")
+            .bind(("record", record_id("reports", report_id)))
+            .bind(("status", to_content(&status)?))
+            .bind(("now", self.db.now().to_rfc3339()))
+            .await?;
+        let report: Option<Report> = take_row(&mut response, 0)?;
+        
+        report.ok_or(error::Error::NotFound)
+    }
+    
+    /// Count the open reports against one target
+    pub async fn count_open_against(&self, target: ReportTarget) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT count() FROM reports
+                WHERE status = 'Open' AND target.type = $kind AND target.id = $id GROUP ALL"
+            )
+            .bind(("kind", target.kind().name()))
+            .bind(("id", target.id().to_string()))
+            .await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Count the open reports against everything
+    pub async fn count_open(&self) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query("SELECT count() FROM reports WHERE status = 'Open' GROUP ALL")
+            .await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// The targets with the most open reports, most first
+    pub async fn most_reported_targets(&self, limit: u32) -> Result<Vec<TargetReportCount>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT target, count() AS open_reports FROM reports
+                WHERE status = 'Open' GROUP BY target
+                ORDER BY open_reports DESC LIMIT $limit"
+            )
+            .bind(("limit", limit))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// Which of `target_ids` have a report against them that's still open or
+    /// being worked on
+    pub async fn reported_among(&self, kind: ReportTargetKind, target_ids: &[Uuid]) -> Result<Vec<Uuid>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT VALUE target.id FROM reports
+                WHERE target.type = $kind AND target.id IN $ids AND status IN ['Open', 'InProgress']"
+            )
+            .bind(("kind", kind.name()))
+            .bind(("ids", target_ids.iter().map(ToString::to_string).collect::<Vec<_>>()))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// Delete every report a user filed
+    pub async fn delete_reports_by_user(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE reports WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+            
+        Ok(())
+    }
+}
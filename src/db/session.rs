@@ -0,0 +1,98 @@
+use chrono::Duration;
+use uuid::Uuid;
+use crate::auth;
+use crate::types::id::UserId;
+use crate::types::session::Session;
+use super::{error, take_row, Db, DB};
+
+/// How long a freshly issued session stays valid
+pub const SESSION_TTL: Duration = Duration::days(30);
+
+/// How long an admin may act as another user before signing in again
+pub const IMPERSONATION_TTL: Duration = Duration::minutes(15);
+
+#[derive(Clone, Copy)]
+pub struct SessionOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> SessionOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        SessionOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> SessionOperations<'static> {
+        SessionOperations::new(&DB)
+    }
+    
+    /// Create a session for a user, returning it along with the plain bearer token
+    pub async fn create_session(&self, user_id: UserId) -> Result<(Session, String), error::Error> {
+        self.issue(user_id, None, SESSION_TTL).await
+    }
+    
+    /// Create a short-lived session for `impersonator_id` to act as `user_id`
+    pub async fn create_impersonation_session(&self, user_id: UserId, impersonator_id: UserId) -> Result<(Session, String), error::Error> {
+        self.issue(user_id, Some(impersonator_id), IMPERSONATION_TTL).await
+    }
+    
+    async fn issue(&self, user_id: UserId, impersonator_id: Option<UserId>, ttl: Duration) -> Result<(Session, String), error::Error> {
+        let token = auth::generate_token();
+        let now = self.db.now();
+        let session_id = Uuid::new_v4();
+        
+        let session = Session {
+            id: session_id,
+            user_id,
+            token_hash: auth::hash_token(&token),
+            created_at: now,
+            expires_at: now + ttl,
+            impersonator_id,
+        };
+        
+        let created: Option<Session> = self.db.create_record("sessions", session_id, &session).await?;
+        let created = created.ok_or(error::Error::Db("Failed to create session".to_string()))?;
+        
+        Ok((created, token))
+    }
+    
+    /// Look up an unexpired session by its bearer token
+    pub async fn get_session_by_token(&self, token: &str) -> Result<Session, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM sessions WHERE token_hash = $token_hash")
+            .bind(("token_hash", auth::hash_token(token)))
+            .await?;
+        let session: Option<Session> = take_row(&mut response, 0)?;
+        
+        match session {
+            Some(session) if session.expires_at > self.db.now() => Ok(session),
+            _ => Err(error::Error::Unauthorized),
+        }
+    }
+    
+    /// Delete a single session
+    pub async fn delete_session(&self, session_id: Uuid) -> Result<(), error::Error> {
+        self.db.delete_record("sessions", session_id).await
+    }
+    
+    /// Delete every session belonging to a user
+    pub async fn delete_sessions_for_user(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE sessions WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+            
+        Ok(())
+    }
+    
+    /// Delete every impersonation session an admin started, returning how many there were
+    pub async fn delete_impersonation_sessions(&self, impersonator_id: UserId) -> Result<usize, error::Error> {
+        let mut response = self.db
+            .query("DELETE sessions WHERE impersonator_id = $impersonator_id RETURN BEFORE")
+            .bind(("impersonator_id", impersonator_id.to_string()))
+            .await?;
+        let deleted: Vec<serde_json::Value> = response.take(0)?;
+            
+        Ok(deleted.len())
+    }
+}
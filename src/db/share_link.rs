@@ -0,0 +1,90 @@
+use uuid::Uuid;
+use crate::auth;
+use crate::types::id::{TrackId, UserId};
+use crate::types::share_link::ShareLink;
+use super::{error, take_row, take_rows, Db, DB};
+
+/// Most share links one track may have, expired ones included
+pub const MAX_LINKS_PER_TRACK: usize = 50;
+
+#[derive(Clone, Copy)]
+pub struct ShareLinkOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> ShareLinkOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        ShareLinkOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> ShareLinkOperations<'static> {
+        ShareLinkOperations::new(&DB)
+    }
+    
+    /// Store a new share link, unless the track already has too many
+    pub async fn create_link(&self, link: ShareLink) -> Result<ShareLink, error::Error> {
+        if self.list_links(link.track_id).await?.len() >= MAX_LINKS_PER_TRACK {
+            return Err(error::Error::Conflict(format!("a track can have at most {} share links", MAX_LINKS_PER_TRACK)));
+        }
+        
+        let created: Option<ShareLink> = self.db.create_record("share_links", link.id, &link).await?;
+        
+        created.ok_or(error::Error::Db("Failed to create share link".to_string()))
+    }
+    
+    /// Get a track's share link by ID
+    pub async fn get_link(&self, track_id: TrackId, link_id: Uuid) -> Result<ShareLink, error::Error> {
+        let link: Option<ShareLink> = self.db.select_record("share_links", link_id).await?;
+        
+        link.filter(|link| link.track_id == track_id).ok_or(error::Error::NotFound)
+    }
+    
+    /// A track's share links, newest first
+    pub async fn list_links(&self, track_id: TrackId) -> Result<Vec<ShareLink>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM share_links WHERE track_id = $track_id ORDER BY created_at DESC")
+            .bind(("track_id", track_id.to_string()))
+            .await?;
+        
+        take_rows(&mut response, 0)
+    }
+    
+    /// Revoke a share link
+    pub async fn delete_link(&self, link_id: Uuid) -> Result<(), error::Error> {
+        self.db.delete_record("share_links", link_id).await
+    }
+    
+    /// Use a share link, counting the use. The expiry and use limit are
+    /// checked in the same statement that counts it, so concurrent requests
+    /// can't open a link more than `max_uses` times. Returns None for an
+    /// unknown, expired or used up token, or one for a different track than
+    /// `track_id` when that's given.
+    pub async fn redeem(&self, token: &str, track_id: Option<TrackId>) -> Result<Option<ShareLink>, error::Error> {
+        let mut response = self.db
+            .query(
+                "UPDATE share_links SET use_count += 1, last_used_at = $now WHERE
+                token_hash = $token_hash AND
+                ($track_id = NONE OR $track_id = NULL OR track_id = $track_id) AND
+                (expires_at = NONE OR expires_at = NULL OR <datetime> expires_at > <datetime> $now) AND
+                (max_uses = NONE OR max_uses = NULL OR use_count < max_uses)
+                RETURN *, record::id(id) AS id"
+            )
+            .bind(("token_hash", auth::hash_token(token)))
+            .bind(("track_id", track_id.map(|id| id.to_string())))
+            .bind(("now", self.db.now().to_rfc3339()))
+            .await?;
+        
+        take_row(&mut response, 0)
+    }
+    
+    /// Delete every share link a user made
+    pub async fn delete_links_for_user(&self, owner_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE share_links WHERE owner_id = $owner_id")
+            .bind(("owner_id", owner_id.to_string()))
+            .await?
+            .check()?;
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+use super::{error, take_rows, Db, DB};
+
+/// Users whose profile and content may be listed publicly. Queries using it
+/// bind `$now`, when temporary bans lift.
+pub(crate) const LISTABLE_USERS: &str = "profile.is_private = false AND profile.is_deleted = false
+    AND (profile.is_banned = false OR (profile.banned_until != NONE AND <datetime> profile.banned_until <= <datetime> $now))";
+
+#[derive(Debug, Deserialize)]
+pub struct ProfileEntry {
+    pub id: Uuid,
+    pub username: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ContentEntry {
+    pub id: Uuid,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SitemapBatch {
+    pub profile: ProfileEntry,
+    #[serde(default)]
+    pub tracks: Vec<ContentEntry>,
+    #[serde(default)]
+    pub playlists: Vec<ContentEntry>,
+}
+
+#[derive(Clone, Copy)]
+pub struct SitemapOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> SitemapOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        SitemapOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> SitemapOperations<'static> {
+        SitemapOperations::new(&DB)
+    }
+    
+    /// One page of listable users with only the ids and timestamps of their
+    /// public tracks and playlists, ordered by id so pages are stable
+    pub async fn get_batch(&self, after: Option<Uuid>, limit: u32) -> Result<Vec<SitemapBatch>, error::Error> {
+        let mut response = self.db
+            .query(format!(
+                "SELECT
+                    {{ id: record::id(id), username: username, updated_at: updated_at }} AS profile,
+                    (profile.uploads[WHERE is_public = true AND is_deleted = false] ?? []).map(|$t| {{ id: $t.id, updated_at: $t.updated_at }}) AS tracks,
+                    (playlists[WHERE is_public = true AND is_deleted = false] ?? []).map(|$p| {{ id: $p.id, updated_at: $p.updated_at }}) AS playlists
+                FROM users
+                WHERE {LISTABLE_USERS} AND ($after = NONE OR record::id(id) > $after)
+                ORDER BY id LIMIT $limit"
+            ))
+            .bind(("after", after.map(|id| id.to_string())))
+            .bind(("now", self.db.now().to_rfc3339()))
+            .bind(("limit", limit))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+}
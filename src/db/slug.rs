@@ -0,0 +1,106 @@
+use std::collections::HashSet;
+use serde::Deserialize;
+use uuid::Uuid;
+use crate::slug;
+use super::{error, take_rows, Db, DB};
+
+/// Tries at finding free slugs before giving up
+const SLUG_ATTEMPTS: usize = 5;
+
+/// Which kind of thing a slug names. Each kind has slugs of its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlugScope {
+    Tracks,
+    Playlists,
+}
+
+impl SlugScope {
+    /// Where the things are kept on a user record
+    fn path(&self) -> &'static str {
+        match self {
+            SlugScope::Tracks => "profile.uploads",
+            SlugScope::Playlists => "playlists",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Slugged {
+    slug: Option<String>,
+    #[serde(default)]
+    slug_aliases: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+pub struct SlugOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> SlugOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        SlugOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> SlugOperations<'static> {
+        SlugOperations::new(&DB)
+    }
+    
+    /// Slugs for things titled `titles`, in order, taken neither by each
+    /// other nor by anything in `scope` but `except`, current slug or alias.
+    /// Each is its title's own slug unless that's taken.
+    pub async fn free_slugs(&self, scope: SlugScope, titles: &[&str], except: Option<Uuid>) -> Result<Vec<String>, error::Error> {
+        let bases: Vec<String> = titles.iter().map(|title| slug::slugify(title)).collect();
+        let mut candidates = bases.clone();
+        let mut slugs: Vec<Option<String>> = vec![None; bases.len()];
+        
+        for _ in 0..SLUG_ATTEMPTS {
+            let pending: Vec<String> = slugs.iter()
+                .zip(&candidates)
+                .filter(|(slug, _)| slug.is_none())
+                .map(|(_, candidate)| candidate.clone())
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+            
+            let mut taken = self.taken(scope, &pending, except).await?;
+            taken.extend(slugs.iter().flatten().cloned());
+            for (index, slug) in slugs.iter_mut().enumerate() {
+                if slug.is_some() {
+                    continue;
+                }
+                if taken.insert(candidates[index].clone()) {
+                    *slug = Some(candidates[index].clone());
+                } else {
+                    candidates[index] = slug::with_suffix(&bases[index]);
+                }
+            }
+        }
+        
+        slugs.into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| error::Error::Conflict("couldn't find a free slug; try again".to_string()))
+    }
+    
+    /// Which of `slugs` something in `scope` other than `except` goes by
+    async fn taken(&self, scope: SlugScope, slugs: &[String], except: Option<Uuid>) -> Result<HashSet<String>, error::Error> {
+        let mut response = self.db
+            .query(format!(
+                "SELECT VALUE ({path} ?? [])[WHERE id != $except AND (slug INSIDE $slugs OR (slug_aliases ?? []) ANYINSIDE $slugs)]
+                FROM users WHERE {path}.*.slug ANYINSIDE $slugs OR array::flatten(({path} ?? []).*.slug_aliases ?? []) ANYINSIDE $slugs",
+                path = scope.path()
+            ))
+            .bind(("slugs", slugs.to_vec()))
+            .bind(("except", except.map(|id| id.to_string())))
+            .await?;
+        let owners: Vec<Vec<Slugged>> = take_rows(&mut response, 0)?;
+        
+        Ok(owners
+            .into_iter()
+            .flatten()
+            .flat_map(|slugged| slugged.slug.into_iter().chain(slugged.slug_aliases))
+            .filter(|slug| slugs.contains(slug))
+            .collect())
+    }
+}
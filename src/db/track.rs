@@ -0,0 +1,1077 @@
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use chrono::{DateTime, Utc};
+use tracing::warn;
+use uuid::Uuid;
+use crate::attachments::{MAX_ATTACHMENTS_PER_TRACK, MAX_TRACK_ATTACHMENT_BYTES};
+use crate::{import, realtime, slug};
+use crate::types::attachment::Attachment;
+use crate::types::audio::{AudioReplacement, AudioVersion, MAX_AUDIO_VERSIONS};
+use crate::types::credit::{Credit, CreditInput, CreditResponse, CreditStatus, MAX_CREDITS};
+use crate::types::id::{CommentId, TrackId, UserId};
+use crate::types::import::{TrackImportResult, TrackManifestEntry};
+use crate::types::language;
+use crate::types::license::{License, LicenseChange};
+use crate::types::notification::NotificationKind;
+use crate::types::pagination::{cursor_page, ordered_page, Cursor};
+use crate::types::realtime::ServerMessage;
+use crate::types::user::{Comment, CommentFilter, GenreCount, OrphanTrack, Playlist, ProfileTrackOrder, ReportTargetKind, Track, TrackPatch, TrackSort, TrackView, User, UserProfile};
+use super::notification::NotificationOperations;
+use super::report::ReportOperations;
+use super::sitemap::LISTABLE_USERS;
+use super::slug::{SlugOperations, SlugScope};
+use super::{add_id, error, record_id, remove_id, take_row, take_rows, UserOperations, Db, DB};
+
+/// Longest comment accepted, in characters
+pub const MAX_COMMENT_LEN: usize = 2000;
+
+/// Most tracks one bulk delete may name
+pub const MAX_BULK_DELETE: usize = 100;
+
+/// Filter over a user's uploads for the tracks a viewer may see
+fn visible_uploads(include_private: bool) -> &'static str {
+    if include_private {
+        "profile.uploads[WHERE is_deleted = false]"
+    } else {
+        "profile.uploads[WHERE is_public = true AND is_deleted = false]"
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct TrackOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> TrackOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        TrackOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> TrackOperations<'static> {
+        TrackOperations::new(&DB)
+    }
+    
+    /// Get the user whose uploads contain a track
+    pub async fn get_owner(&self, track_id: TrackId) -> Result<User, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users WHERE profile.uploads.*.id CONTAINS $track_id")
+            .bind(("track_id", track_id.to_string()))
+            .await?;
+        let owner: Option<User> = take_row(&mut response, 0)?;
+        
+        owner.ok_or(error::Error::TrackNotFound)
+    }
+    
+    /// Get track by ID
+    pub async fn get_track(&self, track_id: TrackId) -> Result<Track, error::Error> {
+        let (_, track) = self.get_track_with_owner(track_id).await?;
+        Ok(track)
+    }
+    
+    /// Get track by ID along with the user who uploaded it
+    pub async fn get_track_with_owner(&self, track_id: TrackId) -> Result<(User, Track), error::Error> {
+        let owner = self.get_owner(track_id).await?;
+        
+        let track = find_track(owner.profile.as_ref().and_then(|p| p.uploads.as_ref()), track_id)
+            .ok_or(error::Error::TrackNotFound)?;
+        
+        Ok((owner, track))
+    }
+    
+    /// The track going by `slug`, and whether that's its current slug
+    /// rather than one it had before a rename
+    pub async fn get_track_by_slug(&self, slug: &str) -> Result<(Track, bool), error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users WHERE profile.uploads.*.slug CONTAINS $slug OR array::flatten((profile.uploads ?? []).*.slug_aliases ?? []) CONTAINS $slug")
+            .bind(("slug", slug.to_string()))
+            .await?;
+        let owners: Vec<User> = take_rows(&mut response, 0)?;
+        let tracks: Vec<Track> = owners.into_iter()
+            .flat_map(|owner| owner.profile.and_then(|p| p.uploads).unwrap_or_default())
+            .collect();
+        
+        if let Some(track) = tracks.iter().find(|track| track.slug.as_deref() == Some(slug)) {
+            return Ok((track.clone(), true));
+        }
+        tracks.into_iter()
+            .find(|track| track.slug_aliases.iter().any(|alias| alias == slug))
+            .map(|track| (track, false))
+            .ok_or(error::Error::TrackNotFound)
+    }
+    
+    /// Load several tracks at once. Missing ids are skipped.
+    pub async fn get_tracks(&self, track_ids: &[TrackId]) -> Result<Vec<Track>, error::Error> {
+        if track_ids.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let ids: Vec<String> = track_ids.iter().map(ToString::to_string).collect();
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users WHERE profile.uploads.*.id CONTAINSANY $ids")
+            .bind(("ids", ids))
+            .await?;
+        let owners: Vec<User> = take_rows(&mut response, 0)?;
+        
+        Ok(owners
+            .into_iter()
+            .flat_map(|owner| owner.profile.and_then(|p| p.uploads).unwrap_or_default())
+            .filter(|track| track_ids.contains(&track.id))
+            .collect())
+    }
+    
+    /// One page of a user's non-deleted tracks in profile order, only public
+    /// ones unless `include_private` and only those under `license` when given
+    pub async fn list_tracks(
+        &self,
+        user_id: UserId,
+        include_private: bool,
+        license: Option<License>,
+        after: Option<Cursor>,
+        limit: u32,
+    ) -> Result<(Vec<Track>, Option<Cursor>), error::Error> {
+        let user = UserOperations::new(self.db).get_user_by_id(user_id).await?;
+        let Some(mut profile) = user.profile else {
+            return Ok((Vec::new(), None));
+        };
+        
+        let mut tracks: Vec<Track> = profile.uploads
+            .take()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|track| !track.is_deleted && (track.is_public || include_private))
+            .filter(|track| license.is_none_or(|license| track.license == license))
+            .collect();
+        sort_for_profile(&mut tracks, &profile);
+        
+        ordered_page(tracks, |track| (track.created_at, track.id), after, limit)
+    }
+    
+    /// One page of the top-level comments on a track the viewer may see,
+    /// newest first, narrowed by `filter`. The caller checks the viewer may
+    /// use filters meant for moderators.
+    pub async fn list_comments(
+        &self,
+        track_id: TrackId,
+        viewer: Option<UserId>,
+        filter: CommentFilter,
+        after: Option<Cursor>,
+        limit: u32,
+    ) -> Result<(Vec<Comment>, Option<Cursor>), error::Error> {
+        let track = self.get_track(track_id).await?;
+        if !track.is_visible_to(viewer) {
+            return Err(error::Error::TrackNotFound);
+        }
+        
+        let comments = track.comments.unwrap_or_default();
+        let reported: HashSet<Uuid> = match filter {
+            CommentFilter::Reported => {
+                let ids: Vec<Uuid> = comments.iter().map(|comment| comment.id.into()).collect();
+                ReportOperations::new(self.db).reported_among(ReportTargetKind::Comment, &ids).await?.into_iter().collect()
+            }
+            _ => HashSet::new(),
+        };
+        let comments: Vec<Comment> = comments
+            .into_iter()
+            .filter(|comment| match filter {
+                CommentFilter::All => !comment.is_deleted,
+                CommentFilter::PinnedOnly => !comment.is_deleted && comment.is_pinned,
+                CommentFilter::Reported => reported.contains(&comment.id.as_uuid()),
+                CommentFilter::IncludeDeleted => true,
+            })
+            .collect();
+        
+        Ok(cursor_page(comments, |comment| (comment.created_at, comment.id), after, limit))
+    }
+    
+    /// Get a comment or reply by ID along with the track it was left on
+    pub async fn get_comment(&self, comment_id: CommentId) -> Result<(Track, Comment), error::Error> {
+        // Comments live inside their track, so find the owner by a text match
+        // and walk the uploads for the exact comment
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users WHERE string::contains(<string> (profile.uploads ?? []), $comment_id)")
+            .bind(("comment_id", comment_id.to_string()))
+            .await?;
+        let owners: Vec<User> = take_rows(&mut response, 0)?;
+        
+        owners
+            .into_iter()
+            .filter_map(|owner| owner.profile?.uploads)
+            .flatten()
+            .find_map(|track| {
+                let comment = find_comment(track.comments.as_ref(), comment_id)?;
+                Some((track, comment))
+            })
+            .ok_or(error::Error::CommentNotFound)
+    }
+    
+    /// Count a user's non-deleted tracks, only public ones unless `include_private`
+    pub async fn count_tracks(&self, user_id: UserId, include_private: bool) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query(format!(
+                "SELECT VALUE array::len({} ?? []) FROM $user",
+                visible_uploads(include_private)
+            ))
+            .bind(("user", record_id("users", user_id)))
+            .await?;
+        let count: Option<u64> = response.take(0)?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Count a user's visible tracks per genre, most common first
+    pub async fn genre_breakdown(&self, user_id: UserId, include_private: bool) -> Result<Vec<GenreCount>, error::Error> {
+        let mut response = self.db
+            .query(format!(
+                "SELECT genre, count() AS count FROM array::flatten(
+                    (SELECT VALUE {} ?? [] FROM $user)
+                ) GROUP BY genre ORDER BY count DESC",
+                visible_uploads(include_private)
+            ))
+            .bind(("user", record_id("users", user_id)))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// One page of the public tracks in a genre, across every listable user,
+    /// and how many there are in all. `genre` is matched case-insensitively.
+    pub async fn tracks_in_genre(
+        &self,
+        genre: &str,
+        sort: TrackSort,
+        exclude_explicit: bool,
+        limit: u32,
+        offset: u32,
+    ) -> Result<(Vec<Track>, u64), error::Error> {
+        let explicit = if exclude_explicit { " AND (is_explicit ?? false) = false" } else { "" };
+        let mut response = self.db
+            .query(format!(
+                "LET $tracks = array::flatten(
+                    (SELECT VALUE profile.uploads[WHERE is_public = true AND is_deleted = false AND string::lowercase(genre ?? '') = $genre{}] ?? []
+                    FROM users WHERE {LISTABLE_USERS})
+                );
+                RETURN array::len($tracks);
+                SELECT * FROM $tracks ORDER BY {} LIMIT $limit START $offset;",
+                explicit,
+                sort.order_by()
+            ))
+            .bind(("genre", genre.to_lowercase()))
+            .bind(("now", self.db.now().to_rfc3339()))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        let total: Option<u64> = response.take(1)?;
+        let tracks = take_rows(&mut response, 2)?;
+        
+        Ok((tracks, total.unwrap_or(0)))
+    }
+    
+    /// Add every valid manifest entry to a user's uploads in one transaction.
+    /// Invalid entries are reported in the results and don't stop the rest.
+    pub async fn import_manifest(
+        &self,
+        user_id: UserId,
+        entries: Vec<TrackManifestEntry>,
+    ) -> Result<Vec<TrackImportResult>, error::Error> {
+        if entries.len() > import::MAX_MANIFEST_ENTRIES {
+            return Err(error::Error::Validation(format!(
+                "a manifest may hold at most {} tracks",
+                import::MAX_MANIFEST_ENTRIES
+            )));
+        }
+        
+        let mut user = UserOperations::new(self.db).get_user_by_id(user_id).await?;
+        let now = self.db.now();
+        
+        let mut results = Vec::with_capacity(entries.len());
+        let mut tracks = Vec::new();
+        for (index, entry) in entries.into_iter().enumerate() {
+            if let Err(reason) = import::validate_track(&entry) {
+                results.push(TrackImportResult { index, track_id: None, error: Some(reason) });
+                continue;
+            }
+            
+            let track = Track {
+                id: TrackId::new(),
+                user_id,
+                title: entry.title.trim().to_string(),
+                description: entry.description,
+                audio_url: entry.audio_url.trim().to_string(),
+                cover_image_url: entry.cover_image_url.map(|url| url.trim().to_string()),
+                genre: entry.genre,
+                tags: entry.tags,
+                created_at: entry.created_at.unwrap_or(now),
+                updated_at: now,
+                is_public: entry.is_public,
+                is_deleted: false,
+                likes: 0,
+                dislikes: 0,
+                comments: None,
+                downloadable: entry.downloadable,
+                download_count: 0,
+                technical_metadata: entry.technical_metadata,
+                comment_count: 0,
+                has_lyrics: false,
+                license: entry.license,
+                license_history: Vec::new(),
+                audio_versions: Vec::new(),
+                attachments: Vec::new(),
+                credited_artists: Vec::new(),
+                language: entry.language.as_deref().and_then(|code| language::normalize(code).ok()), // checked above
+                is_explicit: entry.is_explicit,
+                slug: None,
+                slug_aliases: Vec::new(),
+            };
+            results.push(TrackImportResult { index, track_id: Some(track.id), error: None });
+            tracks.push(track);
+        }
+        
+        if tracks.is_empty() {
+            return Ok(results);
+        }
+        
+        let titles: Vec<&str> = tracks.iter().map(|track| track.title.as_str()).collect();
+        let slugs = SlugOperations::new(self.db).free_slugs(SlugScope::Tracks, &titles, None).await?;
+        for (track, slug) in tracks.iter_mut().zip(slugs) {
+            track.slug = Some(slug);
+        }
+        
+        let published: Vec<Track> = tracks.iter().filter(|track| track.is_public).cloned().collect();
+        let profile = user.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        let followers = profile.followers.clone().unwrap_or_default();
+        profile.uploads.get_or_insert_with(Vec::new).extend(tracks);
+        user.updated_at = now;
+        
+        self.db.transaction(|tx| tx.update("users", user_id, &user)).await?;
+        
+        // Followers with a feed open see new public tracks as they land
+        for follower_id in followers.into_iter().filter(|id| realtime::is_listening(*id)) {
+            for track in &published {
+                realtime::push(follower_id, &ServerMessage::FeedTrack { track: TrackView::from(track.clone()) });
+            }
+        }
+        
+        Ok(results)
+    }
+    
+    /// Comment on a track, or reply to `parent_comment_id` on it
+    pub async fn add_comment(
+        &self,
+        track_id: TrackId,
+        author_id: UserId,
+        content: String,
+        parent_comment_id: Option<CommentId>,
+    ) -> Result<Comment, error::Error> {
+        let content = content.trim().to_string();
+        if content.is_empty() || content.chars().count() > MAX_COMMENT_LEN {
+            return Err(error::Error::Validation(format!("comment must be 1 to {} characters", MAX_COMMENT_LEN)));
+        }
+        
+        let mut owner = self.get_owner(track_id).await?;
+        let now = self.db.now();
+        
+        let track = owner.profile.as_mut()
+            .and_then(|p| p.uploads.as_mut())
+            .and_then(|uploads| uploads.iter_mut().find(|t| t.id == track_id))
+            .ok_or(error::Error::TrackNotFound)?;
+        
+        let comment = Comment {
+            id: CommentId::new(),
+            referred_track_id: track_id,
+            user_id: author_id,
+            content,
+            created_at: now,
+            updated_at: now,
+            is_deleted: false,
+            replies: None,
+            likes: None,
+            dislikes: None,
+            is_pinned: false,
+            reports: None,
+            parent_comment_id,
+        };
+        
+        let siblings = match parent_comment_id {
+            Some(parent_id) => &mut find_comment_mut(track.comments.as_mut(), parent_id)
+                .ok_or(error::Error::CommentNotFound)?
+                .replies,
+            None => &mut track.comments,
+        };
+        siblings.get_or_insert_with(Vec::new).push(comment.clone());
+        track.comment_count += 1;
+        
+        // Credited artists who opted in hear about comments, not about their own
+        let credited: Vec<UserId> = track.credited_artists.iter()
+            .filter(|credit| credit.notify_comments)
+            .filter_map(|credit| credit.linked_user())
+            .filter(|id| *id != author_id)
+            .collect();
+        let title = track.title.clone();
+        
+        owner.updated_at = now;
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        NotificationOperations::new(self.db).notify_many(
+            &credited,
+            NotificationKind::CreditComment,
+            format!("New comment on {}", title),
+            serde_json::json!({ "track_id": track_id, "comment_id": comment.id }),
+        ).await?;
+        
+        Ok(comment)
+    }
+    
+    /// Soft-delete a comment or reply. Its author and the track's owner may
+    /// delete it; replies to it are kept.
+    pub async fn delete_comment(&self, track_id: TrackId, comment_id: CommentId, user_id: UserId) -> Result<(), error::Error> {
+        let mut owner = self.get_owner(track_id).await?;
+        let now = self.db.now();
+        
+        let track = owner.profile.as_mut()
+            .and_then(|p| p.uploads.as_mut())
+            .and_then(|uploads| uploads.iter_mut().find(|t| t.id == track_id))
+            .ok_or(error::Error::TrackNotFound)?;
+        let track_owner = track.user_id;
+        
+        let comment = find_comment_mut(track.comments.as_mut(), comment_id)
+            .filter(|comment| !comment.is_deleted)
+            .ok_or(error::Error::CommentNotFound)?;
+        if comment.user_id != user_id && track_owner != user_id {
+            return Err(error::Error::Forbidden);
+        }
+        comment.is_deleted = true;
+        comment.updated_at = now;
+        track.comment_count = track.comment_count.saturating_sub(1);
+        
+        owner.updated_at = now;
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        Ok(())
+    }
+    
+    /// Recount `comment_count` on every upload and fix the ones that drifted.
+    /// Returns how many tracks were checked and how many corrected.
+    pub async fn repair_comment_counts(&self) -> Result<(usize, usize), error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM users WHERE array::len(profile.uploads ?? []) > 0")
+            .await?;
+        let users: Vec<User> = take_rows(&mut response, 0)?;
+        let (mut checked, mut corrected) = (0, 0);
+        
+        for mut user in users {
+            let mut changed = false;
+            for track in user.profile.iter_mut().flat_map(|p| p.uploads.iter_mut().flatten()) {
+                checked += 1;
+                let actual = track.live_comment_count();
+                if track.comment_count != actual {
+                    warn!("Reconciling comment_count for track {}: {} -> {}", track.id, track.comment_count, actual);
+                    track.comment_count = actual;
+                    corrected += 1;
+                    changed = true;
+                }
+            }
+            if changed {
+                let _: Option<User> = self.db.update_record("users", user.id, &user).await?;
+            }
+        }
+        
+        Ok((checked, corrected))
+    }
+    
+    /// Like or dislike a comment on a track, replacing any earlier reaction
+    pub async fn react_to_comment(
+        &self,
+        track_id: TrackId,
+        comment_id: CommentId,
+        user_id: UserId,
+        like: bool,
+    ) -> Result<Comment, error::Error> {
+        let mut owner = self.get_owner(track_id).await?;
+        
+        let comment = owner.profile.as_mut()
+            .and_then(|p| p.uploads.as_mut())
+            .and_then(|uploads| uploads.iter_mut().find(|t| t.id == track_id))
+            .and_then(|track| find_comment_mut(track.comments.as_mut(), comment_id))
+            .ok_or(error::Error::CommentNotFound)?;
+        
+        let (add, remove) = if like {
+            (&mut comment.likes, &mut comment.dislikes)
+        } else {
+            (&mut comment.dislikes, &mut comment.likes)
+        };
+        remove_id(remove, user_id);
+        add_id(add, user_id);
+        let comment = comment.clone();
+        
+        owner.updated_at = self.db.now();
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        Ok(comment)
+    }
+    
+    /// Apply the fields present in `patch` to a track owned by `owner_id`.
+    /// Counters, comments and `created_at` are never touched.
+    pub async fn patch_track(&self, track_id: TrackId, owner_id: UserId, patch: TrackPatch) -> Result<Track, error::Error> {
+        patch.validate()?;
+        
+        let mut owner = self.get_owner(track_id).await?;
+        let now = self.db.now();
+        
+        // A new title gets a new slug, unless it would come out the same
+        let renamed = patch.title.as_deref().map(str::trim).filter(|title| {
+            find_track(owner.profile.as_ref().and_then(|p| p.uploads.as_ref()), track_id).is_some_and(|track| {
+                track.user_id == owner_id && (track.slug.is_none() || slug::slugify(title) != slug::slugify(&track.title))
+            })
+        });
+        let new_slug = match renamed {
+            Some(title) => SlugOperations::new(self.db).free_slugs(SlugScope::Tracks, &[title], Some(track_id.into())).await?.pop(),
+            None => None,
+        };
+        
+        let track = owner.profile.as_mut()
+            .and_then(|p| p.uploads.as_mut())
+            .and_then(|uploads| uploads.iter_mut().find(|t| t.id == track_id))
+            .ok_or(error::Error::TrackNotFound)?;
+        
+        if track.user_id != owner_id {
+            // Don't reveal tracks the caller couldn't see anyway
+            return Err(if track.is_visible_to(Some(owner_id)) {
+                error::Error::Forbidden
+            } else {
+                error::Error::TrackNotFound
+            });
+        }
+        
+        if let Some(title) = patch.title {
+            track.title = title.trim().to_string();
+        }
+        if let Some(slug) = new_slug {
+            // Links to the old slug keep working
+            track.slug_aliases.retain(|alias| *alias != slug);
+            if let Some(old) = track.slug.replace(slug) {
+                track.slug_aliases.push(old);
+            }
+        }
+        if patch.description.is_some() {
+            track.description = patch.description;
+        }
+        if patch.genre.is_some() {
+            track.genre = patch.genre;
+        }
+        if patch.tags.is_some() {
+            track.tags = patch.tags;
+        }
+        if let Some(url) = patch.cover_image_url {
+            track.cover_image_url = Some(url.trim().to_string());
+        }
+        if patch.license.is_some() || patch.downloadable.is_some() {
+            let license = patch.license.unwrap_or(track.license);
+            let downloadable = patch.downloadable.unwrap_or(track.downloadable);
+            license.check_downloadable(downloadable, patch.download_override)
+                .map_err(error::Error::Validation)?;
+            
+            if license != track.license {
+                track.license_history.push(LicenseChange { license: track.license, changed_at: now });
+                track.license = license;
+            }
+            track.downloadable = downloadable;
+        }
+        if let Some(code) = patch.language {
+            track.language = match code.trim() {
+                "" => None,
+                code => Some(language::normalize(code).map_err(error::Error::Validation)?),
+            };
+        }
+        if let Some(is_explicit) = patch.is_explicit {
+            track.is_explicit = is_explicit;
+        }
+        track.updated_at = now;
+        let track = track.clone();
+        
+        owner.updated_at = now;
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        Ok(track)
+    }
+    
+    /// Delete many of `owner_id`'s tracks at once. Every id must be one of
+    /// their uploads that isn't deleted yet; if any isn't, nothing is
+    /// deleted and the offending ids are reported. Deleted tracks also
+    /// leave the profile's pins and manual order. Returns the ids deleted.
+    pub async fn bulk_soft_delete(&self, owner_id: UserId, track_ids: &[TrackId]) -> Result<Vec<TrackId>, error::Error> {
+        let mut seen = HashSet::new();
+        let track_ids: Vec<TrackId> = track_ids.iter().copied().filter(|id| seen.insert(*id)).collect();
+        if track_ids.is_empty() || track_ids.len() > MAX_BULK_DELETE {
+            return Err(error::Error::Validation(format!("track_ids must list 1 to {} tracks", MAX_BULK_DELETE)));
+        }
+        
+        let mut owner = UserOperations::new(self.db).get_user_by_id(owner_id).await?;
+        let profile = owner.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        let owned: HashSet<TrackId> = profile.uploads.iter()
+            .flatten()
+            .filter(|track| track.user_id == owner_id && !track.is_deleted)
+            .map(|track| track.id)
+            .collect();
+        
+        let mut rejected = error::RejectedTracks::default();
+        for &track_id in track_ids.iter().filter(|id| !owned.contains(id)) {
+            // Don't reveal tracks the caller couldn't see anyway
+            let visible = match self.get_track(track_id).await {
+                Ok(track) => track.user_id != owner_id && track.is_visible_to(Some(owner_id)),
+                Err(error::Error::TrackNotFound) => false,
+                Err(e) => return Err(e),
+            };
+            if visible {
+                rejected.not_owned.push(track_id);
+            } else {
+                rejected.not_found.push(track_id);
+            }
+        }
+        if !rejected.not_owned.is_empty() || !rejected.not_found.is_empty() {
+            return Err(error::Error::TracksRejected(rejected));
+        }
+        
+        let now = self.db.now();
+        for track in profile.uploads.iter_mut().flatten().filter(|track| track_ids.contains(&track.id)) {
+            track.is_deleted = true;
+            track.updated_at = now;
+        }
+        profile.pinned_track_ids.retain(|id| !track_ids.contains(id));
+        profile.manual_track_order.retain(|id| !track_ids.contains(id));
+        owner.updated_at = now;
+        
+        self.db.transaction(|tx| tx.update("users", owner_id, &owner)).await?;
+        
+        Ok(track_ids)
+    }
+    
+    /// Tracks held in playlists whose uploader no longer exists, e.g.
+    /// after a hard delete. Uploads themselves live on the uploader's
+    /// record and go with it, but playlists keep their own copies.
+    pub async fn find_orphans(&self) -> Result<Vec<OrphanTrack>, error::Error> {
+        let owners = playlist_owners(self.db).await?;
+        let missing = missing_users(self.db, &owners).await?;
+        
+        Ok(owners
+            .iter()
+            .flat_map(|owner| owner.playlists.iter().map(move |playlist| (owner.id, playlist)))
+            .flat_map(|(owner_id, playlist)| {
+                playlist.tracks.iter()
+                    .filter(|track| missing.contains(&track.user_id))
+                    .map(move |track| OrphanTrack {
+                        track_id: track.id,
+                        title: track.title.clone(),
+                        missing_user_id: track.user_id,
+                        playlist_id: playlist.id,
+                        playlist_owner_id: owner_id,
+                    })
+            })
+            .collect())
+    }
+    
+    /// Take orphaned tracks out of playlists, or with `hard = false` only
+    /// mark the copies deleted. Returns how many tracks were cleaned up.
+    pub async fn cleanup_orphans(&self, hard: bool) -> Result<usize, error::Error> {
+        let owners = playlist_owners(self.db).await?;
+        let missing = missing_users(self.db, &owners).await?;
+        if missing.is_empty() {
+            return Ok(0);
+        }
+        
+        let mut cleaned = 0;
+        let mut changed = Vec::new();
+        for owner in owners {
+            let mut user = UserOperations::new(self.db).get_user_by_id(owner.id).await?;
+            let before = cleaned;
+            
+            for playlist in user.playlists.iter_mut().flatten() {
+                if hard {
+                    let len = playlist.tracks.len();
+                    playlist.tracks.retain(|track| !missing.contains(&track.user_id));
+                    cleaned += len - playlist.tracks.len();
+                } else {
+                    for track in playlist.tracks.iter_mut().filter(|t| !t.is_deleted && missing.contains(&t.user_id)) {
+                        track.is_deleted = true;
+                        cleaned += 1;
+                    }
+                }
+            }
+            
+            if cleaned > before {
+                user.updated_at = self.db.now();
+                changed.push(user);
+            }
+        }
+        
+        self.db.transaction(|tx| {
+            for user in &changed {
+                tx.update("users", user.id, user)?;
+            }
+            Ok(())
+        }).await?;
+        
+        Ok(cleaned)
+    }
+    
+    /// Point a track owned by `owner_id` at new audio, keeping the old file
+    /// in its audio versions. Counters and comments stay with the track.
+    pub async fn replace_audio(&self, track_id: TrackId, owner_id: UserId, replacement: AudioReplacement) -> Result<Track, error::Error> {
+        replacement.validate()?;
+        
+        let mut owner = self.get_owner(track_id).await?;
+        let now = self.db.now();
+        let track = owned_upload(&mut owner, track_id, owner_id)?;
+        
+        archive_audio(track, now);
+        track.audio_url = replacement.audio_url.trim().to_string();
+        track.technical_metadata = replacement.technical_metadata;
+        track.updated_at = now;
+        let track = track.clone();
+        
+        owner.updated_at = now;
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        Ok(track)
+    }
+    
+    /// Go back to one of a track's earlier audio files. The audio it's
+    /// playing now is archived in its place.
+    pub async fn restore_audio(&self, track_id: TrackId, owner_id: UserId, version_id: Uuid) -> Result<Track, error::Error> {
+        let mut owner = self.get_owner(track_id).await?;
+        let now = self.db.now();
+        let track = owned_upload(&mut owner, track_id, owner_id)?;
+        
+        let index = track.audio_versions.iter()
+            .position(|version| version.id == version_id)
+            .ok_or(error::Error::NotFound)?;
+        let version = track.audio_versions.remove(index);
+        
+        archive_audio(track, now);
+        track.audio_url = version.audio_url;
+        track.technical_metadata = version.technical_metadata;
+        track.updated_at = now;
+        let track = track.clone();
+        
+        owner.updated_at = now;
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        Ok(track)
+    }
+    
+    /// Add an attachment to a track owned by `owner_id`, within the
+    /// per-track count and size caps
+    pub async fn add_attachment(&self, track_id: TrackId, owner_id: UserId, attachment: Attachment) -> Result<Track, error::Error> {
+        let mut owner = self.get_owner(track_id).await?;
+        let now = self.db.now();
+        let track = owned_upload(&mut owner, track_id, owner_id)?;
+        
+        check_attachment_room(track, attachment.size)?;
+        track.attachments.push(attachment);
+        track.updated_at = now;
+        let track = track.clone();
+        
+        owner.updated_at = now;
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        Ok(track)
+    }
+    
+    /// Remove an attachment from a track owned by `owner_id`, returning it
+    /// so its file can be deleted
+    pub async fn remove_attachment(&self, track_id: TrackId, owner_id: UserId, attachment_id: Uuid) -> Result<Attachment, error::Error> {
+        let mut owner = self.get_owner(track_id).await?;
+        let now = self.db.now();
+        let track = owned_upload(&mut owner, track_id, owner_id)?;
+        
+        let index = track.attachments.iter()
+            .position(|attachment| attachment.id == attachment_id)
+            .ok_or(error::Error::NotFound)?;
+        let attachment = track.attachments.remove(index);
+        track.updated_at = now;
+        
+        owner.updated_at = now;
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        Ok(attachment)
+    }
+    
+    /// Record a download of one of a track's attachments
+    pub async fn increment_attachment_downloads(&self, track_id: TrackId, attachment_id: Uuid) -> Result<Attachment, error::Error> {
+        let mut owner = self.get_owner(track_id).await?;
+        
+        let attachment = owner.profile.as_mut()
+            .and_then(|p| p.uploads.as_mut())
+            .and_then(|uploads| uploads.iter_mut().find(|t| t.id == track_id))
+            .and_then(|track| track.attachments.iter_mut().find(|a| a.id == attachment_id))
+            .ok_or(error::Error::NotFound)?;
+        attachment.download_count += 1;
+        let attachment = attachment.clone();
+        
+        owner.updated_at = self.db.now();
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        Ok(attachment)
+    }
+    
+    /// Replace the artists credited on a track owned by `owner_id`. Credits
+    /// kept from before, matched by user or by name and role, keep their
+    /// status; users newly credited are invited to accept.
+    pub async fn set_credits(&self, track_id: TrackId, owner_id: UserId, inputs: Vec<CreditInput>) -> Result<Track, error::Error> {
+        if inputs.len() > MAX_CREDITS {
+            return Err(error::Error::Validation(format!("a track can credit at most {} artists", MAX_CREDITS)));
+        }
+        if inputs.iter().any(|input| input.user_id == Some(owner_id)) {
+            return Err(error::Error::Validation("you can't credit yourself".to_string()));
+        }
+        
+        let user_ids: Vec<UserId> = inputs.iter().filter_map(|input| input.user_id).collect();
+        let users = UserOperations::new(self.db).get_users_by_ids(&user_ids).await?;
+        
+        let mut owner = self.get_owner(track_id).await?;
+        let now = self.db.now();
+        let track = owned_upload(&mut owner, track_id, owner_id)?;
+        
+        let mut credits: Vec<Credit> = Vec::with_capacity(inputs.len());
+        for input in inputs {
+            let user = match input.user_id {
+                Some(user_id) => Some(users.iter()
+                    .find(|user| user.id == user_id)
+                    .ok_or_else(|| error::Error::Validation(format!("no user {}", user_id)))?),
+                None => None,
+            };
+            let mut credit = input.into_credit(user)?;
+            
+            let same = |old: &&Credit| old.role == credit.role && match credit.user_id {
+                Some(user_id) => old.user_id == Some(user_id),
+                None => old.user_id.is_none() && old.name == credit.name,
+            };
+            if credits.iter().any(|c| same(&c)) {
+                return Err(error::Error::Validation(format!("{} is credited twice in the same role", credit.name)));
+            }
+            if let Some(old) = track.credited_artists.iter().find(same) {
+                credit.id = old.id;
+                credit.status = old.status;
+                credit.notify_comments = old.notify_comments;
+                credit.created_at = old.created_at;
+            }
+            credits.push(credit);
+        }
+        
+        let invited: Vec<UserId> = credits.iter()
+            .filter(|credit| credit.status == CreditStatus::Pending)
+            .filter(|credit| !track.credited_artists.iter().any(|old| old.id == credit.id))
+            .filter_map(|credit| credit.user_id)
+            .collect();
+        track.credited_artists = credits;
+        track.updated_at = now;
+        let track = track.clone();
+        
+        owner.updated_at = now;
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        NotificationOperations::new(self.db).notify_many(
+            &invited,
+            NotificationKind::CreditInvitation,
+            format!("{} credited you on {}", owner.username, track.title),
+            serde_json::json!({ "track_id": track.id, "user_id": owner_id }),
+        ).await?;
+        
+        Ok(track)
+    }
+    
+    /// Accept or decline the credits naming `user_id` on a track. Declined
+    /// credits stay on the track as plain names.
+    pub async fn respond_to_credit(
+        &self,
+        track_id: TrackId,
+        credit_id: Uuid,
+        user_id: UserId,
+        response: CreditResponse,
+    ) -> Result<Credit, error::Error> {
+        let mut owner = self.get_owner(track_id).await?;
+        
+        let credit = owner.profile.as_mut()
+            .and_then(|p| p.uploads.as_mut())
+            .and_then(|uploads| uploads.iter_mut().find(|t| t.id == track_id && !t.is_deleted))
+            .and_then(|track| track.credited_artists.iter_mut().find(|c| c.id == credit_id))
+            .filter(|credit| credit.user_id == Some(user_id))
+            .ok_or(error::Error::NotFound)?;
+        credit.status = if response.accept { CreditStatus::Accepted } else { CreditStatus::Declined };
+        credit.notify_comments = response.accept && response.notify_comments;
+        let credit = credit.clone();
+        
+        owner.updated_at = self.db.now();
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        Ok(credit)
+    }
+    
+    /// One page of the public tracks `user_id` accepted a credit on, newest
+    /// first, and how many there are in all
+    pub async fn tracks_appearing_on(&self, user_id: UserId, limit: u32, offset: u32) -> Result<(Vec<Track>, u64), error::Error> {
+        let mut response = self.db
+            .query(format!(
+                "LET $tracks = array::flatten(
+                    (SELECT VALUE profile.uploads[WHERE is_public = true AND is_deleted = false
+                        AND credited_artists[WHERE user_id = $user_id AND status = 'accepted'] != []] ?? []
+                    FROM users WHERE {LISTABLE_USERS})
+                );
+                RETURN array::len($tracks);
+                SELECT * FROM $tracks ORDER BY created_at DESC LIMIT $limit START $offset;"
+            ))
+            .bind(("user_id", user_id.to_string()))
+            .bind(("now", self.db.now().to_rfc3339()))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        let total: Option<u64> = response.take(1)?;
+        let tracks = take_rows(&mut response, 2)?;
+        
+        Ok((tracks, total.unwrap_or(0)))
+    }
+    
+    /// Record a download of a track
+    pub async fn increment_download_count(&self, track_id: TrackId) -> Result<Track, error::Error> {
+        let mut owner = self.get_owner(track_id).await?;
+        
+        let track = owner.profile.as_mut()
+            .and_then(|p| p.uploads.as_mut())
+            .and_then(|uploads| uploads.iter_mut().find(|t| t.id == track_id))
+            .ok_or(error::Error::TrackNotFound)?;
+        track.download_count += 1;
+        let track = track.clone();
+        
+        owner.updated_at = self.db.now();
+        let _: Option<User> = self.db.update_record("users", owner.id, &owner).await?;
+        
+        Ok(track)
+    }
+}
+
+fn find_track(uploads: Option<&Vec<Track>>, track_id: TrackId) -> Option<Track> {
+    uploads?.iter().find(|t| t.id == track_id).cloned()
+}
+
+/// The upload `track_id` in `owner`'s uploads, if `user_id` may edit it
+fn owned_upload(owner: &mut User, track_id: TrackId, user_id: UserId) -> Result<&mut Track, error::Error> {
+    let track = owner.profile.as_mut()
+        .and_then(|p| p.uploads.as_mut())
+        .and_then(|uploads| uploads.iter_mut().find(|t| t.id == track_id))
+        .ok_or(error::Error::TrackNotFound)?;
+    
+    if track.user_id != user_id {
+        // Don't reveal tracks the caller couldn't see anyway
+        return Err(if track.is_visible_to(Some(user_id)) {
+            error::Error::Forbidden
+        } else {
+            error::Error::TrackNotFound
+        });
+    }
+    
+    Ok(track)
+}
+
+/// Put a profile's tracks in the order it lists them: pinned ones first,
+/// the latest pin leading, then the rest in the profile's chosen order
+fn sort_for_profile(tracks: &mut [Track], profile: &UserProfile) {
+    let pin = |track: &Track| profile.pinned_track_ids.iter().position(|id| *id == track.id);
+    let place = |track: &Track| profile.manual_track_order.iter().position(|id| *id == track.id).unwrap_or(usize::MAX);
+    
+    tracks.sort_by(|a, b| {
+        match (pin(a), pin(b)) {
+            (Some(a), Some(b)) => return b.cmp(&a),
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => {}
+        }
+        
+        let newest = (b.created_at, b.id).cmp(&(a.created_at, a.id));
+        match profile.track_order {
+            ProfileTrackOrder::Newest => newest,
+            ProfileTrackOrder::MostLiked => b.likes.cmp(&a.likes).then(newest),
+            ProfileTrackOrder::Manual => place(a).cmp(&place(b)).then(newest),
+        }
+    });
+}
+
+/// Check that a track has room for another attachment of `size` bytes
+pub fn check_attachment_room(track: &Track, size: u64) -> Result<(), error::Error> {
+    if track.attachments.len() >= MAX_ATTACHMENTS_PER_TRACK {
+        return Err(error::Error::Conflict(format!("a track can have at most {} attachments", MAX_ATTACHMENTS_PER_TRACK)));
+    }
+    let total: u64 = track.attachments.iter().map(|attachment| attachment.size).sum();
+    if total + size > MAX_TRACK_ATTACHMENT_BYTES {
+        return Err(error::Error::PayloadTooLarge);
+    }
+    Ok(())
+}
+
+/// Keep a track's current audio among its versions, dropping the oldest
+/// past `MAX_AUDIO_VERSIONS`
+fn archive_audio(track: &mut Track, now: DateTime<Utc>) {
+    track.audio_versions.push(AudioVersion {
+        id: Uuid::new_v4(),
+        audio_url: track.audio_url.clone(),
+        technical_metadata: track.technical_metadata.clone(),
+        replaced_at: now,
+    });
+    let excess = track.audio_versions.len().saturating_sub(MAX_AUDIO_VERSIONS);
+    track.audio_versions.drain(..excess);
+}
+
+/// Find a comment among `comments` or any of their replies
+pub(crate) fn find_comment(comments: Option<&Vec<Comment>>, comment_id: CommentId) -> Option<Comment> {
+    comments?.iter().find_map(|comment| {
+        if comment.id == comment_id {
+            Some(comment.clone())
+        } else {
+            find_comment(comment.replies.as_ref(), comment_id)
+        }
+    })
+}
+
+fn find_comment_mut(comments: Option<&mut Vec<Comment>>, comment_id: CommentId) -> Option<&mut Comment> {
+    comments?.iter_mut().find_map(|comment| {
+        if comment.id == comment_id {
+            Some(comment)
+        } else {
+            find_comment_mut(comment.replies.as_mut(), comment_id)
+        }
+    })
+}
+
+/// The playlists of every user who has any
+#[derive(serde::Deserialize)]
+struct PlaylistOwner {
+    id: UserId,
+    playlists: Vec<Playlist>,
+}
+
+async fn playlist_owners(db: &Db) -> Result<Vec<PlaylistOwner>, error::Error> {
+    let mut response = db
+        .query("SELECT record::id(id) AS id, playlists FROM users WHERE array::len(playlists ?? []) > 0")
+        .await?;
+        
+    take_rows(&mut response, 0)
+}
+
+/// The uploaders referenced from `owners`' playlists that don't exist
+async fn missing_users(db: &Db, owners: &[PlaylistOwner]) -> Result<HashSet<UserId>, error::Error> {
+    let referenced: HashSet<UserId> = owners.iter()
+        .flat_map(|owner| owner.playlists.iter())
+        .flat_map(|playlist| playlist.tracks.iter().map(|track| track.user_id))
+        .collect();
+    if referenced.is_empty() {
+        return Ok(HashSet::new());
+    }
+    
+    let ids: Vec<String> = referenced.iter().map(ToString::to_string).collect();
+    let mut response = db
+        .query("SELECT VALUE record::id(id) FROM users WHERE record::id(id) IN $ids")
+        .bind(("ids", ids))
+        .await?;
+    let existing: Vec<String> = response.take(0)?;
+    
+    Ok(referenced.into_iter().filter(|id| !existing.contains(&id.to_string())).collect())
+}
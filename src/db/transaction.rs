@@ -0,0 +1,167 @@
+//! Atomic multi-record writes.
+//!
+//! SurrealDB only keeps a transaction open for the duration of a single query
+//! call, so writes are staged on a `Transaction` and sent together wrapped in
+//! `BEGIN`/`COMMIT`. If any statement fails the whole batch is cancelled.
+//!
+//! Writes staged with `update` replace records read beforehand. Ones that
+//! must build on what the records hold when the transaction runs, like
+//! adding to a list two requests may change at once, use `set` and
+//! `forbid_if` instead. A commit that conflicts with another one writing
+//! the same records is retried, so both land.
+
+use std::time::Duration;
+use serde::Serialize;
+use serde_json::Value;
+use surrealdb::RecordId;
+use uuid::Uuid;
+use super::{error, record_id, to_content, Db};
+
+/// Thrown by a `forbid_if` check, and turned into `Error::Forbidden`
+const FORBIDDEN: &str = "transaction forbidden";
+
+/// SurrealDB's hint on a commit that lost a race with another transaction
+const CONFLICT: &str = "can be retried";
+
+/// Commits tried before a conflict is given up on
+const MAX_ATTEMPTS: u64 = 5;
+
+#[derive(Default)]
+pub struct Transaction {
+    statements: Vec<String>,
+    bindings: Vec<(String, Value)>,
+    records: Vec<(String, RecordId)>, // bound apart, JSON has no record id type
+    written: Vec<(String, Uuid)>, // records to drop from caches once committed
+}
+
+impl Transaction {
+    fn bind(&mut self, value: Value) -> String {
+        let name = format!("p{}", self.bindings.len());
+        self.bindings.push((name.clone(), value));
+        format!("${}", name)
+    }
+    
+    /// Bind the record's id, noting it as written for the caches
+    fn bind_record(&mut self, table: &str, id: impl Into<Uuid>) -> String {
+        let id = id.into();
+        self.written.push((table.to_string(), id));
+        let name = format!("r{}", self.records.len());
+        self.records.push((name.clone(), record_id(table, id)));
+        format!("${}", name)
+    }
+    
+    /// Stage replacing the content of an existing record. The transaction
+    /// fails if the record doesn't exist.
+    pub fn update<T: Serialize>(&mut self, table: &str, id: impl Into<Uuid>, value: &T) -> Result<(), error::Error> {
+        let record = self.bind_record(table, id);
+        let data = self.bind(to_content(value)?);
+        
+        self.statements.push(format!(
+            "IF !record::exists({record}) {{ THROW \"record not found\" }};\n\
+            UPDATE {record} CONTENT {data};"
+        ));
+        Ok(())
+    }
+    
+    /// Stage changing fields of an existing record with a SurrealQL `SET`
+    /// clause, evaluated against the record as it is when the transaction
+    /// runs. `params` are bound for the whole transaction, so statements
+    /// sharing a name must agree on its value.
+    pub fn set(&mut self, table: &str, id: impl Into<Uuid>, set: &str, params: Vec<(&str, Value)>) {
+        let record = self.bind_record(table, id);
+        
+        self.statement(
+            &format!(
+                "IF !record::exists({record}) {{ THROW \"record not found\" }};\n\
+                UPDATE {record} SET {set};"
+            ),
+            params,
+        );
+    }
+    
+    /// Stage a check that cancels the transaction with `Error::Forbidden`
+    /// if the SurrealQL `condition` holds when it runs
+    pub fn forbid_if(&mut self, condition: &str, params: Vec<(&str, Value)>) {
+        self.statement(&format!("IF {condition} {{ THROW \"{FORBIDDEN}\" }};"), params);
+    }
+    
+    /// Stage creating a record, or replacing its content if it exists
+    pub fn upsert<T: Serialize>(&mut self, table: &str, id: impl Into<Uuid>, value: &T) -> Result<(), error::Error> {
+        let record = self.bind_record(table, id);
+        let data = self.bind(to_content(value)?);
+        
+        self.statements.push(format!("UPSERT {record} CONTENT {data};"));
+        Ok(())
+    }
+    
+    /// Stage deleting a record
+    pub fn delete(&mut self, table: &str, id: impl Into<Uuid>) {
+        let record = self.bind_record(table, id);
+        
+        self.statements.push(format!("DELETE {record};"));
+    }
+    
+    /// Stage a raw statement, binding `params` under the given names
+    pub fn statement(&mut self, statement: &str, params: Vec<(&str, Value)>) {
+        for (name, value) in params {
+            self.bindings.push((name.to_string(), value));
+        }
+        self.statements.push(statement.to_string());
+    }
+    
+    /// Run every staged statement atomically on `db`
+    pub async fn commit(self, db: &Db) -> Result<(), error::Error> {
+        if self.statements.is_empty() {
+            return Ok(());
+        }
+        
+        let query = format!(
+            "BEGIN TRANSACTION;\n{}\nCOMMIT TRANSACTION;",
+            self.statements.join("\n")
+        );
+        
+        let mut attempt = 1;
+        let failure = loop {
+            let mut request = db.query(query.clone());
+            for binding in &self.bindings {
+                request = request.bind(binding.clone());
+            }
+            for record in &self.records {
+                request = request.bind(record.clone());
+            }
+            let failure = match request.await {
+                Ok(response) => response.check().err(),
+                Err(e) => Some(e),
+            };
+            match failure {
+                Some(e) if e.to_string().contains(CONFLICT) && attempt < MAX_ATTEMPTS => {
+                    actix_web::rt::time::sleep(Duration::from_millis(10 * attempt)).await;
+                    attempt += 1;
+                }
+                failure => break failure,
+            }
+        };
+        for (table, id) in &self.written {
+            db.invalidate_cached(table, *id);
+        }
+        match failure {
+            Some(e) if e.to_string().contains(FORBIDDEN) => Err(error::Error::Forbidden),
+            Some(e) => Err(e.into()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Db {
+    /// Stage writes with `build` and commit them on this database as one
+    /// transaction. Nothing is written if `build` returns an error or any
+    /// statement fails.
+    pub async fn transaction<F>(&self, build: F) -> Result<(), error::Error>
+    where
+        F: FnOnce(&mut Transaction) -> Result<(), error::Error>,
+    {
+        let mut tx = Transaction::default();
+        build(&mut tx)?;
+        tx.commit(self).await
+    }
+}
@@ -0,0 +1,78 @@
+use uuid::Uuid;
+use crate::types::id::UserId;
+use crate::types::upload::{ByteRange, ResumableUpload};
+use super::{error, record_id, take_row, take_rows, to_content, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct UploadOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> UploadOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        UploadOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> UploadOperations<'static> {
+        UploadOperations::new(&DB)
+    }
+    
+    /// Store a new resumable upload
+    pub async fn create_upload(&self, upload: ResumableUpload) -> Result<ResumableUpload, error::Error> {
+        let created: Option<ResumableUpload> = self.db.create_record("uploads", upload.id, &upload).await?;
+        
+        created.ok_or(error::Error::Db("Failed to create upload".to_string()))
+    }
+    
+    /// Get one of a user's unexpired uploads
+    pub async fn get_upload(&self, upload_id: Uuid, user_id: UserId) -> Result<ResumableUpload, error::Error> {
+        let upload: Option<ResumableUpload> = self.db.select_record("uploads", upload_id).await?;
+        
+        upload
+            .filter(|upload| upload.user_id == user_id && !upload.is_expired())
+            .ok_or(error::Error::NotFound)
+    }
+    
+    /// Note that a chunk arrived. It's appended in one statement so chunks
+    /// sent in parallel don't overwrite each other's ranges.
+    pub async fn record_chunk(&self, upload_id: Uuid, range: ByteRange) -> Result<ResumableUpload, error::Error> {
+        let mut response = self.db
+            .query("UPDATE $record SET chunks += $range RETURN *, record::id(id) AS id")
+            .bind(("record", record_id("uploads", upload_id)))
+            .bind(("range", to_content(&range)?))
+            .await?;
+        
+        take_row(&mut response, 0)?.ok_or(error::Error::NotFound)
+    }
+    
+    /// Forget an upload
+    pub async fn delete_upload(&self, upload_id: Uuid) -> Result<(), error::Error> {
+        self.db.delete_record("uploads", upload_id).await
+    }
+    
+    /// Forget uploads past their expiry, returning them so their staging
+    /// files can be removed
+    pub async fn delete_expired(&self) -> Result<Vec<ResumableUpload>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT *, record::id(id) AS id FROM uploads WHERE <datetime> expires_at <= <datetime> $now;
+                DELETE uploads WHERE <datetime> expires_at <= <datetime> $now"
+            )
+            .bind(("now", self.db.now().to_rfc3339()))
+            .await?;
+        
+        take_rows(&mut response, 0)
+    }
+    
+    /// Forget every upload a user started, returning them so their staging
+    /// files can be removed
+    pub async fn delete_uploads_for_user(&self, user_id: UserId) -> Result<Vec<ResumableUpload>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM uploads WHERE user_id = $user_id; DELETE uploads WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?;
+        
+        take_rows(&mut response, 0)
+    }
+}
@@ -0,0 +1,173 @@
+use uuid::Uuid;
+use crate::types::id::UserId;
+use crate::types::verification::{reapply_cooldown, VerificationRequest, VerificationStatus};
+use super::{error, take_rows, UserOperations, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct VerificationOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> VerificationOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        VerificationOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> VerificationOperations<'static> {
+        VerificationOperations::new(&DB)
+    }
+    
+    /// File a verification request. Users who are verified or already have
+    /// a pending request can't apply, and a rejection has a cooldown.
+    pub async fn create_request(&self, request: VerificationRequest) -> Result<VerificationRequest, error::Error> {
+        let user = UserOperations::new(self.db).get_user_by_id(request.user_id).await?;
+        if user.profile.as_ref().is_some_and(|p| p.is_verified) {
+            return Err(error::Error::Conflict("account is already verified".to_string()));
+        }
+        
+        if let Some(latest) = self.latest_for_user(request.user_id).await? {
+            if latest.status == VerificationStatus::Pending {
+                return Err(error::Error::Conflict("a verification request is already pending".to_string()));
+            }
+            let reapply_at = latest.reviewed_at.map(|at| at + reapply_cooldown()).filter(|at| *at > self.db.now());
+            if let (VerificationStatus::Rejected, Some(at)) = (latest.status, reapply_at) {
+                return Err(error::Error::TooManyRequests(format!(
+                    "verification was rejected, you can apply again after {}",
+                    at.to_rfc3339()
+                )));
+            }
+        }
+        
+        let created: Option<VerificationRequest> = self.db.create_record("verification_requests", request.id, &request).await?;
+        
+        created.ok_or(error::Error::Db("Failed to create verification request".to_string()))
+    }
+    
+    /// Get verification request by ID
+    pub async fn get_request(&self, request_id: Uuid) -> Result<VerificationRequest, error::Error> {
+        let request: Option<VerificationRequest> = self.db.select_record("verification_requests", request_id).await?;
+        
+        request.ok_or(error::Error::NotFound)
+    }
+    
+    /// A user's most recent request, if they ever applied
+    pub async fn latest_for_user(&self, user_id: UserId) -> Result<Option<VerificationRequest>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM verification_requests WHERE user_id = $user_id ORDER BY created_at DESC LIMIT 1")
+            .bind(("user_id", user_id.to_string()))
+            .await?;
+        let requests: Vec<VerificationRequest> = take_rows(&mut response, 0)?;
+        
+        Ok(requests.into_iter().next())
+    }
+    
+    /// Requests with pagination, oldest first so the queue is worked in
+    /// order, optionally only those with one status
+    pub async fn list_requests(
+        &self,
+        status: Option<VerificationStatus>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<VerificationRequest>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT *, record::id(id) AS id FROM verification_requests
+                WHERE $status = '' OR status = $status
+                ORDER BY created_at ASC LIMIT $limit START $offset"
+            )
+            .bind(("status", status.map_or("", |s| s.as_str())))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+        
+        take_rows(&mut response, 0)
+    }
+    
+    /// Count the requests `list_requests` would return across all pages
+    pub async fn count_requests(&self, status: Option<VerificationStatus>) -> Result<u64, error::Error> {
+        let mut response = self.db
+            .query("SELECT count() FROM verification_requests WHERE $status = '' OR status = $status GROUP ALL")
+            .bind(("status", status.map_or("", |s| s.as_str())))
+            .await?;
+        let count: Option<u64> = response.take((0, "count"))?;
+        
+        Ok(count.unwrap_or(0))
+    }
+    
+    /// Approve or reject a pending request. Approving sets the badge on the
+    /// user in the same transaction.
+    pub async fn review(
+        &self,
+        request_id: Uuid,
+        approve: bool,
+        reviewer_id: UserId,
+        note: Option<String>,
+    ) -> Result<VerificationRequest, error::Error> {
+        let mut request = self.get_request(request_id).await?;
+        if request.status != VerificationStatus::Pending {
+            return Err(error::Error::Conflict("verification request was already reviewed".to_string()));
+        }
+        
+        let mut user = UserOperations::new(self.db).get_user_by_id(request.user_id).await?;
+        let now = self.db.now();
+        request.status = if approve { VerificationStatus::Approved } else { VerificationStatus::Rejected };
+        request.note = note;
+        request.reviewed_by = Some(reviewer_id);
+        request.reviewed_at = Some(now);
+        
+        self.db.transaction(|tx| {
+            if approve {
+                if let Some(profile) = user.profile.as_mut() {
+                    profile.is_verified = true;
+                }
+                user.updated_at = now;
+                tx.update("users", user.id, &user)?;
+            }
+            tx.update("verification_requests", request.id, &request)
+        }).await?;
+        
+        Ok(request)
+    }
+    
+    /// Take the badge away from a verified user, marking the request that
+    /// granted it as revoked
+    pub async fn revoke(&self, user_id: UserId, reviewer_id: UserId, note: Option<String>) -> Result<(), error::Error> {
+        let mut user = UserOperations::new(self.db).get_user_by_id(user_id).await?;
+        let profile = user.profile.as_mut().ok_or(error::Error::ProfileNotFound)?;
+        if !profile.is_verified {
+            return Err(error::Error::Validation("account is not verified".to_string()));
+        }
+        
+        let now = self.db.now();
+        profile.is_verified = false;
+        user.updated_at = now;
+        
+        let mut granted = self.latest_for_user(user_id).await?
+            .filter(|request| request.status == VerificationStatus::Approved);
+        if let Some(request) = granted.as_mut() {
+            request.status = VerificationStatus::Revoked;
+            request.note = note;
+            request.reviewed_by = Some(reviewer_id);
+            request.reviewed_at = Some(now);
+        }
+        
+        self.db.transaction(|tx| {
+            tx.update("users", user_id, &user)?;
+            match &granted {
+                Some(request) => tx.update("verification_requests", request.id, request),
+                None => Ok(()),
+            }
+        }).await
+    }
+    
+    /// Delete every request a user made
+    pub async fn delete_requests_for_user(&self, user_id: UserId) -> Result<(), error::Error> {
+        self.db.query("DELETE verification_requests WHERE user_id = $user_id")
+            .bind(("user_id", user_id.to_string()))
+            .await?
+            .check()?;
+        
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+use uuid::Uuid;
+use crate::types::id::UserId;
+use crate::types::webhook::{Webhook, WebhookDelivery, WebhookEvent};
+use super::{error, take_rows, Db, DB};
+
+#[derive(Clone, Copy)]
+pub struct WebhookOperations<'a> {
+    db: &'a Db,
+}
+
+impl<'a> WebhookOperations<'a> {
+    pub fn new(db: &'a Db) -> Self {
+        WebhookOperations { db }
+    }
+    
+    /// Through the process-wide handle, for work that isn't tied to a request
+    pub fn global() -> WebhookOperations<'static> {
+        WebhookOperations::new(&DB)
+    }
+    
+    /// Register a webhook. `owner_id` is `None` for a global webhook.
+    pub async fn create_webhook(
+        &self,
+        owner_id: Option<UserId>,
+        url: String,
+        secret: String,
+        events: Vec<WebhookEvent>,
+    ) -> Result<Webhook, error::Error> {
+        let now = self.db.now();
+        let webhook_id = Uuid::new_v4();
+        
+        let webhook = Webhook {
+            id: webhook_id,
+            owner_id,
+            url,
+            secret,
+            events,
+            active: true,
+            consecutive_failures: 0,
+            created_at: now,
+            updated_at: now,
+        };
+        
+        let created: Option<Webhook> = self.db.create_record("webhooks", webhook_id, &webhook).await?;
+            
+        created.ok_or(error::Error::Db("Failed to create webhook".to_string()))
+    }
+    
+    /// Get webhook by ID
+    pub async fn get_webhook(&self, webhook_id: Uuid) -> Result<Webhook, error::Error> {
+        let webhook: Option<Webhook> = self.db.select_record("webhooks", webhook_id).await?;
+        
+        webhook.ok_or(error::Error::WebhookNotFound)
+    }
+    
+    /// Get webhook by ID, only if it belongs to `owner_id`
+    pub async fn get_owned_webhook(&self, owner_id: UserId, webhook_id: Uuid) -> Result<Webhook, error::Error> {
+        let webhook = self.get_webhook(webhook_id).await?;
+        
+        if webhook.owner_id != Some(owner_id) {
+            return Err(error::Error::WebhookNotFound);
+        }
+        
+        Ok(webhook)
+    }
+    
+    /// Get a user's webhooks, or the global ones when `owner_id` is `None`
+    pub async fn get_webhooks(&self, owner_id: Option<UserId>) -> Result<Vec<Webhook>, error::Error> {
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM webhooks WHERE owner_id = $owner_id ORDER BY created_at ASC")
+            .bind(("owner_id", owner_id.map(|id| id.to_string())))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// Active webhooks that should receive `event`: the user's own plus every global one
+    pub async fn get_subscribers(&self, owner_id: Option<UserId>, event: WebhookEvent) -> Result<Vec<Webhook>, error::Error> {
+        let mut response = self.db
+            .query(
+                "SELECT *, record::id(id) AS id FROM webhooks WHERE
+                active = true AND events CONTAINS $event AND
+                (owner_id = NONE OR owner_id = NULL OR owner_id = $owner_id)"
+            )
+            .bind(("event", event.as_str()))
+            .bind(("owner_id", owner_id.map(|id| id.to_string())))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+    
+    /// Persist changes to a webhook
+    pub async fn save_webhook(&self, mut webhook: Webhook) -> Result<Webhook, error::Error> {
+        webhook.updated_at = self.db.now();
+        
+        let updated: Option<Webhook> = self.db.update_record("webhooks", webhook.id, &webhook).await?;
+            
+        updated.ok_or(error::Error::WebhookNotFound)
+    }
+    
+    /// Delete a webhook along with its delivery log
+    pub async fn delete_webhook(&self, webhook_id: Uuid) -> Result<(), error::Error> {
+        self.db.delete_record("webhooks", webhook_id).await?;
+        
+        self.db.query("DELETE webhook_deliveries WHERE webhook_id = $webhook_id")
+            .bind(("webhook_id", webhook_id.to_string()))
+            .await?
+            .check()?;
+            
+        Ok(())
+    }
+    
+    /// Delete every webhook a user registered, along with their delivery logs
+    pub async fn delete_webhooks_for_user(&self, owner_id: UserId) -> Result<(), error::Error> {
+        self.db.query(
+                "DELETE webhook_deliveries WHERE webhook_id IN
+                (SELECT VALUE record::id(id) FROM webhooks WHERE owner_id = $owner_id);
+                DELETE webhooks WHERE owner_id = $owner_id;"
+            )
+            .bind(("owner_id", owner_id.to_string()))
+            .await?
+            .check()?;
+            
+        Ok(())
+    }
+    
+    /// Record one delivery attempt
+    pub async fn record_delivery(&self, delivery: &WebhookDelivery) -> Result<(), error::Error> {
+        let _: Option<WebhookDelivery> = self.db.create_record("webhook_deliveries", delivery.id, delivery).await?;
+        Ok(())
+    }
+    
+    /// Get the delivery attempts for a webhook, newest first
+    pub async fn get_deliveries(
+        &self,
+        webhook_id: Uuid,
+        limit: Option<u32>,
+        offset: Option<u32>,
+    ) -> Result<Vec<WebhookDelivery>, error::Error> {
+        let limit = limit.unwrap_or(50);
+        let offset = offset.unwrap_or(0);
+        
+        let mut response = self.db
+            .query("SELECT *, record::id(id) AS id FROM webhook_deliveries WHERE webhook_id = $webhook_id ORDER BY created_at DESC LIMIT $limit START $offset")
+            .bind(("webhook_id", webhook_id.to_string()))
+            .bind(("limit", limit))
+            .bind(("offset", offset))
+            .await?;
+            
+        take_rows(&mut response, 0)
+    }
+}
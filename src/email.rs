@@ -0,0 +1,342 @@
+//! Sending email.
+//!
+//! Flows build an `Email` from one of the templates here and hand it to
+//! `send`, which delivers it in the background through the configured
+//! `Mailer`: nothing waits on the mail server, and a failed delivery
+//! doesn't fail the request that caused it. Instead the message goes into
+//! the `email_outbox` table, and `spawn_job` tries it again with backoff,
+//! up to `MAX_ATTEMPTS` tries in all.
+//!
+//! Templates live in `templates/email`, each with an HTML and a plain text
+//! version extending the matching base layout.
+//!
+//! `EMAIL_BACKEND=smtp` sends through `SMTP_HOST` (`SMTP_PORT`, default
+//! 587, with `SMTP_USERNAME`/`SMTP_PASSWORD` when the relay wants them and
+//! `SMTP_TLS` of `starttls`, `tls` or `none`) from `EMAIL_FROM`. The default
+//! `none` sends nothing, only logging who would have been mailed.
+
+use std::env;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use askama::Template;
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use crate::clock;
+use crate::config;
+use crate::db::email_outbox::EmailOutboxOperations;
+use crate::db::error::Error;
+use crate::types::email::QueuedEmail;
+
+/// Deliveries tried per message, the first included, before it's dropped
+pub const MAX_ATTEMPTS: u32 = 8;
+
+/// Wait before the second try; each one after waits twice as long
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(60);
+
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// How often the outbox is checked for messages due another try
+const RETRY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Most messages retried per check
+const RETRY_BATCH_SIZE: u32 = 50;
+
+/// One message, with plain text and HTML bodies of the same content
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+pub trait Mailer: Send + Sync {
+    /// Hand a message to the mail server
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), Error>>;
+}
+
+/// Sends through an SMTP relay
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    pub fn new(transport: AsyncSmtpTransport<Tokio1Executor>, from: Mailbox) -> Self {
+        Self { transport, from }
+    }
+
+    pub fn from_env() -> Result<Self, Error> {
+        let host = env::var("SMTP_HOST").map_err(|_| Error::Validation("SMTP_HOST must be set".to_string()))?;
+        let from = env::var("EMAIL_FROM")
+            .map_err(|_| Error::Validation("EMAIL_FROM must be set".to_string()))?
+            .parse::<Mailbox>()
+            .map_err(|e| Error::Validation(format!("EMAIL_FROM is not an address: {}", e)))?;
+        let port = env::var("SMTP_PORT").ok().and_then(|port| port.parse().ok()).unwrap_or(587);
+
+        let builder = match env::var("SMTP_TLS").unwrap_or_default().to_lowercase().as_str() {
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host).map_err(|e| Error::Validation(e.to_string()))?,
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host).map_err(|e| Error::Validation(e.to_string()))?,
+        };
+        let builder = match (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+            (Ok(username), Ok(password)) => builder.credentials(Credentials::new(username, password)),
+            _ => builder,
+        };
+
+        Ok(Self::new(builder.port(port).build(), from))
+    }
+}
+
+impl Mailer for SmtpMailer {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            let to = email.to.parse::<Mailbox>().map_err(|e| Error::Validation(format!("bad recipient: {}", e)))?;
+            let message = Message::builder()
+                .from(self.from.clone())
+                .to(to)
+                .subject(email.subject)
+                .multipart(MultiPart::alternative_plain_html(email.text, email.html))
+                .map_err(|e| Error::Validation(e.to_string()))?;
+
+            self.transport.send(message).await.map_err(|e| Error::Db(e.to_string()))?;
+            Ok(())
+        })
+    }
+}
+
+/// Sends nothing, for when no mail server is configured
+pub struct NoopMailer;
+
+impl Mailer for NoopMailer {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), Error>> {
+        Box::pin(async move {
+            info!("Not emailing {} ({}): EMAIL_BACKEND is none", email.to, email.subject);
+            Ok(())
+        })
+    }
+}
+
+/// Keeps what it's given in memory, so a flow can check what it sent
+#[derive(Default)]
+pub struct CaptureMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl CaptureMailer {
+    /// Everything sent so far, oldest first
+    pub fn sent(&self) -> Vec<Email> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Everything sent to `to` so far, oldest first
+    pub fn sent_to(&self, to: &str) -> Vec<Email> {
+        self.sent().into_iter().filter(|email| email.to.eq_ignore_ascii_case(to)).collect()
+    }
+}
+
+impl Mailer for CaptureMailer {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), Error>> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner()).push(email);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+static MAILER: OnceLock<Arc<dyn Mailer>> = OnceLock::new();
+
+/// The backend `EMAIL_BACKEND` picks
+fn from_env() -> Arc<dyn Mailer> {
+    match env::var("EMAIL_BACKEND").unwrap_or_default().to_lowercase().as_str() {
+        "smtp" => match SmtpMailer::from_env() {
+            Ok(mailer) => Arc::new(mailer),
+            Err(e) => {
+                warn!("Email is off, the SMTP backend couldn't be set up: {}", e);
+                Arc::new(NoopMailer)
+            }
+        },
+        _ => Arc::new(NoopMailer),
+    }
+}
+
+/// Send through `mailer` instead of the configured backend, e.g. a
+/// `CaptureMailer` in tests. Only takes before anything is sent; returns
+/// whether it did.
+pub fn install(mailer: Arc<dyn Mailer>) -> bool {
+    MAILER.set(mailer).is_ok()
+}
+
+/// The mail backend in use
+pub fn mailer() -> &'static dyn Mailer {
+    MAILER.get_or_init(from_env).as_ref()
+}
+
+/// How long to wait after the `attempts`th failed try
+fn retry_delay(attempts: u32) -> Duration {
+    FIRST_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(attempts.saturating_sub(1)))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// When to try again after the `attempts`th failed try
+fn next_attempt_at(attempts: u32) -> chrono::DateTime<Utc> {
+    clock::now() + chrono::Duration::seconds(retry_delay(attempts).as_secs() as i64)
+}
+
+/// Deliver `email` in the background, queueing it to be tried again if the
+/// mail server doesn't take it
+pub fn send(email: Email) {
+    actix_web::rt::spawn(async move {
+        let (to, subject) = (email.to.clone(), email.subject.clone());
+        match mailer().send(email.clone()).await {
+            Ok(()) => {}
+            // A message the server can't be given won't get better with time
+            Err(Error::Validation(e)) => warn!("Not emailing {} ({}): {}", to, subject, e),
+            Err(e) => {
+                warn!("Failed to email {} ({}), will try again: {}", to, subject, e);
+                if let Err(e) = EmailOutboxOperations::global().enqueue(email, e.to_string(), next_attempt_at(1)).await {
+                    warn!("Failed to queue email to {} ({}): {}", to, subject, e);
+                }
+            }
+        }
+    });
+}
+
+/// Try one queued message again, dropping it once it's delivered or out
+/// of tries
+async fn retry(mut queued: QueuedEmail) -> Result<(), Error> {
+    let (to, subject) = (queued.email.to.clone(), queued.email.subject.clone());
+    match mailer().send(queued.email.clone()).await {
+        Ok(()) => {
+            info!("Emailed {} ({}) after {} failed tries", to, subject, queued.attempts);
+            EmailOutboxOperations::global().remove(queued.id).await
+        }
+        Err(e) => {
+            queued.attempts += 1;
+            if queued.attempts >= MAX_ATTEMPTS || matches!(e, Error::Validation(_)) {
+                warn!("Gave up emailing {} ({}) after {} tries: {}", to, subject, queued.attempts, e);
+                return EmailOutboxOperations::global().remove(queued.id).await;
+            }
+            queued.last_error = e.to_string();
+            queued.next_attempt_at = next_attempt_at(queued.attempts);
+            EmailOutboxOperations::global().save(queued).await.map(|_| ())
+        }
+    }
+}
+
+/// Retry queued messages in the background for as long as the server runs
+pub fn spawn_job() {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(RETRY_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let due = match EmailOutboxOperations::global().get_due(RETRY_BATCH_SIZE).await {
+                Ok(due) => due,
+                Err(e) => {
+                    warn!("Failed to read the email outbox: {}", e);
+                    continue;
+                }
+            };
+            for queued in due {
+                let id = queued.id;
+                if let Err(e) = retry(queued).await {
+                    warn!("Failed to update queued email {}: {}", id, e);
+                }
+            }
+        }
+    });
+}
+
+#[derive(Template)]
+#[template(path = "email/verification.html")]
+struct VerificationHtml<'a> {
+    username: &'a str,
+    link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/verification.txt")]
+struct VerificationText<'a> {
+    username: &'a str,
+    link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/password_reset.html")]
+struct PasswordResetHtml<'a> {
+    username: &'a str,
+    link: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/password_reset.txt")]
+struct PasswordResetText<'a> {
+    username: &'a str,
+    link: &'a str,
+}
+
+/// Render a message's two bodies. The templates only fill in strings, so
+/// rendering can't fail in practice; if it does it's logged and the body
+/// left empty.
+fn render(to: &str, subject: &str, html: impl Template, text: impl Template) -> Email {
+    let body = |rendered: askama::Result<String>| rendered.unwrap_or_else(|e| {
+        warn!("Failed to render email ({}): {}", subject, e);
+        String::new()
+    });
+    Email {
+        to: to.to_string(),
+        subject: subject.to_string(),
+        html: body(html.render()),
+        text: body(text.render()),
+    }
+}
+
+/// The message asking a user to confirm their address
+pub fn verification(to: &str, username: &str, token: &str) -> Email {
+    let link = format!("{}/verify-email?token={}", config::public_url(), token);
+    render(
+        to,
+        "Confirm your email address",
+        VerificationHtml { username, link: &link },
+        VerificationText { username, link: &link },
+    )
+}
+
+/// The message carrying a password reset token
+pub fn password_reset(to: &str, username: &str, token: &str) -> Email {
+    let link = format!("{}/reset-password?token={}", config::public_url(), token);
+    render(
+        to,
+        "Reset your password",
+        PasswordResetHtml { username, link: &link },
+        PasswordResetText { username, link: &link },
+    )
+}
+
+/// The message carrying the code that confirms an account erasure
+pub fn erase_confirmation(to: &str, username: &str, code: &str) -> Email {
+    render(
+        to,
+        "Confirm erasing your account",
+        EraseAccountHtml { username, code },
+        EraseAccountText { username, code },
+    )
+}
+
+#[derive(Template)]
+#[template(path = "email/erase_account.html")]
+struct EraseAccountHtml<'a> {
+    username: &'a str,
+    code: &'a str,
+}
+
+#[derive(Template)]
+#[template(path = "email/erase_account.txt")]
+struct EraseAccountText<'a> {
+    username: &'a str,
+    code: &'a str,
+}
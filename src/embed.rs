@@ -0,0 +1,177 @@
+//! oEmbed responses and the embeddable player page.
+//!
+//! Both are meant for other sites, so unlike the rest of the API they
+//! answer cross-origin requests from anywhere, though never with
+//! credentials.
+
+use actix_cors::Cors;
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::feed::escape_xml;
+use crate::types::user::{Playlist, Track, User};
+
+pub const DEFAULT_WIDTH: u32 = 400;
+pub const TRACK_HEIGHT: u32 = 120;
+pub const PLAYLIST_HEIGHT: u32 = 320;
+
+/// CORS for the embed routes: any origin may read them, with `*` rather
+/// than the caller's origin echoed back, so browsers won't send cookies or
+/// accept a credentialed response
+pub fn cors() -> Cors {
+    Cors::default()
+        .allow_any_origin()
+        .send_wildcard()
+        .allowed_methods(["GET"])
+        .max_age(3600)
+}
+
+/// What an embeddable URL points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmbedTarget {
+    Track(Uuid),
+    Playlist(Uuid),
+}
+
+impl EmbedTarget {
+    /// Resolve a public track or playlist URL served from `base_url`
+    pub fn parse(url: &str, base_url: &str) -> Option<Self> {
+        let path = url.strip_prefix(base_url)?;
+        let path = path.split(['?', '#']).next()?.trim_end_matches('/');
+        let mut segments = path.trim_start_matches('/').split('/');
+        
+        let target = match (segments.next()?, segments.next()?) {
+            ("tracks", id) => EmbedTarget::Track(id.parse().ok()?),
+            ("playlists", id) => EmbedTarget::Playlist(id.parse().ok()?),
+            _ => return None,
+        };
+        
+        segments.next().is_none().then_some(target)
+    }
+    
+    fn embed_path(&self) -> String {
+        match self {
+            EmbedTarget::Track(id) => format!("/embed/tracks/{}", id),
+            EmbedTarget::Playlist(id) => format!("/embed/playlists/{}", id),
+        }
+    }
+    
+    fn default_height(&self) -> u32 {
+        match self {
+            EmbedTarget::Track(_) => TRACK_HEIGHT,
+            EmbedTarget::Playlist(_) => PLAYLIST_HEIGHT,
+        }
+    }
+}
+
+/// An oEmbed 1.0 `rich` response
+#[derive(Debug, Serialize, ToSchema)]
+pub struct OEmbed {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub version: &'static str,
+    pub title: String,
+    pub author_name: String,
+    pub author_url: String,
+    pub provider_name: &'static str,
+    pub provider_url: String,
+    pub html: String,
+    pub width: u32,
+    pub height: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thumbnail_url: Option<String>,
+}
+
+impl OEmbed {
+    /// Build a response for `target`, fitting the iframe inside the
+    /// consumer's `maxwidth`/`maxheight` if given
+    pub fn new(
+        target: EmbedTarget,
+        title: &str,
+        author: &User,
+        thumbnail_url: Option<String>,
+        base_url: &str,
+        max_width: Option<u32>,
+        max_height: Option<u32>,
+    ) -> Self {
+        let width = max_width.map_or(DEFAULT_WIDTH, |max| max.min(DEFAULT_WIDTH));
+        let height = max_height.map_or(target.default_height(), |max| max.min(target.default_height()));
+        let src = format!("{}{}", base_url, target.embed_path());
+        
+        Self {
+            kind: "rich",
+            version: "1.0",
+            title: title.to_string(),
+            author_name: author.username.clone(),
+            author_url: format!("{}/users/{}", base_url, author.username),
+            provider_name: "Libretune",
+            provider_url: base_url.to_string(),
+            html: format!(
+                "<iframe src=\"{}\" width=\"{}\" height=\"{}\" frameborder=\"0\" allow=\"autoplay\" title=\"{}\"></iframe>",
+                escape_xml(&src),
+                width,
+                height,
+                escape_xml(title),
+            ),
+            width,
+            height,
+            thumbnail_url,
+        }
+    }
+}
+
+const PAGE_STYLE: &str = "body{margin:0;font-family:sans-serif;background:#111;color:#eee}\
+    .player{display:flex;gap:12px;padding:12px;align-items:center}\
+    .player img{width:96px;height:96px;object-fit:cover;border-radius:4px}\
+    .player div{flex:1;min-width:0}\
+    a{color:inherit}audio{width:100%}";
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+        <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n\
+        <title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_xml(title),
+        PAGE_STYLE,
+        body,
+    )
+}
+
+fn track_player(track: &Track, base_url: &str) -> String {
+    let cover = track.cover_image_url.as_ref()
+        .map(|url| format!("<img src=\"{}\" alt=\"\">", escape_xml(url)))
+        .unwrap_or_default();
+    
+    format!(
+        "<div class=\"player\">{}<div>\
+        <a href=\"{}/tracks/{}\" target=\"_blank\" rel=\"noopener\">{}</a>\
+        <audio controls preload=\"none\" src=\"{}\"></audio>\
+        </div></div>\n",
+        cover,
+        escape_xml(base_url),
+        track.id,
+        escape_xml(&track.title),
+        escape_xml(&format!("{}/tracks/{}/stream", base_url, track.id)),
+    )
+}
+
+/// Player page for a single track
+pub fn render_track(track: &Track, base_url: &str) -> String {
+    page(&track.title, &track_player(track, base_url))
+}
+
+/// Player page listing a playlist's public tracks
+pub fn render_playlist(playlist: &Playlist, base_url: &str) -> String {
+    let mut body = format!(
+        "<p><a href=\"{}/playlists/{}\" target=\"_blank\" rel=\"noopener\">{}</a></p>\n",
+        escape_xml(base_url),
+        playlist.id,
+        escape_xml(&playlist.name),
+    );
+    
+    for track in playlist.tracks.iter().filter(|t| t.is_public && !t.is_deleted) {
+        body.push_str(&track_player(track, base_url));
+    }
+    
+    page(&playlist.name, &body)
+}
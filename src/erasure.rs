@@ -0,0 +1,295 @@
+//! Account erasure ("right to be forgotten").
+//!
+//! Unlike the soft delete in `UserOperations::delete_user`, an erasure removes
+//! the account and strips the user's id from every other document. It runs as
+//! a background job whose progress is persisted after each step, so a job that
+//! dies halfway through is picked up again by `resume_pending` on startup.
+//!
+//! The files the user put up go first, while the account and its releases
+//! still say which they are.
+
+use std::collections::HashSet;
+use tracing::{error, info};
+use uuid::Uuid;
+use crate::db::announcement::AnnouncementOperations;
+use crate::db::email_outbox::EmailOutboxOperations;
+use crate::db::email_token::EmailTokenOperations;
+use crate::db::erasure::ErasureOperations;
+use crate::db::error::Error;
+use crate::db::federation::FederationOperations;
+use crate::db::lyrics::LyricsOperations;
+use crate::db::notification::NotificationOperations;
+use crate::db::push::PushOperations;
+use crate::db::release::ReleaseOperations;
+use crate::db::verification::VerificationOperations;
+use crate::db::report::ReportOperations;
+use crate::db::session::SessionOperations;
+use crate::db::share_link::ShareLinkOperations;
+use crate::db::upload::UploadOperations;
+use crate::db::webhook::WebhookOperations;
+use crate::db::{take_rows, Db, UserOperations};
+use crate::resumable;
+use crate::storage::storage;
+use crate::types::credit::CreditStatus;
+use crate::types::erasure::{ErasureJob, ErasureStatus, ErasureStep};
+use crate::types::id::UserId;
+use crate::types::user::{Comment, Track, User};
+
+/// Content left behind in place of an erased user's comments
+pub const TOMBSTONE_CONTENT: &str = "[deleted]";
+
+const SCRUB_BATCH_SIZE: u32 = 100;
+
+/// Run a job in the background
+pub fn spawn(db: Db, job: ErasureJob) {
+    actix_web::rt::spawn(async move {
+        let job_id = job.id;
+        if let Err(e) = run(&db, job).await {
+            error!("Erasure job {} failed: {}", job_id, e);
+        }
+    });
+}
+
+/// Resume every job that didn't complete, e.g. after a crash
+pub async fn resume_pending(db: &Db) -> Result<(), Error> {
+    for job in ErasureOperations::new(db).get_unfinished_jobs().await? {
+        info!("Resuming erasure job {}", job.id);
+        spawn(db.clone(), job);
+    }
+    Ok(())
+}
+
+/// Run the remaining steps of a job, recording progress after each one
+pub async fn run(db: &Db, mut job: ErasureJob) -> Result<ErasureJob, Error> {
+    let Some(user_id) = job.user_id else {
+        return Ok(job);
+    };
+
+    job.status = ErasureStatus::Running;
+    job.attempts += 1;
+    job = ErasureOperations::new(db).save_job(job).await?;
+
+    for step in ErasureStep::ALL {
+        if job.completed_steps.contains(&step) {
+            continue;
+        }
+
+        match run_step(db, step, user_id).await {
+            Ok(scrubbed) => {
+                job.records_scrubbed += scrubbed;
+                job.completed_steps.push(step);
+                job.last_error = None;
+                job = ErasureOperations::new(db).save_job(job).await?;
+            }
+            Err(e) => {
+                job.status = ErasureStatus::Failed;
+                job.last_error = Some(e.to_string());
+                ErasureOperations::new(db).save_job(job).await?;
+                return Err(e);
+            }
+        }
+    }
+
+    job.status = ErasureStatus::Completed;
+    job.user_id = None;
+    job.completed_at = Some(db.now());
+    info!("Erasure job {} completed", job.id);
+
+    ErasureOperations::new(db).save_job(job).await
+}
+
+/// Run one step, returning how many records it touched
+async fn run_step(db: &Db, step: ErasureStep, user_id: UserId) -> Result<u64, Error> {
+    match step {
+        ErasureStep::DeleteMedia => delete_media(db, user_id).await,
+        ErasureStep::RevokeSessions => {
+            SessionOperations::new(db).delete_sessions_for_user(user_id).await?;
+            EmailTokenOperations::new(db).delete_tokens_for_user(user_id).await?;
+            // Queued mail would otherwise keep the address around
+            if let Ok(user) = UserOperations::new(db).get_user_by_id(user_id).await {
+                EmailOutboxOperations::new(db).delete_for_address(&user.email).await?;
+            }
+            WebhookOperations::new(db).delete_webhooks_for_user(user_id).await?;
+            FederationOperations::new(db).delete_remote_followers_for_user(user_id).await?;
+            ReportOperations::new(db).delete_reports_by_user(user_id).await?;
+            AnnouncementOperations::new(db).delete_dismissals_by_user(user_id).await?;
+            NotificationOperations::new(db).delete_notifications_for_user(user_id).await?;
+            PushOperations::new(db).delete_subscriptions_for_user(user_id).await?;
+            ReleaseOperations::new(db).delete_releases_for_user(user_id).await?;
+            VerificationOperations::new(db).delete_requests_for_user(user_id).await?;
+            LyricsOperations::new(db).delete_lyrics_for_user(user_id).await?;
+            ShareLinkOperations::new(db).delete_links_for_user(user_id).await?;
+            for upload in UploadOperations::new(db).delete_uploads_for_user(user_id).await? {
+                resumable::remove_file(upload.id).await?;
+            }
+            Ok(0)
+        }
+        ErasureStep::ScrubReferences => scrub_references(db, user_id).await,
+        ErasureStep::DeleteAccount => {
+            db.delete_record("users", user_id).await?;
+            Ok(1)
+        }
+    }
+}
+
+/// Delete every file the user uploaded from storage. Files already gone are
+/// skipped, so a resumed job can run it again.
+async fn delete_media(db: &Db, user_id: UserId) -> Result<u64, Error> {
+    let user = match UserOperations::new(db).get_user_by_id(user_id).await {
+        Ok(user) => user,
+        Err(Error::UserNotFound) => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let releases = ReleaseOperations::new(db).list_releases(user_id, true).await?;
+
+    let mut seen = HashSet::new();
+    let keys: Vec<String> = user.media_urls()
+        .chain(releases.iter().filter_map(|release| release.cover_image_url.as_deref()))
+        .filter_map(|url| storage().key_for_url(url))
+        .filter(|key| seen.insert(key.clone()))
+        .collect();
+
+    for key in &keys {
+        storage().delete(key).await?;
+    }
+
+    Ok(keys.len() as u64)
+}
+
+/// Remove the user from every other user's document
+async fn scrub_references(db: &Db, user_id: UserId) -> Result<u64, Error> {
+    let mut scrubbed = 0;
+
+    // Scrubbed documents stop matching the filter, so always read the first page
+    // and step past the ones that matched but needed no change.
+    let mut offset = 0;
+    loop {
+        let mut response = db
+            .query(
+                "SELECT *, record::id(id) AS id FROM users WHERE
+                record::id(id) != $user_id AND
+                string::contains(<string> [profile, playlists], $user_id)
+                ORDER BY id LIMIT $limit START $offset"
+            )
+            .bind(("user_id", user_id.to_string()))
+            .bind(("limit", SCRUB_BATCH_SIZE))
+            .bind(("offset", offset))
+            .await?;
+        let users: Vec<User> = take_rows(&mut response, 0)?;
+
+        if users.is_empty() {
+            break;
+        }
+
+        for mut user in users {
+            if scrub_user(&mut user, user_id) {
+                db.update_record("users", user.id, &user).await?;
+                scrubbed += 1;
+            } else {
+                offset += 1;
+            }
+        }
+    }
+
+    Ok(scrubbed)
+}
+
+/// Strip `erased` from a user's social graph, tracks and playlists
+fn scrub_user(user: &mut User, erased: UserId) -> bool {
+    let mut changed = false;
+
+    if let Some(profile) = user.profile.as_mut() {
+        for list in [
+            &mut profile.friends_list,
+            &mut profile.blocked_users,
+            &mut profile.followers,
+            &mut profile.following,
+        ] {
+            changed |= remove_id(list, erased);
+        }
+
+        if let Some(reports) = profile.reports.as_mut() {
+            let before = reports.len();
+            reports.retain(|r| r.user_id != erased);
+            changed |= reports.len() != before;
+        }
+
+        for track in profile.uploads.iter_mut().flatten() {
+            changed |= scrub_track(track, erased);
+        }
+    }
+
+    for playlist in user.playlists.iter_mut().flatten() {
+        let before = playlist.tracks.len();
+        playlist.tracks.retain(|t| t.user_id != erased);
+        changed |= playlist.tracks.len() != before;
+
+        for track in playlist.tracks.iter_mut() {
+            changed |= scrub_track(track, erased);
+        }
+    }
+
+    changed
+}
+
+fn scrub_track(track: &mut Track, erased: UserId) -> bool {
+    let mut changed = false;
+    for comment in track.comments.iter_mut().flatten() {
+        changed |= scrub_comment(comment, erased);
+    }
+    if changed {
+        track.comment_count = track.live_comment_count();
+    }
+
+    // Credits naming the user stay on as the plain names the owner gave
+    for credit in track.credited_artists.iter_mut().filter(|credit| credit.user_id == Some(erased)) {
+        credit.user_id = None;
+        credit.status = CreditStatus::Accepted;
+        credit.notify_comments = false;
+        changed = true;
+    }
+
+    changed
+}
+
+/// Replace the erased user's comments with tombstones, keeping ids and parent
+/// links so reply threads stay intact
+fn scrub_comment(comment: &mut Comment, erased: UserId) -> bool {
+    let mut changed = false;
+
+    if comment.user_id == erased {
+        comment.user_id = UserId::from(Uuid::nil());
+        comment.content = TOMBSTONE_CONTENT.to_string();
+        comment.is_deleted = true;
+        comment.likes = None;
+        comment.dislikes = None;
+        comment.reports = None;
+        changed = true;
+    }
+
+    changed |= remove_id(&mut comment.likes, erased);
+    changed |= remove_id(&mut comment.dislikes, erased);
+
+    if let Some(reports) = comment.reports.as_mut() {
+        let before = reports.len();
+        reports.retain(|r| r.user_id != erased);
+        changed |= reports.len() != before;
+    }
+
+    for reply in comment.replies.iter_mut().flatten() {
+        changed |= scrub_comment(reply, erased);
+    }
+
+    changed
+}
+
+fn remove_id(ids: &mut Option<Vec<UserId>>, erased: UserId) -> bool {
+    match ids {
+        Some(ids) => {
+            let before = ids.len();
+            ids.retain(|id| *id != erased);
+            ids.len() != before
+        }
+        None => false,
+    }
+}
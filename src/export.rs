@@ -0,0 +1,238 @@
+//! Personal data export ("right of access").
+//!
+//! The archive is built while it's sent. Each kind of data goes in as its
+//! own JSON file and uploaded media is copied in one file at a time, all
+//! through a fixed-size buffer, so an export of any size holds about one
+//! page of records and one buffer of a file in memory. A failure partway
+//! through ends the response early, before the archive's central directory,
+//! so a client can't mistake it for a complete one.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+use async_zip::base::write::EntryStreamWriter;
+use async_zip::tokio::write::ZipFileWriter;
+use async_zip::{Compression, ZipEntryBuilder};
+use chrono::{DateTime, Utc};
+use futures_util::io::{copy, AsyncWrite, AsyncWriteExt};
+use serde::Serialize;
+use tokio::fs::File;
+use tokio::io::DuplexStream;
+use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
+use tokio_util::io::ReaderStream;
+use tracing::warn;
+use crate::clock;
+use crate::db::error::Error;
+use crate::db::notification::NotificationOperations;
+use crate::db::{take_rows, Db};
+use crate::storage::{self, storage};
+use crate::types::id::UserId;
+use crate::types::user::{Comment, CreatedVia, Playlist, Track, User, UserProfile};
+
+pub const CONTENT_TYPE: &str = "application/zip";
+
+/// Bytes of archive buffered ahead of the client
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Records read per query
+const PAGE_SIZE: u32 = 50;
+
+type Archive = ZipFileWriter<DuplexStream>;
+
+/// The account itself, without its password hash
+#[derive(Serialize)]
+struct Account<'a> {
+    id: UserId,
+    username: &'a str,
+    email: &'a str,
+    email_verified: bool,
+    bio: Option<&'a str>,
+    created_via: &'a CreatedVia,
+    legal_hold: bool,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl<'a> From<&'a User> for Account<'a> {
+    fn from(user: &'a User) -> Self {
+        Account {
+            id: user.id,
+            username: &user.username,
+            email: &user.email,
+            email_verified: user.email_verified,
+            bio: user.bio.as_deref(),
+            created_via: &user.created_via,
+            legal_hold: user.legal_hold,
+            created_at: user.created_at,
+            updated_at: user.updated_at,
+        }
+    }
+}
+
+/// What the archive is saved as, e.g. `libretune-export-2024-05-03.zip`
+pub fn filename() -> String {
+    format!("libretune-export-{}.zip", clock::now().format("%Y-%m-%d"))
+}
+
+/// `user`'s archive, built as it's read
+pub fn stream(db: Db, user: User) -> ReaderStream<DuplexStream> {
+    let (writer, reader) = tokio::io::duplex(BUFFER_SIZE);
+    actix_web::rt::spawn(async move {
+        let user_id = user.id;
+        if let Err(e) = write_archive(&db, user, writer).await {
+            warn!("Export for user {} ended early: {}", user_id, e);
+        }
+    });
+
+    ReaderStream::new(reader)
+}
+
+fn archive_error(e: impl Display) -> Error {
+    Error::Db(e.to_string())
+}
+
+fn json_entry(name: &str) -> ZipEntryBuilder {
+    ZipEntryBuilder::new(name.to_string().into(), Compression::Deflate)
+}
+
+async fn add_json<T: Serialize>(zip: &mut Archive, name: &str, value: &T) -> Result<(), Error> {
+    let bytes = serde_json::to_vec_pretty(value)?;
+    zip.write_entry_whole(json_entry(name), &bytes).await.map_err(archive_error)
+}
+
+/// A JSON array written into an entry one element at a time
+struct JsonArray<W> {
+    out: W,
+    empty: bool,
+}
+
+impl<W: AsyncWrite + Unpin> JsonArray<W> {
+    async fn open(mut out: W) -> Result<Self, Error> {
+        out.write_all(b"[").await.map_err(archive_error)?;
+        Ok(JsonArray { out, empty: true })
+    }
+
+    async fn push<T: Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        let mut bytes = if self.empty { Vec::new() } else { vec![b','] };
+        serde_json::to_writer(&mut bytes, value)?;
+        self.empty = false;
+        self.out.write_all(&bytes).await.map_err(archive_error)
+    }
+
+    async fn finish(mut self) -> Result<W, Error> {
+        self.out.write_all(b"]").await.map_err(archive_error)?;
+        Ok(self.out)
+    }
+}
+
+async fn open_array<'a>(zip: &'a mut Archive, name: &str) -> Result<JsonArray<EntryStreamWriter<'a, Compat<DuplexStream>>>, Error> {
+    let entry = zip.write_entry_stream(json_entry(name)).await.map_err(archive_error)?;
+    JsonArray::open(entry).await
+}
+
+async fn write_archive(db: &Db, user: User, out: DuplexStream) -> Result<(), Error> {
+    let mut zip = ZipFileWriter::with_tokio(out);
+
+    add_json(&mut zip, "account.json", &Account::from(&user)).await?;
+
+    // Uploads get a file of their own
+    let profile = user.profile.as_ref().map(|profile| UserProfile { uploads: None, ..profile.clone() });
+    add_json(&mut zip, "profile.json", &profile).await?;
+
+    // Other people's comments on the tracks aren't the user's data, and
+    // the user's own go in with the rest of their comments
+    let tracks: Vec<Track> = user.profile.iter()
+        .flat_map(|profile| profile.uploads.iter().flatten())
+        .map(|track| Track { comments: None, ..track.clone() })
+        .collect();
+    add_json(&mut zip, "tracks.json", &tracks).await?;
+
+    let playlists: Vec<&Playlist> = user.playlists.iter().flatten().chain(&user.deleted_playlists).collect();
+    add_json(&mut zip, "playlists.json", &playlists).await?;
+
+    // Comments live on the tracks they're left on, so every uploader whose
+    // tracks mention the user is read, a page at a time
+    let mut comments = open_array(&mut zip, "comments.json").await?;
+    let mut offset = 0;
+    loop {
+        let mut response = db
+            .query(
+                "SELECT *, record::id(id) AS id FROM users
+                WHERE string::contains(<string> (profile.uploads ?? []), $user_id)
+                ORDER BY id LIMIT $limit START $offset"
+            )
+            .bind(("user_id", user.id.to_string()))
+            .bind(("limit", PAGE_SIZE))
+            .bind(("offset", offset))
+            .await?;
+        let uploaders: Vec<User> = take_rows(&mut response, 0)?;
+
+        let mut found = Vec::new();
+        for track in uploaders.iter().filter_map(|u| u.profile.as_ref()).flat_map(|p| p.uploads.iter().flatten()) {
+            authored_by(track.comments.as_ref(), user.id, &mut found);
+        }
+        for comment in &found {
+            comments.push(comment).await?;
+        }
+
+        if uploaders.len() < PAGE_SIZE as usize {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    comments.finish().await?.close().await.map_err(archive_error)?;
+
+    let mut notifications = open_array(&mut zip, "notifications.json").await?;
+    let mut offset = 0;
+    loop {
+        let page = NotificationOperations::new(db).get_notifications(user.id, PAGE_SIZE, offset).await?;
+        for notification in &page {
+            notifications.push(notification).await?;
+        }
+        if page.len() < PAGE_SIZE as usize {
+            break;
+        }
+        offset += PAGE_SIZE;
+    }
+    notifications.finish().await?.close().await.map_err(archive_error)?;
+
+    for key in media_keys(&user) {
+        let Some(path) = storage::local().path(&key) else { continue };
+        let file = match File::open(&path).await {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Export for user {} left out {}: {}", user.id, key, e);
+                continue;
+            }
+        };
+
+        // Audio and images are compressed already
+        let entry = ZipEntryBuilder::new(format!("media/{}", key).into(), Compression::Stored);
+        let mut entry = zip.write_entry_stream(entry).await.map_err(archive_error)?;
+        copy(file.compat(), &mut entry).await.map_err(archive_error)?;
+        entry.close().await.map_err(archive_error)?;
+    }
+
+    zip.close().await.map_err(archive_error)?;
+    Ok(())
+}
+
+/// Collect the comments and replies `user_id` wrote, without the replies
+/// under them, which are listed in their own right if they're theirs
+fn authored_by(comments: Option<&Vec<Comment>>, user_id: UserId, found: &mut Vec<Comment>) {
+    for comment in comments.into_iter().flatten() {
+        if comment.user_id == user_id {
+            found.push(Comment { replies: None, ..comment.clone() });
+        }
+        authored_by(comment.replies.as_ref(), user_id, found);
+    }
+}
+
+/// Storage keys of the files the user uploaded, each once. Files stored
+/// elsewhere, like imported tracks' audio, aren't ours to copy.
+fn media_keys(user: &User) -> Vec<String> {
+    let mut seen = HashSet::new();
+    user.media_urls()
+        .filter_map(|url| storage().key_for_url(url))
+        .filter(|key| seen.insert(key.clone()))
+        .collect()
+}
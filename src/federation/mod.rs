@@ -0,0 +1,174 @@
+//! Read-only ActivityPub federation.
+//!
+//! Public profiles are exposed as `Person` actors with an outbox of `Create`
+//! activities for their public tracks, discoverable through WebFinger. Remote
+//! servers may follow an actor; their `Follow` is accepted and recorded so
+//! follower counts include remote followers. HTTP signatures are not checked
+//! yet. Everything here is off unless `FEDERATION=true`.
+
+use std::env;
+use actix_web::guard::GuardContext;
+use actix_web::http::header;
+use actix_web::web;
+use serde_json::{json, Value};
+use crate::config;
+use crate::feed::{self, audio_mime_type};
+use crate::types::user::{Track, User};
+
+pub mod routes;
+
+pub const ACTIVITY_JSON: &str = "application/activity+json";
+pub const LD_JSON: &str = "application/ld+json";
+pub const ACTIVITYSTREAMS_CONTEXT: &str = "https://www.w3.org/ns/activitystreams";
+pub const PUBLIC_AUDIENCE: &str = "https://www.w3.org/ns/activitystreams#Public";
+
+/// Whether federation endpoints are served
+pub fn enabled() -> bool {
+    env::var("FEDERATION").is_ok_and(|v| matches!(v.to_lowercase().as_str(), "true" | "1" | "on"))
+}
+
+/// Register the federation routes
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(routes::webfinger)
+        .service(routes::actor)
+        .service(routes::outbox)
+        .service(routes::followers)
+        .service(routes::inbox);
+}
+
+/// Route guard matching requests that ask for ActivityPub JSON
+pub fn accepts_activity_json(ctx: &GuardContext) -> bool {
+    ctx.head()
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(ACTIVITY_JSON) || accept.contains(LD_JSON))
+}
+
+/// Host part of the public URL, used as the WebFinger domain
+pub fn domain() -> String {
+    let url = config::public_url();
+    let without_scheme = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+    without_scheme.split('/').next().unwrap_or_default().to_string()
+}
+
+pub fn actor_url(username: &str) -> String {
+    format!("{}/users/{}", config::public_url(), username)
+}
+
+/// Whether a user may be exposed as an actor
+pub fn is_federated(user: &User) -> bool {
+    user.profile.as_ref().is_some_and(|p| !p.is_private && !p.is_deleted() && !p.is_banned())
+}
+
+/// The `Person` document for a user
+pub fn actor(user: &User) -> Value {
+    let id = actor_url(&user.username);
+    let profile = user.profile.as_ref();
+    let name = profile.map(|p| p.profile_name.as_str()).filter(|n| !n.is_empty()).unwrap_or(&user.username);
+    let summary = profile.and_then(|p| p.profile_bio.as_deref()).or(user.bio.as_deref());
+    
+    let mut actor = json!({
+        "@context": ACTIVITYSTREAMS_CONTEXT,
+        "id": id,
+        "type": "Person",
+        "preferredUsername": user.username,
+        "name": name,
+        "url": id,
+        "inbox": format!("{}/inbox", id),
+        "outbox": format!("{}/outbox", id),
+        "followers": format!("{}/followers", id),
+        "published": user.created_at.to_rfc3339(),
+    });
+    
+    if let Some(summary) = summary {
+        actor["summary"] = json!(summary);
+    }
+    if let Some(picture) = profile.and_then(|p| p.profile_picture.as_deref()) {
+        actor["icon"] = json!({ "type": "Image", "url": picture });
+    }
+    if let Some(banner) = profile.and_then(|p| p.profile_banner.as_deref()) {
+        actor["image"] = json!({ "type": "Image", "url": banner });
+    }
+    
+    actor
+}
+
+/// A `Create` activity announcing a public track
+pub fn create_activity(user: &User, track: &Track) -> Value {
+    let actor = actor_url(&user.username);
+    let object_id = format!("{}/tracks/{}", config::public_url(), track.id);
+    let media_type = track.technical_metadata.as_ref()
+        .map_or("audio/mpeg", |meta| audio_mime_type(&meta.format));
+    
+    let mut object = json!({
+        "id": object_id,
+        "type": "Audio",
+        "name": track.title,
+        "attributedTo": actor,
+        "url": object_id,
+        "published": track.created_at.to_rfc3339(),
+        "to": [PUBLIC_AUDIENCE],
+        "attachment": [{
+            "type": "Audio",
+            "mediaType": media_type,
+            "url": track.stream_url(),
+        }],
+    });
+    
+    if let Some(description) = &track.description {
+        object["content"] = json!(description);
+    }
+    if let Some(cover) = &track.cover_image_url {
+        object["icon"] = json!({ "type": "Image", "url": cover });
+    }
+    
+    json!({
+        "id": format!("{}/activity", object_id),
+        "type": "Create",
+        "actor": actor,
+        "published": track.created_at.to_rfc3339(),
+        "to": [PUBLIC_AUDIENCE],
+        "object": object,
+    })
+}
+
+/// The outbox: `Create` activities for the latest public tracks
+pub fn outbox(user: &User) -> Value {
+    let items: Vec<Value> = feed::feed_tracks(user)
+        .into_iter()
+        .map(|track| create_activity(user, track))
+        .collect();
+    
+    json!({
+        "@context": ACTIVITYSTREAMS_CONTEXT,
+        "id": format!("{}/outbox", actor_url(&user.username)),
+        "type": "OrderedCollection",
+        "totalItems": items.len(),
+        "orderedItems": items,
+    })
+}
+
+/// The followers collection. Only the total is exposed.
+pub fn followers(user: &User, total: u64) -> Value {
+    json!({
+        "@context": ACTIVITYSTREAMS_CONTEXT,
+        "id": format!("{}/followers", actor_url(&user.username)),
+        "type": "OrderedCollection",
+        "totalItems": total,
+    })
+}
+
+/// WebFinger JRD pointing `acct:username@domain` at the actor
+pub fn webfinger(user: &User) -> Value {
+    let actor = actor_url(&user.username);
+    
+    json!({
+        "subject": format!("acct:{}@{}", user.username, domain()),
+        "aliases": [actor],
+        "links": [
+            { "rel": "self", "type": ACTIVITY_JSON, "href": actor },
+            { "rel": "http://webfinger.net/rel/profile-page", "type": "text/html", "href": actor },
+        ],
+    })
+}
@@ -0,0 +1,148 @@
+use actix_web::{get, post, web, HttpResponse};
+use serde::Deserialize;
+use serde_json::Value;
+use utoipa::IntoParams;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::federation::FederationOperations;
+use crate::db::{Db, UserOperations};
+use crate::types::user::User;
+use super::ACTIVITY_JSON;
+
+/// Load a user that may be exposed over federation. Private, deleted and
+/// banned users look the same as missing ones.
+async fn load_actor(db: &Db, username: String) -> Result<User, Error> {
+    let user = UserOperations::new(db).get_user_by_username(username).await?;
+    
+    if !super::is_federated(&user) {
+        return Err(Error::UserNotFound);
+    }
+    
+    Ok(user)
+}
+
+fn activity_response(body: Value) -> HttpResponse {
+    HttpResponse::Ok().content_type(ACTIVITY_JSON).json(body)
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct WebfingerQuery {
+    /// `acct:username@domain`
+    pub resource: String,
+}
+
+/// WebFinger lookup of a local account
+#[utoipa::path(
+    tag = "federation",
+    params(WebfingerQuery),
+    responses(
+        (status = 200, content_type = "application/jrd+json", body = Object),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/.well-known/webfinger")]
+pub async fn webfinger(query: web::Query<WebfingerQuery>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let account = query.resource.strip_prefix("acct:").ok_or(Error::UserNotFound)?;
+    let (username, domain) = account.split_once('@').ok_or(Error::UserNotFound)?;
+    
+    if !domain.eq_ignore_ascii_case(&super::domain()) {
+        return Err(Error::UserNotFound);
+    }
+    
+    let user = load_actor(&db, username.to_string()).await?;
+    
+    Ok(HttpResponse::Ok()
+        .content_type("application/jrd+json")
+        .json(super::webfinger(&user)))
+}
+
+/// A user's ActivityPub actor. Only matches requests accepting ActivityPub JSON.
+#[utoipa::path(
+    tag = "federation",
+    params(("username" = String, Path)),
+    responses(
+        (status = 200, content_type = "application/activity+json", body = Object),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/{username}", guard = "super::accepts_activity_json")]
+pub async fn actor(path: web::Path<String>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = load_actor(&db, path.into_inner()).await?;
+    
+    Ok(activity_response(super::actor(&user)))
+}
+
+/// A user's outbox of public tracks
+#[utoipa::path(
+    tag = "federation",
+    params(("username" = String, Path)),
+    responses(
+        (status = 200, content_type = "application/activity+json", body = Object),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/{username}/outbox", guard = "super::accepts_activity_json")]
+pub async fn outbox(path: web::Path<String>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = load_actor(&db, path.into_inner()).await?;
+    
+    Ok(activity_response(super::outbox(&user)))
+}
+
+/// A user's followers, local and remote. Only the total is exposed. Only
+/// matches requests accepting ActivityPub JSON, so it doesn't shadow the
+/// REST followers listing.
+#[utoipa::path(
+    tag = "federation",
+    params(("username" = String, Path)),
+    responses(
+        (status = 200, content_type = "application/activity+json", body = Object),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/{username}/followers", guard = "super::accepts_activity_json")]
+pub async fn followers(path: web::Path<String>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = load_actor(&db, path.into_inner()).await?;
+    
+    let local = user.profile.as_ref()
+        .and_then(|p| p.followers.as_ref())
+        .map_or(0, |f| f.len() as u64);
+    let remote = FederationOperations::new(&db).count_remote_followers(user.id).await?;
+    
+    Ok(activity_response(super::followers(&user, local + remote)))
+}
+
+/// A user's inbox. `Follow` and `Undo` of a follow are recorded; every other
+/// activity is accepted and ignored. Signatures are not verified yet.
+#[utoipa::path(
+    tag = "federation",
+    params(("username" = String, Path)),
+    request_body(content = Object, content_type = "application/activity+json"),
+    responses(
+        (status = 202, description = "Activity accepted"),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("/users/{username}/inbox")]
+pub async fn inbox(path: web::Path<String>, body: web::Bytes, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = load_actor(&db, path.into_inner()).await?;
+    let activity: Value = serde_json::from_slice(&body)
+        .map_err(|_| Error::Validation("activity must be a JSON object".to_string()))?;
+    
+    let kind = activity["type"].as_str().unwrap_or_default();
+    let remote_actor = activity["actor"].as_str()
+        .ok_or_else(|| Error::Validation("activity has no actor".to_string()))?
+        .to_string();
+    
+    match kind {
+        "Follow" => {
+            let follow_id = activity["id"].as_str().map(str::to_string);
+            FederationOperations::new(&db).add_remote_follower(user.id, remote_actor, follow_id).await?;
+        }
+        "Undo" if activity["object"]["type"].as_str() == Some("Follow") => {
+            FederationOperations::new(&db).remove_remote_follower(user.id, remote_actor).await?;
+        }
+        _ => {}
+    }
+    
+    Ok(HttpResponse::Accepted().finish())
+}
@@ -0,0 +1,182 @@
+//! RSS 2.0 and Atom documents of a user's public tracks.
+
+use std::fmt::Write;
+use chrono::{DateTime, Utc};
+use crate::types::user::{Track, User};
+
+/// Most items a feed will contain
+pub const MAX_FEED_ITEMS: usize = 50;
+
+/// The tracks that belong in a user's feed, newest first
+pub fn feed_tracks(user: &User) -> Vec<&Track> {
+    let mut tracks: Vec<&Track> = user.profile.iter()
+        .flat_map(|p| p.uploads.iter().flatten())
+        .filter(|t| t.is_public && !t.is_deleted)
+        .collect();
+    tracks.sort_by_key(|t| std::cmp::Reverse(t.created_at));
+    tracks.truncate(MAX_FEED_ITEMS);
+    tracks
+}
+
+/// When the feed last changed
+pub fn last_updated(tracks: &[&Track]) -> Option<DateTime<Utc>> {
+    tracks.iter().map(|t| t.updated_at).max()
+}
+
+/// Escape text for use in XML content and attribute values
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// MIME type for an audio container format such as "mp3" or "flac"
+pub fn audio_mime_type(format: &str) -> &'static str {
+    match format.to_lowercase().as_str() {
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "flac" => "audio/flac",
+        "ogg" | "opus" => "audio/ogg",
+        "aac" => "audio/aac",
+        "m4a" | "mp4" => "audio/mp4",
+        "webm" => "audio/webm",
+        _ => "application/octet-stream",
+    }
+}
+
+fn display_name(user: &User) -> &str {
+    user.profile.as_ref()
+        .map(|p| p.profile_name.as_str())
+        .filter(|name| !name.is_empty())
+        .unwrap_or(&user.username)
+}
+
+fn enclosure(track: &Track) -> (&'static str, u64) {
+    match &track.technical_metadata {
+        Some(meta) => (audio_mime_type(&meta.format), meta.file_size),
+        None => ("application/octet-stream", 0),
+    }
+}
+
+/// Render an RSS 2.0 document
+pub fn render_rss(user: &User, tracks: &[&Track], base_url: &str) -> String {
+    let profile_url = format!("{}/users/{}", base_url, user.username);
+    let title = escape_xml(display_name(user));
+    let mut xml = String::new();
+    
+    let _ = write!(
+        xml,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\" xmlns:media=\"http://search.yahoo.com/mrss/\">\n\
+        <channel>\n\
+        <title>{title}</title>\n\
+        <link>{link}</link>\n\
+        <description>Tracks by {title}</description>\n\
+        <atom:link href=\"{link}/feed.rss\" rel=\"self\" type=\"application/rss+xml\"/>\n",
+        link = escape_xml(&profile_url),
+    );
+    if let Some(updated) = last_updated(tracks) {
+        let _ = writeln!(xml, "<lastBuildDate>{}</lastBuildDate>", updated.to_rfc2822());
+    }
+    
+    for track in tracks {
+        let track_url = format!("{}/tracks/{}", base_url, track.id);
+        let (mime_type, length) = enclosure(track);
+        
+        let _ = write!(
+            xml,
+            "<item>\n\
+            <title>{}</title>\n\
+            <link>{}</link>\n\
+            <guid isPermaLink=\"false\">{}</guid>\n\
+            <pubDate>{}</pubDate>\n",
+            escape_xml(&track.title),
+            escape_xml(&track_url),
+            track.id,
+            track.created_at.to_rfc2822(),
+        );
+        if let Some(description) = &track.description {
+            let _ = writeln!(xml, "<description>{}</description>", escape_xml(description));
+        }
+        if let Some(cover) = &track.cover_image_url {
+            let _ = writeln!(xml, "<media:thumbnail url=\"{}\"/>", escape_xml(cover));
+        }
+        let _ = write!(
+            xml,
+            "<enclosure url=\"{}\" length=\"{}\" type=\"{}\"/>\n</item>\n",
+            escape_xml(&format!("{}/stream", track_url)),
+            length,
+            mime_type,
+        );
+    }
+    
+    xml.push_str("</channel>\n</rss>\n");
+    xml
+}
+
+/// Render an Atom 1.0 document
+pub fn render_atom(user: &User, tracks: &[&Track], base_url: &str) -> String {
+    let profile_url = format!("{}/users/{}", base_url, user.username);
+    let updated = last_updated(tracks).unwrap_or(user.updated_at);
+    let mut xml = String::new();
+    
+    let _ = write!(
+        xml,
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+        <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+        <id>urn:uuid:{}</id>\n\
+        <title>{}</title>\n\
+        <updated>{}</updated>\n\
+        <link href=\"{link}\"/>\n\
+        <link href=\"{link}/feed.atom\" rel=\"self\"/>\n\
+        <author><name>{}</name></author>\n",
+        user.id,
+        escape_xml(display_name(user)),
+        updated.to_rfc3339(),
+        escape_xml(&user.username),
+        link = escape_xml(&profile_url),
+    );
+    
+    for track in tracks {
+        let track_url = format!("{}/tracks/{}", base_url, track.id);
+        let (mime_type, length) = enclosure(track);
+        
+        let _ = write!(
+            xml,
+            "<entry>\n\
+            <id>urn:uuid:{}</id>\n\
+            <title>{}</title>\n\
+            <published>{}</published>\n\
+            <updated>{}</updated>\n\
+            <link href=\"{}\"/>\n\
+            <link rel=\"enclosure\" href=\"{}\" length=\"{}\" type=\"{}\"/>\n",
+            track.id,
+            escape_xml(&track.title),
+            track.created_at.to_rfc3339(),
+            track.updated_at.to_rfc3339(),
+            escape_xml(&track_url),
+            escape_xml(&format!("{}/stream", track_url)),
+            length,
+            mime_type,
+        );
+        if let Some(description) = &track.description {
+            let _ = writeln!(xml, "<summary>{}</summary>", escape_xml(description));
+        }
+        if let Some(cover) = &track.cover_image_url {
+            let _ = writeln!(xml, "<link rel=\"related\" href=\"{}\"/>", escape_xml(cover));
+        }
+        xml.push_str("</entry>\n");
+    }
+    
+    xml.push_str("</feed>\n");
+    xml
+}
@@ -0,0 +1,649 @@
+//! Builders for users, tracks, playlists and comments, for the integration
+//! tests and the admin tool's `seed` command.
+//!
+//! Every builder starts from defaults that make a valid record, unique
+//! where uniqueness matters, so a test only names what it cares about:
+//!
+//! ```ignore
+//! let admin = UserFixture::new().username("a").verified().admin().create(&db).await?;
+//! let track = TrackFixture::new().owner(admin.id).title("Demo").create(&db).await?;
+//! ```
+//!
+//! `build` only makes the value; `create` writes it through the same
+//! operations the API uses, so fixtures obey the same invariants, and
+//! returns it as stored, all through the `db` it's handed.
+
+use std::sync::OnceLock;
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::auth::hash_password;
+use crate::clock;
+use crate::db::error::Error;
+use crate::db::playlist::PlaylistOperations;
+use crate::db::track::{find_comment, TrackOperations};
+use crate::db::verification::VerificationOperations;
+use crate::db::{Db, UserOperations};
+use crate::types::id::{CommentId, PlaylistId, TrackId, UserId};
+use crate::types::import::TrackManifestEntry;
+use crate::types::license::License;
+use crate::types::user::{username_sort_key, Comment, CreateUserInput, CreatedVia, Playlist, Track, User, UserProfile};
+use crate::types::verification::NewVerificationRequest;
+
+/// The password of every fixture user not given another
+pub const PASSWORD: &str = "correct horse battery staple";
+
+/// Eight random hex digits, to keep default names apart
+fn unique_suffix() -> String {
+    Uuid::new_v4().simple().to_string()[..8].to_string()
+}
+
+/// The hash of `PASSWORD`. Argon2 is slow on purpose, so it's hashed once
+/// per process and shared.
+fn default_hash() -> Result<String, Error> {
+    static HASH: OnceLock<String> = OnceLock::new();
+    if let Some(hash) = HASH.get() {
+        return Ok(hash.clone());
+    }
+    let hash = hash_password(PASSWORD)?;
+    Ok(HASH.get_or_init(|| hash).clone())
+}
+
+/// A user with a profile. Defaults to a unique `user_…` name, an
+/// `@example.com` address, `PASSWORD`, and a public, unverified,
+/// non-admin profile named after them.
+#[derive(Debug, Clone)]
+pub struct UserFixture {
+    username: String,
+    email: Option<String>,
+    password: Option<String>,
+    hashed_password: Option<String>,
+    created_via: CreatedVia,
+    profile_name: Option<String>,
+    pronouns: Option<String>,
+    bio: Option<String>,
+    private: bool,
+    email_verified: bool,
+    verified: bool,
+    admin: bool,
+}
+
+impl Default for UserFixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UserFixture {
+    pub fn new() -> Self {
+        UserFixture {
+            username: format!("user_{}", unique_suffix()),
+            email: None,
+            password: None,
+            hashed_password: None,
+            created_via: CreatedVia::Web,
+            profile_name: None,
+            pronouns: None,
+            bio: None,
+            private: false,
+            email_verified: false,
+            verified: false,
+            admin: false,
+        }
+    }
+
+    /// Exactly this username; the database has to not have it yet
+    pub fn username(mut self, username: impl Into<String>) -> Self {
+        self.username = username.into();
+        self
+    }
+
+    pub fn email(mut self, email: impl Into<String>) -> Self {
+        self.email = Some(email.into());
+        self
+    }
+
+    /// Sign in with `password` instead of `PASSWORD`
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// An already hashed password, for many users sharing one
+    pub fn hashed_password(mut self, hashed_password: impl Into<String>) -> Self {
+        self.hashed_password = Some(hashed_password.into());
+        self
+    }
+
+    pub fn created_via(mut self, created_via: CreatedVia) -> Self {
+        self.created_via = created_via;
+        self
+    }
+
+    pub fn profile_name(mut self, profile_name: impl Into<String>) -> Self {
+        self.profile_name = Some(profile_name.into());
+        self
+    }
+
+    pub fn pronouns(mut self, pronouns: impl Into<String>) -> Self {
+        self.pronouns = Some(pronouns.into());
+        self
+    }
+
+    /// The bio shown on their profile
+    pub fn bio(mut self, bio: impl Into<String>) -> Self {
+        self.bio = Some(bio.into());
+        self
+    }
+
+    pub fn private(mut self) -> Self {
+        self.private = true;
+        self
+    }
+
+    pub fn email_verified(mut self) -> Self {
+        self.email_verified = true;
+        self
+    }
+
+    /// With the verified badge, granted through an approved request
+    pub fn verified(mut self) -> Self {
+        self.verified = true;
+        self
+    }
+
+    pub fn admin(mut self) -> Self {
+        self.admin = true;
+        self
+    }
+
+    fn email_or_default(&self) -> String {
+        self.email.clone().unwrap_or_else(|| format!("{}@example.com", self.username))
+    }
+
+    fn profile(&self) -> UserProfile {
+        let mut profile = UserProfile::new(self.profile_name.clone().unwrap_or_else(|| self.username.clone()));
+        profile.pronouns = self.pronouns.clone();
+        profile.profile_bio = self.bio.clone();
+        profile.is_private = self.private;
+        profile.is_admin = self.admin;
+        profile.is_verified = self.verified;
+        profile
+    }
+
+    /// The user, without storing it. The password is left unhashed
+    /// unless a hash was given.
+    pub fn build(self) -> User {
+        let now = clock::now();
+        let email = self.email_or_default();
+        let profile = self.profile();
+        User {
+            id: UserId::new(),
+            username_sort_key: username_sort_key(&self.username),
+            username: self.username,
+            email,
+            hashed_password: self.hashed_password.unwrap_or_default(),
+            created_at: now,
+            updated_at: now,
+            bio: None,
+            created_via: self.created_via,
+            profile: Some(profile),
+            email_verified: self.email_verified,
+            playlists: None,
+            deleted_playlists: Vec::new(),
+            legal_hold: false,
+        }
+    }
+
+    /// Sign the user up and set up their profile, returning them as stored
+    pub async fn create(self, db: &Db) -> Result<User, Error> {
+        let users = UserOperations::new(db);
+        let hashed_password = match (&self.hashed_password, &self.password) {
+            (Some(hash), _) => hash.clone(),
+            (None, Some(password)) => hash_password(password)?,
+            (None, None) => default_hash()?,
+        };
+
+        let user = users.create_user(CreateUserInput {
+            username: self.username.clone(),
+            email: self.email_or_default(),
+            hashed_password,
+            created_via: self.created_via.clone(),
+            bio: None,
+        })
+        .await?;
+        let mut user = users.update_profile(user.id, self.profile()).await?;
+
+        if self.email_verified {
+            user = users.verify_email(user.id).await?;
+        }
+        if self.verified {
+            let request = NewVerificationRequest {
+                links: vec![format!("https://example.com/{}", user.username)],
+                evidence: None,
+            }
+            .into_request(user.id)?;
+            let request = VerificationOperations::new(db).create_request(request).await?;
+            // No admin reviewed it, so it's approved by nobody in particular
+            VerificationOperations::new(db).review(request.id, true, UserId::from(Uuid::nil()), None).await?;
+            user = users.get_user_by_id(user.id).await?;
+        }
+
+        Ok(user)
+    }
+}
+
+/// A track, imported rather than uploaded so no file is needed. Defaults
+/// to a public, uniquely titled track under the default license.
+#[derive(Debug, Clone)]
+pub struct TrackFixture {
+    owner: UserId,
+    title: String,
+    description: Option<String>,
+    audio_url: String,
+    genre: Option<String>,
+    tags: Option<Vec<String>>,
+    private: bool,
+    downloadable: bool,
+    license: License,
+    created_at: Option<DateTime<Utc>>,
+    language: Option<String>,
+    explicit: bool,
+}
+
+impl Default for TrackFixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TrackFixture {
+    pub fn new() -> Self {
+        let suffix = unique_suffix();
+        TrackFixture {
+            owner: UserId::new(),
+            title: format!("Track {}", suffix),
+            description: None,
+            audio_url: format!("https://media.example.com/{}.mp3", suffix),
+            genre: None,
+            tags: None,
+            private: false,
+            downloadable: false,
+            license: License::default(),
+            created_at: None,
+            language: None,
+            explicit: false,
+        }
+    }
+
+    /// The uploader; `create` needs one that's stored
+    pub fn owner(mut self, owner: UserId) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn audio_url(mut self, audio_url: impl Into<String>) -> Self {
+        self.audio_url = audio_url.into();
+        self
+    }
+
+    pub fn genre(mut self, genre: impl Into<String>) -> Self {
+        self.genre = Some(genre.into());
+        self
+    }
+
+    pub fn tags<T: Into<String>>(mut self, tags: impl IntoIterator<Item = T>) -> Self {
+        self.tags = Some(tags.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn private(mut self) -> Self {
+        self.private = true;
+        self
+    }
+
+    pub fn downloadable(mut self) -> Self {
+        self.downloadable = true;
+        self
+    }
+
+    pub fn license(mut self, license: License) -> Self {
+        self.license = license;
+        self
+    }
+
+    /// Released at `created_at` rather than now
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    pub fn explicit(mut self) -> Self {
+        self.explicit = true;
+        self
+    }
+
+    /// The track, without storing it
+    pub fn build(self) -> Track {
+        let now = clock::now();
+        Track {
+            id: TrackId::new(),
+            user_id: self.owner,
+            title: self.title,
+            description: self.description,
+            audio_url: self.audio_url,
+            cover_image_url: None,
+            genre: self.genre,
+            tags: self.tags,
+            created_at: self.created_at.unwrap_or(now),
+            updated_at: now,
+            is_public: !self.private,
+            is_deleted: false,
+            likes: 0,
+            dislikes: 0,
+            comments: None,
+            downloadable: self.downloadable,
+            download_count: 0,
+            technical_metadata: None,
+            comment_count: 0,
+            has_lyrics: false,
+            license: self.license,
+            license_history: Vec::new(),
+            audio_versions: Vec::new(),
+            attachments: Vec::new(),
+            credited_artists: Vec::new(),
+            language: self.language,
+            is_explicit: self.explicit,
+            slug: None,
+            slug_aliases: Vec::new(),
+        }
+    }
+
+    /// Import the track for its owner, returning it as stored
+    pub async fn create(self, db: &Db) -> Result<Track, Error> {
+        let owner = self.owner;
+        let entry = TrackManifestEntry {
+            title: self.title,
+            description: self.description,
+            audio_url: self.audio_url,
+            cover_image_url: None,
+            genre: self.genre,
+            tags: self.tags,
+            is_public: !self.private,
+            downloadable: self.downloadable,
+            license: self.license,
+            download_override: false,
+            created_at: self.created_at,
+            technical_metadata: None,
+            language: self.language,
+            is_explicit: self.explicit,
+        };
+
+        let result = TrackOperations::new(db).import_manifest(owner, vec![entry]).await?.remove(0);
+        let track_id = match (result.track_id, result.error) {
+            (Some(track_id), _) => track_id,
+            (None, error) => return Err(Error::Validation(error.unwrap_or_else(|| "track was not imported".to_string()))),
+        };
+
+        let owner = UserOperations::new(db).get_user_by_id(owner).await?;
+        owner.profile
+            .and_then(|profile| profile.uploads)
+            .and_then(|uploads| uploads.into_iter().find(|track| track.id == track_id))
+            .ok_or(Error::TrackNotFound)
+    }
+}
+
+/// A playlist. Defaults to a public, uniquely named, empty playlist.
+#[derive(Debug, Clone)]
+pub struct PlaylistFixture {
+    owner: UserId,
+    name: String,
+    description: Option<String>,
+    track_ids: Vec<TrackId>,
+    private: bool,
+}
+
+impl Default for PlaylistFixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PlaylistFixture {
+    pub fn new() -> Self {
+        PlaylistFixture {
+            owner: UserId::new(),
+            name: format!("Playlist {}", unique_suffix()),
+            description: None,
+            track_ids: Vec::new(),
+            private: false,
+        }
+    }
+
+    /// Whose playlist it is; `create` needs one that's stored
+    pub fn owner(mut self, owner: UserId) -> Self {
+        self.owner = owner;
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// These tracks, in this order
+    pub fn tracks(mut self, track_ids: &[TrackId]) -> Self {
+        self.track_ids = track_ids.to_vec();
+        self
+    }
+
+    pub fn private(mut self) -> Self {
+        self.private = true;
+        self
+    }
+
+    /// The playlist, without storing it. Its tracks aren't looked up, so
+    /// it's built empty whatever `tracks` was given.
+    pub fn build(self) -> Playlist {
+        let now = clock::now();
+        Playlist {
+            id: PlaylistId::new(),
+            user_id: self.owner,
+            name: self.name,
+            description: self.description,
+            tags: None,
+            cover_image_url: None,
+            is_public: !self.private,
+            is_deleted: false,
+            is_collaborative: false,
+            tracks: Vec::new(),
+            created_at: now,
+            updated_at: now,
+            revision: 0,
+            slug: None,
+            slug_aliases: Vec::new(),
+        }
+    }
+
+    /// Create the playlist for its owner, returning it as stored
+    pub async fn create(self, db: &Db) -> Result<Playlist, Error> {
+        let owner = self.owner;
+        let playlist = PlaylistOperations::new(db).create_playlist(owner, self.name, self.description, &self.track_ids, !self.private).await?;
+
+        let owner = UserOperations::new(db).get_user_by_id(owner).await?;
+        owner.playlists
+            .and_then(|playlists| playlists.into_iter().find(|p| p.id == playlist.id))
+            .ok_or(Error::PlaylistNotFound)
+    }
+}
+
+/// A comment on a track, or a reply to one. Defaults to a short remark.
+#[derive(Debug, Clone)]
+pub struct CommentFixture {
+    track_id: TrackId,
+    track_owner: UserId,
+    author: UserId,
+    content: String,
+    parent_comment_id: Option<CommentId>,
+}
+
+impl Default for CommentFixture {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CommentFixture {
+    pub fn new() -> Self {
+        CommentFixture {
+            track_id: TrackId::new(),
+            track_owner: UserId::new(),
+            author: UserId::new(),
+            content: "Love this one!".to_string(),
+            parent_comment_id: None,
+        }
+    }
+
+    /// The track it's left on; `create` needs one that's stored
+    pub fn on(mut self, track: &Track) -> Self {
+        self.track_id = track.id;
+        self.track_owner = track.user_id;
+        self
+    }
+
+    /// Who wrote it; `create` needs one that's stored
+    pub fn by(mut self, author: UserId) -> Self {
+        self.author = author;
+        self
+    }
+
+    pub fn content(mut self, content: impl Into<String>) -> Self {
+        self.content = content.into();
+        self
+    }
+
+    /// A reply to `parent_comment_id` on the same track
+    pub fn reply_to(mut self, parent_comment_id: CommentId) -> Self {
+        self.parent_comment_id = Some(parent_comment_id);
+        self
+    }
+
+    /// The comment, without storing it
+    pub fn build(self) -> Comment {
+        let now = clock::now();
+        Comment {
+            id: CommentId::new(),
+            referred_track_id: self.track_id,
+            user_id: self.author,
+            content: self.content,
+            created_at: now,
+            updated_at: now,
+            is_deleted: false,
+            replies: None,
+            likes: None,
+            dislikes: None,
+            is_pinned: false,
+            reports: None,
+            parent_comment_id: self.parent_comment_id,
+        }
+    }
+
+    /// Post the comment, returning it as stored
+    pub async fn create(self, db: &Db) -> Result<Comment, Error> {
+        let comment = TrackOperations::new(db).add_comment(self.track_id, self.author, self.content, self.parent_comment_id).await?;
+
+        let owner = UserOperations::new(db).get_user_by_id(self.track_owner).await?;
+        let track = owner.profile
+            .and_then(|profile| profile.uploads)
+            .and_then(|uploads| uploads.into_iter().find(|track| track.id == self.track_id))
+            .ok_or(Error::TrackNotFound)?;
+        find_comment(track.comments.as_ref(), comment.id).ok_or(Error::CommentNotFound)
+    }
+}
+
+/// An artist with an audience: one user, their tracks, and followers.
+/// `ArtistFixture::new().public_tracks(3).followers(10)` is an artist with
+/// three public tracks and ten followers.
+#[derive(Debug, Clone, Default)]
+pub struct ArtistFixture {
+    artist: UserFixture,
+    public_tracks: usize,
+    private_tracks: usize,
+    followers: usize,
+}
+
+/// What `ArtistFixture::create` made
+#[derive(Debug, Clone)]
+pub struct Artist {
+    pub user: User, // as stored after the follows
+    pub tracks: Vec<Track>, // public ones first
+    pub followers: Vec<User>,
+}
+
+impl ArtistFixture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The artist's account, instead of a default user
+    pub fn user(mut self, artist: UserFixture) -> Self {
+        self.artist = artist;
+        self
+    }
+
+    pub fn public_tracks(mut self, count: usize) -> Self {
+        self.public_tracks = count;
+        self
+    }
+
+    pub fn private_tracks(mut self, count: usize) -> Self {
+        self.private_tracks = count;
+        self
+    }
+
+    pub fn followers(mut self, count: usize) -> Self {
+        self.followers = count;
+        self
+    }
+
+    pub async fn create(self, db: &Db) -> Result<Artist, Error> {
+        let user = self.artist.create(db).await?;
+
+        let mut tracks = Vec::with_capacity(self.public_tracks + self.private_tracks);
+        for n in 0..self.public_tracks + self.private_tracks {
+            let track = TrackFixture::new().owner(user.id);
+            let track = if n < self.public_tracks { track } else { track.private() };
+            tracks.push(track.create(db).await?);
+        }
+
+        let users = UserOperations::new(db);
+        let mut followers = Vec::with_capacity(self.followers);
+        for _ in 0..self.followers {
+            let follower = UserFixture::new().create(db).await?;
+            users.follow_user(follower.id, user.id).await?;
+            followers.push(follower);
+        }
+        let user = users.get_user_by_id(user.id).await?;
+
+        Ok(Artist { user, tracks, followers })
+    }
+}
@@ -0,0 +1,140 @@
+//! Feature flags for shipping risky features dark.
+//!
+//! Flags live in the `feature_flags` table and are copied into memory every
+//! `FLAG_REFRESH_SECS` (default 30), so checking one never waits on the
+//! database. A flag can be on for everyone or for a percentage of signed-in
+//! users, bucketed by a hash of the flag name and user id so each user keeps
+//! their answer as the percentage grows. `FLAG_<NAME>=on|off` in the
+//! environment beats whatever the table says, for emergencies.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{LazyLock, RwLock};
+use std::time::Duration;
+use sha2::{Digest, Sha256};
+use tracing::error;
+use crate::db::error::Error;
+use crate::db::feature_flag::FeatureFlagOperations;
+use crate::db::Db;
+use crate::types::feature_flag::FeatureFlag;
+use crate::types::id::UserId;
+
+/// Flags the code checks, with whether each is on before anyone sets it
+pub const KNOWN: &[(&str, bool)] = &[
+    ("graphql", true),
+];
+
+static FLAGS: LazyLock<RwLock<HashMap<String, FeatureFlag>>> = LazyLock::new(Default::default);
+
+/// Environment overrides, read once at startup
+static OVERRIDES: LazyLock<HashMap<String, bool>> = LazyLock::new(|| {
+    env::vars()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix("FLAG_")?.to_lowercase();
+            match value.to_lowercase().as_str() {
+                "on" | "true" | "1" => Some((name, true)),
+                "off" | "false" | "0" => Some((name, false)),
+                _ => None,
+            }
+        })
+        .collect()
+});
+
+/// Which of 100 buckets a user falls in for a flag
+pub fn bucket(name: &str, user_id: UserId) -> u8 {
+    let digest = Sha256::new()
+        .chain_update(name.as_bytes())
+        .chain_update(b":")
+        .chain_update(user_id.as_uuid().as_bytes())
+        .finalize();
+    (u16::from_be_bytes([digest[0], digest[1]]) % 100) as u8
+}
+
+/// Whether a flag is on, for `user` if signed in
+pub fn is_enabled(name: &str, user: Option<UserId>) -> bool {
+    if let Some(forced) = OVERRIDES.get(name) {
+        return *forced;
+    }
+
+    let flags = FLAGS.read().unwrap();
+    let Some(flag) = flags.get(name) else {
+        return KNOWN.iter().any(|(known, default)| *known == name && *default);
+    };
+
+    match (flag.enabled, flag.rollout_percentage, user) {
+        (false, _, _) => false,
+        (true, None, _) => true,
+        (true, Some(percentage), Some(user_id)) => bucket(name, user_id) < percentage,
+        (true, Some(percentage), None) => percentage >= 100,
+    }
+}
+
+/// Fail with 404 while a flag is off, so the route looks like it isn't there
+pub fn require(name: &str, user: Option<UserId>) -> Result<(), Error> {
+    if is_enabled(name, user) {
+        Ok(())
+    } else {
+        Err(Error::NotFound)
+    }
+}
+
+/// The value an environment override forces a flag to, if any
+pub fn override_for(name: &str) -> Option<bool> {
+    OVERRIDES.get(name).copied()
+}
+
+/// Reload every flag from the database
+pub async fn refresh(db: &Db) -> Result<(), Error> {
+    let flags = FeatureFlagOperations::new(db).list_flags().await?;
+    *FLAGS.write().unwrap() = flags.into_iter().map(|flag| (flag.name.clone(), flag)).collect();
+    Ok(())
+}
+
+/// How often to reload flags, set in seconds with `FLAG_REFRESH_SECS`
+pub fn interval() -> Duration {
+    let secs = env::var("FLAG_REFRESH_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(30);
+    Duration::from_secs(secs)
+}
+
+/// Reload flags now and every `interval()` after
+pub fn spawn_job(db: Db) {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(interval());
+        loop {
+            ticker.tick().await;
+            if let Err(e) = refresh(&db).await {
+                error!("Failed to refresh feature flags: {}", e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+    use super::bucket;
+    use crate::types::id::UserId;
+
+    fn users() -> impl Iterator<Item = UserId> {
+        (0..2000u128).map(|n| UserId::from(Uuid::from_u128(n)))
+    }
+
+    #[test]
+    fn buckets_are_stable_and_spread_evenly() {
+        let user = UserId::from(Uuid::from_u128(0x5eed));
+        // Pinned, so a change to the hash that would reshuffle every
+        // rollout can't go unnoticed
+        assert_eq!(bucket("graphql", user), 90);
+        assert_eq!(bucket("graphql", user), bucket("graphql", user));
+
+        let in_rollout = users().filter(|user| bucket("hls", *user) < 25).count();
+        assert!((400..600).contains(&in_rollout), "{} of 2000 users in a 25% rollout", in_rollout);
+
+        // Each flag draws its own sample, so the same users aren't always first
+        let in_both = users().filter(|user| bucket("hls", *user) < 25 && bucket("federation", *user) < 25).count();
+        assert!(in_both < in_rollout / 2, "{} of {} users share both rollouts", in_both, in_rollout);
+    }
+}
@@ -0,0 +1,157 @@
+//! Garbage collection of media nothing points at any more.
+//!
+//! Replaced images, deleted tracks and abandoned uploads leave objects in
+//! storage. A run lists every object, drops those any URL in the database
+//! refers to, and deletes the rest, up to `GC_MAX_DELETIONS` per run. Objects
+//! written in the last `GC_MIN_AGE_HOURS` (default 24) are never touched, so
+//! an upload whose URL hasn't been saved yet can't be collected out from
+//! under it. Each run's report is kept in `gc_runs`. The job runs every
+//! `GC_INTERVAL_SECS` (default daily) and on demand from `POST /admin/gc/run`.
+
+use std::collections::HashSet;
+use std::env;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use tracing::{error, info, warn};
+use uuid::Uuid;
+use crate::clock;
+use crate::db::error::Error;
+use crate::db::gc::GcOperations;
+use crate::db::Db;
+use crate::maintenance;
+use crate::storage::{storage, Storage, StoredObject};
+use crate::types::gc::GcRun;
+
+#[derive(Debug, Clone, Copy)]
+pub struct GcOptions {
+    /// Report what would be deleted without deleting it
+    pub dry_run: bool,
+    /// Most objects deleted in one run
+    pub max_deletions: usize,
+    /// Objects younger than this are kept whether referenced or not
+    pub min_age: chrono::Duration,
+}
+
+impl GcOptions {
+    /// Options from `GC_MAX_DELETIONS` (default 1000) and `GC_MIN_AGE_HOURS`
+    /// (default 24)
+    pub fn from_env(dry_run: bool) -> Self {
+        let max_deletions = env::var("GC_MAX_DELETIONS")
+            .ok()
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(1000);
+        let min_age_hours = env::var("GC_MIN_AGE_HOURS")
+            .ok()
+            .and_then(|hours| hours.parse().ok())
+            .unwrap_or(24);
+
+        Self { dry_run, max_deletions, min_age: chrono::Duration::hours(min_age_hours) }
+    }
+}
+
+/// Which objects may go, split into those to delete and how many were
+/// unreferenced but too recent. At most `max_deletions` are returned, oldest
+/// first; the flag says whether any were left over.
+pub fn collectable(
+    objects: Vec<StoredObject>,
+    referenced: &HashSet<String>,
+    now: DateTime<Utc>,
+    options: &GcOptions,
+) -> (Vec<StoredObject>, usize, bool) {
+    let cutoff = now - options.min_age;
+    let (mut old, recent): (Vec<_>, Vec<_>) = objects
+        .into_iter()
+        .filter(|object| !referenced.contains(&object.key))
+        .partition(|object| object.modified < cutoff);
+
+    old.sort_by_key(|object| object.modified);
+    let capped = old.len() > options.max_deletions;
+    old.truncate(options.max_deletions);
+
+    (old, recent.len(), capped)
+}
+
+/// Run one pass against `backend` and record its report
+pub async fn run_with(db: &Db, backend: &dyn Storage, options: GcOptions) -> Result<GcRun, Error> {
+    let started_at = clock::now();
+
+    // An upload stored before the listing but saved after the references are
+    // read looks unreferenced here; only the safety window keeps it alive
+    let objects = backend.list().await?;
+    let referenced: HashSet<String> = GcOperations::new(db).referenced_urls()
+        .await?
+        .iter()
+        .filter_map(|url| backend.key_for_url(url))
+        .collect();
+
+    let scanned = objects.len();
+    let referenced_count = objects.iter().filter(|object| referenced.contains(&object.key)).count();
+    let (doomed, too_recent, capped) = collectable(objects, &referenced, started_at, &options);
+
+    let mut deleted = Vec::with_capacity(doomed.len());
+    let mut failed = Vec::new();
+    for object in doomed {
+        if options.dry_run {
+            deleted.push(object.key);
+            continue;
+        }
+        match backend.delete(&object.key).await {
+            Ok(()) => deleted.push(object.key),
+            Err(e) => {
+                warn!("Failed to delete unreferenced media {}: {}", object.key, e);
+                failed.push(object.key);
+            }
+        }
+    }
+
+    let run = GcRun {
+        id: Uuid::new_v4(),
+        dry_run: options.dry_run,
+        scanned,
+        referenced: referenced_count,
+        too_recent,
+        deleted,
+        failed,
+        capped,
+        started_at,
+        finished_at: clock::now(),
+    };
+    info!(
+        "Media gc{}: {} scanned, {} referenced, {} too recent, {} deleted, {} failed",
+        if run.dry_run { " (dry run)" } else { "" },
+        run.scanned, run.referenced, run.too_recent, run.deleted.len(), run.failed.len()
+    );
+
+    GcOperations::new(db).record_run(&run).await
+}
+
+/// Run one pass against the configured storage backend
+pub async fn run(db: &Db, dry_run: bool) -> Result<GcRun, Error> {
+    run_with(db, storage(), GcOptions::from_env(dry_run)).await
+}
+
+/// How often to collect, set in seconds with `GC_INTERVAL_SECS`
+pub fn interval() -> Duration {
+    let secs = env::var("GC_INTERVAL_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(24 * 60 * 60);
+    Duration::from_secs(secs)
+}
+
+/// Collect every `interval()`, starting one interval from now
+pub fn spawn_job(db: Db) {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(interval());
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if maintenance::is_active() {
+                continue;
+            }
+            if let Err(e) = run(&db, false).await {
+                error!("Failed to collect unreferenced media: {}", e);
+            }
+        }
+    });
+}
@@ -0,0 +1,131 @@
+//! Opt-in enrichment that turns a free text location into a structured one.
+//!
+//! This resolves against the bundled country list and a small table of well
+//! known cities and nicknames, so it never calls out to a third party. Enable
+//! it with `GEOCODING=true`.
+
+use std::env;
+use crate::types::location::{country_code, country_name, Location};
+
+/// Common ways of writing major cities, lowercase, with their structured form
+const CITIES: &[(&str, &str, Option<&str>, &str)] = &[
+    ("nyc", "US", Some("New York"), "New York City"),
+    ("new york", "US", Some("New York"), "New York City"),
+    ("new york city", "US", Some("New York"), "New York City"),
+    ("la", "US", Some("California"), "Los Angeles"),
+    ("los angeles", "US", Some("California"), "Los Angeles"),
+    ("sf", "US", Some("California"), "San Francisco"),
+    ("san francisco", "US", Some("California"), "San Francisco"),
+    ("chicago", "US", Some("Illinois"), "Chicago"),
+    ("atlanta", "US", Some("Georgia"), "Atlanta"),
+    ("nashville", "US", Some("Tennessee"), "Nashville"),
+    ("detroit", "US", Some("Michigan"), "Detroit"),
+    ("toronto", "CA", Some("Ontario"), "Toronto"),
+    ("montreal", "CA", Some("Quebec"), "Montreal"),
+    ("vancouver", "CA", Some("British Columbia"), "Vancouver"),
+    ("mexico city", "MX", None, "Mexico City"),
+    ("cdmx", "MX", None, "Mexico City"),
+    ("sao paulo", "BR", Some("São Paulo"), "São Paulo"),
+    ("rio", "BR", Some("Rio de Janeiro"), "Rio de Janeiro"),
+    ("rio de janeiro", "BR", Some("Rio de Janeiro"), "Rio de Janeiro"),
+    ("buenos aires", "AR", None, "Buenos Aires"),
+    ("london", "GB", Some("England"), "London"),
+    ("manchester", "GB", Some("England"), "Manchester"),
+    ("glasgow", "GB", Some("Scotland"), "Glasgow"),
+    ("dublin", "IE", None, "Dublin"),
+    ("paris", "FR", Some("Île-de-France"), "Paris"),
+    ("berlin", "DE", Some("Berlin"), "Berlin"),
+    ("hamburg", "DE", Some("Hamburg"), "Hamburg"),
+    ("amsterdam", "NL", Some("North Holland"), "Amsterdam"),
+    ("brussels", "BE", None, "Brussels"),
+    ("madrid", "ES", None, "Madrid"),
+    ("barcelona", "ES", Some("Catalonia"), "Barcelona"),
+    ("lisbon", "PT", None, "Lisbon"),
+    ("rome", "IT", Some("Lazio"), "Rome"),
+    ("milan", "IT", Some("Lombardy"), "Milan"),
+    ("stockholm", "SE", None, "Stockholm"),
+    ("oslo", "NO", None, "Oslo"),
+    ("copenhagen", "DK", None, "Copenhagen"),
+    ("helsinki", "FI", None, "Helsinki"),
+    ("warsaw", "PL", None, "Warsaw"),
+    ("prague", "CZ", None, "Prague"),
+    ("vienna", "AT", None, "Vienna"),
+    ("istanbul", "TR", None, "Istanbul"),
+    ("ankara", "TR", None, "Ankara"),
+    ("moscow", "RU", None, "Moscow"),
+    ("kyiv", "UA", None, "Kyiv"),
+    ("cairo", "EG", None, "Cairo"),
+    ("lagos", "NG", Some("Lagos"), "Lagos"),
+    ("nairobi", "KE", None, "Nairobi"),
+    ("johannesburg", "ZA", Some("Gauteng"), "Johannesburg"),
+    ("cape town", "ZA", Some("Western Cape"), "Cape Town"),
+    ("mumbai", "IN", Some("Maharashtra"), "Mumbai"),
+    ("delhi", "IN", Some("Delhi"), "Delhi"),
+    ("tokyo", "JP", Some("Tokyo"), "Tokyo"),
+    ("osaka", "JP", Some("Osaka"), "Osaka"),
+    ("seoul", "KR", None, "Seoul"),
+    ("beijing", "CN", None, "Beijing"),
+    ("shanghai", "CN", None, "Shanghai"),
+    ("hong kong", "HK", None, "Hong Kong"),
+    ("singapore", "SG", None, "Singapore"),
+    ("sydney", "AU", Some("New South Wales"), "Sydney"),
+    ("melbourne", "AU", Some("Victoria"), "Melbourne"),
+    ("auckland", "NZ", None, "Auckland"),
+];
+
+/// Whether free text locations should be enriched
+pub fn enabled() -> bool {
+    env::var("GEOCODING").is_ok_and(|v| matches!(v.to_lowercase().as_str(), "true" | "1" | "on"))
+}
+
+/// Best effort resolution of free text such as "NYC", "Berlin, DE" or
+/// "Portland, Oregon, United States"
+pub fn geocode(text: &str) -> Option<Location> {
+    let normalized = text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    
+    if let Some(location) = lookup_city(&normalized) {
+        return Some(location);
+    }
+    
+    let parts: Vec<&str> = normalized.split(',').map(str::trim).filter(|p| !p.is_empty()).collect();
+    let (last, rest) = parts.split_last()?;
+    let country = resolve_country(last)?;
+    
+    // A known city with a country that agrees, e.g. "London, UK"
+    if let [city] = rest {
+        if let Some(location) = lookup_city(city).filter(|l| l.country == country) {
+            return Some(location);
+        }
+    }
+    
+    // Keep the original casing for the parts we can't check
+    let original: Vec<&str> = text.split(',').map(str::trim).filter(|p| !p.is_empty()).collect();
+    let (city, region) = match original.len() {
+        3.. => (Some(original[0].to_string()), Some(original[1].to_string())),
+        2 => (Some(original[0].to_string()), None),
+        _ => (None, None),
+    };
+    
+    Location::new(country, region, city).ok()
+}
+
+fn lookup_city(text: &str) -> Option<Location> {
+    CITIES.iter()
+        .find(|(alias, ..)| *alias == text)
+        .and_then(|(_, country, region, city)| {
+            Location::new(country, region.map(str::to_string), Some(city.to_string())).ok()
+        })
+}
+
+fn resolve_country(text: &str) -> Option<&'static str> {
+    let text = match text {
+        "usa" | "us" | "united states of america" => "US",
+        "uk" | "england" | "scotland" | "wales" | "great britain" => "GB",
+        text => text,
+    };
+    
+    if text.len() == 2 && country_name(text).is_some() {
+        return country_code(country_name(text)?);
+    }
+    country_code(text)
+}
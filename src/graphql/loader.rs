@@ -0,0 +1,32 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use async_graphql::dataloader::Loader;
+use crate::db::error::Error;
+use crate::db::Db;
+use crate::hydrate;
+use crate::types::id::{TrackId, UserId};
+use crate::types::user::{Track, User};
+
+/// Batches user lookups by id
+pub struct UserLoader(pub Db);
+
+impl Loader<UserId> for UserLoader {
+    type Value = User;
+    type Error = Arc<Error>;
+
+    async fn load(&self, keys: &[UserId]) -> Result<HashMap<UserId, User>, Self::Error> {
+        hydrate::load_users(&self.0, keys.iter().copied()).await.map_err(Arc::new)
+    }
+}
+
+/// Batches track lookups by id
+pub struct TrackLoader(pub Db);
+
+impl Loader<TrackId> for TrackLoader {
+    type Value = Track;
+    type Error = Arc<Error>;
+
+    async fn load(&self, keys: &[TrackId]) -> Result<HashMap<TrackId, Track>, Self::Error> {
+        hydrate::load_tracks(&self.0, keys.iter().copied()).await.map_err(Arc::new)
+    }
+}
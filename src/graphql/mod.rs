@@ -0,0 +1,78 @@
+//! Read-only GraphQL API, mounted at `POST /api/graphql`.
+//!
+//! Resolvers reuse the `db` operations and the same visibility rules as the
+//! REST handlers, so anything REST answers with 404 resolves to `null` here.
+//! Users and tracks referenced from other objects are loaded through
+//! per-request dataloaders so a list of comments costs one user lookup, not
+//! one per comment.
+
+use actix_web::{post, web};
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{EmptyMutation, EmptySubscription, Schema};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use crate::auth::AuthUser;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::Db;
+use crate::flags;
+use crate::types::id::UserId;
+
+pub mod loader;
+pub mod objects;
+
+use loader::{TrackLoader, UserLoader};
+use objects::Query;
+
+pub type LibretuneSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// Deepest selection set a query may nest
+pub const MAX_DEPTH: usize = 10;
+/// Highest total complexity a query may have, counting one per field
+pub const MAX_COMPLEXITY: usize = 500;
+
+/// The authenticated user a query runs for, if any
+#[derive(Clone, Copy)]
+pub struct Viewer(pub Option<UserId>);
+
+pub fn schema() -> LibretuneSchema {
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+        .limit_depth(MAX_DEPTH)
+        .limit_complexity(MAX_COMPLEXITY)
+        .finish()
+}
+
+/// Register the GraphQL endpoint
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::Data::new(schema()))
+        .service(graphql);
+}
+
+/// Run a read-only GraphQL query. Authentication is optional and uses the
+/// same bearer token as the REST API.
+#[utoipa::path(
+    tag = "graphql",
+    request_body(content = Object, content_type = "application/json"),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, description = "GraphQL response, errors included", body = Object),
+        (status = 404, description = "The graphql feature flag is off", body = ErrorBody),
+    )
+)]
+#[post("/api/graphql")]
+pub async fn graphql(
+    schema: web::Data<LibretuneSchema>,
+    auth: Option<AuthUser>,
+    request: GraphQLRequest,
+    db: web::Data<Db>,
+) -> Result<GraphQLResponse, Error> {
+    let viewer = auth.map(|auth| auth.user.id);
+    flags::require("graphql", viewer)?;
+
+    let request = request
+        .into_inner()
+        .data(Viewer(viewer))
+        .data(Db::clone(&db))
+        .data(DataLoader::new(UserLoader(Db::clone(&db)), actix_web::rt::spawn))
+        .data(DataLoader::new(TrackLoader(Db::clone(&db)), actix_web::rt::spawn));
+
+    Ok(schema.execute(request).await.into())
+}
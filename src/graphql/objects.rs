@@ -0,0 +1,371 @@
+use async_graphql::dataloader::DataLoader;
+use async_graphql::{Context, Object, Result};
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+use crate::db::error::Error;
+use crate::db::playlist::PlaylistOperations;
+use crate::db::{Db, UserOperations};
+use crate::types::id::UserId;
+use crate::types::user::{Comment, Playlist, Track, User, UserProfile};
+use super::loader::{TrackLoader, UserLoader};
+use super::Viewer;
+
+fn viewer(ctx: &Context<'_>) -> Option<UserId> {
+    ctx.data_unchecked::<Viewer>().0
+}
+
+/// Turn the not-found errors REST answers with 404 into `null`
+fn found<T>(result: Result<T, Error>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(Error::UserNotFound | Error::ProfileNotFound | Error::TrackNotFound | Error::PlaylistNotFound) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+async fn load_user(ctx: &Context<'_>, user_id: UserId) -> Result<Option<UserObject>> {
+    let user = ctx.data_unchecked::<DataLoader<UserLoader>>().load_one(user_id).await?;
+
+    Ok(user.filter(|user| user.profile_visible_to(viewer(ctx))).map(UserObject))
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    /// Look a user up by id or username
+    async fn user(&self, ctx: &Context<'_>, id: Option<Uuid>, username: Option<String>) -> Result<Option<UserObject>> {
+        match (id, username) {
+            (Some(id), _) => load_user(ctx, id.into()).await,
+            (None, Some(username)) => {
+                let user = found(UserOperations::new(ctx.data_unchecked::<Db>()).get_user_by_username(username).await)?;
+                Ok(user.filter(|user| user.profile_visible_to(viewer(ctx))).map(UserObject))
+            }
+            (None, None) => Err("either id or username is required".into()),
+        }
+    }
+
+    async fn track(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<TrackObject>> {
+        let track = ctx.data_unchecked::<DataLoader<TrackLoader>>().load_one(id.into()).await?;
+
+        Ok(track.filter(|track| track.is_visible_to(viewer(ctx))).map(TrackObject))
+    }
+
+    async fn playlist(&self, ctx: &Context<'_>, id: Uuid) -> Result<Option<PlaylistObject>> {
+        let playlist = found(PlaylistOperations::new(ctx.data_unchecked::<Db>()).get_playlist(id.into()).await)?;
+
+        Ok(playlist
+            .map(|(_, playlist)| playlist)
+            .filter(|playlist| playlist.is_visible_to(viewer(ctx)))
+            .map(PlaylistObject))
+    }
+
+    /// Top-level comments on a track, `null` if the track can't be seen
+    async fn comments(&self, ctx: &Context<'_>, track_id: Uuid) -> Result<Option<Vec<CommentObject>>> {
+        let track = self.track(ctx, track_id).await?;
+
+        Ok(track.map(|track| visible_comments(track.0.comments.as_ref())))
+    }
+}
+
+fn visible_comments(comments: Option<&Vec<Comment>>) -> Vec<CommentObject> {
+    comments
+        .into_iter()
+        .flatten()
+        .filter(|comment| !comment.is_deleted)
+        .cloned()
+        .map(CommentObject)
+        .collect()
+}
+
+pub struct UserObject(pub User);
+
+impl UserObject {
+    fn profile(&self) -> Option<&UserProfile> {
+        self.0.profile.as_ref()
+    }
+
+    fn visible_tracks(&self, ctx: &Context<'_>) -> Vec<Track> {
+        let viewer = viewer(ctx);
+        self.profile()
+            .and_then(|p| p.uploads.as_ref())
+            .into_iter()
+            .flatten()
+            .filter(|track| track.is_visible_to(viewer))
+            .cloned()
+            .collect()
+    }
+}
+
+#[Object(name = "User")]
+impl UserObject {
+    async fn id(&self) -> Uuid {
+        self.0.id.into()
+    }
+
+    async fn username(&self) -> &str {
+        &self.0.username
+    }
+
+    async fn profile_name(&self) -> Option<&str> {
+        self.profile().map(|p| p.profile_name.as_str())
+    }
+
+    async fn profile_picture(&self) -> Option<&str> {
+        self.profile().and_then(|p| p.profile_picture.as_deref())
+    }
+
+    async fn is_verified(&self) -> bool {
+        self.profile().is_some_and(|p| p.is_verified)
+    }
+
+    async fn profile_banner(&self) -> Option<&str> {
+        self.profile().and_then(|p| p.profile_banner.as_deref())
+    }
+
+    async fn profile_bio(&self) -> Option<&str> {
+        self.profile().and_then(|p| p.profile_bio.as_deref())
+    }
+
+    async fn pronouns(&self) -> Option<&str> {
+        self.profile().and_then(|p| p.pronouns.as_deref())
+    }
+
+    async fn location(&self) -> Option<&str> {
+        self.profile().and_then(|p| p.location.as_deref())
+    }
+
+    async fn social_links(&self) -> Option<&Vec<String>> {
+        self.profile().and_then(|p| p.social_links.as_ref())
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn follower_count(&self) -> usize {
+        self.profile().and_then(|p| p.followers.as_ref()).map_or(0, Vec::len)
+    }
+
+    async fn following_count(&self) -> usize {
+        self.profile().and_then(|p| p.following.as_ref()).map_or(0, Vec::len)
+    }
+
+    /// Tracks the viewer may see, public ones plus private ones for the owner
+    async fn track_count(&self, ctx: &Context<'_>) -> usize {
+        self.visible_tracks(ctx).len()
+    }
+
+    async fn tracks(&self, ctx: &Context<'_>) -> Vec<TrackObject> {
+        self.visible_tracks(ctx).into_iter().map(TrackObject).collect()
+    }
+
+    async fn playlists(&self, ctx: &Context<'_>) -> Vec<PlaylistObject> {
+        let viewer = viewer(ctx);
+        self.0.playlists
+            .iter()
+            .flatten()
+            .filter(|playlist| playlist.is_visible_to(viewer))
+            .cloned()
+            .map(PlaylistObject)
+            .collect()
+    }
+}
+
+pub struct TrackObject(pub Track);
+
+#[Object(name = "Track")]
+impl TrackObject {
+    async fn id(&self) -> Uuid {
+        self.0.id.into()
+    }
+
+    async fn title(&self) -> &str {
+        &self.0.title
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    /// The original file, only for the owner and on downloadable tracks
+    async fn audio_url(&self, ctx: &Context<'_>) -> Option<&str> {
+        self.0.is_downloadable_by(viewer(ctx)).then_some(self.0.audio_url.as_str())
+    }
+
+    async fn stream_url(&self) -> String {
+        self.0.stream_url()
+    }
+
+    async fn cover_image_url(&self) -> Option<&str> {
+        self.0.cover_image_url.as_deref()
+    }
+
+    async fn genre(&self) -> Option<&str> {
+        self.0.genre.as_deref()
+    }
+
+    async fn tags(&self) -> Option<&Vec<String>> {
+        self.0.tags.as_ref()
+    }
+
+    async fn is_public(&self) -> bool {
+        self.0.is_public
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn likes(&self) -> u32 {
+        self.0.likes
+    }
+
+    async fn dislikes(&self) -> u32 {
+        self.0.dislikes
+    }
+
+    async fn downloadable(&self) -> bool {
+        self.0.downloadable
+    }
+
+    async fn license(&self) -> &str {
+        self.0.license.as_str()
+    }
+
+    async fn license_url(&self) -> Option<&str> {
+        self.0.license.url()
+    }
+
+    async fn download_count(&self) -> u64 {
+        self.0.download_count
+    }
+
+    async fn comment_count(&self) -> u64 {
+        self.0.comment_count
+    }
+
+    async fn has_lyrics(&self) -> bool {
+        self.0.has_lyrics
+    }
+
+    async fn slug(&self) -> Option<&str> {
+        self.0.slug.as_deref()
+    }
+
+    /// ISO 639-1 code
+    async fn language(&self) -> Option<&str> {
+        self.0.language.as_deref()
+    }
+
+    async fn is_explicit(&self) -> bool {
+        self.0.is_explicit
+    }
+
+    /// The uploader, `null` if their profile is hidden from the viewer
+    async fn owner(&self, ctx: &Context<'_>) -> Result<Option<UserObject>> {
+        load_user(ctx, self.0.user_id).await
+    }
+
+    async fn comments(&self) -> Vec<CommentObject> {
+        visible_comments(self.0.comments.as_ref())
+    }
+}
+
+pub struct PlaylistObject(pub Playlist);
+
+#[Object(name = "Playlist")]
+impl PlaylistObject {
+    async fn id(&self) -> Uuid {
+        self.0.id.into()
+    }
+
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    async fn description(&self) -> Option<&str> {
+        self.0.description.as_deref()
+    }
+
+    async fn tags(&self) -> Option<&Vec<String>> {
+        self.0.tags.as_ref()
+    }
+
+    async fn cover_image_url(&self) -> Option<&str> {
+        self.0.cover_image_url.as_deref()
+    }
+
+    async fn is_public(&self) -> bool {
+        self.0.is_public
+    }
+
+    async fn is_collaborative(&self) -> bool {
+        self.0.is_collaborative
+    }
+
+    async fn revision(&self) -> u64 {
+        self.0.revision
+    }
+
+    async fn slug(&self) -> Option<&str> {
+        self.0.slug.as_deref()
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn owner(&self, ctx: &Context<'_>) -> Result<Option<UserObject>> {
+        load_user(ctx, self.0.user_id).await
+    }
+
+    /// Tracks in the playlist the viewer may see
+    async fn tracks(&self, ctx: &Context<'_>) -> Vec<TrackObject> {
+        let viewer = viewer(ctx);
+        self.0.tracks
+            .iter()
+            .filter(|track| track.is_visible_to(viewer))
+            .cloned()
+            .map(TrackObject)
+            .collect()
+    }
+}
+
+pub struct CommentObject(pub Comment);
+
+#[Object(name = "Comment")]
+impl CommentObject {
+    async fn id(&self) -> Uuid {
+        self.0.id.into()
+    }
+
+    async fn content(&self) -> &str {
+        &self.0.content
+    }
+
+    async fn created_at(&self) -> DateTime<Utc> {
+        self.0.created_at
+    }
+
+    async fn is_pinned(&self) -> bool {
+        self.0.is_pinned
+    }
+
+    async fn like_count(&self) -> usize {
+        self.0.likes.as_ref().map_or(0, Vec::len)
+    }
+
+    async fn dislike_count(&self) -> usize {
+        self.0.dislikes.as_ref().map_or(0, Vec::len)
+    }
+
+    /// The commenter, `null` if their profile is hidden from the viewer
+    async fn author(&self, ctx: &Context<'_>) -> Result<Option<UserObject>> {
+        load_user(ctx, self.0.user_id).await
+    }
+
+    async fn replies(&self) -> Vec<CommentObject> {
+        visible_comments(self.0.replies.as_ref())
+    }
+}
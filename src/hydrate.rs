@@ -0,0 +1,142 @@
+//! Batch loading of the users and tracks that listings refer to.
+//!
+//! Listings collect the ids they need and load them with one `IN` query per
+//! kind instead of one query per item. A `Hydrator` extracted in a handler is
+//! shared through the request extensions, so every layer of one request
+//! reuses what was already loaded.
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::future::{ready, Ready};
+use std::hash::Hash;
+use std::rc::Rc;
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpMessage, HttpRequest};
+use crate::db::error::Error;
+use crate::db::track::TrackOperations;
+use crate::db::{Db, UserOperations};
+use crate::types::id::{TrackId, UserId};
+use crate::types::user::{Track, TrackView, User};
+
+fn dedupe<I: Copy + Eq + Hash>(ids: impl IntoIterator<Item = I>) -> Vec<I> {
+    let mut seen = HashSet::new();
+    ids.into_iter().filter(|id| seen.insert(*id)).collect()
+}
+
+/// Load users by id with at most one query, serving what it can from the
+/// user cache. Missing ids are left out of the map.
+pub async fn load_users(db: &Db, ids: impl IntoIterator<Item = UserId>) -> Result<HashMap<UserId, User>, Error> {
+    let mut users = HashMap::new();
+    let mut missing = Vec::new();
+
+    for id in dedupe(ids) {
+        match db.user_cache().get(id) {
+            Some(cached) => {
+                users.insert(id, cached.user.clone());
+            }
+            None => missing.push(id),
+        }
+    }
+
+    for user in UserOperations::new(db).get_users_by_ids(&missing).await? {
+        users.insert(user.id, db.user_cache().insert(user).user.clone());
+    }
+
+    Ok(users)
+}
+
+/// Load tracks by id with at most one query. Missing ids are left out of the map.
+pub async fn load_tracks(db: &Db, ids: impl IntoIterator<Item = TrackId>) -> Result<HashMap<TrackId, Track>, Error> {
+    let tracks = TrackOperations::new(db).get_tracks(&dedupe(ids)).await?;
+
+    Ok(tracks.into_iter().map(|track| (track.id, track)).collect())
+}
+
+/// Per-request memo over `load_users` and `load_tracks`
+#[derive(Clone)]
+pub struct Hydrator {
+    inner: Rc<HydratorState>,
+}
+
+struct HydratorState {
+    db: Db,
+    users: RefCell<HashMap<UserId, User>>,
+    tracks: RefCell<HashMap<TrackId, Track>>,
+}
+
+impl Hydrator {
+    pub fn new(db: Db) -> Self {
+        let state = HydratorState { db, users: RefCell::default(), tracks: RefCell::default() };
+        Hydrator { inner: Rc::new(state) }
+    }
+
+    /// The users behind `ids`, querying only for those not loaded yet in
+    /// this request
+    pub async fn users(&self, ids: impl IntoIterator<Item = UserId>) -> Result<HashMap<UserId, User>, Error> {
+        let ids = dedupe(ids);
+        let missing: Vec<UserId> = {
+            let users = self.inner.users.borrow();
+            ids.iter().copied().filter(|id| !users.contains_key(id)).collect()
+        };
+
+        if !missing.is_empty() {
+            let loaded = load_users(&self.inner.db, missing).await?;
+            self.inner.users.borrow_mut().extend(loaded);
+        }
+
+        let users = self.inner.users.borrow();
+        Ok(ids.iter().filter_map(|id| users.get(id).map(|user| (*id, user.clone()))).collect())
+    }
+
+    /// The tracks behind `ids`, querying only for those not loaded yet in
+    /// this request
+    pub async fn tracks(&self, ids: impl IntoIterator<Item = TrackId>) -> Result<HashMap<TrackId, Track>, Error> {
+        let ids = dedupe(ids);
+        let missing: Vec<TrackId> = {
+            let tracks = self.inner.tracks.borrow();
+            ids.iter().copied().filter(|id| !tracks.contains_key(id)).collect()
+        };
+
+        if !missing.is_empty() {
+            let loaded = load_tracks(&self.inner.db, missing).await?;
+            self.inner.tracks.borrow_mut().extend(loaded);
+        }
+
+        let tracks = self.inner.tracks.borrow();
+        Ok(ids.iter().filter_map(|id| tracks.get(id).map(|track| (*id, track.clone()))).collect())
+    }
+
+    /// `viewer`'s views of `tracks` with their accepted credits linked to the
+    /// credited users, loaded together
+    pub async fn track_views(&self, tracks: Vec<Track>, viewer: Option<UserId>) -> Result<Vec<TrackView>, Error> {
+        let users = self.users(
+            tracks.iter().flat_map(|track| track.credited_artists.iter().filter_map(|credit| credit.linked_user()))
+        ).await?;
+
+        Ok(tracks
+            .into_iter()
+            .map(|track| {
+                let credits = track.credited_artists.iter()
+                    .map(|credit| credit.view(credit.linked_user().and_then(|id| users.get(&id))))
+                    .collect();
+                TrackView { credited_artists: credits, ..TrackView::for_viewer(track, viewer) }
+            })
+            .collect())
+    }
+}
+
+impl FromRequest for Hydrator {
+    type Error = Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let existing = req.extensions().get::<Hydrator>().cloned();
+        let hydrator = existing.unwrap_or_else(|| {
+            let hydrator = Hydrator::new(Db::of(req));
+            req.extensions_mut().insert(hydrator.clone());
+            hydrator
+        });
+
+        ready(Ok(hydrator))
+    }
+}
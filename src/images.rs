@@ -0,0 +1,102 @@
+//! Validation and resizing of uploaded profile images.
+//!
+//! Uploads must be PNG, JPEG, WebP or GIF within `MAX_UPLOAD_BYTES` and of a
+//! shape that suits where they're shown. They're re-encoded as JPEG, which
+//! also strips any metadata the original carried.
+
+use std::io::Cursor;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{ImageFormat, ImageReader};
+use crate::db::error::Error;
+
+pub const MAX_UPLOAD_BYTES: usize = 10 * 1024 * 1024;
+
+const JPEG_QUALITY: u8 = 85;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileImage {
+    Picture,
+    Banner,
+}
+
+impl ProfileImage {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProfileImage::Picture => "picture",
+            ProfileImage::Banner => "banner",
+        }
+    }
+
+    /// Smallest acceptable width and height
+    fn min_size(self) -> (u32, u32) {
+        match self {
+            ProfileImage::Picture => (128, 128),
+            ProfileImage::Banner => (1000, 250),
+        }
+    }
+
+    /// Largest width and height kept, bigger images are scaled down
+    fn max_size(self) -> (u32, u32) {
+        match self {
+            ProfileImage::Picture => (512, 512),
+            ProfileImage::Banner => (1500, 500),
+        }
+    }
+
+    /// Accepted range of width / height
+    fn aspect_ratio(self) -> (f64, f64) {
+        match self {
+            ProfileImage::Picture => (0.8, 1.25), // square-ish
+            ProfileImage::Banner => (2.5, 5.0), // wide
+        }
+    }
+}
+
+/// Check an upload and re-encode it as a JPEG sized for `kind`
+pub fn process(kind: ProfileImage, bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    if bytes.len() > MAX_UPLOAD_BYTES {
+        return Err(Error::PayloadTooLarge);
+    }
+
+    let format = image::guess_format(bytes)
+        .ok()
+        .filter(|format| matches!(format, ImageFormat::Png | ImageFormat::Jpeg | ImageFormat::WebP | ImageFormat::Gif))
+        .ok_or_else(|| Error::UnsupportedMedia("upload must be a PNG, JPEG, WebP or GIF image".to_string()))?;
+
+    let image = ImageReader::with_format(Cursor::new(bytes), format)
+        .decode()
+        .map_err(|_| Error::Validation("image could not be decoded".to_string()))?;
+
+    let (width, height) = (image.width(), image.height());
+    let (min_width, min_height) = kind.min_size();
+    if width < min_width || height < min_height {
+        return Err(Error::Validation(format!(
+            "{} must be at least {}x{} pixels",
+            kind.name(), min_width, min_height
+        )));
+    }
+
+    let ratio = width as f64 / height as f64;
+    let (min_ratio, max_ratio) = kind.aspect_ratio();
+    if ratio < min_ratio || ratio > max_ratio {
+        return Err(Error::Validation(match kind {
+            ProfileImage::Picture => "picture must be roughly square".to_string(),
+            ProfileImage::Banner => "banner must be a wide image".to_string(),
+        }));
+    }
+
+    let (max_width, max_height) = kind.max_size();
+    let image = if width > max_width || height > max_height {
+        image.resize(max_width, max_height, FilterType::Lanczos3)
+    } else {
+        image
+    };
+
+    let mut encoded = Vec::new();
+    JpegEncoder::new_with_quality(&mut encoded, JPEG_QUALITY)
+        .encode_image(&image.to_rgb8())
+        .map_err(|e| Error::Db(e.to_string()))?;
+
+    Ok(encoded)
+}
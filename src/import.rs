@@ -0,0 +1,232 @@
+//! Bulk user import for migrating a community from another platform.
+//!
+//! Records arrive as NDJSON and are processed in batches of `BATCH_SIZE`:
+//! each batch is validated, checked for conflicts with one query and written
+//! with one insert. Bad or conflicting rows are reported, never fatal.
+//!
+//! Creators can also import tracks from a manifest of already uploaded files,
+//! see `TrackOperations::import_manifest`.
+
+use std::collections::HashSet;
+use tracing::info;
+use crate::auth::HashScheme;
+use crate::clock;
+use crate::db::error::Error;
+use crate::db::import::ImportOperations;
+use crate::db::Db;
+use crate::moderation;
+use crate::types::id::UserId;
+use crate::types::import::{ImportIssue, ImportReport, ImportUserRecord, TrackManifestEntry};
+use crate::types::language;
+use crate::types::user::{username_sort_key, CreatedVia, User};
+
+pub const BATCH_SIZE: usize = 500;
+
+pub const MAX_USERNAME_LEN: usize = 32;
+
+pub const MAX_TITLE_LEN: usize = 200;
+
+/// Most entries accepted in one track manifest
+pub const MAX_MANIFEST_ENTRIES: usize = 500;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImportOptions {
+    /// Mark imported emails as verified, since the old platform vouched for them
+    pub skip_email_verification: bool,
+}
+
+/// Accumulates NDJSON lines and imports them a batch at a time
+pub struct UserImporter {
+    db: Db,
+    options: ImportOptions,
+    pending: Vec<(usize, ImportUserRecord)>,
+    seen_emails: HashSet<String>,
+    seen_usernames: HashSet<String>,
+    lines: usize,
+    report: ImportReport,
+}
+
+impl UserImporter {
+    pub fn new(db: Db, options: ImportOptions) -> Self {
+        Self {
+            db,
+            options,
+            pending: Vec::new(),
+            seen_emails: HashSet::new(),
+            seen_usernames: HashSet::new(),
+            lines: 0,
+            report: ImportReport::default(),
+        }
+    }
+    
+    /// Feed one line of input, flushing a batch when it fills up
+    pub async fn push_line(&mut self, line: &str) -> Result<(), Error> {
+        self.lines += 1;
+        let line_number = self.lines;
+        
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+        
+        match serde_json::from_str::<ImportUserRecord>(line) {
+            Ok(record) => match validate(&record) {
+                Ok(()) => self.pending.push((line_number, normalize(record))),
+                Err(reason) => self.error(line_number, Some(record.username), reason),
+            },
+            Err(e) => self.error(line_number, None, format!("invalid JSON: {}", e)),
+        }
+        
+        if self.pending.len() >= BATCH_SIZE {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+    
+    /// Import whatever is left and return the report
+    pub async fn finish(mut self) -> Result<ImportReport, Error> {
+        self.flush().await?;
+        info!(
+            "User import finished: {} created, {} skipped, {} errored",
+            self.report.created,
+            self.report.skipped.len(),
+            self.report.errored.len(),
+        );
+        Ok(self.report)
+    }
+    
+    fn error(&mut self, line: usize, username: Option<String>, reason: String) {
+        self.report.errored.push(ImportIssue { line, username, reason });
+    }
+    
+    fn skip(&mut self, line: usize, username: String, reason: &str) {
+        self.report.skipped.push(ImportIssue { line, username: Some(username), reason: reason.to_string() });
+    }
+    
+    async fn flush(&mut self) -> Result<(), Error> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::take(&mut self.pending);
+        
+        let emails = batch.iter().map(|(_, r)| r.email.clone()).collect();
+        let usernames = batch.iter().map(|(_, r)| r.username.clone()).collect();
+        let (taken_emails, taken_usernames) = ImportOperations::new(&self.db).existing_identities(emails, usernames).await?;
+        
+        let mut users = Vec::with_capacity(batch.len());
+        let mut lines = Vec::with_capacity(batch.len());
+        
+        for (line, record) in batch {
+            if taken_emails.contains(&record.email) || !self.seen_emails.insert(record.email.clone()) {
+                self.skip(line, record.username, "email already exists");
+                continue;
+            }
+            if taken_usernames.contains(&record.username) || !self.seen_usernames.insert(record.username.clone()) {
+                self.skip(line, record.username, "username already exists");
+                continue;
+            }
+            
+            lines.push((line, record.username.clone()));
+            users.push(self.build_user(record));
+        }
+        
+        match ImportOperations::new(&self.db).insert_users(&users).await {
+            Ok(()) => self.report.created += users.len(),
+            Err(e) => {
+                for (line, username) in lines {
+                    self.error(line, Some(username), format!("failed to write batch: {}", e));
+                }
+            }
+        }
+        
+        info!(
+            "User import progress: {} lines read, {} created, {} skipped, {} errored",
+            self.lines,
+            self.report.created,
+            self.report.skipped.len(),
+            self.report.errored.len(),
+        );
+        Ok(())
+    }
+    
+    fn build_user(&self, record: ImportUserRecord) -> User {
+        let now = clock::now();
+        let created_at = record.created_at.unwrap_or(now);
+        
+        User {
+            id: UserId::new(),
+            username_sort_key: username_sort_key(&record.username),
+            username: record.username,
+            email: record.email,
+            hashed_password: record.password_hash,
+            created_at,
+            updated_at: now,
+            bio: record.bio,
+            created_via: record.created_via.unwrap_or(CreatedVia::Web),
+            profile: None,
+            email_verified: self.options.skip_email_verification,
+            playlists: None,
+            deleted_playlists: Vec::new(),
+            legal_hold: false,
+        }
+    }
+}
+
+fn normalize(mut record: ImportUserRecord) -> ImportUserRecord {
+    record.username = record.username.trim().to_string();
+    record.email = record.email.trim().to_lowercase();
+    record
+}
+
+/// Check a record before it's considered for import
+pub fn validate(record: &ImportUserRecord) -> Result<(), String> {
+    moderation::validate_username(record.username.trim())?;
+    
+    let email = record.email.trim();
+    let valid_email = email.split_once('@')
+        .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.') && !domain.starts_with('.'));
+    if !valid_email {
+        return Err("invalid email address".to_string());
+    }
+    
+    if HashScheme::detect(&record.password_hash).is_none() {
+        return Err("password hash must be argon2 or bcrypt".to_string());
+    }
+    
+    if record.created_at.is_some_and(|created_at| created_at > clock::now()) {
+        return Err("created_at is in the future".to_string());
+    }
+    
+    Ok(())
+}
+
+pub fn is_http_url(url: &str) -> bool {
+    url.strip_prefix("https://").or_else(|| url.strip_prefix("http://")).is_some_and(|rest| !rest.is_empty())
+}
+
+/// Check a track manifest entry before it's imported
+pub fn validate_track(entry: &TrackManifestEntry) -> Result<(), String> {
+    let title = entry.title.trim();
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LEN {
+        return Err(format!("title must be 1 to {} characters", MAX_TITLE_LEN));
+    }
+    
+    if !is_http_url(entry.audio_url.trim()) {
+        return Err("audio_url must be an http(s) URL".to_string());
+    }
+    
+    if entry.cover_image_url.as_deref().is_some_and(|url| !is_http_url(url.trim())) {
+        return Err("cover_image_url must be an http(s) URL".to_string());
+    }
+    
+    if entry.created_at.is_some_and(|created_at| created_at > clock::now()) {
+        return Err("created_at is in the future".to_string());
+    }
+    
+    if let Some(code) = &entry.language {
+        language::normalize(code)?;
+    }
+    
+    entry.license.check_downloadable(entry.downloadable, entry.download_override)?;
+    
+    Ok(())
+}
@@ -0,0 +1,125 @@
+//! Database latency probe.
+//!
+//! Every `DB_LATENCY_PROBE_SECS` (default 15) a trivial query is timed
+//! against the database. The recent round trips feed `/metrics`,
+//! `GET /admin/latency` and `GET /ready`, where the database is reported
+//! degraded while the latest probe took longer than
+//! `DB_LATENCY_THRESHOLD_MS` (default 500) and unreachable while it failed.
+//! Each instance probes and reports for itself.
+//!
+//! Probes also move `db::connection_state` between connected and
+//! reconnecting as the database stops and starts answering.
+
+use std::collections::VecDeque;
+use std::env;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use crate::clock;
+use crate::db::{self, Db};
+use crate::types::latency::{DbHealth, DbLatency};
+
+/// Round trips kept for the average and worst
+const SAMPLES: usize = 20;
+
+/// A probe taking longer than this counts as failed
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often to probe, set in seconds with `DB_LATENCY_PROBE_SECS`
+pub fn probe_interval() -> Duration {
+    let secs = env::var("DB_LATENCY_PROBE_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(15);
+    Duration::from_secs(secs)
+}
+
+/// Round trips slower than this mark the database degraded, set with
+/// `DB_LATENCY_THRESHOLD_MS`
+pub fn threshold_ms() -> u64 {
+    env::var("DB_LATENCY_THRESHOLD_MS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(500)
+}
+
+#[derive(Default)]
+struct Probes {
+    recent: VecDeque<f64>,
+    last: Option<Result<f64, String>>,
+    probes: u64,
+    failures: u64,
+    probed_at: Option<DateTime<Utc>>,
+}
+
+static PROBES: LazyLock<Mutex<Probes>> = LazyLock::new(Default::default);
+
+/// Note the outcome of one probe
+pub fn record(outcome: Result<Duration, String>) {
+    let mut probes = PROBES.lock().unwrap_or_else(|e| e.into_inner());
+    probes.probes += 1;
+    probes.probed_at = Some(clock::now());
+    db::note_probe(outcome.is_ok());
+    match outcome {
+        Ok(elapsed) => {
+            let ms = elapsed.as_secs_f64() * 1000.0;
+            if probes.recent.len() == SAMPLES {
+                probes.recent.pop_front();
+            }
+            probes.recent.push_back(ms);
+            probes.last = Some(Ok(ms));
+        }
+        Err(e) => {
+            probes.failures += 1;
+            probes.last = Some(Err(e));
+        }
+    }
+}
+
+/// The database's health and latency as of the latest probe
+pub fn current() -> DbLatency {
+    let probes = PROBES.lock().unwrap_or_else(|e| e.into_inner());
+    let threshold_ms = threshold_ms();
+    let health = match &probes.last {
+        None => DbHealth::Unknown,
+        Some(Ok(ms)) => DbHealth::classify(*ms, threshold_ms),
+        Some(Err(_)) => DbHealth::Unreachable,
+    };
+    let recent_ms: Vec<f64> = probes.recent.iter().copied().collect();
+
+    DbLatency {
+        health,
+        last_ms: probes.last.as_ref().and_then(|last| last.as_ref().ok().copied()),
+        average_ms: (!recent_ms.is_empty()).then(|| recent_ms.iter().sum::<f64>() / recent_ms.len() as f64),
+        max_ms: recent_ms.iter().copied().reduce(f64::max),
+        recent_ms,
+        threshold_ms,
+        probes: probes.probes,
+        failures: probes.failures,
+        probed_at: probes.probed_at,
+        error: probes.last.as_ref().and_then(|last| last.as_ref().err().cloned()),
+    }
+}
+
+/// Time one round trip to the database
+async fn probe(db: &Db) -> Result<Duration, String> {
+    let started = Instant::now();
+    let query = async { db.query("RETURN true").await?.check() };
+    match actix_web::rt::time::timeout(PROBE_TIMEOUT, query).await {
+        Ok(Ok(_)) => Ok(started.elapsed()),
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err(format!("no answer within {}s", PROBE_TIMEOUT.as_secs())),
+    }
+}
+
+/// Probe in the background for as long as the server runs
+pub fn spawn_job(db: Db) {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(probe_interval());
+        loop {
+            ticker.tick().await;
+            record(probe(&db).await);
+        }
+    });
+}
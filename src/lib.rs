@@ -0,0 +1,50 @@
+pub mod app;
+pub mod attachments;
+pub mod auth;
+pub mod auth_audit;
+pub mod clock;
+pub mod conditional;
+pub mod config;
+pub mod connection_limit;
+pub mod db;
+pub mod email;
+pub mod embed;
+pub mod erasure;
+pub mod export;
+pub mod federation;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
+pub mod flags;
+pub mod gc;
+pub mod feed;
+pub mod geocoding;
+pub mod graphql;
+pub mod hydrate;
+pub mod images;
+pub mod import;
+pub mod latency;
+pub mod live_comments;
+pub mod live_moderation;
+pub mod live_playlists;
+pub mod logging;
+pub mod maintenance;
+pub mod markdown;
+pub mod moderation;
+pub mod ndjson;
+pub mod openapi;
+pub mod origin_check;
+pub mod presence;
+pub mod push;
+pub mod realtime;
+pub mod reconcile;
+pub mod request_id;
+pub mod request_logger;
+pub mod resumable;
+pub mod routes;
+pub mod sitemap;
+pub mod slug;
+pub mod storage;
+pub mod types;
+pub mod typing;
+pub mod upload_limit;
+pub mod webhook;
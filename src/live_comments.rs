@@ -0,0 +1,302 @@
+//! Live comment updates for sockets watching a track.
+//!
+//! A socket on `GET /ws` subscribes to a track with a `subscribe` message.
+//! The first subscriber to a track starts a SurrealDB live query on its
+//! comments; the last one leaving kills it, so only tracks somebody is
+//! watching hold a query. Comments live inside their track on the
+//! uploader's user record, so the query selects just that track's comments
+//! out of the record, and each update is diffed against the comments last
+//! seen to tell which were created, edited or deleted. Each change goes
+//! only to subscribers who could read the comment through
+//! `GET /tracks/{id}/comments`, never from banned or deleted accounts but to
+//! themselves, and never between users where one blocked the other.
+//!
+//! Subscribers also get the track's listener count from `presence`.
+//!
+//! When the database connection drops the live query ends with it; while
+//! the track has subscribers it's opened again with backoff, and anything
+//! that changed in between is announced from a fresh read.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use futures_util::StreamExt;
+use serde::Deserialize;
+use surrealdb::{Action, Notification};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+use tracing::warn;
+use crate::db::error::Error;
+use crate::db::track::TrackOperations;
+use crate::db::Db;
+use crate::hydrate::load_users;
+use crate::realtime::Event;
+use crate::types::id::{CommentId, TrackId, UserId};
+use crate::types::realtime::ServerMessage;
+use crate::types::user::{Comment, CommentView, PublicUser, Track, User};
+
+/// Most tracks one socket may watch at once
+pub const MAX_SUBSCRIPTIONS_PER_SOCKET: usize = 20;
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+struct Subscriber {
+    viewer: UserId,
+    sender: UnboundedSender<Event>,
+}
+
+/// The live query on one track and the sockets it feeds
+struct Watch {
+    subscribers: HashMap<u64, Subscriber>,
+    task: JoinHandle<()>,
+}
+
+/// Watched tracks by id. Tracks nobody watches are removed.
+static WATCHES: LazyLock<Mutex<HashMap<TrackId, Watch>>> = LazyLock::new(Default::default);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// One socket's subscription to a track, ended on drop
+#[derive(Debug)]
+pub struct Subscription {
+    track_id: TrackId,
+    id: u64,
+}
+
+/// Send `viewer`'s socket the comment changes on `track` from now on. The
+/// caller checks the viewer may see the track.
+pub fn subscribe(db: &Db, track: &Track, viewer: UserId, sender: UnboundedSender<Event>) -> Subscription {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    let watch = watches.entry(track.id).or_insert_with(|| Watch {
+        subscribers: HashMap::new(),
+        task: actix_web::rt::spawn(watch(Db::clone(db), track.id, track.user_id)),
+    });
+    watch.subscribers.insert(id, Subscriber { viewer, sender });
+
+    Subscription { track_id: track.id, id }
+}
+
+/// Send a message to every socket watching a track
+pub fn broadcast(track_id: TrackId, message: &ServerMessage) {
+    let watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    for subscriber in watches.get(&track_id).into_iter().flat_map(|watch| watch.subscribers.values()) {
+        let _ = subscriber.sender.send(Event { id: None, message: message.clone() });
+    }
+}
+
+/// Send a message from `author` to every other socket watching a track,
+/// under the same rules as their comments: nothing from banned or deleted
+/// accounts, and nothing between users where one blocked the other
+pub async fn relay(db: &Db, track_id: TrackId, author: UserId, message: ServerMessage) -> Result<(), Error> {
+    let viewers: Vec<UserId> = {
+        let watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+        watches.get(&track_id).into_iter().flat_map(|watch| watch.subscribers.values().map(|s| s.viewer)).collect()
+    };
+    if viewers.iter().all(|viewer| *viewer == author) {
+        return Ok(());
+    }
+
+    let users = load_users(db, viewers.into_iter().chain([author])).await?;
+
+    let watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    for subscriber in watches.get(&track_id).into_iter().flat_map(|watch| watch.subscribers.values()) {
+        if subscriber.viewer == author || !hears(&users, subscriber.viewer, author) {
+            continue;
+        }
+        let _ = subscriber.sender.send(Event { id: None, message: message.clone() });
+    }
+
+    Ok(())
+}
+
+/// Whether `viewer` hears live from `author`. Banned and deleted accounts
+/// are heard only by themselves, and nobody hears from someone they blocked
+/// or who blocked them. `users` holds both.
+fn hears(users: &HashMap<UserId, User>, viewer: UserId, author: UserId) -> bool {
+    if viewer == author {
+        return true;
+    }
+    let profile = |id: UserId| users.get(&id).and_then(|user| user.profile.as_ref());
+    let blocks = |a: UserId, b: UserId| profile(a)
+        .and_then(|p| p.blocked_users.as_ref())
+        .is_some_and(|blocked| blocked.contains(&b));
+
+    profile(author).is_some_and(|p| !p.is_banned() && !p.is_deleted())
+        && !blocks(viewer, author)
+        && !blocks(author, viewer)
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(watch) = watches.get_mut(&self.track_id) {
+            watch.subscribers.remove(&self.id);
+            if watch.subscribers.is_empty() {
+                // Dropping the stream inside the task kills the live query
+                watch.task.abort();
+                watches.remove(&self.track_id);
+            }
+        }
+    }
+}
+
+/// Follow a track's comments until aborted, reopening the live query
+/// whenever it ends
+async fn watch(db: Db, track_id: TrackId, owner_id: UserId) {
+    let mut known = None;
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        match follow(&db, track_id, owner_id, &mut known).await {
+            Ok(()) => backoff = Duration::from_secs(1),
+            Err(e) => warn!("Live comments on track {} failed: {}", track_id, e),
+        }
+        actix_web::rt::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// What the live query sends of the watched track: its comments, and
+/// enough to tell who may still see them
+#[derive(Deserialize)]
+struct Watched {
+    track: Option<WatchedTrack>,
+}
+
+#[derive(Deserialize)]
+struct WatchedTrack {
+    comments: Option<Vec<Comment>>,
+    is_public: bool,
+    is_deleted: bool,
+}
+
+/// Run one live query until the stream ends
+async fn follow(db: &Db, track_id: TrackId, owner_id: UserId, known: &mut Option<HashMap<CommentId, Comment>>) -> Result<(), Error> {
+    // Only the track's comments and visibility are selected, so other changes
+    // to the uploader's record arrive as the same comments and diff to
+    // nothing. Live queries don't see bound parameters, so the ids are
+    // written in; both are UUIDs.
+    let mut response = db
+        .query(format!(
+            "LIVE SELECT (profile.uploads[WHERE id = '{}'])[0].{{comments, is_public, is_deleted}} AS track
+            FROM users WHERE id = type::thing('users', '{}')",
+            track_id, owner_id
+        ))
+        .await?;
+    let mut stream = response.stream::<Notification<serde_json::Value>>(0)?;
+
+    // Read once the query is live, so nothing between the two is missed;
+    // after a reconnect this announces what changed while it was down
+    let mut track = TrackOperations::new(db).get_track(track_id).await?;
+    publish(db, &track, known).await?;
+
+    while let Some(notification) = stream.next().await {
+        let notification = notification?;
+        if !matches!(notification.action, Action::Update) {
+            continue;
+        }
+        // Through JSON, since the ids in comments are stored as strings
+        let watched = match serde_json::from_value(notification.data) {
+            Ok(Watched { track: Some(watched) }) => watched,
+            Ok(Watched { track: None }) => continue,
+            Err(e) => {
+                warn!("Skipped comments the live query sent for track {}: {}", track_id, e);
+                continue;
+            }
+        };
+        track.comments = watched.comments;
+        track.is_public = watched.is_public;
+        track.is_deleted = watched.is_deleted;
+        publish(db, &track, known).await?;
+    }
+
+    Ok(())
+}
+
+/// Every comment and reply on a track by id
+fn flatten(comments: Option<&Vec<Comment>>, into: &mut HashMap<CommentId, Comment>) {
+    for comment in comments.into_iter().flatten() {
+        flatten(comment.replies.as_ref(), into);
+        into.insert(comment.id, comment.clone());
+    }
+}
+
+enum Change {
+    Created(Comment),
+    Updated(Comment),
+    Deleted(Comment),
+}
+
+/// Tell subscribers how the track's comments differ from `known`, then
+/// remember them. The first look only remembers.
+async fn publish(db: &Db, track: &Track, known: &mut Option<HashMap<CommentId, Comment>>) -> Result<(), Error> {
+    let mut current = HashMap::new();
+    flatten(track.comments.as_ref(), &mut current);
+    let Some(previous) = known.replace(current.clone()) else {
+        return Ok(());
+    };
+
+    let mut changes: Vec<Change> = Vec::new();
+    let mut comments: Vec<&Comment> = current.values().collect();
+    comments.sort_by_key(|comment| (comment.created_at, comment.id));
+    for comment in comments {
+        match previous.get(&comment.id) {
+            None if !comment.is_deleted => changes.push(Change::Created(comment.clone())),
+            Some(old) if !old.is_deleted && comment.is_deleted => changes.push(Change::Deleted(comment.clone())),
+            Some(old) if !comment.is_deleted && (old.content != comment.content || old.is_pinned != comment.is_pinned) => {
+                changes.push(Change::Updated(comment.clone()));
+            }
+            _ => {}
+        }
+    }
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    // Authors and subscribers load together, since blocks are checked both ways
+    let viewers: Vec<UserId> = {
+        let watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+        watches.get(&track.id).into_iter().flat_map(|watch| watch.subscribers.values().map(|s| s.viewer)).collect()
+    };
+    let authors = changes.iter().map(|change| match change {
+        Change::Created(comment) | Change::Updated(comment) | Change::Deleted(comment) => comment.user_id,
+    });
+    let users = load_users(db, authors.chain(viewers)).await?;
+    let view = |comment: Comment| {
+        let author = users.get(&comment.user_id)
+            .filter(|author| author.profile.as_ref().is_some_and(|p| !p.is_deleted()))
+            .cloned()
+            .map(PublicUser::from);
+        CommentView::new(comment, author)
+    };
+    let messages: Vec<(UserId, ServerMessage)> = changes
+        .into_iter()
+        .map(|change| match change {
+            Change::Created(comment) => (comment.user_id, ServerMessage::CommentCreated { track_id: track.id, comment: view(comment) }),
+            Change::Updated(comment) => (comment.user_id, ServerMessage::CommentUpdated { track_id: track.id, comment: view(comment) }),
+            Change::Deleted(comment) => (comment.user_id, ServerMessage::CommentDeleted { track_id: track.id, comment_id: comment.id }),
+        })
+        .collect();
+
+    let watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    let subscribers = watches.get(&track.id).into_iter().flat_map(|watch| watch.subscribers.values());
+    for subscriber in subscribers {
+        // Same rule as listing comments: a track made private or deleted
+        // stops feeding everyone but its owner
+        if !track.is_visible_to(Some(subscriber.viewer)) {
+            continue;
+        }
+        for (author, message) in &messages {
+            // Same rules as relayed messages, deletions included, so nobody
+            // is told of a comment they were never sent
+            if !hears(&users, subscriber.viewer, *author) {
+                continue;
+            }
+            // A closed socket unsubscribes when its handler finishes
+            let _ = subscriber.sender.send(Event { id: None, message: message.clone() });
+        }
+    }
+
+    Ok(())
+}
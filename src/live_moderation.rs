@@ -0,0 +1,215 @@
+//! Live changes to the report queue for moderators.
+//!
+//! A socket on `GET /ws` subscribes with a `subscribe_moderation` message,
+//! or a moderator opens `GET /admin/reports/stream`. Like `live_comments`,
+//! the first subscriber starts a SurrealDB live query, here on the reports
+//! table, and the last one leaving kills it, so reports filed through any
+//! instance reach every moderator watching. Each change carries how many
+//! reports are open against its target, so clients can reorder the queue
+//! without reloading it, and a filed report that brings its target to
+//! `REPORT_URGENT_THRESHOLD` (default 5) open reports is announced on its
+//! own.
+//!
+//! Moderators are admins; impersonated sessions don't count. Roles are
+//! read again before every change is sent and every `RECHECK_INTERVAL`,
+//! and subscribers who lost theirs are sent `moderation_revoked` and
+//! dropped.
+
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use futures_util::StreamExt;
+use surrealdb::{Action, Notification};
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+use tracing::warn;
+use crate::db::error::Error;
+use crate::db::report::ReportOperations;
+use crate::db::{Db, UserOperations};
+use crate::realtime::Event;
+use crate::types::id::UserId;
+use crate::types::realtime::ServerMessage;
+use crate::types::user::{Report, ReportStatus, User};
+
+/// How often subscribers' roles are read again while the queue is quiet
+const RECHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+struct Subscriber {
+    viewer: UserId,
+    sender: UnboundedSender<Event>,
+}
+
+/// The live query on the reports table and the streams it feeds
+struct Watch {
+    subscribers: HashMap<u64, Subscriber>,
+    task: JoinHandle<()>,
+}
+
+/// None while nobody watches
+static WATCH: LazyLock<Mutex<Option<Watch>>> = LazyLock::new(Default::default);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Open reports against one target that make it urgent, set with
+/// `REPORT_URGENT_THRESHOLD`
+pub fn urgent_threshold() -> u64 {
+    env::var("REPORT_URGENT_THRESHOLD")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|threshold| *threshold > 0)
+        .unwrap_or(5)
+}
+
+/// Whether `user` may watch the report queue
+pub fn is_moderator(user: &User) -> bool {
+    user.profile.as_ref().is_some_and(|p| p.is_admin && !p.is_banned() && !p.is_deleted())
+}
+
+/// One stream's subscription to the report queue, ended on drop
+#[derive(Debug)]
+pub struct Subscription {
+    id: u64,
+}
+
+/// Send `viewer`'s stream the changes to the report queue from now on. The
+/// caller checks the viewer is a moderator.
+pub fn subscribe(db: &Db, viewer: UserId, sender: UnboundedSender<Event>) -> Subscription {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut watch = WATCH.lock().unwrap_or_else(|e| e.into_inner());
+    let watch = watch.get_or_insert_with(|| Watch {
+        subscribers: HashMap::new(),
+        task: actix_web::rt::spawn(run(Db::clone(db))),
+    });
+    watch.subscribers.insert(id, Subscriber { viewer, sender });
+
+    Subscription { id }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut watch = WATCH.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(current) = watch.as_mut() {
+            current.subscribers.remove(&self.id);
+            if current.subscribers.is_empty() {
+                // Dropping the stream inside the task kills the live query
+                current.task.abort();
+                *watch = None;
+            }
+        }
+    }
+}
+
+/// How many moderators are watching the queue on this instance
+pub fn connected_moderators() -> usize {
+    let watch = WATCH.lock().unwrap_or_else(|e| e.into_inner());
+    let viewers: HashSet<UserId> = watch.iter().flat_map(|watch| watch.subscribers.values().map(|s| s.viewer)).collect();
+    viewers.len()
+}
+
+/// Follow the reports table until aborted, reopening the live query
+/// whenever it ends
+async fn run(db: Db) {
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        match follow(&db).await {
+            Ok(()) => backoff = Duration::from_secs(1),
+            Err(e) => warn!("Live moderation queue failed: {}", e),
+        }
+        actix_web::rt::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(MAX_BACKOFF);
+    }
+}
+
+/// Run one live query until the stream ends
+async fn follow(db: &Db) -> Result<(), Error> {
+    let mut response = db
+        .query("LIVE SELECT *, record::id(id) AS id FROM reports")
+        .await?;
+    let mut stream = response.stream::<Notification<serde_json::Value>>(0)?;
+    let mut recheck = actix_web::rt::time::interval(RECHECK_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = recheck.tick() => recheck_roles(db).await?,
+            notification = stream.next() => {
+                let Some(notification) = notification else { return Ok(()) };
+                publish(db, notification?).await?;
+            }
+        }
+    }
+}
+
+/// Tell subscribers about one change to the reports table
+async fn publish(db: &Db, notification: Notification<serde_json::Value>) -> Result<(), Error> {
+    let report: Report = match serde_json::from_value(notification.data) {
+        Ok(report) => report,
+        Err(e) => {
+            warn!("Skipped a report the live query sent: {}", e);
+            return Ok(());
+        }
+    };
+    let open_reports = ReportOperations::new(db).count_open_against(report.target).await?;
+
+    let target = report.target;
+    let messages = match notification.action {
+        Action::Create => {
+            let is_urgent = report.status == ReportStatus::Open && open_reports == urgent_threshold();
+            let mut messages = vec![ServerMessage::ReportCreated { report, open_reports }];
+            if is_urgent {
+                messages.push(ServerMessage::ReportThresholdReached { target, open_reports });
+            }
+            messages
+        }
+        Action::Update => vec![ServerMessage::ReportStatusChanged { report, open_reports }],
+        _ => return Ok(()),
+    };
+
+    recheck_roles(db).await?;
+    let watch = WATCH.lock().unwrap_or_else(|e| e.into_inner());
+    for subscriber in watch.iter().flat_map(|watch| watch.subscribers.values()) {
+        for message in &messages {
+            // A closed stream unsubscribes when its handler finishes
+            let _ = subscriber.sender.send(Event { id: None, message: message.clone() });
+        }
+    }
+
+    Ok(())
+}
+
+/// Drop subscribers who are no longer moderators, reading their accounts
+/// past the cache so a revoked role stops the feed promptly
+async fn recheck_roles(db: &Db) -> Result<(), Error> {
+    let viewers: Vec<UserId> = {
+        let watch = WATCH.lock().unwrap_or_else(|e| e.into_inner());
+        let viewers: HashSet<UserId> = watch.iter().flat_map(|watch| watch.subscribers.values().map(|s| s.viewer)).collect();
+        viewers.into_iter().collect()
+    };
+    if viewers.is_empty() {
+        return Ok(());
+    }
+
+    let moderators: HashSet<UserId> = UserOperations::new(db).get_users_by_ids(&viewers)
+        .await?
+        .iter()
+        .filter(|user| is_moderator(user))
+        .map(|user| user.id)
+        .collect();
+
+    let mut watch = WATCH.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(watch) = watch.as_mut() {
+        watch.subscribers.retain(|_, subscriber| {
+            let keep = moderators.contains(&subscriber.viewer);
+            if !keep {
+                let _ = subscriber.sender.send(Event { id: None, message: ServerMessage::ModerationRevoked });
+            }
+            keep
+        });
+    }
+    // An emptied watch is left to the subscriptions' drops, which end the task
+
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Live edits to playlists for sockets watching them.
+//!
+//! A socket on `GET /ws` subscribes to a playlist with a
+//! `subscribe_playlist` message. Tracks are only added, removed and moved
+//! through the playlist endpoints, which announce each edit here once it's
+//! stored, so unlike comments no live query is needed. Every edit carries
+//! the playlist's new revision and who made it, and a move only says which
+//! track went where, not the whole list.
+//!
+//! Indexes count the tracks the subscriber can see, as `GET /playlists/{id}`
+//! lists them, and edits to tracks hidden from a subscriber aren't sent to
+//! them, so they may see revisions skip. Subscriptions are per process: with
+//! several instances a socket only hears about edits made on its own.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use tokio::sync::mpsc::UnboundedSender;
+use crate::realtime::Event;
+use crate::types::id::{PlaylistId, TrackId, UserId};
+use crate::types::realtime::ServerMessage;
+use crate::types::user::{Playlist, Track, TrackView};
+
+/// Most playlists one socket may watch at once
+pub const MAX_SUBSCRIPTIONS_PER_SOCKET: usize = 20;
+
+struct Subscriber {
+    viewer: UserId,
+    sender: UnboundedSender<Event>,
+}
+
+/// Sockets watching each playlist by id. Playlists nobody watches are removed.
+static WATCHES: LazyLock<Mutex<HashMap<PlaylistId, HashMap<u64, Subscriber>>>> = LazyLock::new(Default::default);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// One socket's subscription to a playlist, ended on drop
+#[derive(Debug)]
+pub struct Subscription {
+    playlist_id: PlaylistId,
+    id: u64,
+}
+
+/// A stored edit to a playlist's tracks
+pub enum Change {
+    Added(TrackId),
+    Removed(Box<Track>),
+    Moved(TrackId),
+}
+
+/// Send `viewer`'s socket the edits to a playlist from now on. The caller
+/// checks the viewer may see the playlist.
+pub fn subscribe(playlist_id: PlaylistId, viewer: UserId, sender: UnboundedSender<Event>) -> Subscription {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    watches.entry(playlist_id).or_default().insert(id, Subscriber { viewer, sender });
+
+    Subscription { playlist_id, id }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(subscribers) = watches.get_mut(&self.playlist_id) {
+            subscribers.remove(&self.id);
+            if subscribers.is_empty() {
+                watches.remove(&self.playlist_id);
+            }
+        }
+    }
+}
+
+/// Tell a deleted playlist's subscribers it's gone, and stop sending them
+/// anything about it
+pub fn close(playlist_id: PlaylistId) {
+    let mut watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    for subscriber in watches.remove(&playlist_id).into_iter().flat_map(HashMap::into_values) {
+        let _ = subscriber.sender.send(Event { id: None, message: ServerMessage::PlaylistDeleted { playlist_id } });
+    }
+}
+
+/// Tell the playlist's subscribers that `user_id` made `change`, leaving
+/// `playlist` as it is now
+pub fn publish(playlist: &Playlist, user_id: UserId, change: &Change) {
+    let watches = WATCHES.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(subscribers) = watches.get(&playlist.id) else { return };
+
+    let (playlist_id, revision) = (playlist.id, playlist.revision);
+    for subscriber in subscribers.values() {
+        let viewer = Some(subscriber.viewer);
+        if !playlist.is_visible_to(viewer) {
+            continue;
+        }
+
+        let message = match change {
+            Change::Removed(track) => {
+                if !track.is_visible_to(viewer) {
+                    continue;
+                }
+                ServerMessage::PlaylistTrackRemoved { playlist_id, revision, user_id, track_id: track.id }
+            }
+            Change::Added(track_id) | Change::Moved(track_id) => {
+                let Some(at) = playlist.tracks.iter().position(|track| track.id == *track_id) else { continue };
+                let track = &playlist.tracks[at];
+                if !track.is_visible_to(viewer) {
+                    continue;
+                }
+                let index = playlist.visible_index(viewer, at);
+                match change {
+                    Change::Added(_) => {
+                        ServerMessage::PlaylistTrackAdded { playlist_id, revision, user_id, index, track: TrackView::from(track.clone()) }
+                    }
+                    _ => ServerMessage::PlaylistTrackMoved { playlist_id, revision, user_id, track_id: *track_id, index },
+                }
+            }
+        };
+        // A closed socket unsubscribes when its handler finishes
+        let _ = subscriber.sender.send(Event { id: None, message });
+    }
+}
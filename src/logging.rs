@@ -1,12 +1,88 @@
-
+use std::env;
+use tracing::Subscriber;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::EnvFilter;
 
+/// Log to stdout, in the compact human format unless `LOG_FORMAT` is
+/// `json`, which writes one JSON object per line for log collectors
 pub fn init() {
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new("info,actix_web=info"));
-    tracing_subscriber::fmt()
+    let json = env::var("LOG_FORMAT").unwrap_or_default().eq_ignore_ascii_case("json");
+
+    subscriber(json, filter, std::io::stdout).init();
+}
+
+/// The subscriber `init` installs, writing to `writer`
+fn subscriber<W>(json: bool, filter: EnvFilter, writer: W) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
         .with_env_filter(filter)
         .with_target(true)
-        .compact()
-        .init();
-}
\ No newline at end of file
+        .with_writer(writer);
+
+    if json {
+        Box::new(builder.json().with_ansi(false).with_current_span(true).finish())
+    } else {
+        Box::new(builder.compact().finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use serde_json::Value;
+    use tracing_subscriber::EnvFilter;
+    use super::subscriber;
+
+    /// Everything written through any of its clones
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn json_mode_writes_a_parseable_object_per_line() {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = subscriber(true, EnvFilter::new("info"), move || writer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("upload", upload_id = 7);
+            let _entered = span.enter();
+            tracing::info!(bytes = 1024, "chunk stored");
+            tracing::debug!("filtered out");
+            tracing::warn!("chunk missing");
+        });
+
+        let output = String::from_utf8(captured.0.lock().unwrap().clone()).expect("logs are UTF-8");
+        let lines: Vec<Value> = output.lines()
+            .map(|line| serde_json::from_str(line).unwrap_or_else(|e| panic!("{:?} isn't JSON: {}", line, e)))
+            .collect();
+        assert_eq!(lines.len(), 2, "{}", output);
+
+        let line = &lines[0];
+        assert_eq!(line["level"], "INFO");
+        assert_eq!(line["target"], module_path!());
+        assert_eq!(line["fields"]["message"], "chunk stored");
+        assert_eq!(line["fields"]["bytes"], 1024);
+        assert_eq!(line["span"]["name"], "upload");
+        assert_eq!(line["span"]["upload_id"], 7);
+        assert!(line["timestamp"].is_string());
+        assert_eq!(lines[1]["level"], "WARN");
+        assert!(!output.contains('\u{1b}'), "no color codes");
+    }
+}
@@ -1,63 +1,28 @@
-mod db;
-mod types;
-mod logging;
-mod request_logger;
-
-use db::connect_db;
-use actix_web::{get, web, App, HttpResponse, HttpServer, Responder};
-use serde::Deserialize;
+use libretune::db::{connect_db, Db};
+use libretune::{app, config, email, erasure, flags, gc, latency, logging, maintenance, presence, reconcile, sitemap, typing};
+use actix_web::HttpServer;
 use std::env;
 use dotenv::dotenv;
-use request_logger::RequestLogger;
-use tracing_actix_web::TracingLogger;
-
-#[get("/")]
-async fn hello() -> impl Responder {
-    HttpResponse::Ok().body("Hello world!")
-}
-
-#[get("/users/{user_id}/")] // <- define path parameters
-async fn index(path: web::Path<String>) -> impl Responder {
-    let user_id = path.into_inner();
-    let result = format!("Welcome {}!", user_id);
-    HttpResponse::Ok().body(result)
-}
-
-#[derive(Deserialize)]
-struct SearchParams {
-    query: String,
-    limit: Option<u32>,
-    offset: Option<u32>,
-}
-
-#[get("/search")]
-async fn search(params: web::Query<SearchParams>) -> impl Responder {
-    let query = &params.query;
-    let limit = params.limit.unwrap_or(10);
-    let offset = params.offset.unwrap_or(0);
-    
-    // Simulate a search operation
-    let result = format!("Searching for '{}' with limit {} and offset {}", query, limit, offset);
-    
-    HttpResponse::Ok().body(result)
-}
-
-// Add a test endpoint that returns different status codes for testing
-#[get("/test/{status}")]
-async fn test_status(path: web::Path<u16>) -> impl Responder {
-    let status_code = path.into_inner();
-    match status_code {
-        200 => HttpResponse::Ok().body("Success!"),
-        404 => HttpResponse::NotFound().body("Not Found!"),
-        500 => HttpResponse::InternalServerError().body("Server Error!"),
-        _ => HttpResponse::BadRequest().body("Bad Request!"),
-    }
-}
+use libretune::app::AppConfig;
+use libretune::config::IssueLevel;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     dotenv().ok(); // Load environment variables from `.env`
     logging::init(); // Initialize logging
+    
+    // Refuse to start on settings that would otherwise fail later, and confusingly
+    let issues = config::check();
+    for issue in &issues {
+        match issue.level {
+            IssueLevel::Error => eprintln!("❌ {}: {}", issue.setting, issue.message),
+            IssueLevel::Warning => eprintln!("⚠️ {}: {}", issue.setting, issue.message),
+        }
+    }
+    if issues.iter().any(|issue| issue.level == IssueLevel::Error) {
+        std::process::exit(1);
+    }
+    
     let host = std::env::var("HOST").unwrap_or_else(|_| "127.0.0.1".to_string());
     let port = env::var("PORT")
         .unwrap_or_else(|_| "8000".to_string())
@@ -68,18 +33,47 @@ async fn main() -> std::io::Result<()> {
         eprintln!("❌ Failed to connect to SurrealDB: {}", e);
         std::process::exit(1);
     } 
+    let db = Db::global();
+    
+    // Pick up erasure jobs interrupted by a previous shutdown
+    if let Err(e) = erasure::resume_pending(&db).await {
+        eprintln!("❌ Failed to resume erasure jobs: {}", e);
+    }
+    
+    // Follow maintenance mode toggles from any instance
+    maintenance::spawn_job(db.clone());
+    
+    // Keep feature flags in memory so checking one is free
+    flags::spawn_job(db.clone());
+    
+    // Keep sitemap.xml fresh in the background
+    sitemap::spawn_job(db.clone());
+    
+    // Repair drifted denormalized counters nightly
+    reconcile::spawn_job(db.clone());
+    
+    // Delete media nothing refers to any more
+    gc::spawn_job(db.clone());
+    
+    // Let listener counts fall as players stop pinging
+    presence::spawn_job();
+    
+    // Say who stopped typing when their client didn't
+    typing::spawn_job(db.clone());
+    
+    // Time database round trips for /ready and /metrics
+    latency::spawn_job(db.clone());
+    
+    // Try again to send emails the mail server didn't take
+    email::spawn_job();
     
     println!("🚀 Libretune is running at http://127.0.0.1:{}", port);
     
-    HttpServer::new(|| {
-        App::new()
-            .wrap(RequestLogger::with_defaults()) // Add custom request logger
-            .wrap(TracingLogger::default()) 
-            .service(hello)
-            .service(index)
-            .service(search)
-            .service(test_status) // Add test endpoint
-    })
+    let app_config = AppConfig::from_env();
+    
+    HttpServer::new(move || app::build(app_config, db.clone()))
+    .workers(config::workers())
+    .keep_alive(config::keep_alive())
     .bind((host.as_str(), port))?
     .run()
     .await
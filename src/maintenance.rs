@@ -0,0 +1,191 @@
+//! Maintenance mode.
+//!
+//! While active, writes from everyone but admins are turned away with 503
+//! and a `Retry-After`, reads keep working and `GET /status` carries the
+//! banner message. With the `all` scope reads are turned away too, taking
+//! the API offline; admin routes, login, `/status`, `/health` and `/ready` always
+//! stay up. Admins toggle it with `POST /admin/maintenance`; it's stored so
+//! every instance picks it up within `MAINTENANCE_REFRESH_SECS` (default
+//! 10). `MAINTENANCE=on` forces it on regardless, with the banner from
+//! `MAINTENANCE_MESSAGE` and the scope from `MAINTENANCE_SCOPE`. Background
+//! jobs that write check `is_active` and skip their run.
+
+use std::env;
+use std::future::{ready, Ready};
+use std::rc::Rc;
+use std::sync::{LazyLock, RwLock};
+use std::time::Duration;
+use actix_web::body::EitherBody;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::{header, Method};
+use actix_web::HttpResponse;
+use futures_util::future::LocalBoxFuture;
+use tracing::error;
+use crate::clock;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::maintenance::MaintenanceOperations;
+use crate::db::Db;
+use crate::types::maintenance::{MaintenanceScope, MaintenanceState};
+
+/// Retry-After sent when none was configured
+pub const DEFAULT_RETRY_AFTER_SECS: u64 = 300;
+
+/// Paths that are always let through: admin routes, login so admins can
+/// get in to turn maintenance off, and the endpoints clients and load
+/// balancers poll
+const EXEMPT_PREFIXES: &[&str] = &["/admin/", "/auth/login", "/status", "/health", "/ready"];
+
+/// Paths that take POSTs but only read, let through unless the API is offline
+const READ_ONLY_PREFIXES: &[&str] = &["/api/graphql"];
+
+static STATE: LazyLock<RwLock<Option<MaintenanceState>>> = LazyLock::new(Default::default);
+
+/// The state forced by `MAINTENANCE=on`, if set
+static FORCED: LazyLock<Option<MaintenanceState>> = LazyLock::new(|| {
+    let on = env::var("MAINTENANCE").is_ok_and(|v| matches!(v.to_lowercase().as_str(), "on" | "true" | "1"));
+    on.then(|| MaintenanceState {
+        active: true,
+        scope: forced_scope(),
+        message: env::var("MAINTENANCE_MESSAGE").ok(),
+        retry_after_secs: DEFAULT_RETRY_AFTER_SECS,
+        changed_by: None,
+        changed_at: clock::now(),
+    })
+});
+
+/// Scope for `MAINTENANCE=on`, set with `MAINTENANCE_SCOPE=writes|all`.
+/// Defaults to `writes`.
+fn forced_scope() -> MaintenanceScope {
+    match env::var("MAINTENANCE_SCOPE") {
+        Ok(scope) if scope.eq_ignore_ascii_case("all") => MaintenanceScope::All,
+        Ok(scope) if scope.eq_ignore_ascii_case("writes") => MaintenanceScope::Writes,
+        Ok(scope) => panic!("MAINTENANCE_SCOPE must be writes or all, got {}", scope),
+        Err(_) => MaintenanceScope::Writes,
+    }
+}
+
+/// The state in effect right now
+pub fn current() -> Option<MaintenanceState> {
+    if let Some(forced) = FORCED.as_ref() {
+        return Some(forced.clone());
+    }
+    STATE.read().unwrap().clone().filter(|state| state.active)
+}
+
+pub fn is_active() -> bool {
+    FORCED.is_some() || STATE.read().unwrap().as_ref().is_some_and(|state| state.active)
+}
+
+/// Store a new state and apply it to this instance immediately
+pub async fn set(db: &Db, state: MaintenanceState) -> Result<(), Error> {
+    MaintenanceOperations::new(db).set_state(&state).await?;
+    *STATE.write().unwrap() = Some(state);
+    Ok(())
+}
+
+/// Reload the stored state
+pub async fn refresh(db: &Db) -> Result<(), Error> {
+    let state = MaintenanceOperations::new(db).get_state().await?;
+    *STATE.write().unwrap() = state;
+    Ok(())
+}
+
+/// How often to reload, set in seconds with `MAINTENANCE_REFRESH_SECS`
+pub fn interval() -> Duration {
+    let secs = env::var("MAINTENANCE_REFRESH_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(10);
+    Duration::from_secs(secs)
+}
+
+/// Reload the state now and every `interval()` after
+pub fn spawn_job(db: Db) {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(interval());
+        loop {
+            ticker.tick().await;
+            if let Err(e) = refresh(&db).await {
+                error!("Failed to refresh maintenance state: {}", e);
+            }
+        }
+    });
+}
+
+fn is_write(method: &Method) -> bool {
+    matches!(*method, Method::POST | Method::PUT | Method::PATCH | Method::DELETE)
+}
+
+fn is_exempt(path: &str) -> bool {
+    EXEMPT_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+fn is_read_only(path: &str) -> bool {
+    READ_ONLY_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+/// Whether maintenance in `scope` turns a request away
+fn blocks(scope: MaintenanceScope, method: &Method, path: &str) -> bool {
+    if is_exempt(path) {
+        return false;
+    }
+    match scope {
+        MaintenanceScope::Writes => is_write(method) && !is_read_only(path),
+        MaintenanceScope::All => true,
+    }
+}
+
+/// Rejects writes, or every request, with 503 while maintenance mode is on
+pub struct MaintenanceMode;
+
+impl<S, B> Transform<S, ServiceRequest> for MaintenanceMode
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type InitError = ();
+    type Transform = MaintenanceModeMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(MaintenanceModeMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct MaintenanceModeMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for MaintenanceModeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let state = current().filter(|state| blocks(state.scope, req.method(), req.path()));
+
+        if let Some(state) = state {
+            let message = state.message.unwrap_or_else(|| "Down for maintenance, try again later".to_string());
+            let response = HttpResponse::ServiceUnavailable()
+                .insert_header((header::RETRY_AFTER, state.retry_after_secs.to_string()))
+                .json(ErrorBody::new(message));
+            return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+        }
+
+        let service = Rc::clone(&self.service);
+        Box::pin(async move {
+            let res = service.call(req).await?;
+            Ok(res.map_into_left_body())
+        })
+    }
+}
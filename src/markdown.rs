@@ -0,0 +1,106 @@
+//! Server-side cleanup of user-supplied markdown.
+//!
+//! Markdown renderers pass inline HTML through untouched and follow any link
+//! scheme, so text is cleaned before it's stored: HTML tags are dropped and
+//! links to script-capable schemes are pointed at `#`. Everything else is
+//! left for the client to render.
+
+/// Link schemes that can run script when followed
+const UNSAFE_SCHEMES: [&str; 3] = ["javascript:", "vbscript:", "data:"];
+
+/// Clean `input` so it's safe to render as markdown
+pub fn sanitize(input: &str) -> String {
+    let text = strip_html(input);
+    let text = neutralize_inline_links(&text);
+
+    text.lines()
+        .map(neutralize_reference_link)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Whether a link target would run script
+fn is_unsafe_target(target: &str) -> bool {
+    // Browsers ignore whitespace and control characters inside a scheme
+    let scheme: String = target.chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .take(16)
+        .collect::<String>()
+        .to_lowercase();
+
+    UNSAFE_SCHEMES.iter().any(|unsafe_scheme| scheme.starts_with(unsafe_scheme))
+}
+
+/// Drop HTML tags and comments, keeping `<https://...>` autolinks and any
+/// `<` that doesn't open a tag, like `a < b`
+fn strip_html(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find('<') {
+        out.push_str(&rest[..start]);
+        let tail = &rest[start + 1..];
+
+        let opens_tag = tail.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || matches!(c, '/' | '!' | '?'));
+        let end = tail.find('>');
+
+        match end {
+            Some(end) if opens_tag => {
+                let inner = &tail[..end];
+                let autolink = ["http://", "https://", "mailto:"].iter().any(|scheme| inner.starts_with(scheme))
+                    && !inner.contains(char::is_whitespace);
+
+                if autolink {
+                    out.push_str(&rest[start..start + end + 2]);
+                }
+                rest = &tail[end + 1..];
+            }
+            _ => {
+                out.push('<');
+                rest = tail;
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Point `[text](target)` and `![alt](target)` links with unsafe targets at `#`
+fn neutralize_inline_links(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(start) = rest.find("](") {
+        out.push_str(&rest[..start + 2]);
+        let tail = &rest[start + 2..];
+        let end = tail.find(')').unwrap_or(tail.len());
+        let target = &tail[..end];
+
+        if is_unsafe_target(target) {
+            out.push('#');
+        } else {
+            out.push_str(target);
+        }
+        rest = &tail[end..];
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Point a `[label]: target` reference definition with an unsafe target at `#`
+fn neutralize_reference_link(line: &str) -> String {
+    let trimmed = line.trim_start();
+
+    let definition = trimmed.strip_prefix('[')
+        .and_then(|rest| rest.find("]:").map(|end| &rest[end + 2..]));
+
+    match definition {
+        Some(target) if is_unsafe_target(target) => {
+            let label_len = trimmed.len() - target.len();
+            format!("{}{} #", &line[..line.len() - trimmed.len()], &trimmed[..label_len])
+        }
+        _ => line.to_string(),
+    }
+}
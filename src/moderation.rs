@@ -0,0 +1,137 @@
+//! Word lists shared by everything that screens user-chosen text.
+//!
+//! Matching is done on whole words of a folded form of the text: lowercased
+//! and with common digit substitutions undone, so `4dm1n` and `admin_2` are
+//! caught the same as `admin`. The whole handle with its separators removed
+//! counts as a word too, which catches `a.d.m.i.n`. Words are never matched
+//! inside longer ones, so `ecosystem` and `staffordshire` are fine; offensive
+//! compounds are listed in full instead.
+
+use std::env;
+use std::sync::LazyLock;
+use crate::import::MAX_USERNAME_LEN;
+
+/// Words that aren't allowed in usernames or other public handles
+pub const BANNED_WORDS: &[&str] = &[
+    "asshole", "bastard", "bitch", "bollocks", "cunt", "dickhead", "fag",
+    "fuck", "motherfucker", "nazi", "nigger", "nigga", "retard", "shit",
+    "shithead", "slut", "twat", "wanker", "whore",
+];
+
+/// Handles that would let someone pose as the service or its staff, used
+/// unless `RESERVED_USERNAMES` gives a comma-separated list instead
+pub const DEFAULT_RESERVED: &[&str] = &[
+    "admin", "administrator", "libretune", "moderator", "official", "root",
+    "staff", "support", "system",
+];
+
+static RESERVED: LazyLock<Vec<String>> = LazyLock::new(|| match env::var("RESERVED_USERNAMES") {
+    Ok(list) => list.split(',').map(fold).filter(|word| !word.is_empty()).collect(),
+    Err(_) => DEFAULT_RESERVED.iter().map(|word| fold(word)).collect(),
+});
+
+/// Whether the word filters apply to usernames, turned off with
+/// `USERNAME_FILTER=off`
+fn filter_enabled() -> bool {
+    env::var("USERNAME_FILTER").map_or(true, |v| !matches!(v.to_lowercase().as_str(), "off" | "false" | "0"))
+}
+
+/// Lowercase, drop anything that isn't a letter and undo leetspeak
+fn fold(text: &str) -> String {
+    text.chars()
+        .filter_map(|c| match c.to_ascii_lowercase() {
+            '0' => Some('o'),
+            '1' | '!' => Some('i'),
+            '3' => Some('e'),
+            '4' | '@' => Some('a'),
+            '5' | '$' => Some('s'),
+            '7' => Some('t'),
+            c if c.is_alphabetic() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The words in `text`, split at separators and where a lowercase letter
+/// meets a capital, folded and without any number on the end, plus the whole
+/// text folded
+fn words(text: &str) -> Vec<String> {
+    let mut words = vec![fold(text)];
+    let mut word = String::new();
+    let mut last = None;
+    for c in text.chars() {
+        let separator = !c.is_alphanumeric() && !matches!(c, '!' | '@' | '$');
+        if separator || (c.is_uppercase() && last.is_some_and(char::is_lowercase)) {
+            words.push(fold(word.trim_end_matches(|c: char| c.is_ascii_digit())));
+            word.clear();
+        }
+        if !separator {
+            word.push(c);
+        }
+        last = Some(c);
+    }
+    words.push(fold(word.trim_end_matches(|c: char| c.is_ascii_digit())));
+    words.retain(|word| !word.is_empty());
+
+    words
+}
+
+/// Whether `text` has a banned word as one of its words
+pub fn is_offensive(text: &str) -> bool {
+    let words = words(text);
+    BANNED_WORDS.iter().any(|banned| words.iter().any(|word| word == banned))
+}
+
+/// Whether `text` has a reserved handle as one of its words
+pub fn is_reserved(text: &str) -> bool {
+    let words = words(text);
+    RESERVED.iter().any(|reserved| words.contains(reserved))
+}
+
+/// Check a username's length, characters and, unless turned off, that it's
+/// neither offensive nor reserved
+pub fn validate_username(username: &str) -> Result<(), String> {
+    if username.is_empty() || username.chars().count() > MAX_USERNAME_LEN {
+        return Err(format!("username must be 1 to {} characters", MAX_USERNAME_LEN));
+    }
+    if !username.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.') {
+        return Err("username may only contain letters, digits, '_', '-' and '.'".to_string());
+    }
+
+    if filter_enabled() {
+        if is_offensive(username) {
+            return Err("username contains a word that isn't allowed".to_string());
+        }
+        if is_reserved(username) {
+            return Err("username is reserved".to_string());
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_username;
+
+    #[test]
+    fn reserved_and_offensive_handles_are_rejected() {
+        for username in ["admin123", "4dm1n", "a.d.m.i.n", "Support_Team", "sh1thead"] {
+            assert!(validate_username(username).is_err(), "{} was let through", username);
+        }
+        for username in ["alice", "dj_nova", "the.lumineers"] {
+            assert_eq!(validate_username(username), Ok(()), "{} was turned away", username);
+        }
+    }
+
+    #[test]
+    fn words_are_only_matched_whole() {
+        for username in ["SupportTeam", "admin_2", "staff.picks", "ROOT"] {
+            assert!(validate_username(username).is_err(), "{} was let through", username);
+        }
+        // Reserved and banned words inside longer, innocent ones
+        for username in ["grassroots", "ecosystem", "staffordshire", "Scunthorpe", "classic_shitake"] {
+            assert_eq!(validate_username(username), Ok(()), "{} was turned away", username);
+        }
+    }
+}
@@ -0,0 +1,83 @@
+//! Streamed NDJSON exports.
+//!
+//! `stream` fetches a listing one page at a time and sends each page as soon
+//! as it arrives, so an export of any size holds one page in memory. If a page
+//! fails the stream ends with an `ErrorBody` line, letting the client tell a
+//! failed export from a complete one.
+
+use std::future::Future;
+use std::rc::Rc;
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse};
+use futures_util::stream;
+use serde::Serialize;
+use crate::db::error::{Error, ErrorBody};
+use crate::request_id;
+
+pub const CONTENT_TYPE: &str = "application/x-ndjson";
+
+/// Rows fetched per query
+pub const PAGE_SIZE: u32 = 500;
+
+/// Whether the client asked for NDJSON with `Accept: application/x-ndjson`
+pub fn wanted(req: &HttpRequest) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains(CONTENT_TYPE))
+}
+
+fn error_line(e: &Error) -> Bytes {
+    let body = match e {
+        Error::Db(detail) => ErrorBody::internal(detail),
+        e => ErrorBody::new(e.to_string()),
+    };
+    let mut line = serde_json::to_vec(&body).unwrap_or_default();
+    line.push(b'\n');
+    Bytes::from(line)
+}
+
+fn encode_page<T: Serialize>(rows: &[T]) -> Result<Bytes, Error> {
+    let mut chunk = Vec::new();
+    for row in rows {
+        serde_json::to_writer(&mut chunk, row)?;
+        chunk.push(b'\n');
+    }
+    Ok(Bytes::from(chunk))
+}
+
+/// Stream every row `fetch(limit, offset)` returns, page by page
+pub fn stream<T, F, Fut>(fetch: F) -> HttpResponse
+where
+    T: Serialize + 'static,
+    F: Fn(u32, u32) -> Fut + 'static,
+    Fut: Future<Output = Result<Vec<T>, Error>> + 'static,
+{
+    let fetch = Rc::new(fetch);
+    // The body is read after the handler returns, outside its request scope
+    let request_id = request_id::current();
+
+    // The state is the offset of the next page, None once finished
+    let body = stream::unfold(Some(0), move |offset: Option<u32>| {
+        let fetch = Rc::clone(&fetch);
+        request_id::scope(request_id, async move {
+            let offset = offset?;
+            let page = fetch(PAGE_SIZE, offset).await.and_then(|rows| {
+                let last = rows.len() < PAGE_SIZE as usize;
+                Ok((encode_page(&rows)?, rows.is_empty(), last))
+            });
+
+            match page {
+                Ok((_, true, _)) => None,
+                Ok((chunk, false, last)) => {
+                    let next = (!last).then_some(offset + PAGE_SIZE);
+                    Some((Ok::<_, Error>(chunk), next))
+                }
+                Err(e) => Some((Ok(error_line(&e)), None)),
+            }
+        })
+    });
+
+    HttpResponse::Ok().content_type(CONTENT_TYPE).streaming(body)
+}
@@ -0,0 +1,261 @@
+//! OpenAPI document for the REST API, served with Swagger UI.
+//!
+//! The Swagger UI page loads its script and styles from `SWAGGER_UI_ASSETS`,
+//! by default a pinned release on jsDelivr, so building doesn't download
+//! anything and the binary doesn't carry the UI.
+//!
+//! Every handler registered in `routes::configure` must also be listed in
+//! `ApiDoc` so the document stays complete, as must the optional
+//! `federation::configure` routes.
+
+use std::env;
+use std::sync::LazyLock;
+use actix_web::{web, HttpResponse};
+use askama::Template;
+use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use crate::db::error::ErrorBody;
+use crate::{federation, graphql, routes};
+use crate::types::lyrics::LyricsFormat;
+use crate::types::notification::Notification;
+use crate::types::pagination::Paginated;
+use crate::types::user::{CommentFilter, CommentView, PublicUser, Report, ReportTargetKind, TrackSort, TrackView, UserSort, UserSummary};
+use crate::types::verification::VerificationRequest;
+
+pub const SPEC_PATH: &str = "/api/v1/openapi.json";
+pub const DOCS_PATH: &str = "/api/v1/docs";
+
+/// Where the Swagger UI page loads `swagger-ui-bundle.js` and
+/// `swagger-ui.css` from unless `SWAGGER_UI_ASSETS` says otherwise
+const DEFAULT_ASSETS: &str = "https://cdn.jsdelivr.net/npm/swagger-ui-dist@5.17.14";
+
+/// Built on first use; it doesn't change while the server runs
+static SPEC: LazyLock<utoipa::openapi::OpenApi> = LazyLock::new(ApiDoc::openapi);
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Libretune API"),
+    paths(
+        routes::auth::register,
+        routes::auth::login,
+        routes::auth::send_verification_email,
+        routes::auth::verify_email,
+        routes::auth::forgot_password,
+        routes::auth::reset_password,
+        routes::search::search,
+        routes::tracks::get_track,
+        routes::tracks::patch_track,
+        routes::tracks::get_lyrics,
+        routes::tracks::patch_lyrics,
+        routes::tracks::download_track,
+        routes::tracks::stream_track,
+        routes::tracks::record_play,
+        routes::tracks::get_track_stats,
+        routes::tracks::ping_presence,
+        routes::tracks::get_listener_count,
+        routes::tracks::list_comments,
+        routes::tracks::add_comment,
+        routes::tracks::delete_comment,
+        routes::tracks::create_share_link,
+        routes::tracks::list_share_links,
+        routes::tracks::revoke_share_link,
+        routes::tracks::get_shared_track,
+        routes::tracks::get_track_by_slug,
+        routes::tracks::replace_audio,
+        routes::tracks::list_audio_versions,
+        routes::tracks::restore_audio,
+        routes::tracks::upload_attachment,
+        routes::tracks::list_attachments,
+        routes::tracks::download_attachment,
+        routes::tracks::delete_attachment,
+        routes::tracks::pin_track,
+        routes::tracks::unpin_track,
+        routes::tracks::set_credits,
+        routes::tracks::list_credits,
+        routes::tracks::respond_to_credit,
+        routes::reports::report_track,
+        routes::reports::report_comment,
+        routes::tracks::import_tracks,
+        routes::tracks::bulk_delete_tracks,
+        routes::uploads::create_upload,
+        routes::uploads::get_upload,
+        routes::uploads::upload_chunk,
+        routes::uploads::finalize_upload,
+        routes::uploads::cancel_upload,
+        routes::genres::list_genre_tracks,
+        routes::feeds::rss_feed,
+        routes::feeds::atom_feed,
+        routes::embed::oembed,
+        routes::embed::embed_track,
+        routes::embed::embed_playlist,
+        routes::playlists::get_playlist,
+        routes::playlists::add_playlist_track,
+        routes::playlists::remove_playlist_track,
+        routes::playlists::move_playlist_track,
+        routes::playlists::set_playlist_collaborative,
+        routes::playlists::delete_playlist,
+        routes::media::get_media,
+        routes::sitemap::sitemap_index,
+        routes::sitemap::sitemap_part,
+        routes::users::get_me,
+        routes::users::get_profile,
+        routes::users::get_profile_by_username,
+        routes::users::list_tracks,
+        routes::users::list_appearances,
+        routes::users::list_releases,
+        routes::users::list_followers,
+        routes::users::send_erase_code,
+        routes::users::erase_me,
+        routes::users::export_me,
+        routes::users::apply_for_verification,
+        routes::users::get_verification,
+        routes::users::set_location,
+        routes::users::set_track_order,
+        routes::users::set_now_playing_sharing,
+        routes::users::suggestions,
+        routes::users::upload_picture,
+        routes::users::upload_banner,
+        routes::webhooks::list_webhooks,
+        routes::webhooks::create_webhook,
+        routes::webhooks::get_webhook,
+        routes::webhooks::update_webhook,
+        routes::webhooks::delete_webhook,
+        routes::webhooks::list_deliveries,
+        routes::admin::list_users,
+        routes::admin::list_erasures,
+        routes::admin::get_erasure,
+        routes::admin::list_audit_entries,
+        routes::admin::set_legal_hold,
+        routes::admin::list_global_webhooks,
+        routes::admin::create_global_webhook,
+        routes::admin::update_global_webhook,
+        routes::admin::delete_global_webhook,
+        routes::admin::list_global_deliveries,
+        routes::admin::import_users,
+        routes::admin::signups_by_source,
+        routes::admin::list_reports,
+        routes::admin::set_report_status,
+        routes::admin::moderation_summary,
+        routes::admin::reconcile_counters,
+        routes::admin::list_flags,
+        routes::admin::set_flag,
+        routes::admin::list_orphan_tracks,
+        routes::admin::cleanup_orphan_tracks,
+        routes::admin::set_maintenance,
+        routes::admin::list_announcements,
+        routes::admin::create_announcement,
+        routes::admin::update_announcement,
+        routes::admin::delete_announcement,
+        routes::admin::impersonate,
+        routes::admin::end_impersonation,
+        routes::admin::get_config,
+        routes::admin::get_latency,
+        routes::admin::run_gc,
+        routes::admin::list_gc_runs,
+        routes::admin::list_verification_requests,
+        routes::admin::review_verification,
+        routes::admin::revoke_verification,
+        routes::metrics::metrics,
+        routes::status::status,
+        routes::status::health,
+        routes::status::ready,
+        routes::announcements::active_announcements,
+        routes::announcements::dismiss_announcement,
+        routes::notifications::list_notifications,
+        routes::notifications::mark_notifications_read,
+        routes::notifications::push_public_key,
+        routes::notifications::create_push_subscription,
+        routes::notifications::list_push_subscriptions,
+        routes::notifications::delete_push_subscription,
+        routes::notifications::get_push_preferences,
+        routes::notifications::set_push_preferences,
+        routes::ws::connect,
+        routes::events::notification_stream,
+        routes::events::feed_stream,
+        routes::events::moderation_stream,
+        routes::releases::create_release,
+        routes::releases::get_release,
+        routes::releases::update_release,
+        routes::releases::delete_release,
+        routes::releases::publish_release,
+        graphql::graphql,
+        federation::routes::webfinger,
+        federation::routes::actor,
+        federation::routes::outbox,
+        federation::routes::followers,
+        federation::routes::inbox,
+    ),
+    // Query parameter enums aren't collected on their own
+    components(schemas(ErrorBody, Paginated<PublicUser>, Paginated<TrackView>, Paginated<CommentView>, Paginated<UserSummary>, Paginated<Report>, Paginated<Notification>, Paginated<VerificationRequest>, CommentFilter, LyricsFormat, ReportTargetKind, TrackSort, UserSort)),
+    modifiers(&BearerAuth),
+    tags(
+        (name = "auth", description = "Sessions and login"),
+        (name = "search", description = "Finding users"),
+        (name = "tracks", description = "Track playback and downloads"),
+        (name = "feeds", description = "RSS and Atom feeds"),
+        (name = "embed", description = "oEmbed and embeddable players"),
+        (name = "playlists", description = "Playlists"),
+        (name = "media", description = "Uploaded media"),
+        (name = "seo", description = "Sitemaps for search engines"),
+        (name = "reports", description = "Reporting content to moderators"),
+        (name = "users", description = "Account management"),
+        (name = "webhooks", description = "Outgoing webhooks"),
+        (name = "admin", description = "Administration, requires an admin account"),
+        (name = "metrics", description = "Prometheus metrics"),
+        (name = "status", description = "Service status for clients to poll"),
+        (name = "announcements", description = "Site-wide announcements"),
+        (name = "notifications", description = "Notifications for the signed-in user"),
+        (name = "releases", description = "Singles, EPs and albums"),
+        (name = "graphql", description = "Read-only GraphQL API"),
+        (name = "federation", description = "Read-only ActivityPub, only served with FEDERATION=true"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Registers the `bearer` security scheme referenced by authenticated endpoints
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+/// Whether to serve the spec and Swagger UI. Set `API_DOCS=false` to turn them
+/// off, e.g. in production.
+pub fn docs_enabled() -> bool {
+    env::var("API_DOCS")
+        .map(|v| !matches!(v.to_lowercase().as_str(), "false" | "0" | "off"))
+        .unwrap_or(true)
+}
+
+/// Serve the spec at `SPEC_PATH` and Swagger UI at `DOCS_PATH`
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route(SPEC_PATH, web::get().to(spec))
+        .route(DOCS_PATH, web::get().to(docs))
+        .route(&format!("{DOCS_PATH}/"), web::get().to(docs));
+}
+
+async fn spec() -> HttpResponse {
+    HttpResponse::Ok().json(&*SPEC)
+}
+
+#[derive(Template)]
+#[template(path = "docs.html")]
+struct DocsPage<'a> {
+    assets: &'a str,
+    spec_url: &'a str,
+}
+
+async fn docs() -> HttpResponse {
+    let assets = env::var("SWAGGER_UI_ASSETS").unwrap_or_else(|_| DEFAULT_ASSETS.to_string());
+    let page = DocsPage { assets: assets.trim_end_matches('/'), spec_url: SPEC_PATH };
+    match page.render() {
+        Ok(html) => HttpResponse::Ok().content_type("text/html; charset=utf-8").body(html),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
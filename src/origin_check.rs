@@ -0,0 +1,151 @@
+//! CSRF protection for state-changing requests.
+//!
+//! POST, PUT, PATCH and DELETE requests that carry an `Origin` (or, failing
+//! that, a `Referer`) header must come from a trusted origin, otherwise they
+//! are rejected with 403. Requests authenticated only with a bearer token are
+//! skipped since browsers never attach those on their own, and requests with
+//! neither header are let through as they don't come from a browser page.
+
+use actix_web::{
+    body::EitherBody,
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::{header, Method},
+    Error, HttpResponse,
+};
+use futures_util::future::LocalBoxFuture;
+use std::{
+    env,
+    future::{ready, Ready},
+    rc::Rc,
+};
+use tracing::warn;
+use crate::config;
+use crate::db::error::ErrorBody;
+
+#[derive(Clone)]
+pub struct TrustedOriginsConfig {
+    pub origins: Vec<String>, // scheme://host[:port], lowercase, no trailing slash
+}
+
+impl TrustedOriginsConfig {
+    /// Read the comma separated `TRUSTED_ORIGINS`, defaulting to the origin
+    /// of `PUBLIC_URL`
+    pub fn from_env() -> Self {
+        let origins = env::var("TRUSTED_ORIGINS")
+            .map(|origins| origins.split(',').filter_map(origin_of).collect())
+            .unwrap_or_else(|_| origin_of(&config::public_url()).into_iter().collect());
+
+        Self { origins }
+    }
+
+    pub fn is_trusted(&self, origin: &str) -> bool {
+        origin_of(origin).is_some_and(|origin| self.origins.contains(&origin))
+    }
+}
+
+/// Normalize a URL down to its origin
+fn origin_of(url: &str) -> Option<String> {
+    let url = url.trim();
+    let (scheme, rest) = url.split_once("://")?;
+    let host = rest.split(['/', '?', '#']).next().filter(|host| !host.is_empty())?;
+
+    Some(format!("{}://{}", scheme, host).to_lowercase())
+}
+
+fn is_state_changing(method: &Method) -> bool {
+    matches!(*method, Method::POST | Method::PUT | Method::PATCH | Method::DELETE)
+}
+
+/// Whether the request authenticates only with a bearer token, which a
+/// browser can't be tricked into sending
+fn is_token_only(req: &ServiceRequest) -> bool {
+    let headers = req.headers();
+    let has_bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .is_some_and(|h| h.starts_with("Bearer "));
+
+    has_bearer && !headers.contains_key(header::COOKIE)
+}
+
+pub struct TrustedOrigins {
+    config: TrustedOriginsConfig,
+}
+
+impl TrustedOrigins {
+    pub fn new(config: TrustedOriginsConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn with_defaults() -> Self {
+        Self::new(TrustedOriginsConfig::from_env())
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for TrustedOrigins
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = TrustedOriginsMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(TrustedOriginsMiddleware {
+            service: Rc::new(service),
+            config: self.config.clone(),
+        }))
+    }
+}
+
+pub struct TrustedOriginsMiddleware<S> {
+    service: Rc<S>,
+    config: TrustedOriginsConfig,
+}
+
+impl<S, B> Service<ServiceRequest> for TrustedOriginsMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let origin = req
+            .headers()
+            .get(header::ORIGIN)
+            .or_else(|| req.headers().get(header::REFERER))
+            .and_then(|h| h.to_str().ok())
+            .map(|h| h.to_string());
+
+        let rejected = is_state_changing(req.method())
+            && !is_token_only(&req)
+            && origin.as_deref().is_some_and(|origin| !self.config.is_trusted(origin));
+
+        if rejected {
+            warn!(
+                "Rejected {} {} from untrusted origin {}",
+                req.method(),
+                req.uri(),
+                origin.unwrap_or_default()
+            );
+            let response = HttpResponse::Forbidden().json(ErrorBody::new("Untrusted origin"));
+            return Box::pin(async move { Ok(req.into_response(response).map_into_right_body()) });
+        }
+
+        let service = Rc::clone(&self.service);
+        Box::pin(async move {
+            let res = service.call(req).await?;
+            Ok(res.map_into_left_body())
+        })
+    }
+}
@@ -0,0 +1,182 @@
+//! Who's listening to what right now.
+//!
+//! Players ping while a track plays, over `POST /tracks/{id}/presence` or a
+//! `now_playing` WebSocket message. A listener counts toward a track until
+//! `PRESENCE_TTL` passes without a ping, so counts fall on their own when
+//! players stop. Signed-in users count once however many devices they
+//! play on, and playing something else moves them; anonymous listeners
+//! are told apart by address and user agent, and only ever counted.
+//!
+//! At most `PRESENCE_MAX_LISTENERS` (default 100000) listeners are held.
+//! Past that, pings from new listeners are ignored until old ones expire.
+//! Everything is in memory and per process, so counts only cover the
+//! instance the pings reached.
+
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::env;
+use std::hash::BuildHasher;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+use crate::clock;
+use crate::live_comments;
+use crate::types::id::{TrackId, UserId};
+use crate::types::realtime::ServerMessage;
+
+/// How long a listener counts after their last ping
+pub const PRESENCE_TTL: Duration = Duration::from_secs(60);
+
+/// How often expired listeners are dropped and changed counts announced
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Most listeners held at once, set with `PRESENCE_MAX_LISTENERS`
+pub fn max_listeners() -> usize {
+    env::var("PRESENCE_MAX_LISTENERS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(100_000)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Listener {
+    User(UserId),
+    Anonymous(u64), // hash of address and user agent, keyed per process
+}
+
+static ANONYMOUS_KEY: LazyLock<RandomState> = LazyLock::new(RandomState::new);
+
+impl Listener {
+    pub fn anonymous(ip: &str, user_agent: &str) -> Self {
+        Listener::Anonymous(ANONYMOUS_KEY.hash_one((ip, user_agent)))
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    /// Last ping of each listener, by track
+    tracks: HashMap<TrackId, HashMap<Listener, Instant>>,
+    /// What each signed-in listener is playing and since when
+    playing: HashMap<UserId, (TrackId, DateTime<Utc>)>,
+    /// Counts last sent to track subscribers
+    announced: HashMap<TrackId, usize>,
+    size: usize,
+}
+
+impl Store {
+    fn forget(&mut self, track_id: TrackId, listener: Listener) {
+        if let Some(listeners) = self.tracks.get_mut(&track_id) {
+            if listeners.remove(&listener).is_some() {
+                self.size -= 1;
+            }
+            if listeners.is_empty() {
+                self.tracks.remove(&track_id);
+            }
+        }
+    }
+
+    fn expire(&mut self) {
+        let mut expired = 0;
+        self.tracks.retain(|_, listeners| {
+            let before = listeners.len();
+            listeners.retain(|_, pinged| pinged.elapsed() <= PRESENCE_TTL);
+            expired += before - listeners.len();
+            !listeners.is_empty()
+        });
+        self.size -= expired;
+
+        let tracks = &self.tracks;
+        self.playing.retain(|user_id, (track_id, _)| {
+            tracks.get(track_id).is_some_and(|listeners| listeners.contains_key(&Listener::User(*user_id)))
+        });
+    }
+}
+
+static STORE: LazyLock<Mutex<Store>> = LazyLock::new(Default::default);
+
+/// Note that `listener` is playing `track_id`. Returns false when the store
+/// is full and the ping was dropped.
+pub fn ping(track_id: TrackId, listener: Listener) -> bool {
+    let mut store = STORE.lock().unwrap_or_else(|e| e.into_inner());
+
+    if let Listener::User(user_id) = listener {
+        match store.playing.get(&user_id) {
+            Some((playing, _)) if *playing == track_id => {}
+            Some((playing, _)) => {
+                let previous = *playing;
+                store.forget(previous, listener);
+                store.playing.insert(user_id, (track_id, clock::now()));
+            }
+            None => {
+                store.playing.insert(user_id, (track_id, clock::now()));
+            }
+        }
+    }
+
+    let known = store.tracks.get(&track_id).is_some_and(|listeners| listeners.contains_key(&listener));
+    if !known {
+        if store.size >= max_listeners() {
+            store.expire();
+        }
+        if store.size >= max_listeners() {
+            if let Listener::User(user_id) = listener {
+                store.playing.remove(&user_id);
+            }
+            return false;
+        }
+        store.size += 1;
+    }
+    store.tracks.entry(track_id).or_default().insert(listener, Instant::now());
+    true
+}
+
+/// How many are listening to a track now
+pub fn listener_count(track_id: TrackId) -> usize {
+    let store = STORE.lock().unwrap_or_else(|e| e.into_inner());
+    store.tracks.get(&track_id).map_or(0, |listeners| {
+        listeners.values().filter(|pinged| pinged.elapsed() <= PRESENCE_TTL).count()
+    })
+}
+
+/// The track a user is playing now and when they started it
+pub fn now_playing(user_id: UserId) -> Option<(TrackId, DateTime<Utc>)> {
+    let store = STORE.lock().unwrap_or_else(|e| e.into_inner());
+    let (track_id, since) = *store.playing.get(&user_id)?;
+    store.tracks.get(&track_id)?
+        .get(&Listener::User(user_id))
+        .filter(|pinged| pinged.elapsed() <= PRESENCE_TTL)
+        .map(|_| (track_id, since))
+}
+
+/// Drop expired listeners and tell subscribers of each track whose count
+/// changed, at most once per sweep however busy the track is
+fn sweep() {
+    let changed: Vec<(TrackId, usize)> = {
+        let mut store = STORE.lock().unwrap_or_else(|e| e.into_inner());
+        store.expire();
+
+        let counts: HashMap<TrackId, usize> = store.tracks.iter().map(|(id, listeners)| (*id, listeners.len())).collect();
+        let changed = counts.iter()
+            .filter(|(id, count)| store.announced.get(id) != Some(count))
+            .map(|(id, count)| (*id, *count))
+            .chain(store.announced.keys().filter(|id| !counts.contains_key(id)).map(|id| (*id, 0)))
+            .collect();
+        store.announced = counts;
+        changed
+    };
+
+    for (track_id, listeners) in changed {
+        live_comments::broadcast(track_id, &ServerMessage::ListenerCount { track_id, listeners });
+    }
+}
+
+/// Sweep in the background for as long as the server runs
+pub fn spawn_job() {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(SWEEP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            sweep();
+        }
+    });
+}
@@ -0,0 +1,205 @@
+//! Web Push notifications for users who aren't connected.
+//!
+//! When a notification is stored, users with no stream open on this
+//! instance also get it on every browser they subscribed, if they want its
+//! kind pushed (by default everything but new releases). A push holds only
+//! a title and a link, encrypted for the browser and signed with the
+//! server's VAPID key, so the push service learns nothing else.
+//!
+//! Pushes are sent in the background. One the push service throttles or
+//! can't take right now is tried again, waiting as long as it asks; one
+//! whose subscription is gone (404 or 410) deletes the subscription.
+//!
+//! Pushes are off unless `VAPID_PUBLIC_KEY` and `VAPID_PRIVATE_KEY` are
+//! set, as `libretune-admin config vapid-keys` prints them, with `VAPID_SUBJECT`
+//! a `mailto:` or `https:` contact for push services.
+
+use std::env;
+use std::sync::LazyLock;
+use std::time::Duration;
+use reqwest::StatusCode;
+use tracing::warn;
+use web_push::{ContentEncoding, SubscriptionInfo, VapidSignatureBuilder, WebPushError, WebPushMessage, WebPushMessageBuilder};
+use crate::db::error::Error;
+use crate::db::push::PushOperations;
+use crate::db::Db;
+use crate::hydrate::load_users;
+use crate::realtime;
+use crate::types::notification::Notification;
+use crate::types::push::{PushPayload, PushSubscription};
+
+/// Attempts per push before giving up on it
+const MAX_ATTEMPTS: u32 = 4;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Longest wait a `Retry-After` is honoured for
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(120);
+
+/// How long a push service keeps trying to reach an offline device
+const PUSH_TTL: u32 = 24 * 60 * 60;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+struct Vapid {
+    public_key: String,
+    private_key: String,
+    subject: String,
+}
+
+static VAPID: LazyLock<Option<Vapid>> = LazyLock::new(|| {
+    let public_key = env::var("VAPID_PUBLIC_KEY").ok()?;
+    let private_key = env::var("VAPID_PRIVATE_KEY").ok()?;
+    let subject = env::var("VAPID_SUBJECT").unwrap_or_default();
+    Some(Vapid { public_key, private_key, subject })
+});
+
+static CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("the push client builds")
+});
+
+/// The key browsers subscribe with, None when pushes are off
+pub fn public_key() -> Option<&'static str> {
+    VAPID.as_ref().map(|vapid| vapid.public_key.as_str())
+}
+
+/// Push `notifications` to those of their users who aren't connected,
+/// without waiting for it
+pub fn notify(db: &Db, notifications: Vec<Notification>) {
+    if VAPID.is_none() {
+        return;
+    }
+    let notifications: Vec<Notification> = notifications.into_iter()
+        .filter(|notification| !realtime::is_connected(notification.user_id))
+        .collect();
+    if notifications.is_empty() {
+        return;
+    }
+
+    let db = Db::clone(db);
+    actix_web::rt::spawn(async move {
+        if let Err(e) = push_all(&db, notifications).await {
+            warn!("Failed to send push notifications: {}", e);
+        }
+    });
+}
+
+async fn push_all(db: &Db, notifications: Vec<Notification>) -> Result<(), Error> {
+    let user_ids: Vec<_> = notifications.iter().map(|notification| notification.user_id).collect();
+    let subscriptions = PushOperations::new(db).subscriptions_for(&user_ids).await?;
+    if subscriptions.is_empty() {
+        return Ok(());
+    }
+    let users = load_users(db, subscriptions.iter().map(|subscription| subscription.user_id)).await?;
+
+    for notification in &notifications {
+        let wants = users.get(&notification.user_id)
+            .and_then(|user| user.profile.as_ref())
+            .is_some_and(|profile| profile.wants_push(notification.kind));
+        if !wants {
+            continue;
+        }
+
+        let payload = serde_json::to_vec(&PushPayload::for_notification(notification))?;
+        for subscription in subscriptions.iter().filter(|subscription| subscription.user_id == notification.user_id) {
+            actix_web::rt::spawn(deliver(Db::clone(db), subscription.clone(), payload.clone()));
+        }
+    }
+    Ok(())
+}
+
+enum Outcome {
+    Delivered,
+    Gone,
+    Retry(Option<Duration>),
+    Failed(String),
+}
+
+/// Send one push, trying again while the push service asks to
+async fn deliver(db: Db, subscription: PushSubscription, payload: Vec<u8>) {
+    let mut backoff = INITIAL_BACKOFF;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let outcome = match build(&subscription, &payload) {
+            Ok(message) => send(message).await,
+            Err(e) => Outcome::Failed(e.to_string()),
+        };
+
+        match outcome {
+            Outcome::Delivered => return,
+            Outcome::Gone => {
+                if let Err(e) = PushOperations::new(&db).prune(subscription.id).await {
+                    warn!("Failed to delete expired push subscription {}: {}", subscription.id, e);
+                }
+                return;
+            }
+            Outcome::Retry(wait) if attempt < MAX_ATTEMPTS => {
+                actix_web::rt::time::sleep(wait.unwrap_or(backoff).min(MAX_RETRY_AFTER)).await;
+                backoff *= 2;
+            }
+            Outcome::Retry(_) => warn!("Gave up pushing to subscription {} after {} attempts", subscription.id, attempt),
+            Outcome::Failed(reason) => {
+                warn!("Failed to push to subscription {}: {}", subscription.id, reason);
+                return;
+            }
+        }
+    }
+}
+
+/// Encrypt `payload` for a subscription and sign it
+fn build(subscription: &PushSubscription, payload: &[u8]) -> Result<WebPushMessage, WebPushError> {
+    let vapid = VAPID.as_ref().ok_or(WebPushError::MissingCryptoKeys)?;
+    let info = SubscriptionInfo::new(&subscription.endpoint, &subscription.keys.p256dh, &subscription.keys.auth);
+
+    let mut signature = VapidSignatureBuilder::from_base64(&vapid.private_key, &info)?;
+    signature.add_claim("sub", vapid.subject.as_str());
+
+    let mut builder = WebPushMessageBuilder::new(&info);
+    builder.set_ttl(PUSH_TTL);
+    builder.set_payload(ContentEncoding::Aes128Gcm, payload);
+    builder.set_vapid_signature(signature.build()?);
+    builder.build()
+}
+
+async fn send(message: WebPushMessage) -> Outcome {
+    let mut request = CLIENT
+        .post(message.endpoint.to_string())
+        .header("TTL", message.ttl.to_string());
+    if let Some(payload) = message.payload {
+        request = request
+            .header(reqwest::header::CONTENT_ENCODING, payload.content_encoding.to_str())
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream");
+        for (name, value) in payload.crypto_headers {
+            request = request.header(name, value);
+        }
+        request = request.body(payload.content);
+    }
+
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) if e.is_timeout() || e.is_connect() => return Outcome::Retry(None),
+        Err(e) => return Outcome::Failed(e.to_string()),
+    };
+    let status = response.status();
+    match status {
+        status if status.is_success() => Outcome::Delivered,
+        StatusCode::NOT_FOUND | StatusCode::GONE => Outcome::Gone,
+        StatusCode::TOO_MANY_REQUESTS => Outcome::Retry(retry_after(&response)),
+        status if status.is_server_error() => Outcome::Retry(retry_after(&response)),
+        status => Outcome::Failed(format!("push service answered {}", status)),
+    }
+}
+
+/// A `Retry-After` in seconds, the form push services send
+fn retry_after(response: &reqwest::Response) -> Option<Duration> {
+    response.headers()
+        .get(reqwest::header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()
+        .map(Duration::from_secs)
+}
@@ -0,0 +1,205 @@
+//! Pushing events to users' open streams.
+//!
+//! Each WebSocket opened on `GET /ws` and each event stream opened on
+//! `GET /notifications/stream` or `GET /feed/stream` registers here under
+//! its user, and `push` hands an event to every stream that user has open
+//! for its channel. Features push once and reach both transports.
+//!
+//! Pushed events are numbered per user, increasing even across restarts,
+//! and kept for `REPLAY_WINDOW` so an event stream reconnecting with
+//! `Last-Event-ID` picks up where it left off. Users who haven't had a
+//! stream open within the window have nothing kept: clients catch up
+//! through the REST endpoints instead.
+//!
+//! A user may hold `WS_MAX_CONNECTIONS_PER_USER` streams (default 5), on
+//! top of the server and per-IP caps in `connection_limit`. The registry
+//! is per process, so with several instances a user only hears about
+//! events raised by the instance their stream is on.
+
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use crate::clock;
+use crate::types::id::UserId;
+use crate::types::realtime::{Channel, ServerMessage};
+
+/// How long pushed events are kept for resuming streams
+pub const REPLAY_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Most events kept per user for resuming streams
+const REPLAY_LIMIT: usize = 100;
+
+/// A message for one stream, numbered when it was pushed to the user
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// None for replies meant for one socket, which aren't kept
+    pub id: Option<u64>,
+    pub message: ServerMessage,
+}
+
+struct Stream {
+    id: u64,
+    channel: Option<Channel>, // None takes every channel
+    sender: UnboundedSender<Event>,
+}
+
+#[derive(Default)]
+struct Listener {
+    streams: Vec<Stream>,
+    last_event_id: u64,
+    recent: VecDeque<(Instant, u64, ServerMessage)>,
+    /// Highest id dropped from `recent`; resuming from before it would miss events
+    evicted_up_to: u64,
+    disconnected_at: Option<Instant>,
+}
+
+impl Listener {
+    /// Whether nothing kept for this user could still be wanted
+    fn is_stale(&self) -> bool {
+        self.streams.is_empty() && self.disconnected_at.is_none_or(|at| at.elapsed() > REPLAY_WINDOW)
+    }
+
+    fn next_event_id(&mut self) -> u64 {
+        // Based on the clock so ids keep increasing after a restart
+        let now = clock::now().timestamp_micros().max(0) as u64;
+        self.last_event_id = now.max(self.last_event_id + 1);
+        self.last_event_id
+    }
+
+    fn evict(&mut self) {
+        while let Some((at, id, _)) = self.recent.front() {
+            if self.recent.len() <= REPLAY_LIMIT && at.elapsed() <= REPLAY_WINDOW {
+                break;
+            }
+            self.evicted_up_to = *id;
+            self.recent.pop_front();
+        }
+    }
+}
+
+/// Users with a stream open, or one closed within the replay window
+static LISTENERS: LazyLock<Mutex<HashMap<UserId, Listener>>> = LazyLock::new(Default::default);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Most streams one user may hold, set with `WS_MAX_CONNECTIONS_PER_USER`
+pub fn max_per_user() -> usize {
+    env::var("WS_MAX_CONNECTIONS_PER_USER")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|max| *max > 0)
+        .unwrap_or(5)
+}
+
+/// One registered stream, removed from the registry on drop
+#[derive(Debug)]
+pub struct Registration {
+    user_id: UserId,
+    id: u64,
+    sender: UnboundedSender<Event>,
+}
+
+/// What a stream starts with
+pub struct Opened {
+    pub registration: Registration,
+    pub events: UnboundedReceiver<Event>,
+    /// Events after the `Last-Event-ID` the stream resumed from
+    pub missed: Vec<Event>,
+    /// Whether some events after it are no longer kept
+    pub gap: bool,
+}
+
+/// Register a stream for `user_id` taking `channel`, or every channel when
+/// None. `resume_after` is the last event id the client saw. Returns None
+/// when the user already holds too many streams.
+pub fn register(user_id: UserId, channel: Option<Channel>, resume_after: Option<u64>) -> Option<Opened> {
+    let mut listeners = LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    let listener = listeners.entry(user_id).or_default();
+    if listener.streams.len() >= max_per_user() {
+        return None;
+    }
+    listener.evict();
+
+    // Collected under the same lock the stream is added under, so nothing
+    // pushed in between is lost or sent twice
+    let (missed, gap) = match resume_after {
+        Some(after) => {
+            let missed = listener.recent.iter()
+                .filter(|(_, id, message)| *id > after && channel.is_none_or(|channel| message.channel() == Some(channel)))
+                .map(|(_, id, message)| Event { id: Some(*id), message: message.clone() })
+                .collect();
+            (missed, after < listener.evicted_up_to)
+        }
+        None => (Vec::new(), false),
+    };
+
+    let (sender, events) = unbounded_channel();
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    listener.streams.push(Stream { id, channel, sender: sender.clone() });
+    listener.disconnected_at = None;
+
+    Some(Opened { registration: Registration { user_id, id, sender }, events, missed, gap })
+}
+
+impl Registration {
+    /// Where to send messages meant for this stream alone
+    pub fn sender(&self) -> UnboundedSender<Event> {
+        self.sender.clone()
+    }
+}
+
+/// Whether events pushed to `user_id` would be delivered or kept: they have
+/// a stream open on this instance, or closed one within the replay window
+pub fn is_listening(user_id: UserId) -> bool {
+    LISTENERS.lock().unwrap_or_else(|e| e.into_inner())
+        .get(&user_id)
+        .is_some_and(|listener| !listener.is_stale())
+}
+
+/// Whether `user_id` has a stream taking notifications open on this
+/// instance right now
+pub fn is_connected(user_id: UserId) -> bool {
+    LISTENERS.lock().unwrap_or_else(|e| e.into_inner())
+        .get(&user_id)
+        .is_some_and(|listener| listener.streams.iter().any(|stream| stream.channel.is_none_or(|channel| channel == Channel::Notifications)))
+}
+
+/// Send a message to every stream `user_id` has open for its channel, and
+/// keep it for streams that resume
+pub fn push(user_id: UserId, message: &ServerMessage) {
+    let mut listeners = LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(listener) = listeners.get_mut(&user_id) else { return };
+    if listener.is_stale() {
+        listeners.remove(&user_id);
+        return;
+    }
+
+    let id = listener.next_event_id();
+    listener.recent.push_back((Instant::now(), id, message.clone()));
+    listener.evict();
+
+    let channel = message.channel();
+    for stream in &listener.streams {
+        if stream.channel.is_none() || stream.channel == channel {
+            // A closed stream is unregistered when its handler finishes
+            let _ = stream.sender.send(Event { id: Some(id), message: message.clone() });
+        }
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        let mut listeners = LISTENERS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(listener) = listeners.get_mut(&self.user_id) {
+            listener.streams.retain(|stream| stream.id != self.id);
+            if listener.streams.is_empty() {
+                listener.disconnected_at = Some(Instant::now());
+            }
+        }
+        // Forget users whose window has passed, whoever they are
+        listeners.retain(|_, listener| !listener.is_stale());
+    }
+}
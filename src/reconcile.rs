@@ -0,0 +1,149 @@
+//! Repairs denormalized values that have drifted from their source of truth.
+//!
+//! Each `Counter` names a recount query, which yields the stored and true
+//! value of every record, and the field to write the true value back to.
+//! Adding one is a new entry in `COUNTERS`. The job runs every
+//! `RECONCILE_INTERVAL_SECS` (default daily) and on demand from
+//! `POST /admin/reconcile`; every correction is logged.
+//!
+//! Track `comment_count`s live inside each user's uploads, where a single
+//! field write can't reach them, so they're repaired by
+//! `reconcile_comment_counts` instead of a `Counter`. Username sort keys
+//! can't be computed in a query, so `reconcile_username_sort_keys` fills
+//! them in from Rust, including for users stored before they existed.
+
+use std::env;
+use std::time::Duration;
+use serde::Serialize;
+use tracing::{error, info, warn};
+use utoipa::ToSchema;
+use crate::db::error::Error;
+use crate::db::reconcile::ReconcileOperations;
+use crate::db::track::TrackOperations;
+use crate::db::{Db, UserOperations};
+use crate::maintenance;
+
+pub struct Counter {
+    pub name: &'static str,
+    /// Table holding the denormalized value
+    pub table: &'static str,
+    /// Query returning `{ id, stored, actual }` for every record in `table`
+    pub recount: &'static str,
+    /// Field the true value is written to
+    pub field: &'static str,
+}
+
+pub const COUNTERS: &[Counter] = &[
+    // Follower lists mirror the following lists of everyone else
+    Counter {
+        name: "followers",
+        table: "users",
+        recount: "SELECT
+                record::id(id) AS id,
+                array::sort(profile.followers ?? []) AS stored,
+                array::sort((SELECT VALUE record::id(id) FROM users WHERE profile.following CONTAINS record::id($parent.id))) AS actual
+            FROM users WHERE profile != NONE",
+        field: "profile.followers",
+    },
+];
+
+/// Name of the track comment count, reconciled alongside `COUNTERS`
+pub const COMMENT_COUNT: &str = "comment_count";
+
+/// Name of the username sort key, reconciled alongside `COUNTERS`
+pub const USERNAME_SORT_KEY: &str = "username_sort_key";
+
+pub fn counter(name: &str) -> Option<&'static Counter> {
+    COUNTERS.iter().find(|counter| counter.name == name)
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReconcileReport {
+    pub counter: String,
+    pub checked: usize,
+    pub corrected: usize,
+}
+
+/// Recount one counter and fix every record that disagrees
+pub async fn reconcile(db: &Db, counter: &Counter) -> Result<ReconcileReport, Error> {
+    let rows = ReconcileOperations::new(db).recount(counter).await?;
+    let checked = rows.len();
+    let mut corrected = 0;
+
+    for row in rows.into_iter().filter(|row| row.stored != row.actual) {
+        warn!(
+            "Reconciling {} for {}:{}: {} -> {}",
+            counter.name, counter.table, row.id, row.stored, row.actual
+        );
+        ReconcileOperations::new(db).fix(counter, row.id, row.actual).await?;
+        corrected += 1;
+    }
+
+    info!("Reconciled {}: {} checked, {} corrected", counter.name, checked, corrected);
+    Ok(ReconcileReport { counter: counter.name.to_string(), checked, corrected })
+}
+
+/// Recount every track's comments and fix the counts that drifted
+pub async fn reconcile_comment_counts(db: &Db) -> Result<ReconcileReport, Error> {
+    let (checked, corrected) = TrackOperations::new(db).repair_comment_counts().await?;
+
+    info!("Reconciled {}: {} checked, {} corrected", COMMENT_COUNT, checked, corrected);
+    Ok(ReconcileReport { counter: COMMENT_COUNT.to_string(), checked, corrected })
+}
+
+/// Recompute every user's username sort key and fix the ones that differ
+pub async fn reconcile_username_sort_keys(db: &Db) -> Result<ReconcileReport, Error> {
+    let (checked, corrected) = UserOperations::new(db).repair_username_sort_keys().await?;
+
+    info!("Reconciled {}: {} checked, {} corrected", USERNAME_SORT_KEY, checked, corrected);
+    Ok(ReconcileReport { counter: USERNAME_SORT_KEY.to_string(), checked, corrected })
+}
+
+/// Reconcile one counter by name
+pub async fn reconcile_named(db: &Db, name: &str) -> Result<ReconcileReport, Error> {
+    if name == COMMENT_COUNT {
+        return reconcile_comment_counts(db).await;
+    }
+    if name == USERNAME_SORT_KEY {
+        return reconcile_username_sort_keys(db).await;
+    }
+    let counter = counter(name).ok_or_else(|| Error::Validation(format!("unknown counter {}", name)))?;
+    reconcile(db, counter).await
+}
+
+/// Reconcile every registered counter
+pub async fn reconcile_all(db: &Db) -> Result<Vec<ReconcileReport>, Error> {
+    let mut reports = Vec::with_capacity(COUNTERS.len() + 2);
+    for counter in COUNTERS {
+        reports.push(reconcile(db, counter).await?);
+    }
+    reports.push(reconcile_comment_counts(db).await?);
+    reports.push(reconcile_username_sort_keys(db).await?);
+    Ok(reports)
+}
+
+/// How often to reconcile, set in seconds with `RECONCILE_INTERVAL_SECS`
+pub fn interval() -> Duration {
+    let secs = env::var("RECONCILE_INTERVAL_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(24 * 60 * 60);
+    Duration::from_secs(secs)
+}
+
+/// Reconcile every counter each `interval()`, starting one interval from now
+pub fn spawn_job(db: Db) {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(interval());
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            if maintenance::is_active() {
+                continue;
+            }
+            if let Err(e) = reconcile_all(&db).await {
+                error!("Failed to reconcile counters: {}", e);
+            }
+        }
+    });
+}
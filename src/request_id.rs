@@ -0,0 +1,76 @@
+//! The request id `TracingLogger` gives each request, for code that only
+//! sees the error.
+//!
+//! `RequestIdScope` sits inside `TracingLogger` and makes the id readable
+//! through `current` while the request is handled, so the id a server error
+//! reports is the one on the request's log lines. Streamed bodies run after
+//! the handler returns and carry it over with `scope`.
+
+use std::future::{ready, Future, Ready};
+use std::rc::Rc;
+use actix_web::dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::HttpMessage;
+use futures_util::future::LocalBoxFuture;
+use tracing_actix_web::RequestId;
+use uuid::Uuid;
+
+tokio::task_local! {
+    static REQUEST_ID: Uuid;
+}
+
+/// The id of the request being handled, if any
+pub fn current() -> Option<Uuid> {
+    REQUEST_ID.try_with(|id| *id).ok()
+}
+
+/// Run `future` as part of the request `id`
+pub async fn scope<F: Future>(id: Option<Uuid>, future: F) -> F::Output {
+    match id {
+        Some(id) => REQUEST_ID.scope(id, future).await,
+        None => future.await,
+    }
+}
+
+/// Makes the `TracingLogger` request id available to handlers. Wrap it
+/// inside `TracingLogger`, which sets the id.
+pub struct RequestIdScope;
+
+impl<S, B> Transform<S, ServiceRequest> for RequestIdScope
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type InitError = ();
+    type Transform = RequestIdScopeMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestIdScopeMiddleware { service: Rc::new(service) }))
+    }
+}
+
+pub struct RequestIdScopeMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestIdScopeMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = actix_web::Error> + 'static,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = actix_web::Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let id = req.extensions().get::<RequestId>().copied().map(Uuid::from);
+        let service = Rc::clone(&self.service);
+        Box::pin(scope(id, async move { service.call(req).await }))
+    }
+}
@@ -3,8 +3,11 @@ use actix_web::{
     Error,
 };
 use futures_util::future::LocalBoxFuture;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use std::{
+    cell::RefCell,
     env,
     fs::{create_dir_all, OpenOptions},
     future::{ready, Ready},
@@ -66,6 +69,9 @@ pub struct RequestLoggerConfig {
     pub log_to_file: bool,
     pub log_file_path: String,
     pub log_format: LogFormat,
+    pub slow_request_threshold_ms: Option<u128>, // None disables slow request warnings
+    pub sample_rate: f64, // share of 2xx requests logged, 0.0 to 1.0; errors are always logged
+    pub sample_seed: Option<u64>, // fixes the sampler's RNG, for reproducible sampling
 }
 
 #[derive(Clone)]
@@ -81,6 +87,9 @@ impl Default for RequestLoggerConfig {
             log_to_file: false,
             log_file_path: "logs/requests.log".to_string(),
             log_format: LogFormat::Text,
+            slow_request_threshold_ms: None,
+            sample_rate: 1.0,
+            sample_seed: None,
         }
     }
 }
@@ -108,8 +117,46 @@ impl RequestLoggerConfig {
             log_file_path: env::var("LOG_REQUESTS_FILE_PATH")
                 .unwrap_or_else(|_| "logs/requests.log".to_string()),
             log_format,
+            slow_request_threshold_ms: env::var("LOG_SLOW_REQUEST_MS")
+                .ok()
+                .and_then(|ms| ms.parse().ok()),
+            sample_rate: env::var("LOG_REQUESTS_SAMPLE_RATE")
+                .ok()
+                .and_then(|rate| rate.parse::<f64>().ok())
+                .map_or(1.0, |rate| rate.clamp(0.0, 1.0)),
+            sample_seed: env::var("LOG_REQUESTS_SAMPLE_SEED")
+                .ok()
+                .and_then(|seed| seed.parse().ok()),
         }
     }
+    
+    /// Whether to log a request. Successful requests that weren't slow are
+    /// kept with probability `sample_rate`; everything else always is.
+    pub fn should_log(&self, log: &RequestLog, rng: &mut impl Rng) -> bool {
+        let sampled = matches!(log.status_category, StatusCategory::Success) && !self.is_slow(log.response_time_ms);
+        
+        !sampled || self.sample_rate >= 1.0 || rng.random_bool(self.sample_rate.max(0.0))
+    }
+    
+    /// The RNG sampling decisions are drawn from
+    fn sampler(&self) -> StdRng {
+        match self.sample_seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_os_rng(),
+        }
+    }
+    
+    /// Whether a request took longer than the slow request threshold
+    pub fn is_slow(&self, response_time_ms: u128) -> bool {
+        self.slow_request_threshold_ms
+            .is_some_and(|threshold| response_time_ms > threshold)
+    }
+    
+    /// What a log line starts with for a slow request, the same on the
+    /// console as in the file so either can be searched for it
+    fn slow_marker(&self, response_time_ms: u128) -> &'static str {
+        if self.is_slow(response_time_ms) { "SLOW " } else { "" }
+    }
 }
 
 pub struct RequestLogger {
@@ -138,8 +185,10 @@ impl RequestLogger {
     }
 
     fn log_to_console(&self, log: &RequestLog) {
+        let slow = self.config.is_slow(log.response_time_ms);
         let log_message = format!(
-            "{} {} {} {} - {} {}ms [{}->{}] {}",
+            "{}{} {} {} {} - {} {}ms [{}->{}] {}",
+            self.config.slow_marker(log.response_time_ms),
             log.status_category.emoji(),
             log.method,
             log.uri,
@@ -156,6 +205,7 @@ impl RequestLogger {
 
         // Also use tracing for structured logging
         match log.status_category {
+            StatusCategory::Success | StatusCategory::Redirect if slow => warn!("{}", log_message),
             StatusCategory::Success => info!("{}", log_message),
             StatusCategory::Redirect => info!("{}", log_message),
             StatusCategory::ClientError => warn!("{}", log_message),
@@ -191,9 +241,10 @@ impl RequestLogger {
                 }
             }
             LogFormat::Text => format!(
-                "{} [{}] {} {} {} - {} {}ms [{}->{}] {}\n",
+                "{} [{}] {}{} {} {} - {} {}ms [{}->{}] {}\n",
                 log.timestamp,
                 log.status_category.emoji(),
+                self.config.slow_marker(log.response_time_ms),
                 log.method,
                 log.uri,
                 log.client_ip,
@@ -226,6 +277,7 @@ where
     fn new_transform(&self, service: S) -> Self::Future {
         ready(Ok(RequestLoggerMiddleware {
             service: Rc::new(service),
+            sampler: Rc::new(RefCell::new(self.config.sampler())),
             config: self.config.clone(),
         }))
     }
@@ -234,6 +286,7 @@ where
 pub struct RequestLoggerMiddleware<S> {
     service: Rc<S>,
     config: RequestLoggerConfig,
+    sampler: Rc<RefCell<StdRng>>, // one per worker
 }
 
 impl<S, B> Service<ServiceRequest> for RequestLoggerMiddleware<S>
@@ -273,6 +326,7 @@ where
 
         let service = Rc::clone(&self.service);
         let config = self.config.clone();
+        let sampler = Rc::clone(&self.sampler);
 
         Box::pin(async move {
             let res = service.call(req).await?;
@@ -309,10 +363,107 @@ where
                 status_category,
             };
 
-            let logger = RequestLogger::new(config);
-            logger.log_request(&log);
+            if config.should_log(&log, &mut *sampler.borrow_mut()) {
+                let logger = RequestLogger::new(config);
+                logger.log_request(&log);
+            }
 
             Ok(res)
         })
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use super::{RequestLog, RequestLogger, RequestLoggerConfig, StatusCategory};
+
+    /// Collects what the tracing subscriber writes
+    #[derive(Clone, Default)]
+    struct Capture(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Capture {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Capture {
+        type Writer = Capture;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn log(status_code: u16) -> RequestLog {
+        RequestLog {
+            timestamp: 0,
+            client_ip: "203.0.113.1".to_string(),
+            method: "GET".to_string(),
+            uri: "/".to_string(),
+            user_agent: None,
+            status_code,
+            response_time_ms: 5,
+            request_size: 0,
+            response_size: 0,
+            status_category: StatusCategory::from_status_code(status_code),
+        }
+    }
+
+    #[test]
+    fn a_seeded_sampler_keeps_about_the_rate_of_successes_and_every_error() {
+        let config = RequestLoggerConfig { sample_rate: 0.25, sample_seed: Some(42), ..Default::default() };
+
+        let mut rng = config.sampler();
+        let decisions: Vec<bool> = (0..2000).map(|_| config.should_log(&log(200), &mut rng)).collect();
+        let logged = decisions.iter().filter(|logged| **logged).count();
+        assert!((400..600).contains(&logged), "{} of 2000 successes logged at 25%", logged);
+
+        // The same seed makes the same choices
+        let mut again = config.sampler();
+        assert!(decisions.iter().all(|logged| config.should_log(&log(200), &mut again) == *logged));
+
+        let mut rng = config.sampler();
+        for status in [400, 404, 429, 500, 503] {
+            assert!((0..100).all(|_| config.should_log(&log(status), &mut rng)), "a {} went unlogged", status);
+        }
+    }
+
+    #[test]
+    fn a_request_over_the_slow_threshold_is_a_warning_marked_alike_everywhere() {
+        std::env::set_var("LOG_SLOW_REQUEST_MS", "100");
+        let path = std::env::temp_dir().join(format!("libretune-slow-{}.log", uuid::Uuid::new_v4().simple()));
+        let config = RequestLoggerConfig {
+            log_to_file: true,
+            log_file_path: path.to_string_lossy().into_owned(),
+            ..RequestLoggerConfig::from_env()
+        };
+        let logger = RequestLogger::new(config);
+
+        let capture = Capture::default();
+        let subscriber = tracing_subscriber::fmt().with_writer(capture.clone()).with_ansi(false).finish();
+        tracing::subscriber::with_default(subscriber, || {
+            logger.log_request(&RequestLog { response_time_ms: 100, ..log(200) });
+            logger.log_request(&RequestLog { response_time_ms: 250, ..log(200) });
+        });
+
+        let console = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = console.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", console);
+        assert!(lines[0].contains(" INFO ") && !lines[0].contains("SLOW"), "{}", lines[0]);
+        assert!(lines[1].contains(" WARN ") && lines[1].contains("SLOW ✅ GET /"), "{}", lines[1]);
+
+        let file = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        let lines: Vec<&str> = file.lines().collect();
+        assert!(!lines[0].contains("SLOW"), "{}", lines[0]);
+        assert!(lines[1].contains("[✅] SLOW GET /"), "{}", lines[1]);
+    }
+}
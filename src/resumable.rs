@@ -0,0 +1,117 @@
+//! Resumable audio uploads, for files too large to send reliably in one go.
+//!
+//! A client starts an upload with the file's name and size, then sends the
+//! bytes in chunks, each a `PATCH` with a `Content-Range` saying where it
+//! goes. Chunks are written at their offsets into a staging file under
+//! `UPLOAD_STAGING_DIR`, so they can arrive in any order and a chunk sent
+//! twice just overwrites itself. Once every byte has arrived the upload is
+//! finalized into a track and the staging file is removed. Files may be up
+//! to `AUDIO_MAX_MB` (default 500), and uploads left unfinished expire
+//! after a day.
+
+use std::env;
+use std::fs::OpenOptions;
+use std::io::{Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use actix_web::web;
+use uuid::Uuid;
+use crate::db::error::Error;
+
+/// Largest chunk accepted in one request
+pub const MAX_CHUNK_BYTES: usize = 16 * 1024 * 1024;
+
+/// Most chunks one upload may take, so tiny chunks can't bloat its record
+pub const MAX_CHUNKS: usize = 10_000;
+
+/// How long an unfinished upload is kept
+pub const UPLOAD_EXPIRY_HOURS: i64 = 24;
+
+/// Audio extensions a track may be uploaded as
+const AUDIO_TYPES: &[&str] = &["mp3", "wav", "flac", "ogg", "opus", "m4a", "aiff"];
+
+/// Largest audio file accepted, in bytes, set in megabytes with `AUDIO_MAX_MB`
+pub fn max_bytes() -> u64 {
+    env::var("AUDIO_MAX_MB")
+        .ok()
+        .and_then(|mb| mb.parse::<u64>().ok())
+        .filter(|mb| *mb > 0)
+        .unwrap_or(500)
+        * 1024 * 1024
+}
+
+/// Directory unfinished uploads are staged in, set with `UPLOAD_STAGING_DIR`
+pub fn staging_dir() -> PathBuf {
+    env::var("UPLOAD_STAGING_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| env::temp_dir().join("libretune-uploads"))
+}
+
+fn staging_path(upload_id: Uuid) -> PathBuf {
+    staging_dir().join(upload_id.to_string())
+}
+
+/// The lowercase extension of an audio file name, if it's an accepted type
+pub fn audio_extension(filename: &str) -> Result<String, Error> {
+    let extension = filename.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+    if !AUDIO_TYPES.contains(&extension.as_str()) {
+        return Err(Error::UnsupportedMedia(format!("audio must be one of: {}", AUDIO_TYPES.join(", "))));
+    }
+    Ok(extension)
+}
+
+/// Parse `Content-Range: bytes <first>-<last>/<size>` into the half-open
+/// range it covers and the file size it claims
+pub fn parse_content_range(value: &str) -> Result<(u64, u64, u64), Error> {
+    let invalid = || Error::Validation("Content-Range must be bytes <first>-<last>/<size>".to_string());
+
+    let (range, size) = value.trim().strip_prefix("bytes ").ok_or_else(invalid)?.split_once('/').ok_or_else(invalid)?;
+    let (first, last) = range.split_once('-').ok_or_else(invalid)?;
+    let first: u64 = first.parse().map_err(|_| invalid())?;
+    let last: u64 = last.parse().map_err(|_| invalid())?;
+    let size: u64 = size.parse().map_err(|_| invalid())?;
+    if last < first || last >= size {
+        return Err(invalid());
+    }
+
+    Ok((first, last + 1, size))
+}
+
+/// Write a chunk into an upload's staging file at `offset`
+pub async fn write_chunk(upload_id: Uuid, offset: u64, bytes: Vec<u8>) -> Result<(), Error> {
+    let path = staging_path(upload_id);
+
+    web::block(move || {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).truncate(false).write(true).open(path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        file.write_all(&bytes)
+    })
+    .await
+    .map_err(|e| Error::Db(e.to_string()))?
+    .map_err(|e| Error::Db(e.to_string()))
+}
+
+/// Read back a complete upload's staging file
+pub async fn read_file(upload_id: Uuid) -> Result<Vec<u8>, Error> {
+    let path = staging_path(upload_id);
+
+    web::block(move || std::fs::read(path))
+        .await
+        .map_err(|e| Error::Db(e.to_string()))?
+        .map_err(|e| Error::Db(e.to_string()))
+}
+
+/// Remove an upload's staging file. A missing file is not an error.
+pub async fn remove_file(upload_id: Uuid) -> Result<(), Error> {
+    let path = staging_path(upload_id);
+
+    web::block(move || match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    })
+    .await
+    .map_err(|e| Error::Db(e.to_string()))?
+    .map_err(|e| Error::Db(e.to_string()))
+}
@@ -0,0 +1,1078 @@
+use actix_web::{delete, get, patch, post, put, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use tracing::info;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+use crate::clock;
+use crate::auth::{self, AdminUser, AuthUser};
+use crate::auth_audit::{self, AuthEvent, Client};
+use crate::config::{self, ConfigIssue, EffectiveSetting};
+use crate::db::announcement::AnnouncementOperations;
+use crate::db::erasure::ErasureOperations;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::audit::AuditOperations;
+use crate::db::feature_flag::FeatureFlagOperations;
+use crate::db::gc::GcOperations;
+use crate::db::notification::NotificationOperations;
+use crate::db::session::SessionOperations;
+use crate::db::track::TrackOperations;
+use crate::db::verification::VerificationOperations;
+use crate::db::report::ReportOperations;
+use crate::db::webhook::WebhookOperations;
+use crate::db::{bounded, Db, UserOperations};
+use crate::import::{ImportOptions, UserImporter};
+use crate::flags;
+use crate::gc;
+use crate::latency;
+use crate::live_moderation;
+use crate::maintenance;
+use crate::ndjson;
+use crate::reconcile::{self, ReconcileReport};
+use crate::types::audit::AuditEntry;
+use crate::types::announcement::{Announcement, AnnouncementPatch, NewAnnouncement};
+use crate::types::erasure::ErasureJob;
+use crate::types::feature_flag::FeatureFlagStatus;
+use crate::types::gc::GcRun;
+use crate::types::id::UserId;
+use crate::types::maintenance::{MaintenanceScope, MaintenanceState};
+use crate::types::notification::NotificationKind;
+use crate::types::import::ImportReport;
+use crate::types::latency::DbLatency;
+use crate::types::pagination::Paginated;
+use crate::types::user::{ModerationSummary, OrphanTrack, Report, ReportStatus, ReportTargetKind, SignupCount, UserSort, UserSummary};
+use crate::types::verification::{self, ReviewVerification, VerificationDecision, VerificationRequest, VerificationStatus};
+use crate::types::webhook::{Webhook, WebhookDelivery};
+use super::webhooks::{self, CreateWebhookRequest, UpdateWebhookRequest};
+use super::{paged, PageParams};
+
+#[derive(Deserialize, IntoParams)]
+pub struct UserSortParams {
+    /// `newest` (the default) or `username`
+    #[serde(default)]
+    pub sort: UserSort,
+}
+
+/// List users, newest first or by username with accents folded, so "Ärni"
+/// sorts next to "Arni". With `Accept: application/x-ndjson` every user is
+/// streamed instead, oldest first, ignoring the paging and sort parameters.
+#[utoipa::path(
+    tag = "admin",
+    params(PageParams, UserSortParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, content(
+            (Paginated<UserSummary> = "application/json"),
+            (UserSummary = "application/x-ndjson"),
+        ), headers(
+            ("X-Total-Count" = u64, description = "Users across all pages, JSON only"),
+            ("Link" = String, description = "URLs of the first, previous, next and last pages, JSON only"),
+        )),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/users")]
+pub async fn list_users(
+    req: HttpRequest,
+    _admin: AdminUser,
+    params: web::Query<PageParams>,
+    sort: web::Query<UserSortParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    if ndjson::wanted(&req) {
+        let db = db.into_inner();
+        return Ok(ndjson::stream(move |limit, offset| {
+            let db = db.clone();
+            async move {
+                let users = UserOperations::new(&db).get_users_oldest_first(limit, offset).await?;
+                Ok(users.into_iter().map(UserSummary::from).collect::<Vec<_>>())
+            }
+        }));
+    }
+    
+    let limit = params.limit.unwrap_or(50);
+    let offset = params.offset.unwrap_or(0);
+    let ops = UserOperations::new(&db);
+    let (users, total) = futures_util::try_join!(
+        bounded("users", ops.get_users(Some(limit), Some(offset), sort.sort)),
+        bounded("user count", ops.count_users()),
+    )?;
+    let users = users.into_iter().map(UserSummary::from).collect();
+    
+    Ok(paged(&req, Paginated::new(users, limit, offset), total))
+}
+
+/// List erasure jobs, newest first. With `Accept: application/x-ndjson` the
+/// whole erasure audit trail is streamed instead, ignoring the paging
+/// parameters.
+#[utoipa::path(
+    tag = "admin",
+    params(PageParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, content(
+            (Vec<ErasureJob> = "application/json"),
+            (ErasureJob = "application/x-ndjson"),
+        )),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/erasures")]
+pub async fn list_erasures(req: HttpRequest, _admin: AdminUser, params: web::Query<PageParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    if ndjson::wanted(&req) {
+        return Ok(ndjson::stream(move |limit, offset| {
+            let db = db.clone();
+            async move { ErasureOperations::new(&db).get_jobs(Some(limit), Some(offset)).await }
+        }));
+    }
+    
+    let jobs = ErasureOperations::new(&db).get_jobs(params.limit, params.offset).await?;
+    Ok(HttpResponse::Ok().json(jobs))
+}
+
+/// List the operator audit log, newest first. With
+/// `Accept: application/x-ndjson` the whole log is streamed instead,
+/// ignoring the paging parameters.
+#[utoipa::path(
+    tag = "admin",
+    params(PageParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, content(
+            (Vec<AuditEntry> = "application/json"),
+            (AuditEntry = "application/x-ndjson"),
+        )),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/audit")]
+pub async fn list_audit_entries(req: HttpRequest, _admin: AdminUser, params: web::Query<PageParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    if ndjson::wanted(&req) {
+        return Ok(ndjson::stream(move |limit, offset| {
+            let db = db.clone();
+            async move { AuditOperations::new(&db).get_entries(limit, offset).await }
+        }));
+    }
+    
+    let entries = AuditOperations::new(&db).get_entries(params.limit.unwrap_or(50), params.offset.unwrap_or(0)).await?;
+    Ok(HttpResponse::Ok().json(entries))
+}
+
+/// Get the progress of a single erasure job
+#[utoipa::path(
+    tag = "admin",
+    params(("job_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = ErasureJob),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/admin/erasures/{job_id}")]
+pub async fn get_erasure(_admin: AdminUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let job = ErasureOperations::new(&db).get_job(path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(job))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LegalHoldRequest {
+    pub legal_hold: bool,
+}
+
+/// Place or lift a legal hold on an account
+#[utoipa::path(
+    tag = "admin",
+    params(("user_id" = Uuid, Path)),
+    request_body = LegalHoldRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[put("/admin/users/{user_id}/legal-hold")]
+pub async fn set_legal_hold(
+    _admin: AdminUser,
+    user_id: UserId,
+    body: web::Json<LegalHoldRequest>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    UserOperations::new(&db).set_legal_hold(user_id, body.legal_hold).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// List global webhooks, which receive events for every user
+#[utoipa::path(
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<Webhook>),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/webhooks")]
+pub async fn list_global_webhooks(_admin: AdminUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let webhooks = WebhookOperations::new(&db).get_webhooks(None).await?;
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+/// Register a global webhook
+#[utoipa::path(
+    tag = "admin",
+    request_body = CreateWebhookRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = Webhook),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[post("/admin/webhooks")]
+pub async fn create_global_webhook(_admin: AdminUser, body: web::Json<CreateWebhookRequest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let CreateWebhookRequest { url, events } = body.into_inner();
+    webhooks::validate_url(&url)?;
+    webhooks::validate_events(&events)?;
+    
+    let webhook = WebhookOperations::new(&db).create_webhook(None, url, auth::generate_token(), events).await?;
+    Ok(HttpResponse::Created().json(webhook))
+}
+
+/// Change a global webhook
+#[utoipa::path(
+    tag = "admin",
+    params(("webhook_id" = Uuid, Path)),
+    request_body = UpdateWebhookRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Webhook),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[patch("/admin/webhooks/{webhook_id}")]
+pub async fn update_global_webhook(
+    _admin: AdminUser,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateWebhookRequest>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let mut webhook = global_webhook(&db, path.into_inner()).await?;
+    webhooks::apply_update(&mut webhook, body.into_inner())?;
+    
+    let webhook = WebhookOperations::new(&db).save_webhook(webhook).await?;
+    Ok(HttpResponse::Ok().json(webhook))
+}
+
+/// Delete a global webhook
+#[utoipa::path(
+    tag = "admin",
+    params(("webhook_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/admin/webhooks/{webhook_id}")]
+pub async fn delete_global_webhook(_admin: AdminUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let webhook = global_webhook(&db, path.into_inner()).await?;
+    WebhookOperations::new(&db).delete_webhook(webhook.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Delivery attempts for a global webhook, newest first
+#[utoipa::path(
+    tag = "admin",
+    params(("webhook_id" = Uuid, Path), PageParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<WebhookDelivery>),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/admin/webhooks/{webhook_id}/deliveries")]
+pub async fn list_global_deliveries(
+    _admin: AdminUser,
+    path: web::Path<Uuid>,
+    params: web::Query<PageParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let webhook = global_webhook(&db, path.into_inner()).await?;
+    let deliveries = WebhookOperations::new(&db).get_deliveries(webhook.id, params.limit, params.offset).await?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
+
+async fn global_webhook(db: &Db, webhook_id: Uuid) -> Result<Webhook, Error> {
+    let webhook = WebhookOperations::new(db).get_webhook(webhook_id).await?;
+    
+    if webhook.owner_id.is_some() {
+        return Err(Error::WebhookNotFound);
+    }
+    
+    Ok(webhook)
+}
+
+/// Longest NDJSON line accepted by imports
+const MAX_IMPORT_LINE_LEN: usize = 64 * 1024;
+
+#[derive(Deserialize, IntoParams)]
+pub struct ImportParams {
+    /// Mark imported emails as already verified
+    #[serde(default)]
+    pub skip_email_verification: bool,
+}
+
+/// Bulk import users from an NDJSON body, one `ImportUserRecord` per line.
+/// Invalid and conflicting rows are reported rather than failing the import.
+#[utoipa::path(
+    tag = "admin",
+    params(ImportParams),
+    request_body(content = String, content_type = "application/x-ndjson"),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = ImportReport),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[post("/admin/import/users")]
+pub async fn import_users(
+    _admin: AdminUser,
+    params: web::Query<ImportParams>,
+    mut payload: web::Payload,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let mut importer = UserImporter::new(Db::clone(&db), ImportOptions {
+        skip_email_verification: params.skip_email_verification,
+    });
+    let mut buffer: Vec<u8> = Vec::new();
+    
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|e| Error::Validation(e.to_string()))?;
+        buffer.extend_from_slice(&chunk);
+        
+        while let Some(newline) = buffer.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = buffer.drain(..=newline).collect();
+            importer.push_line(&String::from_utf8_lossy(&line)).await?;
+        }
+        
+        if buffer.len() > MAX_IMPORT_LINE_LEN {
+            return Err(Error::Validation(format!("lines may be at most {} bytes", MAX_IMPORT_LINE_LEN)));
+        }
+    }
+    importer.push_line(&String::from_utf8_lossy(&buffer)).await?;
+    
+    let report = importer.finish().await?;
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct SignupRangeParams {
+    /// RFC 3339 start of the range, inclusive
+    pub from: DateTime<Utc>,
+    /// RFC 3339 end of the range, inclusive
+    pub to: DateTime<Utc>,
+}
+
+/// Signups per source between two dates
+#[utoipa::path(
+    tag = "admin",
+    params(SignupRangeParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<SignupCount>),
+        (status = 400, description = "from is after to", body = ErrorBody),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/analytics/signups")]
+pub async fn signups_by_source(_admin: AdminUser, params: web::Query<SignupRangeParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let counts = UserOperations::new(&db).signups_by_source(params.from, params.to).await?;
+    
+    Ok(HttpResponse::Ok().json(counts))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ReportQueueParams {
+    /// Only reports against this kind of target
+    pub target: Option<ReportTargetKind>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// The moderation queue, oldest reports first
+#[utoipa::path(
+    tag = "admin",
+    params(ReportQueueParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Paginated<Report>, headers(
+            ("X-Total-Count" = u64, description = "Reports across all pages"),
+            ("Link" = String, description = "URLs of the first, previous, next and last pages"),
+        )),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/reports")]
+pub async fn list_reports(req: HttpRequest, _admin: AdminUser, params: web::Query<ReportQueueParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let limit = params.limit.unwrap_or(50).clamp(1, 100);
+    let offset = params.offset.unwrap_or(0);
+    
+    let ops = ReportOperations::new(&db);
+    let (reports, total) = futures_util::try_join!(
+        bounded("reports", ops.list_reports(params.target, limit, offset)),
+        bounded("report count", ops.count_reports(params.target)),
+    )?;
+    
+    Ok(paged(&req, Paginated::new(reports, limit, offset), total))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetReportStatusRequest {
+    pub status: ReportStatus,
+}
+
+/// Move a report along the queue. Moderators watching the queue live hear
+/// about it.
+#[utoipa::path(
+    tag = "admin",
+    params(("report_id" = Uuid, Path)),
+    request_body = SetReportStatusRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Report),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[put("/admin/reports/{report_id}/status")]
+pub async fn set_report_status(
+    _admin: AdminUser,
+    path: web::Path<Uuid>,
+    body: web::Json<SetReportStatusRequest>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let report = ReportOperations::new(&db).set_status(path.into_inner(), body.status).await?;
+    
+    Ok(HttpResponse::Ok().json(report))
+}
+
+/// The moderation queue at a glance: open reports, the targets with the
+/// most of them, and how many moderators are watching live
+#[utoipa::path(
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = ModerationSummary),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/reports/summary")]
+pub async fn moderation_summary(_admin: AdminUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let ops = ReportOperations::new(&db);
+    let (open_reports, targets) = futures_util::try_join!(
+        bounded("open report count", ops.count_open()),
+        bounded("most reported targets", ops.most_reported_targets(50)),
+    )?;
+    
+    Ok(HttpResponse::Ok().json(ModerationSummary {
+        open_reports,
+        targets,
+        connected_moderators: live_moderation::connected_moderators(),
+    }))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct ReconcileParams {
+    /// Counter to reconcile, all of them when absent
+    pub counter: Option<String>,
+}
+
+/// Recount denormalized counters now and fix any that drifted
+#[utoipa::path(
+    tag = "admin",
+    params(ReconcileParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<ReconcileReport>),
+        (status = 400, description = "Unknown counter", body = ErrorBody),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[post("/admin/reconcile")]
+pub async fn reconcile_counters(_admin: AdminUser, params: web::Query<ReconcileParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let reports = match &params.counter {
+        Some(name) => vec![reconcile::reconcile_named(&db, name).await?],
+        None => reconcile::reconcile_all(&db).await?,
+    };
+    
+    Ok(HttpResponse::Ok().json(reports))
+}
+
+/// Every feature flag: stored ones plus those still at their default
+#[utoipa::path(
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<FeatureFlagStatus>),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/flags")]
+pub async fn list_flags(_admin: AdminUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let stored = FeatureFlagOperations::new(&db).list_flags().await?;
+    
+    let mut statuses: Vec<FeatureFlagStatus> = stored
+        .into_iter()
+        .map(|flag| FeatureFlagStatus {
+            env_override: flags::override_for(&flag.name),
+            name: flag.name,
+            enabled: flag.enabled,
+            rollout_percentage: flag.rollout_percentage,
+            updated_at: Some(flag.updated_at),
+        })
+        .collect();
+    for (name, default) in flags::KNOWN {
+        if !statuses.iter().any(|status| status.name == *name) {
+            statuses.push(FeatureFlagStatus {
+                name: name.to_string(),
+                enabled: *default,
+                rollout_percentage: None,
+                env_override: flags::override_for(name),
+                updated_at: None,
+            });
+        }
+    }
+    statuses.sort_by(|a, b| a.name.cmp(&b.name));
+    
+    Ok(HttpResponse::Ok().json(statuses))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetFlagRequest {
+    pub enabled: bool,
+    /// Share of signed-in users to turn it on for, 0 to 100. Everyone when absent.
+    pub rollout_percentage: Option<u8>,
+}
+
+/// Turn a feature flag on or off, or roll it out to a percentage of users.
+/// Takes effect immediately on this instance and within a refresh interval
+/// on the others.
+#[utoipa::path(
+    tag = "admin",
+    params(("name" = String, Path)),
+    request_body = SetFlagRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = FeatureFlagStatus),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[put("/admin/flags/{name}")]
+pub async fn set_flag(_admin: AdminUser, path: web::Path<String>, body: web::Json<SetFlagRequest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let name = path.into_inner();
+    let SetFlagRequest { enabled, rollout_percentage } = body.into_inner();
+    
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+        return Err(Error::Validation("flag names may only contain a-z, 0-9 and '_'".to_string()));
+    }
+    if rollout_percentage.is_some_and(|percentage| percentage > 100) {
+        return Err(Error::Validation("rollout_percentage must be between 0 and 100".to_string()));
+    }
+    
+    let flag = FeatureFlagOperations::new(&db).set_flag(name, enabled, rollout_percentage).await?;
+    flags::refresh(&db).await?;
+    
+    Ok(HttpResponse::Ok().json(FeatureFlagStatus {
+        env_override: flags::override_for(&flag.name),
+        name: flag.name,
+        enabled: flag.enabled,
+        rollout_percentage: flag.rollout_percentage,
+        updated_at: Some(flag.updated_at),
+    }))
+}
+
+/// Tracks left in playlists after their uploader was hard-deleted
+#[utoipa::path(
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<OrphanTrack>),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/orphans/tracks")]
+pub async fn list_orphan_tracks(_admin: AdminUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let orphans = TrackOperations::new(&db).find_orphans().await?;
+    Ok(HttpResponse::Ok().json(orphans))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct OrphanCleanupParams {
+    /// Remove orphans from playlists instead of marking them deleted
+    #[serde(default)]
+    pub hard: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OrphanCleanupResult {
+    pub cleaned: usize,
+}
+
+/// Soft- or hard-delete every orphaned track
+#[utoipa::path(
+    tag = "admin",
+    params(OrphanCleanupParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = OrphanCleanupResult),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[post("/admin/orphans/tracks/cleanup")]
+pub async fn cleanup_orphan_tracks(_admin: AdminUser, params: web::Query<OrphanCleanupParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let cleaned = TrackOperations::new(&db).cleanup_orphans(params.hard).await?;
+    info!("Cleaned up {} orphaned track(s), hard = {}", cleaned, params.hard);
+    
+    Ok(HttpResponse::Ok().json(OrphanCleanupResult { cleaned }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct SetMaintenanceRequest {
+    pub active: bool,
+    /// Turn away only writes, the default, or every request
+    #[serde(default)]
+    pub scope: MaintenanceScope,
+    /// Banner shown to clients and in 503 responses
+    pub message: Option<String>,
+    /// Seconds clients should wait before retrying a write
+    pub retry_after_secs: Option<u64>,
+}
+
+/// Turn maintenance mode on or off. Non-admin writes, or with the `all`
+/// scope every non-admin request, get 503 while it's on.
+#[utoipa::path(
+    tag = "admin",
+    request_body = SetMaintenanceRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = MaintenanceState),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[post("/admin/maintenance")]
+pub async fn set_maintenance(AdminUser(admin): AdminUser, body: web::Json<SetMaintenanceRequest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let SetMaintenanceRequest { active, scope, message, retry_after_secs } = body.into_inner();
+    
+    let state = MaintenanceState {
+        active,
+        scope,
+        message: message.map(|m| m.trim().to_string()).filter(|m| !m.is_empty()),
+        retry_after_secs: retry_after_secs.unwrap_or(maintenance::DEFAULT_RETRY_AFTER_SECS),
+        changed_by: Some(admin.id),
+        changed_at: clock::now(),
+    };
+    maintenance::set(&db, state.clone()).await?;
+    
+    let action = if active { "maintenance.enabled" } else { "maintenance.disabled" };
+    AuditOperations::new(&db).record(Some(admin.id), action, serde_json::to_value(&state)?).await?;
+    info!("{} set by admin {}", action, admin.id);
+    
+    Ok(HttpResponse::Ok().json(state))
+}
+
+/// Every announcement, including scheduled and expired ones
+#[utoipa::path(
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<Announcement>),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/announcements")]
+pub async fn list_announcements(_admin: AdminUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let announcements = AnnouncementOperations::new(&db).list_announcements().await?;
+    Ok(HttpResponse::Ok().json(announcements))
+}
+
+/// Post a site-wide announcement. The body is markdown and is sanitized
+/// before it's stored.
+#[utoipa::path(
+    tag = "admin",
+    request_body = NewAnnouncement,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = Announcement),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[post("/admin/announcements")]
+pub async fn create_announcement(AdminUser(admin): AdminUser, body: web::Json<NewAnnouncement>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let announcement = body.into_inner().into_announcement(admin.id)?;
+    
+    let announcement = AnnouncementOperations::new(&db).create_announcement(announcement).await?;
+    Ok(HttpResponse::Created().json(announcement))
+}
+
+/// Change an announcement
+#[utoipa::path(
+    tag = "admin",
+    params(("announcement_id" = Uuid, Path)),
+    request_body = AnnouncementPatch,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Announcement),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[patch("/admin/announcements/{announcement_id}")]
+pub async fn update_announcement(
+    _admin: AdminUser,
+    path: web::Path<Uuid>,
+    body: web::Json<AnnouncementPatch>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let mut announcement = AnnouncementOperations::new(&db).get_announcement(path.into_inner()).await?;
+    body.into_inner().apply(&mut announcement)?;
+    
+    let announcement = AnnouncementOperations::new(&db).save_announcement(announcement).await?;
+    Ok(HttpResponse::Ok().json(announcement))
+}
+
+/// Delete an announcement
+#[utoipa::path(
+    tag = "admin",
+    params(("announcement_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/admin/announcements/{announcement_id}")]
+pub async fn delete_announcement(_admin: AdminUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let announcement = AnnouncementOperations::new(&db).get_announcement(path.into_inner()).await?;
+    AnnouncementOperations::new(&db).delete_announcement(announcement.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct ImpersonateRequest {
+    /// The admin's own password, asked for again before taking over an account
+    pub password: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImpersonationResponse {
+    pub token: String,
+    pub user_id: UserId,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Get a short-lived session acting as another user, for reproducing their
+/// bugs. Requires the admin's password. Writes made with it are audited
+/// under the admin, account erasure refuses it, and the user is notified.
+/// Admins and banned users can't be impersonated.
+#[utoipa::path(
+    tag = "admin",
+    params(("user_id" = Uuid, Path)),
+    request_body = ImpersonateRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = ImpersonationResponse),
+        (status = 401, description = "Wrong password", body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("/admin/impersonate/{user_id}")]
+pub async fn impersonate(
+    req: HttpRequest,
+    AdminUser(admin): AdminUser,
+    user_id: UserId,
+    body: web::Json<ImpersonateRequest>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let client = Client::from_request(&req);
+    if !auth::verify_password(&body.password, &admin.hashed_password) {
+        auth_audit::record(AuthEvent::StepUpFailed, Some(admin.id), None, &client);
+        return Err(Error::InvalidCredentials);
+    }
+    
+    let target = UserOperations::new(&db).get_user_by_id(user_id).await?;
+    let off_limits = target.profile.as_ref().is_some_and(|p| p.is_admin || p.is_banned());
+    if off_limits {
+        return Err(Error::Forbidden);
+    }
+    
+    let (session, token) = SessionOperations::new(&db).create_impersonation_session(target.id, admin.id).await?;
+    
+    let detail = serde_json::json!({ "user_id": target.id, "session_id": session.id, "expires_at": session.expires_at });
+    AuditOperations::new(&db).record(Some(admin.id), "impersonation.started", detail).await?;
+    NotificationOperations::new(&db).notify(
+        target.id,
+        NotificationKind::AccountAccessed,
+        "Support accessed your account to investigate an issue".to_string(),
+        serde_json::json!({ "expires_at": session.expires_at }),
+    ).await?;
+    info!("admin {} started impersonating user {}", admin.id, target.id);
+    auth_audit::record(AuthEvent::ImpersonationStarted, Some(admin.id), None, &client);
+    
+    Ok(HttpResponse::Ok().json(ImpersonationResponse {
+        token,
+        user_id: target.id,
+        expires_at: session.expires_at,
+    }))
+}
+
+/// End impersonation early. Sent with the impersonation token it ends that
+/// session; sent by an admin with their own token it ends every session
+/// they started.
+#[utoipa::path(
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[delete("/admin/impersonate")]
+pub async fn end_impersonation(req: HttpRequest, auth: AuthUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let is_admin = auth.user.profile.as_ref().is_some_and(|p| p.is_admin);
+    
+    let (admin_id, ended) = match auth.impersonator() {
+        Some(admin_id) => {
+            SessionOperations::new(&db).delete_session(auth.session.id).await?;
+            (admin_id, 1)
+        }
+        None if is_admin => (auth.user.id, SessionOperations::new(&db).delete_impersonation_sessions(auth.user.id).await?),
+        None => return Err(Error::Forbidden),
+    };
+    
+    AuditOperations::new(&db).record(Some(admin_id), "impersonation.ended", serde_json::json!({ "sessions": ended })).await?;
+    auth_audit::record(AuthEvent::ImpersonationEnded, Some(admin_id), None, &Client::from_request(&req));
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ConfigReport {
+    pub settings: Vec<EffectiveSetting>,
+    pub issues: Vec<ConfigIssue>,
+}
+
+/// Database round-trip latency as this instance's background probe sees it
+#[utoipa::path(
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = DbLatency),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/latency")]
+pub async fn get_latency(_admin: AdminUser) -> HttpResponse {
+    HttpResponse::Ok().json(latency::current())
+}
+
+/// The configuration this process loaded, secrets masked, with any problems
+/// found in it
+#[utoipa::path(
+    tag = "admin",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = ConfigReport),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/config")]
+pub async fn get_config(_admin: AdminUser) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(ConfigReport {
+            settings: config::effective(),
+            issues: config::check(),
+        })
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct GcParams {
+    /// Report what would be deleted without deleting anything
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// Delete stored media nothing in the database refers to. Objects newer
+/// than the safety window are kept, and each run deletes at most a capped
+/// number of objects.
+#[utoipa::path(
+    tag = "admin",
+    params(GcParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = GcRun),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[post("/admin/gc/run")]
+pub async fn run_gc(AdminUser(admin): AdminUser, params: web::Query<GcParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let run = gc::run(&db, params.dry_run).await?;
+    
+    if !run.dry_run {
+        let detail = serde_json::json!({ "run_id": run.id, "deleted": run.deleted.len() });
+        AuditOperations::new(&db).record(Some(admin.id), "gc.run", detail).await?;
+    }
+    
+    Ok(HttpResponse::Ok().json(run))
+}
+
+/// Reports of past garbage collection runs, newest first
+#[utoipa::path(
+    tag = "admin",
+    params(PageParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<GcRun>),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/gc/runs")]
+pub async fn list_gc_runs(_admin: AdminUser, params: web::Query<PageParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let limit = params.limit.unwrap_or(20).clamp(1, 100);
+    let offset = params.offset.unwrap_or(0);
+    
+    let runs = GcOperations::new(&db).get_runs(limit, offset).await?;
+    Ok(HttpResponse::Ok().json(runs))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct VerificationQueueParams {
+    /// Only requests with this status, all of them when absent
+    pub status: Option<VerificationStatus>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// The verification queue, oldest requests first
+#[utoipa::path(
+    tag = "admin",
+    params(VerificationQueueParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Paginated<VerificationRequest>, headers(
+            ("X-Total-Count" = u64, description = "Requests across all pages"),
+            ("Link" = String, description = "URLs of the first, previous, next and last pages"),
+        )),
+        (status = 403, body = ErrorBody),
+    )
+)]
+#[get("/admin/verification")]
+pub async fn list_verification_requests(
+    req: HttpRequest,
+    _admin: AdminUser,
+    params: web::Query<VerificationQueueParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let limit = params.limit.unwrap_or(50).clamp(1, 100);
+    let offset = params.offset.unwrap_or(0);
+    
+    let ops = VerificationOperations::new(&db);
+    let (requests, total) = futures_util::try_join!(
+        bounded("verification requests", ops.list_requests(params.status, limit, offset)),
+        bounded("verification request count", ops.count_requests(params.status)),
+    )?;
+    
+    Ok(paged(&req, Paginated::new(requests, limit, offset), total))
+}
+
+/// Approve or reject a pending verification request. The user is notified
+/// either way, with the note as the reason.
+#[utoipa::path(
+    tag = "admin",
+    params(("request_id" = Uuid, Path)),
+    request_body = ReviewVerification,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = VerificationRequest),
+        (status = 400, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Already reviewed", body = ErrorBody),
+    )
+)]
+#[post("/admin/verification/{request_id}/review")]
+pub async fn review_verification(
+    AdminUser(admin): AdminUser,
+    path: web::Path<Uuid>,
+    body: web::Json<ReviewVerification>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let body = body.into_inner();
+    let approve = body.decision == VerificationDecision::Approve;
+    let note = verification::clean_note(body.note)?;
+    
+    let request = VerificationOperations::new(&db).review(path.into_inner(), approve, admin.id, note).await?;
+    
+    let (action, kind, message) = if approve {
+        ("verification.approved", NotificationKind::VerificationApproved, "Your account is now verified")
+    } else {
+        ("verification.rejected", NotificationKind::VerificationRejected, "Your verification request was not approved")
+    };
+    let detail = serde_json::json!({ "request_id": request.id, "user_id": request.user_id, "note": request.note });
+    AuditOperations::new(&db).record(Some(admin.id), action, detail.clone()).await?;
+    NotificationOperations::new(&db).notify(request.user_id, kind, message.to_string(), detail).await?;
+    
+    Ok(HttpResponse::Ok().json(request))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct RevokeVerificationParams {
+    /// Reason shown to the user
+    pub note: Option<String>,
+}
+
+/// Take the verified badge away from a user
+#[utoipa::path(
+    tag = "admin",
+    params(("user_id" = Uuid, Path), RevokeVerificationParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 400, description = "User is not verified", body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/admin/users/{user_id}/verification")]
+pub async fn revoke_verification(
+    AdminUser(admin): AdminUser,
+    user_id: UserId,
+    params: web::Query<RevokeVerificationParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let note = verification::clean_note(params.into_inner().note)?;
+    
+    VerificationOperations::new(&db).revoke(user_id, admin.id, note.clone()).await?;
+    
+    let detail = serde_json::json!({ "user_id": user_id, "note": note });
+    AuditOperations::new(&db).record(Some(admin.id), "verification.revoked", detail.clone()).await?;
+    NotificationOperations::new(&db).notify(
+        user_id,
+        NotificationKind::VerificationRevoked,
+        "Your account is no longer verified".to_string(),
+        detail,
+    ).await?;
+    
+    Ok(HttpResponse::NoContent().finish())
+}
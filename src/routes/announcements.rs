@@ -0,0 +1,44 @@
+use actix_web::{get, post, web, HttpResponse};
+use uuid::Uuid;
+use crate::auth::AuthUser;
+use crate::db::announcement::AnnouncementOperations;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::Db;
+use crate::types::announcement::{Announcement, Audience};
+
+/// Announcements currently in their time window for the caller's audience,
+/// newest first. Signed-in callers don't see the ones they dismissed.
+#[utoipa::path(
+    tag = "announcements",
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<Announcement>),
+    )
+)]
+#[get("/announcements/active")]
+pub async fn active_announcements(auth: Option<AuthUser>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = auth.map(|auth| auth.user);
+    let audiences = Audience::for_user(user.as_ref());
+    
+    let announcements = AnnouncementOperations::new(&db).get_active(&audiences, user.map(|user| user.id)).await?;
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "private, no-store"))
+        .json(announcements))
+}
+
+/// Stop showing an announcement to the caller
+#[utoipa::path(
+    tag = "announcements",
+    params(("announcement_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("/announcements/{announcement_id}/dismiss")]
+pub async fn dismiss_announcement(auth: AuthUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    AnnouncementOperations::new(&db).dismiss(path.into_inner(), auth.user.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -0,0 +1,204 @@
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
+use crate::auth::{hash_password, validate_password, verify_dummy_password, verify_password, AuthUser};
+use crate::auth_audit::{self, AuthEvent, Client};
+use crate::db::email_token::EmailTokenOperations;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::session::SessionOperations;
+use crate::db::{Db, UserOperations};
+use crate::email;
+use crate::types::email_token::{EmailTokenPurpose, ForgotPassword, ResetPassword, VerifyEmail};
+use crate::types::id::UserId;
+use crate::types::user::{CreatedVia, NewUser, PublicUser, UserProfile};
+
+/// Create an account. Sign in with `/auth/login` afterwards.
+#[utoipa::path(
+    tag = "auth",
+    request_body = NewUser,
+    responses(
+        (status = 201, body = PublicUser),
+        (status = 400, description = "Unacceptable username or password", body = ErrorBody),
+        (status = 409, description = "The username or email is taken", body = ErrorBody),
+    )
+)]
+#[post("/auth/register")]
+pub async fn register(body: web::Json<NewUser>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let users = UserOperations::new(&db);
+    let user = users.create_user(body.into_inner().into_input(CreatedVia::Web)?).await?;
+    let user = users.update_profile(user.id, UserProfile::new(user.username.clone())).await?;
+
+    Ok(HttpResponse::Created().json(PublicUser::from(user)))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LoginRequest {
+    pub email: String,
+    pub password: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LoginResponse {
+    pub token: String,
+    pub user_id: UserId,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Exchange an email and password for a bearer token
+#[utoipa::path(
+    tag = "auth",
+    request_body = LoginRequest,
+    responses(
+        (status = 200, body = LoginResponse),
+        (status = 401, description = "Invalid credentials", body = ErrorBody),
+    )
+)]
+#[post("/auth/login")]
+pub async fn login(req: HttpRequest, body: web::Json<LoginRequest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let LoginRequest { email, password } = body.into_inner();
+    let client = Client::from_request(&req);
+
+    // Unknown emails and wrong passwords take the same time and get the same
+    // response, so neither reveals whether an email is registered
+    let user = match UserOperations::new(&db).get_user_by_email(email.clone()).await {
+        Ok(user) => user,
+        Err(Error::UserNotFound) => {
+            verify_dummy_password(&password);
+            auth_audit::record(AuthEvent::LoginFailed, None, Some(&email), &client);
+            return Err(Error::InvalidCredentials);
+        }
+        Err(e) => return Err(e),
+    };
+
+    if !verify_password(&password, &user.hashed_password) {
+        auth_audit::record(AuthEvent::LoginFailed, Some(user.id), Some(&email), &client);
+        return Err(Error::InvalidCredentials);
+    }
+
+    let (session, token) = SessionOperations::new(&db).create_session(user.id).await?;
+    UserOperations::new(&db).update_last_login(user.id).await?;
+    auth_audit::record(AuthEvent::LoginSucceeded, Some(user.id), None, &client);
+
+    Ok(HttpResponse::Ok().json(LoginResponse {
+        token,
+        user_id: user.id,
+        expires_at: session.expires_at,
+    }))
+}
+
+/// Email the caller a link to confirm their address. Does nothing when
+/// it's already confirmed.
+#[utoipa::path(
+    tag = "auth",
+    security(("bearer" = [])),
+    responses(
+        (status = 202, description = "The email is on its way"),
+        (status = 204, description = "The address is already confirmed"),
+        (status = 401, body = ErrorBody),
+        (status = 429, description = "An email was sent less than a minute ago", body = ErrorBody),
+    )
+)]
+#[post("/auth/verify-email/send")]
+pub async fn send_verification_email(auth: AuthUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = auth.user;
+    if user.email_verified {
+        return Ok(HttpResponse::NoContent().finish());
+    }
+
+    let token = EmailTokenOperations::new(&db).issue(user.id, &user.email, EmailTokenPurpose::VerifyEmail).await?;
+    email::send(email::verification(&user.email, &user.username, &token));
+
+    Ok(HttpResponse::Accepted().finish())
+}
+
+/// Confirm an email address with the token from the verification email
+#[utoipa::path(
+    tag = "auth",
+    request_body = VerifyEmail,
+    responses(
+        (status = 204),
+        (status = 400, description = "Invalid, expired or used token", body = ErrorBody),
+    )
+)]
+#[post("/auth/verify-email")]
+pub async fn verify_email(body: web::Json<VerifyEmail>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let token = EmailTokenOperations::new(&db).redeem(&body.token, EmailTokenPurpose::VerifyEmail).await?;
+    let user = UserOperations::new(&db).get_user_by_id(token.user_id).await?;
+
+    // A token only vouches for the address it was sent to
+    if !user.email.eq_ignore_ascii_case(&token.email) {
+        return Err(Error::Validation("invalid or expired token".to_string()));
+    }
+
+    UserOperations::new(&db).verify_email(user.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Email a password reset link to an account's address. Always answers 200,
+/// whether or not the address is registered, so it can't be used to find
+/// out. The link works once, for an hour.
+#[utoipa::path(
+    tag = "auth",
+    request_body = ForgotPassword,
+    responses(
+        (status = 200, description = "If the address is registered, a reset link is on its way"),
+    )
+)]
+#[post("/auth/forgot-password")]
+pub async fn forgot_password(req: HttpRequest, body: web::Json<ForgotPassword>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let email = body.into_inner().email;
+    let client = Client::from_request(&req);
+
+    // Looked up and sent after responding, so timing doesn't give it away
+    actix_web::rt::spawn(async move {
+        let user = match UserOperations::new(&db).get_user_by_email(email.clone()).await {
+            Ok(user) if user.profile.as_ref().is_none_or(|p| !p.is_deleted()) => user,
+            Ok(_) | Err(Error::UserNotFound) => return,
+            Err(e) => {
+                warn!("Failed to look up password reset for {}: {}", email, e);
+                return;
+            }
+        };
+        match EmailTokenOperations::new(&db).issue(user.id, &user.email, EmailTokenPurpose::ResetPassword).await {
+            Ok(token) => {
+                auth_audit::record(AuthEvent::PasswordResetRequested, Some(user.id), None, &client);
+                email::send(email::password_reset(&user.email, &user.username, &token));
+            }
+            // The last link is still on its way
+            Err(Error::TooManyRequests(_)) => {}
+            Err(e) => warn!("Failed to issue password reset for user {}: {}", user.id, e),
+        }
+    });
+
+    Ok(HttpResponse::Ok().finish())
+}
+
+/// Set a new password with the token from a reset email. Every session of
+/// the account is signed out.
+#[utoipa::path(
+    tag = "auth",
+    request_body = ResetPassword,
+    responses(
+        (status = 204),
+        (status = 400, description = "Invalid, expired or used token, or an unacceptable password", body = ErrorBody),
+    )
+)]
+#[post("/auth/reset-password")]
+pub async fn reset_password(req: HttpRequest, body: web::Json<ResetPassword>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let ResetPassword { token, new_password } = body.into_inner();
+    validate_password(&new_password)?;
+
+    let token = EmailTokenOperations::new(&db).redeem(&token, EmailTokenPurpose::ResetPassword).await?;
+    let user = UserOperations::new(&db).get_user_by_id(token.user_id).await?;
+    if !user.email.eq_ignore_ascii_case(&token.email) {
+        return Err(Error::Validation("invalid or expired token".to_string()));
+    }
+
+    UserOperations::new(&db).update_password(user.id, hash_password(&new_password)?).await?;
+    SessionOperations::new(&db).delete_sessions_for_user(user.id).await?;
+    auth_audit::record(AuthEvent::PasswordReset, Some(user.id), None, &Client::from_request(&req));
+
+    Ok(HttpResponse::NoContent().finish())
+}
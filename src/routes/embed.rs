@@ -0,0 +1,105 @@
+use actix_web::{get, http::header, web, HttpResponse};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use crate::config;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::playlist::PlaylistOperations;
+use crate::db::track::TrackOperations;
+use crate::db::Db;
+use crate::embed::{self, EmbedTarget, OEmbed};
+use crate::types::id::{PlaylistId, TrackId};
+
+#[derive(Deserialize, IntoParams)]
+pub struct OEmbedParams {
+    pub url: String,
+    pub format: Option<String>,
+    pub maxwidth: Option<u32>,
+    pub maxheight: Option<u32>,
+}
+
+/// Resolve a track or playlist URL into an oEmbed rich response
+#[utoipa::path(
+    tag = "embed",
+    params(OEmbedParams),
+    responses(
+        (status = 200, body = OEmbed),
+        (status = 404, body = ErrorBody),
+        (status = 501, description = "Only the json format is supported", body = ErrorBody),
+    )
+)]
+#[get("/oembed", wrap = "embed::cors()")]
+pub async fn oembed(params: web::Query<OEmbedParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    if params.format.as_deref().is_some_and(|format| format != "json") {
+        return Ok(HttpResponse::NotImplemented().json(ErrorBody::new("Only the json format is supported")));
+    }
+    
+    let base_url = config::public_url();
+    let target = EmbedTarget::parse(&params.url, &base_url)
+        .ok_or(Error::Validation("url is not an embeddable track or playlist".to_string()))?;
+    
+    let response = match target {
+        EmbedTarget::Track(track_id) => {
+            let (owner, track) = TrackOperations::new(&db).get_track_with_owner(track_id.into()).await?;
+            if !track.is_public || track.is_deleted {
+                return Err(Error::TrackNotFound);
+            }
+            OEmbed::new(target, &track.title, &owner, track.cover_image_url, &base_url, params.maxwidth, params.maxheight)
+        }
+        EmbedTarget::Playlist(playlist_id) => {
+            let (owner, playlist) = PlaylistOperations::new(&db).get_playlist(playlist_id.into()).await?;
+            if !playlist.is_public || playlist.is_deleted {
+                return Err(Error::PlaylistNotFound);
+            }
+            OEmbed::new(target, &playlist.name, &owner, playlist.cover_image_url, &base_url, params.maxwidth, params.maxheight)
+        }
+    };
+    
+    Ok(HttpResponse::Ok().json(response))
+}
+
+/// Embed pages are meant to be framed by any site and never rely on cookies
+fn embed_response(body: String) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .insert_header((header::CONTENT_SECURITY_POLICY, "frame-ancestors *"))
+        .insert_header((header::CACHE_CONTROL, "public, max-age=300"))
+        .body(body)
+}
+
+/// Minimal player page for a public track
+#[utoipa::path(
+    tag = "embed",
+    params(("track_id" = Uuid, Path)),
+    responses(
+        (status = 200, content_type = "text/html", body = String),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/embed/tracks/{track_id}", wrap = "embed::cors()")]
+pub async fn embed_track(track_id: TrackId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    if !track.is_public || track.is_deleted {
+        return Err(Error::TrackNotFound);
+    }
+    
+    Ok(embed_response(embed::render_track(&track, &config::public_url())))
+}
+
+/// Minimal player page for a public playlist
+#[utoipa::path(
+    tag = "embed",
+    params(("playlist_id" = Uuid, Path)),
+    responses(
+        (status = 200, content_type = "text/html", body = String),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/embed/playlists/{playlist_id}", wrap = "embed::cors()")]
+pub async fn embed_playlist(playlist_id: PlaylistId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let (_, playlist) = PlaylistOperations::new(&db).get_playlist(playlist_id).await?;
+    if !playlist.is_public || playlist.is_deleted {
+        return Err(Error::PlaylistNotFound);
+    }
+    
+    Ok(embed_response(embed::render_playlist(&playlist, &config::public_url())))
+}
@@ -0,0 +1,177 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+use actix_web::http::header;
+use actix_web::web::Bytes;
+use actix_web::{get, web, FromRequest, HttpRequest, HttpResponse};
+use futures_util::stream;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{interval_at, Instant, Interval};
+use crate::auth::AuthUser;
+use crate::clock;
+use crate::connection_limit::{self, ConnectionPermit};
+use crate::db::error::{Error, ErrorBody};
+use crate::db::notification::NotificationOperations;
+use crate::db::Db;
+use crate::live_moderation;
+use crate::realtime::{self, Event, Registration};
+use crate::types::realtime::{Channel, ServerMessage, StreamParams};
+
+/// How often a comment is sent on a quiet stream, so proxies keep it open
+const HEARTBEAT: Duration = Duration::from_secs(25);
+
+/// Server-sent events of the caller's notifications, unread counts and
+/// upload processing updates, the same payloads `GET /ws` sends with the
+/// `type` as the event name. Starts with `ready`. Reconnecting with
+/// `Last-Event-ID` replays what was missed in the last five minutes, or
+/// sends `resync` when that's no longer kept.
+#[utoipa::path(
+    tag = "notifications",
+    params(
+        StreamParams,
+        ("Last-Event-ID" = Option<u64>, Header, description = "Id of the last event received"),
+    ),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, content_type = "text/event-stream", body = ServerMessage),
+        (status = 401, body = ErrorBody),
+        (status = 429, description = "Too many open streams", body = ErrorBody),
+    )
+)]
+#[get("/notifications/stream")]
+pub async fn notification_stream(req: HttpRequest, params: web::Query<StreamParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    open(&db, &req, params.into_inner(), Channel::Notifications).await
+}
+
+/// Server-sent events of tracks published by people the caller follows,
+/// as `feed_track` events. Resumes with `Last-Event-ID` like
+/// `GET /notifications/stream`.
+#[utoipa::path(
+    tag = "notifications",
+    params(
+        StreamParams,
+        ("Last-Event-ID" = Option<u64>, Header, description = "Id of the last event received"),
+    ),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, content_type = "text/event-stream", body = ServerMessage),
+        (status = 401, body = ErrorBody),
+        (status = 429, description = "Too many open streams", body = ErrorBody),
+    )
+)]
+#[get("/feed/stream")]
+pub async fn feed_stream(req: HttpRequest, params: web::Query<StreamParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    open(&db, &req, params.into_inner(), Channel::Feed).await
+}
+
+/// Server-sent events of the report queue's changes for moderators:
+/// `report_created`, `report_status_changed` and
+/// `report_threshold_reached`. The stream ends with `moderation_revoked` if
+/// the caller stops being a moderator. Nothing missed is replayed; reload
+/// `GET /admin/reports` after reconnecting.
+#[utoipa::path(
+    tag = "admin",
+    params(StreamParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, content_type = "text/event-stream", body = ServerMessage),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 429, description = "Too many open streams", body = ErrorBody),
+    )
+)]
+#[get("/admin/reports/stream")]
+pub async fn moderation_stream(req: HttpRequest, params: web::Query<StreamParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    open(&db, &req, params.into_inner(), Channel::Moderation).await
+}
+
+async fn open(db: &Db, req: &HttpRequest, params: StreamParams, channel: Channel) -> Result<HttpResponse, Error> {
+    let auth = match params.token.as_deref() {
+        Some(token) => AuthUser::from_token(db, token).await?,
+        None => AuthUser::extract(req).await?,
+    };
+    // Same rule as `AdminUser`, with bans and deletions on top since the
+    // stream outlives the request
+    if channel == Channel::Moderation && (auth.impersonator().is_some() || !live_moderation::is_moderator(&auth.user)) {
+        return Err(Error::Forbidden);
+    }
+    let resume_after = req.headers()
+        .get("Last-Event-ID")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .or(params.last_event_id);
+
+    // Forwarding headers are whatever the client says, so go by the socket
+    let ip = req.connection_info().peer_addr().unwrap_or("unknown").to_string();
+    let permit = connection_limit::acquire(&ip).map_err(|rejection| Error::TooManyRequests(rejection.reason().to_string()))?;
+    let opened = realtime::register(auth.user.id, Some(channel), resume_after)
+        .ok_or_else(|| Error::TooManyRequests("too many connections for this account".to_string()))?;
+
+    let mut backlog = VecDeque::new();
+    if opened.gap {
+        backlog.push_back(Event { id: None, message: ServerMessage::Resync });
+    }
+    if channel == Channel::Notifications {
+        let unread_count = NotificationOperations::new(db).count_unread(auth.user.id).await?;
+        let ready = ServerMessage::Ready { user_id: auth.user.id, unread_count, missed_count: None, server_time: clock::now() };
+        backlog.push_back(Event { id: None, message: ready });
+    }
+    backlog.extend(opened.missed);
+    let moderation = (channel == Channel::Moderation)
+        .then(|| live_moderation::subscribe(db, auth.user.id, opened.registration.sender()));
+
+    let state = Stream {
+        backlog,
+        events: opened.events,
+        heartbeat: interval_at(Instant::now() + HEARTBEAT, HEARTBEAT),
+        ended: false,
+        _moderation: moderation,
+        _registration: opened.registration,
+        _permit: permit,
+    };
+
+    Ok(HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .insert_header(("X-Accel-Buffering", "no"))
+        .streaming(stream::unfold(state, next_frame)))
+}
+
+/// One open event stream. Dropped when the client goes away, which
+/// unregisters it and frees its connection slot.
+struct Stream {
+    backlog: VecDeque<Event>,
+    events: UnboundedReceiver<Event>,
+    heartbeat: Interval,
+    /// Set once the last event has been sent
+    ended: bool,
+    _moderation: Option<live_moderation::Subscription>,
+    _registration: Registration,
+    _permit: ConnectionPermit,
+}
+
+async fn next_frame(mut state: Stream) -> Option<(Result<Bytes, Error>, Stream)> {
+    if state.ended {
+        return None;
+    }
+    if let Some(event) = state.backlog.pop_front() {
+        return Some((Ok(frame(&event)), state));
+    }
+
+    let bytes = tokio::select! {
+        _ = state.heartbeat.tick() => Bytes::from_static(b": heartbeat\n\n"),
+        event = state.events.recv() => {
+            let event = event?;
+            state.ended = matches!(event.message, ServerMessage::ModerationRevoked);
+            frame(&event)
+        }
+    };
+    Some((Ok(bytes), state))
+}
+
+/// An event in the `text/event-stream` format
+fn frame(event: &Event) -> Bytes {
+    let data = serde_json::to_string(&event.message).expect("server messages serialize");
+    let id = event.id.map(|id| format!("id: {}\n", id)).unwrap_or_default();
+
+    Bytes::from(format!("{}event: {}\ndata: {}\n\n", id, event.message.kind(), data))
+}
@@ -0,0 +1,71 @@
+use actix_web::{get, http::header, web, HttpResponse};
+use crate::config;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::{Db, UserOperations};
+use crate::feed;
+use crate::types::user::{Track, User};
+
+/// How long feed readers may cache a feed, in seconds
+const FEED_MAX_AGE: u32 = 900;
+
+/// Load a user along with their feed tracks. Missing, deleted and private
+/// users, and users with nothing public, all look the same: not found.
+async fn load_feed(db: &Db, username: String) -> Result<User, Error> {
+    let user = UserOperations::new(db).get_user_by_username(username).await?;
+    
+    let visible = user.profile.as_ref().is_some_and(|p| !p.is_private && !p.is_deleted());
+    if !visible || feed::feed_tracks(&user).is_empty() {
+        return Err(Error::UserNotFound);
+    }
+    
+    Ok(user)
+}
+
+fn feed_response(content_type: &str, tracks: &[&Track], body: String) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(content_type)
+        .insert_header((header::CACHE_CONTROL, format!("public, max-age={}", FEED_MAX_AGE)));
+    
+    if let Some(updated) = feed::last_updated(tracks) {
+        response.insert_header((header::LAST_MODIFIED, updated.format("%a, %d %b %Y %H:%M:%S GMT").to_string()));
+    }
+    
+    response.body(body)
+}
+
+/// RSS 2.0 feed of a user's latest public tracks
+#[utoipa::path(
+    tag = "feeds",
+    params(("username" = String, Path)),
+    responses(
+        (status = 200, content_type = "application/rss+xml", body = String),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/{username}/feed.rss")]
+pub async fn rss_feed(path: web::Path<String>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = load_feed(&db, path.into_inner()).await?;
+    let tracks = feed::feed_tracks(&user);
+    let body = feed::render_rss(&user, &tracks, &config::public_url());
+    
+    Ok(feed_response("application/rss+xml; charset=utf-8", &tracks, body))
+}
+
+/// Atom feed of a user's latest public tracks
+#[utoipa::path(
+    tag = "feeds",
+    params(("username" = String, Path)),
+    responses(
+        (status = 200, content_type = "application/atom+xml", body = String),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/{username}/feed.atom")]
+pub async fn atom_feed(path: web::Path<String>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = load_feed(&db, path.into_inner()).await?;
+    let tracks = feed::feed_tracks(&user);
+    let body = feed::render_atom(&user, &tracks, &config::public_url());
+    
+    Ok(feed_response("application/atom+xml; charset=utf-8", &tracks, body))
+}
@@ -0,0 +1,59 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::track::TrackOperations;
+use crate::db::Db;
+use crate::types::pagination::Paginated;
+use crate::types::user::{TrackSort, TrackView};
+use super::paged;
+
+/// Longest genre name accepted, in characters
+const MAX_GENRE_LEN: usize = 50;
+
+#[derive(Deserialize, IntoParams)]
+pub struct GenreTracksParams {
+    /// `newest` (the default) or `most-liked`
+    #[serde(default)]
+    pub sort: TrackSort,
+    /// Leave out tracks marked explicit
+    #[serde(default)]
+    pub exclude_explicit: bool,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// Public tracks in a genre from every public profile. Genres are free text,
+/// so the name is matched case-insensitively and an unused one is empty.
+/// `exclude_explicit` leaves out tracks their owners marked explicit.
+#[utoipa::path(
+    tag = "tracks",
+    params(("genre" = String, Path), GenreTracksParams),
+    responses(
+        (status = 200, body = Paginated<TrackView>, headers(
+            ("X-Total-Count" = u64, description = "Matches across all pages"),
+            ("Link" = String, description = "URLs of the first, previous, next and last pages"),
+        )),
+        (status = 400, description = "Blank or overlong genre, or unknown sort", body = ErrorBody),
+    )
+)]
+#[get("/genres/{genre}/tracks")]
+pub async fn list_genre_tracks(
+    req: HttpRequest,
+    path: web::Path<String>,
+    params: web::Query<GenreTracksParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let genre = path.into_inner();
+    let genre = genre.trim();
+    if genre.is_empty() || genre.chars().count() > MAX_GENRE_LEN {
+        return Err(Error::Validation(format!("genre must be 1 to {} characters", MAX_GENRE_LEN)));
+    }
+    
+    let limit = params.limit.unwrap_or(20).clamp(1, 100);
+    let offset = params.offset.unwrap_or(0);
+    let (tracks, total) = TrackOperations::new(&db).tracks_in_genre(genre, params.sort, params.exclude_explicit, limit, offset).await?;
+    let tracks: Vec<TrackView> = tracks.into_iter().map(TrackView::from).collect();
+    
+    Ok(paged(&req, Paginated::new(tracks, limit, offset), total))
+}
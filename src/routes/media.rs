@@ -0,0 +1,69 @@
+use actix_web::{get, http::header, web, HttpResponse};
+use tokio_util::io::ReaderStream;
+use crate::db::error::{Error, ErrorBody};
+use crate::storage::{self, storage};
+
+/// Keys under these are only served through the track endpoints, which check
+/// who's asking
+const GATED_PREFIXES: &[&str] = &["audio/", "attachments/"];
+
+fn content_type(key: &str) -> &'static str {
+    match key.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).as_deref() {
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("gif") => "image/gif",
+        _ => "application/octet-stream",
+    }
+}
+
+/// A file from local media storage. Keys are never reused, so files may be
+/// cached forever. Audio and attachments aren't served here.
+#[utoipa::path(
+    tag = "media",
+    params(("key" = String, Path)),
+    responses(
+        (status = 200, content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/media/{key:.*}")]
+pub async fn get_media(path: web::Path<String>) -> Result<HttpResponse, Error> {
+    let key = path.into_inner();
+    if GATED_PREFIXES.iter().any(|prefix| key.starts_with(prefix)) {
+        return Err(Error::MediaNotFound);
+    }
+    let file = storage::local().path(&key).ok_or(Error::MediaNotFound)?;
+    
+    let body = web::block(move || std::fs::read(file))
+        .await
+        .map_err(|e| Error::Db(e.to_string()))?
+        .map_err(|_| Error::MediaNotFound)?;
+    
+    Ok(HttpResponse::Ok()
+        .content_type(content_type(&key))
+        .insert_header((header::CACHE_CONTROL, "public, max-age=31536000, immutable"))
+        .body(body))
+}
+
+/// Send the stored file behind `url` to someone already checked, streamed
+/// from disk, as a download named `filename` if given. Files hosted
+/// elsewhere are redirected to.
+pub(crate) async fn send_stored(url: &str, content_type: &str, filename: Option<&str>) -> Result<HttpResponse, Error> {
+    let Some(key) = storage().key_for_url(url) else {
+        return Ok(HttpResponse::Found().insert_header((header::LOCATION, url)).finish());
+    };
+    let path = storage::local().path(&key).ok_or(Error::MediaNotFound)?;
+    let file = tokio::fs::File::open(path).await.map_err(|_| Error::MediaNotFound)?;
+    let length = file.metadata().await.map_err(|e| Error::Db(e.to_string()))?.len();
+    
+    let mut response = HttpResponse::Ok();
+    response
+        .content_type(content_type)
+        .insert_header((header::CACHE_CONTROL, "private, no-cache"))
+        .no_chunking(length);
+    if let Some(filename) = filename {
+        response.insert_header(header::ContentDisposition::attachment(filename));
+    }
+    Ok(response.streaming(ReaderStream::new(file)))
+}
@@ -0,0 +1,46 @@
+use actix_web::{get, web, HttpResponse};
+use crate::db::Db;
+use crate::latency;
+use crate::types::latency::DbHealth;
+
+/// Process metrics in the Prometheus text format
+#[utoipa::path(
+    tag = "metrics",
+    responses(
+        (status = 200, content_type = "text/plain", body = String),
+    )
+)]
+#[get("/metrics")]
+pub async fn metrics(db: web::Data<Db>) -> HttpResponse {
+    let stats = db.user_cache().stats();
+    let database = latency::current();
+    
+    let body = format!(
+        "# HELP libretune_user_cache_hits_total User lookups served from the cache\n\
+        # TYPE libretune_user_cache_hits_total counter\n\
+        libretune_user_cache_hits_total {}\n\
+        # HELP libretune_user_cache_misses_total User lookups that went to the database\n\
+        # TYPE libretune_user_cache_misses_total counter\n\
+        libretune_user_cache_misses_total {}\n\
+        # HELP libretune_user_cache_entries Users currently cached\n\
+        # TYPE libretune_user_cache_entries gauge\n\
+        libretune_user_cache_entries {}\n\
+        # HELP libretune_db_latency_seconds Round trip of the latest database probe\n\
+        # TYPE libretune_db_latency_seconds gauge\n\
+        libretune_db_latency_seconds {}\n\
+        # HELP libretune_db_degraded Whether the latest database probe was slow or failed\n\
+        # TYPE libretune_db_degraded gauge\n\
+        libretune_db_degraded {}\n\
+        # HELP libretune_db_probe_failures_total Database probes that failed\n\
+        # TYPE libretune_db_probe_failures_total counter\n\
+        libretune_db_probe_failures_total {}\n",
+        stats.hits, stats.misses, stats.size,
+        database.last_ms.map_or("NaN".to_string(), |ms| (ms / 1000.0).to_string()),
+        u8::from(matches!(database.health, DbHealth::Degraded | DbHealth::Unreachable)),
+        database.failures,
+    );
+    
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(body)
+}
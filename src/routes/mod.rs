@@ -0,0 +1,298 @@
+use actix_multipart::Multipart;
+use actix_web::error::{InternalError, JsonPayloadError};
+use actix_web::http::header;
+use actix_web::{mime, web, HttpRequest, HttpResponse};
+use futures_util::TryStreamExt;
+use reqwest::Url;
+use serde::{Deserialize, Serialize};
+use utoipa::IntoParams;
+use crate::config;
+use crate::db::error::{Error, ErrorBody};
+use crate::types::pagination::{Cursor, Paginated};
+
+pub mod admin;
+pub mod announcements;
+pub mod auth;
+pub mod embed;
+pub mod events;
+pub mod feeds;
+pub mod genres;
+pub mod media;
+pub mod metrics;
+pub mod notifications;
+pub mod playlists;
+pub mod releases;
+pub mod reports;
+pub mod search;
+pub mod sitemap;
+pub mod status;
+pub mod tracks;
+pub mod uploads;
+pub mod users;
+pub mod webhooks;
+pub mod ws;
+
+#[derive(Deserialize, IntoParams)]
+pub struct PageParams {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct CursorParams {
+    pub limit: Option<u32>,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+}
+
+impl CursorParams {
+    /// The requested page size, between 1 and 100, and the decoded cursor
+    pub fn page(&self) -> Result<(u32, Option<Cursor>), Error> {
+        let limit = self.limit.unwrap_or(20).clamp(1, 100);
+        let cursor = self.cursor.as_deref().map(Cursor::decode).transpose()?;
+        Ok((limit, cursor))
+    }
+}
+
+/// Respond with an offset page plus GitHub-style pagination headers:
+/// `X-Total-Count` and a `Link` with `first`, `prev`, `next` and `last`
+/// URLs. `prev` and `next` are left out on the first and last pages.
+pub fn paged<T: Serialize>(req: &HttpRequest, page: Paginated<T>, total: u64) -> HttpResponse {
+    let limit = page.limit.max(1);
+    let offset = page.offset.unwrap_or(0);
+    let last = total.saturating_sub(1) / u64::from(limit) * u64::from(limit);
+    
+    let mut links = vec![(0, "first")];
+    if offset > 0 {
+        links.push((offset.saturating_sub(limit), "prev"));
+    }
+    if u64::from(offset) + u64::from(limit) < total {
+        links.push((offset + limit, "next"));
+    }
+    links.push((u32::try_from(last).unwrap_or(u32::MAX), "last"));
+    
+    let link = links
+        .into_iter()
+        .map(|(offset, rel)| format!("<{}>; rel=\"{}\"", page_url(req, limit, offset), rel))
+        .collect::<Vec<_>>()
+        .join(", ");
+    
+    HttpResponse::Ok()
+        .insert_header(("X-Total-Count", total.to_string()))
+        .insert_header((header::LINK, link))
+        .json(page)
+}
+
+/// The current URL with `limit` and `offset` swapped for the given window
+fn page_url(req: &HttpRequest, limit: u32, offset: u32) -> String {
+    let mut url = match Url::parse(&format!("{}{}", config::public_url(), req.path())) {
+        Ok(url) => url,
+        Err(_) => return format!("{}?limit={}&offset={}", req.path(), limit, offset),
+    };
+    
+    let current = web::Query::<Vec<(String, String)>>::from_query(req.query_string())
+        .map(web::Query::into_inner)
+        .unwrap_or_default();
+    
+    url.query_pairs_mut()
+        .extend_pairs(current.iter().filter(|(key, _)| key != "limit" && key != "offset"))
+        .append_pair("limit", &limit.to_string())
+        .append_pair("offset", &offset.to_string());
+    url.into()
+}
+
+/// The `file` field of a multipart upload
+pub struct Upload {
+    pub bytes: Vec<u8>,
+    pub filename: Option<String>, // as the client named it, untrusted
+}
+
+/// Read the `file` field of a multipart upload, refusing anything larger
+/// than `max_bytes`
+pub async fn read_upload(mut payload: Multipart, max_bytes: usize) -> Result<Upload, Error> {
+    let invalid = |e: actix_multipart::MultipartError| Error::Validation(e.to_string());
+    
+    while let Some(mut field) = payload.try_next().await.map_err(invalid)? {
+        if field.name() != Some("file") {
+            continue;
+        }
+        
+        let filename = field.content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .map(str::to_string);
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.try_next().await.map_err(invalid)? {
+            if bytes.len() + chunk.len() > max_bytes {
+                return Err(Error::PayloadTooLarge);
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        return Ok(Upload { bytes, filename });
+    }
+    
+    Err(Error::Validation("missing file field".to_string()))
+}
+
+/// JSON bodies must be sent as `application/json`. Anything else, form
+/// encoded or `text/plain` included, is rejected with 415 instead of being
+/// parsed anyway.
+pub fn json_config() -> web::JsonConfig {
+    web::JsonConfig::default()
+        .content_type_required(true)
+        .content_type(|mime| mime.type_() == mime::APPLICATION && mime.subtype() == mime::JSON)
+        .error_handler(|err, _req| {
+            let response = match &err {
+                JsonPayloadError::ContentType => HttpResponse::UnsupportedMediaType()
+                    .json(ErrorBody::new("Content-Type must be application/json")),
+                err => HttpResponse::BadRequest().json(ErrorBody::new(err.to_string())),
+            };
+            InternalError::from_response(err, response).into()
+        })
+}
+
+/// Register every API route on an `App`
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(json_config())
+        .service(auth::register)
+        .service(auth::login)
+        .service(auth::send_verification_email)
+        .service(auth::verify_email)
+        .service(auth::forgot_password)
+        .service(auth::reset_password)
+        .service(search::search)
+        .service(tracks::get_track)
+        .service(tracks::patch_track)
+        .service(tracks::get_lyrics)
+        .service(tracks::patch_lyrics)
+        .service(tracks::download_track)
+        .service(tracks::stream_track)
+        .service(tracks::record_play)
+        .service(tracks::get_track_stats)
+        .service(tracks::ping_presence)
+        .service(tracks::get_listener_count)
+        .service(tracks::list_comments)
+        .service(tracks::add_comment)
+        .service(tracks::delete_comment)
+        .service(tracks::create_share_link)
+        .service(tracks::list_share_links)
+        .service(tracks::revoke_share_link)
+        .service(tracks::get_shared_track)
+        .service(tracks::get_track_by_slug)
+        .service(tracks::replace_audio)
+        .service(tracks::list_audio_versions)
+        .service(tracks::restore_audio)
+        .service(tracks::upload_attachment)
+        .service(tracks::list_attachments)
+        .service(tracks::download_attachment)
+        .service(tracks::delete_attachment)
+        .service(tracks::pin_track)
+        .service(tracks::unpin_track)
+        .service(tracks::set_credits)
+        .service(tracks::list_credits)
+        .service(tracks::respond_to_credit)
+        .service(reports::report_track)
+        .service(reports::report_comment)
+        .service(tracks::import_tracks)
+        .service(tracks::bulk_delete_tracks)
+        .service(uploads::create_upload)
+        .service(uploads::get_upload)
+        .service(uploads::upload_chunk)
+        .service(uploads::finalize_upload)
+        .service(uploads::cancel_upload)
+        .service(genres::list_genre_tracks)
+        .service(feeds::rss_feed)
+        .service(feeds::atom_feed)
+        .service(embed::oembed)
+        .service(embed::embed_track)
+        .service(embed::embed_playlist)
+        .service(playlists::get_playlist)
+        .service(playlists::add_playlist_track)
+        .service(playlists::remove_playlist_track)
+        .service(playlists::move_playlist_track)
+        .service(playlists::set_playlist_collaborative)
+        .service(playlists::delete_playlist)
+        .service(media::get_media)
+        .service(sitemap::sitemap_index)
+        .service(sitemap::sitemap_part)
+        .service(users::get_me)
+        .service(users::get_profile)
+        .service(users::get_profile_by_username)
+        .service(users::list_tracks)
+        .service(users::list_appearances)
+        .service(users::list_releases)
+        .service(users::list_followers)
+        .service(users::send_erase_code)
+        .service(users::erase_me)
+        .service(users::export_me)
+        .service(users::apply_for_verification)
+        .service(users::get_verification)
+        .service(users::set_location)
+        .service(users::set_track_order)
+        .service(users::set_now_playing_sharing)
+        .service(users::suggestions)
+        .service(users::upload_picture)
+        .service(users::upload_banner)
+        .service(webhooks::list_webhooks)
+        .service(webhooks::create_webhook)
+        .service(webhooks::get_webhook)
+        .service(webhooks::update_webhook)
+        .service(webhooks::delete_webhook)
+        .service(webhooks::list_deliveries)
+        .service(admin::list_users)
+        .service(admin::list_erasures)
+        .service(admin::get_erasure)
+        .service(admin::list_audit_entries)
+        .service(admin::set_legal_hold)
+        .service(admin::list_global_webhooks)
+        .service(admin::create_global_webhook)
+        .service(admin::update_global_webhook)
+        .service(admin::delete_global_webhook)
+        .service(admin::list_global_deliveries)
+        .service(admin::import_users)
+        .service(admin::signups_by_source)
+        .service(admin::list_reports)
+        .service(admin::set_report_status)
+        .service(admin::moderation_summary)
+        .service(admin::reconcile_counters)
+        .service(admin::list_flags)
+        .service(admin::set_flag)
+        .service(admin::list_orphan_tracks)
+        .service(admin::cleanup_orphan_tracks)
+        .service(admin::set_maintenance)
+        .service(admin::list_announcements)
+        .service(admin::create_announcement)
+        .service(admin::update_announcement)
+        .service(admin::delete_announcement)
+        .service(admin::impersonate)
+        .service(admin::end_impersonation)
+        .service(admin::get_config)
+        .service(admin::get_latency)
+        .service(admin::run_gc)
+        .service(admin::list_gc_runs)
+        .service(admin::list_verification_requests)
+        .service(admin::review_verification)
+        .service(admin::revoke_verification)
+        .service(metrics::metrics)
+        .service(status::status)
+        .service(status::health)
+        .service(status::ready)
+        .service(announcements::active_announcements)
+        .service(announcements::dismiss_announcement)
+        .service(notifications::list_notifications)
+        .service(notifications::mark_notifications_read)
+        .service(notifications::push_public_key)
+        .service(notifications::create_push_subscription)
+        .service(notifications::list_push_subscriptions)
+        .service(notifications::delete_push_subscription)
+        .service(notifications::get_push_preferences)
+        .service(notifications::set_push_preferences)
+        .service(ws::connect)
+        .service(events::notification_stream)
+        .service(events::feed_stream)
+        .service(events::moderation_stream)
+        .service(releases::create_release)
+        .service(releases::get_release)
+        .service(releases::update_release)
+        .service(releases::delete_release)
+        .service(releases::publish_release);
+}
@@ -0,0 +1,170 @@
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use uuid::Uuid;
+use crate::auth::AuthUser;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::notification::NotificationOperations;
+use crate::db::push::PushOperations;
+use crate::db::{bounded, Db, UserOperations};
+use crate::push;
+use crate::types::notification::{Notification, NotificationKind};
+use crate::types::pagination::Paginated;
+use crate::types::push::{NewPushSubscription, PushPreferences, PushSubscriptionView, VapidPublicKey};
+use super::{paged, PageParams};
+
+/// The caller's notifications, newest first
+#[utoipa::path(
+    tag = "notifications",
+    params(PageParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Paginated<Notification>, headers(
+            ("X-Total-Count" = u64, description = "Notifications across all pages"),
+            ("Link" = String, description = "URLs of the first, previous, next and last pages"),
+        )),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[get("/notifications")]
+pub async fn list_notifications(req: HttpRequest, auth: AuthUser, params: web::Query<PageParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let limit = params.limit.unwrap_or(20).clamp(1, 100);
+    let offset = params.offset.unwrap_or(0);
+    
+    let ops = NotificationOperations::new(&db);
+    let (notifications, total) = futures_util::try_join!(
+        bounded("notifications", ops.get_notifications(auth.user.id, limit, offset)),
+        bounded("notification count", ops.count_notifications(auth.user.id)),
+    )?;
+    
+    Ok(paged(&req, Paginated::new(notifications, limit, offset), total))
+}
+
+/// Mark all of the caller's notifications as read
+#[utoipa::path(
+    tag = "notifications",
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[post("/notifications/read")]
+pub async fn mark_notifications_read(auth: AuthUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    NotificationOperations::new(&db).mark_all_read(auth.user.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// The key to pass browsers as `applicationServerKey` when subscribing to
+/// pushes
+#[utoipa::path(
+    tag = "notifications",
+    responses(
+        (status = 200, body = VapidPublicKey),
+        (status = 404, description = "This server doesn't send pushes", body = ErrorBody),
+    )
+)]
+#[get("/push/public-key")]
+pub async fn push_public_key() -> Result<HttpResponse, Error> {
+    let public_key = push::public_key().ok_or(Error::NotFound)?;
+    
+    Ok(HttpResponse::Ok().json(VapidPublicKey { public_key: public_key.to_string() }))
+}
+
+/// Have notifications pushed to a browser while the caller isn't
+/// connected. Send the browser's `PushSubscription` as JSON. Resubscribing
+/// the same endpoint replaces it, and past ten subscriptions the oldest is
+/// dropped. Pushes carry only a title and a link.
+#[utoipa::path(
+    tag = "notifications",
+    request_body = NewPushSubscription,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = PushSubscriptionView),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, description = "This server doesn't send pushes", body = ErrorBody),
+    )
+)]
+#[post("/users/me/push-subscriptions")]
+pub async fn create_push_subscription(auth: AuthUser, body: web::Json<NewPushSubscription>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    if push::public_key().is_none() {
+        return Err(Error::NotFound);
+    }
+    
+    let subscription = body.into_inner().into_subscription(auth.user.id)?;
+    let subscription = PushOperations::new(&db).subscribe(subscription).await?;
+    
+    Ok(HttpResponse::Created().json(PushSubscriptionView::from(subscription)))
+}
+
+/// The browsers the caller gets pushes on, newest first
+#[utoipa::path(
+    tag = "notifications",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<PushSubscriptionView>),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[get("/users/me/push-subscriptions")]
+pub async fn list_push_subscriptions(auth: AuthUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let subscriptions = PushOperations::new(&db).get_subscriptions(auth.user.id).await?;
+    let views: Vec<PushSubscriptionView> = subscriptions.into_iter().map(PushSubscriptionView::from).collect();
+    
+    Ok(HttpResponse::Ok().json(views))
+}
+
+/// Stop pushing to a browser
+#[utoipa::path(
+    tag = "notifications",
+    params(("subscription_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/users/me/push-subscriptions/{subscription_id}")]
+pub async fn delete_push_subscription(auth: AuthUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    PushOperations::new(&db).unsubscribe(auth.user.id, path.into_inner()).await?;
+    
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Which kinds of notification the caller gets pushed. Until chosen, every
+/// kind but `release_published`.
+#[utoipa::path(
+    tag = "notifications",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = PushPreferences),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[get("/users/me/push-preferences")]
+pub async fn get_push_preferences(auth: AuthUser) -> Result<HttpResponse, Error> {
+    let profile = auth.user.profile.as_ref().ok_or(Error::ProfileNotFound)?;
+    
+    Ok(HttpResponse::Ok().json(PushPreferences { kinds: profile.push_kinds() }))
+}
+
+/// Choose which kinds of notification the caller gets pushed; an empty
+/// list turns pushes off
+#[utoipa::path(
+    tag = "notifications",
+    request_body = PushPreferences,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = PushPreferences),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[put("/users/me/push-preferences")]
+pub async fn set_push_preferences(auth: AuthUser, body: web::Json<PushPreferences>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let mut kinds = body.into_inner().kinds;
+    kinds.sort_by_key(|kind| NotificationKind::ALL.iter().position(|k| k == kind));
+    kinds.dedup();
+    UserOperations::new(&db).set_push_kinds(auth.user.id, kinds.clone()).await?;
+    
+    Ok(HttpResponse::Ok().json(PushPreferences { kinds }))
+}
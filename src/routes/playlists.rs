@@ -0,0 +1,207 @@
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use crate::auth::AuthUser;
+use crate::conditional::{self, CachePolicy};
+use crate::db::error::{Error, ErrorBody};
+use crate::db::playlist::PlaylistOperations;
+use crate::db::Db;
+use crate::types::id::{PlaylistId, TrackId, UserId};
+use crate::types::playlist::{AddPlaylistTrack, CollaborativeSettings, DeletePlaylistParams, MovePlaylistTrack, RevisionParams};
+use crate::types::user::{Playlist, PlaylistView, PublicUser, TrackView, User};
+
+/// A playlist with the tracks in it the caller may see. Private playlists are
+/// only visible to their owner. `revision` is what to send with edits.
+#[utoipa::path(
+    tag = "playlists",
+    params(("playlist_id" = Uuid, Path)),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = PlaylistView, headers(
+            ("Last-Modified" = String, description = "When the playlist, its tracks or its owner last changed"),
+        )),
+        (status = 304, description = "Matches If-None-Match, or unchanged since If-Modified-Since"),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/playlists/{playlist_id}")]
+pub async fn get_playlist(req: HttpRequest, auth: Option<AuthUser>, playlist_id: PlaylistId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let (owner, playlist) = PlaylistOperations::new(&db).get_playlist(playlist_id).await?;
+    let viewer = auth.map(|auth| auth.user.id);
+    
+    if !playlist.is_visible_to(viewer) {
+        return Err(Error::PlaylistNotFound);
+    }
+    
+    let is_owner = viewer == Some(owner.id);
+    
+    // Edits to the owner's public details or to a track change the view too
+    let last_modified = playlist.tracks.iter()
+        .map(|track| track.updated_at)
+        .chain([playlist.updated_at, owner.updated_at])
+        .max()
+        .unwrap_or(playlist.updated_at);
+    
+    let view = playlist_view(owner, playlist, viewer);
+    
+    // The caller may be seeing their own private tracks in it
+    let personalized = is_owner || view.tracks.iter().any(|track| !track.is_public);
+    conditional::json_modified(&req, CachePolicy::for_viewer(personalized, conditional::DEFAULT_MAX_AGE), &view, last_modified)
+}
+
+/// Add a track to a playlist, at `index` among the tracks the caller sees or
+/// at the end. The owner may edit a playlist, and so may anyone who can see
+/// it once it's collaborative. With `revision`, the edit is refused with 409
+/// if someone else changed the tracks first, so the client can reload and
+/// apply it again. Sockets subscribed to the playlist get `playlist_track_added`.
+#[utoipa::path(
+    tag = "playlists",
+    params(("playlist_id" = Uuid, Path)),
+    request_body = AddPlaylistTrack,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = PlaylistView),
+        (status = 400, description = "The playlist is full", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Not the owner and the playlist isn't collaborative", body = ErrorBody),
+        (status = 404, description = "No such playlist, or no such track the caller can see", body = ErrorBody),
+        (status = 409, description = "Stale revision, or the track is already in the playlist", body = ErrorBody),
+    )
+)]
+#[post("/playlists/{playlist_id}/tracks")]
+pub async fn add_playlist_track(auth: AuthUser, playlist_id: PlaylistId, body: web::Json<AddPlaylistTrack>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let body = body.into_inner();
+    let (owner, playlist) = PlaylistOperations::new(&db).add_track(playlist_id, auth.user.id, body.track_id, body.index, body.revision).await?;
+    
+    Ok(HttpResponse::Ok().json(playlist_view(owner, playlist, Some(auth.user.id))))
+}
+
+/// Take a track out of a playlist. Who may, and how `revision` is checked,
+/// is as for adding one. Subscribers get `playlist_track_removed`.
+#[utoipa::path(
+    tag = "playlists",
+    params(("playlist_id" = Uuid, Path), ("track_id" = Uuid, Path), RevisionParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = PlaylistView),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Stale revision", body = ErrorBody),
+    )
+)]
+#[delete("/playlists/{playlist_id}/tracks/{track_id}")]
+pub async fn remove_playlist_track(
+    auth: AuthUser,
+    playlist_id: PlaylistId,
+    track_id: TrackId,
+    params: web::Query<RevisionParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (owner, playlist) = PlaylistOperations::new(&db).remove_track(playlist_id, auth.user.id, track_id, params.revision).await?;
+    
+    Ok(HttpResponse::Ok().json(playlist_view(owner, playlist, Some(auth.user.id))))
+}
+
+/// Move a track to `index` among the other tracks the caller sees. Who may,
+/// and how `revision` is checked, is as for adding one. Subscribers get a
+/// `playlist_track_moved` with just the track and where it went.
+#[utoipa::path(
+    tag = "playlists",
+    params(("playlist_id" = Uuid, Path), ("track_id" = Uuid, Path)),
+    request_body = MovePlaylistTrack,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = PlaylistView),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Stale revision", body = ErrorBody),
+    )
+)]
+#[put("/playlists/{playlist_id}/tracks/{track_id}/position")]
+pub async fn move_playlist_track(
+    auth: AuthUser,
+    playlist_id: PlaylistId,
+    track_id: TrackId,
+    body: web::Json<MovePlaylistTrack>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (owner, playlist) = PlaylistOperations::new(&db).move_track(playlist_id, auth.user.id, track_id, body.index, body.revision).await?;
+    
+    Ok(HttpResponse::Ok().json(playlist_view(owner, playlist, Some(auth.user.id))))
+}
+
+/// Let anyone who can see the playlist edit its tracks, or stop them. Only
+/// the owner may change this.
+#[utoipa::path(
+    tag = "playlists",
+    params(("playlist_id" = Uuid, Path)),
+    request_body = CollaborativeSettings,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = PlaylistView),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[put("/playlists/{playlist_id}/collaborative")]
+pub async fn set_playlist_collaborative(
+    auth: AuthUser,
+    playlist_id: PlaylistId,
+    body: web::Json<CollaborativeSettings>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (owner, playlist) = PlaylistOperations::new(&db).set_collaborative(playlist_id, auth.user.id, body.is_collaborative).await?;
+    
+    Ok(HttpResponse::Ok().json(playlist_view(owner, playlist, Some(auth.user.id))))
+}
+
+/// Delete a playlist. It's kept aside unless `hard`, but no longer listed
+/// or reachable. Only the owner may. Sockets subscribed to it get
+/// `playlist_deleted`.
+#[utoipa::path(
+    tag = "playlists",
+    params(("playlist_id" = Uuid, Path), DeletePlaylistParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Not the owner", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Edited while being deleted", body = ErrorBody),
+    )
+)]
+#[delete("/playlists/{playlist_id}")]
+pub async fn delete_playlist(
+    auth: AuthUser,
+    playlist_id: PlaylistId,
+    params: web::Query<DeletePlaylistParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    PlaylistOperations::new(&db).delete_playlist(playlist_id, auth.user.id, params.hard).await?;
+    
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// A playlist as `viewer` may see it
+fn playlist_view(owner: User, playlist: Playlist, viewer: Option<UserId>) -> PlaylistView {
+    PlaylistView {
+        id: playlist.id,
+        slug: playlist.slug,
+        owner: PublicUser::from(owner),
+        name: playlist.name,
+        description: playlist.description,
+        tags: playlist.tags,
+        cover_image_url: playlist.cover_image_url,
+        is_public: playlist.is_public,
+        is_collaborative: playlist.is_collaborative,
+        tracks: playlist.tracks
+            .into_iter()
+            .filter(|track| track.is_visible_to(viewer))
+            .map(|track| TrackView::for_viewer(track, viewer))
+            .collect(),
+        revision: playlist.revision,
+        created_at: playlist.created_at,
+        updated_at: playlist.updated_at,
+    }
+}
@@ -0,0 +1,150 @@
+use actix_web::{delete, get, patch, post, web, HttpResponse};
+use uuid::Uuid;
+use crate::auth::AuthUser;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::release::ReleaseOperations;
+use crate::db::{Db, UserOperations};
+use crate::types::id::UserId;
+use crate::types::release::{NewRelease, Release, ReleasePatch, ReleaseView};
+use crate::types::user::{PublicUser, TrackView, User};
+
+/// A release with the tracks on it `viewer` may see, in release order
+pub(crate) fn view(release: Release, artist: &User, viewer: Option<UserId>) -> ReleaseView {
+    let uploads = artist.profile.as_ref().and_then(|p| p.uploads.as_ref());
+    let tracks = release.track_ids.iter()
+        .filter_map(|id| uploads.and_then(|uploads| uploads.iter().find(|t| t.id == *id)))
+        .filter(|track| track.is_visible_to(viewer))
+        .cloned()
+        .map(|track| TrackView::for_viewer(track, viewer))
+        .collect();
+    
+    ReleaseView {
+        id: release.id,
+        artist: PublicUser::from(artist.clone()),
+        title: release.title,
+        release_type: release.release_type,
+        cover_image_url: release.cover_image_url,
+        release_date: release.release_date,
+        catalog_number: release.catalog_number,
+        upc: release.upc,
+        tracks,
+        published_at: release.published_at,
+        created_at: release.created_at,
+        updated_at: release.updated_at,
+    }
+}
+
+/// Create a draft release from some of the caller's tracks. Nobody else
+/// sees it until it's published.
+#[utoipa::path(
+    tag = "releases",
+    request_body = NewRelease,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = ReleaseView),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[post("/releases")]
+pub async fn create_release(auth: AuthUser, body: web::Json<NewRelease>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let release = body.into_inner().into_release(auth.user.id)?;
+    let release = ReleaseOperations::new(&db).create_release(release).await?;
+    
+    let artist = UserOperations::new(&db).get_user_by_id(auth.user.id).await?;
+    Ok(HttpResponse::Created().json(view(release, &artist, Some(auth.user.id))))
+}
+
+/// A release and its tracks. Drafts are only visible to their artist.
+#[utoipa::path(
+    tag = "releases",
+    params(("release_id" = Uuid, Path)),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = ReleaseView),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/releases/{release_id}")]
+pub async fn get_release(auth: Option<AuthUser>, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let release = ReleaseOperations::new(&db).get_release(path.into_inner()).await?;
+    let viewer = auth.map(|auth| auth.user.id);
+    
+    if !release.is_visible_to(viewer) {
+        return Err(Error::ReleaseNotFound);
+    }
+    
+    let artist = UserOperations::new(&db).get_user_by_id(release.user_id).await?;
+    if !artist.profile_visible_to(viewer) {
+        return Err(Error::ReleaseNotFound);
+    }
+    
+    Ok(HttpResponse::Ok().json(view(release, &artist, viewer)))
+}
+
+/// Change some of a release's details or its track list. Only the artist
+/// may edit a release.
+#[utoipa::path(
+    tag = "releases",
+    params(("release_id" = Uuid, Path)),
+    request_body = ReleasePatch,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = ReleaseView),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[patch("/releases/{release_id}")]
+pub async fn update_release(auth: AuthUser, path: web::Path<Uuid>, body: web::Json<ReleasePatch>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let mut release = ReleaseOperations::new(&db).get_owned_release(path.into_inner(), auth.user.id).await?;
+    body.into_inner().apply(&mut release)?;
+    let release = ReleaseOperations::new(&db).save_release(release).await?;
+    
+    let artist = UserOperations::new(&db).get_user_by_id(auth.user.id).await?;
+    Ok(HttpResponse::Ok().json(view(release, &artist, Some(auth.user.id))))
+}
+
+/// Delete a release. The tracks on it are kept.
+#[utoipa::path(
+    tag = "releases",
+    params(("release_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/releases/{release_id}")]
+pub async fn delete_release(auth: AuthUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let release = ReleaseOperations::new(&db).get_owned_release(path.into_inner(), auth.user.id).await?;
+    ReleaseOperations::new(&db).delete_release(release.id).await?;
+    
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Publish a draft release. Its private tracks are made public in the same
+/// step, and the artist's followers get one notification for the release.
+#[utoipa::path(
+    tag = "releases",
+    params(("release_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = ReleaseView),
+        (status = 400, description = "Already published, or has no tracks", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("/releases/{release_id}/publish")]
+pub async fn publish_release(auth: AuthUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let release = ReleaseOperations::new(&db).publish(path.into_inner(), auth.user.id).await?;
+    
+    let artist = UserOperations::new(&db).get_user_by_id(auth.user.id).await?;
+    Ok(HttpResponse::Ok().json(view(release, &artist, Some(auth.user.id))))
+}
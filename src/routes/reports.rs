@@ -0,0 +1,98 @@
+use actix_web::{post, web, HttpResponse};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use crate::auth::AuthUser;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::report::ReportOperations;
+use crate::db::track::TrackOperations;
+use crate::db::Db;
+use crate::types::id::{CommentId, TrackId};
+use crate::types::user::{Report, ReportTarget};
+
+const MAX_REASON_LEN: usize = 200;
+const MAX_DESCRIPTION_LEN: usize = 2000;
+
+#[derive(Deserialize, ToSchema)]
+pub struct ReportRequest {
+    pub reason: String,
+    pub description: Option<String>,
+}
+
+impl ReportRequest {
+    fn validate(self) -> Result<(String, Option<String>), Error> {
+        let reason = self.reason.trim().to_string();
+        if reason.is_empty() || reason.chars().count() > MAX_REASON_LEN {
+            return Err(Error::Validation(format!("reason must be 1 to {} characters", MAX_REASON_LEN)));
+        }
+        
+        let description = self.description.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+        if description.as_ref().is_some_and(|d| d.chars().count() > MAX_DESCRIPTION_LEN) {
+            return Err(Error::Validation(format!("description must be at most {} characters", MAX_DESCRIPTION_LEN)));
+        }
+        
+        Ok((reason, description))
+    }
+}
+
+/// Report a track to the moderators
+#[utoipa::path(
+    tag = "reports",
+    params(("track_id" = Uuid, Path)),
+    request_body = ReportRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = Report),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("/tracks/{track_id}/report")]
+pub async fn report_track(
+    auth: AuthUser,
+    track_id: TrackId,
+    body: web::Json<ReportRequest>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (reason, description) = body.into_inner().validate()?;
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    
+    if !track.is_visible_to(Some(auth.user.id)) {
+        return Err(Error::TrackNotFound);
+    }
+    
+    let report = ReportOperations::new(&db).create_report(auth.user.id, ReportTarget::Track(track.id), reason, description).await?;
+    Ok(HttpResponse::Created().json(report))
+}
+
+/// Report a comment or reply to the moderators
+#[utoipa::path(
+    tag = "reports",
+    params(("comment_id" = Uuid, Path)),
+    request_body = ReportRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = Report),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("/comments/{comment_id}/report")]
+pub async fn report_comment(
+    auth: AuthUser,
+    comment_id: CommentId,
+    body: web::Json<ReportRequest>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (reason, description) = body.into_inner().validate()?;
+    let (track, comment) = TrackOperations::new(&db).get_comment(comment_id).await?;
+    
+    // Comments on tracks the reporter can't see don't exist as far as they know
+    if comment.is_deleted || !track.is_visible_to(Some(auth.user.id)) {
+        return Err(Error::CommentNotFound);
+    }
+    
+    let report = ReportOperations::new(&db).create_report(auth.user.id, ReportTarget::Comment(comment.id), reason, description).await?;
+    Ok(HttpResponse::Created().json(report))
+}
@@ -0,0 +1,43 @@
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use serde::Deserialize;
+use utoipa::IntoParams;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::{bounded, Db, UserOperations};
+use crate::types::pagination::Paginated;
+use crate::types::user::PublicUser;
+use super::paged;
+
+#[derive(Deserialize, IntoParams)]
+pub struct SearchParams {
+    pub query: String,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// Search users by username or profile name
+#[utoipa::path(
+    tag = "search",
+    params(SearchParams),
+    responses(
+        (status = 200, body = Paginated<PublicUser>, headers(
+            ("X-Total-Count" = u64, description = "Matches across all pages"),
+            ("Link" = String, description = "URLs of the first, previous, next and last pages"),
+        )),
+        (status = 500, body = ErrorBody),
+    )
+)]
+#[get("/search")]
+pub async fn search(req: HttpRequest, params: web::Query<SearchParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let SearchParams { query, limit, offset } = params.into_inner();
+    let limit = limit.unwrap_or(10);
+    let offset = offset.unwrap_or(0);
+    
+    let ops = UserOperations::new(&db);
+    let (users, total) = futures_util::try_join!(
+        bounded("search", ops.search_users(query.clone(), Some(limit), Some(offset))),
+        bounded("search count", ops.count_search_users(query)),
+    )?;
+    let users: Vec<PublicUser> = users.into_iter().map(PublicUser::from).collect();
+    
+    Ok(paged(&req, Paginated::new(users, limit, offset), total))
+}
@@ -0,0 +1,49 @@
+use actix_web::{get, http::header, web, HttpResponse};
+use crate::db::error::{Error, ErrorBody};
+use crate::sitemap;
+
+async fn serve(name: &str) -> Result<HttpResponse, Error> {
+    if !sitemap::is_sitemap_file(name) {
+        return Err(Error::SitemapNotFound);
+    }
+    
+    let body = web::block({
+        let path = sitemap::sitemap_dir().join(name);
+        move || std::fs::read(path)
+    })
+    .await
+    .map_err(|e| Error::Db(e.to_string()))?
+    .map_err(|_| Error::SitemapNotFound)?;
+    
+    Ok(HttpResponse::Ok()
+        .content_type("application/xml; charset=utf-8")
+        .insert_header((header::CACHE_CONTROL, "public, max-age=3600"))
+        .body(body))
+}
+
+/// The sitemap, or the sitemap index when it's split
+#[utoipa::path(
+    tag = "seo",
+    responses(
+        (status = 200, content_type = "application/xml", body = String),
+        (status = 404, description = "Not generated yet", body = ErrorBody),
+    )
+)]
+#[get("/sitemap.xml")]
+pub async fn sitemap_index() -> Result<HttpResponse, Error> {
+    serve("sitemap.xml").await
+}
+
+/// One part of a split sitemap
+#[utoipa::path(
+    tag = "seo",
+    params(("part" = u32, Path)),
+    responses(
+        (status = 200, content_type = "application/xml", body = String),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/sitemap-{part}.xml")]
+pub async fn sitemap_part(path: web::Path<u32>) -> Result<HttpResponse, Error> {
+    serve(&format!("sitemap-{}.xml", path.into_inner())).await
+}
@@ -0,0 +1,93 @@
+use actix_web::{get, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+use crate::db;
+use crate::latency;
+use crate::maintenance;
+use crate::types::latency::{ConnectionState, DbHealth, DbLatency};
+use crate::types::maintenance::MaintenanceScope;
+
+#[derive(Serialize, ToSchema)]
+pub struct Status {
+    pub maintenance: bool,
+    /// What maintenance turns away, while in maintenance
+    pub scope: Option<MaintenanceScope>,
+    /// Banner to show while in maintenance
+    pub message: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+}
+
+/// Cheap status for clients to poll. Never touches the database.
+#[utoipa::path(
+    tag = "status",
+    responses(
+        (status = 200, body = Status),
+    )
+)]
+#[get("/status")]
+pub async fn status() -> HttpResponse {
+    let state = maintenance::current();
+    
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(Status {
+            maintenance: state.is_some(),
+            scope: state.as_ref().map(|state| state.scope),
+            message: state.as_ref().and_then(|state| state.message.clone()),
+            since: state.map(|state| state.changed_at),
+        })
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Health {
+    pub status: &'static str,
+}
+
+/// Liveness check for load balancers. Answers 200 even in maintenance.
+#[utoipa::path(
+    tag = "status",
+    responses(
+        (status = 200, body = Health),
+    )
+)]
+#[get("/health")]
+pub async fn health() -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(Health { status: "ok" })
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Readiness {
+    /// `ok`, `degraded` while the database is slow, or `unavailable`
+    pub status: &'static str,
+    pub connection: ConnectionState,
+    pub database: DbLatency,
+}
+
+/// Readiness check for load balancers, from the connection state and the
+/// background database probe rather than a query of its own. Answers 503
+/// while the database isn't connected or is unreachable and 200 otherwise,
+/// with `degraded` while it's slower than `DB_LATENCY_THRESHOLD_MS`.
+#[utoipa::path(
+    tag = "status",
+    responses(
+        (status = 200, body = Readiness),
+        (status = 503, description = "The database isn't connected or is unreachable", body = Readiness),
+    )
+)]
+#[get("/ready")]
+pub async fn ready() -> HttpResponse {
+    let connection = db::connection_state();
+    let database = latency::current();
+    let (mut response, state) = match (db::ensure_connected(), database.health) {
+        (Err(_), _) | (_, DbHealth::Unreachable) => (HttpResponse::ServiceUnavailable(), "unavailable"),
+        (Ok(()), DbHealth::Ok | DbHealth::Unknown) => (HttpResponse::Ok(), "ok"),
+        (Ok(()), DbHealth::Degraded) => (HttpResponse::Ok(), "degraded"),
+    };
+    
+    response
+        .insert_header(("Cache-Control", "no-store"))
+        .json(Readiness { status: state, connection, database })
+}
@@ -0,0 +1,1031 @@
+use actix_multipart::Multipart;
+use actix_web::{delete, get, http::header, patch, post, put, web, FromRequest, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::auth::{generate_token, AuthUser};
+use crate::conditional::{self, CachePolicy};
+use crate::db::error::{Error, ErrorBody, RejectedTracksBody};
+use crate::db::lyrics::LyricsOperations;
+use crate::db::play::PlayOperations;
+use crate::db::release::ReleaseOperations;
+use crate::db::share_link::ShareLinkOperations;
+use crate::db::track::{check_attachment_room, TrackOperations};
+use crate::db::{bounded, Db, UserOperations};
+use crate::feed::audio_mime_type;
+use crate::hydrate::Hydrator;
+use crate::presence::Listener;
+use crate::storage::storage;
+use crate::{attachments, clock, live_moderation, presence, upload_limit};
+use crate::types::attachment::{Attachment, AttachmentView};
+use crate::types::audio::{AudioReplacement, AudioVersion};
+use crate::types::credit::{Credit, CreditInput, CreditResponse};
+use crate::types::id::{CommentId, TrackId, UserId};
+use crate::types::import::{TrackImportResult, TrackManifestEntry};
+use crate::types::lyrics::{LyricsFormat, LyricsParams, LyricsPatch, LyricsView};
+use crate::types::pagination::Paginated;
+use crate::types::presence::ListenerCount;
+use crate::types::release::TrackDetail;
+use crate::types::share_link::{NewShareLink, ShareLink, ShareLinkView, ShareParams};
+use crate::types::stats::{StatsParams, TrackStats, MAX_STATS_DAYS};
+use crate::types::user::{CommentFilterParams, CommentView, NewComment, PublicUser, Track, TrackPatch, TrackView};
+use super::{media, read_upload, CursorParams};
+
+/// Let `viewer` see `track` directly, or else through the share link
+/// `share`. Returns the link when it was needed; opening one counts a use.
+async fn check_access(db: &Db, track: &Track, viewer: Option<UserId>, share: Option<&str>) -> Result<Option<ShareLink>, Error> {
+    if track.is_visible_to(viewer) {
+        return Ok(None);
+    }
+    
+    // A share link never brings back a deleted track
+    let Some(token) = share.filter(|_| !track.is_deleted) else {
+        return Err(Error::TrackNotFound);
+    };
+    
+    ShareLinkOperations::new(db).redeem(token, Some(track.id)).await?
+        .map(Some)
+        .ok_or(Error::TrackNotFound)
+}
+
+/// Respond with a track's metadata, its credits linked to their users, and
+/// the releases `viewer` may see it on. Seen through a share link, the
+/// original file is only included if the link allows downloads.
+async fn track_detail(db: &Db, req: &HttpRequest, track: Track, viewer: Option<UserId>, link: Option<ShareLink>) -> Result<HttpResponse, Error> {
+    let releases = ReleaseOperations::new(db).releases_for_track(track.id, viewer == Some(track.user_id)).await?;
+    
+    // Renaming or reordering a release changes the view too
+    let last_modified = releases.iter()
+        .map(|release| release.updated_at)
+        .chain([track.updated_at])
+        .max()
+        .unwrap_or(track.updated_at);
+    
+    // The owner may be seeing their own drafts in it
+    let personalized = !track.is_public || releases.iter().any(|release| release.published_at.is_none());
+    let policy = CachePolicy::for_viewer(personalized, conditional::DEFAULT_MAX_AGE);
+    let releases = releases.iter().filter_map(|release| release.summary_for(track.id)).collect();
+    let hydrator = Hydrator::extract(req).await?;
+    let shared_audio = link.map(|link| link.allow_download.then(|| track.audio_url.clone()));
+    let mut track = hydrator.track_views(vec![track], viewer).await?.remove(0);
+    if let Some(audio_url) = shared_audio {
+        track.audio_url = audio_url;
+    }
+    let detail = TrackDetail { releases, track };
+    conditional::json_modified(req, policy, &detail, last_modified)
+}
+
+/// A track's metadata and the releases it's on. Private tracks are only
+/// visible to their owner and to holders of one of its share links.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), ShareParams),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = TrackDetail, headers(
+            ("Last-Modified" = String, description = "When the track was last changed"),
+        )),
+        (status = 304, description = "Matches If-None-Match, or unchanged since If-Modified-Since"),
+        (status = 404, description = "Track not found, or the share link is expired or used up", body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}")]
+pub async fn get_track(
+    req: HttpRequest,
+    auth: Option<AuthUser>,
+    track_id: TrackId,
+    params: web::Query<ShareParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    let viewer = auth.map(|auth| auth.user.id);
+    
+    let link = check_access(&db, &track, viewer, params.share.as_deref()).await?;
+    track_detail(&db, &req, track, viewer, link).await
+}
+
+/// The track a share link is for, the path form of `GET /tracks/{id}?share=`
+#[utoipa::path(
+    tag = "tracks",
+    params(("token" = String, Path)),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = TrackDetail),
+        (status = 304, description = "Matches If-None-Match, or unchanged since If-Modified-Since"),
+        (status = 404, description = "Unknown, expired or used up share link", body = ErrorBody),
+    )
+)]
+#[get("/share/{token}")]
+pub async fn get_shared_track(req: HttpRequest, auth: Option<AuthUser>, path: web::Path<String>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let link = ShareLinkOperations::new(&db).redeem(&path.into_inner(), None).await?.ok_or(Error::NotFound)?;
+    let track = TrackOperations::new(&db).get_track(link.track_id).await?;
+    
+    if track.is_deleted {
+        return Err(Error::TrackNotFound);
+    }
+    
+    // Those who'd see the track anyway see it as usual
+    let viewer = auth.map(|auth| auth.user.id);
+    let link = Some(link).filter(|_| !track.is_visible_to(viewer));
+    track_detail(&db, &req, track, viewer, link).await
+}
+
+/// The track going by a slug, under the same rules as `GET /tracks/{id}`.
+/// A slug the track had before it was renamed redirects to its current one.
+#[utoipa::path(
+    tag = "tracks",
+    params(("slug" = String, Path), ShareParams),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = TrackDetail),
+        (status = 301, description = "Redirect from an earlier slug to the current one"),
+        (status = 304, description = "Matches If-None-Match, or unchanged since If-Modified-Since"),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/t/{slug}")]
+pub async fn get_track_by_slug(
+    req: HttpRequest,
+    auth: Option<AuthUser>,
+    path: web::Path<String>,
+    params: web::Query<ShareParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (track, current) = TrackOperations::new(&db).get_track_by_slug(&path.into_inner()).await?;
+    let viewer = auth.map(|auth| auth.user.id);
+    
+    // Only tracks the caller sees anyway redirect, so a share link isn't
+    // used up twice
+    if let Some(slug) = track.slug.as_deref().filter(|_| !current && track.is_visible_to(viewer)) {
+        return Ok(HttpResponse::MovedPermanently()
+            .insert_header((header::LOCATION, format!("/t/{}", slug)))
+            .finish());
+    }
+    
+    let link = check_access(&db, &track, viewer, params.share.as_deref()).await?;
+    track_detail(&db, &req, track, viewer, link).await
+}
+
+/// Change some of a track's metadata. Only the owner may edit a track. A
+/// license change keeps the old license in the track's history, and only
+/// Creative Commons tracks can be made downloadable without `download_override`.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    request_body = TrackPatch,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = TrackView),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[patch("/tracks/{track_id}")]
+pub async fn patch_track(auth: AuthUser, track_id: TrackId, body: web::Json<TrackPatch>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).patch_track(track_id, auth.user.id, body.into_inner()).await?;
+    
+    Ok(HttpResponse::Ok().json(TrackView::for_viewer(track, Some(auth.user.id))))
+}
+
+/// A track's lyrics as plain text, as LRC, or as JSON with the LRC lines
+/// parsed into timestamps (the default)
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), LyricsParams, ShareParams),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, description = "JSON by default, text for `plain` and `lrc`", content(
+            (LyricsView = "application/json"),
+            (String = "text/plain"),
+        )),
+        (status = 404, description = "Track not found, or no lyrics in that format", body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/lyrics")]
+pub async fn get_lyrics(
+    auth: Option<AuthUser>,
+    track_id: TrackId,
+    params: web::Query<LyricsParams>,
+    share: web::Query<ShareParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    check_access(&db, &track, auth.map(|auth| auth.user.id), share.share.as_deref()).await?;
+    
+    let lyrics = LyricsOperations::new(&db).get_lyrics(track.id).await?.ok_or(Error::NotFound)?;
+    let text = match params.format {
+        LyricsFormat::Json => return Ok(HttpResponse::Ok().json(lyrics.view())),
+        LyricsFormat::Plain => lyrics.plain,
+        LyricsFormat::Lrc => lyrics.lrc,
+    };
+    
+    Ok(HttpResponse::Ok()
+        .content_type("text/plain; charset=utf-8")
+        .body(text.ok_or(Error::NotFound)?))
+}
+
+/// Set or clear a track's plain and timestamped lyrics. Only the owner may
+/// edit them. LRC lines must be in order and within the track's duration.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    request_body = LyricsPatch,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = LyricsView),
+        (status = 204, description = "Both variants cleared"),
+        (status = 400, description = "Too large, or invalid LRC with the offending line numbers", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[patch("/tracks/{track_id}/lyrics")]
+pub async fn patch_lyrics(auth: AuthUser, track_id: TrackId, body: web::Json<LyricsPatch>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let lyrics = LyricsOperations::new(&db).set_lyrics(track_id, auth.user.id, body.into_inner()).await?;
+    
+    Ok(match lyrics {
+        Some(lyrics) => HttpResponse::Ok().json(lyrics.view()),
+        None => HttpResponse::NoContent().finish(),
+    })
+}
+
+/// Download the original audio of a track. Anyone may download a public track
+/// the creator marked downloadable; the owner can always download their own.
+/// A share link only allows downloads if it was made with `allow_download`.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), ShareParams),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, description = "The original audio file", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 302, description = "Redirect to audio hosted elsewhere"),
+        (status = 403, description = "Track is not downloadable", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/download")]
+pub async fn download_track(
+    auth: Option<AuthUser>,
+    track_id: TrackId,
+    params: web::Query<ShareParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    let viewer = auth.map(|auth| auth.user.id);
+    let is_owner = viewer == Some(track.user_id);
+    
+    // Hide private and deleted tracks entirely from everyone but the owner
+    let allowed = match check_access(&db, &track, viewer, params.share.as_deref()).await? {
+        Some(link) => link.allow_download,
+        None => track.downloadable || is_owner,
+    };
+    if !allowed {
+        return Err(Error::Forbidden);
+    }
+    
+    let track = TrackOperations::new(&db).increment_download_count(track.id).await?;
+    let filename = match track.audio_url.rsplit_once('.') {
+        Some((_, ext)) => format!("{}.{}", track.title, ext),
+        None => track.title.clone(),
+    };
+    
+    media::send_stored(&track.audio_url, audio_content_type(&track), Some(&filename)).await
+}
+
+/// Play a track. Anyone who may see it may, downloadable or not. Through a
+/// share link this uses the link up like opening it does.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), ShareParams),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, description = "The audio", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 302, description = "Redirect to audio hosted elsewhere"),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/stream")]
+pub async fn stream_track(
+    auth: Option<AuthUser>,
+    track_id: TrackId,
+    params: web::Query<ShareParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    
+    check_access(&db, &track, auth.map(|auth| auth.user.id), params.share.as_deref()).await?;
+    media::send_stored(&track.audio_url, audio_content_type(&track), None).await
+}
+
+/// The audio's content type, from its analysed format or else its file name
+fn audio_content_type(track: &Track) -> &'static str {
+    let format = match &track.technical_metadata {
+        Some(meta) => Some(meta.format.as_str()),
+        None => track.audio_url.rsplit_once('.').map(|(_, ext)| ext),
+    };
+    format.map_or("application/octet-stream", audio_mime_type)
+}
+
+/// Count a play of a track, reported by the player once playback starts.
+/// Plays aren't tied to the listener, but a signed-in listener's replays
+/// within `PLAY_DEDUP_WINDOW` count once. Plays through a share link aren't
+/// counted, since reporting one would use up the link.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("/tracks/{track_id}/plays")]
+pub async fn record_play(auth: Option<AuthUser>, track_id: TrackId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    let listener = auth.map(|auth| auth.user.id);
+    if !track.is_visible_to(listener) {
+        return Err(Error::TrackNotFound);
+    }
+    
+    PlayOperations::new(&db).record_play(track.id, listener).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// A track's likes, dislikes, plays, downloads and comments, with its plays
+/// per day over a recent window. Only the owner and admins see stats.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), StatsParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = TrackStats),
+        (status = 400, description = "Window longer than 90 days", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Not the owner or an admin", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/stats")]
+pub async fn get_track_stats(auth: AuthUser, track_id: TrackId, params: web::Query<StatsParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let days = params.days.unwrap_or(30);
+    if days == 0 || days > MAX_STATS_DAYS {
+        return Err(Error::Validation(format!("days must be between 1 and {}", MAX_STATS_DAYS)));
+    }
+    
+    // Admin rights never carry over into an impersonated session
+    let is_admin = auth.impersonator().is_none() && auth.user.profile.as_ref().is_some_and(|p| p.is_admin);
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    if !is_admin {
+        if !track.is_visible_to(Some(auth.user.id)) {
+            return Err(Error::TrackNotFound);
+        }
+        if track.user_id != auth.user.id {
+            return Err(Error::Forbidden);
+        }
+    }
+    
+    let ops = PlayOperations::new(&db);
+    let (play_count, plays) = futures_util::try_join!(
+        bounded("play count", ops.count_plays(track.id)),
+        bounded("plays by day", ops.plays_by_day(track.id, days)),
+    )?;
+    
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "private, no-store"))
+        .json(TrackStats {
+            track_id: track.id,
+            likes: track.likes,
+            dislikes: track.dislikes,
+            play_count,
+            download_count: track.download_count,
+            comment_count: track.comment_count,
+            plays,
+        }))
+}
+
+/// Tell the server a track is playing. Players ping about every 30
+/// seconds; a listener stops counting a minute after their last ping.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("/tracks/{track_id}/presence")]
+pub async fn ping_presence(req: HttpRequest, auth: Option<AuthUser>, track_id: TrackId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let viewer = auth.map(|auth| auth.user.id);
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    if !track.is_visible_to(viewer) {
+        return Err(Error::TrackNotFound);
+    }
+    
+    let listener = match viewer {
+        Some(user_id) => Listener::User(user_id),
+        None => {
+            // Forwarding headers are whatever the client says, so go by the socket
+            let ip = req.connection_info().peer_addr().unwrap_or("unknown").to_string();
+            let user_agent = req.headers().get(header::USER_AGENT).and_then(|value| value.to_str().ok()).unwrap_or_default();
+            Listener::anonymous(&ip, user_agent)
+        }
+    };
+    presence::ping(track.id, listener);
+    
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// How many are listening to a track right now
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = ListenerCount),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/listeners")]
+pub async fn get_listener_count(auth: Option<AuthUser>, track_id: TrackId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    if !track.is_visible_to(auth.map(|auth| auth.user.id)) {
+        return Err(Error::TrackNotFound);
+    }
+    
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "no-store"))
+        .json(ListenerCount { track_id: track.id, listeners: presence::listener_count(track.id) }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TrackManifest {
+    pub tracks: Vec<TrackManifestEntry>,
+}
+
+/// Import tracks into the caller's uploads from a manifest of already
+/// uploaded files. Valid entries are added together; invalid ones are
+/// reported per entry without failing the import.
+#[utoipa::path(
+    tag = "tracks",
+    request_body = TrackManifest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<TrackImportResult>),
+        (status = 400, description = "Manifest is too large", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 429, description = "Too many uploads in flight", body = ErrorBody),
+    )
+)]
+#[post("/tracks/import")]
+pub async fn import_tracks(auth: AuthUser, body: web::Json<TrackManifest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let _permit = upload_limit::acquire(auth.user.id)?;
+    let results = TrackOperations::new(&db).import_manifest(auth.user.id, body.into_inner().tracks).await?;
+    
+    Ok(HttpResponse::Ok().json(results))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct BulkDeleteTracks {
+    pub track_ids: Vec<TrackId>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BulkDeleteResult {
+    pub deleted: Vec<TrackId>,
+}
+
+/// Delete several of the caller's tracks at once. Either all of them are
+/// deleted or, if any isn't theirs or doesn't exist, none are and those ids
+/// are listed in the 403.
+#[utoipa::path(
+    tag = "tracks",
+    request_body = BulkDeleteTracks,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = BulkDeleteResult),
+        (status = 400, description = "No tracks, or too many", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Some tracks aren't the caller's", body = RejectedTracksBody),
+    )
+)]
+#[post("/me/tracks/delete")]
+pub async fn bulk_delete_tracks(auth: AuthUser, body: web::Json<BulkDeleteTracks>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let deleted = TrackOperations::new(&db).bulk_soft_delete(auth.user.id, &body.track_ids).await?;
+    
+    Ok(HttpResponse::Ok().json(BulkDeleteResult { deleted }))
+}
+
+/// Top-level comments on a track, newest first. Deleted comments are left
+/// out unless a moderator asks for them.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), CursorParams, CommentFilterParams),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = Paginated<CommentView>),
+        (status = 400, description = "Invalid cursor", body = ErrorBody),
+        (status = 403, description = "The filter is for moderators", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/comments")]
+pub async fn list_comments(
+    auth: Option<AuthUser>,
+    hydrator: Hydrator,
+    track_id: TrackId,
+    params: web::Query<CursorParams>,
+    filter: web::Query<CommentFilterParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (limit, cursor) = params.page()?;
+    let filter = filter.filter;
+    // Same rule as the moderation stream: impersonated sessions don't count
+    if filter.requires_moderator() && !auth.as_ref().is_some_and(|auth| auth.impersonator().is_none() && live_moderation::is_moderator(&auth.user)) {
+        return Err(Error::Forbidden);
+    }
+    let viewer = auth.map(|auth| auth.user.id);
+    
+    let (comments, next) = TrackOperations::new(&db).list_comments(track_id, viewer, filter, cursor, limit).await?;
+    let authors = hydrator.users(comments.iter().map(|comment| comment.user_id)).await?;
+    
+    let comments = comments
+        .into_iter()
+        .map(|comment| {
+            let author = authors.get(&comment.user_id)
+                .filter(|author| author.profile.as_ref().is_some_and(|p| !p.is_deleted()))
+                .cloned()
+                .map(PublicUser::from);
+            CommentView::new(comment, author)
+        })
+        .collect();
+    
+    Ok(HttpResponse::Ok().json(Paginated::with_cursor(comments, limit, next)))
+}
+
+/// Comment on a track, or reply to one of its comments
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    request_body = NewComment,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = CommentView),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("/tracks/{track_id}/comments")]
+pub async fn add_comment(auth: AuthUser, track_id: TrackId, body: web::Json<NewComment>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    
+    if !track.is_visible_to(Some(auth.user.id)) {
+        return Err(Error::TrackNotFound);
+    }
+    
+    let NewComment { content, parent_comment_id } = body.into_inner();
+    let comment = TrackOperations::new(&db).add_comment(track.id, auth.user.id, content, parent_comment_id).await?;
+    Ok(HttpResponse::Created().json(CommentView::new(comment, Some(PublicUser::from(auth.user)))))
+}
+
+/// Delete a comment or reply. Its author and the track's owner may delete it.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), ("comment_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/tracks/{track_id}/comments/{comment_id}")]
+pub async fn delete_comment(auth: AuthUser, track_id: TrackId, comment_id: CommentId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    
+    if !track.is_visible_to(Some(auth.user.id)) {
+        return Err(Error::TrackNotFound);
+    }
+    
+    TrackOperations::new(&db).delete_comment(track.id, comment_id, auth.user.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Check that `user_id` owns `track_id`, hiding tracks they couldn't see anyway
+async fn get_owned_track(db: &Db, track_id: TrackId, user_id: UserId) -> Result<Track, Error> {
+    let track = TrackOperations::new(db).get_track(track_id).await?;
+    
+    if !track.is_visible_to(Some(user_id)) {
+        return Err(Error::TrackNotFound);
+    }
+    if track.user_id != user_id {
+        return Err(Error::Forbidden);
+    }
+    
+    Ok(track)
+}
+
+/// Make a secret link that lets anyone holding it see and play the track,
+/// even while it's private. It can expire, be limited to a number of uses
+/// and allow downloading the original audio. Only the owner may share.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    request_body = NewShareLink,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = ShareLinkView),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Too many share links on the track", body = ErrorBody),
+    )
+)]
+#[post("/tracks/{track_id}/share-links")]
+pub async fn create_share_link(auth: AuthUser, track_id: TrackId, body: web::Json<NewShareLink>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = get_owned_track(&db, track_id, auth.user.id).await?;
+    let token = generate_token();
+    let link = body.into_inner().into_link(track.id, auth.user.id, &token)?;
+    let link = ShareLinkOperations::new(&db).create_link(link).await?;
+    
+    Ok(HttpResponse::Created().json(ShareLinkView::with_token(link, token)))
+}
+
+/// A track's share links, newest first, with how often and when each was
+/// last used
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<ShareLinkView>),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/share-links")]
+pub async fn list_share_links(auth: AuthUser, track_id: TrackId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = get_owned_track(&db, track_id, auth.user.id).await?;
+    let links = ShareLinkOperations::new(&db).list_links(track.id).await?;
+    
+    Ok(HttpResponse::Ok().json(links.into_iter().map(ShareLinkView::from).collect::<Vec<_>>()))
+}
+
+/// Revoke a share link. It stops working at once.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), ("link_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/tracks/{track_id}/share-links/{link_id}")]
+pub async fn revoke_share_link(auth: AuthUser, path: web::Path<(TrackId, Uuid)>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let (track_id, link_id) = path.into_inner();
+    let track = get_owned_track(&db, track_id, auth.user.id).await?;
+    let link = ShareLinkOperations::new(&db).get_link(track.id, link_id).await?;
+    
+    ShareLinkOperations::new(&db).delete_link(link.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Swap a track's audio for a newly uploaded file, e.g. a fixed master.
+/// Likes, downloads, comments and lyrics stay with the track, and the old
+/// file is kept among its audio versions. Only the owner may replace audio.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    request_body = AudioReplacement,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = TrackView),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[post("/tracks/{track_id}/audio/replace")]
+pub async fn replace_audio(auth: AuthUser, track_id: TrackId, body: web::Json<AudioReplacement>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).replace_audio(track_id, auth.user.id, body.into_inner()).await?;
+    
+    Ok(HttpResponse::Ok().json(TrackView::for_viewer(track, Some(auth.user.id))))
+}
+
+/// The audio files a track played before, oldest first. Only the owner
+/// sees them.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<AudioVersion>),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/audio/versions")]
+pub async fn list_audio_versions(auth: AuthUser, track_id: TrackId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = get_owned_track(&db, track_id, auth.user.id).await?;
+    
+    Ok(HttpResponse::Ok().json(track.audio_versions))
+}
+
+/// Go back to an earlier audio file. The current one becomes a version.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), ("version_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = TrackView),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, description = "Track or version not found", body = ErrorBody),
+    )
+)]
+#[post("/tracks/{track_id}/audio/versions/{version_id}/restore")]
+pub async fn restore_audio(auth: AuthUser, path: web::Path<(TrackId, Uuid)>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let (track_id, version_id) = path.into_inner();
+    let track = TrackOperations::new(&db).restore_audio(track_id, auth.user.id, version_id).await?;
+    
+    Ok(HttpResponse::Ok().json(TrackView::for_viewer(track, Some(auth.user.id))))
+}
+
+#[derive(ToSchema)]
+pub struct AttachmentUpload {
+    /// One of the accepted attachment types, named with its extension
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+}
+
+/// Attach a file to a track, such as a stem, a project file or sheet music.
+/// Only the owner may add attachments.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    request_body(content = AttachmentUpload, content_type = "multipart/form-data"),
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = AttachmentView),
+        (status = 400, description = "Empty file, or a missing or overlong name", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+        (status = 409, description = "Track already has the most attachments allowed", body = ErrorBody),
+        (status = 413, description = "File too large, or the track's attachments would be", body = ErrorBody),
+        (status = 415, description = "Not an accepted file type", body = ErrorBody),
+        (status = 429, description = "Too many uploads in flight", body = ErrorBody),
+    )
+)]
+#[post("/tracks/{track_id}/attachments")]
+pub async fn upload_attachment(auth: AuthUser, track_id: TrackId, payload: Multipart, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let _permit = upload_limit::acquire(auth.user.id)?;
+    let track = get_owned_track(&db, track_id, auth.user.id).await?;
+    
+    let upload = read_upload(payload, attachments::max_bytes()).await?;
+    let file = attachments::check_file(upload.filename.as_deref(), upload.bytes.len())?;
+    let size = upload.bytes.len() as u64;
+    check_attachment_room(&track, size)?;
+    
+    let id = Uuid::new_v4();
+    let key = format!("attachments/{}/{}.{}", track.id, id, file.extension);
+    let url = storage().put(&key, upload.bytes).await?;
+    let attachment = Attachment {
+        id,
+        name: file.name,
+        content_type: file.content_type.to_string(),
+        size,
+        url,
+        download_count: 0,
+        created_at: clock::now(),
+    };
+    
+    if let Err(e) = TrackOperations::new(&db).add_attachment(track.id, auth.user.id, attachment.clone()).await {
+        // Nothing refers to the file, so losing track of it only wastes space
+        if let Err(e) = storage().delete(&key).await {
+            warn!("Failed to delete unsaved attachment {}: {}", key, e);
+        }
+        return Err(e);
+    }
+    
+    Ok(HttpResponse::Created().json(AttachmentView::from(attachment)))
+}
+
+/// The files attached to a track, oldest first
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), ShareParams),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<AttachmentView>),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/attachments")]
+pub async fn list_attachments(
+    auth: Option<AuthUser>,
+    track_id: TrackId,
+    params: web::Query<ShareParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    check_access(&db, &track, auth.map(|auth| auth.user.id), params.share.as_deref()).await?;
+    
+    let attachments: Vec<AttachmentView> = track.attachments.into_iter().map(AttachmentView::from).collect();
+    Ok(HttpResponse::Ok().json(attachments))
+}
+
+/// Download an attachment. Anyone who can see the track may when it's under
+/// a Creative Commons license; otherwise only the owner can. A share link
+/// must also allow downloads. Counted apart from the track's own downloads.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), ("attachment_id" = Uuid, Path), ShareParams),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, description = "The attached file", content_type = "application/octet-stream", body = Vec<u8>),
+        (status = 302, description = "Redirect to a file hosted elsewhere"),
+        (status = 403, description = "The track's license or the share link doesn't allow it", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/attachments/{attachment_id}/download")]
+pub async fn download_attachment(
+    auth: Option<AuthUser>,
+    path: web::Path<(TrackId, Uuid)>,
+    params: web::Query<ShareParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (track_id, attachment_id) = path.into_inner();
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    let viewer = auth.map(|auth| auth.user.id);
+    
+    let link = check_access(&db, &track, viewer, params.share.as_deref()).await?;
+    if link.is_some_and(|link| !link.allow_download) {
+        return Err(Error::Forbidden);
+    }
+    if !track.license.is_creative_commons() && viewer != Some(track.user_id) {
+        return Err(Error::Forbidden);
+    }
+    if !track.attachments.iter().any(|attachment| attachment.id == attachment_id) {
+        return Err(Error::NotFound);
+    }
+    
+    let attachment = TrackOperations::new(&db).increment_attachment_downloads(track.id, attachment_id).await?;
+    
+    media::send_stored(&attachment.url, &attachment.content_type, Some(&attachment.name)).await
+}
+
+/// Remove an attachment and its file. Only the owner may.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), ("attachment_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/tracks/{track_id}/attachments/{attachment_id}")]
+pub async fn delete_attachment(auth: AuthUser, path: web::Path<(TrackId, Uuid)>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let (track_id, attachment_id) = path.into_inner();
+    let attachment = TrackOperations::new(&db).remove_attachment(track_id, auth.user.id, attachment_id).await?;
+    
+    // Left behind, the file would only waste space until the next GC run
+    if let Some(key) = storage().key_for_url(&attachment.url) {
+        if let Err(e) = storage().delete(&key).await {
+            warn!("Failed to delete removed attachment {}: {}", key, e);
+        }
+    }
+    
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Pin one of the caller's public tracks to the top of their profile. At
+/// most three can be pinned; a fourth is refused until one is unpinned.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 400, description = "The track isn't public", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, description = "Not one of the caller's tracks", body = ErrorBody),
+        (status = 409, description = "Three tracks are pinned already", body = ErrorBody),
+    )
+)]
+#[post("/tracks/{track_id}/pin")]
+pub async fn pin_track(auth: AuthUser, track_id: TrackId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    UserOperations::new(&db).pin_track(auth.user.id, track_id).await?;
+    
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Unpin a track from the caller's profile
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[delete("/tracks/{track_id}/pin")]
+pub async fn unpin_track(auth: AuthUser, track_id: TrackId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    UserOperations::new(&db).unpin_track(auth.user.id, track_id).await?;
+    
+    Ok(HttpResponse::NoContent().finish())
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreditList {
+    pub credits: Vec<CreditInput>,
+}
+
+/// Replace the artists credited on a track, in the order given. Registered
+/// users are invited and only linked once they accept; until then, and if
+/// they decline, the credit shows as a plain name. Only the owner may edit
+/// credits, and sees where each one stands.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    request_body = CreditList,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<Credit>),
+        (status = 400, description = "Too many credits, a duplicate, an unknown user or the owner", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[put("/tracks/{track_id}/credits")]
+pub async fn set_credits(auth: AuthUser, track_id: TrackId, body: web::Json<CreditList>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = TrackOperations::new(&db).set_credits(track_id, auth.user.id, body.into_inner().credits).await?;
+    
+    Ok(HttpResponse::Ok().json(track.credited_artists))
+}
+
+/// The artists credited on a track and where each invitation stands. Only
+/// the owner sees this.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<Credit>),
+        (status = 401, body = ErrorBody),
+        (status = 403, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/tracks/{track_id}/credits")]
+pub async fn list_credits(auth: AuthUser, track_id: TrackId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let track = get_owned_track(&db, track_id, auth.user.id).await?;
+    
+    Ok(HttpResponse::Ok().json(track.credited_artists))
+}
+
+/// Accept or decline being credited on a track. Accepting links the credit
+/// to the caller's profile and lists the track under their appearances;
+/// they can also opt in to the track's comment notifications.
+#[utoipa::path(
+    tag = "tracks",
+    params(("track_id" = Uuid, Path), ("credit_id" = Uuid, Path)),
+    request_body = CreditResponse,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Credit),
+        (status = 401, body = ErrorBody),
+        (status = 404, description = "No such credit naming the caller", body = ErrorBody),
+    )
+)]
+#[post("/tracks/{track_id}/credits/{credit_id}/respond")]
+pub async fn respond_to_credit(
+    auth: AuthUser,
+    path: web::Path<(TrackId, Uuid)>,
+    body: web::Json<CreditResponse>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (track_id, credit_id) = path.into_inner();
+    let credit = TrackOperations::new(&db).respond_to_credit(track_id, credit_id, auth.user.id, body.into_inner()).await?;
+    
+    Ok(HttpResponse::Ok().json(credit))
+}
@@ -0,0 +1,235 @@
+use actix_web::{delete, get, http::header, patch, post, web, HttpRequest, HttpResponse};
+use futures_util::TryStreamExt;
+use tracing::warn;
+use uuid::Uuid;
+use crate::auth::AuthUser;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::track::TrackOperations;
+use crate::db::upload::UploadOperations;
+use crate::db::Db;
+use crate::storage::storage;
+use crate::{realtime, resumable, upload_limit};
+use crate::types::import::TrackManifestEntry;
+use crate::types::realtime::{ProcessingState, ServerMessage};
+use crate::types::upload::{ByteRange, FinalizeUpload, NewUpload, UploadStatus};
+use crate::types::user::TrackView;
+
+/// Start a resumable audio upload. Send the file's bytes in chunks with
+/// `PATCH /uploads/{id}`, then make it a track with `POST /uploads/{id}/finalize`.
+/// Unfinished uploads expire after a day.
+#[utoipa::path(
+    tag = "tracks",
+    request_body = NewUpload,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = UploadStatus, headers(
+            ("Location" = String, description = "Where to send the chunks"),
+        )),
+        (status = 400, description = "Empty file", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 413, description = "File larger than AUDIO_MAX_MB", body = ErrorBody),
+        (status = 415, description = "Not an accepted audio type", body = ErrorBody),
+    )
+)]
+#[post("/uploads")]
+pub async fn create_upload(auth: AuthUser, body: web::Json<NewUpload>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    // Starting an upload is a good time to clear out abandoned ones
+    for expired in UploadOperations::new(&db).delete_expired().await? {
+        if let Err(e) = resumable::remove_file(expired.id).await {
+            warn!("Failed to remove staging file of expired upload {}: {}", expired.id, e);
+        }
+    }
+    
+    let upload = body.into_inner().into_upload(auth.user.id)?;
+    let upload = UploadOperations::new(&db).create_upload(upload).await?;
+    
+    Ok(HttpResponse::Created()
+        .insert_header((header::LOCATION, format!("/uploads/{}", upload.id)))
+        .json(UploadStatus::from(upload)))
+}
+
+/// What's been received of an upload, so an interrupted client knows what
+/// it still has to send
+#[utoipa::path(
+    tag = "tracks",
+    params(("upload_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = UploadStatus),
+        (status = 401, body = ErrorBody),
+        (status = 404, description = "No such upload, or it expired", body = ErrorBody),
+    )
+)]
+#[get("/uploads/{upload_id}")]
+pub async fn get_upload(auth: AuthUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let upload = UploadOperations::new(&db).get_upload(path.into_inner(), auth.user.id).await?;
+    
+    Ok(HttpResponse::Ok().json(UploadStatus::from(upload)))
+}
+
+/// Send one chunk of an upload, placed by its `Content-Range` header, e.g.
+/// `bytes 0-1048575/5000000`. Chunks may come in any order, and sending one
+/// again is harmless.
+#[utoipa::path(
+    tag = "tracks",
+    params(
+        ("upload_id" = Uuid, Path),
+        ("Content-Range" = String, Header, description = "bytes <first>-<last>/<size>, both ends inclusive"),
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = UploadStatus),
+        (status = 400, description = "Missing or invalid Content-Range, or a body of another length", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, description = "No such upload, or it expired", body = ErrorBody),
+        (status = 409, description = "Content-Range is for a file of another size, or the upload has taken too many chunks", body = ErrorBody),
+        (status = 413, description = "Chunk larger than 16 MiB", body = ErrorBody),
+        (status = 429, description = "Too many uploads in flight", body = ErrorBody),
+    )
+)]
+#[patch("/uploads/{upload_id}")]
+pub async fn upload_chunk(
+    req: HttpRequest,
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    mut payload: web::Payload,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let _permit = upload_limit::acquire(auth.user.id)?;
+    let upload = UploadOperations::new(&db).get_upload(path.into_inner(), auth.user.id).await?;
+    
+    let content_range = req.headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| Error::Validation("Content-Range is required".to_string()))?;
+    let (start, end, size) = resumable::parse_content_range(content_range)?;
+    if size != upload.size {
+        return Err(Error::Conflict(format!("the upload is {} bytes, not {}", upload.size, size)));
+    }
+    let length = (end - start) as usize;
+    if length > resumable::MAX_CHUNK_BYTES {
+        return Err(Error::PayloadTooLarge);
+    }
+    if upload.chunks.len() >= resumable::MAX_CHUNKS {
+        return Err(Error::Conflict(format!("an upload may take at most {} chunks", resumable::MAX_CHUNKS)));
+    }
+    
+    let mut bytes = Vec::with_capacity(length);
+    while let Some(chunk) = payload.try_next().await.map_err(|e| Error::Validation(e.to_string()))? {
+        if bytes.len() + chunk.len() > length {
+            return Err(Error::Validation("body is longer than its Content-Range".to_string()));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    if bytes.len() != length {
+        return Err(Error::Validation("body is shorter than its Content-Range".to_string()));
+    }
+    
+    resumable::write_chunk(upload.id, start, bytes).await?;
+    let upload = UploadOperations::new(&db).record_chunk(upload.id, ByteRange { start, end }).await?;
+    
+    Ok(HttpResponse::Ok().json(UploadStatus::from(upload)))
+}
+
+/// Make a complete upload into a track owned by the caller. The upload is
+/// gone afterwards.
+#[utoipa::path(
+    tag = "tracks",
+    params(("upload_id" = Uuid, Path)),
+    request_body = FinalizeUpload,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = TrackView),
+        (status = 400, description = "Invalid track metadata", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 404, description = "No such upload, or it expired", body = ErrorBody),
+        (status = 409, description = "Some of the file hasn't been received yet", body = ErrorBody),
+        (status = 429, description = "Too many uploads in flight", body = ErrorBody),
+    )
+)]
+#[post("/uploads/{upload_id}/finalize")]
+pub async fn finalize_upload(auth: AuthUser, path: web::Path<Uuid>, body: web::Json<FinalizeUpload>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let _permit = upload_limit::acquire(auth.user.id)?;
+    let upload = UploadOperations::new(&db).get_upload(path.into_inner(), auth.user.id).await?;
+    
+    if !upload.is_complete() {
+        let received: u64 = upload.received().iter().map(|range| range.end - range.start).sum();
+        let missing = upload.size - received;
+        return Err(Error::Conflict(format!("{} bytes of the file are still missing", missing)));
+    }
+    
+    let bytes = resumable::read_file(upload.id).await?;
+    if bytes.len() as u64 != upload.size {
+        return Err(Error::Conflict("the staged file doesn't match the upload's size".to_string()));
+    }
+    
+    let key = format!("audio/{}/{}.{}", auth.user.id, upload.id, upload.extension);
+    let audio_url = storage().put(&key, bytes).await?;
+    
+    let details = body.into_inner();
+    let entry = TrackManifestEntry {
+        title: details.title,
+        description: details.description,
+        audio_url,
+        cover_image_url: details.cover_image_url,
+        genre: details.genre,
+        tags: details.tags,
+        is_public: details.is_public,
+        downloadable: details.downloadable,
+        license: details.license,
+        download_override: details.download_override,
+        created_at: None,
+        technical_metadata: details.technical_metadata,
+        language: details.language,
+        is_explicit: details.is_explicit,
+    };
+    let created = TrackOperations::new(&db).import_manifest(auth.user.id, vec![entry]).await
+        .and_then(|mut results| {
+            let result = results.remove(0);
+            result.track_id.ok_or_else(|| Error::Validation(result.error.unwrap_or_default()))
+        });
+    let track_id = match created {
+        Ok(track_id) => track_id,
+        Err(e) => {
+            // Keep the staged file so the client can fix the metadata and retry
+            if let Err(e) = storage().delete(&key).await {
+                warn!("Failed to delete unsaved audio {}: {}", key, e);
+            }
+            return Err(e);
+        }
+    };
+    
+    UploadOperations::new(&db).delete_upload(upload.id).await?;
+    if let Err(e) = resumable::remove_file(upload.id).await {
+        warn!("Failed to remove staging file of upload {}: {}", upload.id, e);
+    }
+    
+    // Other tabs and devices watching the upload learn it's done
+    let status = ServerMessage::ProcessingStatus { upload_id: upload.id, track_id: Some(track_id), status: ProcessingState::Ready };
+    realtime::push(auth.user.id, &status);
+    
+    let track = TrackOperations::new(&db).get_track(track_id).await?;
+    Ok(HttpResponse::Created().json(TrackView::for_viewer(track, Some(auth.user.id))))
+}
+
+/// Abandon an upload and discard what was sent
+#[utoipa::path(
+    tag = "tracks",
+    params(("upload_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/uploads/{upload_id}")]
+pub async fn cancel_upload(auth: AuthUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let upload = UploadOperations::new(&db).get_upload(path.into_inner(), auth.user.id).await?;
+    
+    UploadOperations::new(&db).delete_upload(upload.id).await?;
+    resumable::remove_file(upload.id).await?;
+    
+    Ok(HttpResponse::NoContent().finish())
+}
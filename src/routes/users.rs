@@ -0,0 +1,666 @@
+use actix_multipart::Multipart;
+use actix_web::http::header;
+use actix_web::{get, post, put, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+use tracing::warn;
+use uuid::Uuid;
+use crate::auth::{verify_password, AuthUser};
+use crate::conditional::{self, CachePolicy};
+use crate::db::email_token::EmailTokenOperations;
+use crate::db::erasure::ErasureOperations;
+use crate::db::notification::NotificationOperations;
+use crate::db::error::{Error, ErrorBody};
+use crate::db::release::ReleaseOperations;
+use crate::db::track::TrackOperations;
+use crate::db::verification::VerificationOperations;
+use crate::db::{bounded, Db, UserOperations};
+use crate::hydrate::Hydrator;
+use crate::images::{self, ProfileImage};
+use crate::storage::storage;
+use crate::{email, erasure, export, geocoding, maintenance, presence, upload_limit};
+use crate::types::email_token::EmailTokenPurpose;
+use crate::types::erasure::ErasureJob;
+use crate::types::id::{TrackId, UserId};
+use crate::types::license::LicenseFilter;
+use crate::types::location::Location;
+use crate::types::pagination::Paginated;
+use crate::types::presence::{NowPlaying, NowPlayingSettings};
+use crate::types::release::ReleaseView;
+use crate::types::user::{normalize_username, FollowSuggestion, ProfileTrackOrder, ProfileView, PublicUser, TrackView, User};
+use crate::types::verification::{NewVerificationRequest, VerificationRequest};
+use super::{paged, read_upload, releases, CursorParams};
+
+#[derive(Deserialize, ToSchema)]
+pub struct EraseCodeRequest {
+    pub password: String,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct EraseRequest {
+    pub password: String,
+    /// The code from the confirmation email
+    pub code: String,
+}
+
+/// Email the caller the code that confirms erasing their account. The code
+/// works once, for fifteen minutes.
+#[utoipa::path(
+    tag = "users",
+    request_body = EraseCodeRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 202, description = "The email is on its way"),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Impersonated session", body = ErrorBody),
+        (status = 409, description = "Account is under legal hold", body = ErrorBody),
+        (status = 429, description = "An email was sent less than a minute ago", body = ErrorBody),
+    )
+)]
+#[post("/users/me/erase/code")]
+pub async fn send_erase_code(auth: AuthUser, body: web::Json<EraseCodeRequest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    auth.reject_impersonation()?;
+    let user = auth.user;
+    
+    if !verify_password(&body.password, &user.hashed_password) {
+        return Err(Error::InvalidCredentials);
+    }
+    
+    if user.legal_hold {
+        return Err(Error::LegalHold);
+    }
+    
+    let code = EmailTokenOperations::new(&db).issue(user.id, &user.email, EmailTokenPurpose::EraseAccount).await?;
+    email::send(email::erase_confirmation(&user.email, &user.username, &code));
+    
+    Ok(HttpResponse::Accepted().finish())
+}
+
+/// Irreversibly erase the caller's account, confirmed with their password
+/// and the code from `/users/me/erase/code`. The work happens in a background
+/// job, so this returns 202 with the job for the client to keep a reference to.
+#[utoipa::path(
+    tag = "users",
+    request_body = EraseRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 202, description = "Erasure queued", body = ErasureJob),
+        (status = 400, description = "Invalid, expired or used code", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Impersonated session", body = ErrorBody),
+        (status = 409, description = "Account is under legal hold", body = ErrorBody),
+    )
+)]
+#[post("/users/me/erase")]
+pub async fn erase_me(auth: AuthUser, body: web::Json<EraseRequest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    auth.reject_impersonation()?;
+    let user = auth.user;
+    
+    if !verify_password(&body.password, &user.hashed_password) {
+        return Err(Error::InvalidCredentials);
+    }
+    
+    if user.legal_hold {
+        return Err(Error::LegalHold);
+    }
+    
+    // A code only confirms the account and address it was sent for
+    let token = EmailTokenOperations::new(&db).redeem(&body.code, EmailTokenPurpose::EraseAccount).await?;
+    if token.user_id != user.id || !user.email.eq_ignore_ascii_case(&token.email) {
+        return Err(Error::Validation("invalid or expired code".to_string()));
+    }
+    
+    let job = ErasureOperations::new(&db).create_job(user.id).await?;
+    erasure::spawn(Db::clone(&db), job.clone());
+    
+    Ok(HttpResponse::Accepted().json(job))
+}
+
+/// Download everything stored about the caller as a ZIP archive. It holds
+/// a JSON file for each kind of data and a copy of every file they
+/// uploaded. The archive is streamed while it's built, so it starts at once
+/// whatever its size.
+#[utoipa::path(
+    tag = "users",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, content_type = "application/zip", body = Vec<u8>),
+        (status = 401, body = ErrorBody),
+        (status = 403, description = "Impersonated session", body = ErrorBody),
+    )
+)]
+#[get("/me/export")]
+pub async fn export_me(auth: AuthUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    auth.reject_impersonation()?;
+    
+    Ok(HttpResponse::Ok()
+        .content_type(export::CONTENT_TYPE)
+        .insert_header((header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", export::filename())))
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .streaming(export::stream(Db::clone(&db), auth.user)))
+}
+
+/// Apply for the verified badge with links and evidence for an admin to
+/// review. A rejected user has to wait before applying again.
+#[utoipa::path(
+    tag = "users",
+    request_body = NewVerificationRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = VerificationRequest),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 409, description = "Already verified, or a request is pending", body = ErrorBody),
+        (status = 429, description = "Rejected too recently to apply again", body = ErrorBody),
+    )
+)]
+#[post("/users/me/verification")]
+pub async fn apply_for_verification(auth: AuthUser, body: web::Json<NewVerificationRequest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let request = body.into_inner().into_request(auth.user.id)?;
+    let request = VerificationOperations::new(&db).create_request(request).await?;
+    
+    Ok(HttpResponse::Created().json(request))
+}
+
+/// The caller's latest verification request and its outcome
+#[utoipa::path(
+    tag = "users",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = VerificationRequest),
+        (status = 401, body = ErrorBody),
+        (status = 404, description = "Never applied", body = ErrorBody),
+    )
+)]
+#[get("/users/me/verification")]
+pub async fn get_verification(auth: AuthUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let request = VerificationOperations::new(&db).latest_for_user(auth.user.id).await?.ok_or(Error::NotFound)?;
+    
+    Ok(HttpResponse::Ok().json(request))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct LocationRequest {
+    /// ISO 3166-1 alpha-2 code
+    pub country: Option<String>,
+    pub region: Option<String>,
+    pub city: Option<String>,
+    /// Free text shown on the profile
+    pub display: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct LocationResponse {
+    pub location: Option<String>,
+    pub structured_location: Option<Location>,
+}
+
+/// Set the caller's location. Sending only `display` stores free text, which
+/// is resolved into a structured location when geocoding is enabled. Sending
+/// nothing clears the location.
+#[utoipa::path(
+    tag = "users",
+    request_body = LocationRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = LocationResponse),
+        (status = 400, description = "Unknown country code", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[put("/users/me/location")]
+pub async fn set_location(auth: AuthUser, body: web::Json<LocationRequest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let LocationRequest { country, region, city, display } = body.into_inner();
+    let display = display.map(|d| d.trim().to_string()).filter(|d| !d.is_empty());
+    
+    let location = match (country, &display) {
+        (Some(country), _) => Some(Location::new(&country, region, city)?),
+        (None, Some(display)) if geocoding::enabled() => geocoding::geocode(display),
+        (None, _) => None,
+    };
+    
+    let user = UserOperations::new(&db).set_location(auth.user.id, location, display).await?;
+    let profile = user.profile.ok_or(Error::ProfileNotFound)?;
+    
+    Ok(HttpResponse::Ok().json(LocationResponse {
+        location: profile.location,
+        structured_location: profile.structured_location,
+    }))
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TrackOrderRequest {
+    pub order: ProfileTrackOrder,
+    /// The caller's tracks in the order `manual` lists them; kept as it was
+    /// when left out
+    pub track_ids: Option<Vec<TrackId>>,
+}
+
+/// Choose how the caller's uploads are listed on their profile after the
+/// pinned tracks: newest first, most liked first, or as arranged by hand
+#[utoipa::path(
+    tag = "users",
+    request_body = TrackOrderRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 400, description = "A listed track isn't the caller's, or is listed twice", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[put("/users/me/track-order")]
+pub async fn set_track_order(auth: AuthUser, body: web::Json<TrackOrderRequest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let TrackOrderRequest { order, track_ids } = body.into_inner();
+    UserOperations::new(&db).set_track_order(auth.user.id, order, track_ids).await?;
+    
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Choose whether the caller's profile shows the track they're playing.
+/// Private tracks are only shown to those who may see them.
+#[utoipa::path(
+    tag = "users",
+    request_body = NowPlayingSettings,
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[put("/users/me/now-playing")]
+pub async fn set_now_playing_sharing(auth: AuthUser, body: web::Json<NowPlayingSettings>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    UserOperations::new(&db).set_share_now_playing(auth.user.id, body.share).await?;
+    
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// A user's profile with upload stats. Private profiles are only visible to
+/// their owner, and only the owner's stats include private tracks.
+#[utoipa::path(
+    tag = "users",
+    params(("user_id" = Uuid, Path)),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = ProfileView),
+        (status = 304, description = "Matches If-None-Match"),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/{user_id}/profile")]
+pub async fn get_profile(req: HttpRequest, auth: Option<AuthUser>, user_id: UserId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = UserOperations::new(&db).get_user_by_id(user_id).await?;
+    let viewer = auth.map(|auth| auth.user);
+    
+    // Signed-in viewers get their relationship to the user in the view
+    let personalized = viewer.is_some();
+    let view = profile_view(&db, user, viewer.as_ref()).await?;
+    conditional::json(&req, CachePolicy::for_viewer(personalized, conditional::DEFAULT_MAX_AGE), &view)
+}
+
+/// A user's public profile by username, counting a view unless the owner
+/// is looking or the site is in maintenance. Private profiles are only
+/// visible to their owner.
+#[utoipa::path(
+    tag = "users",
+    params(("username" = String, Path)),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = ProfileView),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/u/{username}")]
+pub async fn get_profile_by_username(auth: Option<AuthUser>, path: web::Path<String>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = UserOperations::new(&db).get_user_by_username(normalize_username(&path.into_inner())).await?;
+    let viewer = auth.map(|auth| auth.user);
+    let is_owner = viewer.as_ref().is_some_and(|viewer| viewer.id == user.id);
+    
+    let view = profile_view(&db, user, viewer.as_ref()).await?;
+    if !is_owner && !maintenance::is_active() {
+        UserOperations::new(&db).record_profile_view(view.user.id).await?;
+    }
+    
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "private, no-store"))
+        .json(view))
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AccountView {
+    /// The profile as its owner sees it, private tracks counted
+    pub profile: ProfileView,
+    pub email: String,
+    pub email_verified: bool,
+    pub unread_notifications: u64,
+    /// The latest verification request, if they ever applied
+    pub verification: Option<VerificationRequest>,
+}
+
+/// The caller's own account: their profile, the details only they see, and
+/// what's waiting for them. The reads behind it run together.
+#[utoipa::path(
+    tag = "users",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = AccountView),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[get("/users/me")]
+pub async fn get_me(auth: AuthUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = auth.user;
+    let notifications = NotificationOperations::new(&db);
+    let verifications = VerificationOperations::new(&db);
+    let (profile, unread_notifications, verification) = futures_util::try_join!(
+        profile_view(&db, user.clone(), Some(&user)),
+        bounded("unread notifications", notifications.count_unread(user.id)),
+        bounded("verification", verifications.latest_for_user(user.id)),
+    )?;
+    
+    Ok(HttpResponse::Ok()
+        .insert_header(("Cache-Control", "private, no-store"))
+        .json(AccountView {
+            profile,
+            email: user.email,
+            email_verified: user.email_verified,
+            unread_notifications,
+            verification,
+        }))
+}
+
+/// Build the profile `viewer` sees, or ProfileNotFound if they can't see it.
+/// Only the owner's stats include private tracks, and only signed-in viewers
+/// get the follow and block flags.
+async fn profile_view(db: &Db, user: User, viewer: Option<&User>) -> Result<ProfileView, Error> {
+    let viewer_id = viewer.map(|viewer| viewer.id);
+    let is_owner = viewer_id == Some(user.id);
+    
+    if !user.profile_visible_to(viewer_id) {
+        return Err(Error::ProfileNotFound);
+    }
+    let profile = user.profile.clone().ok_or(Error::ProfileNotFound)?;
+    
+    let lists = |ids: &Option<Vec<UserId>>, id: UserId| ids.as_ref().is_some_and(|ids| ids.contains(&id));
+    let is_following = viewer_id.map(|id| lists(&profile.followers, id));
+    let is_followed_by = viewer_id.map(|id| lists(&profile.following, id));
+    let is_blocked = viewer.map(|viewer| viewer.profile.as_ref().is_some_and(|p| lists(&p.blocked_users, user.id)));
+    
+    // Pinned tracks made private or deleted since aren't shown
+    let uploads = profile.uploads.as_deref().unwrap_or_default();
+    let pinned_tracks = profile.pinned_track_ids.iter()
+        .rev()
+        .filter_map(|id| uploads.iter().find(|t| t.id == *id && t.is_visible_to(None)))
+        .cloned()
+        .map(|track| TrackView::for_viewer(track, viewer_id))
+        .collect();
+    
+    let ops = TrackOperations::new(db);
+    let now_playing = async {
+        let Some((track_id, since)) = presence::now_playing(user.id).filter(|_| profile.share_now_playing) else {
+            return Ok(None);
+        };
+        match bounded("now playing", ops.get_track(track_id)).await {
+            Ok(track) if track.is_visible_to(viewer_id) => Ok(Some(NowPlaying { track: TrackView::for_viewer(track, viewer_id), since })),
+            Ok(_) | Err(Error::TrackNotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    };
+    let (track_count, genres, now_playing) = futures_util::try_join!(
+        bounded("track count", ops.count_tracks(user.id, is_owner)),
+        bounded("genre breakdown", ops.genre_breakdown(user.id, is_owner)),
+        now_playing,
+    )?;
+    
+    Ok(ProfileView {
+        user: PublicUser::from(user),
+        pronouns: profile.pronouns,
+        location: profile.location,
+        structured_location: profile.structured_location,
+        profile_banner: profile.profile_banner,
+        profile_bio: profile.profile_bio,
+        social_links: profile.social_links,
+        pinned_tracks,
+        track_count,
+        genres,
+        is_following,
+        is_followed_by,
+        is_blocked,
+        now_playing,
+    })
+}
+
+/// A user's tracks, pinned ones first and the rest in the order the user
+/// chose, optionally only those under one license. Only the owner sees their
+/// private tracks.
+#[utoipa::path(
+    tag = "users",
+    params(("user_id" = Uuid, Path), CursorParams, LicenseFilter),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = Paginated<TrackView>),
+        (status = 400, description = "Invalid cursor, or one for a track no longer listed", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/{user_id}/tracks")]
+pub async fn list_tracks(
+    auth: Option<AuthUser>,
+    hydrator: Hydrator,
+    user_id: UserId,
+    params: web::Query<CursorParams>,
+    filter: web::Query<LicenseFilter>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (limit, cursor) = params.page()?;
+    let user = UserOperations::new(&db).get_user_by_id(user_id).await?;
+    let viewer = auth.map(|auth| auth.user.id);
+    
+    if !user.profile_visible_to(viewer) {
+        return Err(Error::ProfileNotFound);
+    }
+    
+    let is_owner = viewer == Some(user.id);
+    let (tracks, next) = TrackOperations::new(&db).list_tracks(user.id, is_owner, filter.license, cursor, limit).await?;
+    let tracks = hydrator.track_views(tracks, viewer).await?;
+    
+    Ok(HttpResponse::Ok().json(Paginated::with_cursor(tracks, limit, next)))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct AppearsOnParams {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// Public tracks by other artists that credit this user, newest first. Only
+/// credits the user accepted count.
+#[utoipa::path(
+    tag = "users",
+    params(("user_id" = Uuid, Path), AppearsOnParams),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = Paginated<TrackView>, headers(
+            ("X-Total-Count" = u64, description = "Matches across all pages"),
+            ("Link" = String, description = "URLs of the first, previous, next and last pages"),
+        )),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/{user_id}/appears-on")]
+pub async fn list_appearances(
+    req: HttpRequest,
+    auth: Option<AuthUser>,
+    hydrator: Hydrator,
+    user_id: UserId,
+    params: web::Query<AppearsOnParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let user = UserOperations::new(&db).get_user_by_id(user_id).await?;
+    let viewer = auth.map(|auth| auth.user.id);
+    
+    if !user.profile_visible_to(viewer) {
+        return Err(Error::ProfileNotFound);
+    }
+    
+    let limit = params.limit.unwrap_or(20).clamp(1, 100);
+    let offset = params.offset.unwrap_or(0);
+    let (tracks, total) = TrackOperations::new(&db).tracks_appearing_on(user.id, limit, offset).await?;
+    let tracks = hydrator.track_views(tracks, viewer).await?;
+    
+    Ok(paged(&req, Paginated::new(tracks, limit, offset), total))
+}
+
+/// A user's releases, newest first. Only the artist sees their drafts.
+#[utoipa::path(
+    tag = "users",
+    params(("user_id" = Uuid, Path)),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<ReleaseView>),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/{user_id}/releases")]
+pub async fn list_releases(auth: Option<AuthUser>, user_id: UserId, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let user = UserOperations::new(&db).get_user_by_id(user_id).await?;
+    let viewer = auth.map(|auth| auth.user.id);
+    
+    if !user.profile_visible_to(viewer) {
+        return Err(Error::ProfileNotFound);
+    }
+    
+    let releases = ReleaseOperations::new(&db).list_releases(user.id, viewer == Some(user.id)).await?;
+    let releases = releases.into_iter()
+        .map(|release| releases::view(release, &user, viewer))
+        .collect::<Vec<_>>();
+    
+    Ok(HttpResponse::Ok().json(releases))
+}
+
+/// A user's followers, newest accounts first
+#[utoipa::path(
+    tag = "users",
+    params(("user_id" = Uuid, Path), CursorParams),
+    security((), ("bearer" = [])),
+    responses(
+        (status = 200, body = Paginated<PublicUser>),
+        (status = 400, description = "Invalid cursor", body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/{user_id}/followers")]
+pub async fn list_followers(
+    auth: Option<AuthUser>,
+    user_id: UserId,
+    params: web::Query<CursorParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let (limit, cursor) = params.page()?;
+    let user = UserOperations::new(&db).get_user_by_id(user_id).await?;
+    
+    if !user.profile_visible_to(auth.map(|auth| auth.user.id)) {
+        return Err(Error::ProfileNotFound);
+    }
+    
+    let (followers, next) = UserOperations::new(&db).list_followers(user.id, cursor, limit).await?;
+    let followers = followers.into_iter().map(PublicUser::from).collect();
+    
+    Ok(HttpResponse::Ok().json(Paginated::with_cursor(followers, limit, next)))
+}
+
+#[derive(Deserialize, IntoParams)]
+pub struct SuggestionParams {
+    pub limit: Option<u32>,
+}
+
+/// People the caller might want to follow: those followed by the people they
+/// follow, most mutual follows first
+#[utoipa::path(
+    tag = "users",
+    params(SuggestionParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<FollowSuggestion>),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[get("/me/suggestions")]
+pub async fn suggestions(auth: AuthUser, params: web::Query<SuggestionParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let limit = params.limit.unwrap_or(10).min(50);
+    let suggestions = UserOperations::new(&db).suggested_follows(auth.user.id, limit).await?;
+    
+    Ok(HttpResponse::Ok().json(suggestions))
+}
+
+#[derive(ToSchema)]
+pub struct ProfileImageUpload {
+    /// PNG, JPEG, WebP or GIF image
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProfileImageResponse {
+    pub url: String,
+}
+
+async fn upload_profile_image(db: &Db, user_id: UserId, kind: ProfileImage, payload: Multipart) -> Result<HttpResponse, Error> {
+    let _permit = upload_limit::acquire(user_id)?;
+    let bytes = read_upload(payload, images::MAX_UPLOAD_BYTES).await?.bytes;
+    let encoded = web::block(move || images::process(kind, &bytes))
+        .await
+        .map_err(|e| Error::Db(e.to_string()))??;
+    
+    let key = format!("profiles/{}/{}-{}.jpg", user_id, kind.name(), Uuid::new_v4());
+    let url = storage().put(&key, encoded).await?;
+    let previous = UserOperations::new(db).set_profile_image(user_id, kind, url.clone()).await?;
+    
+    // The old image is unreachable now, so losing track of it only wastes space
+    if let Some(old_key) = previous.as_deref().and_then(|url| storage().key_for_url(url)) {
+        if let Err(e) = storage().delete(&old_key).await {
+            warn!("Failed to delete replaced {} {}: {}", kind.name(), old_key, e);
+        }
+    }
+    
+    Ok(HttpResponse::Ok().json(ProfileImageResponse { url }))
+}
+
+/// Upload a new profile picture. It must be roughly square and at least
+/// 128x128; larger pictures are scaled down to 512x512.
+#[utoipa::path(
+    tag = "users",
+    request_body(content = ProfileImageUpload, content_type = "multipart/form-data"),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = ProfileImageResponse),
+        (status = 400, description = "Wrong dimensions or not decodable", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 413, body = ErrorBody),
+        (status = 415, description = "Not a supported image type", body = ErrorBody),
+        (status = 429, description = "Too many uploads in flight", body = ErrorBody),
+    )
+)]
+#[post("/me/profile/picture")]
+pub async fn upload_picture(auth: AuthUser, payload: Multipart, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    upload_profile_image(&db, auth.user.id, ProfileImage::Picture, payload).await
+}
+
+/// Upload a new profile banner. It must be wide, between 2.5:1 and 5:1, and
+/// at least 1000x250; larger banners are scaled down to fit 1500x500.
+#[utoipa::path(
+    tag = "users",
+    request_body(content = ProfileImageUpload, content_type = "multipart/form-data"),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = ProfileImageResponse),
+        (status = 400, description = "Wrong dimensions or not decodable", body = ErrorBody),
+        (status = 401, body = ErrorBody),
+        (status = 413, body = ErrorBody),
+        (status = 415, description = "Not a supported image type", body = ErrorBody),
+        (status = 429, description = "Too many uploads in flight", body = ErrorBody),
+    )
+)]
+#[post("/me/profile/banner")]
+pub async fn upload_banner(auth: AuthUser, payload: Multipart, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    upload_profile_image(&db, auth.user.id, ProfileImage::Banner, payload).await
+}
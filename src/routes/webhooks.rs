@@ -0,0 +1,177 @@
+use actix_web::{delete, get, patch, post, web, HttpResponse};
+use serde::Deserialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::auth::{self, AuthUser};
+use crate::db::error::{Error, ErrorBody};
+use crate::db::webhook::WebhookOperations;
+use crate::db::Db;
+use crate::types::webhook::{Webhook, WebhookDelivery, WebhookEvent};
+use super::PageParams;
+
+#[derive(Deserialize, ToSchema)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub events: Vec<WebhookEvent>,
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct UpdateWebhookRequest {
+    pub url: Option<String>,
+    pub events: Option<Vec<WebhookEvent>>,
+    pub active: Option<bool>,
+}
+
+/// Webhook targets must be absolute http(s) URLs
+pub fn validate_url(url: &str) -> Result<(), Error> {
+    let valid = ["http://", "https://"].iter()
+        .any(|scheme| url.strip_prefix(scheme).is_some_and(|rest| !rest.is_empty()));
+    
+    if !valid {
+        return Err(Error::Validation("webhook url must be an http or https URL".to_string()));
+    }
+    Ok(())
+}
+
+pub fn validate_events(events: &[WebhookEvent]) -> Result<(), Error> {
+    if events.is_empty() {
+        return Err(Error::Validation("webhook must subscribe to at least one event".to_string()));
+    }
+    Ok(())
+}
+
+/// Apply an update request to a webhook. Reactivating clears its failure count.
+pub fn apply_update(webhook: &mut Webhook, update: UpdateWebhookRequest) -> Result<(), Error> {
+    if let Some(url) = update.url {
+        validate_url(&url)?;
+        webhook.url = url;
+    }
+    if let Some(events) = update.events {
+        validate_events(&events)?;
+        webhook.events = events;
+    }
+    if let Some(active) = update.active {
+        if active && !webhook.active {
+            webhook.consecutive_failures = 0;
+        }
+        webhook.active = active;
+    }
+    Ok(())
+}
+
+/// List the caller's webhooks
+#[utoipa::path(
+    tag = "webhooks",
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<Webhook>),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[get("/users/me/webhooks")]
+pub async fn list_webhooks(auth: AuthUser, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let webhooks = WebhookOperations::new(&db).get_webhooks(Some(auth.user.id)).await?;
+    Ok(HttpResponse::Ok().json(webhooks))
+}
+
+/// Register a webhook. The response includes the generated signing secret.
+#[utoipa::path(
+    tag = "webhooks",
+    request_body = CreateWebhookRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 201, body = Webhook),
+        (status = 400, body = ErrorBody),
+        (status = 401, body = ErrorBody),
+    )
+)]
+#[post("/users/me/webhooks")]
+pub async fn create_webhook(auth: AuthUser, body: web::Json<CreateWebhookRequest>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let CreateWebhookRequest { url, events } = body.into_inner();
+    validate_url(&url)?;
+    validate_events(&events)?;
+    
+    let webhook = WebhookOperations::new(&db).create_webhook(Some(auth.user.id), url, auth::generate_token(), events).await?;
+    Ok(HttpResponse::Created().json(webhook))
+}
+
+/// Get one of the caller's webhooks
+#[utoipa::path(
+    tag = "webhooks",
+    params(("webhook_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Webhook),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/me/webhooks/{webhook_id}")]
+pub async fn get_webhook(auth: AuthUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let webhook = WebhookOperations::new(&db).get_owned_webhook(auth.user.id, path.into_inner()).await?;
+    Ok(HttpResponse::Ok().json(webhook))
+}
+
+/// Change a webhook's target, events, or active flag
+#[utoipa::path(
+    tag = "webhooks",
+    params(("webhook_id" = Uuid, Path)),
+    request_body = UpdateWebhookRequest,
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Webhook),
+        (status = 400, body = ErrorBody),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[patch("/users/me/webhooks/{webhook_id}")]
+pub async fn update_webhook(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    body: web::Json<UpdateWebhookRequest>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let mut webhook = WebhookOperations::new(&db).get_owned_webhook(auth.user.id, path.into_inner()).await?;
+    apply_update(&mut webhook, body.into_inner())?;
+    
+    let webhook = WebhookOperations::new(&db).save_webhook(webhook).await?;
+    Ok(HttpResponse::Ok().json(webhook))
+}
+
+/// Delete a webhook and its delivery log
+#[utoipa::path(
+    tag = "webhooks",
+    params(("webhook_id" = Uuid, Path)),
+    security(("bearer" = [])),
+    responses(
+        (status = 204),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[delete("/users/me/webhooks/{webhook_id}")]
+pub async fn delete_webhook(auth: AuthUser, path: web::Path<Uuid>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    let webhook = WebhookOperations::new(&db).get_owned_webhook(auth.user.id, path.into_inner()).await?;
+    WebhookOperations::new(&db).delete_webhook(webhook.id).await?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Delivery attempts for one of the caller's webhooks, newest first
+#[utoipa::path(
+    tag = "webhooks",
+    params(("webhook_id" = Uuid, Path), PageParams),
+    security(("bearer" = [])),
+    responses(
+        (status = 200, body = Vec<WebhookDelivery>),
+        (status = 404, body = ErrorBody),
+    )
+)]
+#[get("/users/me/webhooks/{webhook_id}/deliveries")]
+pub async fn list_deliveries(
+    auth: AuthUser,
+    path: web::Path<Uuid>,
+    params: web::Query<PageParams>,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, Error> {
+    let webhook = WebhookOperations::new(&db).get_owned_webhook(auth.user.id, path.into_inner()).await?;
+    let deliveries = WebhookOperations::new(&db).get_deliveries(webhook.id, params.limit, params.offset).await?;
+    Ok(HttpResponse::Ok().json(deliveries))
+}
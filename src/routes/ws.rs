@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::time::Instant;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use actix_ws::{CloseCode, CloseReason, Message, MessageStream, Session};
+use chrono::{DateTime, Utc};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tracing::warn;
+use crate::clock;
+use crate::auth::AuthUser;
+use crate::connection_limit::{self, ConnectionPermit};
+use crate::db::error::{Error, ErrorBody};
+use crate::db::notification::NotificationOperations;
+use crate::db::playlist::PlaylistOperations;
+use crate::db::track::TrackOperations;
+use crate::db::{Db, UserOperations};
+use crate::live_comments::{self, Subscription};
+use crate::live_moderation;
+use crate::live_playlists;
+use crate::presence::{self, Listener};
+use crate::realtime::{self, Event};
+use crate::types::id::{PlaylistId, TrackId, UserId};
+use crate::typing;
+use crate::types::realtime::{ClientMessage, ServerMessage, WsParams};
+
+fn close_reason(code: u16, description: &str) -> Option<CloseReason> {
+    Some(CloseReason { code: CloseCode::from(code), description: Some(description.to_string()) })
+}
+
+/// Open a WebSocket the server pushes the caller's notifications, unread
+/// counts and upload processing updates over, as JSON tagged by `type`.
+/// Authenticate with `token` in the URL or an `auth` message first. Nothing
+/// missed while disconnected is replayed; `ready` carries how many
+/// notifications arrived after `since`, so a client knows whether to reload
+/// `GET /notifications`. Send `subscribe` with a track id to also get that
+/// track's new, edited and deleted comments, its listener count and who's
+/// typing while it's open, `typing_start` and `typing_stop` while writing a
+/// comment on it, `subscribe_playlist` with a playlist id to get its tracks'
+/// edits, `subscribe_moderation` as a moderator to get the report queue's
+/// changes, and `now_playing` while playing a track. Sockets silent
+/// for `WS_IDLE_TIMEOUT_SECS` are closed.
+#[utoipa::path(
+    tag = "notifications",
+    params(WsParams),
+    responses(
+        (status = 101, description = "Switched to the WebSocket protocol", body = ServerMessage),
+        (status = 400, description = "Not a WebSocket handshake", body = ErrorBody),
+    )
+)]
+#[get("/ws")]
+pub async fn connect(req: HttpRequest, body: web::Payload, params: web::Query<WsParams>, db: web::Data<Db>) -> Result<HttpResponse, Error> {
+    // Forwarding headers are whatever the client says, so go by the socket
+    let ip = req.connection_info().peer_addr().unwrap_or("unknown").to_string();
+    let (response, session, messages) = actix_ws::handle(&req, body).map_err(|e| Error::Validation(e.to_string()))?;
+    let params = params.into_inner();
+    
+    actix_web::rt::spawn(async move {
+        match connection_limit::acquire(&ip) {
+            Ok(permit) => serve(&db, session, messages, params, permit).await,
+            Err(rejection) => {
+                let _ = session.close(close_reason(rejection.close_code(), rejection.reason())).await;
+            }
+        }
+    });
+    
+    Ok(response)
+}
+
+/// Wait for an `auth` message, unless the URL carried the token
+async fn authenticate(db: &Db, messages: &mut MessageStream, params: WsParams) -> Option<(AuthUser, Option<DateTime<Utc>>)> {
+    if let Some(token) = params.token {
+        return AuthUser::from_token(db, &token).await.ok().map(|auth| (auth, params.since));
+    }
+    
+    let first = actix_web::rt::time::timeout(connection_limit::idle_timeout(), messages.recv()).await.ok()??;
+    let Ok(Message::Text(text)) = first else { return None };
+    match serde_json::from_str(&text) {
+        Ok(ClientMessage::Auth { token, since }) => AuthUser::from_token(db, &token).await.ok().map(|auth| (auth, since)),
+        _ => None,
+    }
+}
+
+async fn serve(db: &Db, mut session: Session, mut messages: MessageStream, params: WsParams, _permit: ConnectionPermit) {
+    let Some((auth, since)) = authenticate(db, &mut messages, params).await else {
+        let _ = session.close(close_reason(connection_limit::CLOSE_POLICY_VIOLATION, "authentication required")).await;
+        return;
+    };
+    let user_id = auth.user.id;
+    let impersonated = auth.impersonator().is_some();
+    
+    let Some(realtime::Opened { registration, events, .. }) = realtime::register(user_id, None, None) else {
+        let reason = "too many connections for this account";
+        let _ = session.close(close_reason(connection_limit::CLOSE_POLICY_VIOLATION, reason)).await;
+        return;
+    };
+    
+    let ready = match ready(db, user_id, since).await {
+        Ok(ready) => ready,
+        Err(e) => {
+            warn!("Failed to start WebSocket for user {}: {}", user_id, e);
+            let _ = session.close(close_reason(connection_limit::CLOSE_TRY_AGAIN_LATER, "try again later")).await;
+            return;
+        }
+    };
+    if send(&mut session, &ready).await.is_err() {
+        return;
+    }
+    
+    let reason = run(db, &mut session, &mut messages, events, user_id, impersonated, registration.sender()).await;
+    let _ = session.close(reason).await;
+}
+
+async fn ready(db: &Db, user_id: UserId, since: Option<DateTime<Utc>>) -> Result<ServerMessage, Error> {
+    let server_time = clock::now();
+    let unread_count = NotificationOperations::new(db).count_unread(user_id).await?;
+    let missed_count = match since {
+        Some(since) => Some(NotificationOperations::new(db).count_since(user_id, since).await?),
+        None => None,
+    };
+    
+    Ok(ServerMessage::Ready { user_id, unread_count, missed_count, server_time })
+}
+
+async fn send(session: &mut Session, message: &ServerMessage) -> Result<(), actix_ws::Closed> {
+    let text = serde_json::to_string(message).expect("server messages serialize");
+    session.text(text).await
+}
+
+/// Start sending `viewer` the comments on a track they may see, answering
+/// with whether it worked
+async fn subscribe(
+    db: &Db,
+    subscriptions: &mut HashMap<TrackId, Subscription>,
+    track_id: TrackId,
+    viewer: UserId,
+    sender: &UnboundedSender<Event>,
+) -> ServerMessage {
+    let rejected = |reason: &str| ServerMessage::SubscriptionRejected { track_id, reason: reason.to_string() };
+    if subscriptions.contains_key(&track_id) {
+        return ServerMessage::Subscribed { track_id };
+    }
+    if subscriptions.len() >= live_comments::MAX_SUBSCRIPTIONS_PER_SOCKET {
+        return rejected("too many subscriptions");
+    }
+    
+    match TrackOperations::new(db).get_track(track_id).await {
+        Ok(track) if track.is_visible_to(Some(viewer)) => {
+            subscriptions.insert(track_id, live_comments::subscribe(db, &track, viewer, sender.clone()));
+            // Queued, so it follows the `subscribed` reply
+            let listeners = ServerMessage::ListenerCount { track_id, listeners: presence::listener_count(track_id) };
+            let _ = sender.send(Event { id: None, message: listeners });
+            ServerMessage::Subscribed { track_id }
+        }
+        Ok(_) | Err(Error::TrackNotFound) => rejected("track not found"),
+        Err(e) => {
+            warn!("Failed to subscribe to comments on track {}: {}", track_id, e);
+            rejected("try again later")
+        }
+    }
+}
+
+/// Start sending `viewer` the edits to a playlist they may see, answering
+/// with whether it worked
+async fn subscribe_playlist(
+    db: &Db,
+    subscriptions: &mut HashMap<PlaylistId, live_playlists::Subscription>,
+    playlist_id: PlaylistId,
+    viewer: UserId,
+    sender: &UnboundedSender<Event>,
+) -> ServerMessage {
+    let rejected = |reason: &str| ServerMessage::PlaylistSubscriptionRejected { playlist_id, reason: reason.to_string() };
+    if subscriptions.len() >= live_playlists::MAX_SUBSCRIPTIONS_PER_SOCKET && !subscriptions.contains_key(&playlist_id) {
+        return rejected("too many subscriptions");
+    }
+    
+    // Subscribed before reading, so no edit after `revision` is missed
+    let subscription = subscriptions.remove(&playlist_id)
+        .unwrap_or_else(|| live_playlists::subscribe(playlist_id, viewer, sender.clone()));
+    match PlaylistOperations::new(db).get_playlist(playlist_id).await {
+        Ok((_, playlist)) if playlist.is_visible_to(Some(viewer)) => {
+            subscriptions.insert(playlist_id, subscription);
+            ServerMessage::PlaylistSubscribed { playlist_id, revision: playlist.revision }
+        }
+        Ok(_) | Err(Error::PlaylistNotFound) => rejected("playlist not found"),
+        Err(e) => {
+            warn!("Failed to subscribe to playlist {}: {}", playlist_id, e);
+            rejected("try again later")
+        }
+    }
+}
+
+/// Start sending `viewer` the report queue's changes if they're a
+/// moderator, answering with whether it worked
+async fn subscribe_moderation(
+    db: &Db,
+    subscription: &mut Option<live_moderation::Subscription>,
+    viewer: UserId,
+    impersonated: bool,
+    sender: &UnboundedSender<Event>,
+) -> ServerMessage {
+    let rejected = |reason: &str| ServerMessage::ModerationSubscriptionRejected { reason: reason.to_string() };
+    if subscription.is_some() {
+        return ServerMessage::ModerationSubscribed;
+    }
+    if impersonated {
+        return rejected("not a moderator");
+    }
+    
+    // Read again, since the role may have changed since the socket opened
+    match UserOperations::new(db).get_user_by_id(viewer).await {
+        Ok(user) if live_moderation::is_moderator(&user) => {
+            *subscription = Some(live_moderation::subscribe(db, viewer, sender.clone()));
+            ServerMessage::ModerationSubscribed
+        }
+        Ok(_) => rejected("not a moderator"),
+        Err(e) => {
+            warn!("Failed to subscribe user {} to the report queue: {}", viewer, e);
+            rejected("try again later")
+        }
+    }
+}
+
+/// Count `user_id` as listening to a track they may see
+async fn now_playing(db: &Db, track_id: TrackId, user_id: UserId) {
+    match TrackOperations::new(db).get_track(track_id).await {
+        Ok(track) if track.is_visible_to(Some(user_id)) => {
+            presence::ping(track_id, Listener::User(user_id));
+        }
+        Ok(_) | Err(Error::TrackNotFound) => {}
+        Err(e) => warn!("Failed to record presence on track {}: {}", track_id, e),
+    }
+}
+
+/// Relay pushed messages and answer the client until either side closes or
+/// the client goes quiet, returning why the socket should be closed
+async fn run(
+    db: &Db,
+    session: &mut Session,
+    messages: &mut MessageStream,
+    mut pushed: UnboundedReceiver<Event>,
+    user_id: UserId,
+    impersonated: bool,
+    sender: UnboundedSender<Event>,
+) -> Option<CloseReason> {
+    // Ended when this returns, which kills live queries nobody else needs
+    let mut subscriptions = HashMap::new();
+    let mut playlist_subscriptions = HashMap::new();
+    let mut moderation = None;
+    let mut typing_limit = typing::RateLimit::new();
+    let mut last_seen = Instant::now();
+    let mut heartbeat = actix_web::rt::time::interval(connection_limit::idle_timeout() / 2);
+    
+    loop {
+        tokio::select! {
+            _ = heartbeat.tick() => {
+                if connection_limit::is_idle(last_seen) {
+                    return close_reason(1001, "idle timeout");
+                }
+                if session.ping(b"").await.is_err() {
+                    return None;
+                }
+            }
+            message = pushed.recv() => {
+                let message = message?.message;
+                match message {
+                    ServerMessage::ModerationRevoked => moderation = None,
+                    ServerMessage::PlaylistDeleted { playlist_id } => {
+                        playlist_subscriptions.remove(&playlist_id);
+                    }
+                    _ => {}
+                }
+                if send(session, &message).await.is_err() {
+                    return None;
+                }
+            }
+            message = messages.recv() => {
+                last_seen = Instant::now();
+                match message {
+                    Some(Ok(Message::Text(text))) => {
+                        // `auth` means nothing once authenticated
+                        let reply = match serde_json::from_str(&text) {
+                            Ok(ClientMessage::Ping) => Some(ServerMessage::Pong),
+                            Ok(ClientMessage::Subscribe { track_id }) => {
+                                Some(subscribe(db, &mut subscriptions, track_id, user_id, &sender).await)
+                            }
+                            Ok(ClientMessage::Unsubscribe { track_id }) => {
+                                subscriptions.remove(&track_id);
+                                None
+                            }
+                            Ok(ClientMessage::TypingStart { track_id }) => {
+                                if subscriptions.contains_key(&track_id) && typing_limit.allow() {
+                                    if let Err(e) = typing::start(db, track_id, user_id).await {
+                                        warn!("Failed to announce typing on track {}: {}", track_id, e);
+                                    }
+                                }
+                                None
+                            }
+                            Ok(ClientMessage::TypingStop { track_id }) => {
+                                if subscriptions.contains_key(&track_id) && typing_limit.allow() {
+                                    if let Err(e) = typing::stop(db, track_id, user_id).await {
+                                        warn!("Failed to announce typing stopped on track {}: {}", track_id, e);
+                                    }
+                                }
+                                None
+                            }
+                            Ok(ClientMessage::SubscribePlaylist { playlist_id }) => {
+                                Some(subscribe_playlist(db, &mut playlist_subscriptions, playlist_id, user_id, &sender).await)
+                            }
+                            Ok(ClientMessage::UnsubscribePlaylist { playlist_id }) => {
+                                playlist_subscriptions.remove(&playlist_id);
+                                None
+                            }
+                            Ok(ClientMessage::SubscribeModeration) => {
+                                Some(subscribe_moderation(db, &mut moderation, user_id, impersonated, &sender).await)
+                            }
+                            Ok(ClientMessage::UnsubscribeModeration) => {
+                                moderation = None;
+                                None
+                            }
+                            Ok(ClientMessage::NowPlaying { track_id }) => {
+                                now_playing(db, track_id, user_id).await;
+                                None
+                            }
+                            Ok(ClientMessage::Auth { .. }) | Err(_) => None,
+                        };
+                        if let Some(reply) = reply {
+                            if send(session, &reply).await.is_err() {
+                                return None;
+                            }
+                        }
+                    }
+                    Some(Ok(Message::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            return None;
+                        }
+                    }
+                    Some(Ok(Message::Close(reason))) => return reason,
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) => return close_reason(1002, "protocol error"),
+                    None => return None,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,183 @@
+//! sitemap.xml generation.
+//!
+//! A background job periodically walks every listable user in batches and
+//! writes the sitemap to `SITEMAP_DIR`, so requests only ever read files. When
+//! there are more than `MAX_URLS_PER_SITEMAP` URLs the output is split into
+//! numbered sitemaps with `sitemap.xml` as their index.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use tracing::{error, info};
+use uuid::Uuid;
+use crate::{clock, config, maintenance};
+use crate::db::error::Error;
+use crate::db::sitemap::SitemapOperations;
+use crate::db::Db;
+use crate::feed::escape_xml;
+
+/// Limit set by the sitemaps protocol
+pub const MAX_URLS_PER_SITEMAP: usize = 50_000;
+
+const BATCH_SIZE: u32 = 500;
+
+/// Where generated sitemaps are stored, set with `SITEMAP_DIR`
+pub fn sitemap_dir() -> PathBuf {
+    PathBuf::from(env::var("SITEMAP_DIR").unwrap_or_else(|_| "sitemaps".to_string()))
+}
+
+/// How often to regenerate, set in seconds with `SITEMAP_INTERVAL_SECS`
+pub fn interval() -> Duration {
+    let secs = env::var("SITEMAP_INTERVAL_SECS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(3600);
+    Duration::from_secs(secs)
+}
+
+/// Regenerate the sitemap now and then every `interval()`
+pub fn spawn_job(db: Db) {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(interval());
+        loop {
+            ticker.tick().await;
+            if maintenance::is_active() {
+                continue;
+            }
+            match generate(&db, &sitemap_dir(), &config::public_url()).await {
+                Ok(urls) => info!("Generated sitemap with {} urls", urls),
+                Err(e) => error!("Failed to generate sitemap: {}", e),
+            }
+        }
+    });
+}
+
+/// Writes `<url>` entries, starting a new numbered file every
+/// `MAX_URLS_PER_SITEMAP` entries
+struct SitemapWriter {
+    dir: PathBuf,
+    file: Option<BufWriter<File>>,
+    files: usize,
+    urls_in_file: usize,
+    total: usize,
+}
+
+impl SitemapWriter {
+    fn new(dir: PathBuf) -> Self {
+        Self { dir, file: None, files: 0, urls_in_file: 0, total: 0 }
+    }
+    
+    fn write_url(&mut self, loc: &str, lastmod: DateTime<Utc>) -> io::Result<()> {
+        if self.file.is_none() || self.urls_in_file == MAX_URLS_PER_SITEMAP {
+            self.finish_file()?;
+            self.files += 1;
+            let mut file = BufWriter::new(File::create(self.dir.join(part_name(self.files)))?);
+            file.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n")?;
+            self.file = Some(file);
+            self.urls_in_file = 0;
+        }
+        
+        if let Some(file) = self.file.as_mut() {
+            writeln!(
+                file,
+                "<url><loc>{}</loc><lastmod>{}</lastmod></url>",
+                escape_xml(loc),
+                lastmod.format("%Y-%m-%d"),
+            )?;
+        }
+        self.urls_in_file += 1;
+        self.total += 1;
+        Ok(())
+    }
+    
+    fn finish_file(&mut self) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.write_all(b"</urlset>\n")?;
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+fn part_name(n: usize) -> String {
+    format!("sitemap-{}.xml", n)
+}
+
+fn write_index(path: &Path, parts: usize, base_url: &str) -> io::Result<()> {
+    let mut index = BufWriter::new(File::create(path)?);
+    let now = clock::now().format("%Y-%m-%d");
+    
+    index.write_all(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<sitemapindex xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n")?;
+    for n in 1..=parts {
+        let loc = format!("{}/{}", base_url, part_name(n));
+        writeln!(index, "<sitemap><loc>{}</loc><lastmod>{}</lastmod></sitemap>", escape_xml(&loc), now)?;
+    }
+    index.write_all(b"</sitemapindex>\n")?;
+    index.flush()
+}
+
+/// Generate the sitemap into `dir`, replacing the previous one only once the
+/// new one is complete. Returns the number of URLs written.
+pub async fn generate(db: &Db, dir: &Path, base_url: &str) -> Result<usize, Error> {
+    let staging = dir.with_extension("tmp");
+    let io_error = |e: io::Error| Error::Db(e.to_string());
+    
+    if staging.exists() {
+        fs::remove_dir_all(&staging).map_err(io_error)?;
+    }
+    fs::create_dir_all(&staging).map_err(io_error)?;
+    
+    let mut writer = SitemapWriter::new(staging.clone());
+    let mut after: Option<Uuid> = None;
+    
+    loop {
+        let batch = SitemapOperations::new(db).get_batch(after, BATCH_SIZE).await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after = Some(last.profile.id);
+        
+        for entry in &batch {
+            let profile_url = format!("{}/users/{}", base_url, entry.profile.username);
+            writer.write_url(&profile_url, entry.profile.updated_at).map_err(io_error)?;
+            
+            for track in &entry.tracks {
+                writer.write_url(&format!("{}/tracks/{}", base_url, track.id), track.updated_at).map_err(io_error)?;
+            }
+            for playlist in &entry.playlists {
+                writer.write_url(&format!("{}/playlists/{}", base_url, playlist.id), playlist.updated_at).map_err(io_error)?;
+            }
+        }
+    }
+    writer.finish_file().map_err(io_error)?;
+    
+    // A single part is the sitemap itself, several need an index
+    if writer.files <= 1 {
+        let sitemap = staging.join("sitemap.xml");
+        if writer.files == 1 {
+            fs::rename(staging.join(part_name(1)), &sitemap).map_err(io_error)?;
+        } else {
+            fs::write(&sitemap, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n</urlset>\n").map_err(io_error)?;
+        }
+    } else {
+        write_index(&staging.join("sitemap.xml"), writer.files, base_url).map_err(io_error)?;
+    }
+    
+    if dir.exists() {
+        fs::remove_dir_all(dir).map_err(io_error)?;
+    }
+    fs::rename(&staging, dir).map_err(io_error)?;
+    
+    Ok(writer.total)
+}
+
+/// Whether `name` is a file the generator could have written
+pub fn is_sitemap_file(name: &str) -> bool {
+    name == "sitemap.xml"
+        || name.strip_prefix("sitemap-")
+            .and_then(|rest| rest.strip_suffix(".xml"))
+            .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
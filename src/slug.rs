@@ -0,0 +1,42 @@
+//! Readable URL slugs for tracks and playlists.
+//!
+//! A slug is made from the title and kept unique among its kind by adding
+//! a short random suffix when the plain one is taken. Renaming gives a new
+//! slug and keeps the old one as an alias, so links to it still resolve.
+
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+/// Longest slug made from a title, in characters, before any suffix
+pub const MAX_SLUG_LEN: usize = 60;
+
+/// Characters of the suffix that tells apart slugs of the same title
+const SUFFIX_LEN: usize = 6;
+
+/// A title made fit for a URL: accents stripped, lowercased, and every run
+/// of anything but ASCII letters and digits turned into one hyphen. Titles
+/// with none of those come out as `untitled`.
+pub fn slugify(title: &str) -> String {
+    let mut slug = String::new();
+    for c in title.nfkd().filter(|c| !is_combining_mark(*c)).flat_map(char::to_lowercase) {
+        if c.is_ascii_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug: String = slug.chars().take(MAX_SLUG_LEN).collect();
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        "untitled".to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// `base` with a random suffix, for when it's taken
+pub fn with_suffix(base: &str) -> String {
+    format!("{}-{}", base, &Uuid::new_v4().simple().to_string()[..SUFFIX_LEN])
+}
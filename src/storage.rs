@@ -0,0 +1,174 @@
+//! Where uploaded media lives.
+//!
+//! Handlers go through the `Storage` trait so another backend (S3 and the
+//! like) can replace the local directory without touching them. The local
+//! backend writes under `MEDIA_DIR` and is served back from `/media/`.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+use actix_web::web;
+use chrono::{DateTime, Utc};
+use futures_util::future::BoxFuture;
+use crate::config;
+use crate::db::error::Error;
+
+pub trait Storage: Send + Sync {
+    /// Store `bytes` under `key`, returning the URL it can be fetched from
+    fn put(&self, key: &str, bytes: Vec<u8>) -> BoxFuture<'_, Result<String, Error>>;
+
+    /// Remove what's stored under `key`. Missing keys are not an error.
+    fn delete(&self, key: &str) -> BoxFuture<'_, Result<(), Error>>;
+
+    /// The key a URL returned by `put` points at, if it's one of ours
+    fn key_for_url(&self, url: &str) -> Option<String>;
+
+    /// Every stored object
+    fn list(&self) -> BoxFuture<'_, Result<Vec<StoredObject>, Error>>;
+}
+
+/// An object as `Storage::list` reports it
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub key: String,
+    pub size: u64,
+    pub modified: DateTime<Utc>,
+}
+
+/// Stores media as files on local disk
+pub struct LocalStorage {
+    dir: PathBuf,
+    base_url: String,
+}
+
+impl LocalStorage {
+    pub fn new(dir: PathBuf, base_url: String) -> Self {
+        Self { dir, base_url }
+    }
+
+    pub fn from_env() -> Self {
+        let dir = env::var("MEDIA_DIR").unwrap_or_else(|_| "media".to_string());
+        Self::new(PathBuf::from(dir), format!("{}/media", config::public_url()))
+    }
+
+    /// Directory the files are kept in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Path of a key on disk, refusing keys that could escape the directory
+    pub fn path(&self, key: &str) -> Option<PathBuf> {
+        is_valid_key(key).then(|| self.dir.join(key))
+    }
+}
+
+/// Keys are relative paths of plain segments
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.split('/').all(|segment| {
+            !segment.is_empty()
+                && segment != "."
+                && segment != ".."
+                && segment.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+        })
+}
+
+impl Storage for LocalStorage {
+    fn put(&self, key: &str, bytes: Vec<u8>) -> BoxFuture<'_, Result<String, Error>> {
+        let path = self.path(key);
+        let url = format!("{}/{}", self.base_url, key);
+
+        Box::pin(async move {
+            let path = path.ok_or_else(|| Error::Validation("invalid storage key".to_string()))?;
+            web::block(move || {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, bytes)
+            })
+            .await
+            .map_err(|e| Error::Db(e.to_string()))?
+            .map_err(|e| Error::Db(e.to_string()))?;
+
+            Ok(url)
+        })
+    }
+
+    fn delete(&self, key: &str) -> BoxFuture<'_, Result<(), Error>> {
+        let path = self.path(key);
+
+        Box::pin(async move {
+            let Some(path) = path else { return Ok(()) };
+            web::block(move || match std::fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            })
+            .await
+            .map_err(|e| Error::Db(e.to_string()))?
+            .map_err(|e| Error::Db(e.to_string()))
+        })
+    }
+
+    fn key_for_url(&self, url: &str) -> Option<String> {
+        url.strip_prefix(&self.base_url)?
+            .strip_prefix('/')
+            .filter(|key| is_valid_key(key))
+            .map(str::to_string)
+    }
+
+    fn list(&self) -> BoxFuture<'_, Result<Vec<StoredObject>, Error>> {
+        let dir = self.dir.clone();
+
+        Box::pin(async move {
+            web::block(move || {
+                let mut objects = Vec::new();
+                if dir.is_dir() {
+                    walk(&dir, &dir, &mut objects)?;
+                }
+                Ok::<_, std::io::Error>(objects)
+            })
+            .await
+            .map_err(|e| Error::Db(e.to_string()))?
+            .map_err(|e| Error::Db(e.to_string()))
+        })
+    }
+}
+
+/// Collect every file under `dir`, keyed by its path relative to `root`
+fn walk(root: &Path, dir: &Path, objects: &mut Vec<StoredObject>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let path = entry.path();
+
+        if metadata.is_dir() {
+            walk(root, &path, objects)?;
+            continue;
+        }
+
+        let key = path.strip_prefix(root)
+            .ok()
+            .and_then(|relative| relative.to_str())
+            .map(|relative| relative.replace(std::path::MAIN_SEPARATOR, "/"));
+        if let Some(key) = key.filter(|key| is_valid_key(key)) {
+            objects.push(StoredObject {
+                key,
+                size: metadata.len(),
+                modified: metadata.modified().map(DateTime::<Utc>::from)?,
+            });
+        }
+    }
+    Ok(())
+}
+
+static LOCAL: LazyLock<LocalStorage> = LazyLock::new(LocalStorage::from_env);
+
+/// The configured storage backend
+pub fn storage() -> &'static dyn Storage {
+    &*LOCAL
+}
+
+/// The local backend, for serving its files
+pub fn local() -> &'static LocalStorage {
+    &LOCAL
+}
@@ -0,0 +1,178 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::clock;
+use crate::db::error::Error;
+use crate::markdown;
+use crate::types::id::UserId;
+use crate::types::user::User;
+
+/// Longest announcement title accepted, in characters
+pub const MAX_TITLE_LEN: usize = 200;
+
+/// Longest announcement body accepted, in characters
+pub const MAX_BODY_LEN: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+/// Who an announcement is shown to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Audience {
+    #[default]
+    All,
+    Artists, // users with at least one upload
+    Admins,
+}
+
+impl Audience {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Audience::All => "all",
+            Audience::Artists => "artists",
+            Audience::Admins => "admins",
+        }
+    }
+
+    /// Every audience `user` belongs to; anonymous callers only see `All`
+    pub fn for_user(user: Option<&User>) -> Vec<Audience> {
+        let mut audiences = vec![Audience::All];
+        let profile = user.and_then(|user| user.profile.as_ref());
+
+        if profile.is_some_and(|p| p.uploads.as_ref().is_some_and(|uploads| !uploads.is_empty())) {
+            audiences.push(Audience::Artists);
+        }
+        if profile.is_some_and(|p| p.is_admin) {
+            audiences.push(Audience::Admins);
+        }
+
+        audiences
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Announcement {
+    pub id: Uuid,
+    pub title: String,
+    pub body: String, // markdown, sanitized before it's stored
+    pub severity: Severity,
+    pub audience: Audience,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: Option<DateTime<Utc>>, // shown until removed when None
+    pub created_by: UserId,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A user having dismissed an announcement
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnnouncementDismissal {
+    pub announcement_id: Uuid,
+    pub user_id: UserId,
+    pub dismissed_at: DateTime<Utc>,
+}
+
+/// An announcement as sent by an admin
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewAnnouncement {
+    pub title: String,
+    pub body: String,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default)]
+    pub audience: Audience,
+    pub starts_at: Option<DateTime<Utc>>, // now when left out
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+/// Changes to an announcement. Fields left out are kept as they are.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct AnnouncementPatch {
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub severity: Option<Severity>,
+    pub audience: Option<Audience>,
+    pub starts_at: Option<DateTime<Utc>>,
+    pub ends_at: Option<DateTime<Utc>>,
+}
+
+fn clean_title(title: &str) -> Result<String, Error> {
+    let title = title.trim();
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LEN {
+        return Err(Error::Validation(format!("title must be 1 to {} characters", MAX_TITLE_LEN)));
+    }
+    Ok(title.to_string())
+}
+
+fn clean_body(body: &str) -> Result<String, Error> {
+    let body = markdown::sanitize(body.trim());
+    if body.is_empty() || body.chars().count() > MAX_BODY_LEN {
+        return Err(Error::Validation(format!("body must be 1 to {} characters", MAX_BODY_LEN)));
+    }
+    Ok(body)
+}
+
+fn check_window(starts_at: DateTime<Utc>, ends_at: Option<DateTime<Utc>>) -> Result<(), Error> {
+    if ends_at.is_some_and(|ends_at| ends_at <= starts_at) {
+        return Err(Error::Validation("ends_at must be after starts_at".to_string()));
+    }
+    Ok(())
+}
+
+impl NewAnnouncement {
+    /// Validate and sanitize into an announcement posted by `created_by`
+    pub fn into_announcement(self, created_by: UserId) -> Result<Announcement, Error> {
+        let now = clock::now();
+        let starts_at = self.starts_at.unwrap_or(now);
+        check_window(starts_at, self.ends_at)?;
+
+        Ok(Announcement {
+            id: Uuid::new_v4(),
+            title: clean_title(&self.title)?,
+            body: clean_body(&self.body)?,
+            severity: self.severity,
+            audience: self.audience,
+            starts_at,
+            ends_at: self.ends_at,
+            created_by,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+}
+
+impl AnnouncementPatch {
+    /// Validate and sanitize the fields that are present into `announcement`
+    pub fn apply(self, announcement: &mut Announcement) -> Result<(), Error> {
+        if let Some(title) = &self.title {
+            announcement.title = clean_title(title)?;
+        }
+        if let Some(body) = &self.body {
+            announcement.body = clean_body(body)?;
+        }
+        if let Some(severity) = self.severity {
+            announcement.severity = severity;
+        }
+        if let Some(audience) = self.audience {
+            announcement.audience = audience;
+        }
+        if let Some(starts_at) = self.starts_at {
+            announcement.starts_at = starts_at;
+        }
+        if let Some(ends_at) = self.ends_at {
+            announcement.ends_at = Some(ends_at);
+        }
+
+        check_window(announcement.starts_at, announcement.ends_at)?;
+        announcement.updated_at = clock::now();
+        Ok(())
+    }
+}
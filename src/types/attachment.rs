@@ -0,0 +1,40 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+/// An extra file on a track, such as a stem or a project file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: Uuid,
+    pub name: String,
+    pub content_type: String,
+    pub size: u64, // in bytes
+    pub url: String, // only handed out through the download endpoint
+    pub download_count: u64, // apart from the track's own downloads
+    pub created_at: DateTime<Utc>,
+}
+
+/// An attachment as listed on a track
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AttachmentView {
+    pub id: Uuid,
+    pub name: String,
+    pub content_type: String,
+    pub size: u64,
+    pub download_count: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Attachment> for AttachmentView {
+    fn from(attachment: Attachment) -> Self {
+        Self {
+            id: attachment.id,
+            name: attachment.name,
+            content_type: attachment.content_type,
+            size: attachment.size,
+            download_count: attachment.download_count,
+            created_at: attachment.created_at,
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::db::error::Error;
+use crate::import::is_http_url;
+use crate::types::user::TrackTechnicalMetadata;
+
+/// Most earlier audio files kept per track; the oldest go first
+pub const MAX_AUDIO_VERSIONS: usize = 10;
+
+/// An audio file a track used to play, kept so the owner can go back to it
+/// and so streams already started on it keep working
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AudioVersion {
+    pub id: Uuid,
+    pub audio_url: String,
+    pub technical_metadata: Option<TrackTechnicalMetadata>,
+    pub replaced_at: DateTime<Utc>,
+}
+
+/// New audio for a track, already uploaded
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct AudioReplacement {
+    pub audio_url: String,
+    pub technical_metadata: Option<TrackTechnicalMetadata>,
+}
+
+impl AudioReplacement {
+    pub fn validate(&self) -> Result<(), Error> {
+        if !is_http_url(self.audio_url.trim()) {
+            return Err(Error::Validation("audio_url must be an http(s) URL".to_string()));
+        }
+        if self.technical_metadata.as_ref().is_some_and(|m| !m.duration.is_finite() || m.duration <= 0.0) {
+            return Err(Error::Validation("duration must be positive".to_string()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,15 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::types::id::UserId;
+
+/// A record of an operator action
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AuditEntry {
+    pub id: Uuid,
+    pub actor_id: Option<UserId>, // None for actions taken by the system
+    pub action: String,
+    pub detail: serde_json::Value,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,124 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::clock;
+use crate::db::error::Error;
+use crate::types::id::UserId;
+use crate::types::user::{PublicUser, User};
+
+/// Most artists credited on one track
+pub const MAX_CREDITS: usize = 20;
+
+/// Longest credit name, in characters
+const MAX_CREDIT_NAME_LEN: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CreditRole {
+    Feature,
+    Producer,
+    Remixer,
+}
+
+/// Where a credit stands with the user it names. Credits by name alone are
+/// always `Accepted`; there's nobody to ask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CreditStatus {
+    Pending,
+    Accepted,
+    Declined,
+}
+
+/// Another artist credited on a track
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Credit {
+    pub id: Uuid,
+    pub user_id: Option<UserId>, // the registered user credited, if any
+    pub name: String, // shown as plain text until the user accepts
+    pub role: CreditRole,
+    pub status: CreditStatus,
+    #[serde(default)]
+    pub notify_comments: bool, // the credited user gets the track's comment notifications
+    pub created_at: DateTime<Utc>,
+}
+
+/// A credit as the track's owner sends it: a registered user, a name, or both
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CreditInput {
+    pub user_id: Option<UserId>,
+    /// Defaults to the user's profile name
+    pub name: Option<String>,
+    pub role: CreditRole,
+}
+
+/// A credited user's answer to an invitation. Declining an accepted credit
+/// unlinks it again.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct CreditResponse {
+    pub accept: bool,
+    /// Get notified of comments on the track; ignored when declining
+    #[serde(default)]
+    pub notify_comments: bool,
+}
+
+/// A credit as shown on a track
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CreditView {
+    pub id: Uuid,
+    pub name: String, // the linked user's current profile name
+    pub role: CreditRole,
+    pub user: Option<PublicUser>, // only once the user accepted
+}
+
+impl Credit {
+    /// The user this credit links to, once they've accepted it
+    pub fn linked_user(&self) -> Option<UserId> {
+        self.user_id.filter(|_| self.status == CreditStatus::Accepted)
+    }
+
+    /// The credit as shown, linked to `user` when that's the accepted,
+    /// still active user it names
+    pub fn view(&self, user: Option<&User>) -> CreditView {
+        let user = user
+            .filter(|user| self.linked_user() == Some(user.id))
+            .filter(|user| user.profile.as_ref().is_some_and(|p| !p.is_deleted()))
+            .cloned()
+            .map(PublicUser::from);
+
+        CreditView {
+            id: self.id,
+            name: user.as_ref().and_then(|user| user.profile_name.clone()).unwrap_or_else(|| self.name.clone()),
+            role: self.role,
+            user,
+        }
+    }
+}
+
+impl CreditInput {
+    /// Check the input and turn it into a new credit. `user` is the
+    /// registered user it names, if any; their profile name is used when no
+    /// name was sent, and they have to accept before it links to them.
+    pub fn into_credit(self, user: Option<&User>) -> Result<Credit, Error> {
+        let name = self.name
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .or_else(|| user.and_then(|user| user.profile.as_ref()).map(|p| p.profile_name.clone()))
+            .or_else(|| user.map(|user| user.username.clone()))
+            .ok_or_else(|| Error::Validation("a credit needs a user_id or a name".to_string()))?;
+        if name.chars().count() > MAX_CREDIT_NAME_LEN {
+            return Err(Error::Validation(format!("credit names must be at most {} characters", MAX_CREDIT_NAME_LEN)));
+        }
+
+        Ok(Credit {
+            id: Uuid::new_v4(),
+            user_id: self.user_id,
+            name,
+            role: self.role,
+            status: if self.user_id.is_some() { CreditStatus::Pending } else { CreditStatus::Accepted },
+            notify_comments: false,
+            created_at: clock::now(),
+        })
+    }
+}
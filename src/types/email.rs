@@ -0,0 +1,15 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use crate::email::Email;
+
+/// A message the mail server didn't take, waiting for `email`'s retry job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueuedEmail {
+    pub id: Uuid,
+    pub email: Email,
+    pub attempts: u32, // deliveries tried so far, the first included
+    pub last_error: String,
+    pub next_attempt_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
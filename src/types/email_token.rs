@@ -0,0 +1,64 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+use utoipa::ToSchema;
+use crate::types::id::UserId;
+
+/// What an emailed token lets its holder do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTokenPurpose {
+    VerifyEmail,
+    ResetPassword,
+    EraseAccount,
+}
+
+impl EmailTokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailTokenPurpose::VerifyEmail => "verify_email",
+            EmailTokenPurpose::ResetPassword => "reset_password",
+            EmailTokenPurpose::EraseAccount => "erase_account",
+        }
+    }
+
+    /// How long a token stays valid after it's sent
+    pub fn ttl(&self) -> Duration {
+        match self {
+            EmailTokenPurpose::VerifyEmail => Duration::days(2),
+            EmailTokenPurpose::ResetPassword => Duration::hours(1),
+            EmailTokenPurpose::EraseAccount => Duration::minutes(15),
+        }
+    }
+}
+
+/// A single-use token sent to a user's email address
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EmailToken {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub purpose: EmailTokenPurpose,
+    pub token_hash: String, // SHA-256 of the token, the token itself is only in the email
+    pub email: String, // the address it was sent to, so changing email voids it
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub used_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct VerifyEmail {
+    /// The token from the verification email
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ForgotPassword {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ResetPassword {
+    /// The token from the reset email
+    pub token: String,
+    pub new_password: String,
+}
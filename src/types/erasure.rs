@@ -0,0 +1,46 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::types::id::UserId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum ErasureStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum ErasureStep {
+    DeleteMedia,
+    RevokeSessions,
+    ScrubReferences,
+    DeleteAccount,
+}
+
+impl ErasureStep {
+    /// Steps in the order they run. Every step is idempotent so a job can be
+    /// resumed from whatever it last recorded.
+    pub const ALL: [ErasureStep; 4] = [
+        ErasureStep::DeleteMedia,
+        ErasureStep::RevokeSessions,
+        ErasureStep::ScrubReferences,
+        ErasureStep::DeleteAccount,
+    ];
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ErasureJob {
+    pub id: Uuid,
+    pub user_id: Option<UserId>, // cleared once the job completes
+    pub status: ErasureStatus,
+    pub completed_steps: Vec<ErasureStep>,
+    pub records_scrubbed: u64,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub requested_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
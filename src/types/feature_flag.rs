@@ -0,0 +1,21 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FeatureFlag {
+    pub name: String,
+    pub enabled: bool,
+    pub rollout_percentage: Option<u8>, // share of signed-in users it's on for, everyone when None
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A flag as admins see it: what's stored and what actually applies
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FeatureFlagStatus {
+    pub name: String,
+    pub enabled: bool,
+    pub rollout_percentage: Option<u8>,
+    pub env_override: Option<bool>, // set by FLAG_<NAME>, beats `enabled`
+    pub updated_at: Option<DateTime<Utc>>, // None while the flag is at its default
+}
@@ -0,0 +1,14 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use crate::types::id::UserId;
+
+/// An actor on another server following a local user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RemoteFollower {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub actor: String, // the remote actor's id URL
+    pub follow_id: Option<String>, // id of the Follow activity, so an Undo can be matched
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,19 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+/// What one pass of the media garbage collector found and did
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GcRun {
+    pub id: Uuid,
+    pub dry_run: bool,
+    pub scanned: usize, // objects in storage
+    pub referenced: usize, // objects something in the database points at
+    pub too_recent: usize, // unreferenced, but inside the safety window
+    pub deleted: Vec<String>, // keys removed, or that would be on a dry run
+    pub failed: Vec<String>, // keys whose deletion failed
+    pub capped: bool, // more were collectable than the per-run cap allowed
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+}
@@ -0,0 +1,123 @@
+//! Ids that know what they name.
+//!
+//! Each is a `Uuid` underneath and goes over the wire and into the database
+//! exactly as one: serde-transparent, displayed and parsed as the
+//! hyphenated uuid. Keeping them apart means a track id can't be passed
+//! where a user id is expected. Handlers take them straight from the path,
+//! by the segment name routes already use: `{user_id}`, `{track_id}`,
+//! `{playlist_id}` and `{comment_id}`.
+//!
+//! Only users are records of their own. Tracks and playlists live inside
+//! their owner's user record and comments inside their track, so their ids
+//! are keys within that record rather than record ids.
+
+use std::fmt;
+use std::future::{ready, Ready};
+use std::str::FromStr;
+use actix_web::dev::Payload;
+use actix_web::{FromRequest, HttpRequest};
+use serde::{Deserialize, Serialize};
+use surrealdb::RecordId;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::db::error::Error;
+
+macro_rules! typed_id {
+    ($(#[$doc:meta])* $name:ident, $segment:literal, $not_found:expr) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize, ToSchema)]
+        #[serde(transparent)]
+        pub struct $name(Uuid);
+
+        impl $name {
+            /// A fresh random id
+            pub fn new() -> Self {
+                $name(Uuid::new_v4())
+            }
+
+            pub fn as_uuid(&self) -> Uuid {
+                self.0
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl From<Uuid> for $name {
+            fn from(id: Uuid) -> Self {
+                $name(id)
+            }
+        }
+
+        impl From<$name> for Uuid {
+            fn from(id: $name) -> Self {
+                id.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt(f)
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = uuid::Error;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                Uuid::parse_str(s).map($name)
+            }
+        }
+
+        /// Taken from the route's `{segment}`; one that isn't a uuid can't
+        /// name anything, so it's a 404 like any unknown id
+        impl FromRequest for $name {
+            type Error = Error;
+            type Future = Ready<Result<Self, Error>>;
+
+            fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+                ready(match req.match_info().get($segment) {
+                    Some(segment) => segment.parse().map_err(|_| $not_found),
+                    None => Err(Error::NotFound),
+                })
+            }
+        }
+    };
+}
+
+typed_id!(
+    /// Names a user, and the `users` record that holds everything of theirs
+    UserId, "user_id", Error::UserNotFound
+);
+
+typed_id!(
+    /// Names a track among its uploader's uploads
+    TrackId, "track_id", Error::TrackNotFound
+);
+
+typed_id!(
+    /// Names a playlist among its owner's playlists
+    PlaylistId, "playlist_id", Error::PlaylistNotFound
+);
+
+typed_id!(
+    /// Names a comment or reply on a track
+    CommentId, "comment_id", Error::CommentNotFound
+);
+
+impl UserId {
+    /// The table and key of the user's record, as SurrealDB takes a record
+    /// id: `("users", "<uuid>")`
+    pub fn record(&self) -> (&'static str, String) {
+        ("users", self.0.to_string())
+    }
+}
+
+impl From<UserId> for RecordId {
+    fn from(id: UserId) -> Self {
+        RecordId::from(id.record())
+    }
+}
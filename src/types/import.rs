@@ -0,0 +1,63 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::types::id::TrackId;
+use crate::types::license::License;
+use crate::types::user::{CreatedVia, TrackTechnicalMetadata};
+
+/// One NDJSON line of a user import
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ImportUserRecord {
+    pub username: String,
+    pub email: String,
+    pub password_hash: String, // argon2 or bcrypt, detected by prefix
+    pub bio: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub created_via: Option<CreatedVia>,
+}
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ImportIssue {
+    pub line: usize,
+    pub username: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, ToSchema)]
+pub struct ImportReport {
+    pub created: usize,
+    pub skipped: Vec<ImportIssue>, // conflicts with existing users or earlier rows
+    pub errored: Vec<ImportIssue>, // rows that failed validation or couldn't be written
+}
+
+/// One track of an import manifest, pointing at an already uploaded file
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct TrackManifestEntry {
+    pub title: String,
+    pub description: Option<String>,
+    pub audio_url: String,
+    pub cover_image_url: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub is_public: bool,
+    #[serde(default)]
+    pub downloadable: bool,
+    #[serde(default)]
+    pub license: License,
+    #[serde(default)]
+    pub download_override: bool, // allow downloads of an all-rights-reserved track
+    pub created_at: Option<DateTime<Utc>>, // keeps the original release date when migrating
+    pub technical_metadata: Option<TrackTechnicalMetadata>,
+    pub language: Option<String>, // ISO 639-1
+    #[serde(default)]
+    pub is_explicit: bool,
+}
+
+/// Outcome of one manifest entry, in manifest order
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TrackImportResult {
+    pub index: usize,
+    pub track_id: Option<TrackId>, // set when the track was created
+    pub error: Option<String>, // set when the entry was rejected
+}
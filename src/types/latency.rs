@@ -0,0 +1,91 @@
+use serde::Serialize;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+
+/// How the database looked to the last latency probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum DbHealth {
+    /// Not probed yet
+    Unknown,
+    Ok,
+    /// Answering, but slower than `DB_LATENCY_THRESHOLD_MS`
+    Degraded,
+    /// The last probe failed
+    Unreachable,
+}
+
+impl DbHealth {
+    /// Judge a probe that took `latency_ms`
+    pub fn classify(latency_ms: f64, threshold_ms: u64) -> Self {
+        if latency_ms > threshold_ms as f64 {
+            DbHealth::Degraded
+        } else {
+            DbHealth::Ok
+        }
+    }
+}
+
+/// Whether the process-wide database handle is connected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionState {
+    /// Never connected, or the last attempt failed
+    Disconnected,
+    /// Connecting for the first time
+    Connecting,
+    Connected,
+    /// Was connected, but stopped answering or is being connected again
+    Reconnecting,
+}
+
+impl ConnectionState {
+    pub fn name(self) -> &'static str {
+        match self {
+            ConnectionState::Disconnected => "disconnected",
+            ConnectionState::Connecting => "connecting",
+            ConnectionState::Connected => "connected",
+            ConnectionState::Reconnecting => "reconnecting",
+        }
+    }
+
+    pub(crate) fn from_u8(value: u8) -> Self {
+        match value {
+            1 => ConnectionState::Connecting,
+            2 => ConnectionState::Connected,
+            3 => ConnectionState::Reconnecting,
+            _ => ConnectionState::Disconnected,
+        }
+    }
+}
+
+/// Database round-trip latency as measured by the background probe
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct DbLatency {
+    pub health: DbHealth,
+    /// Latest probe's round trip; None before the first or after a failure
+    pub last_ms: Option<f64>,
+    /// Mean and worst over the recent probes
+    pub average_ms: Option<f64>,
+    pub max_ms: Option<f64>,
+    /// Round trips of the recent probes, oldest first
+    pub recent_ms: Vec<f64>,
+    pub threshold_ms: u64,
+    pub probes: u64,
+    pub failures: u64,
+    pub probed_at: Option<DateTime<Utc>>,
+    /// Why the last probe failed, if it did
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DbHealth;
+
+    #[test]
+    fn a_slow_probe_is_degraded() {
+        assert_eq!(DbHealth::classify(250.5, 200), DbHealth::Degraded);
+        assert_eq!(DbHealth::classify(200.0, 200), DbHealth::Ok);
+        assert_eq!(DbHealth::classify(3.2, 200), DbHealth::Ok);
+    }
+}
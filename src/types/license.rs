@@ -0,0 +1,73 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use utoipa::{IntoParams, ToSchema};
+
+/// How a track may be reused
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum License {
+    #[default]
+    #[serde(rename = "all-rights-reserved")]
+    AllRightsReserved,
+    #[serde(rename = "cc-by")]
+    CcBy,
+    #[serde(rename = "cc-by-sa")]
+    CcBySa,
+    #[serde(rename = "cc-by-nc")]
+    CcByNc,
+    #[serde(rename = "cc-by-nc-sa")]
+    CcByNcSa,
+    #[serde(rename = "cc0")]
+    Cc0,
+}
+
+impl License {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            License::AllRightsReserved => "all-rights-reserved",
+            License::CcBy => "cc-by",
+            License::CcBySa => "cc-by-sa",
+            License::CcByNc => "cc-by-nc",
+            License::CcByNcSa => "cc-by-nc-sa",
+            License::Cc0 => "cc0",
+        }
+    }
+
+    pub fn is_creative_commons(&self) -> bool {
+        *self != License::AllRightsReserved
+    }
+
+    /// Canonical deed of the license; None for all rights reserved
+    pub fn url(&self) -> Option<&'static str> {
+        match self {
+            License::AllRightsReserved => None,
+            License::CcBy => Some("https://creativecommons.org/licenses/by/4.0/"),
+            License::CcBySa => Some("https://creativecommons.org/licenses/by-sa/4.0/"),
+            License::CcByNc => Some("https://creativecommons.org/licenses/by-nc/4.0/"),
+            License::CcByNcSa => Some("https://creativecommons.org/licenses/by-nc-sa/4.0/"),
+            License::Cc0 => Some("https://creativecommons.org/publicdomain/zero/1.0/"),
+        }
+    }
+
+    /// Downloads are for reusable material. An all-rights-reserved track can
+    /// only be made downloadable when the owner says so with `download_override`.
+    pub fn check_downloadable(&self, downloadable: bool, download_override: bool) -> Result<(), String> {
+        if downloadable && !self.is_creative_commons() && !download_override {
+            return Err("only Creative Commons tracks can be downloadable; set download_override to allow it anyway".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// A license a track was published under before it changed. Grants can't be
+/// taken back, so copies made meanwhile keep this license.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct LicenseChange {
+    pub license: License,
+    pub changed_at: DateTime<Utc>, // when it stopped applying
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LicenseFilter {
+    /// Only tracks under this license
+    pub license: Option<License>,
+}
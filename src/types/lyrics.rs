@@ -0,0 +1,191 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use utoipa::{IntoParams, ToSchema};
+use crate::clock;
+use crate::db::error::Error;
+use crate::types::id::{TrackId, UserId};
+
+/// Most lyrics one track may carry, plain and timestamped together, in bytes
+pub const MAX_LYRICS_BYTES: usize = 64 * 1024;
+
+/// A track's lyrics, stored apart from the track so listings stay small.
+/// The record id is the track id.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Lyrics {
+    pub track_id: TrackId,
+    pub user_id: UserId, // the track's owner, so erasure can find them
+    pub plain: Option<String>,
+    pub lrc: Option<String>, // LRC text, one `[mm:ss.xx]` timestamp per line
+    pub updated_at: DateTime<Utc>,
+}
+
+/// One timestamped line of LRC lyrics
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LyricLine {
+    pub time_ms: u64,
+    pub text: String,
+}
+
+/// Lyrics in the `json` format, with the LRC variant parsed for
+/// karaoke-style display
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct LyricsView {
+    pub track_id: TrackId,
+    pub plain: Option<String>,
+    pub lines: Option<Vec<LyricLine>>, // None without a timestamped variant
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Changes to a track's lyrics. A field left out is kept, an empty one
+/// removes that variant.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct LyricsPatch {
+    pub plain: Option<String>,
+    pub lrc: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LyricsFormat {
+    Plain,
+    Lrc,
+    #[default]
+    Json,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct LyricsParams {
+    /// `plain`, `lrc` or `json` (the default)
+    #[serde(default)]
+    pub format: LyricsFormat,
+}
+
+/// Normalize line endings, drop control characters other than newlines and
+/// tabs and trailing whitespace on each line
+fn sanitize(text: &str) -> String {
+    text.replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .lines()
+        .map(|line| line.chars().filter(|c| *c == '\t' || !c.is_control()).collect::<String>().trim_end().to_string())
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Parse `mm:ss`, `mm:ss.xx` or `mm:ss.xxx` into milliseconds
+fn parse_timestamp(stamp: &str) -> Option<u64> {
+    let (minutes, seconds) = stamp.split_once(':')?;
+    let (seconds, fraction) = seconds.split_once('.').unwrap_or((seconds, ""));
+    if minutes.is_empty() || seconds.len() != 2 || fraction.len() > 3 {
+        return None;
+    }
+    let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if !digits(minutes) || !digits(seconds) || !digits(fraction) {
+        return None;
+    }
+
+    let minutes: u64 = minutes.parse().ok()?;
+    let seconds: u64 = seconds.parse().ok()?;
+    if seconds >= 60 {
+        return None;
+    }
+    let fraction: u64 = format!("{:0<3}", fraction).parse().ok()?;
+    Some((minutes * 60 + seconds) * 1000 + fraction)
+}
+
+/// Whether a bracketed tag is LRC metadata such as `[ar:Artist]`
+fn is_metadata_tag(tag: &str) -> bool {
+    tag.split_once(':').is_some_and(|(key, _)| !key.is_empty() && key.chars().all(|c| c.is_ascii_alphabetic()))
+}
+
+/// Parse LRC text into timestamped lines. Blank lines and metadata tags are
+/// skipped. Every problem is reported with its 1-based line number: lines
+/// without a valid timestamp, timestamps before the previous line's, and
+/// timestamps past `duration_ms` when it's known.
+pub fn parse_lrc(lrc: &str, duration_ms: Option<u64>) -> Result<Vec<LyricLine>, Error> {
+    let mut lines = Vec::new();
+    let mut problems = Vec::new();
+    let mut previous = 0;
+
+    for (index, raw) in lrc.lines().enumerate() {
+        let number = index + 1;
+        let raw = raw.trim();
+        if raw.is_empty() {
+            continue;
+        }
+
+        let Some((tag, text)) = raw.strip_prefix('[').and_then(|rest| rest.split_once(']')) else {
+            problems.push(format!("line {}: expected a [mm:ss.xx] timestamp", number));
+            continue;
+        };
+        let Some(time_ms) = parse_timestamp(tag) else {
+            if !is_metadata_tag(tag) {
+                problems.push(format!("line {}: invalid timestamp [{}]", number, tag));
+            }
+            continue;
+        };
+
+        if time_ms < previous {
+            problems.push(format!("line {}: [{}] is earlier than the line before it", number, tag));
+        }
+        if duration_ms.is_some_and(|duration| time_ms > duration) {
+            problems.push(format!("line {}: [{}] is past the end of the track", number, tag));
+        }
+        previous = previous.max(time_ms);
+        lines.push(LyricLine { time_ms, text: text.trim().to_string() });
+    }
+
+    if !problems.is_empty() {
+        return Err(Error::Validation(format!("invalid lrc: {}", problems.join("; "))));
+    }
+    if lines.is_empty() {
+        return Err(Error::Validation("invalid lrc: no timestamped lines".to_string()));
+    }
+    Ok(lines)
+}
+
+impl LyricsPatch {
+    /// Apply to the current lyrics of a track, returning None when no variant
+    /// is left. `duration` is the track's length in seconds, when known.
+    pub fn apply(
+        self,
+        current: Option<Lyrics>,
+        track_id: TrackId,
+        user_id: UserId,
+        duration: Option<f64>,
+    ) -> Result<Option<Lyrics>, Error> {
+        let (mut plain, mut lrc) = current.map_or((None, None), |lyrics| (lyrics.plain, lyrics.lrc));
+
+        if let Some(text) = self.plain {
+            plain = Some(sanitize(&text)).filter(|text| !text.is_empty());
+        }
+        if let Some(text) = self.lrc {
+            let text = sanitize(&text);
+            if !text.is_empty() {
+                let duration_ms = duration.filter(|d| *d > 0.0).map(|d| (d * 1000.0).round() as u64);
+                parse_lrc(&text, duration_ms)?;
+            }
+            lrc = Some(text).filter(|text| !text.is_empty());
+        }
+
+        let size = plain.as_ref().map_or(0, String::len) + lrc.as_ref().map_or(0, String::len);
+        if size > MAX_LYRICS_BYTES {
+            return Err(Error::Validation(format!("lyrics must be at most {} bytes", MAX_LYRICS_BYTES)));
+        }
+
+        if plain.is_none() && lrc.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(Lyrics { track_id, user_id, plain, lrc, updated_at: clock::now() }))
+    }
+}
+
+impl Lyrics {
+    /// The `json` format of these lyrics
+    pub fn view(self) -> LyricsView {
+        // Stored LRC was validated on the way in
+        let lines = self.lrc.as_deref().and_then(|lrc| parse_lrc(lrc, None).ok());
+        LyricsView { track_id: self.track_id, plain: self.plain, lines, updated_at: self.updated_at }
+    }
+}
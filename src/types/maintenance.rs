@@ -0,0 +1,26 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::types::id::UserId;
+
+/// What maintenance mode turns away
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum MaintenanceScope {
+    /// Writes get 503, reads keep working
+    #[default]
+    Writes,
+    /// Everything gets 503, the API is offline
+    All,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct MaintenanceState {
+    pub active: bool,
+    #[serde(default)]
+    pub scope: MaintenanceScope,
+    pub message: Option<String>, // shown to clients while active
+    pub retry_after_secs: u64, // sent as Retry-After on rejected writes
+    pub changed_by: Option<UserId>, // None when forced by MAINTENANCE
+    pub changed_at: DateTime<Utc>,
+}
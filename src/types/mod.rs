@@ -1 +1,33 @@
-pub mod user;
\ No newline at end of file
+pub mod user;
+pub mod session;
+pub mod erasure;
+pub mod pagination;
+pub mod location;
+pub mod import;
+pub mod webhook;
+pub mod federation;
+pub mod feature_flag;
+pub mod maintenance;
+pub mod audit;
+pub mod announcement;
+pub mod notification;
+pub mod gc;
+pub mod release;
+pub mod verification;
+pub mod lyrics;
+pub mod license;
+pub mod share_link;
+pub mod audio;
+pub mod attachment;
+pub mod credit;
+pub mod upload;
+pub mod realtime;
+pub mod stats;
+pub mod email_token;
+pub mod presence;
+pub mod language;
+pub mod playlist;
+pub mod push;
+pub mod latency;
+pub mod email;
+pub mod id;
@@ -0,0 +1,59 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::types::id::UserId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    AccountAccessed, // support signed in as the user
+    CreditInvitation, // someone credited the user on a track
+    CreditComment, // someone commented on a track the user is credited on
+    ReleasePublished, // someone the user follows published a release
+    VerificationApproved,
+    VerificationRejected,
+    VerificationRevoked,
+}
+
+impl NotificationKind {
+    pub const ALL: [NotificationKind; 7] = [
+        NotificationKind::AccountAccessed,
+        NotificationKind::CreditInvitation,
+        NotificationKind::CreditComment,
+        NotificationKind::ReleasePublished,
+        NotificationKind::VerificationApproved,
+        NotificationKind::VerificationRejected,
+        NotificationKind::VerificationRevoked,
+    ];
+
+    /// Whether it's pushed to users who haven't chosen otherwise
+    pub fn is_high_priority(&self) -> bool {
+        !matches!(self, NotificationKind::ReleasePublished)
+    }
+
+    /// What a push of it says. Deliberately vague: pushes pass through
+    /// third parties and show on locked screens.
+    pub fn push_title(&self) -> &'static str {
+        match self {
+            NotificationKind::AccountAccessed => "Support signed in to your account",
+            NotificationKind::CreditInvitation => "You were credited on a track",
+            NotificationKind::CreditComment => "New comment on a track you're credited on",
+            NotificationKind::ReleasePublished => "New release from someone you follow",
+            NotificationKind::VerificationApproved
+            | NotificationKind::VerificationRejected
+            | NotificationKind::VerificationRevoked => "Your verification status changed",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Notification {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub kind: NotificationKind,
+    pub message: String,
+    pub data: serde_json::Value,
+    pub read: bool,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,128 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+use crate::db::error::Error;
+
+/// One page of a listing. Offset pages echo the requested window; cursor
+/// pages carry `next_cursor` to fetch the following page, absent on the last.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct Paginated<T> {
+    pub items: Vec<T>,
+    pub limit: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+impl<T> Paginated<T> {
+    /// A page of an offset listing, for tables that need page numbers
+    pub fn new(items: Vec<T>, limit: u32, offset: u32) -> Self {
+        Self { items, limit, offset: Some(offset), next_cursor: None }
+    }
+
+    /// A page of a cursor listing
+    pub fn with_cursor(items: Vec<T>, limit: u32, next: Option<Cursor>) -> Self {
+        Self { items, limit, offset: None, next_cursor: next.map(|cursor| cursor.encode()) }
+    }
+}
+
+/// Position in a listing ordered newest first by `(created_at, id)`. Rows
+/// inserted while a client pages through can't shift it, unlike an offset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    pub created_at: DateTime<Utc>,
+    pub id: Uuid,
+}
+
+impl Cursor {
+    pub fn new(created_at: DateTime<Utc>, id: Uuid) -> Self {
+        Self { created_at, id }
+    }
+
+    /// Opaque form handed to clients
+    pub fn encode(&self) -> String {
+        hex::encode(format!("{}|{}", self.created_at.to_rfc3339(), self.id))
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, Error> {
+        let invalid = || Error::Validation("invalid cursor".to_string());
+
+        let raw = hex::decode(cursor).map_err(|_| invalid())?;
+        let raw = String::from_utf8(raw).map_err(|_| invalid())?;
+        let (created_at, id) = raw.split_once('|').ok_or_else(invalid)?;
+
+        Ok(Self {
+            created_at: DateTime::parse_from_rfc3339(created_at).map_err(|_| invalid())?.with_timezone(&Utc),
+            id: id.parse().map_err(|_| invalid())?,
+        })
+    }
+
+    /// Whether a row at `(created_at, id)` comes after this cursor
+    pub fn precedes(&self, created_at: DateTime<Utc>, id: Uuid) -> bool {
+        (created_at, id) < (self.created_at, self.id)
+    }
+}
+
+/// Cut one cursor page out of rows already in memory, such as a user's
+/// embedded uploads. Rows are ordered newest first by `key`.
+pub fn cursor_page<T, I: Into<Uuid>>(
+    mut rows: Vec<T>,
+    key: impl Fn(&T) -> (DateTime<Utc>, I),
+    after: Option<Cursor>,
+    limit: u32,
+) -> (Vec<T>, Option<Cursor>) {
+    let key = |row: &T| {
+        let (created_at, id) = key(row);
+        (created_at, id.into())
+    };
+    if let Some(after) = after {
+        rows.retain(|row| {
+            let (created_at, id) = key(row);
+            after.precedes(created_at, id)
+        });
+    }
+    rows.sort_by_key(|row| std::cmp::Reverse(key(row)));
+
+    let limit = (limit as usize).max(1);
+    let next = (rows.len() > limit).then(|| {
+        let (created_at, id) = key(&rows[limit - 1]);
+        Cursor::new(created_at, id)
+    });
+    rows.truncate(limit);
+
+    (rows, next)
+}
+
+/// Cut one cursor page out of rows already in the order they're listed in,
+/// such as a hand-arranged one. The page resumes after the row the cursor
+/// names; a cursor for a row that's no longer listed is rejected.
+pub fn ordered_page<T, I: Into<Uuid>>(
+    rows: Vec<T>,
+    key: impl Fn(&T) -> (DateTime<Utc>, I),
+    after: Option<Cursor>,
+    limit: u32,
+) -> Result<(Vec<T>, Option<Cursor>), Error> {
+    let key = |row: &T| {
+        let (created_at, id) = key(row);
+        (created_at, id.into())
+    };
+    let start = match after {
+        Some(after) => rows.iter()
+            .position(|row| key(row) == (after.created_at, after.id))
+            .map(|index| index + 1)
+            .ok_or_else(|| Error::Validation("cursor no longer matches a listed item".to_string()))?,
+        None => 0,
+    };
+
+    let limit = (limit as usize).max(1);
+    let mut rows: Vec<T> = rows.into_iter().skip(start).collect();
+    let next = (rows.len() > limit).then(|| {
+        let (created_at, id) = key(&rows[limit - 1]);
+        Cursor::new(created_at, id)
+    });
+    rows.truncate(limit);
+
+    Ok((rows, next))
+}
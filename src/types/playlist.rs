@@ -0,0 +1,41 @@
+use serde::{Serialize, Deserialize};
+use utoipa::{IntoParams, ToSchema};
+use crate::types::id::TrackId;
+
+/// A track to add to a playlist
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct AddPlaylistTrack {
+    pub track_id: TrackId,
+    /// Where among the tracks the caller sees to put it; the end when left out
+    pub index: Option<usize>,
+    /// The revision the caller last saw; refused with 409 if it's changed since
+    pub revision: Option<u64>,
+}
+
+/// Where to move a track in a playlist
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct MovePlaylistTrack {
+    /// Among the tracks the caller sees, after taking this one out
+    pub index: usize,
+    /// The revision the caller last saw; refused with 409 if it's changed since
+    pub revision: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct RevisionParams {
+    /// The revision the caller last saw; refused with 409 if it's changed since
+    pub revision: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CollaborativeSettings {
+    /// Let anyone who can see the playlist add, remove and reorder its tracks
+    pub is_collaborative: bool,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct DeletePlaylistParams {
+    /// Drop the playlist for good instead of keeping it aside
+    #[serde(default)]
+    pub hard: bool,
+}
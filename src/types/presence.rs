@@ -0,0 +1,24 @@
+use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::types::id::TrackId;
+use crate::types::user::TrackView;
+
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ListenerCount {
+    pub track_id: TrackId,
+    pub listeners: usize, // signed-in and anonymous listeners pinging in the last minute
+}
+
+/// What a user who shares it is listening to
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct NowPlaying {
+    pub track: TrackView,
+    pub since: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NowPlayingSettings {
+    /// Show the track you're playing on your profile
+    pub share: bool,
+}
@@ -0,0 +1,116 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::clock;
+use crate::config;
+use crate::db::error::Error;
+use crate::types::id::UserId;
+use crate::types::notification::{Notification, NotificationKind};
+
+/// Most push subscriptions one user may hold; subscribing past it drops
+/// the oldest
+pub const MAX_PUSH_SUBSCRIPTIONS: usize = 10;
+
+/// Longest push service endpoint accepted
+pub const MAX_ENDPOINT_LEN: usize = 2048;
+
+/// The keys a browser encrypts its pushes with, base64url encoded
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PushKeys {
+    pub p256dh: String,
+    pub auth: String,
+}
+
+/// A browser's subscription as `PushSubscription.toJSON()` gives it
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewPushSubscription {
+    pub endpoint: String,
+    pub keys: PushKeys,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PushSubscription {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub endpoint: String,
+    pub keys: PushKeys,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A subscription as shown to its user, without its keys
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct PushSubscriptionView {
+    pub id: Uuid,
+    pub endpoint: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Which kinds of notification are also sent as pushes
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PushPreferences {
+    pub kinds: Vec<NotificationKind>,
+}
+
+/// The key browsers need to subscribe, as `applicationServerKey`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct VapidPublicKey {
+    pub public_key: String,
+}
+
+/// What a push carries. Nothing but a title and where to go, since push
+/// services see the metadata and devices may show it on a locked screen.
+#[derive(Debug, Clone, Serialize)]
+pub struct PushPayload {
+    pub title: String,
+    pub url: String,
+}
+
+impl NewPushSubscription {
+    pub fn into_subscription(self, user_id: UserId) -> Result<PushSubscription, Error> {
+        let endpoint = self.endpoint.trim().to_string();
+        if !endpoint.starts_with("https://") || endpoint.len() > MAX_ENDPOINT_LEN {
+            return Err(Error::Validation(format!("endpoint must be an https URL of at most {} characters", MAX_ENDPOINT_LEN)));
+        }
+        let is_key = |key: &str| !key.is_empty() && key.len() <= 256 && key.chars().all(|c| c.is_ascii_alphanumeric() || "-_=".contains(c));
+        if !is_key(&self.keys.p256dh) || !is_key(&self.keys.auth) {
+            return Err(Error::Validation("keys must be base64url encoded".to_string()));
+        }
+
+        Ok(PushSubscription {
+            id: Uuid::new_v4(),
+            user_id,
+            endpoint,
+            keys: self.keys,
+            created_at: clock::now(),
+        })
+    }
+}
+
+impl From<PushSubscription> for PushSubscriptionView {
+    fn from(subscription: PushSubscription) -> Self {
+        Self {
+            id: subscription.id,
+            endpoint: subscription.endpoint,
+            created_at: subscription.created_at,
+        }
+    }
+}
+
+impl PushPayload {
+    /// The push for a notification: a title for its kind and a link to what
+    /// it's about, never the message itself
+    pub fn for_notification(notification: &Notification) -> Self {
+        let id = |key: &str| notification.data.get(key).and_then(|id| id.as_str()).map(str::to_string);
+        let path = match (id("track_id"), id("release_id")) {
+            (Some(track_id), _) => format!("/tracks/{}", track_id),
+            (None, Some(release_id)) => format!("/releases/{}", release_id),
+            (None, None) => "/notifications".to_string(),
+        };
+
+        Self {
+            title: notification.kind.push_title().to_string(),
+            url: format!("{}{}", config::public_url(), path),
+        }
+    }
+}
@@ -0,0 +1,280 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::{IntoParams, ToSchema};
+use crate::types::id::{CommentId, PlaylistId, TrackId, UserId};
+use crate::types::notification::Notification;
+use crate::types::user::{CommentView, Report, ReportTarget, TrackView};
+
+/// Where an uploaded file is on its way to becoming playable
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingState {
+    Processing,
+    Ready,
+    Failed,
+}
+
+/// Which event stream a pushed message belongs on. The WebSocket takes them all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Notifications,
+    Feed,
+    Moderation,
+}
+
+/// A message the server pushes over `GET /ws` and the event streams, tagged
+/// by `type`
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerMessage {
+    /// Sent once the socket is authenticated
+    Ready {
+        user_id: UserId,
+        unread_count: u64,
+        /// Notifications since the `since` the client connected with; left
+        /// out without one. Zero means the client's list is current.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        missed_count: Option<u64>,
+        /// Pass as `since` when reconnecting
+        server_time: DateTime<Utc>,
+    },
+    Notification {
+        notification: Notification,
+    },
+    UnreadCount {
+        unread_count: u64,
+    },
+    ProcessingStatus {
+        upload_id: Uuid,
+        track_id: Option<TrackId>,
+        status: ProcessingState,
+    },
+    /// The socket now gets the comment changes on a track
+    Subscribed {
+        track_id: TrackId,
+    },
+    /// A `subscribe` was refused, e.g. for a track the caller can't see
+    SubscriptionRejected {
+        track_id: TrackId,
+        reason: String,
+    },
+    CommentCreated {
+        track_id: TrackId,
+        comment: CommentView,
+    },
+    CommentUpdated {
+        track_id: TrackId,
+        comment: CommentView,
+    },
+    CommentDeleted {
+        track_id: TrackId,
+        comment_id: CommentId,
+    },
+    /// How many are listening to a subscribed track, sent on subscribing
+    /// and whenever it changes
+    ListenerCount {
+        track_id: TrackId,
+        listeners: usize,
+    },
+    /// `user_id` is writing a comment on a subscribed track
+    TypingStart {
+        track_id: TrackId,
+        user_id: UserId,
+    },
+    /// `user_id` sent or gave up their comment, or went quiet for ten seconds
+    TypingStop {
+        track_id: TrackId,
+        user_id: UserId,
+    },
+    /// The socket now gets the edits to a playlist's tracks
+    PlaylistSubscribed {
+        playlist_id: PlaylistId,
+        revision: u64,
+    },
+    /// A `subscribe_playlist` was refused
+    PlaylistSubscriptionRejected {
+        playlist_id: PlaylistId,
+        reason: String,
+    },
+    /// `user_id` added a track at `index` among those the subscriber sees
+    PlaylistTrackAdded {
+        playlist_id: PlaylistId,
+        revision: u64,
+        user_id: UserId,
+        index: usize,
+        track: TrackView,
+    },
+    PlaylistTrackRemoved {
+        playlist_id: PlaylistId,
+        revision: u64,
+        user_id: UserId,
+        track_id: TrackId,
+    },
+    /// `user_id` moved a track to `index` among those the subscriber sees
+    PlaylistTrackMoved {
+        playlist_id: PlaylistId,
+        revision: u64,
+        user_id: UserId,
+        track_id: TrackId,
+        index: usize,
+    },
+    /// The socket now gets changes to the report queue
+    ModerationSubscribed,
+    /// A `subscribe_moderation` was refused
+    ModerationSubscriptionRejected {
+        reason: String,
+    },
+    /// A report was filed. `open_reports` counts those open against its
+    /// target, this one included.
+    ReportCreated {
+        report: Report,
+        open_reports: u64,
+    },
+    /// A report moved along the queue. `open_reports` counts those still
+    /// open against its target.
+    ReportStatusChanged {
+        report: Report,
+        open_reports: u64,
+    },
+    /// A target just reached `REPORT_URGENT_THRESHOLD` open reports
+    ReportThresholdReached {
+        target: ReportTarget,
+        open_reports: u64,
+    },
+    /// The user is no longer a moderator, so queue changes stopped. Sent
+    /// last on `GET /admin/reports/stream`.
+    ModerationRevoked,
+    /// The owner deleted a subscribed playlist; no more edits will come
+    PlaylistDeleted {
+        playlist_id: PlaylistId,
+    },
+    /// Someone the user follows published a track
+    FeedTrack {
+        track: TrackView,
+    },
+    /// Sent first on an event stream resumed from an event no longer kept:
+    /// some were missed, so reload them over REST
+    Resync,
+    Pong,
+}
+
+impl ServerMessage {
+    /// The event stream a pushed message goes out on; None for replies to
+    /// one socket
+    pub fn channel(&self) -> Option<Channel> {
+        match self {
+            ServerMessage::Notification { .. }
+            | ServerMessage::UnreadCount { .. }
+            | ServerMessage::ProcessingStatus { .. } => Some(Channel::Notifications),
+            ServerMessage::FeedTrack { .. } => Some(Channel::Feed),
+            ServerMessage::ReportCreated { .. }
+            | ServerMessage::ReportStatusChanged { .. }
+            | ServerMessage::ReportThresholdReached { .. }
+            | ServerMessage::ModerationRevoked => Some(Channel::Moderation),
+            _ => None,
+        }
+    }
+
+    /// The `type` tag, used as the event name on event streams
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ServerMessage::Ready { .. } => "ready",
+            ServerMessage::Notification { .. } => "notification",
+            ServerMessage::UnreadCount { .. } => "unread_count",
+            ServerMessage::ProcessingStatus { .. } => "processing_status",
+            ServerMessage::Subscribed { .. } => "subscribed",
+            ServerMessage::SubscriptionRejected { .. } => "subscription_rejected",
+            ServerMessage::CommentCreated { .. } => "comment_created",
+            ServerMessage::CommentUpdated { .. } => "comment_updated",
+            ServerMessage::CommentDeleted { .. } => "comment_deleted",
+            ServerMessage::ListenerCount { .. } => "listener_count",
+            ServerMessage::TypingStart { .. } => "typing_start",
+            ServerMessage::TypingStop { .. } => "typing_stop",
+            ServerMessage::PlaylistSubscribed { .. } => "playlist_subscribed",
+            ServerMessage::PlaylistSubscriptionRejected { .. } => "playlist_subscription_rejected",
+            ServerMessage::PlaylistTrackAdded { .. } => "playlist_track_added",
+            ServerMessage::PlaylistTrackRemoved { .. } => "playlist_track_removed",
+            ServerMessage::PlaylistTrackMoved { .. } => "playlist_track_moved",
+            ServerMessage::PlaylistDeleted { .. } => "playlist_deleted",
+            ServerMessage::ModerationSubscribed => "moderation_subscribed",
+            ServerMessage::ModerationSubscriptionRejected { .. } => "moderation_subscription_rejected",
+            ServerMessage::ReportCreated { .. } => "report_created",
+            ServerMessage::ReportStatusChanged { .. } => "report_status_changed",
+            ServerMessage::ReportThresholdReached { .. } => "report_threshold_reached",
+            ServerMessage::ModerationRevoked => "moderation_revoked",
+            ServerMessage::FeedTrack { .. } => "feed_track",
+            ServerMessage::Resync => "resync",
+            ServerMessage::Pong => "pong",
+        }
+    }
+}
+
+/// A message a client sends over `GET /ws`, tagged by `type`
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ClientMessage {
+    /// Authenticate, when no token was given in the URL. Must be the first
+    /// message.
+    Auth {
+        token: String,
+        since: Option<DateTime<Utc>>,
+    },
+    /// Get new, edited and deleted comments on a track as they happen,
+    /// answered with `subscribed` or `subscription_rejected`
+    Subscribe {
+        track_id: TrackId,
+    },
+    /// Stop getting a track's comments
+    Unsubscribe {
+        track_id: TrackId,
+    },
+    /// Sent every few seconds while writing a comment on a subscribed
+    /// track; it counts for ten seconds. Ignored past twenty in ten seconds.
+    TypingStart {
+        track_id: TrackId,
+    },
+    /// Sent when the comment is sent or abandoned
+    TypingStop {
+        track_id: TrackId,
+    },
+    /// Get the tracks added to, removed from and moved in a playlist as it
+    /// happens, answered with `playlist_subscribed` or
+    /// `playlist_subscription_rejected`
+    SubscribePlaylist {
+        playlist_id: PlaylistId,
+    },
+    /// Stop getting a playlist's edits
+    UnsubscribePlaylist {
+        playlist_id: PlaylistId,
+    },
+    /// Moderators only: get reports as they're filed and change status,
+    /// answered with `moderation_subscribed` or
+    /// `moderation_subscription_rejected`
+    SubscribeModeration,
+    /// Stop getting report queue changes
+    UnsubscribeModeration,
+    /// Sent by players every 30 seconds or so while a track plays
+    NowPlaying {
+        track_id: TrackId,
+    },
+    /// Answered with `pong`, for clients that can't send ping frames
+    Ping,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct WsParams {
+    /// Bearer token; otherwise send an `auth` message first
+    pub token: Option<String>,
+    /// When the client last had an up-to-date notification list, usually
+    /// the `server_time` of its last `ready`
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct StreamParams {
+    /// Bearer token, for clients like `EventSource` that can't send headers
+    pub token: Option<String>,
+    /// Resume after this event, for clients that can't send `Last-Event-ID`
+    pub last_event_id: Option<u64>,
+}
@@ -0,0 +1,211 @@
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, Utc};
+use utoipa::ToSchema;
+use crate::clock;
+use crate::db::error::Error;
+use crate::import;
+use crate::types::id::{TrackId, UserId};
+use crate::types::user::{PublicUser, TrackView};
+
+/// Longest release title accepted, in characters
+pub const MAX_TITLE_LEN: usize = 200;
+
+/// Most tracks on one release
+pub const MAX_TRACKS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseType {
+    Single,
+    Ep,
+    Album,
+}
+
+/// A single, EP or album grouping an artist's tracks in order. Tracks are
+/// referenced, not owned: deleting a release leaves them in place.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Release {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub title: String,
+    pub release_type: ReleaseType,
+    pub cover_image_url: Option<String>,
+    pub release_date: Option<NaiveDate>,
+    pub track_ids: Vec<TrackId>, // in release order, all uploads of `user_id`
+    pub catalog_number: Option<String>,
+    pub upc: Option<String>,
+    pub published_at: Option<DateTime<Utc>>, // None while a draft only the artist sees
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Release {
+    /// Drafts are only visible to the artist
+    pub fn is_visible_to(&self, viewer: Option<UserId>) -> bool {
+        self.published_at.is_some() || viewer == Some(self.user_id)
+    }
+
+    /// Where `track_id` sits on this release, if it's on it
+    pub fn summary_for(&self, track_id: TrackId) -> Option<ReleaseSummary> {
+        let index = self.track_ids.iter().position(|id| *id == track_id)?;
+        Some(ReleaseSummary {
+            id: self.id,
+            title: self.title.clone(),
+            release_type: self.release_type,
+            position: index + 1,
+        })
+    }
+}
+
+/// Which release a track appears on, as shown with the track
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ReleaseSummary {
+    pub id: Uuid,
+    pub title: String,
+    pub release_type: ReleaseType,
+    pub position: usize, // 1-based track number on the release
+}
+
+/// A track together with the releases it appears on
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TrackDetail {
+    #[serde(flatten)]
+    pub track: TrackView,
+    pub releases: Vec<ReleaseSummary>,
+}
+
+/// A release with the tracks on it the viewer may see, in order
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ReleaseView {
+    pub id: Uuid,
+    pub artist: PublicUser,
+    pub title: String,
+    pub release_type: ReleaseType,
+    pub cover_image_url: Option<String>,
+    pub release_date: Option<NaiveDate>,
+    pub catalog_number: Option<String>,
+    pub upc: Option<String>,
+    pub tracks: Vec<TrackView>,
+    pub published_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A release as sent by its artist
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewRelease {
+    pub title: String,
+    pub release_type: ReleaseType,
+    pub cover_image_url: Option<String>,
+    pub release_date: Option<NaiveDate>,
+    #[serde(default)]
+    pub track_ids: Vec<TrackId>,
+    pub catalog_number: Option<String>,
+    pub upc: Option<String>,
+}
+
+/// Changes to a release. Fields left out are kept as they are; `track_ids`
+/// replaces the whole list.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct ReleasePatch {
+    pub title: Option<String>,
+    pub release_type: Option<ReleaseType>,
+    pub cover_image_url: Option<String>,
+    pub release_date: Option<NaiveDate>,
+    pub track_ids: Option<Vec<TrackId>>,
+    pub catalog_number: Option<String>,
+    pub upc: Option<String>,
+}
+
+fn clean_title(title: &str) -> Result<String, Error> {
+    let title = title.trim();
+    if title.is_empty() || title.chars().count() > MAX_TITLE_LEN {
+        return Err(Error::Validation(format!("title must be 1 to {} characters", MAX_TITLE_LEN)));
+    }
+    Ok(title.to_string())
+}
+
+fn clean_cover(url: &str) -> Result<String, Error> {
+    let url = url.trim();
+    if !import::is_http_url(url) {
+        return Err(Error::Validation("cover_image_url must be an http(s) URL".to_string()));
+    }
+    Ok(url.to_string())
+}
+
+/// UPCs are 12 digits, EANs 13
+fn clean_upc(upc: &str) -> Result<String, Error> {
+    let upc = upc.trim();
+    if !matches!(upc.len(), 12 | 13) || !upc.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Error::Validation("upc must be 12 or 13 digits".to_string()));
+    }
+    Ok(upc.to_string())
+}
+
+fn check_track_ids(track_ids: &[TrackId]) -> Result<(), Error> {
+    if track_ids.len() > MAX_TRACKS {
+        return Err(Error::Validation(format!("a release holds at most {} tracks", MAX_TRACKS)));
+    }
+    let mut seen = HashSet::new();
+    if !track_ids.iter().all(|id| seen.insert(id)) {
+        return Err(Error::Validation("a track can only appear once on a release".to_string()));
+    }
+    Ok(())
+}
+
+impl NewRelease {
+    /// Validate into a draft release by `user_id`. Whether the tracks are
+    /// the artist's own is checked when it's stored.
+    pub fn into_release(self, user_id: UserId) -> Result<Release, Error> {
+        check_track_ids(&self.track_ids)?;
+        let now = clock::now();
+
+        Ok(Release {
+            id: Uuid::new_v4(),
+            user_id,
+            title: clean_title(&self.title)?,
+            release_type: self.release_type,
+            cover_image_url: self.cover_image_url.as_deref().map(clean_cover).transpose()?,
+            release_date: self.release_date,
+            track_ids: self.track_ids,
+            catalog_number: self.catalog_number.map(|c| c.trim().to_string()).filter(|c| !c.is_empty()),
+            upc: self.upc.as_deref().map(clean_upc).transpose()?,
+            published_at: None,
+            created_at: now,
+            updated_at: now,
+        })
+    }
+}
+
+impl ReleasePatch {
+    /// Validate the fields that are present into `release`
+    pub fn apply(self, release: &mut Release) -> Result<(), Error> {
+        if let Some(title) = &self.title {
+            release.title = clean_title(title)?;
+        }
+        if let Some(release_type) = self.release_type {
+            release.release_type = release_type;
+        }
+        if let Some(url) = &self.cover_image_url {
+            release.cover_image_url = Some(clean_cover(url)?);
+        }
+        if self.release_date.is_some() {
+            release.release_date = self.release_date;
+        }
+        if let Some(track_ids) = self.track_ids {
+            check_track_ids(&track_ids)?;
+            release.track_ids = track_ids;
+        }
+        if let Some(catalog_number) = self.catalog_number {
+            release.catalog_number = Some(catalog_number.trim().to_string()).filter(|c| !c.is_empty());
+        }
+        if let Some(upc) = &self.upc {
+            release.upc = Some(clean_upc(upc)?);
+        }
+
+        release.updated_at = clock::now();
+        Ok(())
+    }
+}
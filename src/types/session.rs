@@ -0,0 +1,15 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use crate::types::id::UserId;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub token_hash: String, // SHA-256 of the bearer token, the token itself is never stored
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(default)]
+    pub impersonator_id: Option<UserId>, // the admin acting as `user_id`, for support sessions
+}
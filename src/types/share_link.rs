@@ -0,0 +1,113 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::{IntoParams, ToSchema};
+use crate::{auth, clock, config};
+use crate::db::error::Error;
+use crate::types::id::{TrackId, UserId};
+
+/// Most uses a share link may be given
+pub const MAX_SHARE_LINK_USES: u32 = 100_000;
+
+/// A secret link that lets anyone holding it hear a track that isn't public
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShareLink {
+    pub id: Uuid,
+    pub track_id: TrackId,
+    pub owner_id: UserId,
+    pub token_hash: String,
+    pub allow_download: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_uses: Option<u32>,
+    pub use_count: u32,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct NewShareLink {
+    /// When the link stops working; never when left out
+    pub expires_at: Option<DateTime<Utc>>,
+    /// How many times the link may be opened; unlimited when left out
+    pub max_uses: Option<u32>,
+    /// Let the link download the original audio too
+    #[serde(default)]
+    pub allow_download: bool,
+}
+
+/// A share link as its owner sees it
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct ShareLinkView {
+    pub id: Uuid,
+    pub track_id: TrackId,
+    /// Only when the link is made, since just a hash of the token is kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    pub allow_download: bool,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_uses: Option<u32>,
+    pub use_count: u32,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct ShareParams {
+    /// Token of a share link for a track that isn't public
+    pub share: Option<String>,
+}
+
+impl NewShareLink {
+    pub fn into_link(self, track_id: TrackId, owner_id: UserId, token: &str) -> Result<ShareLink, Error> {
+        let now = clock::now();
+        if self.expires_at.is_some_and(|expires_at| expires_at <= now) {
+            return Err(Error::Validation("expires_at must be in the future".to_string()));
+        }
+        if self.max_uses.is_some_and(|max| max == 0 || max > MAX_SHARE_LINK_USES) {
+            return Err(Error::Validation(format!("max_uses must be between 1 and {}", MAX_SHARE_LINK_USES)));
+        }
+
+        Ok(ShareLink {
+            id: Uuid::new_v4(),
+            track_id,
+            owner_id,
+            token_hash: auth::hash_token(token),
+            allow_download: self.allow_download,
+            expires_at: self.expires_at,
+            max_uses: self.max_uses,
+            use_count: 0,
+            last_used_at: None,
+            created_at: now,
+        })
+    }
+}
+
+impl ShareLinkView {
+    /// The view of a link just made, the one time its token is shown
+    pub fn with_token(link: ShareLink, token: String) -> Self {
+        Self {
+            url: Some(format!("{}/share/{}", config::public_url(), token)),
+            token: Some(token),
+            ..Self::from(link)
+        }
+    }
+}
+
+impl From<ShareLink> for ShareLinkView {
+    fn from(link: ShareLink) -> Self {
+        Self {
+            id: link.id,
+            track_id: link.track_id,
+            url: None,
+            token: None,
+            allow_download: link.allow_download,
+            expires_at: link.expires_at,
+            max_uses: link.max_uses,
+            use_count: link.use_count,
+            last_used_at: link.last_used_at,
+            created_at: link.created_at,
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Serialize, Deserialize};
+use chrono::NaiveDate;
+use utoipa::{IntoParams, ToSchema};
+use crate::types::id::TrackId;
+
+/// Longest window of daily plays a stats request may cover
+pub const MAX_STATS_DAYS: u32 = 90;
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct StatsParams {
+    /// Days of plays to include, ending today; 30 by default, at most 90
+    pub days: Option<u32>,
+}
+
+/// Plays on one day, in UTC
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PlayBucket {
+    pub day: NaiveDate,
+    pub plays: u64,
+}
+
+/// How a track is doing, for its owner
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct TrackStats {
+    pub track_id: TrackId,
+    pub likes: u32,
+    pub dislikes: u32,
+    pub play_count: u64, // all time
+    pub download_count: u64,
+    pub comment_count: u64,
+    pub plays: Vec<PlayBucket>, // one per day of the window, oldest first, days without plays included
+}
@@ -0,0 +1,138 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+use utoipa::ToSchema;
+use crate::clock;
+use crate::db::error::Error;
+use crate::resumable::{self, UPLOAD_EXPIRY_HOURS};
+use crate::types::id::UserId;
+use crate::types::license::License;
+use crate::types::user::TrackTechnicalMetadata;
+
+/// Bytes `start` up to but not including `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+/// An audio upload sent in chunks, not yet made into a track
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResumableUpload {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub filename: String,
+    pub extension: String,
+    pub size: u64,
+    pub chunks: Vec<ByteRange>, // as received, overlaps and repeats included
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewUpload {
+    /// The file's name, whose extension must be an accepted audio type
+    pub filename: String,
+    /// The file's size in bytes
+    pub size: u64,
+}
+
+/// The track an upload becomes once every byte has arrived
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct FinalizeUpload {
+    pub title: String,
+    pub description: Option<String>,
+    pub cover_image_url: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Option<Vec<String>>,
+    #[serde(default)]
+    pub is_public: bool,
+    #[serde(default)]
+    pub downloadable: bool,
+    #[serde(default)]
+    pub license: License,
+    #[serde(default)]
+    pub download_override: bool, // allow downloads of an all-rights-reserved track
+    pub technical_metadata: Option<TrackTechnicalMetadata>,
+    pub language: Option<String>, // ISO 639-1
+    #[serde(default)]
+    pub is_explicit: bool,
+}
+
+/// Where an upload stands, so a client can tell what's left to send
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct UploadStatus {
+    pub id: Uuid,
+    pub filename: String,
+    pub size: u64,
+    pub received: Vec<ByteRange>, // merged, in order
+    pub received_bytes: u64,
+    pub complete: bool,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl NewUpload {
+    pub fn into_upload(self, user_id: UserId) -> Result<ResumableUpload, Error> {
+        let filename = self.filename.trim().to_string();
+        let extension = resumable::audio_extension(&filename)?;
+        if self.size == 0 {
+            return Err(Error::Validation("file is empty".to_string()));
+        }
+        if self.size > resumable::max_bytes() {
+            return Err(Error::PayloadTooLarge);
+        }
+
+        let now = clock::now();
+        Ok(ResumableUpload {
+            id: Uuid::new_v4(),
+            user_id,
+            filename,
+            extension,
+            size: self.size,
+            chunks: Vec::new(),
+            created_at: now,
+            expires_at: now + Duration::hours(UPLOAD_EXPIRY_HOURS),
+        })
+    }
+}
+
+impl ResumableUpload {
+    /// The ranges received so far, merged and in order
+    pub fn received(&self) -> Vec<ByteRange> {
+        let mut chunks = self.chunks.clone();
+        chunks.sort_by_key(|chunk| chunk.start);
+
+        let mut merged: Vec<ByteRange> = Vec::new();
+        for chunk in chunks {
+            match merged.last_mut() {
+                Some(last) if chunk.start <= last.end => last.end = last.end.max(chunk.end),
+                _ => merged.push(chunk),
+            }
+        }
+        merged
+    }
+
+    /// Whether every byte of the file has arrived
+    pub fn is_complete(&self) -> bool {
+        self.received().first().is_some_and(|range| range.start == 0 && range.end == self.size)
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= clock::now()
+    }
+}
+
+impl From<ResumableUpload> for UploadStatus {
+    fn from(upload: ResumableUpload) -> Self {
+        let received = upload.received();
+        Self {
+            received_bytes: received.iter().map(|range| range.end - range.start).sum(),
+            complete: upload.is_complete(),
+            received,
+            id: upload.id,
+            filename: upload.filename,
+            size: upload.size,
+            expires_at: upload.expires_at,
+        }
+    }
+}
@@ -1,17 +1,33 @@
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use utoipa::{IntoParams, ToSchema};
+use crate::auth::{hash_password, validate_password};
+use crate::db::error::Error;
+use crate::types::id::{CommentId, PlaylistId, TrackId, UserId};
+use crate::{clock, config, import, moderation};
+use crate::types::attachment::{Attachment, AttachmentView};
+use crate::types::audio::AudioVersion;
+use crate::types::credit::{Credit, CreditView};
+use crate::types::language;
+use crate::types::license::{License, LicenseChange};
+use crate::types::location::Location;
+use crate::types::notification::NotificationKind;
+use crate::types::presence::NowPlaying;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub enum CreatedVia {
     Web,
     Mobile,
     Google,
     Spotify,
-    SoundCloud
+    SoundCloud,
+    Cli, // created by an operator with libretune-admin
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
 pub enum ReportStatus {
     Open,
     InProgress,
@@ -19,10 +35,57 @@ pub enum ReportStatus {
     Closed,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// What a report is about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", content = "id", rename_all = "lowercase")]
+pub enum ReportTarget {
+    User(UserId),
+    Track(TrackId),
+    Comment(CommentId),
+}
+
+impl ReportTarget {
+    pub fn kind(self) -> ReportTargetKind {
+        match self {
+            ReportTarget::User(_) => ReportTargetKind::User,
+            ReportTarget::Track(_) => ReportTargetKind::Track,
+            ReportTarget::Comment(_) => ReportTargetKind::Comment,
+        }
+    }
+
+    pub fn id(self) -> Uuid {
+        match self {
+            ReportTarget::User(id) => id.into(),
+            ReportTarget::Track(id) => id.into(),
+            ReportTarget::Comment(id) => id.into(),
+        }
+    }
+}
+
+/// The kind of a `ReportTarget`, for filtering moderation queues
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportTargetKind {
+    User,
+    Track,
+    Comment,
+}
+
+impl ReportTargetKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ReportTargetKind::User => "user",
+            ReportTargetKind::Track => "track",
+            ReportTargetKind::Comment => "comment",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct Report {
     pub id: Uuid,
-    pub user_id: Uuid,
+    pub user_id: UserId, // who filed the report
+    pub target: ReportTarget,
     pub reason: String,
     pub description: Option<String>,
     pub created_at: DateTime<Utc>,
@@ -30,24 +93,79 @@ pub struct Report {
     pub status: ReportStatus,
 }
 
+/// How many open reports there are against one target
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TargetReportCount {
+    pub target: ReportTarget,
+    pub open_reports: u64,
+}
+
+/// The state of the moderation queue at a glance
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ModerationSummary {
+    pub open_reports: u64,
+    /// Targets with the most open reports, most first
+    pub targets: Vec<TargetReportCount>,
+    /// Moderators watching the queue live on this instance
+    pub connected_moderators: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Comment {
-    pub id: Uuid,
-    pub referred_track_id: Uuid,
-    pub user_id: Uuid,
+    pub id: CommentId,
+    pub referred_track_id: TrackId,
+    pub user_id: UserId,
     pub content: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub is_deleted: bool,
     pub replies: Option<Vec<Comment>>,
-    pub likes: Option<Vec<Uuid>>,
-    pub dislikes: Option<Vec<Uuid>>,
+    pub likes: Option<Vec<UserId>>,
+    pub dislikes: Option<Vec<UserId>>,
     pub is_pinned: bool,
     pub reports: Option<Vec<Report>>,
-    pub parent_comment_id: Option<Uuid>,
+    pub parent_comment_id: Option<CommentId>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Which of a track's comments to list
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CommentFilter {
+    /// Every comment that isn't deleted
+    #[default]
+    All,
+    /// Only pinned comments that aren't deleted
+    PinnedOnly,
+    /// Moderators only: comments with an open or in-progress report,
+    /// deleted or not
+    Reported,
+    /// Moderators only: every comment, deleted ones included
+    IncludeDeleted,
+}
+
+impl CommentFilter {
+    pub fn requires_moderator(self) -> bool {
+        matches!(self, CommentFilter::Reported | CommentFilter::IncludeDeleted)
+    }
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+pub struct CommentFilterParams {
+    /// Which comments to list; `reported` and `include_deleted` are for
+    /// moderators
+    #[serde(default)]
+    pub filter: CommentFilter,
+}
+
+/// A comment as posted by a client
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewComment {
+    pub content: String,
+    /// The comment this replies to, if any
+    pub parent_comment_id: Option<CommentId>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub struct TrackTechnicalMetadata {
     pub bitrate: u32, // in kbps
     pub sample_rate: u32, // in Hz
@@ -61,8 +179,8 @@ pub struct TrackTechnicalMetadata {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Track {
-    pub id: Uuid,
-    pub user_id: Uuid,
+    pub id: TrackId,
+    pub user_id: UserId,
     pub title: String,
     pub description: Option<String>,
     pub audio_url: String,
@@ -76,13 +194,43 @@ pub struct Track {
     pub likes: u32,
     pub dislikes: u32,
     pub comments: Option<Vec<Comment>>,
+    #[serde(default)]
+    pub downloadable: bool, // lets anyone download the original file, not just the owner
+    #[serde(default)]
+    pub download_count: u64,
+    #[serde(default)]
+    pub technical_metadata: Option<TrackTechnicalMetadata>,
+    #[serde(default)]
+    pub comment_count: u64, // live comments and replies, so reads needn't load `comments`
+    #[serde(default)]
+    pub has_lyrics: bool, // the lyrics themselves live in the `lyrics` table
+    #[serde(default)]
+    pub license: License,
+    #[serde(default)]
+    pub license_history: Vec<LicenseChange>, // earlier licenses, oldest first
+    #[serde(default)]
+    pub audio_versions: Vec<AudioVersion>, // replaced audio files, oldest first; owner only
+    #[serde(default)]
+    pub attachments: Vec<Attachment>, // stems and other extra files, oldest first
+    #[serde(default)]
+    pub credited_artists: Vec<Credit>, // in the order the owner listed them
+    #[serde(default)]
+    pub language: Option<String>, // ISO 639-1 code of the lyrics or vocals
+    #[serde(default)]
+    pub is_explicit: bool,
+    #[serde(default)]
+    pub slug: Option<String>, // None for tracks from before slugs, until renamed
+    #[serde(default)]
+    pub slug_aliases: Vec<String>, // earlier slugs, which still resolve
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserProfile {
     pub profile_name: String,
     pub pronouns: Option<String>,
-    pub location: Option<String>,
+    pub location: Option<String>, // display string, derived from `structured_location` unless given
+    #[serde(default)]
+    pub structured_location: Option<Location>,
     pub social_links: Option<Vec<String>>,
     pub profile_banner: Option<String>,
     pub profile_picture: Option<String>,
@@ -90,25 +238,103 @@ pub struct UserProfile {
     #[serde(rename = "social_links_dup")] // to prevent field conflict
     pub social_links_dup: Option<Vec<String>>,
     pub profile_views: u32,
-    pub friends_list: Option<Vec<Uuid>>,
-    pub blocked_users: Option<Vec<Uuid>>,
+    pub friends_list: Option<Vec<UserId>>,
+    pub blocked_users: Option<Vec<UserId>>,
     pub is_private: bool,
     pub uploads: Option<Vec<Track>>,
-    pub followers: Option<Vec<Uuid>>,
-    pub following: Option<Vec<Uuid>>,
+    pub followers: Option<Vec<UserId>>,
+    pub following: Option<Vec<UserId>>,
     pub last_login: Option<DateTime<Utc>>,
     pub last_activity: Option<DateTime<Utc>>,
-    pub is_active: bool,
+    #[serde(flatten, with = "account_flags")]
+    pub status: AccountStatus, // also stored as `is_active`, `is_banned` and `is_deleted`
+    #[serde(default)]
+    pub banned_until: Option<DateTime<Utc>>, // when a temporary ban lifts, None for good
     pub is_admin: bool,
-    pub is_banned: bool,
-    pub is_deleted: bool,
     pub reports: Option<Vec<Report>>,
+    #[serde(default)]
+    pub is_verified: bool, // only set by an admin approving a verification request
+    #[serde(default)]
+    pub pinned_track_ids: Vec<TrackId>, // public uploads featured first, oldest pin first
+    #[serde(default)]
+    pub track_order: ProfileTrackOrder, // how the other uploads are listed
+    #[serde(default)]
+    pub manual_track_order: Vec<TrackId>, // the owner's arrangement, for `Manual`
+    #[serde(default)]
+    pub share_now_playing: bool, // show what they're listening to on their profile
+    #[serde(default)]
+    pub push_kinds: Option<Vec<NotificationKind>>, // None pushes the high-priority kinds
+}
+
+/// Where an account stands
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountStatus {
+    #[default]
+    Active,
+    Suspended, // kept from acting for now, without a ban
+    Banned,
+    Deleted,
+}
+
+/// A profile's status as stored: the status, and the flags it replaced
+/// derived from it so queries and older clients reading them keep working.
+/// Records from before the status are read from the flags.
+mod account_flags {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use super::AccountStatus;
+
+    #[derive(Serialize, Deserialize)]
+    struct Flags {
+        #[serde(default)]
+        status: Option<AccountStatus>,
+        #[serde(default)]
+        is_active: bool,
+        #[serde(default)]
+        is_banned: bool,
+        #[serde(default)]
+        is_deleted: bool,
+    }
+
+    pub fn serialize<S: Serializer>(status: &AccountStatus, serializer: S) -> Result<S::Ok, S::Error> {
+        Flags {
+            status: Some(*status),
+            is_active: *status == AccountStatus::Active,
+            is_banned: *status == AccountStatus::Banned,
+            is_deleted: *status == AccountStatus::Deleted,
+        }
+        .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<AccountStatus, D::Error> {
+        let flags = Flags::deserialize(deserializer)?;
+        Ok(match flags {
+            Flags { status: Some(status), .. } => status,
+            Flags { is_deleted: true, .. } => AccountStatus::Deleted,
+            Flags { is_banned: true, .. } => AccountStatus::Banned,
+            Flags { is_active: false, .. } => AccountStatus::Suspended,
+            _ => AccountStatus::Active,
+        })
+    }
+}
+
+/// Most tracks pinned to one profile
+pub const MAX_PINNED_TRACKS: usize = 3;
+
+/// How a profile lists its uploads after the pinned ones
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum ProfileTrackOrder {
+    #[default]
+    Newest,
+    MostLiked,
+    Manual, // as arranged, then anything not arranged newest first
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Playlist {
-    pub id: Uuid,
-    pub user_id: Uuid,
+    pub id: PlaylistId,
+    pub user_id: UserId,
     pub name: String,
     pub description: Option<String>,
     pub tags: Option<Vec<String>>,
@@ -119,11 +345,17 @@ pub struct Playlist {
     pub tracks: Vec<Track>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    #[serde(default)]
+    pub revision: u64, // bumped by every change to `tracks`
+    #[serde(default)]
+    pub slug: Option<String>,
+    #[serde(default)]
+    pub slug_aliases: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
-    pub id: Uuid,
+    pub id: UserId,
     pub username: String,
     pub email: String,
     pub hashed_password: String,
@@ -134,4 +366,546 @@ pub struct User {
     pub profile: Option<UserProfile>,
     pub email_verified: bool,
     pub playlists: Option<Vec<Playlist>>,
+    #[serde(default)]
+    pub deleted_playlists: Vec<Playlist>, // soft-deleted, no longer listed or reachable
+    #[serde(default)]
+    pub legal_hold: bool, // blocks account erasure while set
+    #[serde(default)]
+    pub username_sort_key: String, // `username_sort_key(username)`, for ordering by name
+}
+
+/// Sign-up details as sent by a client
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewUser {
+    pub username: String,
+    pub email: String,
+    pub password: String, // plain text, only ever hashed
+    pub bio: Option<String>,
+}
+
+/// Everything `UserOperations::create_user` needs. Ids, timestamps and flags
+/// are filled in when the user is stored.
+#[derive(Debug, Clone)]
+pub struct CreateUserInput {
+    pub username: String,
+    pub email: String,
+    pub hashed_password: String,
+    pub created_via: CreatedVia,
+    pub bio: Option<String>,
+}
+
+/// The form usernames are stored in, so lookups match what sign-up saved
+pub fn normalize_username(username: &str) -> String {
+    username.trim().to_string()
+}
+
+/// A username folded for sorting: decomposed, stripped of accents and
+/// lowercased, so "Ärni" sorts with "arni" rather than after "zed"
+pub fn username_sort_key(username: &str) -> String {
+    username.nfkd().filter(|c| !is_combining_mark(*c)).flat_map(char::to_lowercase).collect()
+}
+
+impl NewUser {
+    /// Check the username and password, hash the password and note where
+    /// the sign-up came from
+    pub fn into_input(self, created_via: CreatedVia) -> Result<CreateUserInput, Error> {
+        let username = normalize_username(&self.username);
+        moderation::validate_username(&username).map_err(Error::Validation)?;
+        validate_password(&self.password)?;
+
+        Ok(CreateUserInput {
+            hashed_password: hash_password(&self.password)?,
+            username,
+            email: self.email.trim().to_lowercase(),
+            created_via,
+            bio: self.bio,
+        })
+    }
+}
+
+/// The parts of a user that are safe to show to anyone
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PublicUser {
+    pub id: UserId,
+    pub username: String,
+    pub profile_name: Option<String>,
+    pub profile_picture: Option<String>,
+    #[serde(default)]
+    pub is_verified: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A user as shown to admins: account details, never credentials
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct UserSummary {
+    pub id: UserId,
+    pub username: String,
+    pub email: String,
+    pub email_verified: bool,
+    pub created_via: CreatedVia,
+    pub is_admin: bool,
+    pub status: AccountStatus,
+    pub is_banned: bool,
+    pub is_deleted: bool,
+    pub legal_hold: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct GenreCount {
+    pub genre: Option<String>, // None groups tracks without a genre
+    pub count: u64,
+}
+
+/// Order of a listing of tracks from many users
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum TrackSort {
+    #[default]
+    Newest,
+    MostLiked,
+}
+
+impl TrackSort {
+    /// The ORDER BY clause for this sort, newest first among ties
+    pub fn order_by(&self) -> &'static str {
+        match self {
+            TrackSort::Newest => "created_at DESC, id DESC",
+            TrackSort::MostLiked => "likes DESC, created_at DESC, id DESC",
+        }
+    }
+}
+
+/// Order of a listing of users
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "kebab-case")]
+pub enum UserSort {
+    #[default]
+    Newest,
+    Username,
+}
+
+impl UserSort {
+    /// The ORDER BY clause for this sort
+    pub fn order_by(&self) -> &'static str {
+        match self {
+            UserSort::Newest => "created_at DESC",
+            UserSort::Username => "username_sort_key ASC, username ASC",
+        }
+    }
+}
+
+/// Changes to a track's metadata. Fields left out are kept as they are.
+#[derive(Debug, Clone, Default, Deserialize, ToSchema)]
+pub struct TrackPatch {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub cover_image_url: Option<String>,
+    pub license: Option<License>,
+    pub downloadable: Option<bool>,
+    #[serde(default)]
+    pub download_override: bool, // allow downloads of an all-rights-reserved track
+    pub language: Option<String>, // ISO 639-1; an empty string clears it
+    pub is_explicit: Option<bool>,
+}
+
+impl TrackPatch {
+    /// Check the fields that are present
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(title) = &self.title {
+            let title = title.trim();
+            if title.is_empty() || title.chars().count() > import::MAX_TITLE_LEN {
+                return Err(Error::Validation(format!("title must be 1 to {} characters", import::MAX_TITLE_LEN)));
+            }
+        }
+        
+        if self.cover_image_url.as_deref().is_some_and(|url| !import::is_http_url(url.trim())) {
+            return Err(Error::Validation("cover_image_url must be an http(s) URL".to_string()));
+        }
+        
+        if let Some(code) = self.language.as_deref().filter(|code| !code.trim().is_empty()) {
+            language::normalize(code).map_err(Error::Validation)?;
+        }
+        
+        Ok(())
+    }
+}
+
+/// A copy of a track in a playlist whose uploader no longer exists
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct OrphanTrack {
+    pub track_id: TrackId,
+    pub title: String,
+    pub missing_user_id: UserId,
+    pub playlist_id: PlaylistId,
+    pub playlist_owner_id: UserId,
+}
+
+/// A track's public metadata
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TrackView {
+    pub id: TrackId,
+    pub slug: Option<String>, // resolves with `GET /t/{slug}`
+    pub user_id: UserId,
+    pub title: String,
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audio_url: Option<String>, // the original file, only for the owner and on downloadable tracks
+    pub stream_url: String,
+    pub cover_image_url: Option<String>,
+    pub genre: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub is_public: bool,
+    pub downloadable: bool,
+    pub likes: u32,
+    pub dislikes: u32,
+    pub download_count: u64,
+    pub comment_count: u64,
+    pub has_lyrics: bool,
+    pub license: License,
+    pub license_url: Option<String>,
+    pub license_history: Vec<LicenseChange>,
+    pub attachments: Vec<AttachmentView>,
+    pub credited_artists: Vec<CreditView>, // linked users are filled in by `Hydrator::track_views`
+    pub technical_metadata: Option<TrackTechnicalMetadata>,
+    pub language: Option<String>,
+    pub is_explicit: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A comment as shown publicly, without moderation data
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CommentView {
+    pub id: CommentId,
+    pub track_id: TrackId,
+    pub user_id: UserId,
+    pub author: Option<PublicUser>, // None once the author's account is gone
+    pub content: String,
+    pub is_pinned: bool,
+    pub like_count: usize,
+    pub dislike_count: usize,
+    pub reply_count: usize,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A playlist with the tracks in it the viewer may see
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct PlaylistView {
+    pub id: PlaylistId,
+    pub slug: Option<String>,
+    pub owner: PublicUser,
+    pub name: String,
+    pub description: Option<String>,
+    pub tags: Option<Vec<String>>,
+    pub cover_image_url: Option<String>,
+    pub is_public: bool,
+    pub is_collaborative: bool,
+    pub tracks: Vec<TrackView>,
+    /// Pass back when editing the tracks, so edits made meanwhile aren't
+    /// overwritten
+    pub revision: u64,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Someone the viewer might want to follow
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct FollowSuggestion {
+    pub user: PublicUser,
+    pub mutual_count: u64, // how many people the viewer follows already follow them
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct SignupCount {
+    pub created_via: CreatedVia,
+    pub count: u64,
+}
+
+/// A profile as shown to a viewer, with upload stats computed for them
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProfileView {
+    pub user: PublicUser,
+    pub pronouns: Option<String>,
+    pub location: Option<String>,
+    pub structured_location: Option<Location>,
+    pub profile_banner: Option<String>,
+    pub profile_bio: Option<String>,
+    pub social_links: Option<Vec<String>>,
+    pub pinned_tracks: Vec<TrackView>, // latest pin first, public ones only
+    pub track_count: u64,
+    pub genres: Vec<GenreCount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_following: Option<bool>, // the viewer follows this user; left out for anonymous viewers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_followed_by: Option<bool>, // this user follows the viewer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_blocked: Option<bool>, // the viewer blocked this user
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub now_playing: Option<NowPlaying>, // only for users who share it, and tracks the viewer may see
+}
+
+impl From<User> for PublicUser {
+    fn from(user: User) -> Self {
+        let profile = user.profile.as_ref();
+        Self {
+            id: user.id,
+            profile_name: profile.map(|p| p.profile_name.clone()),
+            profile_picture: profile.and_then(|p| p.profile_picture.clone()),
+            is_verified: profile.is_some_and(|p| p.is_verified),
+            username: user.username,
+            created_at: user.created_at,
+        }
+    }
+}
+
+impl UserProfile {
+    /// A fresh public profile with nothing filled in but the display name
+    pub fn new(profile_name: String) -> Self {
+        UserProfile {
+            profile_name,
+            pronouns: None,
+            location: None,
+            structured_location: None,
+            social_links: None,
+            profile_banner: None,
+            profile_picture: None,
+            profile_bio: None,
+            social_links_dup: None,
+            profile_views: 0,
+            friends_list: None,
+            blocked_users: None,
+            is_private: false,
+            uploads: None,
+            followers: None,
+            following: None,
+            last_login: None,
+            last_activity: None,
+            status: AccountStatus::Active,
+            banned_until: None,
+            is_admin: false,
+            reports: None,
+            is_verified: false,
+            pinned_track_ids: Vec::new(),
+            track_order: ProfileTrackOrder::default(),
+            manual_track_order: Vec::new(),
+            share_now_playing: false,
+            push_kinds: None,
+        }
+    }
+
+    /// Where the account stands now: active again once a temporary ban lifts
+    pub fn status(&self) -> AccountStatus {
+        match self.banned_until {
+            Some(until) if self.status == AccountStatus::Banned && clock::now() >= until => AccountStatus::Active,
+            _ => self.status,
+        }
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status() == AccountStatus::Active
+    }
+
+    pub fn is_banned(&self) -> bool {
+        self.status() == AccountStatus::Banned
+    }
+
+    pub fn is_deleted(&self) -> bool {
+        self.status == AccountStatus::Deleted
+    }
+
+    /// The kinds of notification this user gets pushed
+    pub fn push_kinds(&self) -> Vec<NotificationKind> {
+        match &self.push_kinds {
+            Some(kinds) => kinds.clone(),
+            None => NotificationKind::ALL.into_iter().filter(NotificationKind::is_high_priority).collect(),
+        }
+    }
+
+    /// Whether notifications of `kind` should reach this user as pushes
+    pub fn wants_push(&self, kind: NotificationKind) -> bool {
+        match &self.push_kinds {
+            Some(kinds) => kinds.contains(&kind),
+            None => kind.is_high_priority(),
+        }
+    }
+}
+
+impl User {
+    /// Whether a viewer may see this user's profile. Deleted profiles are
+    /// hidden from everyone, private ones from everyone but their owner.
+    pub fn profile_visible_to(&self, viewer: Option<UserId>) -> bool {
+        let is_owner = viewer == Some(self.id);
+        self.profile.as_ref().is_some_and(|p| !p.is_deleted() && (!p.is_private || is_owner))
+    }
+
+    /// URLs of the files this user put up: profile images, their tracks'
+    /// audio, covers and attachments, and their playlists' covers. Tracks
+    /// saved into their playlists belong to the uploader and aren't listed.
+    pub fn media_urls(&self) -> impl Iterator<Item = &str> {
+        let profile = self.profile.as_ref();
+        let images = profile
+            .into_iter()
+            .flat_map(|profile| [profile.profile_picture.as_deref(), profile.profile_banner.as_deref()])
+            .flatten();
+        let uploads = profile
+            .and_then(|profile| profile.uploads.as_ref())
+            .into_iter()
+            .flatten()
+            .flat_map(|track| {
+                [Some(track.audio_url.as_str()), track.cover_image_url.as_deref()]
+                    .into_iter()
+                    .flatten()
+                    .chain(track.audio_versions.iter().map(|version| version.audio_url.as_str()))
+                    .chain(track.attachments.iter().map(|attachment| attachment.url.as_str()))
+            });
+        let covers = self.playlists
+            .iter()
+            .flatten()
+            .chain(&self.deleted_playlists)
+            .filter_map(|playlist| playlist.cover_image_url.as_deref());
+
+        images.chain(uploads).chain(covers)
+    }
+}
+
+impl Track {
+    /// Whether a viewer may see this track. Deleted tracks are hidden from
+    /// everyone, private ones from everyone but the uploader.
+    pub fn is_visible_to(&self, viewer: Option<UserId>) -> bool {
+        !self.is_deleted && (self.is_public || viewer == Some(self.user_id))
+    }
+
+    /// Whether a viewer may have the original file rather than only play it
+    pub fn is_downloadable_by(&self, viewer: Option<UserId>) -> bool {
+        self.downloadable || viewer == Some(self.user_id)
+    }
+
+    /// Where anyone who may see the track can play it
+    pub fn stream_url(&self) -> String {
+        format!("{}/tracks/{}/stream", config::public_url(), self.id)
+    }
+
+    /// Recount the comments and replies that aren't deleted, which is what
+    /// `comment_count` should hold
+    pub fn live_comment_count(&self) -> u64 {
+        fn count(comments: Option<&Vec<Comment>>) -> u64 {
+            comments.into_iter().flatten()
+                .map(|comment| u64::from(!comment.is_deleted) + count(comment.replies.as_ref()))
+                .sum()
+        }
+        count(self.comments.as_ref())
+    }
+}
+
+impl Playlist {
+    /// Whether a viewer may see this playlist, by the same rules as tracks
+    pub fn is_visible_to(&self, viewer: Option<UserId>) -> bool {
+        !self.is_deleted && (self.is_public || viewer == Some(self.user_id))
+    }
+
+    /// Whether a user may change the tracks in this playlist: its owner, or
+    /// anyone who can see it once it's collaborative
+    pub fn is_editable_by(&self, user_id: UserId) -> bool {
+        self.is_visible_to(Some(user_id)) && (self.is_collaborative || user_id == self.user_id)
+    }
+
+    /// Where the track at `at` shows among the tracks `viewer` may see
+    pub fn visible_index(&self, viewer: Option<UserId>, at: usize) -> usize {
+        self.tracks[..at.min(self.tracks.len())].iter().filter(|track| track.is_visible_to(viewer)).count()
+    }
+
+    /// Where in `tracks` the `index`th track `viewer` may see is, or the
+    /// end when they see fewer
+    pub fn full_index(&self, viewer: Option<UserId>, index: usize) -> usize {
+        self.tracks.iter()
+            .enumerate()
+            .filter(|(_, track)| track.is_visible_to(viewer))
+            .nth(index)
+            .map_or(self.tracks.len(), |(at, _)| at)
+    }
+}
+
+impl TrackView {
+    /// The view `viewer` gets, with the original file if they may download it
+    pub fn for_viewer(track: Track, viewer: Option<UserId>) -> Self {
+        let audio_url = track.is_downloadable_by(viewer).then(|| track.audio_url.clone());
+        Self { audio_url, ..Self::from(track) }
+    }
+}
+
+/// The view anyone gets
+impl From<Track> for TrackView {
+    fn from(track: Track) -> Self {
+        Self {
+            stream_url: track.stream_url(),
+            audio_url: track.downloadable.then_some(track.audio_url),
+            id: track.id,
+            slug: track.slug,
+            user_id: track.user_id,
+            title: track.title,
+            description: track.description,
+            cover_image_url: track.cover_image_url,
+            genre: track.genre,
+            tags: track.tags,
+            is_public: track.is_public,
+            downloadable: track.downloadable,
+            likes: track.likes,
+            dislikes: track.dislikes,
+            download_count: track.download_count,
+            comment_count: track.comment_count,
+            has_lyrics: track.has_lyrics,
+            license: track.license,
+            license_url: track.license.url().map(str::to_string),
+            license_history: track.license_history,
+            attachments: track.attachments.into_iter().map(AttachmentView::from).collect(),
+            credited_artists: track.credited_artists.iter().map(|credit| credit.view(None)).collect(),
+            technical_metadata: track.technical_metadata,
+            language: track.language,
+            is_explicit: track.is_explicit,
+            created_at: track.created_at,
+            updated_at: track.updated_at,
+        }
+    }
+}
+
+impl CommentView {
+    pub fn new(comment: Comment, author: Option<PublicUser>) -> Self {
+        let count = |ids: &Option<Vec<UserId>>| ids.as_ref().map_or(0, Vec::len);
+        Self {
+            author,
+            like_count: count(&comment.likes),
+            dislike_count: count(&comment.dislikes),
+            reply_count: comment.replies.iter().flatten().filter(|reply| !reply.is_deleted).count(),
+            id: comment.id,
+            track_id: comment.referred_track_id,
+            user_id: comment.user_id,
+            content: comment.content,
+            is_pinned: comment.is_pinned,
+            created_at: comment.created_at,
+            updated_at: comment.updated_at,
+        }
+    }
+}
+
+impl From<User> for UserSummary {
+    fn from(user: User) -> Self {
+        let profile = user.profile.as_ref();
+        Self {
+            is_admin: profile.is_some_and(|p| p.is_admin),
+            status: profile.map(|p| p.status()).unwrap_or_default(),
+            is_banned: profile.is_some_and(|p| p.is_banned()),
+            is_deleted: profile.is_some_and(|p| p.is_deleted()),
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            email_verified: user.email_verified,
+            created_via: user.created_via,
+            legal_hold: user.legal_hold,
+            created_at: user.created_at,
+        }
+    }
 }
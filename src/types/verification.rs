@@ -0,0 +1,116 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Duration, Utc};
+use utoipa::ToSchema;
+use crate::clock;
+use crate::db::error::Error;
+use crate::import;
+use crate::types::id::UserId;
+
+/// Most links one request may carry
+pub const MAX_LINKS: usize = 10;
+
+/// Longest free-text evidence accepted, in characters
+pub const MAX_EVIDENCE_LEN: usize = 2000;
+
+/// Longest reviewer note accepted, in characters
+pub const MAX_NOTE_LEN: usize = 1000;
+
+/// How long a rejected user waits before applying again
+pub fn reapply_cooldown() -> Duration {
+    Duration::days(30)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationStatus {
+    Pending,
+    Approved,
+    Rejected,
+    Revoked, // approved once, badge later taken away
+}
+
+impl VerificationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            VerificationStatus::Pending => "pending",
+            VerificationStatus::Approved => "approved",
+            VerificationStatus::Rejected => "rejected",
+            VerificationStatus::Revoked => "revoked",
+        }
+    }
+}
+
+/// A user asking for the verified badge, and the admin's answer
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct VerificationRequest {
+    pub id: Uuid,
+    pub user_id: UserId,
+    pub links: Vec<String>, // official sites, label pages, press
+    pub evidence: Option<String>,
+    pub status: VerificationStatus,
+    pub note: Option<String>, // reviewer's reason, shown to the user
+    pub reviewed_by: Option<UserId>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An application as sent by the user
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct NewVerificationRequest {
+    pub links: Vec<String>,
+    pub evidence: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum VerificationDecision {
+    Approve,
+    Reject,
+}
+
+/// An admin's decision on a pending request
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+pub struct ReviewVerification {
+    pub decision: VerificationDecision,
+    pub note: Option<String>,
+}
+
+/// Trim a reviewer note, dropping it when empty
+pub fn clean_note(note: Option<String>) -> Result<Option<String>, Error> {
+    let note = note.map(|n| n.trim().to_string()).filter(|n| !n.is_empty());
+    if note.as_ref().is_some_and(|n| n.chars().count() > MAX_NOTE_LEN) {
+        return Err(Error::Validation(format!("note must be at most {} characters", MAX_NOTE_LEN)));
+    }
+    Ok(note)
+}
+
+impl NewVerificationRequest {
+    /// Validate into a pending request by `user_id`
+    pub fn into_request(self, user_id: UserId) -> Result<VerificationRequest, Error> {
+        let links: Vec<String> = self.links.iter().map(|l| l.trim().to_string()).filter(|l| !l.is_empty()).collect();
+        if links.is_empty() || links.len() > MAX_LINKS {
+            return Err(Error::Validation(format!("give 1 to {} links", MAX_LINKS)));
+        }
+        if let Some(link) = links.iter().find(|l| !import::is_http_url(l)) {
+            return Err(Error::Validation(format!("{} is not an http(s) URL", link)));
+        }
+
+        let evidence = self.evidence.map(|e| e.trim().to_string()).filter(|e| !e.is_empty());
+        if evidence.as_ref().is_some_and(|e| e.chars().count() > MAX_EVIDENCE_LEN) {
+            return Err(Error::Validation(format!("evidence must be at most {} characters", MAX_EVIDENCE_LEN)));
+        }
+
+        Ok(VerificationRequest {
+            id: Uuid::new_v4(),
+            user_id,
+            links,
+            evidence,
+            status: VerificationStatus::Pending,
+            note: None,
+            reviewed_by: None,
+            reviewed_at: None,
+            created_at: clock::now(),
+        })
+    }
+}
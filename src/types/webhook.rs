@@ -0,0 +1,54 @@
+use serde::{Serialize, Deserialize};
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+use utoipa::ToSchema;
+use crate::types::id::UserId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum WebhookEvent {
+    #[serde(rename = "track.published")]
+    TrackPublished,
+    #[serde(rename = "comment.created")]
+    CommentCreated,
+    #[serde(rename = "user.followed")]
+    UserFollowed,
+    #[serde(rename = "report.resolved")]
+    ReportResolved,
+}
+
+impl WebhookEvent {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::TrackPublished => "track.published",
+            WebhookEvent::CommentCreated => "comment.created",
+            WebhookEvent::UserFollowed => "user.followed",
+            WebhookEvent::ReportResolved => "report.resolved",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub owner_id: Option<UserId>, // None for admin-level global webhooks
+    pub url: String,
+    pub secret: String, // HMAC key for the signature header
+    pub events: Vec<WebhookEvent>,
+    pub active: bool,
+    pub consecutive_failures: u32, // reset on any successful delivery
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: WebhookEvent,
+    pub payload_id: Uuid, // shared by every attempt at the same payload
+    pub attempt: u32,
+    pub status_code: Option<u16>,
+    pub error: Option<String>,
+    pub success: bool,
+    pub created_at: DateTime<Utc>,
+}
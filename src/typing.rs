@@ -0,0 +1,171 @@
+//! Who's composing a comment on a track right now.
+//!
+//! A socket subscribed to a track's comments sends `typing_start` while its
+//! user writes a comment and `typing_stop` when they send or abandon it.
+//! The track's other subscribers get the same, minus anyone the typist
+//! blocked or who blocked them, and nothing from banned or deleted
+//! accounts. Someone who stops without saying so counts as typing for
+//! `TYPING_TTL` after their last `typing_start`, then a `typing_stop` is
+//! sent for them. Nothing here is stored, and like comment subscriptions
+//! it's per process.
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tracing::warn;
+use crate::db::error::Error;
+use crate::db::Db;
+use crate::live_comments;
+use crate::types::id::{TrackId, UserId};
+use crate::types::realtime::ServerMessage;
+
+/// How long someone counts as typing after their last `typing_start`
+pub const TYPING_TTL: Duration = Duration::from_secs(10);
+
+/// Most typing messages one socket may send per `RATE_WINDOW`; the rest are
+/// ignored
+const RATE_LIMIT: u32 = 20;
+
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// How often expired typists are dropped
+const SWEEP_INTERVAL: Duration = Duration::from_secs(2);
+
+/// When each typist's `typing_start` runs out, by track
+static TYPING: LazyLock<Mutex<HashMap<TrackId, HashMap<UserId, Instant>>>> = LazyLock::new(Default::default);
+
+/// Counts one socket's typing messages, so a client can't flood a thread
+pub struct RateLimit {
+    window_start: Instant,
+    sent: u32,
+}
+
+impl RateLimit {
+    pub fn new() -> Self {
+        RateLimit { window_start: Instant::now(), sent: 0 }
+    }
+
+    /// Whether another message may go through now
+    pub fn allow(&mut self) -> bool {
+        if self.window_start.elapsed() > RATE_WINDOW {
+            self.window_start = Instant::now();
+            self.sent = 0;
+        }
+        self.sent += 1;
+        self.sent <= RATE_LIMIT
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Note that `user_id` is writing a comment on `track_id`, telling the
+/// track's subscribers if they weren't already
+pub async fn start(db: &Db, track_id: TrackId, user_id: UserId) -> Result<(), Error> {
+    let started = {
+        let mut typing = TYPING.lock().unwrap_or_else(|e| e.into_inner());
+        let expires = Instant::now() + TYPING_TTL;
+        typing.entry(track_id).or_default().insert(user_id, expires).is_none()
+    };
+    if started {
+        live_comments::relay(db, track_id, user_id, ServerMessage::TypingStart { track_id, user_id }).await?;
+    }
+    Ok(())
+}
+
+/// Note that `user_id` stopped writing on `track_id`, telling the track's
+/// subscribers if they were
+pub async fn stop(db: &Db, track_id: TrackId, user_id: UserId) -> Result<(), Error> {
+    let stopped = {
+        let mut typing = TYPING.lock().unwrap_or_else(|e| e.into_inner());
+        let stopped = typing.get_mut(&track_id).is_some_and(|typists| typists.remove(&user_id).is_some());
+        if typing.get(&track_id).is_some_and(HashMap::is_empty) {
+            typing.remove(&track_id);
+        }
+        stopped
+    };
+    if stopped {
+        live_comments::relay(db, track_id, user_id, ServerMessage::TypingStop { track_id, user_id }).await?;
+    }
+    Ok(())
+}
+
+/// Drop typists whose `typing_start` ran out and say they stopped
+async fn sweep(db: &Db) {
+    let expired: Vec<(TrackId, UserId)> = {
+        let mut typing = TYPING.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        typing.retain(|track_id, typists| {
+            typists.retain(|user_id, expires| {
+                let live = *expires > now;
+                if !live {
+                    expired.push((*track_id, *user_id));
+                }
+                live
+            });
+            !typists.is_empty()
+        });
+        expired
+    };
+
+    for (track_id, user_id) in expired {
+        if let Err(e) = live_comments::relay(db, track_id, user_id, ServerMessage::TypingStop { track_id, user_id }).await {
+            warn!("Failed to announce typing stopped on track {}: {}", track_id, e);
+        }
+    }
+}
+
+/// Sweep in the background for as long as the server runs
+pub fn spawn_job(db: Db) {
+    actix_web::rt::spawn(async move {
+        let mut ticker = actix_web::rt::time::interval(SWEEP_INTERVAL);
+        loop {
+            ticker.tick().await;
+            sweep(&db).await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+    use crate::db::Db;
+    use crate::types::id::{TrackId, UserId};
+    use super::{sweep, RateLimit, RATE_LIMIT, RATE_WINDOW, TYPING};
+
+    #[test]
+    fn a_socket_is_held_to_the_rate_limit_until_the_window_passes() {
+        let mut limit = RateLimit::new();
+        assert!((0..RATE_LIMIT).all(|_| limit.allow()));
+        assert!(!limit.allow());
+        assert!(!limit.allow(), "ignored messages count too");
+
+        limit.window_start = Instant::now() - RATE_WINDOW - Duration::from_millis(1);
+        assert!(limit.allow());
+    }
+
+    #[actix_web::test]
+    async fn typists_are_dropped_once_their_start_runs_out() {
+        let db = Db::memory().await.expect("in-memory database connects");
+        let track_id = TrackId::new();
+        let (stale, fresh) = (UserId::new(), UserId::new());
+        {
+            let mut typing = TYPING.lock().unwrap();
+            let typists = typing.entry(track_id).or_default();
+            typists.insert(stale, Instant::now() - Duration::from_millis(1));
+            typists.insert(fresh, Instant::now() + Duration::from_secs(5));
+        }
+
+        sweep(&db).await;
+        let typists: Vec<UserId> = TYPING.lock().unwrap()[&track_id].keys().copied().collect();
+        assert_eq!(typists, [fresh]);
+
+        TYPING.lock().unwrap().get_mut(&track_id).expect("track has a typist").insert(fresh, Instant::now());
+        sweep(&db).await;
+        assert!(!TYPING.lock().unwrap().contains_key(&track_id), "tracks nobody types on are dropped");
+    }
+}
@@ -0,0 +1,78 @@
+//! Caps how many uploads one user can have in flight at once, so a single
+//! account can't tie up storage and the image workers for everyone else.
+//!
+//! Each user gets a counting semaphore; `acquire` takes a permit without
+//! waiting and fails with 429 when they're all taken. The permit is given
+//! back when it's dropped, however the upload ends. The limit is set with
+//! `MAX_CONCURRENT_UPLOADS` (default 2) and is per process.
+
+use std::collections::HashMap;
+use std::env;
+use std::sync::{LazyLock, Mutex};
+use crate::db::error::Error;
+use crate::types::id::UserId;
+
+/// Uploads in flight per user. Users with none are removed.
+static IN_FLIGHT: LazyLock<Mutex<HashMap<UserId, usize>>> = LazyLock::new(Default::default);
+
+/// Most uploads one user may have in flight, set with `MAX_CONCURRENT_UPLOADS`
+pub fn max_concurrent() -> usize {
+    env::var("MAX_CONCURRENT_UPLOADS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .filter(|max| *max > 0)
+        .unwrap_or(2)
+}
+
+/// A user's claim on one upload slot, released on drop
+#[derive(Debug)]
+pub struct UploadPermit {
+    user_id: UserId,
+}
+
+/// Take one of `user_id`'s upload slots, or fail with 429 if they're all in use
+pub fn acquire(user_id: UserId) -> Result<UploadPermit, Error> {
+    let max = max_concurrent();
+    let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+    let count = in_flight.entry(user_id).or_insert(0);
+
+    if *count >= max {
+        return Err(Error::TooManyRequests(format!("at most {} uploads can run at once", max)));
+    }
+    *count += 1;
+
+    Ok(UploadPermit { user_id })
+}
+
+impl Drop for UploadPermit {
+    fn drop(&mut self) {
+        let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(count) = in_flight.get_mut(&self.user_id) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                in_flight.remove(&self.user_id);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{acquire, max_concurrent};
+    use crate::db::error::Error;
+    use crate::types::id::UserId;
+
+    #[test]
+    fn uploads_over_the_limit_are_throttled_for_that_user_only() {
+        let (alice, bob) = (UserId::new(), UserId::new());
+        let mut permits: Vec<_> = (0..max_concurrent())
+            .map(|_| acquire(alice).expect("under the limit"))
+            .collect();
+
+        assert!(matches!(acquire(alice), Err(Error::TooManyRequests(_))));
+        assert!(acquire(bob).is_ok(), "other users have slots of their own");
+
+        permits.pop();
+        assert!(acquire(alice).is_ok(), "a finished upload frees its slot");
+    }
+}
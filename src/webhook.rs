@@ -0,0 +1,156 @@
+//! Outgoing webhook delivery.
+//!
+//! `dispatch` hands an event to a background task and returns immediately.
+//! Each subscriber gets a signed JSON POST, retried with exponential backoff
+//! until it answers 2xx or `MAX_ATTEMPTS` is reached. Every attempt is logged
+//! to `webhook_deliveries`, and a webhook that keeps failing is deactivated.
+
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tracing::{error, warn};
+use uuid::Uuid;
+use crate::clock;
+use crate::db::error::Error;
+use crate::db::webhook::WebhookOperations;
+use crate::db::Db;
+use crate::types::id::UserId;
+use crate::types::webhook::{Webhook, WebhookDelivery, WebhookEvent};
+
+/// Header carrying `sha256=<hex hmac of the body>`
+pub const SIGNATURE_HEADER: &str = "X-Libretune-Signature";
+pub const EVENT_HEADER: &str = "X-Libretune-Event";
+pub const DELIVERY_HEADER: &str = "X-Libretune-Delivery";
+
+/// Attempts per payload before giving up on it
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// Payloads in a row that may fail before a webhook is deactivated
+pub const DISABLE_AFTER_FAILURES: u32 = 10;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Serialize)]
+pub struct Payload<T: Serialize> {
+    pub id: Uuid,
+    pub event: WebhookEvent,
+    pub created_at: DateTime<Utc>,
+    pub data: T,
+}
+
+/// Sign a request body with a webhook's secret
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Deliver `event` to the subscribers of `owner_id` and to global webhooks,
+/// without waiting for the deliveries
+pub fn dispatch<T: Serialize>(db: &Db, event: WebhookEvent, owner_id: Option<UserId>, data: T) {
+    let payload = Payload {
+        id: Uuid::new_v4(),
+        event,
+        created_at: clock::now(),
+        data,
+    };
+    
+    let body = match serde_json::to_vec(&payload) {
+        Ok(body) => body,
+        Err(e) => {
+            error!("Failed to serialize {} webhook payload: {}", event.as_str(), e);
+            return;
+        }
+    };
+    let payload_id = payload.id;
+    let db = Db::clone(db);
+    
+    actix_web::rt::spawn(async move {
+        let webhooks = match WebhookOperations::new(&db).get_subscribers(owner_id, event).await {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                error!("Failed to load webhooks for {}: {}", event.as_str(), e);
+                return;
+            }
+        };
+        
+        for webhook in webhooks {
+            let body = body.clone();
+            let db = Db::clone(&db);
+            actix_web::rt::spawn(async move {
+                let webhook_id = webhook.id;
+                if let Err(e) = deliver(&db, webhook, event, payload_id, body).await {
+                    error!("Failed to record delivery for webhook {}: {}", webhook_id, e);
+                }
+            });
+        }
+    });
+}
+
+/// Try a payload until it succeeds or runs out of attempts
+async fn deliver(db: &Db, mut webhook: Webhook, event: WebhookEvent, payload_id: Uuid, body: Vec<u8>) -> Result<(), Error> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .map_err(|e| Error::Db(e.to_string()))?;
+    let signature = sign(&webhook.secret, &body);
+    let mut backoff = INITIAL_BACKOFF;
+    
+    for attempt in 1..=MAX_ATTEMPTS {
+        let result = client
+            .post(&webhook.url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(SIGNATURE_HEADER, &signature)
+            .header(EVENT_HEADER, event.as_str())
+            .header(DELIVERY_HEADER, payload_id.to_string())
+            .body(body.clone())
+            .send()
+            .await;
+        
+        let (status_code, error) = match result {
+            Ok(response) => (Some(response.status().as_u16()), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        let success = status_code.is_some_and(|code| (200..300).contains(&code));
+        
+        WebhookOperations::new(db).record_delivery(&WebhookDelivery {
+            id: Uuid::new_v4(),
+            webhook_id: webhook.id,
+            event,
+            payload_id,
+            attempt,
+            status_code,
+            error,
+            success,
+            created_at: clock::now(),
+        }).await?;
+        
+        if success {
+            if webhook.consecutive_failures > 0 {
+                webhook.consecutive_failures = 0;
+                WebhookOperations::new(db).save_webhook(webhook).await?;
+            }
+            return Ok(());
+        }
+        
+        if attempt < MAX_ATTEMPTS {
+            actix_web::rt::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+    
+    // Re-read so concurrent deliveries don't overwrite each other's counts
+    let mut webhook = WebhookOperations::new(db).get_webhook(webhook.id).await?;
+    webhook.consecutive_failures += 1;
+    if webhook.consecutive_failures >= DISABLE_AFTER_FAILURES {
+        warn!("Disabling webhook {} after {} failed payloads", webhook.id, webhook.consecutive_failures);
+        webhook.active = false;
+    }
+    WebhookOperations::new(db).save_webhook(webhook).await?;
+    
+    Ok(())
+}
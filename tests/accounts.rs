@@ -0,0 +1,145 @@
+//! Signing up and where signups come from, an account's status, the flags
+//! stored alongside it, and the order accounts are listed in
+
+mod common;
+
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use chrono::{Duration, TimeZone, Utc};
+use libretune::auth::{verify_password, MAX_PASSWORD_LEN, MIN_PASSWORD_LEN};
+use libretune::clock::MockClock;
+use libretune::db::error::Error;
+use libretune::db::UserOperations;
+use libretune::fixtures::UserFixture;
+use libretune::types::user::{AccountStatus, CreatedVia, NewUser, UserProfile, UserSort};
+use serde_json::{json, Value};
+use common::create_test_user;
+
+#[actix_web::test]
+async fn a_new_user_from_json_is_stored_with_a_hashed_password() {
+    let db = common::db().await;
+    let new_user: NewUser = serde_json::from_value(json!({
+        "username": "  erin ",
+        "email": "Erin@Example.com",
+        "password": "a long enough password",
+        "bio": "Hi",
+    }))
+    .expect("sign-up details deserialize");
+
+    let input = new_user.into_input(CreatedVia::Mobile).expect("details are acceptable");
+    let user = UserOperations::new(&db).create_user(input).await.expect("user is created");
+
+    assert_eq!((user.username.as_str(), user.email.as_str()), ("erin", "erin@example.com"));
+    assert!(matches!(user.created_via, CreatedVia::Mobile));
+    assert_eq!(user.bio.as_deref(), Some("Hi"));
+    assert!(verify_password("a long enough password", &user.hashed_password));
+}
+
+#[actix_web::test]
+async fn passwords_outside_the_allowed_length_are_refused() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+
+    for password in ["x".repeat(MIN_PASSWORD_LEN - 1), "x".repeat(MAX_PASSWORD_LEN + 1)] {
+        let new_user = NewUser { username: "frank".to_string(), email: "frank@example.com".to_string(), password: password.clone(), bio: None };
+        assert!(matches!(new_user.into_input(CreatedVia::Cli), Err(Error::Validation(_))));
+
+        let req = test::TestRequest::post()
+            .uri("/auth/register")
+            .set_json(json!({ "username": "frank", "email": "frank@example.com", "password": password }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    }
+    assert!(matches!(UserOperations::new(&db).get_user_by_username("frank".to_string()).await, Err(Error::UserNotFound)));
+
+    // The shortest allowed is fine
+    let req = test::TestRequest::post()
+        .uri("/auth/register")
+        .set_json(json!({ "username": "frank", "email": "frank@example.com", "password": "x".repeat(MIN_PASSWORD_LEN) }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::CREATED);
+}
+
+#[actix_web::test]
+async fn banning_sets_the_status_and_the_derived_flags() {
+    let db = common::db().await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let user = UserOperations::new(&db).ban_user(alice.user.id).await.expect("user is banned");
+    let profile = user.profile.expect("user has a profile");
+    assert_eq!(profile.status, AccountStatus::Banned);
+    assert!(profile.is_banned());
+    assert!(!profile.is_active());
+
+    // The flags are still written for queries and clients reading them
+    let stored: Value = serde_json::to_value(&profile).expect("profile serializes");
+    assert_eq!(stored["status"], "banned");
+    assert_eq!(stored["is_banned"], true);
+    assert_eq!(stored["is_active"], false);
+    assert_eq!(stored["is_deleted"], false);
+
+    let user = UserOperations::new(&db).unban_user(alice.user.id).await.expect("user is unbanned");
+    assert_eq!(user.profile.map(|p| p.status), Some(AccountStatus::Active));
+}
+
+#[actix_web::test]
+async fn profiles_from_before_the_status_are_read_from_their_flags() {
+    let db = common::db().await;
+    let alice = create_test_user(&db, "alice").await;
+    let mut stored = serde_json::to_value(alice.user.profile.expect("user has a profile")).expect("profile serializes");
+    let fields = stored.as_object_mut().expect("profile is an object");
+    fields.remove("status");
+    fields.insert("is_deleted".to_string(), Value::Bool(true));
+
+    let profile: UserProfile = serde_json::from_value(stored).expect("old profile reads");
+    assert_eq!(profile.status, AccountStatus::Deleted);
+}
+
+#[actix_web::test]
+async fn usernames_sort_by_letter_not_by_byte() {
+    let db = common::db().await;
+    for (i, username) in ["zed", "Ärni", "bea", "arnold", "Émile"].into_iter().enumerate() {
+        UserFixture::new().username(username).email(format!("user{}@example.com", i)).create(&db).await.expect("user is created");
+    }
+
+    let users = UserOperations::new(&db).get_users(None, None, UserSort::Username).await.expect("users are listed");
+    let collated: Vec<&str> = users.iter().map(|user| user.username.as_str()).collect();
+    assert_eq!(collated, ["Ärni", "arnold", "bea", "Émile", "zed"]);
+
+    // Plain byte order puts the accented names after every unaccented one
+    let mut bytewise = collated.clone();
+    bytewise.sort();
+    assert_eq!(bytewise, ["arnold", "bea", "zed", "Ärni", "Émile"]);
+}
+
+#[actix_web::test]
+async fn signups_are_counted_by_source_within_the_range() {
+    let start = Utc.with_ymd_and_hms(2024, 3, 1, 0, 0, 0).unwrap();
+    let clock = Arc::new(MockClock::new(start));
+    let db = common::db().await.with_clock(clock.clone());
+    let signups = [
+        (0, CreatedVia::Web), // before the range
+        (1, CreatedVia::Web),
+        (1, CreatedVia::Mobile),
+        (2, CreatedVia::Mobile),
+        (3, CreatedVia::Mobile),
+        (3, CreatedVia::Google),
+        (4, CreatedVia::Spotify), // after it
+    ];
+    for (day, created_via) in signups {
+        clock.set(start + Duration::days(day));
+        UserFixture::new().created_via(created_via).create(&db).await.expect("user is created");
+    }
+
+    let users = UserOperations::new(&db);
+    let counts = users.signups_by_source(start + Duration::days(1), start + Duration::days(3)).await.expect("signups are counted");
+    let counts: Vec<(String, u64)> = counts.into_iter().map(|count| (format!("{:?}", count.created_via), count.count)).collect();
+    assert_eq!(counts.len(), 3, "{:?}", counts);
+    assert_eq!(counts[0], ("Mobile".to_string(), 3), "the most common source comes first");
+    assert!(counts.contains(&("Web".to_string(), 1)));
+    assert!(counts.contains(&("Google".to_string(), 1)));
+
+    let backwards = users.signups_by_source(start + Duration::days(3), start + Duration::days(1)).await;
+    assert!(matches!(backwards, Err(Error::Validation(_))), "from after to is refused, got {:?}", backwards);
+}
@@ -0,0 +1,71 @@
+//! Authentication events, as a log subscriber sees them
+
+mod common;
+
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use libretune::fixtures::PASSWORD;
+use serde_json::json;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use common::create_test_user;
+
+/// The fields of every `auth_audit` event, by name
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<HashMap<String, String>>>>);
+
+#[derive(Default)]
+struct Fields(HashMap<String, String>);
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Captured {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if event.metadata().target() == "auth_audit" {
+            let mut fields = Fields::default();
+            event.record(&mut fields);
+            self.0.lock().unwrap().push(fields.0);
+        }
+    }
+}
+
+#[actix_web::test]
+async fn a_failed_login_is_logged_with_the_email_but_not_the_password() {
+    let captured = Captured::default();
+    let _subscriber = tracing::subscriber::set_default(tracing_subscriber::registry().with(captured.clone()));
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    for (email, password) in [(alice.user.email.as_str(), "hunter2-but-wrong"), ("nobody@example.com", PASSWORD)] {
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .insert_header((header::USER_AGENT, "curl/8.0"))
+            .set_json(json!({ "email": email, "password": password }))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    let events = captured.0.lock().unwrap().clone();
+    assert_eq!(events.len(), 2, "{:?}", events);
+    for (event, email) in events.iter().zip([&alice.user.email, "nobody@example.com"]) {
+        assert_eq!(event.get("event").map(String::as_str), Some("auth.login_failed"));
+        assert_eq!(event.get("email").map(String::as_str), Some(email));
+        assert_eq!(event.get("user_agent").map(String::as_str), Some("curl/8.0"));
+        assert!(event.values().all(|value| !value.contains("hunter2") && !value.contains(PASSWORD)), "{:?}", event);
+    }
+    assert_eq!(events[0].get("user_id"), Some(&alice.user.id.to_string()));
+    assert_eq!(events[1].get("user_id"), None, "there's no user to name");
+}
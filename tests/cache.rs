@@ -0,0 +1,64 @@
+//! Reads through the user cache see every write made before them
+
+mod common;
+
+use libretune::db::{Db, UserOperations};
+use libretune::types::id::UserId;
+use common::create_test_user;
+
+/// Read the user once so the next read is served from the cache
+async fn warm(db: &Db, user_id: UserId) {
+    let users = UserOperations::new(db);
+    users.get_user_by_id(user_id).await.expect("user is here");
+    let hits = db.user_cache().stats().hits;
+    users.get_public_user(user_id).await.expect("user is here");
+    assert_eq!(db.user_cache().stats().hits, hits + 1, "the second read is a hit");
+}
+
+#[actix_web::test]
+async fn a_read_right_after_a_profile_update_sees_it() {
+    let db = common::db().await;
+    let users = UserOperations::new(&db);
+    let alice = create_test_user(&db, "alice").await;
+    warm(&db, alice.user.id).await;
+
+    let mut profile = alice.user.profile.clone().expect("alice has a profile");
+    profile.profile_name = "Alice Liddell".to_string();
+    profile.profile_bio = Some("Down the rabbit hole".to_string());
+    users.update_profile(alice.user.id, profile).await.expect("profile is updated");
+
+    let user = users.get_user_by_id(alice.user.id).await.expect("alice is here");
+    let profile = user.profile.expect("alice has a profile");
+    assert_eq!(profile.profile_name, "Alice Liddell");
+    assert_eq!(profile.profile_bio.as_deref(), Some("Down the rabbit hole"));
+    let public = users.get_public_user(alice.user.id).await.expect("alice is here");
+    assert_eq!(public.profile_name.as_deref(), Some("Alice Liddell"));
+}
+
+#[actix_web::test]
+async fn reads_see_writes_made_by_query_and_in_transactions() {
+    let db = common::db().await;
+    let users = UserOperations::new(&db);
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+
+    // An increment done in the database
+    warm(&db, alice.user.id).await;
+    users.record_profile_view(alice.user.id).await.expect("view is counted");
+    let views = users.get_user_by_id(alice.user.id).await.expect("alice is here").profile.expect("alice has a profile").profile_views;
+    assert_eq!(views, 1);
+
+    // Both sides of a follow, written in one transaction
+    warm(&db, alice.user.id).await;
+    warm(&db, bob.user.id).await;
+    users.follow_user(alice.user.id, bob.user.id).await.expect("alice follows bob");
+    let following = users.get_user_by_id(alice.user.id).await.expect("alice is here").profile.expect("alice has a profile").following;
+    let followers = users.get_user_by_id(bob.user.id).await.expect("bob is here").profile.expect("bob has a profile").followers;
+    assert_eq!(following, Some(vec![bob.user.id]));
+    assert_eq!(followers, Some(vec![alice.user.id]));
+
+    // A ban, which the public view reports
+    warm(&db, bob.user.id).await;
+    users.ban_user(bob.user.id).await.expect("bob is banned");
+    assert!(users.get_user_by_id(bob.user.id).await.expect("bob is here").profile.expect("bob has a profile").is_banned());
+}
@@ -0,0 +1,125 @@
+//! Expiry checked against a clock the tests move. The mock clock is
+//! process-wide, so these live in a binary of their own, and tests that
+//! move it take turns.
+
+mod common;
+
+use std::sync::Arc;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use chrono::{Duration, TimeZone, Utc};
+use libretune::clock::MockClock;
+use libretune::db::play::{PlayOperations, PLAY_DEDUP_WINDOW};
+use libretune::db::session::SESSION_TTL;
+use libretune::db::track::TrackOperations;
+use libretune::db::{Db, UserOperations};
+use libretune::fixtures::TrackFixture;
+use libretune::types::user::{CreateUserInput, CreatedVia, TrackSort};
+use tokio::sync::{Mutex, MutexGuard};
+use common::{auth_header_for, create_test_user, mock_clock, TestUser};
+
+/// The mock clock, held until the test is done moving it
+async fn move_the_clock() -> (Arc<MockClock>, MutexGuard<'static, ()>) {
+    static TURN: Mutex<()> = Mutex::const_new(());
+    let turn = TURN.lock().await;
+    (mock_clock(), turn)
+}
+
+#[actix_web::test]
+async fn sessions_stop_working_once_they_expire() {
+    let (clock, _turn) = move_the_clock().await;
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    clock.advance(SESSION_TTL - Duration::minutes(1));
+    let req = test::TestRequest::get()
+        .uri("/me/suggestions")
+        .insert_header(auth_header_for(&alice))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    clock.advance(Duration::minutes(2));
+    let req = test::TestRequest::get()
+        .uri("/me/suggestions")
+        .insert_header(auth_header_for(&alice))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn a_handle_tells_the_time_by_its_own_clock() {
+    mock_clock();
+    let at = Utc.with_ymd_and_hms(2020, 2, 29, 12, 0, 0).unwrap();
+    let db = Db::memory().await.expect("in-memory database connects").with_clock(Arc::new(MockClock::new(at)));
+
+    let user = UserOperations::new(&db).create_user(CreateUserInput {
+        email: "leap@example.com".to_string(),
+        username: "leap".to_string(),
+        hashed_password: String::new(),
+        created_via: CreatedVia::Web,
+        bio: None,
+    })
+    .await
+    .expect("user is created");
+
+    assert_eq!(user.created_at, at);
+    assert_eq!(db.now(), at);
+}
+
+#[actix_web::test]
+async fn a_temporary_ban_lifts_when_it_runs_out() {
+    let (clock, _turn) = move_the_clock().await;
+    let db = common::db().await;
+    let users = UserOperations::new(&db);
+    let alice = create_test_user(&db, "alice").await;
+    TrackFixture::new().owner(alice.user.id).genre("banjo-step").create(&db).await.expect("track is imported");
+    let banjo_step = || async {
+        let (tracks, _) = TrackOperations::new(&db).tracks_in_genre("banjo-step", TrackSort::Newest, false, 10, 0).await.expect("genre is listed");
+        tracks.len()
+    };
+
+    users.ban_user_for(alice.user.id, Duration::days(7)).await.expect("alice is banned");
+    clock.advance(Duration::days(7) - Duration::minutes(1));
+    let banned = users.get_user_by_id(alice.user.id).await.expect("alice is here");
+    assert!(banned.profile.as_ref().is_some_and(|p| p.is_banned()));
+    assert_eq!(banjo_step().await, 0, "a banned user's tracks aren't listed");
+
+    clock.advance(Duration::minutes(2));
+    let lifted = users.get_user_by_id(alice.user.id).await.expect("alice is here");
+    assert!(lifted.profile.as_ref().is_some_and(|p| !p.is_banned() && p.is_active()));
+    assert_eq!(banjo_step().await, 1, "tracks are listed again once the ban lifts");
+}
+
+#[actix_web::test]
+async fn replays_count_once_within_the_dedup_window() {
+    let (clock, _turn) = move_the_clock().await;
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let owner = create_test_user(&db, "owner").await;
+    let alice = create_test_user(&db, "alice").await;
+    let track = TrackFixture::new().owner(owner.user.id).create(&db).await.expect("track is imported");
+    let play = |listener: Option<&TestUser>| {
+        let mut req = test::TestRequest::post().uri(&format!("/tracks/{}/plays", track.id));
+        if let Some(listener) = listener {
+            req = req.insert_header(auth_header_for(listener));
+        }
+        test::call_service(&app, req.to_request())
+    };
+    let plays = PlayOperations::new(&db);
+
+    assert_eq!(play(Some(&alice)).await.status(), StatusCode::NO_CONTENT);
+    clock.advance(PLAY_DEDUP_WINDOW - Duration::minutes(1));
+    assert_eq!(play(Some(&alice)).await.status(), StatusCode::NO_CONTENT);
+    assert_eq!(plays.count_plays(track.id).await.expect("plays are counted"), 1, "a replay within the window counts once");
+
+    clock.advance(Duration::minutes(2));
+    play(Some(&alice)).await;
+    assert_eq!(plays.count_plays(track.id).await.expect("plays are counted"), 2, "a replay after the window counts again");
+
+    play(None).await;
+    play(None).await;
+    assert_eq!(plays.count_plays(track.id).await.expect("plays are counted"), 4, "anonymous plays aren't deduped");
+}
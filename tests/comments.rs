@@ -0,0 +1,182 @@
+//! Listing a track's comments, through the filters, a page at a time, and
+//! what a page costs
+
+mod common;
+
+use actix_web::http::StatusCode;
+use std::collections::HashSet;
+use actix_web::test;
+use libretune::db::track::TrackOperations;
+use libretune::db::UserOperations;
+use serde_json::{json, Value};
+use common::{auth_header_for, create_test_user, import_track};
+
+#[actix_web::test]
+async fn pinned_only_leaves_out_unpinned_comments() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let track_id = import_track(&db, &alice, "Comment Filters").await;
+
+    let pinned = TrackOperations::new(&db).add_comment(track_id, bob.user.id, "Pin me".to_string(), None)
+        .await
+        .expect("comment is added");
+    TrackOperations::new(&db).add_comment(track_id, bob.user.id, "Leave me".to_string(), None)
+        .await
+        .expect("comment is added");
+
+    // There's no endpoint for pinning comments, so it's set directly
+    let owner = UserOperations::new(&db).get_user_by_id(alice.user.id).await.expect("owner exists");
+    let mut profile = owner.profile.expect("owner has a profile");
+    let comment = profile.uploads.iter_mut()
+        .flatten()
+        .filter(|track| track.id == track_id)
+        .flat_map(|track| track.comments.iter_mut().flatten())
+        .find(|comment| comment.id == pinned.id)
+        .expect("comment is stored on the track");
+    comment.is_pinned = true;
+    UserOperations::new(&db).update_profile(alice.user.id, profile).await.expect("comment is pinned");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/tracks/{}/comments?filter=pinned_only", track_id))
+        .to_request();
+    let comments: Value = test::call_and_read_body_json(&app, req).await;
+
+    let items = comments["items"].as_array().expect("a page of comments");
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["content"], "Pin me");
+}
+
+#[actix_web::test]
+async fn deleted_comments_are_for_moderators() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let track_id = import_track(&db, &alice, "Comment Filters").await;
+
+    let comment = TrackOperations::new(&db).add_comment(track_id, bob.user.id, "Gone soon".to_string(), None)
+        .await
+        .expect("comment is added");
+    TrackOperations::new(&db).delete_comment(track_id, comment.id, bob.user.id)
+        .await
+        .expect("comment is deleted");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/tracks/{}/comments?filter=include_deleted", track_id))
+        .insert_header(auth_header_for(&bob))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // Without the filter the deleted comment is left out
+    let req = test::TestRequest::get().uri(&format!("/tracks/{}/comments", track_id)).to_request();
+    let comments: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(comments["items"].as_array().map(Vec::len), Some(0));
+}
+
+#[actix_web::test]
+async fn comments_arriving_mid_scroll_cause_no_gaps_or_repeats() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let track_id = import_track(&db, &alice, "Busy Thread").await;
+
+    let mut before = Vec::new();
+    for n in 0..5 {
+        let comment = TrackOperations::new(&db).add_comment(track_id, bob.user.id, format!("Comment {}", n), None)
+            .await
+            .expect("comment is added");
+        before.push(comment.id.to_string());
+    }
+
+    let mut seen = Vec::new();
+    let mut cursor: Option<String> = None;
+    loop {
+        let uri = match &cursor {
+            Some(cursor) => format!("/tracks/{}/comments?limit=2&cursor={}", track_id, cursor),
+            None => format!("/tracks/{}/comments?limit=2", track_id),
+        };
+        let req = test::TestRequest::get().uri(&uri).to_request();
+        let page: Value = test::call_and_read_body_json(&app, req).await;
+        let items = page["items"].as_array().expect("a page of comments");
+        seen.extend(items.iter().map(|item| item["id"].as_str().expect("comments have ids").to_string()));
+
+        // A newer comment would push every offset along by one
+        TrackOperations::new(&db).add_comment(track_id, bob.user.id, "Interrupting".to_string(), None)
+            .await
+            .expect("comment is added");
+
+        match page["next_cursor"].as_str() {
+            Some(next) => cursor = Some(next.to_string()),
+            None => break,
+        }
+    }
+
+    let unique: HashSet<&String> = seen.iter().collect();
+    assert_eq!(unique.len(), seen.len(), "a comment was listed twice: {:?}", seen);
+    before.reverse();
+    assert_eq!(seen, before, "newest first, each once");
+}
+
+#[actix_web::test]
+async fn a_page_of_fifty_comments_costs_a_few_queries() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track_id = import_track(&db, &alice, "Popular").await;
+    for n in 0..50 {
+        let fan = create_test_user(&db, &format!("fan{}", n)).await;
+        TrackOperations::new(&db).add_comment(track_id, fan.user.id, format!("Comment {}", n), None)
+            .await
+            .expect("comment is added");
+    }
+    db.user_cache().clear();
+
+    let before = db.queries_sent();
+    let req = test::TestRequest::get().uri(&format!("/tracks/{}/comments?limit=50", track_id)).to_request();
+    let comments: Value = test::call_and_read_body_json(&app, req).await;
+    let queries = db.queries_sent() - before;
+
+    let items = comments["items"].as_array().expect("a page of comments");
+    assert_eq!(items.len(), 50);
+    assert!(items.iter().all(|comment| comment["author"]["username"].is_string()), "every author is filled in");
+    assert!(queries <= 3, "the page took {} queries", queries);
+}
+
+#[actix_web::test]
+async fn the_comment_count_follows_adds_and_deletes() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let track_id = import_track(&db, &alice, "Talked About").await;
+    let uri = format!("/tracks/{}", track_id);
+    let comment_count = || async {
+        let track: Value = test::call_and_read_body_json(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        track["comment_count"].clone()
+    };
+    assert_eq!(comment_count().await, 0);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/comments", uri))
+        .insert_header(auth_header_for(&bob))
+        .set_json(json!({ "content": "First!" }))
+        .to_request();
+    let comment: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(comment_count().await, 1);
+
+    let delete = || {
+        test::TestRequest::delete()
+            .uri(&format!("{}/comments/{}", uri, comment["id"].as_str().expect("comments have an id")))
+            .insert_header(auth_header_for(&bob))
+            .to_request()
+    };
+    assert_eq!(test::call_service(&app, delete()).await.status(), StatusCode::NO_CONTENT);
+    assert_eq!(comment_count().await, 0, "soft-deleted comments aren't counted");
+
+    assert_eq!(test::call_service(&app, delete()).await.status(), StatusCode::NOT_FOUND);
+    assert_eq!(comment_count().await, 0, "deleting twice doesn't count twice");
+}
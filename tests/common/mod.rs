@@ -0,0 +1,133 @@
+//! Shared setup for the integration tests: the app as `main` builds it,
+//! talking to an in-memory database, and shortcuts for the accounts tests
+//! act as.
+//!
+//! Every test gets an empty database of its own from `db()`, and builds the
+//! app and its accounts on it.
+
+#![allow(dead_code)] // not every test binary uses every helper
+
+use std::sync::{mpsc, Arc, Once, OnceLock};
+use std::time::Duration;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::header::{self, HeaderName};
+use actix_web::test;
+use libretune::app::{self, AppConfig};
+use libretune::clock::{self, MockClock};
+use libretune::db::session::SessionOperations;
+use libretune::email::{self, CaptureMailer, Email};
+use libretune::db::{connect_memory_db, Db};
+use libretune::fixtures::{TrackFixture, UserFixture};
+use libretune::types::id::TrackId;
+use libretune::types::user::User;
+use uuid::Uuid;
+
+static DB_READY: Once = Once::new();
+
+/// Connect the process-wide database once per test binary, for the
+/// background work the app hands it. It's connected from a
+/// runtime of its own that outlives any one test, since the connection's
+/// background task would die with the runtime of the test that opened it.
+pub fn init_db() {
+    DB_READY.call_once(|| {
+        let (ready, connected) = mpsc::channel();
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+                .expect("test runtime starts");
+            runtime.block_on(async {
+                connect_memory_db().await.expect("in-memory database connects");
+                ready.send(()).expect("test is waiting");
+                std::future::pending::<()>().await;
+            });
+        });
+        connected.recv().expect("database thread reports in");
+    });
+}
+
+/// An empty in-memory database for one test
+pub async fn db() -> Db {
+    Db::memory().await.expect("in-memory database connects")
+}
+
+/// The app with every route on `db`, ready for `test::call_service`
+pub async fn app(db: &Db) -> impl Service<actix_http::Request, Response = ServiceResponse<impl MessageBody>, Error = actix_web::Error> {
+    init_db();
+    test::init_service(app::build(AppConfig { docs: false, federation: false }, Db::clone(db))).await
+}
+
+/// A signed-up account and a bearer token for it
+pub struct TestUser {
+    pub user: User,
+    pub token: String,
+}
+
+/// Sign up a user on `db` named `{prefix}_{random}` with a profile and the
+/// fixtures' `PASSWORD`, and open a session for them
+pub async fn create_test_user(db: &Db, prefix: &str) -> TestUser {
+    let username = format!("{}_{}", prefix, &Uuid::new_v4().simple().to_string()[..8]);
+    let user = UserFixture::new().username(username).create(db).await.expect("user is created");
+    let (_, token) = SessionOperations::new(db).create_session(user.id).await.expect("session is created");
+
+    TestUser { user, token }
+}
+
+/// Sign up an admin on `db` and open a session for them
+pub async fn create_test_admin(db: &Db) -> TestUser {
+    let user = UserFixture::new().admin().create(db).await.expect("admin is created");
+    let (_, token) = SessionOperations::new(db).create_session(user.id).await.expect("session is created");
+
+    TestUser { user, token }
+}
+
+/// The `Authorization` header that signs a request in as `user`
+pub fn auth_header_for(user: &TestUser) -> (HeaderName, String) {
+    (header::AUTHORIZATION, format!("Bearer {}", user.token))
+}
+
+/// Import a public track titled `title` for `owner`, skipping the upload,
+/// and return its id
+pub async fn import_track(db: &Db, owner: &TestUser, title: &str) -> TrackId {
+    let track = TrackFixture::new().owner(owner.user.id).title(title).create(db).await.expect("track is imported");
+
+    track.id
+}
+
+/// The mailer every email this test binary sends goes to
+pub fn capture_mail() -> Arc<CaptureMailer> {
+    static CAPTURE: OnceLock<Arc<CaptureMailer>> = OnceLock::new();
+    CAPTURE
+        .get_or_init(|| {
+            let capture = Arc::new(CaptureMailer::default());
+            assert!(email::install(capture.clone()), "capture is installed before anything is sent");
+            capture
+        })
+        .clone()
+}
+
+/// The latest email to `to`, waiting a few seconds for it since mail goes
+/// out in the background
+pub async fn wait_for_email(capture: &CaptureMailer, to: &str) -> Email {
+    for _ in 0..50 {
+        if let Some(email) = capture.sent_to(to).pop() {
+            return email;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("nothing was emailed to {}", to)
+}
+
+/// The clock this test binary tells the time by. Call it before anything
+/// else in the binary asks the time, i.e. first thing in every test.
+pub fn mock_clock() -> Arc<MockClock> {
+    static MOCK: OnceLock<Arc<MockClock>> = OnceLock::new();
+    MOCK
+        .get_or_init(|| {
+            let mock = Arc::new(MockClock::default());
+            assert!(clock::install(mock.clone()), "mock clock is installed before the time is asked");
+            mock
+        })
+        .clone()
+}
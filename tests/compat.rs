@@ -0,0 +1,325 @@
+//! Documents already in the database keep reading into the current types.
+//!
+//! `tests/fixtures/<kind>/` holds one JSON document per shape users,
+//! tracks, playlists and comments have been stored in; `current.json` is
+//! the shape written today. A change that breaks one of them needs a
+//! migration for the stored documents, or a conscious decision that no
+//! document of that shape is left and the fixture can go.
+
+use std::fs;
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use libretune::fixtures::{CommentFixture, PlaylistFixture, TrackFixture, UserFixture};
+use libretune::types::credit::{Credit, CreditRole, CreditStatus};
+use libretune::types::license::{License, LicenseChange};
+use libretune::types::user::{AccountStatus, Comment, Playlist, ProfileTrackOrder, ReportTarget, Track, User};
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use uuid::Uuid;
+
+/// Generated values per type in the round-trip tests
+const ROUNDS: u64 = 200;
+
+fn fixture(path: &str) -> Value {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(path);
+    let text = fs::read_to_string(&path).unwrap_or_else(|e| panic!("{} reads: {}", path.display(), e));
+    serde_json::from_str(&text).unwrap_or_else(|e| panic!("{} is JSON: {}", path.display(), e))
+}
+
+fn read<T: DeserializeOwned>(path: &str) -> T {
+    serde_json::from_value(fixture(path)).unwrap_or_else(|e| panic!("{} no longer deserializes: {}", path, e))
+}
+
+fn write<T: Serialize>(value: &T) -> Value {
+    serde_json::to_value(value).expect("serializes")
+}
+
+/// Every document of one kind reads, and writes back in a shape that
+/// reads back the same. The current shape writes back unchanged.
+fn assert_shapes_read<T: Serialize + DeserializeOwned>(kind: &str) {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(kind);
+    let mut documents: Vec<String> = fs::read_dir(&dir)
+        .unwrap_or_else(|e| panic!("{} lists: {}", dir.display(), e))
+        .map(|entry| entry.expect("entry reads").file_name().to_string_lossy().into_owned())
+        .filter(|name| name.ends_with(".json"))
+        .collect();
+    documents.sort();
+    assert!(documents.iter().any(|name| name == "current.json"), "{} has no current shape", kind);
+
+    for name in documents {
+        let path = format!("{}/{}", kind, name);
+        let stored = fixture(&path);
+        let written = write(&read::<T>(&path));
+        let rewritten = write(&serde_json::from_value::<T>(written.clone()).expect("written shape reads"));
+        assert_eq!(rewritten, written, "{} doesn't settle after one write", path);
+        if name == "current.json" {
+            assert_eq!(written, stored, "{} isn't written the way it's stored", path);
+        }
+    }
+}
+
+#[test]
+fn every_stored_user_reads() {
+    assert_shapes_read::<User>("user");
+}
+
+#[test]
+fn every_stored_track_reads() {
+    assert_shapes_read::<Track>("track");
+}
+
+#[test]
+fn every_stored_playlist_reads() {
+    assert_shapes_read::<Playlist>("playlist");
+}
+
+#[test]
+fn every_stored_comment_reads() {
+    assert_shapes_read::<Comment>("comment");
+}
+
+#[test]
+fn the_original_user_reads_with_todays_defaults() {
+    let user: User = read("user/original.json");
+    assert_eq!(user.username, "Ärni");
+    assert!(user.deleted_playlists.is_empty());
+    assert!(!user.legal_hold);
+    assert_eq!(user.username_sort_key, ""); // until reconciled
+
+    let profile = user.profile.expect("profile is kept");
+    assert_eq!(profile.status, AccountStatus::Active);
+    assert_eq!(profile.structured_location, None);
+    assert_eq!(profile.location.as_deref(), Some("Reykjavík"));
+    assert!(!profile.is_verified);
+    assert!(profile.pinned_track_ids.is_empty());
+    assert_eq!(profile.track_order, ProfileTrackOrder::Newest);
+    assert!(!profile.share_now_playing);
+    assert_eq!(profile.push_kinds, None);
+
+    let uploads = profile.uploads.expect("uploads are kept");
+    assert_eq!(uploads.len(), 1);
+    assert_eq!(uploads[0].title, "Night Drive");
+}
+
+#[test]
+fn a_ban_from_before_the_status_is_still_a_ban() {
+    let user: User = read("user/banned-before-status.json");
+    let profile = user.profile.expect("profile is kept");
+    assert_eq!(profile.status, AccountStatus::Banned);
+    assert!(profile.is_banned());
+}
+
+#[test]
+fn the_current_user_reads_every_field() {
+    let user: User = read("user/current.json");
+    assert!(user.legal_hold);
+    assert_eq!(user.username_sort_key, "arni");
+
+    let profile = user.profile.expect("profile is kept");
+    assert_eq!(profile.status, AccountStatus::Suspended);
+    assert_eq!(profile.structured_location.map(|l| l.country), Some("IS".to_string()));
+    assert_eq!(profile.track_order, ProfileTrackOrder::MostLiked);
+    assert_eq!(profile.pinned_track_ids.len(), 1);
+    assert_eq!(profile.push_kinds.map(|kinds| kinds.len()), Some(2));
+
+    let reports = profile.reports.expect("reports are kept");
+    assert!(matches!(reports[0].target, ReportTarget::Track(_)));
+}
+
+#[test]
+fn the_original_track_reads_with_todays_defaults() {
+    let track: Track = read("track/original.json");
+    assert_eq!(track.title, "Night Drive");
+    assert_eq!(track.likes, 4);
+    assert!(!track.downloadable);
+    assert_eq!(track.download_count, 0);
+    assert_eq!(track.comment_count, 0);
+    assert!(!track.has_lyrics);
+    assert_eq!(track.license, License::AllRightsReserved);
+    assert!(track.license_history.is_empty());
+    assert!(track.audio_versions.is_empty());
+    assert!(track.attachments.is_empty());
+    assert!(track.credited_artists.is_empty());
+    assert_eq!(track.language, None);
+    assert_eq!(track.slug, None);
+}
+
+#[test]
+fn the_current_track_reads_every_field() {
+    let track: Track = read("track/current.json");
+    assert_eq!(track.license, License::CcBySa);
+    assert_eq!(track.license_history[0].license, License::AllRightsReserved);
+    assert_eq!(track.technical_metadata.map(|m| m.sample_rate), Some(44100));
+    assert_eq!(track.audio_versions.len(), 1);
+    assert_eq!(track.attachments[0].name, "stems.zip");
+    assert_eq!(track.credited_artists[0].role, CreditRole::Feature);
+    assert_eq!(track.credited_artists[1].user_id, None);
+    assert_eq!(track.slug.as_deref(), Some("night-drive"));
+}
+
+#[test]
+fn the_original_playlist_reads_with_todays_defaults() {
+    let playlist: Playlist = read("playlist/original.json");
+    assert_eq!(playlist.revision, 0);
+    assert_eq!(playlist.slug, None);
+    assert!(playlist.slug_aliases.is_empty());
+
+    // Tracks are still copies, not references
+    assert_eq!(playlist.tracks.len(), 1);
+    assert_eq!(playlist.tracks[0].title, "Night Drive");
+}
+
+#[test]
+fn the_current_playlist_reads_every_field() {
+    let playlist: Playlist = read("playlist/current.json");
+    assert_eq!(playlist.revision, 9);
+    assert!(playlist.is_collaborative);
+    assert_eq!(playlist.slug_aliases, vec!["late-night-mix".to_string()]);
+}
+
+#[test]
+fn the_current_comment_reads_its_thread() {
+    let comment: Comment = read("comment/current.json");
+    assert!(comment.is_pinned);
+    assert_eq!(comment.likes.map(|likes| likes.len()), Some(1));
+
+    let replies = comment.replies.expect("replies are kept");
+    assert_eq!(replies[0].parent_comment_id, Some(comment.id));
+}
+
+fn some<T>(rng: &mut StdRng, value: impl FnOnce(&mut StdRng) -> T) -> Option<T> {
+    rng.random_bool(0.5).then(|| value(rng))
+}
+
+fn uuid<I: From<Uuid>>(rng: &mut StdRng) -> I {
+    Uuid::from_u128(rng.random()).into()
+}
+
+fn uuids<I: From<Uuid>>(rng: &mut StdRng) -> Vec<I> {
+    (0..rng.random_range(0..4)).map(|_| uuid(rng)).collect()
+}
+
+fn time(rng: &mut StdRng) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(rng.random_range(0..4_000_000_000_000)).expect("in range")
+}
+
+fn word(rng: &mut StdRng) -> String {
+    const WORDS: &[&str] = &["night", "drive", "Ärni", "tape", "", "ß", "🎧", "a \"quoted\" word"];
+    WORDS.choose(rng).copied().unwrap_or("night").to_string()
+}
+
+fn license(rng: &mut StdRng) -> License {
+    const LICENSES: &[License] = &[License::AllRightsReserved, License::CcBy, License::CcBySa, License::CcByNc, License::CcByNcSa, License::Cc0];
+    LICENSES.choose(rng).copied().unwrap_or_default()
+}
+
+fn random_comment(rng: &mut StdRng, track: &Track, depth: usize) -> Comment {
+    let mut comment = CommentFixture::new().on(track).by(uuid(rng)).content(word(rng)).build();
+    comment.created_at = time(rng);
+    comment.is_deleted = rng.random_bool(0.2);
+    comment.is_pinned = rng.random_bool(0.2);
+    comment.likes = some(rng, uuids);
+    comment.dislikes = some(rng, uuids);
+    if depth > 0 {
+        comment.replies = some(rng, |rng| (0..rng.random_range(0..3)).map(|_| random_comment(rng, track, depth - 1)).collect());
+    }
+    comment
+}
+
+fn random_track(rng: &mut StdRng) -> Track {
+    let mut fixture = TrackFixture::new()
+        .owner(uuid(rng))
+        .title(word(rng))
+        .license(license(rng))
+        .created_at(time(rng));
+    if rng.random_bool(0.5) {
+        fixture = fixture.private();
+    }
+    if rng.random_bool(0.5) {
+        fixture = fixture.downloadable();
+    }
+    let mut track = fixture.build();
+    track.description = some(rng, word);
+    track.tags = some(rng, |rng| vec![word(rng), word(rng)]);
+    track.likes = rng.random();
+    track.download_count = rng.random();
+    track.license_history = (0..rng.random_range(0..3))
+        .map(|_| LicenseChange { license: license(rng), changed_at: time(rng) })
+        .collect();
+    track.credited_artists = (0..rng.random_range(0..3))
+        .map(|_| Credit {
+            id: uuid(rng),
+            user_id: some(rng, uuid),
+            name: word(rng),
+            role: *[CreditRole::Feature, CreditRole::Producer, CreditRole::Remixer].choose(rng).expect("non-empty"),
+            status: *[CreditStatus::Pending, CreditStatus::Accepted, CreditStatus::Declined].choose(rng).expect("non-empty"),
+            notify_comments: rng.random_bool(0.5),
+            created_at: time(rng),
+        })
+        .collect();
+    track.slug = some(rng, word);
+    let comments = some(rng, |rng| (0..rng.random_range(0..3)).map(|_| random_comment(rng, &track, 2)).collect());
+    track.comments = comments;
+    track
+}
+
+fn random_playlist(rng: &mut StdRng) -> Playlist {
+    let mut playlist = PlaylistFixture::new().owner(uuid(rng)).name(word(rng)).build();
+    playlist.tracks = (0..rng.random_range(0..3)).map(|_| random_track(rng)).collect();
+    playlist.revision = rng.random();
+    playlist.is_collaborative = rng.random_bool(0.5);
+    playlist
+}
+
+fn random_user(rng: &mut StdRng) -> User {
+    let mut fixture = UserFixture::new().username(word(rng)).hashed_password(word(rng));
+    if rng.random_bool(0.5) {
+        fixture = fixture.private();
+    }
+    if rng.random_bool(0.5) {
+        fixture = fixture.admin();
+    }
+    if rng.random_bool(0.5) {
+        fixture = fixture.verified();
+    }
+    let mut user = fixture.build();
+    user.legal_hold = rng.random_bool(0.5);
+    user.playlists = some(rng, |rng| (0..rng.random_range(0..3)).map(|_| random_playlist(rng)).collect());
+
+    let profile = user.profile.as_mut().expect("fixture users have a profile");
+    profile.status = *[AccountStatus::Active, AccountStatus::Suspended, AccountStatus::Banned, AccountStatus::Deleted]
+        .choose(rng)
+        .expect("non-empty");
+    profile.banned_until = some(rng, time);
+    profile.track_order = *[ProfileTrackOrder::Newest, ProfileTrackOrder::MostLiked, ProfileTrackOrder::Manual]
+        .choose(rng)
+        .expect("non-empty");
+    profile.followers = some(rng, uuids);
+    profile.pinned_track_ids = uuids(rng);
+    profile.last_login = some(rng, time);
+    profile.uploads = some(rng, |rng| (0..rng.random_range(0..3)).map(|_| random_track(rng)).collect());
+    user
+}
+
+/// Writing `value` and reading it back gives what was written
+fn assert_round_trips<T: Serialize + DeserializeOwned>(value: &T) {
+    let written = write(value);
+    let read: T = serde_json::from_value(written.clone()).unwrap_or_else(|e| panic!("{} doesn't read back: {}", written, e));
+    assert_eq!(write(&read), written);
+}
+
+#[test]
+fn generated_values_round_trip() {
+    for seed in 0..ROUNDS {
+        let mut rng = StdRng::seed_from_u64(seed);
+        assert_round_trips(&random_user(&mut rng));
+        assert_round_trips(&random_playlist(&mut rng));
+        let track = random_track(&mut rng);
+        assert_round_trips(&random_comment(&mut rng, &track, 3));
+        assert_round_trips(&track);
+    }
+}
@@ -0,0 +1,162 @@
+//! Conditional GETs: a cached copy is confirmed with a 304 until the
+//! resource changes
+
+mod common;
+
+use std::sync::Arc;
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use chrono::{Duration, TimeZone, Utc};
+use libretune::clock::MockClock;
+use libretune::fixtures::PlaylistFixture;
+use serde_json::json;
+use common::{auth_header_for, create_test_user, import_track};
+
+/// GET `uri`, sending `etag` in `If-None-Match` if given, and return the
+/// status, the ETag it answered with and the body's length
+async fn fetch<B: MessageBody>(
+    app: &impl Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    uri: &str,
+    etag: Option<&str>,
+) -> (StatusCode, String, usize) {
+    let mut req = test::TestRequest::get().uri(uri);
+    if let Some(etag) = etag {
+        req = req.insert_header((header::IF_NONE_MATCH, etag));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    let status = resp.status();
+    let etag = resp.headers()
+        .get(header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .expect("responses carry an ETag")
+        .to_string();
+    let body = test::read_body(resp).await;
+
+    (status, etag, body.len())
+}
+
+/// GET `uri`, sending `since` in `If-Modified-Since` if given, and return
+/// the status and the `Last-Modified` it answered with
+async fn fetch_since<B: MessageBody>(
+    app: &impl Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    uri: &str,
+    since: Option<&str>,
+) -> (StatusCode, String) {
+    let mut req = test::TestRequest::get().uri(uri);
+    if let Some(since) = since {
+        req = req.insert_header((header::IF_MODIFIED_SINCE, since));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    let last_modified = resp.headers()
+        .get(header::LAST_MODIFIED)
+        .and_then(|value| value.to_str().ok())
+        .expect("responses carry a Last-Modified")
+        .to_string();
+
+    (resp.status(), last_modified)
+}
+
+#[actix_web::test]
+async fn a_track_is_not_modified_until_it_changes() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track_id = import_track(&db, &alice, "Cached").await;
+    let uri = format!("/tracks/{}", track_id);
+
+    let (status, etag, _) = fetch(&app, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(etag.starts_with("W/\""), "{}", etag);
+
+    let (status, same, body) = fetch(&app, &uri, Some(&etag)).await;
+    assert_eq!((status, body), (StatusCode::NOT_MODIFIED, 0));
+    assert_eq!(same, etag);
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "title": "Cached, Renamed" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let (status, changed, body) = fetch(&app, &uri, Some(&etag)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(body > 0);
+    assert_ne!(changed, etag);
+}
+
+#[actix_web::test]
+async fn a_profile_is_not_modified_until_it_changes() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let uri = format!("/users/{}/profile", alice.user.id);
+
+    let (status, etag, _) = fetch(&app, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+
+    let (status, _, body) = fetch(&app, &uri, Some(&etag)).await;
+    assert_eq!((status, body), (StatusCode::NOT_MODIFIED, 0));
+
+    let req = test::TestRequest::put()
+        .uri("/users/me/location")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "country": "NL" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let (status, changed, _) = fetch(&app, &uri, Some(&etag)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_ne!(changed, etag);
+}
+
+#[actix_web::test]
+async fn a_track_is_not_modified_since_its_last_change() {
+    let clock = Arc::new(MockClock::new(Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap()));
+    let db = common::db().await.with_clock(clock.clone());
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track_id = import_track(&db, &alice, "Dated").await;
+    let uri = format!("/tracks/{}", track_id);
+
+    let (status, last_modified) = fetch_since(&app, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(last_modified, "Wed, 01 May 2024 12:00:00 GMT");
+
+    clock.advance(Duration::minutes(5));
+    let (status, same) = fetch_since(&app, &uri, Some(&last_modified)).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+    assert_eq!(same, last_modified);
+    let (status, _) = fetch_since(&app, &uri, Some("Wed, 01 May 2024 11:59:59 GMT")).await;
+    assert_eq!(status, StatusCode::OK, "a copy from before the last change is stale");
+
+    let req = test::TestRequest::patch()
+        .uri(&uri)
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "title": "Dated, Renamed" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let (status, changed) = fetch_since(&app, &uri, Some(&last_modified)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(changed, "Wed, 01 May 2024 12:05:00 GMT");
+}
+
+#[actix_web::test]
+async fn a_playlist_is_not_modified_since_its_last_change() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track_id = import_track(&db, &alice, "Listed").await;
+    let playlist = PlaylistFixture::new().owner(alice.user.id).tracks(&[track_id]).create(&db).await.expect("playlist is created");
+    let uri = format!("/playlists/{}", playlist.id);
+
+    let (status, last_modified) = fetch_since(&app, &uri, None).await;
+    assert_eq!(status, StatusCode::OK);
+    let (status, _) = fetch_since(&app, &uri, Some(&last_modified)).await;
+    assert_eq!(status, StatusCode::NOT_MODIFIED);
+}
@@ -0,0 +1,194 @@
+//! Server tuning read from the environment, and what `check` makes of it.
+//! The environment is shared by the whole process, so tests take turns.
+
+use std::env;
+use std::num::NonZeroUsize;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
+use actix_web::http::KeepAlive;
+use libretune::config::{self, ConfigIssue, IssueLevel};
+use IssueLevel::{Error, Warning};
+
+/// The environment, held until the test is done changing it
+fn take_turn() -> MutexGuard<'static, ()> {
+    static TURN: Mutex<()> = Mutex::new(());
+    TURN.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// What `check` finds with `vars` set, which are unset again afterwards
+fn check_with(vars: &[(&str, &str)]) -> Vec<ConfigIssue> {
+    let _turn = take_turn();
+    for (name, value) in vars {
+        env::set_var(name, value);
+    }
+    let issues = config::check();
+    for (name, _) in vars {
+        env::remove_var(name);
+    }
+    issues
+}
+
+/// The level of each issue raised about `setting`
+fn levels(issues: &[ConfigIssue], setting: &str) -> Vec<IssueLevel> {
+    issues.iter().filter(|issue| issue.setting == setting).map(|issue| issue.level).collect()
+}
+
+/// The errors `check` finds in `setting`
+fn errors_in(setting: &str) -> usize {
+    config::check()
+        .iter()
+        .filter(|issue| issue.setting == setting && issue.level == IssueLevel::Error)
+        .count()
+}
+
+#[test]
+fn workers_and_keep_alive_follow_the_environment() {
+    let _turn = take_turn();
+    env::remove_var("WORKERS");
+    env::remove_var("KEEP_ALIVE_SECS");
+    assert_eq!(config::workers(), thread::available_parallelism().map_or(1, NonZeroUsize::get), "one worker per CPU by default");
+    assert_eq!(config::keep_alive(), KeepAlive::default());
+
+    env::set_var("WORKERS", "3");
+    env::set_var("KEEP_ALIVE_SECS", "15");
+    assert_eq!(config::workers(), 3);
+    assert_eq!(config::keep_alive(), KeepAlive::Timeout(Duration::from_secs(15)));
+    assert_eq!(errors_in("WORKERS") + errors_in("KEEP_ALIVE_SECS"), 0);
+
+    env::set_var("KEEP_ALIVE_SECS", "0");
+    assert_eq!(config::keep_alive(), KeepAlive::Disabled, "0 turns keep-alive off");
+
+    // Refused at startup rather than panicking when the server is built
+    for workers in ["0", "-2", "lots"] {
+        env::set_var("WORKERS", workers);
+        assert_eq!(errors_in("WORKERS"), 1, "WORKERS={}", workers);
+    }
+    env::set_var("KEEP_ALIVE_SECS", "soon");
+    assert_eq!(errors_in("KEEP_ALIVE_SECS"), 1);
+
+    env::remove_var("WORKERS");
+    env::remove_var("KEEP_ALIVE_SECS");
+}
+
+#[test]
+fn numbers_must_be_whole() {
+    assert_eq!(levels(&check_with(&[("GC_INTERVAL_SECS", "hourly")]), "GC_INTERVAL_SECS"), [Error]);
+    assert_eq!(levels(&check_with(&[("GC_INTERVAL_SECS", "1.5")]), "GC_INTERVAL_SECS"), [Error]);
+    assert_eq!(levels(&check_with(&[("GC_INTERVAL_SECS", "3600")]), "GC_INTERVAL_SECS"), []);
+}
+
+#[test]
+fn the_port_must_fit_a_port_number() {
+    assert_eq!(levels(&check_with(&[("PORT", "70000")]), "PORT"), [Error]);
+    assert_eq!(levels(&check_with(&[("PORT", "8080")]), "PORT"), []);
+}
+
+#[test]
+fn deleted_handles_is_reserve_or_release() {
+    assert_eq!(levels(&check_with(&[("DELETED_HANDLES", "forget")]), "DELETED_HANDLES"), [Error]);
+    assert_eq!(levels(&check_with(&[("DELETED_HANDLES", "Release")]), "DELETED_HANDLES"), [], "case doesn't matter");
+}
+
+#[test]
+fn an_unknown_log_format_is_a_warning() {
+    assert_eq!(levels(&check_with(&[("LOG_FORMAT", "xml")]), "LOG_FORMAT"), [Warning]);
+    assert_eq!(levels(&check_with(&[("LOG_FORMAT", "JSON")]), "LOG_FORMAT"), []);
+}
+
+#[test]
+fn the_sample_rate_is_a_fraction() {
+    for rate in ["1.5", "-0.1", "half"] {
+        assert_eq!(levels(&check_with(&[("LOG_REQUESTS_SAMPLE_RATE", rate)]), "LOG_REQUESTS_SAMPLE_RATE"), [Warning], "{}", rate);
+    }
+    assert_eq!(levels(&check_with(&[("LOG_REQUESTS_SAMPLE_RATE", "0.25")]), "LOG_REQUESTS_SAMPLE_RATE"), []);
+}
+
+#[test]
+fn request_log_switches_are_true_or_false() {
+    let issues = check_with(&[("LOG_REQUESTS_CONSOLE", "yes"), ("LOG_REQUESTS_FILE", "on")]);
+    assert_eq!(levels(&issues, "LOG_REQUESTS_CONSOLE"), [Warning]);
+    assert_eq!(levels(&issues, "LOG_REQUESTS_FILE"), [Warning]);
+    assert_eq!(levels(&check_with(&[("LOG_REQUESTS_CONSOLE", "false")]), "LOG_REQUESTS_CONSOLE"), []);
+}
+
+#[test]
+fn the_request_log_needs_a_path_while_on() {
+    let empty_path = [("LOG_REQUESTS_FILE", "true"), ("LOG_REQUESTS_FILE_PATH", " ")];
+    assert_eq!(levels(&check_with(&empty_path), "LOG_REQUESTS_FILE_PATH"), [Error]);
+    assert_eq!(levels(&check_with(&empty_path[1..]), "LOG_REQUESTS_FILE_PATH"), [], "it's not read while the file log is off");
+}
+
+#[test]
+fn the_public_url_is_http_and_https_for_federation() {
+    assert_eq!(levels(&check_with(&[("PUBLIC_URL", "tunes.example.com")]), "PUBLIC_URL"), [Error]);
+    assert_eq!(levels(&check_with(&[("PUBLIC_URL", "http://tunes.example.com"), ("FEDERATION", "on")]), "PUBLIC_URL"), [Error]);
+    assert_eq!(levels(&check_with(&[("PUBLIC_URL", "https://tunes.example.com"), ("FEDERATION", "on")]), "PUBLIC_URL"), []);
+    assert_eq!(levels(&check_with(&[("PUBLIC_URL", "http://localhost:8000")]), "PUBLIC_URL"), []);
+}
+
+#[test]
+fn production_needs_a_public_url() {
+    assert_eq!(levels(&check_with(&[("ENV", "production")]), "PUBLIC_URL"), [Error]);
+    assert_eq!(levels(&check_with(&[]), "PUBLIC_URL"), [], "development falls back to the bind address");
+}
+
+#[test]
+fn smtp_needs_a_host_and_a_sender() {
+    let issues = check_with(&[("EMAIL_BACKEND", "smtp")]);
+    assert_eq!(levels(&issues, "SMTP_HOST"), [Error]);
+    assert_eq!(levels(&issues, "EMAIL_FROM"), [Error]);
+    let issues = check_with(&[("EMAIL_BACKEND", "SMTP"), ("SMTP_HOST", "mail.example.com"), ("EMAIL_FROM", "tunes@example.com")]);
+    assert_eq!(levels(&issues, "SMTP_HOST").len() + levels(&issues, "EMAIL_FROM").len(), 0);
+}
+
+#[test]
+fn the_email_backend_is_smtp_or_none() {
+    assert_eq!(levels(&check_with(&[("EMAIL_BACKEND", "pigeon")]), "EMAIL_BACKEND"), [Error]);
+    assert_eq!(levels(&check_with(&[("EMAIL_BACKEND", "none")]), "EMAIL_BACKEND"), []);
+}
+
+#[test]
+fn smtp_tls_is_a_known_mode() {
+    assert_eq!(levels(&check_with(&[("SMTP_TLS", "ssl")]), "SMTP_TLS"), [Error]);
+    assert_eq!(levels(&check_with(&[("SMTP_TLS", "STARTTLS")]), "SMTP_TLS"), []);
+}
+
+#[test]
+fn the_maintenance_scope_is_writes_or_all() {
+    assert_eq!(levels(&check_with(&[("MAINTENANCE", "on"), ("MAINTENANCE_SCOPE", "reads")]), "MAINTENANCE_SCOPE"), [Error]);
+    assert_eq!(levels(&check_with(&[("MAINTENANCE", "on"), ("MAINTENANCE_SCOPE", "all")]), "MAINTENANCE_SCOPE"), []);
+}
+
+#[test]
+fn maintenance_details_warn_while_maintenance_is_off() {
+    assert_eq!(levels(&check_with(&[("MAINTENANCE_MESSAGE", "Back soon")]), "MAINTENANCE_MESSAGE"), [Warning]);
+    assert_eq!(levels(&check_with(&[("MAINTENANCE_SCOPE", "all")]), "MAINTENANCE_SCOPE"), [Warning]);
+    assert_eq!(levels(&check_with(&[("MAINTENANCE", "true"), ("MAINTENANCE_MESSAGE", "Back soon")]), "MAINTENANCE_MESSAGE"), []);
+}
+
+#[test]
+fn push_keys_come_in_pairs_with_a_contact() {
+    assert_eq!(levels(&check_with(&[("VAPID_PUBLIC_KEY", "public")]), "VAPID_PRIVATE_KEY"), [Error]);
+    assert_eq!(levels(&check_with(&[("VAPID_PRIVATE_KEY", "private")]), "VAPID_PUBLIC_KEY"), [Error]);
+
+    let keys = [("VAPID_PUBLIC_KEY", "public"), ("VAPID_PRIVATE_KEY", "private")];
+    assert_eq!(levels(&check_with(&keys), "VAPID_SUBJECT"), [Error]);
+    assert_eq!(levels(&check_with(&[keys[0], keys[1], ("VAPID_SUBJECT", "admin@example.com")]), "VAPID_SUBJECT"), [Error]);
+    assert_eq!(levels(&check_with(&[keys[0], keys[1], ("VAPID_SUBJECT", "mailto:admin@example.com")]), "VAPID_SUBJECT"), []);
+}
+
+#[test]
+fn overriding_an_unknown_flag_is_a_warning() {
+    assert_eq!(levels(&check_with(&[("FLAG_TELEPORT", "on")]), "FLAG_TELEPORT"), [Warning]);
+    assert_eq!(levels(&check_with(&[("FLAG_GRAPHQL", "off")]), "FLAG_GRAPHQL"), []);
+}
+
+#[test]
+fn stray_prefixed_variables_are_warned_about() {
+    let issues = check_with(&[("LIBRETUNE_PORT", "8080"), ("LIBRETUNE_COLOR", "blue")]);
+    let port: Vec<_> = issues.iter().filter(|issue| issue.setting == "LIBRETUNE_PORT").collect();
+    assert_eq!(port.len(), 1);
+    assert_eq!((port[0].level, port[0].message.as_str()), (Warning, "is not read, did you mean PORT?"));
+    assert_eq!(levels(&issues, "LIBRETUNE_COLOR"), [Warning]);
+}
@@ -0,0 +1,41 @@
+//! The database connection state as `/ready` reports it. Kept to one test
+//! in its own binary, since the state is process-wide.
+
+mod common;
+
+use std::time::Duration;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::db::{connection_state, ensure_connected};
+use libretune::latency;
+use libretune::types::latency::ConnectionState;
+use serde_json::Value;
+
+#[actix_web::test]
+async fn connection_state_follows_connects_and_drops() {
+    assert_eq!(connection_state(), ConnectionState::Disconnected);
+    assert!(ensure_connected().is_err());
+
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    assert_eq!(connection_state(), ConnectionState::Connected);
+    assert!(ensure_connected().is_ok());
+
+    // A failed probe is how a dropped connection shows up
+    latency::record(Err("simulated drop".to_string()));
+    assert_eq!(connection_state(), ConnectionState::Reconnecting);
+    assert!(ensure_connected().is_err());
+
+    let req = test::TestRequest::get().uri("/ready").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["connection"], "reconnecting");
+
+    latency::record(Ok(Duration::from_millis(2)));
+    assert_eq!(connection_state(), ConnectionState::Connected);
+
+    let req = test::TestRequest::get().uri("/ready").to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
@@ -0,0 +1,246 @@
+//! The flows everything else builds on, end to end through the HTTP API
+
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use serde_json::{json, Value};
+use libretune::fixtures::PASSWORD;
+use libretune::types::id::TrackId;
+use common::{auth_header_for, create_test_user};
+
+#[actix_web::test]
+async fn login_with_the_signup_password() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({ "email": alice.user.email, "password": PASSWORD }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body["user_id"], alice.user.id.to_string());
+    assert!(body["token"].as_str().is_some_and(|token| !token.is_empty()));
+}
+
+#[actix_web::test]
+async fn login_with_a_wrong_password_is_refused() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({ "email": alice.user.email, "password": "not the password" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn an_unknown_email_is_refused_like_a_wrong_password() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let mut bodies = Vec::new();
+    for (email, password) in [(alice.user.email.as_str(), "not the password"), ("nobody@example.com", PASSWORD)] {
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .set_json(json!({ "email": email, "password": password }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED, "{}", email);
+        bodies.push(test::read_body(resp).await);
+    }
+
+    assert_eq!(bodies[0], bodies[1], "nothing tells the two apart");
+}
+
+#[actix_web::test]
+async fn json_sent_as_anything_else_is_unsupported() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let login = json!({ "email": alice.user.email, "password": PASSWORD }).to_string();
+
+    for content_type in ["text/plain", "application/x-www-form-urlencoded"] {
+        let req = test::TestRequest::post()
+            .uri("/auth/login")
+            .insert_header((header::CONTENT_TYPE, content_type))
+            .set_payload(login.clone())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE, "{}", content_type);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["error"], "Content-Type must be application/json");
+    }
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .insert_header((header::CONTENT_TYPE, "application/json; charset=utf-8"))
+        .set_payload(login)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK, "a charset is fine");
+}
+
+#[actix_web::test]
+async fn update_profile_location() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let req = test::TestRequest::put()
+        .uri("/users/me/location")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "country": "NL", "city": "Utrecht" }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    assert_eq!(body["structured_location"]["country"], "NL");
+
+    let req = test::TestRequest::get().uri(&format!("/users/{}/profile", alice.user.id)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn anonymous_writes_are_refused() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+
+    let req = test::TestRequest::put()
+        .uri("/users/me/location")
+        .set_json(json!({ "country": "NL" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[actix_web::test]
+async fn create_a_track_and_comment_on_it() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+
+    // Tracks are created without an upload by importing already hosted audio
+    let req = test::TestRequest::post()
+        .uri("/tracks/import")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "tracks": [{
+            "title": "Harness Test",
+            "audio_url": "https://media.example.com/harness-test.mp3",
+            "is_public": true,
+        }] }))
+        .to_request();
+    let results: Value = test::call_and_read_body_json(&app, req).await;
+    let track_id: TrackId = serde_json::from_value(results[0]["track_id"].clone()).expect("the track was created");
+
+    let req = test::TestRequest::get().uri(&format!("/tracks/{}", track_id)).to_request();
+    let track: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(track["title"], "Harness Test");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/tracks/{}/comments", track_id))
+        .insert_header(auth_header_for(&bob))
+        .set_json(json!({ "content": "Lovely" }))
+        .to_request();
+    let comment: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(comment["author"]["id"], bob.user.id.to_string());
+
+    let req = test::TestRequest::get().uri(&format!("/tracks/{}/comments", track_id)).to_request();
+    let comments: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(comments["items"][0]["content"], "Lovely");
+}
+
+#[actix_web::test]
+async fn search_finds_users_by_name() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let carol = create_test_user(&db, "carol").await;
+
+    let req = test::TestRequest::get().uri(&format!("/search?query={}", carol.user.username)).to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+
+    let found = body["items"].as_array().expect("a page of users");
+    assert!(found.iter().any(|user| user["id"] == carol.user.id.to_string()));
+}
+
+#[actix_web::test]
+async fn register_log_in_post_and_be_found() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/register")
+        .set_json(json!({ "username": "dora", "email": "Dora@Example.com", "password": PASSWORD, "bio": null }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let user: Value = test::read_body_json(resp).await;
+    assert_eq!(user["username"], "dora");
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({ "email": "dora@example.com", "password": PASSWORD }))
+        .to_request();
+    let login: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(login["user_id"], user["id"]);
+    let bearer = (header::AUTHORIZATION, format!("Bearer {}", login["token"].as_str().expect("a token")));
+
+    let req = test::TestRequest::put()
+        .uri("/users/me/location")
+        .insert_header(bearer.clone())
+        .set_json(json!({ "country": "NL", "city": "Utrecht" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/tracks/import")
+        .insert_header(bearer.clone())
+        .set_json(json!({ "tracks": [{
+            "title": "First Light",
+            "audio_url": "https://media.example.com/first-light.mp3",
+            "is_public": true,
+        }] }))
+        .to_request();
+    let results: Value = test::call_and_read_body_json(&app, req).await;
+    let track_id = results[0]["track_id"].as_str().expect("the track was created").to_string();
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/tracks/{}/comments", track_id))
+        .insert_header(bearer)
+        .set_json(json!({ "content": "Out now" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+
+    let req = test::TestRequest::get().uri(&format!("/tracks/{}/comments", track_id)).to_request();
+    let comments: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(comments["items"][0]["content"], "Out now");
+    assert_eq!(comments["items"][0]["author"]["id"], user["id"]);
+
+    let req = test::TestRequest::get().uri("/search?query=dora").to_request();
+    let found: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(found["items"].as_array().is_some_and(|users| users.iter().any(|found| found["id"] == user["id"])), "{}", found);
+}
+
+#[actix_web::test]
+async fn registering_a_taken_username_conflicts() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/register")
+        .set_json(json!({ "username": alice.user.username, "email": "someone-else@example.com", "password": PASSWORD, "bio": null }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+
+    assert_eq!(resp.status(), StatusCode::CONFLICT);
+}
@@ -0,0 +1,173 @@
+//! Who gets a track's original file, directly or through a share link,
+//! and playing tracks that can't be downloaded
+
+mod common;
+
+use std::sync::Once;
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use chrono::Utc;
+use libretune::db::share_link::ShareLinkOperations;
+use libretune::db::track::TrackOperations;
+use libretune::db::Db;
+use libretune::fixtures::TrackFixture;
+use libretune::storage::storage;
+use libretune::types::attachment::Attachment;
+use libretune::types::license::License;
+use libretune::types::user::Track;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use common::{auth_header_for, create_test_user, TestUser};
+
+const AUDIO: &[u8] = b"ID3 not really audio";
+
+/// Keep this binary's media in a directory of its own
+fn use_temp_dir() {
+    static DIR: Once = Once::new();
+    DIR.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("libretune-downloads-{}", Uuid::new_v4().simple()));
+        std::env::set_var("MEDIA_DIR", dir);
+    });
+}
+
+/// A public track by `owner` whose audio is stored here
+async fn stored_track(db: &Db, owner: &TestUser, fixture: TrackFixture) -> Track {
+    let key = format!("audio/{}/{}.mp3", owner.user.id, Uuid::new_v4().simple());
+    let audio_url = storage().put(&key, AUDIO.to_vec()).await.expect("audio is stored");
+    fixture.owner(owner.user.id).audio_url(audio_url).create(db).await.expect("track is imported")
+}
+
+#[actix_web::test]
+async fn only_the_owner_downloads_a_track_that_isnt_downloadable() {
+    use_temp_dir();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let track = stored_track(&db, &alice, TrackFixture::new()).await;
+
+    // Nothing in the view or under /media leads a stranger to the file
+    let req = test::TestRequest::get().uri(&format!("/tracks/{}", track.id)).insert_header(auth_header_for(&bob)).to_request();
+    let detail: Value = test::call_and_read_body_json(&app, req).await;
+    assert!(detail.get("audio_url").is_none(), "{}", detail);
+    let media = track.audio_url.split_once("/media/").map(|(_, key)| format!("/media/{}", key)).expect("stored under /media");
+    let resp = test::call_service(&app, test::TestRequest::get().uri(&media).to_request()).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let download = format!("/tracks/{}/download", track.id);
+    let req = test::TestRequest::get().uri(&download).insert_header(auth_header_for(&bob)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    // They may still play it
+    let stream = format!("/tracks/{}/stream", track.id);
+    assert!(detail["stream_url"].as_str().is_some_and(|url| url.ends_with(&stream)), "{}", detail);
+    let req = test::TestRequest::get().uri(&stream).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(test::read_body(resp).await, AUDIO);
+
+    let req = test::TestRequest::get().uri(&format!("/tracks/{}", track.id)).insert_header(auth_header_for(&alice)).to_request();
+    let detail: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(detail["audio_url"], track.audio_url.as_str());
+
+    let req = test::TestRequest::get().uri(&download).insert_header(auth_header_for(&alice)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let disposition = resp.headers().get(header::CONTENT_DISPOSITION).and_then(|value| value.to_str().ok()).unwrap_or_default();
+    assert!(disposition.starts_with("attachment"), "{}", disposition);
+    assert_eq!(test::read_body(resp).await, AUDIO);
+}
+
+#[actix_web::test]
+async fn anyone_downloads_a_downloadable_track() {
+    use_temp_dir();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track = stored_track(&db, &alice, TrackFixture::new().license(License::CcBy).downloadable()).await;
+
+    let req = test::TestRequest::get().uri(&format!("/tracks/{}", track.id)).to_request();
+    let detail: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(detail["audio_url"], track.audio_url.as_str());
+
+    let req = test::TestRequest::get().uri(&format!("/tracks/{}/download", track.id)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(test::read_body(resp).await, AUDIO);
+}
+
+#[actix_web::test]
+async fn a_share_link_only_hands_out_files_if_it_allows_downloads() {
+    use_temp_dir();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track = stored_track(&db, &alice, TrackFixture::new().license(License::CcBy).private()).await;
+    let stems = storage().put(&format!("attachments/{}/stems.zip", track.id), b"PK".to_vec()).await.expect("stems are stored");
+    let attachment = Attachment {
+        id: Uuid::new_v4(),
+        name: "stems.zip".to_string(),
+        content_type: "application/zip".to_string(),
+        size: 2,
+        url: stems,
+        download_count: 0,
+        created_at: Utc::now(),
+    };
+    TrackOperations::new(&db).add_attachment(track.id, alice.user.id, attachment.clone()).await.expect("attachment is added");
+
+    for allow_download in [false, true] {
+        let req = test::TestRequest::post()
+            .uri(&format!("/tracks/{}/share-links", track.id))
+            .insert_header(auth_header_for(&alice))
+            .set_json(json!({ "allow_download": allow_download }))
+            .to_request();
+        let link: Value = test::call_and_read_body_json(&app, req).await;
+        let token = link["token"].as_str().expect("the new link has its token");
+
+        let req = test::TestRequest::get().uri(&format!("/share/{}", token)).to_request();
+        let detail: Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(detail.get("audio_url").is_some(), allow_download, "{}", detail);
+
+        let expected = if allow_download { StatusCode::OK } else { StatusCode::FORBIDDEN };
+        let req = test::TestRequest::get().uri(&format!("/tracks/{}/download?share={}", track.id, token)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), expected);
+        let req = test::TestRequest::get()
+            .uri(&format!("/tracks/{}/attachments/{}/download?share={}", track.id, attachment.id, token))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), expected);
+
+        // Playing is always fine
+        let req = test::TestRequest::get().uri(&format!("/tracks/{}/stream?share={}", track.id, token)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    }
+}
+
+#[actix_web::test]
+async fn share_tokens_are_only_stored_hashed() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track = TrackFixture::new().owner(alice.user.id).private().create(&db).await.expect("track is imported");
+
+    let uri = format!("/tracks/{}/share-links", track.id);
+    let req = test::TestRequest::post().uri(&uri).insert_header(auth_header_for(&alice)).set_json(json!({})).to_request();
+    let created: Value = test::call_and_read_body_json(&app, req).await;
+    let token = created["token"].as_str().expect("the new link has its token");
+    assert!(created["url"].as_str().is_some_and(|url| url.ends_with(&format!("/share/{}", token))));
+
+    let stored = ShareLinkOperations::new(&db).list_links(track.id).await.expect("links are listed");
+    assert_eq!(stored.len(), 1);
+    assert_ne!(stored[0].token_hash, token);
+
+    // Listing can't show it again
+    let req = test::TestRequest::get().uri(&uri).insert_header(auth_header_for(&alice)).to_request();
+    let listed: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(listed[0]["id"], created["id"]);
+    assert!(listed[0].get("token").is_none() && listed[0].get("url").is_none(), "{}", listed);
+
+    let req = test::TestRequest::get().uri(&format!("/share/{}", token)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri(&format!("/share/{}", stored[0].token_hash)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,115 @@
+//! Flows that send email, checked through the capturing mailer
+
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::db::UserOperations;
+use serde_json::json;
+use common::{auth_header_for, capture_mail, create_test_user, wait_for_email};
+
+#[actix_web::test]
+async fn password_reset_email_carries_a_token_that_works_once() {
+    let capture = capture_mail();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/forgot-password")
+        .set_json(json!({ "email": alice.user.email }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let email = wait_for_email(&capture, &alice.user.email).await;
+    assert_eq!(email.subject, "Reset your password");
+    let token = email.text
+        .split("token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("the email links to a reset token");
+    assert!(email.html.contains(token));
+
+    let req = test::TestRequest::post()
+        .uri("/auth/reset-password")
+        .set_json(json!({ "token": token, "new_password": "a brand new passphrase" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({ "email": alice.user.email, "password": "a brand new passphrase" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    // Whoever finds the email later can't use it again
+    let req = test::TestRequest::post()
+        .uri("/auth/reset-password")
+        .set_json(json!({ "token": token, "new_password": "someone else's passphrase" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/login")
+        .set_json(json!({ "email": alice.user.email, "password": "a brand new passphrase" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[actix_web::test]
+async fn unknown_addresses_get_no_email() {
+    let capture = capture_mail();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+
+    let req = test::TestRequest::post()
+        .uri("/auth/forgot-password")
+        .set_json(json!({ "email": "nobody-here@example.com" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    actix_web::rt::time::sleep(std::time::Duration::from_millis(500)).await;
+    assert!(capture.sent_to("nobody-here@example.com").is_empty());
+}
+
+#[actix_web::test]
+async fn verification_email_carries_a_token_that_confirms_the_address() {
+    let capture = capture_mail();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    assert!(!alice.user.email_verified);
+
+    let req = test::TestRequest::post()
+        .uri("/auth/verify-email/send")
+        .insert_header(auth_header_for(&alice))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+
+    let email = wait_for_email(&capture, &alice.user.email).await;
+    assert_eq!(email.subject, "Confirm your email address");
+    assert!(email.text.contains(&alice.user.username));
+    let token = email.text
+        .split("token=")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("the email links to a verification token");
+    assert!(email.html.contains(token));
+
+    let req = test::TestRequest::post()
+        .uri("/auth/verify-email")
+        .set_json(json!({ "token": token }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let user = UserOperations::new(&db).get_user_by_id(alice.user.id).await.expect("alice exists");
+    assert!(user.email_verified);
+}
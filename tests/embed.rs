@@ -0,0 +1,47 @@
+//! The embed routes are open to any origin; the rest of the API isn't
+
+mod common;
+
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::config;
+use common::{auth_header_for, create_test_user, import_track};
+
+const ELSEWHERE: &str = "https://blog.example.net";
+
+#[actix_web::test]
+async fn oembed_allows_any_origin_without_credentials() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track_id = import_track(&db, &alice, "Embed Me").await;
+
+    let url = format!("{}/tracks/{}", config::public_url(), track_id);
+    let req = test::TestRequest::get()
+        .uri(&format!("/oembed?url={}", url))
+        .insert_header((header::ORIGIN, ELSEWHERE))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let headers = resp.headers();
+    assert_eq!(headers.get(header::ACCESS_CONTROL_ALLOW_ORIGIN).and_then(|v| v.to_str().ok()), Some("*"));
+    assert!(headers.get(header::ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+}
+
+#[actix_web::test]
+async fn the_rest_of_the_api_sends_no_cors_headers() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let req = test::TestRequest::get()
+        .uri("/me/suggestions")
+        .insert_header(auth_header_for(&alice))
+        .insert_header((header::ORIGIN, ELSEWHERE))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get(header::ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+}
@@ -0,0 +1,154 @@
+//! Account erasure, confirmed by password and an emailed code
+
+mod common;
+
+use std::sync::Once;
+use std::time::Duration;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::db::backup::BackupOperations;
+use libretune::db::erasure::ErasureOperations;
+use libretune::db::track::TrackOperations;
+use libretune::db::{Db, UserOperations};
+use libretune::fixtures::{CommentFixture, PlaylistFixture, TrackFixture, PASSWORD};
+use libretune::storage::{local, storage};
+use libretune::types::credit::{CreditInput, CreditRole, CreditStatus};
+use libretune::types::erasure::{ErasureJob, ErasureStatus};
+use serde_json::json;
+use uuid::Uuid;
+use common::{auth_header_for, capture_mail, create_test_user, import_track, wait_for_email, TestUser};
+
+/// Keep this binary's media in a directory of its own
+fn use_temp_media_dir() {
+    static MEDIA_DIR: Once = Once::new();
+    MEDIA_DIR.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("libretune-erasure-{}", Uuid::new_v4().simple()));
+        std::env::set_var("MEDIA_DIR", dir);
+    });
+}
+
+/// The code from the latest erasure confirmation sent to `user`
+async fn erase_code(user: &TestUser) -> String {
+    let email = wait_for_email(&capture_mail(), &user.user.email).await;
+    assert_eq!(email.subject, "Confirm erasing your account");
+    email.text
+        .split("code:")
+        .nth(1)
+        .and_then(|rest| rest.split_whitespace().next())
+        .expect("the email carries a code")
+        .to_string()
+}
+
+async fn wait_for_completion(db: &Db, job: &ErasureJob) -> ErasureJob {
+    for _ in 0..50 {
+        let job = ErasureOperations::new(db).get_job(job.id).await.expect("job is readable");
+        if job.status == ErasureStatus::Completed {
+            return job;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("erasure job {} didn't complete", job.id)
+}
+
+#[actix_web::test]
+async fn erasure_leaves_no_trace_of_the_user() {
+    use_temp_media_dir();
+    capture_mail();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+
+    let key = format!("erasure/{}.mp3", Uuid::new_v4().simple());
+    let audio_url = storage().put(&key, b"not really audio".to_vec()).await.expect("audio is stored");
+    let alice_track = TrackFixture::new().owner(alice.user.id).title("Gone Soon").audio_url(audio_url).create(&db).await.expect("track is imported");
+    let bob_track_id = import_track(&db, &bob, "Staying").await;
+    let bob_track = TrackFixture::new().owner(bob.user.id).title("Commented").create(&db).await.expect("track is imported");
+    CommentFixture::new().on(&bob_track).by(alice.user.id).content("Nice one").create(&db).await.expect("comment is left");
+    PlaylistFixture::new().owner(bob.user.id).name("Mix").tracks(&[alice_track.id, bob_track_id]).create(&db).await.expect("playlist is created");
+    UserOperations::new(&db).follow_user(bob.user.id, alice.user.id).await.expect("bob follows alice");
+    UserOperations::new(&db).follow_user(alice.user.id, bob.user.id).await.expect("alice follows bob");
+    let credit = CreditInput { user_id: Some(alice.user.id), name: Some("Al".to_string()), role: CreditRole::Feature };
+    TrackOperations::new(&db).set_credits(bob_track.id, bob.user.id, vec![credit]).await.expect("alice is credited");
+
+    let req = test::TestRequest::post()
+        .uri("/users/me/erase/code")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "password": PASSWORD }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let code = erase_code(&alice).await;
+
+    let req = test::TestRequest::post()
+        .uri("/users/me/erase")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "password": PASSWORD, "code": code }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let job: ErasureJob = test::read_body_json(resp).await;
+    wait_for_completion(&db, &job).await;
+
+    let path = local().path(&key).expect("key is valid");
+    assert!(!path.exists(), "{} is still stored", key);
+
+    let erased = alice.user.id.to_string();
+    for table in BackupOperations::new(&db).tables().await.expect("tables are listed") {
+        let mut response = db
+            .query("SELECT VALUE <string> $this FROM type::table($table)")
+            .bind(("table", table.clone()))
+            .await
+            .expect("table is readable");
+        let rows: Vec<String> = response.take(0).expect("rows are strings");
+        assert!(rows.iter().all(|row| !row.contains(&erased)), "{} still references the erased user", table);
+    }
+
+    // Bob's own track stays in his playlist, alice's doesn't
+    let bob_now = UserOperations::new(&db).get_user_by_id(bob.user.id).await.expect("bob is still here");
+    let playlist = &bob_now.playlists.as_ref().expect("bob has playlists")[0];
+    assert_eq!(playlist.tracks.iter().map(|track| track.id).collect::<Vec<_>>(), [bob_track_id]);
+
+    // and her credit on his track is a plain name now
+    let track = TrackOperations::new(&db).get_track(bob_track.id).await.expect("bob's track is still here");
+    let credit = &track.credited_artists[0];
+    assert_eq!((credit.user_id, credit.name.as_str(), credit.status), (None, "Al", CreditStatus::Accepted));
+}
+
+#[actix_web::test]
+async fn erasure_needs_the_emailed_code() {
+    use_temp_media_dir();
+    capture_mail();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+
+    let req = test::TestRequest::post()
+        .uri("/users/me/erase")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "password": PASSWORD, "code": "made-up" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // One account's code doesn't confirm another's erasure
+    let req = test::TestRequest::post()
+        .uri("/users/me/erase/code")
+        .insert_header(auth_header_for(&bob))
+        .set_json(json!({ "password": PASSWORD }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let code = erase_code(&bob).await;
+
+    let req = test::TestRequest::post()
+        .uri("/users/me/erase")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "password": PASSWORD, "code": code }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    assert!(UserOperations::new(&db).get_user_by_id(alice.user.id).await.is_ok());
+}
@@ -0,0 +1,34 @@
+//! What a client is told of a server error
+
+use std::sync::{Arc, Mutex};
+use actix_web::http::StatusCode;
+use actix_web::{test, web, App, HttpResponse};
+use libretune::db::error::Error;
+use libretune::request_id::RequestIdScope;
+use serde_json::Value;
+use tracing_actix_web::{RequestId, TracingLogger};
+use uuid::Uuid;
+
+#[actix_web::test]
+async fn production_hides_the_detail_behind_the_logged_request_id() {
+    std::env::set_var("ENV", "production");
+    let seen: Arc<Mutex<Option<Uuid>>> = Arc::default();
+    let handler_saw = Arc::clone(&seen);
+    let app = test::init_service(
+        App::new()
+            .wrap(RequestIdScope)
+            .wrap(TracingLogger::default())
+            .route("/", web::get().to(move |request_id: RequestId| {
+                *handler_saw.lock().unwrap() = Some(request_id.into());
+                async { Err::<HttpResponse, _>(Error::Db("connection to db.internal:8000 refused".to_string())) }
+            })),
+    ).await;
+
+    let res = test::call_service(&app, test::TestRequest::get().uri("/").to_request()).await;
+    assert_eq!(res.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body: Value = test::read_body_json(res).await;
+    assert_eq!(body["error"], "Internal server error");
+    assert!(!body.to_string().contains("db.internal"), "the detail stays in the log");
+    let request_id = seen.lock().unwrap().expect("the handler ran");
+    assert_eq!(body["request_id"], request_id.to_string(), "the id is the one the request was logged under");
+}
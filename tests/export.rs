@@ -0,0 +1,82 @@
+//! The personal data export, streamed as a ZIP archive
+
+mod common;
+
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use async_zip::base::read::mem::ZipFileReader;
+use libretune::db::track::TrackOperations;
+use serde_json::Value;
+use common::{auth_header_for, create_test_user, import_track};
+
+#[actix_web::test]
+async fn the_archive_has_a_file_per_kind_of_data() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track_id = import_track(&db, &alice, "Only Track").await;
+
+    let req = test::TestRequest::get()
+        .uri("/me/export")
+        .insert_header(auth_header_for(&alice))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let headers = resp.headers();
+    assert_eq!(headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()), Some("application/zip"));
+    let disposition = headers.get(header::CONTENT_DISPOSITION).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    assert!(disposition.starts_with("attachment; filename=\"libretune-export-"), "{}", disposition);
+
+    let body = test::read_body(resp).await;
+    let archive = ZipFileReader::new(body.to_vec()).await.expect("archive is complete");
+    let names: Vec<&str> = archive.file()
+        .entries()
+        .iter()
+        .map(|entry| entry.filename().as_str().expect("names are UTF-8"))
+        .collect();
+    // The imported track's audio is hosted elsewhere, so there's no media
+    assert_eq!(names, ["account.json", "profile.json", "tracks.json", "playlists.json", "comments.json", "notifications.json"]);
+
+    let index = names.iter().position(|name| *name == "tracks.json").expect("tracks are exported");
+    let mut entry = archive.reader_with_entry(index).await.expect("entry opens");
+    let mut text = String::new();
+    entry.read_to_string_checked(&mut text).await.expect("entry reads");
+    let tracks: Value = serde_json::from_str(&text).expect("tracks are JSON");
+    assert_eq!(tracks.as_array().map(Vec::len), Some(1));
+    assert_eq!(tracks[0]["id"], track_id.to_string());
+}
+
+#[actix_web::test]
+async fn comments_on_other_peoples_tracks_are_exported() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await; // has no uploads of his own
+    let track_id = import_track(&db, &alice, "Commented On").await;
+    TrackOperations::new(&db).add_comment(track_id, bob.user.id, "Lovely".to_string(), None)
+        .await
+        .expect("comment is added");
+
+    let req = test::TestRequest::get()
+        .uri("/me/export")
+        .insert_header(auth_header_for(&bob))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let body = test::read_body(resp).await;
+    let archive = ZipFileReader::new(body.to_vec()).await.expect("archive is complete");
+    let index = archive.file()
+        .entries()
+        .iter()
+        .position(|entry| entry.filename().as_str().ok() == Some("comments.json"))
+        .expect("comments are exported");
+    let mut entry = archive.reader_with_entry(index).await.expect("entry opens");
+    let mut text = String::new();
+    entry.read_to_string_checked(&mut text).await.expect("entry reads");
+    let comments: Value = serde_json::from_str(&text).expect("comments are JSON");
+    assert_eq!(comments.as_array().map(Vec::len), Some(1), "{}", comments);
+    assert_eq!(comments[0]["content"], "Lovely");
+}
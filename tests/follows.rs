@@ -0,0 +1,110 @@
+//! Following and blocking, which change two users at once
+
+mod common;
+
+use actix_web::test;
+use futures_util::future::join_all;
+use libretune::db::error::Error;
+use libretune::db::{Db, UserOperations};
+use libretune::types::id::UserId;
+use libretune::types::user::UserProfile;
+use serde_json::Value;
+use common::{auth_header_for, create_test_user};
+
+async fn profile(db: &Db, user_id: UserId) -> UserProfile {
+    let user = UserOperations::new(db).get_user_by_id(user_id).await.expect("user is here");
+    user.profile.expect("user has a profile")
+}
+
+#[actix_web::test]
+async fn a_failure_midway_leaves_both_users_unchanged() {
+    let db = common::db().await;
+    let users = UserOperations::new(&db);
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let before = (profile(&db, alice.user.id).await, profile(&db, bob.user.id).await);
+
+    let mut follower = users.get_user_by_id(alice.user.id).await.expect("alice is here");
+    let mut followee = users.get_user_by_id(bob.user.id).await.expect("bob is here");
+    follower.profile.as_mut().expect("alice has a profile").following = Some(vec![bob.user.id]);
+    followee.profile.as_mut().expect("bob has a profile").followers = Some(vec![alice.user.id]);
+    let result = db.transaction(|tx| {
+        tx.update("users", alice.user.id, &follower)?;
+        tx.statement("THROW \"the process died here\";", Vec::new());
+        tx.update("users", bob.user.id, &followee)
+    }).await;
+
+    assert!(matches!(result, Err(Error::Db(_))), "the transaction fails, got {:?}", result);
+    let after = (profile(&db, alice.user.id).await, profile(&db, bob.user.id).await);
+    assert_eq!(after.0.following, before.0.following, "the write before the failure is rolled back");
+    assert_eq!(after.1.followers, before.1.followers);
+}
+
+#[actix_web::test]
+async fn follows_at_once_are_all_kept() {
+    let db = common::db().await;
+    let carol = create_test_user(&db, "carol").await;
+    let mut fans = Vec::new();
+    for n in 0..8 {
+        fans.push(create_test_user(&db, &format!("fan{}", n)).await.user.id);
+    }
+
+    let users = UserOperations::new(&db);
+    let results = join_all(fans.iter().map(|fan| users.follow_user(*fan, carol.user.id))).await;
+    assert!(results.iter().all(Result::is_ok), "every follow goes through: {:?}", results);
+
+    let mut followers = profile(&db, carol.user.id).await.followers.unwrap_or_default();
+    followers.sort();
+    fans.sort();
+    assert_eq!(followers, fans, "no follow overwrites another");
+}
+
+#[actix_web::test]
+async fn a_block_and_a_follow_at_once_never_leave_a_follow_across_the_block() {
+    let db = common::db().await;
+    let users = UserOperations::new(&db);
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+
+    let (blocked, followed) = futures_util::join!(
+        users.block_user(alice.user.id, bob.user.id),
+        users.follow_user(bob.user.id, alice.user.id),
+    );
+    blocked.expect("the block goes through");
+    assert!(matches!(followed, Ok(()) | Err(Error::Forbidden)), "the follow goes through or is refused, got {:?}", followed);
+
+    assert!(profile(&db, alice.user.id).await.followers.unwrap_or_default().is_empty());
+    assert!(profile(&db, bob.user.id).await.following.unwrap_or_default().is_empty(), "bob doesn't follow alice past her block");
+}
+
+#[actix_web::test]
+async fn suggestions_are_friends_of_friends_not_yet_followed() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let users = UserOperations::new(&db);
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let carol = create_test_user(&db, "carol").await;
+    let dave = create_test_user(&db, "dave").await;
+    let erin = create_test_user(&db, "erin").await;
+    let frank = create_test_user(&db, "frank").await;
+    let gina = create_test_user(&db, "gina").await;
+    for (follower, followee) in [
+        (&alice, &bob), (&alice, &carol),
+        (&bob, &carol), (&bob, &dave), (&bob, &erin), (&bob, &frank), (&bob, &gina),
+        (&carol, &dave), (&carol, &alice),
+    ] {
+        users.follow_user(follower.user.id, followee.user.id).await.expect("follow goes through");
+    }
+    users.block_user(alice.user.id, erin.user.id).await.expect("alice blocks erin");
+    users.block_user(frank.user.id, alice.user.id).await.expect("frank blocks alice");
+
+    let req = test::TestRequest::get().uri("/me/suggestions").insert_header(auth_header_for(&alice)).to_request();
+    let suggestions: Vec<Value> = test::call_and_read_body_json(&app, req).await;
+    let suggested: Vec<(String, u64)> = suggestions.iter()
+        .map(|s| (s["user"]["id"].as_str().unwrap_or_default().to_string(), s["mutual_count"].as_u64().unwrap_or_default()))
+        .collect();
+    // Carol is already followed, alice is herself, and erin and frank are
+    // on either side of a block
+    assert_eq!(suggested, [(dave.user.id.to_string(), 2), (gina.user.id.to_string(), 1)]);
+}
@@ -0,0 +1,105 @@
+//! The media garbage collector against a storage backend the tests control
+
+mod common;
+
+use std::sync::Mutex;
+use chrono::{DateTime, Duration, Utc};
+use futures_util::future::BoxFuture;
+use libretune::db::error::Error;
+use libretune::db::UserOperations;
+use libretune::gc::{self, GcOptions};
+use libretune::storage::{Storage, StoredObject};
+use uuid::Uuid;
+use common::create_test_user;
+
+const BASE_URL: &str = "mock://media";
+
+/// Objects listed as given; deletions are recorded instead of done
+struct MockStorage {
+    objects: Vec<StoredObject>,
+    deleted: Mutex<Vec<String>>,
+}
+
+impl MockStorage {
+    fn new(objects: &[(&str, DateTime<Utc>)]) -> Self {
+        let objects = objects
+            .iter()
+            .map(|(key, modified)| StoredObject { key: key.to_string(), size: 1, modified: *modified })
+            .collect();
+        MockStorage { objects, deleted: Mutex::new(Vec::new()) }
+    }
+
+    fn deleted(&self) -> Vec<String> {
+        let mut deleted = self.deleted.lock().unwrap().clone();
+        deleted.sort();
+        deleted
+    }
+}
+
+impl Storage for MockStorage {
+    fn put(&self, key: &str, _bytes: Vec<u8>) -> BoxFuture<'_, Result<String, Error>> {
+        let url = format!("{}/{}", BASE_URL, key);
+        Box::pin(async move { Ok(url) })
+    }
+
+    fn delete(&self, key: &str) -> BoxFuture<'_, Result<(), Error>> {
+        self.deleted.lock().unwrap().push(key.to_string());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn key_for_url(&self, url: &str) -> Option<String> {
+        url.strip_prefix(BASE_URL)?.strip_prefix('/').map(str::to_string)
+    }
+
+    fn list(&self) -> BoxFuture<'_, Result<Vec<StoredObject>, Error>> {
+        let objects = self.objects.clone();
+        Box::pin(async move { Ok(objects) })
+    }
+}
+
+fn options(dry_run: bool) -> GcOptions {
+    GcOptions { dry_run, max_deletions: 100, min_age: Duration::hours(24) }
+}
+
+/// A key no other test uses
+fn key(name: &str) -> String {
+    format!("{}-{}", name, Uuid::new_v4().simple())
+}
+
+#[actix_web::test]
+async fn only_old_unreferenced_objects_are_collected() {
+    let db = common::db().await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let (avatar, old, fresh, edge) = (key("avatar"), key("old"), key("fresh"), key("edge"));
+    let mut profile = alice.user.profile.clone().expect("alice has a profile");
+    profile.profile_picture = Some(format!("{}/{}", BASE_URL, avatar));
+    UserOperations::new(&db).update_profile(alice.user.id, profile).await.expect("picture is set");
+
+    let now = Utc::now();
+    let storage = MockStorage::new(&[
+        (&avatar, now - Duration::days(30)),
+        (&old, now - Duration::hours(48)),
+        // An upload whose URL isn't saved yet looks just like an orphan
+        (&fresh, now - Duration::minutes(5)),
+        (&edge, now - Duration::hours(23)),
+    ]);
+
+    let run = gc::run_with(&db, &storage, options(false)).await.expect("gc runs");
+    assert_eq!(run.deleted, [old]);
+    assert_eq!(storage.deleted(), run.deleted);
+    assert_eq!((run.scanned, run.referenced, run.too_recent), (4, 1, 2));
+    assert!(!run.capped);
+}
+
+#[actix_web::test]
+async fn a_dry_run_reports_without_deleting() {
+    let db = common::db().await;
+    let old = key("old");
+    let storage = MockStorage::new(&[(&old, Utc::now() - Duration::hours(48))]);
+
+    let run = gc::run_with(&db, &storage, options(true)).await.expect("gc runs");
+    assert!(run.dry_run);
+    assert_eq!(run.deleted, [old]);
+    assert!(storage.deleted().is_empty());
+}
@@ -0,0 +1,144 @@
+//! GraphQL answers what REST answers: the same things are hidden from the
+//! same viewers, with `null` where REST says 404
+
+mod common;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::db::session::SessionOperations;
+use libretune::db::track::TrackOperations;
+use libretune::fixtures::{PlaylistFixture, TrackFixture, UserFixture};
+use serde_json::{json, Value};
+use common::{auth_header_for, create_test_user, TestUser};
+
+/// GET `uri` over REST, `None` for a 404
+async fn rest<B: MessageBody>(
+    app: &impl Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    viewer: Option<&TestUser>,
+    uri: &str,
+) -> Option<Value> {
+    let mut req = test::TestRequest::get().uri(uri);
+    if let Some(viewer) = viewer {
+        req = req.insert_header(auth_header_for(viewer));
+    }
+    let resp = test::call_service(app, req.to_request()).await;
+    match resp.status() {
+        StatusCode::NOT_FOUND => None,
+        StatusCode::OK => Some(test::read_body_json(resp).await),
+        status => panic!("GET {} answered {}", uri, status),
+    }
+}
+
+/// Run `query` over GraphQL, returning its data
+async fn graphql<B: MessageBody>(
+    app: &impl Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+    viewer: Option<&TestUser>,
+    query: &str,
+) -> Value {
+    let mut req = test::TestRequest::post().uri("/api/graphql").set_json(json!({ "query": query }));
+    if let Some(viewer) = viewer {
+        req = req.insert_header(auth_header_for(viewer));
+    }
+    let body: Value = test::call_and_read_body_json(app, req.to_request()).await;
+    assert!(body["errors"].is_null(), "{} ran without errors: {}", query, body);
+
+    body["data"].clone()
+}
+
+/// The ids in a list of objects, in order
+fn ids(items: &Value) -> Vec<String> {
+    items.as_array()
+        .expect("a list")
+        .iter()
+        .map(|item| item["id"].as_str().expect("an id").to_string())
+        .collect()
+}
+
+#[actix_web::test]
+async fn profiles_and_their_tracks_match() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    for n in 0..2 {
+        TrackFixture::new().owner(alice.user.id).title(format!("Public {}", n)).create(&db).await.expect("track is imported");
+    }
+    TrackFixture::new().owner(alice.user.id).title("Private").private().create(&db).await.expect("track is imported");
+    let hermit = UserFixture::new().private().create(&db).await.expect("user is created");
+    let (_, token) = SessionOperations::new(&db).create_session(hermit.id).await.expect("session is created");
+    let hermit = TestUser { user: hermit, token };
+
+    for viewer in [None, Some(&bob), Some(&alice), Some(&hermit)] {
+        for user in [&alice, &hermit] {
+            let profile = rest(&app, viewer, &format!("/users/{}/profile", user.user.id)).await;
+            let tracks = rest(&app, viewer, &format!("/users/{}/tracks", user.user.id)).await;
+            let query = format!("{{ user(id: \"{}\") {{ username trackCount tracks {{ id }} }} }}", user.user.id);
+            let data = graphql(&app, viewer, &query).await;
+
+            let who = (viewer.map(|v| &v.user.username), &user.user.username);
+            match (profile, tracks) {
+                (Some(profile), Some(tracks)) => {
+                    assert_eq!(data["user"]["username"], profile["user"]["username"], "{:?}", who);
+                    assert_eq!(data["user"]["trackCount"], profile["track_count"], "{:?}", who);
+                    let mut rest_ids = ids(&tracks["items"]);
+                    let mut graphql_ids = ids(&data["user"]["tracks"]);
+                    rest_ids.sort();
+                    graphql_ids.sort();
+                    assert_eq!(graphql_ids, rest_ids, "{:?}", who);
+                }
+                (None, None) => assert!(data["user"].is_null(), "{:?} is hidden from GraphQL too", who),
+                (profile, tracks) => panic!("REST disagrees with itself for {:?}: {:?} {:?}", who, profile, tracks),
+            }
+        }
+    }
+}
+
+#[actix_web::test]
+async fn tracks_playlists_and_comments_match() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let public = TrackFixture::new().owner(alice.user.id).create(&db).await.expect("track is imported");
+    let private = TrackFixture::new().owner(alice.user.id).private().create(&db).await.expect("track is imported");
+    let mixed = PlaylistFixture::new().owner(alice.user.id).tracks(&[public.id, private.id]).create(&db).await.expect("playlist is created");
+    let hidden = PlaylistFixture::new().owner(alice.user.id).private().create(&db).await.expect("playlist is created");
+    let tracks = TrackOperations::new(&db);
+    tracks.add_comment(public.id, bob.user.id, "Kept".to_string(), None).await.expect("comment is added");
+    let gone = tracks.add_comment(public.id, bob.user.id, "Gone".to_string(), None).await.expect("comment is added");
+    tracks.delete_comment(public.id, gone.id, bob.user.id).await.expect("comment is deleted");
+
+    for viewer in [None, Some(&bob), Some(&alice)] {
+        let who = viewer.map(|v| &v.user.username);
+
+        for track_id in [public.id, private.id] {
+            let track = rest(&app, viewer, &format!("/tracks/{}", track_id)).await;
+            let data = graphql(&app, viewer, &format!("{{ track(id: \"{}\") {{ id title }} }}", track_id)).await;
+            match track {
+                Some(track) => assert_eq!(data["track"]["title"], track["title"], "{:?}", who),
+                None => assert!(data["track"].is_null(), "{:?}", who),
+            }
+
+            let comments = rest(&app, viewer, &format!("/tracks/{}/comments", track_id)).await;
+            let data = graphql(&app, viewer, &format!("{{ comments(trackId: \"{}\") {{ id }} }}", track_id)).await;
+            match comments {
+                Some(comments) => assert_eq!(ids(&data["comments"]), ids(&comments["items"]), "{:?}", who),
+                None => assert!(data["comments"].is_null(), "{:?}", who),
+            }
+        }
+
+        for playlist_id in [mixed.id, hidden.id] {
+            let playlist = rest(&app, viewer, &format!("/playlists/{}", playlist_id)).await;
+            let data = graphql(&app, viewer, &format!("{{ playlist(id: \"{}\") {{ name tracks {{ id }} }} }}", playlist_id)).await;
+            match playlist {
+                Some(playlist) => {
+                    assert_eq!(data["playlist"]["name"], playlist["name"], "{:?}", who);
+                    assert_eq!(ids(&data["playlist"]["tracks"]), ids(&playlist["tracks"]), "{:?}", who);
+                }
+                None => assert!(data["playlist"].is_null(), "{:?}", who),
+            }
+        }
+    }
+}
@@ -0,0 +1,34 @@
+//! Whether a deleted account's username and email can be signed up with
+//! again. The policy is read from the environment, which the whole process
+//! shares, so both are tried from one test.
+
+mod common;
+
+use libretune::db::error::Error;
+use libretune::db::UserOperations;
+use libretune::fixtures::UserFixture;
+
+#[actix_web::test]
+async fn deleted_handles_are_released_only_when_configured() {
+    let db = common::db().await;
+    let users = UserOperations::new(&db);
+
+    std::env::set_var("DELETED_HANDLES", "release");
+    let gone = UserFixture::new().username("phoenix").email("phoenix@example.com").create(&db).await.expect("user is created");
+    users.delete_user(gone.id).await.expect("user is deleted");
+    assert!(users.is_username_available("phoenix".to_string()).await.expect("username is checked"));
+    assert!(users.is_email_available("phoenix@example.com".to_string()).await.expect("email is checked"));
+    let reborn = UserFixture::new().username("phoenix").email("phoenix@example.com").create(&db).await.expect("the handle is free again");
+    assert_ne!(reborn.id, gone.id);
+    let tombstoned = users.get_user_by_id(gone.id).await.expect("the deleted user is kept");
+    assert_ne!(tombstoned.username, "phoenix");
+
+    std::env::set_var("DELETED_HANDLES", "reserve");
+    let kept = UserFixture::new().username("dodo").email("dodo@example.com").create(&db).await.expect("user is created");
+    users.delete_user(kept.id).await.expect("user is deleted");
+    assert!(!users.is_username_available("dodo".to_string()).await.expect("username is checked"));
+    let taken = UserFixture::new().username("dodo").email("dodo2@example.com").create(&db).await;
+    assert!(matches!(taken, Err(Error::UsernameExists)), "the username stays reserved, got {:?}", taken);
+
+    std::env::remove_var("DELETED_HANDLES");
+}
@@ -0,0 +1,67 @@
+//! Typed ids read and write exactly what bare uuids did
+
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test as atest;
+use libretune::types::id::{CommentId, TrackId, UserId};
+use libretune::types::user::Comment;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use surrealdb::RecordId;
+use uuid::Uuid;
+
+/// A comment as stored on its track
+const STORED_COMMENT: &str = include_str!("fixtures/comment/current.json");
+
+/// The stored comment with its ids typed
+#[derive(Serialize, Deserialize)]
+struct TypedComment {
+    id: CommentId,
+    referred_track_id: TrackId,
+    user_id: UserId,
+    parent_comment_id: Option<CommentId>,
+    likes: Option<Vec<UserId>>,
+    replies: Option<Vec<TypedComment>>,
+    #[serde(flatten)]
+    rest: Map<String, Value>,
+}
+
+#[test]
+fn stored_comments_round_trip_with_typed_ids() {
+    let stored: Value = serde_json::from_str(STORED_COMMENT).expect("fixture is JSON");
+
+    let typed: TypedComment = serde_json::from_value(stored.clone()).expect("typed ids read the fixture");
+    assert_eq!(serde_json::to_value(&typed).expect("serializes"), stored);
+
+    let comment: Comment = serde_json::from_value(stored.clone()).expect("the model reads the fixture");
+    assert_eq!(serde_json::to_value(&comment).expect("serializes"), stored);
+    assert_eq!(typed.id, comment.id);
+}
+
+#[test]
+fn ids_display_and_parse_as_their_uuid() {
+    let uuid = Uuid::new_v4();
+    let id = TrackId::from(uuid);
+
+    assert_eq!(id.to_string(), uuid.to_string());
+    assert_eq!(uuid.to_string().parse::<TrackId>().expect("parses"), id);
+    assert!("not-a-uuid".parse::<TrackId>().is_err());
+
+    let user_id = UserId::from(uuid);
+    assert_eq!(user_id.record(), ("users", uuid.to_string()));
+    assert_eq!(RecordId::from(user_id), RecordId::from_table_key("users", uuid.to_string()));
+}
+
+#[actix_web::test]
+async fn a_path_id_that_isnt_a_uuid_names_nothing() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+
+    let req = atest::TestRequest::get().uri("/tracks/not-a-uuid").to_request();
+    let resp = atest::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    let body: Value = atest::read_body_json(resp).await;
+    assert_eq!(body["error"], "Track not found");
+}
@@ -0,0 +1,83 @@
+//! Composite reads take about as long as their slowest query, not the sum
+//! of them all
+
+mod common;
+
+use std::thread;
+use std::time::{Duration, Instant};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::db::notification::NotificationOperations;
+use libretune::db::track::TrackOperations;
+use libretune::db::verification::VerificationOperations;
+use libretune::db::{Db, UserOperations};
+use libretune::fixtures::TrackFixture;
+use libretune::types::id::UserId;
+use serde_json::Value;
+use common::{auth_header_for, create_test_user};
+
+/// Enough uploads that counting them takes a while
+const UPLOADS: usize = 3000;
+
+async fn seed_uploads(db: &Db, owner: UserId) {
+    let users = UserOperations::new(db);
+    let mut user = users.get_user_by_id(owner).await.expect("owner is here");
+    let genres = ["ambient", "drill", "folk", "techno", "zydeco"];
+    let uploads = (0..UPLOADS)
+        .map(|n| TrackFixture::new().owner(owner).title(format!("Track {}", n)).genre(genres[n % genres.len()]).build())
+        .collect();
+    user.profile.as_mut().expect("owner has a profile").uploads = Some(uploads);
+    users.update_user(owner, user).await.expect("uploads are stored");
+}
+
+/// The fastest of a few runs, so a stall elsewhere doesn't count
+async fn fastest<F, Fut>(mut run: F) -> Duration
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = ()>,
+{
+    let mut best = Duration::MAX;
+    for _ in 0..5 {
+        let started = Instant::now();
+        run().await;
+        best = best.min(started.elapsed());
+    }
+    best
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn the_account_view_reads_its_parts_together() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    seed_uploads(&db, alice.user.id).await;
+
+    // What the endpoint reads besides the signed-in user, one at a time
+    let one_by_one = fastest(|| async {
+        let tracks = TrackOperations::new(&db);
+        tracks.count_tracks(alice.user.id, true).await.expect("tracks are counted");
+        tracks.genre_breakdown(alice.user.id, true).await.expect("genres are counted");
+        NotificationOperations::new(&db).count_unread(alice.user.id).await.expect("notifications are counted");
+        VerificationOperations::new(&db).latest_for_user(alice.user.id).await.expect("verification is read");
+    }).await;
+    let together = fastest(|| async {
+        let req = test::TestRequest::get().uri("/users/me").insert_header(auth_header_for(&alice)).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }).await;
+
+    let req = test::TestRequest::get().uri("/users/me").insert_header(auth_header_for(&alice)).to_request();
+    let account: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(account["profile"]["track_count"], UPLOADS);
+    assert_eq!(account["profile"]["genres"].as_array().map(Vec::len), Some(5));
+    assert_eq!(account["email"], alice.user.email);
+    assert_eq!(account["unread_notifications"], 0);
+
+    // In memory the queries only overlap given a core each; on one they
+    // take turns, and the endpoint mustn't cost more than they do
+    if thread::available_parallelism().map_or(1, usize::from) > 1 {
+        assert!(together < one_by_one, "the endpoint ({:?}) beats its queries one by one ({:?})", together, one_by_one);
+    } else {
+        assert!(together < one_by_one * 5 / 4, "the endpoint ({:?}) costs about what its queries do ({:?})", together, one_by_one);
+    }
+}
@@ -0,0 +1,146 @@
+//! Comments reaching the sockets watching a track as they're posted, edited
+//! and deleted, filtered the way listing them is
+
+mod common;
+
+use std::time::Duration;
+use libretune::db::track::TrackOperations;
+use libretune::db::{Db, UserOperations};
+use libretune::fixtures::TrackFixture;
+use libretune::live_comments;
+use libretune::realtime::Event;
+use libretune::types::id::{TrackId, UserId};
+use libretune::types::realtime::ServerMessage;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use tokio::time::timeout;
+use common::create_test_user;
+
+/// The next comment change a subscriber is sent, if one comes soon
+async fn next_change(events: &mut UnboundedReceiver<Event>, wait: Duration) -> Option<ServerMessage> {
+    timeout(wait, async {
+        while let Some(event) = events.recv().await {
+            if matches!(
+                event.message,
+                ServerMessage::CommentCreated { .. } | ServerMessage::CommentUpdated { .. } | ServerMessage::CommentDeleted { .. }
+            ) {
+                return Some(event.message);
+            }
+        }
+        None
+    }).await.ok().flatten()
+}
+
+/// Wait until the database holds the live query, before writing anything.
+/// SurrealDB can miss a live query for good if a write to its table lands
+/// while the query is being set up.
+async fn until_live(db: &Db) {
+    for _ in 0..250 {
+        let mut response = db.query("INFO FOR TABLE users").await.expect("table info is read");
+        let info: Option<serde_json::Value> = response.take(0).expect("table info is read");
+        if info.and_then(|info| info["lives"].as_object().map(|lives| !lives.is_empty())).unwrap_or(false) {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("the live query never started");
+}
+
+/// Once the query is live, post until a comment comes through to both
+/// subscribers: ones from before the first read are only remembered
+async fn until_heard(db: &Db, track_id: TrackId, author: UserId, first: &mut UnboundedReceiver<Event>, second: &mut UnboundedReceiver<Event>) {
+    until_live(db).await;
+    loop {
+        TrackOperations::new(db).add_comment(track_id, author, "anyone there?".into(), None).await.expect("comment is added");
+        if next_change(first, Duration::from_millis(200)).await.is_some() {
+            break;
+        }
+    }
+    assert_eq!(created(next_change(second, Duration::from_secs(5)).await), "anyone there?");
+    while next_change(second, Duration::from_millis(50)).await.is_some() {}
+}
+
+fn created(message: Option<ServerMessage>) -> String {
+    match message {
+        Some(ServerMessage::CommentCreated { comment, .. }) => comment.content,
+        other => panic!("expected a new comment, got {:?}", other),
+    }
+}
+
+#[actix_web::test]
+async fn two_clients_follow_a_tracks_comments() {
+    let db = common::db().await;
+    let owner = create_test_user(&db, "owner").await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let banned = create_test_user(&db, "banned").await;
+    let track = TrackFixture::new().owner(owner.user.id).create(&db).await.expect("track is imported");
+    let tracks = TrackOperations::new(&db);
+    UserOperations::new(&db).block_user(bob.user.id, banned.user.id).await.expect("bob blocks");
+    UserOperations::new(&db).ban_user(banned.user.id).await.expect("user is banned");
+
+    let (sender, mut alice_sees) = mpsc::unbounded_channel();
+    let _alice = live_comments::subscribe(&db, &track, alice.user.id, sender);
+    let (sender, mut bob_sees) = mpsc::unbounded_channel();
+    let _bob = live_comments::subscribe(&db, &track, bob.user.id, sender);
+
+    until_heard(&db, track.id, owner.user.id, &mut alice_sees, &mut bob_sees).await;
+    let wait = Duration::from_secs(5);
+
+    let comment = tracks.add_comment(track.id, alice.user.id, "first".into(), None).await.expect("comment is added");
+    assert_eq!(created(next_change(&mut bob_sees, wait).await), "first");
+    assert_eq!(created(next_change(&mut alice_sees, wait).await), "first", "authors hear themselves");
+
+    // Edited in place, as erasure does
+    let mut stored = UserOperations::new(&db).get_user_by_id(owner.user.id).await.expect("owner is here");
+    let uploads = stored.profile.as_mut().and_then(|p| p.uploads.as_mut()).expect("owner has uploads");
+    let edited = uploads[0].comments.iter_mut().flatten().find(|c| c.id == comment.id).expect("comment is stored");
+    edited.content = "first, edited".to_string();
+    UserOperations::new(&db).update_user(owner.user.id, stored).await.expect("comment is edited");
+    for events in [&mut alice_sees, &mut bob_sees] {
+        match next_change(events, wait).await {
+            Some(ServerMessage::CommentUpdated { comment: view, .. }) => assert_eq!((view.id, view.content.as_str()), (comment.id, "first, edited")),
+            other => panic!("expected an edit, got {:?}", other),
+        }
+    }
+
+    tracks.delete_comment(track.id, comment.id, alice.user.id).await.expect("comment is deleted");
+    for events in [&mut alice_sees, &mut bob_sees] {
+        assert!(matches!(next_change(events, wait).await, Some(ServerMessage::CommentDeleted { comment_id, .. }) if comment_id == comment.id));
+    }
+
+    // Nothing from a banned author, nor a profile view on the same record,
+    // comes before the next real comment
+    tracks.add_comment(track.id, banned.user.id, "spam".into(), None).await.expect("comment is added");
+    UserOperations::new(&db).record_profile_view(owner.user.id).await.expect("view is counted");
+    tracks.add_comment(track.id, owner.user.id, "last".into(), None).await.expect("comment is added");
+    assert_eq!(created(next_change(&mut alice_sees, wait).await), "last");
+    assert_eq!(created(next_change(&mut bob_sees, wait).await), "last");
+}
+
+#[actix_web::test]
+async fn blocks_hold_both_ways() {
+    let db = common::db().await;
+    let owner = create_test_user(&db, "owner").await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let track = TrackFixture::new().owner(owner.user.id).create(&db).await.expect("track is imported");
+    let tracks = TrackOperations::new(&db);
+    UserOperations::new(&db).block_user(alice.user.id, bob.user.id).await.expect("alice blocks bob");
+
+    let (sender, mut alice_sees) = mpsc::unbounded_channel();
+    let _alice = live_comments::subscribe(&db, &track, alice.user.id, sender);
+    let (sender, mut bob_sees) = mpsc::unbounded_channel();
+    let _bob = live_comments::subscribe(&db, &track, bob.user.id, sender);
+
+    until_heard(&db, track.id, owner.user.id, &mut alice_sees, &mut bob_sees).await;
+    let wait = Duration::from_secs(5);
+
+    tracks.add_comment(track.id, bob.user.id, "from bob".into(), None).await.expect("comment is added");
+    tracks.add_comment(track.id, alice.user.id, "from alice".into(), None).await.expect("comment is added");
+    tracks.add_comment(track.id, owner.user.id, "from the owner".into(), None).await.expect("comment is added");
+
+    assert_eq!(created(next_change(&mut alice_sees, wait).await), "from alice");
+    assert_eq!(created(next_change(&mut alice_sees, wait).await), "from the owner");
+    assert_eq!(created(next_change(&mut bob_sees, wait).await), "from bob");
+    assert_eq!(created(next_change(&mut bob_sees, wait).await), "from the owner");
+}
@@ -0,0 +1,53 @@
+//! Structured profile locations
+
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use serde_json::{json, Value};
+use common::{auth_header_for, create_test_user};
+
+#[actix_web::test]
+async fn a_structured_location_round_trips() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let req = test::TestRequest::put()
+        .uri("/users/me/location")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "country": "us", "region": "  NY ", "city": "New   York" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let set: Value = test::read_body_json(resp).await;
+    let expected = json!({ "country": "US", "region": "NY", "city": "New York" });
+    assert_eq!(set["structured_location"], expected);
+    assert_eq!(set["location"], "New York, NY, United States");
+
+    let req = test::TestRequest::get().uri(&format!("/users/{}/profile", alice.user.id)).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(profile["structured_location"], expected);
+    assert_eq!(profile["location"], "New York, NY, United States");
+}
+
+#[actix_web::test]
+async fn an_unknown_country_code_is_rejected() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    for country in ["ZZ", "USA", ""] {
+        let req = test::TestRequest::put()
+            .uri("/users/me/location")
+            .insert_header(auth_header_for(&alice))
+            .set_json(json!({ "country": country, "city": "Nowhere" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{:?}", country);
+    }
+
+    let req = test::TestRequest::get().uri(&format!("/users/{}/profile", alice.user.id)).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(profile["structured_location"], Value::Null);
+}
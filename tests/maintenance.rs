@@ -0,0 +1,48 @@
+//! Maintenance mode. The state is shared by the whole process, so it's all
+//! toggled from one test.
+
+mod common;
+
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use serde_json::{json, Value};
+use common::{auth_header_for, create_test_admin, create_test_user, import_track};
+
+#[actix_web::test]
+async fn writes_get_503_while_health_stays_up() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let admin = create_test_admin(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track_id = import_track(&db, &alice, "Under Construction").await;
+    let toggle = |active: bool| {
+        test::TestRequest::post()
+            .uri("/admin/maintenance")
+            .insert_header(auth_header_for(&admin))
+            .set_json(json!({ "active": active, "message": "Migrating", "retry_after_secs": 120 }))
+            .to_request()
+    };
+    let comment = || {
+        test::TestRequest::post()
+            .uri(&format!("/tracks/{}/comments", track_id))
+            .insert_header(auth_header_for(&alice))
+            .set_json(json!({ "content": "Hello?" }))
+            .to_request()
+    };
+
+    assert_eq!(test::call_service(&app, toggle(true)).await.status(), StatusCode::OK);
+
+    let resp = test::call_service(&app, comment()).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(resp.headers().get(header::RETRY_AFTER).and_then(|v| v.to_str().ok()), Some("120"));
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["error"], "Migrating");
+
+    let req = test::TestRequest::get().uri("/health").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+    let req = test::TestRequest::get().uri(&format!("/tracks/{}", track_id)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK, "reads keep working");
+
+    assert_eq!(test::call_service(&app, toggle(false)).await.status(), StatusCode::OK, "admins can still write");
+    assert_eq!(test::call_service(&app, comment()).await.status(), StatusCode::CREATED);
+}
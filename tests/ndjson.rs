@@ -0,0 +1,87 @@
+//! Admin exports streamed as NDJSON a page at a time
+
+mod common;
+
+use std::future::poll_fn;
+use std::pin::pin;
+use actix_web::body::MessageBody;
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use libretune::db::audit::AuditOperations;
+use libretune::ndjson::{self, PAGE_SIZE};
+use serde_json::{json, Value};
+use common::{auth_header_for, create_test_admin, create_test_user};
+
+#[actix_web::test]
+async fn the_audit_log_streams_page_by_page() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let admin = create_test_admin(&db).await;
+    let entries = PAGE_SIZE as usize + 20;
+    for n in 0..entries {
+        AuditOperations::new(&db).record(Some(admin.user.id), "test.entry", json!({ "n": n })).await.expect("entry is recorded");
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/admin/audit")
+        .insert_header(auth_header_for(&admin))
+        .insert_header((header::ACCEPT, ndjson::CONTENT_TYPE))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()), Some(ndjson::CONTENT_TYPE));
+
+    // Read it as a client would, a chunk at a time, parsing whole lines as
+    // they're completed
+    let mut body = pin!(resp.into_body());
+    let mut pending = Vec::new();
+    let mut lines: Vec<Value> = Vec::new();
+    let mut lines_per_chunk = Vec::new();
+    while let Some(chunk) = poll_fn(|cx| body.as_mut().poll_next(cx)).await {
+        let Ok(chunk) = chunk else { panic!("the stream failed") };
+        pending.extend_from_slice(&chunk);
+        let before = lines.len();
+        while let Some(end) = pending.iter().position(|byte| *byte == b'\n') {
+            let line: Vec<u8> = pending.drain(..=end).collect();
+            lines.push(serde_json::from_slice(&line[..end]).expect("each line is JSON"));
+        }
+        lines_per_chunk.push(lines.len() - before);
+    }
+
+    assert!(pending.is_empty(), "the last line is complete");
+    assert_eq!(lines_per_chunk, [PAGE_SIZE as usize, 20], "a chunk per page");
+    let numbers: Vec<u64> = lines.iter().map(|entry| entry["detail"]["n"].as_u64().expect("an entry")).collect();
+    assert_eq!(numbers, (0..entries as u64).rev().collect::<Vec<_>>(), "newest first, each once");
+}
+
+#[actix_web::test]
+async fn the_user_dump_has_a_line_per_user() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let admin = create_test_admin(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let req = test::TestRequest::get()
+        .uri("/admin/users")
+        .insert_header(auth_header_for(&admin))
+        .insert_header((header::ACCEPT, ndjson::CONTENT_TYPE))
+        .to_request();
+    let body = test::call_and_read_body(&app, req).await;
+    let ids: Vec<Value> = body
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice::<Value>(line).expect("each line is JSON")["id"].clone())
+        .collect();
+
+    assert_eq!(ids, [json!(admin.user.id), json!(alice.user.id)], "oldest first");
+}
+
+#[actix_web::test]
+async fn only_admins_read_the_audit_log() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let req = test::TestRequest::get().uri("/admin/audit").insert_header(auth_header_for(&alice)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN);
+}
@@ -0,0 +1,122 @@
+//! The OpenAPI document: well formed, and listing every route the app serves
+
+mod common;
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+use actix_web::http::StatusCode;
+use libretune::app::{self, AppConfig};
+use libretune::openapi::{ApiDoc, DOCS_PATH, SPEC_PATH};
+use serde_json::Value;
+use utoipa::OpenApi;
+
+const METHODS: [&str; 5] = ["get", "post", "put", "patch", "delete"];
+
+/// Every `#[get("/path")]`-style route under `dir`, as (method, path) with
+/// path parameters written the way the document writes them
+fn routes_in(dir: &Path, found: &mut BTreeSet<(String, String)>) {
+    for entry in fs::read_dir(dir).expect("source directory reads") {
+        let path = entry.expect("entry reads").path();
+        if path.is_dir() {
+            routes_in(&path, found);
+            continue;
+        }
+        let source = fs::read_to_string(&path).expect("source file reads");
+        for line in source.lines().map(str::trim) {
+            for method in METHODS {
+                let Some(rest) = line.strip_prefix(&format!("#[{}(\"", method)) else { continue };
+                let route = rest.split('"').next().expect("route is quoted");
+                // `{key:.*}` matches like `{key}`, it's only looser
+                let route: String = route
+                    .split('{')
+                    .enumerate()
+                    .map(|(i, part)| match (i, part.split_once(':')) {
+                        (0, _) | (_, None) => part.to_string(),
+                        (_, Some((name, rest))) => format!("{}{}", name, &rest[rest.find('}').expect("parameter closes")..]),
+                    })
+                    .collect::<Vec<_>>()
+                    .join("{");
+                found.insert((method.to_string(), route));
+            }
+        }
+    }
+}
+
+/// Where a `$ref` in the document points, for every `$ref` in `value`
+fn refs(value: &Value, found: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            if let Some(Value::String(target)) = map.get("$ref") {
+                found.push(target.clone());
+            }
+            map.values().for_each(|value| refs(value, found));
+        }
+        Value::Array(values) => values.iter().for_each(|value| refs(value, found)),
+        _ => {}
+    }
+}
+
+#[test]
+fn every_route_is_documented() {
+    let src = Path::new(env!("CARGO_MANIFEST_DIR")).join("src");
+    let mut routes = BTreeSet::new();
+    for dir in ["routes", "federation", "graphql"] {
+        routes_in(&src.join(dir), &mut routes);
+    }
+    assert!(!routes.is_empty(), "the scan finds the routes");
+
+    let spec = serde_json::to_value(ApiDoc::openapi()).expect("document serializes");
+    let paths = spec["paths"].as_object().expect("document has paths");
+    let undocumented: Vec<String> = routes
+        .iter()
+        .filter(|(method, route)| paths.get(route).and_then(|item| item.get(method)).is_none())
+        .map(|(method, route)| format!("{} {}", method.to_uppercase(), route))
+        .collect();
+    assert!(undocumented.is_empty(), "routes missing from ApiDoc: {:#?}", undocumented);
+}
+
+#[test]
+fn the_document_is_valid_openapi() {
+    let spec = serde_json::to_value(ApiDoc::openapi()).expect("document serializes");
+    assert!(spec["openapi"].as_str().is_some_and(|version| version.starts_with("3.")), "{}", spec["openapi"]);
+
+    let mut targets = Vec::new();
+    refs(&spec, &mut targets);
+    assert!(targets.iter().any(|target| target == "#/components/schemas/ErrorBody"), "errors share one schema");
+    let dangling: BTreeSet<&String> = targets
+        .iter()
+        .filter(|target| target.strip_prefix('#').and_then(|pointer| spec.pointer(pointer)).is_none())
+        .collect();
+    assert!(dangling.is_empty(), "references to nothing: {:#?}", dangling);
+
+    let security = &spec["components"]["securitySchemes"]["bearer"];
+    assert_eq!(security["scheme"], "bearer");
+}
+
+#[actix_web::test]
+async fn the_document_is_served() {
+    let db = common::db().await;
+    common::init_db();
+    let app = actix_web::test::init_service(app::build(AppConfig { docs: true, federation: false }, db)).await;
+
+    let req = actix_web::test::TestRequest::get().uri(SPEC_PATH).to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let served: Value = actix_web::test::read_body_json(resp).await;
+    assert_eq!(served, serde_json::to_value(ApiDoc::openapi()).expect("document serializes"));
+}
+
+#[actix_web::test]
+async fn the_docs_page_loads_the_document() {
+    let db = common::db().await;
+    common::init_db();
+    let app = actix_web::test::init_service(app::build(AppConfig { docs: true, federation: false }, db)).await;
+
+    let req = actix_web::test::TestRequest::get().uri(DOCS_PATH).to_request();
+    let resp = actix_web::test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let page = String::from_utf8(actix_web::test::read_body(resp).await.to_vec()).expect("page is UTF-8");
+    assert!(page.contains(&format!("url: \"{}\"", SPEC_PATH)), "{}", page);
+    assert!(page.contains("swagger-ui-bundle.js"));
+}
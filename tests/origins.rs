@@ -0,0 +1,73 @@
+//! Which cross-site requests the trusted origins check turns away
+
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::{header, StatusCode};
+use actix_web::{test, web, App, HttpResponse};
+use libretune::origin_check::{TrustedOrigins, TrustedOriginsConfig};
+
+const TRUSTED: &str = "https://libretune.example";
+
+async fn app() -> impl Service<actix_http::Request, Response = ServiceResponse<impl actix_web::body::MessageBody>, Error = actix_web::Error> {
+    let config = TrustedOriginsConfig { origins: vec![TRUSTED.to_string()] };
+    test::init_service(
+        App::new()
+            .wrap(TrustedOrigins::new(config))
+            .route("/likes", web::post().to(HttpResponse::Ok))
+            .route("/likes", web::get().to(HttpResponse::Ok)),
+    ).await
+}
+
+#[actix_web::test]
+async fn a_write_from_an_untrusted_origin_is_forbidden() {
+    let app = app().await;
+
+    for (name, value) in [(header::ORIGIN, "https://evil.example"), (header::REFERER, "https://evil.example/page")] {
+        let req = test::TestRequest::post()
+            .uri("/likes")
+            .insert_header((name.clone(), value))
+            .insert_header((header::COOKIE, "session=abc"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN, "{} {}", name, value);
+    }
+}
+
+#[actix_web::test]
+async fn a_write_from_a_trusted_origin_passes() {
+    let app = app().await;
+
+    for origin in [TRUSTED, "HTTPS://LibreTune.example", "https://libretune.example/upload?x=1"] {
+        let req = test::TestRequest::post()
+            .uri("/likes")
+            .insert_header((header::ORIGIN, origin))
+            .insert_header((header::COOKIE, "session=abc"))
+            .to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK, "{}", origin);
+    }
+}
+
+#[actix_web::test]
+async fn bearer_only_requests_reads_and_requests_without_an_origin_skip_the_check() {
+    let app = app().await;
+
+    let bearer = test::TestRequest::post()
+        .uri("/likes")
+        .insert_header((header::ORIGIN, "https://evil.example"))
+        .insert_header((header::AUTHORIZATION, "Bearer token"))
+        .to_request();
+    assert_eq!(test::call_service(&app, bearer).await.status(), StatusCode::OK);
+
+    let read = test::TestRequest::get().uri("/likes").insert_header((header::ORIGIN, "https://evil.example")).to_request();
+    assert_eq!(test::call_service(&app, read).await.status(), StatusCode::OK);
+
+    let headless = test::TestRequest::post().uri("/likes").insert_header((header::COOKIE, "session=abc")).to_request();
+    assert_eq!(test::call_service(&app, headless).await.status(), StatusCode::OK);
+
+    // A cookie alongside the token could have been sent by the browser
+    let both = test::TestRequest::post()
+        .uri("/likes")
+        .insert_header((header::ORIGIN, "https://evil.example"))
+        .insert_header((header::AUTHORIZATION, "Bearer token"))
+        .insert_header((header::COOKIE, "session=abc"))
+        .to_request();
+    assert_eq!(test::call_service(&app, both).await.status(), StatusCode::FORBIDDEN);
+}
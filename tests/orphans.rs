@@ -0,0 +1,51 @@
+//! Tracks left in playlists after their owner was hard-deleted
+
+mod common;
+
+use actix_web::test;
+use libretune::db::track::TrackOperations;
+use libretune::db::UserOperations;
+use libretune::fixtures::{PlaylistFixture, TrackFixture};
+use serde_json::Value;
+use common::{auth_header_for, create_test_admin, create_test_user};
+
+#[actix_web::test]
+async fn a_hard_deleted_users_tracks_are_found_and_cleaned_up() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let admin = create_test_admin(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let gone = TrackFixture::new().owner(alice.user.id).title("Left Behind").create(&db).await.expect("track is imported");
+    let kept = TrackFixture::new().owner(bob.user.id).create(&db).await.expect("track is imported");
+    let playlist = PlaylistFixture::new().owner(bob.user.id).tracks(&[gone.id, kept.id]).create(&db).await.expect("playlist is created");
+    let tracks = TrackOperations::new(&db);
+    assert!(tracks.find_orphans().await.expect("orphans are found").is_empty());
+
+    UserOperations::new(&db).hard_delete_user(alice.user.id).await.expect("alice is deleted");
+
+    let orphans = tracks.find_orphans().await.expect("orphans are found");
+    assert_eq!(orphans.len(), 1);
+    assert_eq!(orphans[0].track_id, gone.id);
+    assert_eq!(orphans[0].missing_user_id, alice.user.id);
+    assert_eq!((orphans[0].playlist_id, orphans[0].playlist_owner_id), (playlist.id, bob.user.id));
+
+    let cleanup = |hard: bool| {
+        test::TestRequest::post()
+            .uri(&format!("/admin/orphans/tracks/cleanup?hard={}", hard))
+            .insert_header(auth_header_for(&admin))
+            .to_request()
+    };
+    let soft: Value = test::call_and_read_body_json(&app, cleanup(false)).await;
+    assert_eq!(soft["cleaned"], 1, "{}", soft);
+    let bob_now = UserOperations::new(&db).get_user_by_id(bob.user.id).await.expect("bob is here");
+    let copies: Vec<(bool, bool)> = bob_now.playlists.iter().flatten().flat_map(|p| &p.tracks).map(|t| (t.id == gone.id, t.is_deleted)).collect();
+    assert_eq!(copies, [(true, true), (false, false)], "only the orphan is marked deleted");
+
+    let hard: Value = test::call_and_read_body_json(&app, cleanup(true)).await;
+    assert_eq!(hard["cleaned"], 1, "{}", hard);
+    assert!(tracks.find_orphans().await.expect("orphans are found").is_empty());
+    let bob_now = UserOperations::new(&db).get_user_by_id(bob.user.id).await.expect("bob is here");
+    let ids: Vec<_> = bob_now.playlists.iter().flatten().flat_map(|p| &p.tracks).map(|t| t.id).collect();
+    assert_eq!(ids, [kept.id]);
+}
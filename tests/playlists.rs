@@ -0,0 +1,199 @@
+//! Collaborative playlists edited by two people at once, and deleting playlists
+
+mod common;
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::db::UserOperations;
+use libretune::fixtures::PlaylistFixture;
+use libretune::live_playlists::{self, Subscription};
+use libretune::realtime::Event;
+use libretune::types::id::{PlaylistId, TrackId};
+use libretune::types::realtime::ServerMessage;
+use libretune::types::user::PlaylistView;
+use serde_json::json;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use common::{auth_header_for, create_test_user, import_track, TestUser};
+
+/// Someone with a playlist open, keeping their copy of its tracks up to
+/// date from the edits their socket is sent
+struct Client {
+    user: TestUser,
+    playlist_id: PlaylistId,
+    tracks: Vec<TrackId>,
+    revision: u64,
+    events: UnboundedReceiver<Event>,
+    _subscription: Subscription,
+}
+
+enum Edit {
+    Add(TrackId, usize),
+    Remove(TrackId),
+    Move(TrackId, usize),
+}
+
+impl Client {
+    /// Subscribe to the playlist, then load it, as a client opening it would
+    async fn open<B: MessageBody>(
+        app: &impl Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+        user: TestUser,
+        playlist_id: PlaylistId,
+    ) -> Client {
+        let (sender, events) = mpsc::unbounded_channel();
+        let subscription = live_playlists::subscribe(playlist_id, user.user.id, sender);
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/playlists/{}", playlist_id))
+            .insert_header(auth_header_for(&user))
+            .to_request();
+        let playlist: PlaylistView = test::call_and_read_body_json(app, req).await;
+
+        Client {
+            user,
+            playlist_id,
+            tracks: playlist.tracks.iter().map(|track| track.id).collect(),
+            revision: playlist.revision,
+            events,
+            _subscription: subscription,
+        }
+    }
+
+    /// Apply the edits sent since last time, skipping any the load already had
+    fn catch_up(&mut self) {
+        while let Ok(event) = self.events.try_recv() {
+            let revision = match event.message {
+                ServerMessage::PlaylistTrackAdded { revision, .. }
+                | ServerMessage::PlaylistTrackRemoved { revision, .. }
+                | ServerMessage::PlaylistTrackMoved { revision, .. } if revision <= self.revision => continue,
+                ServerMessage::PlaylistTrackAdded { revision, index, track, .. } => {
+                    self.tracks.insert(index, track.id);
+                    revision
+                }
+                ServerMessage::PlaylistTrackRemoved { revision, track_id, .. } => {
+                    self.tracks.retain(|id| *id != track_id);
+                    revision
+                }
+                ServerMessage::PlaylistTrackMoved { revision, track_id, index, .. } => {
+                    self.tracks.retain(|id| *id != track_id);
+                    self.tracks.insert(index, track_id);
+                    revision
+                }
+                message => panic!("unexpected {:?}", message),
+            };
+            self.revision = revision;
+        }
+    }
+
+    /// Send `edit` against the revision this client last saw
+    async fn send<B: MessageBody>(
+        &self,
+        app: &impl Service<actix_http::Request, Response = ServiceResponse<B>, Error = actix_web::Error>,
+        edit: Edit,
+    ) -> StatusCode {
+        let playlist = format!("/playlists/{}/tracks", self.playlist_id);
+        let req = match edit {
+            Edit::Add(track_id, index) => test::TestRequest::post()
+                .uri(&playlist)
+                .set_json(json!({ "track_id": track_id, "index": index, "revision": self.revision })),
+            Edit::Remove(track_id) => test::TestRequest::delete()
+                .uri(&format!("{}/{}?revision={}", playlist, track_id, self.revision)),
+            Edit::Move(track_id, index) => test::TestRequest::put()
+                .uri(&format!("{}/{}/position", playlist, track_id))
+                .set_json(json!({ "index": index, "revision": self.revision })),
+        };
+        let req = req.insert_header(auth_header_for(&self.user)).to_request();
+
+        test::call_service(app, req).await.status()
+    }
+}
+
+#[actix_web::test]
+async fn interleaved_edits_converge() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let mut ids = Vec::new();
+    for title in ["A", "B", "C", "D", "E"] {
+        ids.push(import_track(&db, &alice, title).await);
+    }
+    let [a, b, c, d, e] = ids[..] else { unreachable!() };
+    let playlist = PlaylistFixture::new().owner(alice.user.id).tracks(&[a, b, c]).create(&db).await.expect("playlist is created");
+
+    let req = test::TestRequest::put()
+        .uri(&format!("/playlists/{}/collaborative", playlist.id))
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "is_collaborative": true }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let mut alice = Client::open(&app, alice, playlist.id).await;
+    let mut bob = Client::open(&app, bob, playlist.id).await;
+
+    // Each goes ahead without waiting for the other's last edit, is told
+    // their copy is stale, catches up and tries again
+    assert_eq!(alice.send(&app, Edit::Move(c, 0)).await, StatusCode::OK);
+    assert_eq!(bob.send(&app, Edit::Add(d, 1)).await, StatusCode::CONFLICT);
+    bob.catch_up();
+    assert_eq!(bob.send(&app, Edit::Add(d, 1)).await, StatusCode::OK);
+
+    assert_eq!(alice.send(&app, Edit::Remove(a)).await, StatusCode::CONFLICT);
+    alice.catch_up();
+    assert_eq!(alice.send(&app, Edit::Remove(a)).await, StatusCode::OK);
+
+    bob.catch_up();
+    assert_eq!(bob.send(&app, Edit::Move(d, 2)).await, StatusCode::OK);
+    assert_eq!(alice.send(&app, Edit::Add(e, 0)).await, StatusCode::CONFLICT);
+    alice.catch_up();
+    assert_eq!(alice.send(&app, Edit::Add(e, 0)).await, StatusCode::OK);
+
+    alice.catch_up();
+    bob.catch_up();
+    let stored = Client::open(&app, alice.user, playlist.id).await;
+    assert_eq!(stored.tracks, [e, c, b, d]);
+    assert_eq!((alice.tracks, alice.revision), (stored.tracks.clone(), stored.revision));
+    assert_eq!((bob.tracks, bob.revision), (stored.tracks, stored.revision));
+}
+
+#[actix_web::test]
+async fn only_the_owner_deletes_a_playlist_and_it_leaves_their_list() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let track_id = import_track(&db, &alice, "Opener").await;
+    let kept = PlaylistFixture::new().owner(alice.user.id).name("Kept").create(&db).await.expect("playlist is created");
+    let doomed = PlaylistFixture::new().owner(alice.user.id).name("Doomed").tracks(&[track_id]).create(&db).await.expect("playlist is created");
+    let mut bob = Client::open(&app, bob, doomed.id).await;
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/playlists/{}", doomed.id))
+        .insert_header(auth_header_for(&bob.user))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let req = test::TestRequest::delete()
+        .uri(&format!("/playlists/{}", doomed.id))
+        .insert_header(auth_header_for(&alice))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    let owner = UserOperations::new(&db).get_user_by_id(alice.user.id).await.expect("alice is still here");
+    let listed: Vec<_> = owner.playlists.iter().flatten().map(|playlist| playlist.id).collect();
+    assert_eq!(listed, [kept.id]);
+    // Kept aside, since it wasn't a hard delete
+    assert_eq!(owner.deleted_playlists.iter().map(|playlist| playlist.id).collect::<Vec<_>>(), [doomed.id]);
+
+    let req = test::TestRequest::get().uri(&format!("/playlists/{}", doomed.id)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // Whoever had it open is told it's gone
+    let event = bob.events.try_recv().expect("the subscriber hears of it");
+    assert!(matches!(event.message, ServerMessage::PlaylistDeleted { playlist_id } if playlist_id == doomed.id));
+}
@@ -0,0 +1,124 @@
+//! What a profile shows, and to whom
+
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::db::UserOperations;
+use libretune::fixtures::TrackFixture;
+use serde_json::{json, Value};
+use common::{auth_header_for, create_test_user};
+
+#[actix_web::test]
+async fn strangers_count_only_public_tracks() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    for genre in ["folk", "folk", "techno"] {
+        TrackFixture::new().owner(alice.user.id).genre(genre).create(&db).await.expect("track is imported");
+    }
+    TrackFixture::new().owner(alice.user.id).genre("drill").private().create(&db).await.expect("track is imported");
+
+    let uri = format!("/users/{}/profile", alice.user.id);
+    let req = test::TestRequest::get().uri(&uri).insert_header(auth_header_for(&bob)).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(profile["track_count"], 3);
+    assert_eq!(profile["genres"], json!([{ "genre": "folk", "count": 2 }, { "genre": "techno", "count": 1 }]));
+
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(profile["track_count"], 3, "signed out counts as a stranger");
+
+    let req = test::TestRequest::get().uri(&uri).insert_header(auth_header_for(&alice)).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(profile["track_count"], 4, "the owner counts their private track too");
+}
+
+#[actix_web::test]
+async fn profiles_are_found_by_username() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+
+    let uri = format!("/u/{}", alice.user.username);
+    let req = test::TestRequest::get().uri(&uri).insert_header(auth_header_for(&bob)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let profile: Value = test::read_body_json(resp).await;
+    assert_eq!(profile["user"]["id"], alice.user.id.to_string());
+    let views = UserOperations::new(&db).get_user_by_id(alice.user.id).await.expect("alice is here").profile.expect("alice has a profile").profile_views;
+    assert_eq!(views, 1, "a stranger's visit is counted");
+
+    let req = test::TestRequest::get().uri("/u/nobody-by-this-name").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+
+    UserOperations::new(&db).delete_user(alice.user.id).await.expect("alice is deleted");
+    let req = test::TestRequest::get().uri(&uri).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND, "deleted users are gone");
+}
+
+#[actix_web::test]
+async fn a_profile_shows_how_the_viewer_relates_to_it() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let users = UserOperations::new(&db);
+    users.follow_user(alice.user.id, bob.user.id).await.expect("alice follows bob");
+    let relation = |profile: &Value| (profile["is_following"].clone(), profile["is_followed_by"].clone(), profile["is_blocked"].clone());
+
+    let req = test::TestRequest::get().uri(&format!("/users/{}/profile", bob.user.id)).insert_header(auth_header_for(&alice)).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(relation(&profile), (json!(true), json!(false), json!(false)));
+
+    let req = test::TestRequest::get().uri(&format!("/users/{}/profile", alice.user.id)).insert_header(auth_header_for(&bob)).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(relation(&profile), (json!(false), json!(true), json!(false)));
+
+    let req = test::TestRequest::get().uri(&format!("/users/{}/profile", bob.user.id)).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(relation(&profile), (Value::Null, Value::Null, Value::Null), "left out for anonymous viewers");
+
+    users.block_user(bob.user.id, alice.user.id).await.expect("bob blocks alice");
+    let req = test::TestRequest::get().uri(&format!("/users/{}/profile", alice.user.id)).insert_header(auth_header_for(&bob)).to_request();
+    let profile: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(relation(&profile), (json!(false), json!(false), json!(true)), "the block ends alice's follow");
+}
+
+#[actix_web::test]
+async fn only_your_own_public_tracks_can_be_pinned() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let mut tracks = Vec::new();
+    for title in ["One", "Two", "Three", "Four"] {
+        tracks.push(TrackFixture::new().owner(alice.user.id).title(title).create(&db).await.expect("track is imported").id);
+    }
+    let private = TrackFixture::new().owner(alice.user.id).private().create(&db).await.expect("track is imported").id;
+    let bobs = TrackFixture::new().owner(bob.user.id).create(&db).await.expect("track is imported").id;
+    let pin = |track_id, pinned: bool| {
+        let req = if pinned { test::TestRequest::post() } else { test::TestRequest::delete() };
+        req.uri(&format!("/tracks/{}/pin", track_id)).insert_header(auth_header_for(&alice)).to_request()
+    };
+    let pinned = || async {
+        let req = test::TestRequest::get().uri(&format!("/users/{}/profile", alice.user.id)).to_request();
+        let profile: Value = test::call_and_read_body_json(&app, req).await;
+        profile["pinned_tracks"].as_array().expect("pins are listed").iter().map(|track| track["id"].clone()).collect::<Vec<_>>()
+    };
+
+    assert_eq!(test::call_service(&app, pin(bobs, true)).await.status(), StatusCode::NOT_FOUND, "another user's track");
+    assert_eq!(test::call_service(&app, pin(private, true)).await.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(pinned().await, Vec::<Value>::new());
+
+    for track_id in &tracks[..3] {
+        assert_eq!(test::call_service(&app, pin(*track_id, true)).await.status(), StatusCode::NO_CONTENT);
+    }
+    assert_eq!(pinned().await, [json!(tracks[2]), json!(tracks[1]), json!(tracks[0])], "the latest pin comes first");
+    assert_eq!(test::call_service(&app, pin(tracks[3], true)).await.status(), StatusCode::CONFLICT, "three is the most");
+
+    assert_eq!(test::call_service(&app, pin(tracks[1], false)).await.status(), StatusCode::NO_CONTENT);
+    assert_eq!(pinned().await, [json!(tracks[2]), json!(tracks[0])]);
+}
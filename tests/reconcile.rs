@@ -0,0 +1,72 @@
+//! Counters that drifted from their source of truth, put right by the
+//! reconcile job
+
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::db::track::TrackOperations;
+use libretune::db::UserOperations;
+use serde_json::Value;
+use common::{create_test_admin, create_test_user, import_track};
+
+#[actix_web::test]
+async fn a_corrupted_follower_list_is_repaired() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let admin = create_test_admin(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    UserOperations::new(&db).follow_user(bob.user.id, alice.user.id).await.expect("bob follows alice");
+
+    // As a crash between the two writes of a follow would leave it
+    db.query("UPDATE type::thing('users', $id) SET profile.followers = []")
+        .bind(("id", alice.user.id.to_string()))
+        .await
+        .expect("followers are cleared");
+    db.user_cache().clear();
+
+    let req = test::TestRequest::post()
+        .uri("/admin/reconcile?counter=followers")
+        .insert_header(common::auth_header_for(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let reports: Value = test::read_body_json(resp).await;
+    assert_eq!(reports[0]["counter"], "followers");
+    assert!(reports[0]["corrected"].as_u64().is_some_and(|n| n >= 1), "{}", reports);
+
+    let alice = UserOperations::new(&db).get_user_by_id(alice.user.id).await.expect("alice exists");
+    let followers = alice.profile.and_then(|profile| profile.followers).unwrap_or_default();
+    assert_eq!(followers, [bob.user.id]);
+}
+
+#[actix_web::test]
+async fn a_corrupted_comment_count_is_repaired() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let admin = create_test_admin(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track_id = import_track(&db, &alice, "Miscounted").await;
+    TrackOperations::new(&db).add_comment(track_id, alice.user.id, "Only one".to_string(), None)
+        .await
+        .expect("comment is added");
+
+    let owner = UserOperations::new(&db).get_user_by_id(alice.user.id).await.expect("alice exists");
+    let mut profile = owner.profile.expect("alice has a profile");
+    let track = profile.uploads.iter_mut().flatten().find(|track| track.id == track_id).expect("track is stored");
+    track.comment_count = 7;
+    UserOperations::new(&db).update_profile(alice.user.id, profile).await.expect("count is corrupted");
+    let track = TrackOperations::new(&db).get_track(track_id).await.expect("track exists");
+    assert_eq!(track.comment_count, 7);
+
+    let req = test::TestRequest::post()
+        .uri("/admin/reconcile?counter=comment_count")
+        .insert_header(common::auth_header_for(&admin))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let track = TrackOperations::new(&db).get_track(track_id).await.expect("track exists");
+    assert_eq!(track.comment_count, 1);
+}
@@ -0,0 +1,70 @@
+//! Reporting tracks and comments, and the moderation queue by kind
+
+mod common;
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::db::track::TrackOperations;
+use serde_json::{json, Value};
+use common::{auth_header_for, create_test_admin, create_test_user, import_track};
+
+#[actix_web::test]
+async fn track_and_comment_reports_are_queued_by_kind() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let admin = create_test_admin(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let track_id = import_track(&db, &alice, "Questionable").await;
+    let comment = TrackOperations::new(&db).add_comment(track_id, alice.user.id, "Rude".to_string(), None)
+        .await
+        .expect("comment is added");
+
+    for (uri, target) in [(format!("/tracks/{}/report", track_id), track_id.to_string()), (format!("/comments/{}/report", comment.id), comment.id.to_string())] {
+        let req = test::TestRequest::post()
+            .uri(&uri)
+            .insert_header(auth_header_for(&bob))
+            .set_json(json!({ "reason": "spam", "description": "  " }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED, "{}", uri);
+        let report: Value = test::read_body_json(resp).await;
+        assert_eq!(report["target"]["id"], target);
+    }
+
+    let queue = |target: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/admin/reports?target={}", target))
+            .insert_header(auth_header_for(&admin))
+            .to_request()
+    };
+    for (kind, target) in [("track", track_id.to_string()), ("comment", comment.id.to_string())] {
+        let page: Value = test::call_and_read_body_json(&app, queue(kind)).await;
+        assert_eq!(page["items"].as_array().map(Vec::len), Some(1), "{}", kind);
+        assert_eq!(page["items"][0]["target"], json!({ "type": kind, "id": target }));
+    }
+    let page: Value = test::call_and_read_body_json(&app, queue("user")).await;
+    assert_eq!(page["items"], json!([]));
+
+    let req = test::TestRequest::get().uri("/admin/reports?target=track").insert_header(auth_header_for(&bob)).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN, "the queue is for admins");
+}
+
+#[actix_web::test]
+async fn a_deleted_comment_cant_be_reported() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let track_id = import_track(&db, &alice, "Quiet").await;
+    let tracks = TrackOperations::new(&db);
+    let comment = tracks.add_comment(track_id, alice.user.id, "Never mind".to_string(), None).await.expect("comment is added");
+    tracks.delete_comment(track_id, comment.id, alice.user.id).await.expect("comment is deleted");
+
+    let req = test::TestRequest::post()
+        .uri(&format!("/comments/{}/report", comment.id))
+        .insert_header(auth_header_for(&bob))
+        .set_json(json!({ "reason": "spam" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,45 @@
+//! The fixture builders make what they say, through the operations
+
+mod common;
+
+use libretune::fixtures::{ArtistFixture, CommentFixture, TrackFixture, UserFixture};
+
+#[actix_web::test]
+async fn a_verified_admin_is_stored_as_one() {
+    let db = common::db().await;
+
+    let user = UserFixture::new().verified().admin().email_verified().create(&db).await.expect("user is created");
+    let profile = user.profile.expect("user has a profile");
+    assert!(profile.is_verified);
+    assert!(profile.is_admin);
+    assert!(user.email_verified);
+}
+
+#[actix_web::test]
+async fn an_artist_comes_with_tracks_and_followers() {
+    let db = common::db().await;
+
+    let artist = ArtistFixture::new().public_tracks(3).followers(10).create(&db).await.expect("artist is created");
+    assert_eq!(artist.tracks.len(), 3);
+    assert!(artist.tracks.iter().all(|track| track.is_public && track.user_id == artist.user.id));
+
+    let followers = artist.user.profile.and_then(|p| p.followers).unwrap_or_default();
+    assert_eq!(followers.len(), 10);
+    assert!(artist.followers.iter().all(|follower| followers.contains(&follower.id)));
+}
+
+#[actix_web::test]
+async fn replies_are_read_back_from_their_thread() {
+    let db = common::db().await;
+    let owner = UserFixture::new().create(&db).await.expect("owner is created");
+    let fan = UserFixture::new().create(&db).await.expect("fan is created");
+    let track = TrackFixture::new().owner(owner.id).create(&db).await.expect("track is imported");
+
+    let comment = CommentFixture::new().on(&track).by(fan.id).create(&db).await.expect("comment is posted");
+    let reply = CommentFixture::new().on(&track).by(owner.id).content("Thanks!").reply_to(comment.id)
+        .create(&db)
+        .await
+        .expect("reply is posted");
+    assert_eq!(reply.parent_comment_id, Some(comment.id));
+    assert_eq!(reply.content, "Thanks!");
+}
@@ -0,0 +1,72 @@
+//! Searching users, a page at a time
+
+mod common;
+
+use actix_web::dev::ServiceResponse;
+use actix_web::http::header;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use libretune::db::Db;
+use uuid::Uuid;
+use common::create_test_user;
+
+/// The URL of each relation in a response's `Link` header
+fn links<B>(resp: &ServiceResponse<B>) -> Vec<(String, String)> {
+    let link = resp.headers().get(header::LINK).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    link.split(", ")
+        .filter_map(|part| part.split_once(">; rel="))
+        .map(|(url, rel)| (rel.trim_matches('"').to_string(), url.trim_start_matches('<').to_string()))
+        .collect()
+}
+
+fn url_for<'a>(links: &'a [(String, String)], rel: &str) -> Option<&'a str> {
+    links.iter().find(|(r, _)| r == rel).map(|(_, url)| url.as_str())
+}
+
+/// Three users whose names start with the returned prefix
+async fn three_users(db: &Db) -> String {
+    let prefix = format!("pager{}", &Uuid::new_v4().simple().to_string()[..6]);
+    for _ in 0..3 {
+        create_test_user(db, &prefix).await;
+    }
+    prefix
+}
+
+#[actix_web::test]
+async fn the_link_header_points_at_the_next_and_last_pages() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let prefix = three_users(&db).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/search?query={}&limit=2&offset=0", prefix))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    assert_eq!(resp.headers().get("X-Total-Count").and_then(|v| v.to_str().ok()), Some("3"));
+    let links = links(&resp);
+    assert!(url_for(&links, "prev").is_none(), "{:?}", links);
+    assert!(url_for(&links, "next").is_some_and(|url| url.ends_with("limit=2&offset=2")), "{:?}", links);
+    assert!(url_for(&links, "last").is_some_and(|url| url.ends_with("limit=2&offset=2")), "{:?}", links);
+    assert!(url_for(&links, "next").is_some_and(|url| url.contains(&format!("query={}", prefix))), "{:?}", links);
+}
+
+#[actix_web::test]
+async fn the_last_page_has_no_next_link() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let prefix = three_users(&db).await;
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/search?query={}&limit=2&offset=2", prefix))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let links = links(&resp);
+    assert!(url_for(&links, "next").is_none(), "{:?}", links);
+    assert!(url_for(&links, "prev").is_some_and(|url| url.ends_with("limit=2&offset=0")), "{:?}", links);
+    assert!(url_for(&links, "first").is_some_and(|url| url.ends_with("limit=2&offset=0")), "{:?}", links);
+    assert!(url_for(&links, "last").is_some_and(|url| url.ends_with("limit=2&offset=2")), "{:?}", links);
+}
@@ -0,0 +1,286 @@
+//! Importing and deleting several of one's own tracks at once, finding
+//! tracks by slug, patching them, their language and explicit flag,
+//! listing a genre, and who sees a track's stats
+
+mod common;
+
+use std::collections::HashMap;
+use actix_web::http::StatusCode;
+use actix_web::test;
+use chrono::{Duration, Utc};
+use libretune::db::track::TrackOperations;
+use libretune::db::UserOperations;
+use libretune::fixtures::TrackFixture;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use common::{auth_header_for, create_test_admin, create_test_user, import_track, TestUser};
+
+#[actix_web::test]
+async fn owned_tracks_are_deleted_together() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let first = import_track(&db, &alice, "First Take").await;
+    let second = import_track(&db, &alice, "Second Take").await;
+
+    let req = test::TestRequest::post()
+        .uri("/me/tracks/delete")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "track_ids": [first, second] }))
+        .to_request();
+    let body: Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["deleted"].as_array().map(Vec::len), Some(2));
+
+    for track_id in [first, second] {
+        let track = TrackOperations::new(&db).get_track(track_id).await.expect("track is still stored");
+        assert!(track.is_deleted);
+    }
+}
+
+#[actix_web::test]
+async fn a_mixed_batch_deletes_nothing() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let own = import_track(&db, &alice, "Keep Me").await;
+    let theirs = import_track(&db, &bob, "Not Yours").await;
+    let missing = Uuid::new_v4();
+
+    let req = test::TestRequest::post()
+        .uri("/me/tracks/delete")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "track_ids": [own, theirs, missing] }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!(body["not_owned"], json!([theirs]));
+    assert_eq!(body["not_found"], json!([missing]));
+
+    // The caller's own track went nowhere either
+    let track = TrackOperations::new(&db).get_track(own).await.expect("track exists");
+    assert!(!track.is_deleted);
+    let track = TrackOperations::new(&db).get_track(theirs).await.expect("track exists");
+    assert!(!track.is_deleted);
+}
+
+#[actix_web::test]
+async fn the_same_title_gets_a_slug_of_its_own() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let title = format!("Same Song {}", &Uuid::new_v4().simple().to_string()[..8]);
+    let first = import_track(&db, &alice, &title).await;
+    let second = import_track(&db, &bob, &title).await;
+
+    let first = TrackOperations::new(&db).get_track(first).await.expect("track exists");
+    let second = TrackOperations::new(&db).get_track(second).await.expect("track exists");
+    let (Some(first_slug), Some(second_slug)) = (first.slug.clone(), second.slug.clone()) else {
+        panic!("imported tracks get slugs");
+    };
+    assert_ne!(first_slug, second_slug);
+
+    for (slug, track) in [(first_slug, first), (second_slug, second)] {
+        let req = test::TestRequest::get().uri(&format!("/t/{}", slug)).to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body: Value = test::read_body_json(resp).await;
+        assert_eq!(body["id"], track.id.to_string());
+    }
+}
+
+#[actix_web::test]
+async fn only_iso_639_1_languages_are_accepted() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let track_id = import_track(&db, &alice, "Chanson").await;
+
+    for (language, status) in [("xx", StatusCode::BAD_REQUEST), ("eng", StatusCode::BAD_REQUEST), (" FR ", StatusCode::OK)] {
+        let req = test::TestRequest::patch()
+            .uri(&format!("/tracks/{}", track_id))
+            .insert_header(auth_header_for(&alice))
+            .set_json(json!({ "language": language }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), status, "{:?}", language);
+    }
+
+    let track = TrackOperations::new(&db).get_track(track_id).await.expect("track exists");
+    assert_eq!(track.language.as_deref(), Some("fr"));
+}
+
+#[actix_web::test]
+async fn the_explicit_filter_leaves_out_flagged_tracks() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let clean = TrackFixture::new().owner(alice.user.id).genre("Grime").create(&db).await.expect("track is imported");
+    let explicit = TrackFixture::new().owner(alice.user.id).genre("Grime").explicit().create(&db).await.expect("track is imported");
+
+    let listed = |exclude_explicit: bool| {
+        test::TestRequest::get()
+            .uri(&format!("/genres/grime/tracks?exclude_explicit={}", exclude_explicit))
+            .to_request()
+    };
+    let body: Value = test::call_and_read_body_json(&app, listed(false)).await;
+    let mut ids: Vec<&str> = body["items"].as_array().expect("a page of tracks").iter().filter_map(|track| track["id"].as_str()).collect();
+    ids.sort();
+    let mut both = [clean.id.to_string(), explicit.id.to_string()];
+    both.sort();
+    assert_eq!(ids, both);
+
+    let body: Value = test::call_and_read_body_json(&app, listed(true)).await;
+    let ids: Vec<&str> = body["items"].as_array().expect("a page of tracks").iter().filter_map(|track| track["id"].as_str()).collect();
+    assert_eq!(ids, [clean.id.to_string()]);
+}
+
+#[actix_web::test]
+async fn a_manifest_imports_its_valid_entries_and_reports_the_rest() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let req = test::TestRequest::post()
+        .uri("/tracks/import")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "tracks": [
+            { "title": "Opener", "audio_url": "https://cdn.example/opener.mp3", "is_public": true },
+            { "title": "Broken", "audio_url": "ftp://cdn.example/broken.mp3" },
+            { "title": "Closer", "audio_url": "https://cdn.example/closer.mp3", "genre": "Folk" },
+        ] }))
+        .to_request();
+    let results: Value = test::call_and_read_body_json(&app, req).await;
+    let results = results.as_array().expect("a result per entry");
+    assert_eq!(results.len(), 3);
+
+    assert_eq!(results[1]["index"], 1);
+    assert!(results[1]["track_id"].is_null());
+    assert_eq!(results[1]["error"], "audio_url must be an http(s) URL");
+
+    for (result, title) in [(&results[0], "Opener"), (&results[2], "Closer")] {
+        assert!(result["error"].is_null(), "{} is imported", title);
+        let track_id = result["track_id"].as_str().and_then(|id| id.parse().ok()).expect("the new track's id");
+        let track = TrackOperations::new(&db).get_track(track_id).await.expect("track is stored");
+        assert_eq!(track.title, title);
+        assert_eq!(track.user_id, alice.user.id);
+    }
+}
+
+#[actix_web::test]
+async fn patching_the_genre_leaves_the_rest_alone() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let before = TrackFixture::new()
+        .owner(alice.user.id)
+        .title("Slow Burn")
+        .description("Late night")
+        .genre("Ambient")
+        .tags(["night", "drone"])
+        .create(&db)
+        .await
+        .expect("track is imported");
+
+    let req = test::TestRequest::patch()
+        .uri(&format!("/tracks/{}", before.id))
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "genre": "Drone" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let after = TrackOperations::new(&db).get_track(before.id).await.expect("track exists");
+    assert_eq!(after.genre.as_deref(), Some("Drone"));
+    assert_eq!(after.title, before.title);
+    assert_eq!(after.description, before.description);
+    assert_eq!(after.tags, before.tags);
+    assert_eq!(after.created_at, before.created_at);
+    assert_eq!((after.likes, after.comment_count, after.is_public), (before.likes, before.comment_count, before.is_public));
+
+    let bob = create_test_user(&db, "bob").await;
+    let req = test::TestRequest::patch()
+        .uri(&format!("/tracks/{}", before.id))
+        .insert_header(auth_header_for(&bob))
+        .set_json(json!({ "genre": "Polka" }))
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::FORBIDDEN, "only the owner patches");
+}
+
+#[actix_web::test]
+async fn a_genre_lists_only_its_public_tracks_in_the_chosen_order() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let now = Utc::now();
+    let mut likes = HashMap::new();
+    for (title, genre, days_ago, liked) in [("Old", "folk", 3, 5), ("Middle", "folk", 2, 9), ("New", "FOLK", 1, 1), ("Elsewhere", "techno", 0, 20)] {
+        let track = TrackFixture::new().owner(alice.user.id).title(title).genre(genre).created_at(now - Duration::days(days_ago)).create(&db).await.expect("track is imported");
+        likes.insert(track.id, liked);
+    }
+    TrackFixture::new().owner(alice.user.id).title("Hidden").genre("folk").private().create(&db).await.expect("track is imported");
+    let deleted = TrackFixture::new().owner(alice.user.id).title("Gone").genre("folk").create(&db).await.expect("track is imported");
+    TrackOperations::new(&db).bulk_soft_delete(alice.user.id, &[deleted.id]).await.expect("track is deleted");
+
+    let users = UserOperations::new(&db);
+    let mut profile = users.get_user_by_id(alice.user.id).await.expect("alice exists").profile.expect("alice has a profile");
+    for track in profile.uploads.iter_mut().flatten() {
+        track.likes = likes.get(&track.id).copied().unwrap_or_default();
+    }
+    users.update_profile(alice.user.id, profile).await.expect("likes are set");
+
+    let titles = |query: &str| {
+        let req = test::TestRequest::get().uri(&format!("/genres/folk/tracks{}", query)).to_request();
+        async {
+            let page: Value = test::call_and_read_body_json(&app, req).await;
+            page["items"].as_array().expect("a page of tracks").iter().map(|track| track["title"].as_str().unwrap_or_default().to_string()).collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(titles("").await, ["New", "Middle", "Old"], "newest first, whatever the genre's case");
+    assert_eq!(titles("?sort=most-liked").await, ["Middle", "Old", "New"]);
+    assert_eq!(titles("?limit=1&offset=1").await, ["Middle"]);
+
+    let req = test::TestRequest::get().uri("/genres/folk/tracks?sort=loudest").to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+    let req = test::TestRequest::get().uri(&format!("/genres/{}/tracks", "a".repeat(51))).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::BAD_REQUEST);
+}
+
+#[actix_web::test]
+async fn only_the_owner_and_admins_see_a_tracks_stats() {
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let admin = create_test_admin(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let track_id = import_track(&db, &alice, "Counted").await;
+    for _ in 0..2 {
+        let req = test::TestRequest::post().uri(&format!("/tracks/{}/plays", track_id)).to_request();
+        assert_eq!(test::call_service(&app, req).await.status(), StatusCode::NO_CONTENT);
+    }
+    TrackOperations::new(&db).add_comment(track_id, bob.user.id, "Nice".to_string(), None).await.expect("comment is added");
+    let stats = |viewer: &TestUser, query: &str| {
+        test::TestRequest::get()
+            .uri(&format!("/tracks/{}/stats{}", track_id, query))
+            .insert_header(auth_header_for(viewer))
+            .to_request()
+    };
+
+    let resp = test::call_service(&app, stats(&alice, "?days=7")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body: Value = test::read_body_json(resp).await;
+    assert_eq!((body["play_count"].clone(), body["comment_count"].clone(), body["likes"].clone()), (json!(2), json!(1), json!(0)));
+    let plays = body["plays"].as_array().expect("plays by day");
+    assert_eq!(plays.len(), 7);
+    assert_eq!(plays[6]["plays"], 2, "today's plays come last");
+
+    let resp = test::call_service(&app, stats(&bob, "")).await;
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+    let body: Value = test::read_body_json(resp).await;
+    assert!(body.get("play_count").is_none(), "{}", body);
+
+    assert_eq!(test::call_service(&app, stats(&admin, "")).await.status(), StatusCode::OK);
+    assert_eq!(test::call_service(&app, stats(&alice, "?days=91")).await.status(), StatusCode::BAD_REQUEST);
+}
@@ -0,0 +1,61 @@
+//! "Someone is typing" on a track's comments, relayed to its other
+//! subscribers under the same rules as the comments themselves
+
+mod common;
+
+use libretune::db::UserOperations;
+use libretune::fixtures::TrackFixture;
+use libretune::live_comments;
+use libretune::realtime::Event;
+use libretune::typing;
+use libretune::types::id::UserId;
+use libretune::types::realtime::ServerMessage;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+use common::create_test_user;
+
+/// The typing messages waiting for a subscriber, as (started, who)
+fn typing_seen(events: &mut UnboundedReceiver<Event>) -> Vec<(bool, UserId)> {
+    let mut seen = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        match event.message {
+            ServerMessage::TypingStart { user_id, .. } => seen.push((true, user_id)),
+            ServerMessage::TypingStop { user_id, .. } => seen.push((false, user_id)),
+            _ => {}
+        }
+    }
+    seen
+}
+
+#[actix_web::test]
+async fn typing_reaches_other_subscribers_except_across_blocks_and_bans() {
+    let db = common::db().await;
+    let owner = create_test_user(&db, "owner").await;
+    let alice = create_test_user(&db, "alice").await;
+    let bob = create_test_user(&db, "bob").await;
+    let blocked = create_test_user(&db, "blocked").await;
+    let blocker = create_test_user(&db, "blocker").await;
+    let banned = create_test_user(&db, "banned").await;
+    let track = TrackFixture::new().owner(owner.user.id).create(&db).await.expect("track is imported");
+    UserOperations::new(&db).block_user(alice.user.id, blocked.user.id).await.expect("alice blocks");
+    UserOperations::new(&db).block_user(blocker.user.id, alice.user.id).await.expect("alice is blocked");
+    UserOperations::new(&db).ban_user(banned.user.id).await.expect("user is banned");
+
+    let mut watching = Vec::new();
+    for viewer in [&alice, &bob, &blocked, &blocker] {
+        let (sender, events) = mpsc::unbounded_channel();
+        watching.push((live_comments::subscribe(&db, &track, viewer.user.id, sender), events));
+    }
+    let [(_, alice_sees), (_, bob_sees), (_, blocked_sees), (_, blocker_sees)] = &mut watching[..] else { unreachable!() };
+
+    typing::start(&db, track.id, alice.user.id).await.expect("start is relayed");
+    typing::start(&db, track.id, alice.user.id).await.expect("a repeated start is fine");
+    typing::stop(&db, track.id, alice.user.id).await.expect("stop is relayed");
+    typing::stop(&db, track.id, alice.user.id).await.expect("a repeated stop is fine");
+    typing::start(&db, track.id, banned.user.id).await.expect("a banned typist is ignored");
+
+    let alice_id = alice.user.id;
+    assert_eq!(typing_seen(bob_sees), [(true, alice_id), (false, alice_id)], "once each, and nothing from the banned");
+    assert_eq!(typing_seen(alice_sees), [], "typists don't hear themselves");
+    assert_eq!(typing_seen(blocked_sees), []);
+    assert_eq!(typing_seen(blocker_sees), []);
+}
@@ -0,0 +1,148 @@
+//! Resumable uploads, chunks in any order finalized into one track, and
+//! profile pictures
+
+mod common;
+
+use std::io::Cursor;
+use std::sync::Once;
+use actix_web::http::{header, StatusCode};
+use actix_web::test;
+use image::{ImageFormat, Rgb, RgbImage};
+use libretune::db::track::TrackOperations;
+use libretune::db::UserOperations;
+use libretune::storage::{local, storage};
+use libretune::types::id::TrackId;
+use serde_json::{json, Value};
+use uuid::Uuid;
+use common::{auth_header_for, create_test_user};
+
+/// Keep this binary's staged uploads and media in directories of their own
+fn use_temp_dirs() {
+    static DIRS: Once = Once::new();
+    DIRS.call_once(|| {
+        let dir = std::env::temp_dir().join(format!("libretune-uploads-{}", Uuid::new_v4().simple()));
+        std::env::set_var("UPLOAD_STAGING_DIR", dir.join("staging"));
+        std::env::set_var("MEDIA_DIR", dir.join("media"));
+    });
+}
+
+#[actix_web::test]
+async fn a_file_sent_in_two_chunks_becomes_one_track() {
+    use_temp_dirs();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let file: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+    let (first, second) = file.split_at(600);
+
+    let req = test::TestRequest::post()
+        .uri("/uploads")
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "filename": "Demo.mp3", "size": file.len() }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let upload: Value = test::read_body_json(resp).await;
+    let uri = format!("/uploads/{}", upload["id"].as_str().expect("upload has an id"));
+
+    // The second half first, and once more for good measure
+    let mut status = Value::Null;
+    for (offset, chunk) in [(600, second), (600, second), (0, first)] {
+        let range = format!("bytes {}-{}/{}", offset, offset + chunk.len() - 1, file.len());
+        let req = test::TestRequest::patch()
+            .uri(&uri)
+            .insert_header(auth_header_for(&alice))
+            .insert_header((header::CONTENT_RANGE, range))
+            .set_payload(chunk.to_vec())
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        status = test::read_body_json(resp).await;
+    }
+    assert_eq!(status["received"], json!([{ "start": 0, "end": 1000 }]));
+    assert_eq!(status["complete"], true);
+
+    let req = test::TestRequest::post()
+        .uri(&format!("{}/finalize", uri))
+        .insert_header(auth_header_for(&alice))
+        .set_json(json!({ "title": "Demo" }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    let track: Value = test::read_body_json(resp).await;
+    let track_id: TrackId = serde_json::from_value(track["id"].clone()).expect("track has an id");
+
+    let track = TrackOperations::new(&db).get_track(track_id).await.expect("track is stored");
+    assert_eq!(track.title, "Demo");
+    let key = storage().key_for_url(&track.audio_url).expect("audio is stored here");
+    let stored = std::fs::read(local().path(&key).expect("key is valid")).expect("audio file reads");
+    assert_eq!(stored, file);
+
+    // The upload is used up
+    let req = test::TestRequest::get().uri(&uri).insert_header(auth_header_for(&alice)).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+/// A multipart form holding `bytes` as its `file` field
+fn file_form(filename: &str, content_type: &str, bytes: &[u8]) -> ((header::HeaderName, String), Vec<u8>) {
+    let boundary = format!("libretune-{}", Uuid::new_v4().simple());
+    let mut body = format!(
+        "--{}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+        boundary, filename, content_type,
+    ).into_bytes();
+    body.extend_from_slice(bytes);
+    body.extend_from_slice(format!("\r\n--{}--\r\n", boundary).as_bytes());
+
+    ((header::CONTENT_TYPE, format!("multipart/form-data; boundary={}", boundary)), body)
+}
+
+#[actix_web::test]
+async fn a_square_picture_is_scaled_down_and_set_on_the_profile() {
+    use_temp_dirs();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+    let mut png = Vec::new();
+    RgbImage::from_pixel(600, 600, Rgb([200, 40, 90]))
+        .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+        .expect("png encodes");
+
+    let (content_type, body) = file_form("me.png", "image/png", &png);
+    let req = test::TestRequest::post()
+        .uri("/me/profile/picture")
+        .insert_header(auth_header_for(&alice))
+        .insert_header(content_type)
+        .set_payload(body)
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let uploaded: Value = test::read_body_json(resp).await;
+    let url = uploaded["url"].as_str().expect("the picture's url");
+
+    let user = UserOperations::new(&db).get_user_by_id(alice.user.id).await.expect("alice is here");
+    assert_eq!(user.profile.and_then(|p| p.profile_picture).as_deref(), Some(url));
+    let key = storage().key_for_url(url).expect("picture is stored here");
+    let stored = image::open(local().path(&key).expect("key is valid")).expect("stored picture decodes");
+    assert_eq!((stored.width(), stored.height()), (512, 512));
+}
+
+#[actix_web::test]
+async fn a_pdf_is_not_a_picture() {
+    use_temp_dirs();
+    let db = common::db().await;
+    let app = common::app(&db).await;
+    let alice = create_test_user(&db, "alice").await;
+
+    let (content_type, body) = file_form("me.pdf", "application/pdf", b"%PDF-1.7\n1 0 obj << /Type /Catalog >> endobj\n%%EOF\n");
+    let req = test::TestRequest::post()
+        .uri("/me/profile/picture")
+        .insert_header(auth_header_for(&alice))
+        .insert_header(content_type)
+        .set_payload(body)
+        .to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let user = UserOperations::new(&db).get_user_by_id(alice.user.id).await.expect("alice is here");
+    assert_eq!(user.profile.and_then(|p| p.profile_picture), alice.user.profile.and_then(|p| p.profile_picture));
+}